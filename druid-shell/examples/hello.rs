@@ -19,12 +19,12 @@ use piet_common::kurbo::{Line, Rect};
 use piet_common::{Color, FillRule, RenderContext};
 
 use druid_shell::dialog::{FileDialogOptions, FileDialogType};
-use druid_shell::keyboard::{KeyEvent, KeyModifiers};
+use druid_shell::keyboard::KeyEvent;
 use druid_shell::keycodes::MenuKey;
 use druid_shell::menu::Menu;
 use druid_shell::platform::WindowBuilder;
 use druid_shell::runloop;
-use druid_shell::window::{MouseEvent, WinHandler, WindowHandle};
+use druid_shell::window::{MouseEvent, WheelEvent, WinHandler, WindowHandle};
 
 const BG_COLOR: Color = Color::rgb24(0x27_28_22);
 const FG_COLOR: Color = Color::rgb24(0xf0_f0_ea);
@@ -71,12 +71,8 @@ impl WinHandler for HelloState {
         false
     }
 
-    fn mouse_wheel(&self, delta: i32, mods: KeyModifiers) {
-        println!("mouse_wheel {} {:?}", delta, mods);
-    }
-
-    fn mouse_hwheel(&self, delta: i32, mods: KeyModifiers) {
-        println!("mouse_hwheel {} {:?}", delta, mods);
+    fn wheel(&self, event: &WheelEvent) {
+        println!("wheel {:?}", event);
     }
 
     fn mouse_move(&self, event: &MouseEvent) {
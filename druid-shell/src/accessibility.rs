@@ -0,0 +1,144 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The contents of a window as described to assistive technology, such as
+//! screen readers, platform independent types.
+
+use crate::kurbo::Rect;
+
+/// Identifies a node of an `AccessTree`; stable while the node exists.
+pub type NodeId = u64;
+
+/// What a node is, which decides how assistive technology presents it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    /// A node only containing others.
+    Group,
+    Window,
+    Label,
+    Button,
+    CheckBox,
+    TextInput,
+    /// A text input whose value is not to be read out.
+    PasswordInput,
+    Slider,
+    ProgressIndicator,
+    Image,
+    List,
+    ListItem,
+    Menu,
+    MenuItem,
+}
+
+impl Default for Role {
+    fn default() -> Role {
+        Role::Group
+    }
+}
+
+/// Something assistive technology can ask a node to do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Focus,
+    Click,
+    Increment,
+    Decrement,
+    /// Replace the value, with the one given in the request.
+    SetValue,
+}
+
+/// How urgently an announcement made with `WindowHandle::announce` is
+/// read out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Politeness {
+    /// Once the screen reader has finished what it is reading.
+    Polite,
+    /// Right away, interrupting what is being read.
+    Assertive,
+}
+
+/// An action assistive technology asks a node to perform.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ActionRequest {
+    pub node: NodeId,
+    pub action: Action,
+    /// The value for `Action::SetValue`.
+    pub value: Option<String>,
+}
+
+/// A node of an `AccessTree`.
+#[derive(Debug, Clone, Default)]
+pub struct AccessNode {
+    pub role: Role,
+    /// The name read out for the node, such as the text of a button.
+    pub name: Option<String>,
+    /// The value of the node as text, such as the contents of a text input.
+    pub value: Option<String>,
+    /// The value of a slider or progress indicator, and its range.
+    pub numeric_value: Option<f64>,
+    pub min_value: Option<f64>,
+    pub max_value: Option<f64>,
+    /// The actions the node supports.
+    pub actions: Vec<Action>,
+    /// The bounds of the node in the window, in px.
+    pub bounds: Rect,
+    pub children: Vec<NodeId>,
+}
+
+impl AccessNode {
+    pub fn new(role: Role) -> AccessNode {
+        AccessNode {
+            role,
+            ..Default::default()
+        }
+    }
+
+    pub fn supports(&self, action: Action) -> bool {
+        self.actions.contains(&action)
+    }
+}
+
+impl PartialEq for AccessNode {
+    fn eq(&self, other: &AccessNode) -> bool {
+        let (a, b) = (self.bounds, other.bounds);
+        self.role == other.role
+            && self.name == other.name
+            && self.value == other.value
+            && self.numeric_value == other.numeric_value
+            && self.min_value == other.min_value
+            && self.max_value == other.max_value
+            && self.actions == other.actions
+            && (a.x0, a.y0, a.x1, a.y1) == (b.x0, b.y0, b.x1, b.y1)
+            && self.children == other.children
+    }
+}
+
+/// The contents of a window as given to `WindowHandle::update_accessibility`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccessTree {
+    /// The nodes, parents before their children.
+    pub nodes: Vec<(NodeId, AccessNode)>,
+    pub root: NodeId,
+    /// The node with the keyboard focus, if any.
+    pub focus: Option<NodeId>,
+}
+
+impl AccessTree {
+    pub fn node(&self, id: NodeId) -> Option<&AccessNode> {
+        self.nodes
+            .iter()
+            .find(|(node_id, _)| *node_id == id)
+            .map(|(_, node)| node)
+    }
+}
@@ -0,0 +1,118 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native message boxes, platform independent types.
+
+/// The icon of an alert, conveying its severity.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertStyle {
+    Info,
+    Warning,
+    Error,
+}
+
+/// The set of buttons an alert offers.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertButtons {
+    Ok,
+    OkCancel,
+    YesNo,
+    YesNoCancel,
+}
+
+/// The button chosen to dismiss an alert.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AlertResponse {
+    Ok,
+    Cancel,
+    Yes,
+    No,
+}
+
+/// Options for an alert.
+#[derive(Debug, Clone)]
+pub struct AlertOptions {
+    pub(crate) style: AlertStyle,
+    pub(crate) title: String,
+    #[cfg_attr(feature = "headless", allow(dead_code))]
+    pub(crate) message: String,
+    pub(crate) detail: Option<String>,
+    pub(crate) buttons: AlertButtons,
+    pub(crate) default_button: Option<AlertResponse>,
+}
+
+impl AlertButtons {
+    /// The responses of the buttons, in the order the platforms list them.
+    pub fn responses(self) -> &'static [AlertResponse] {
+        match self {
+            AlertButtons::Ok => &[AlertResponse::Ok],
+            AlertButtons::OkCancel => &[AlertResponse::Ok, AlertResponse::Cancel],
+            AlertButtons::YesNo => &[AlertResponse::Yes, AlertResponse::No],
+            AlertButtons::YesNoCancel => {
+                &[AlertResponse::Yes, AlertResponse::No, AlertResponse::Cancel]
+            }
+        }
+    }
+}
+
+impl AlertOptions {
+    /// An info alert showing `message` with an OK button.
+    pub fn new(message: impl Into<String>) -> AlertOptions {
+        AlertOptions {
+            style: AlertStyle::Info,
+            title: String::new(),
+            message: message.into(),
+            detail: None,
+            buttons: AlertButtons::Ok,
+            default_button: None,
+        }
+    }
+
+    pub fn set_style(&mut self, style: AlertStyle) {
+        self.style = style;
+    }
+
+    /// Set the title of the alert window. macOS alerts have no title.
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    /// Set a longer explanation, shown below the message.
+    pub fn set_detail(&mut self, detail: impl Into<String>) {
+        self.detail = Some(detail.into());
+    }
+
+    pub fn set_buttons(&mut self, buttons: AlertButtons) {
+        self.buttons = buttons;
+    }
+
+    /// Set the button chosen by pressing Enter. By default this is the first
+    /// one, OK or Yes.
+    pub fn set_default_button(&mut self, response: AlertResponse) {
+        self.default_button = Some(response);
+    }
+
+    /// The index of the default button in `AlertButtons::responses`.
+    #[cfg_attr(target_arch = "wasm32", allow(dead_code))]
+    pub(crate) fn default_index(&self) -> usize {
+        self.default_button
+            .and_then(|default| {
+                self.buttons
+                    .responses()
+                    .iter()
+                    .position(|&response| response == default)
+            })
+            .unwrap_or(0)
+    }
+}
@@ -0,0 +1,20 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! How the platform draws text that runs right to left.
+
+/// Whether text drawn with piet is put in visual order by the platform, as
+/// DirectWrite and browsers do. cairo draws chars left to right in the
+/// order given, so right to left runs are reversed before drawing there.
+pub const ORDERS_TEXT: bool = cfg!(not(any(target_os = "linux", target_os = "macos")));
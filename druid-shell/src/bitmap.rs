@@ -0,0 +1,148 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Painting into bitmaps in memory rather than windows.
+//!
+//! The bitmaps are painted with the same piet backend as windows, so their
+//! pixels can be drawn into a window as an image.
+
+use crate::blend::Painting;
+use crate::piet::Piet;
+use crate::Error;
+
+/// A bitmap in memory.
+pub struct RgbaImage {
+    /// Pixels in RGBA order with 8 bits per channel, row by row. The color
+    /// channels are not premultiplied by alpha.
+    pub image: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Paint with `f` into a transparent bitmap of `width` by `height` pixels,
+/// with the coordinates scaled by `scale`, for exporting or comparing.
+pub fn render_to_image<F>(width: usize, height: usize, scale: f64, f: F) -> Result<RgbaImage, Error>
+where
+    F: FnOnce(&mut Piet),
+{
+    let mut image = render_to_pixels(width, height, scale, f)?;
+    for pixel in image.chunks_exact_mut(4) {
+        let alpha = u16::from(pixel[3]);
+        if alpha != 0 {
+            for c in &mut pixel[..3] {
+                *c = ((u16::from(*c) * 255 + alpha / 2) / alpha).min(255) as u8;
+            }
+        }
+    }
+    Ok(RgbaImage {
+        image,
+        width,
+        height,
+    })
+}
+
+/// Paint with `f` into a transparent bitmap of `width` by `height` pixels,
+/// with the coordinates scaled by `scale`, and return its pixels row by row
+/// as premultiplied RGBA.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_to_pixels<F>(width: usize, height: usize, scale: f64, f: F) -> Result<Vec<u8>, Error>
+where
+    F: FnOnce(&mut Piet),
+{
+    use std::cell::RefCell;
+
+    use crate::piet::{Device, ImageFormat, RenderContext};
+
+    // Creating a device is expensive on Direct2D, so it is kept. It is only
+    // borrowed mutably to create it, since `f` may paint into bitmaps too.
+    thread_local! {
+        static DEVICE: RefCell<Option<Device>> = RefCell::new(None);
+    }
+
+    DEVICE.with(|device| {
+        if device.borrow().is_none() {
+            let new = Device::new().map_err(|_| Error::Other("no bitmap device"))?;
+            *device.borrow_mut() = Some(new);
+        }
+        let device = device.borrow();
+        let device = device.as_ref().unwrap();
+        let mut target = device
+            .bitmap_target(width, height, scale)
+            .map_err(|_| Error::Other("failed to create the bitmap"))?;
+        {
+            let _painting = Painting::new(None);
+            let mut piet_ctx = target.render_context();
+            f(&mut piet_ctx);
+            piet_ctx
+                .finish()
+                .map_err(|_| Error::Other("failed to paint the bitmap"))?;
+        }
+        target
+            .into_raw_pixels(ImageFormat::RgbaPremul)
+            .map_err(|_| Error::Other("failed to read the bitmap"))
+    })
+}
+
+/// Paint with `f` into a transparent bitmap of `width` by `height` pixels,
+/// with the coordinates scaled by `scale`, and return its pixels row by row
+/// as premultiplied RGBA.
+///
+/// The bitmap is a canvas that is not part of the page.
+#[cfg(target_arch = "wasm32")]
+pub fn render_to_pixels<F>(width: usize, height: usize, scale: f64, f: F) -> Result<Vec<u8>, Error>
+where
+    F: FnOnce(&mut Piet),
+{
+    use wasm_bindgen::JsCast;
+    use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement};
+
+    use crate::piet::RenderContext;
+    use crate::platform::util::window;
+
+    let window = window();
+    let canvas: HtmlCanvasElement = window
+        .document()
+        .and_then(|document| document.create_element("canvas").ok())
+        .ok_or(Error::Other("failed to create the bitmap"))?
+        .unchecked_into();
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+    let mut context: CanvasRenderingContext2d = canvas
+        .get_context("2d")
+        .ok()
+        .and_then(|context| context)
+        .ok_or(Error::Other("failed to create the bitmap"))?
+        .unchecked_into();
+    let _ = context.scale(scale, scale);
+    {
+        let _painting = Painting::new(Some(&context));
+        let mut piet_ctx = Piet::new(&mut context, &window);
+        f(&mut piet_ctx);
+        piet_ctx
+            .finish()
+            .map_err(|_| Error::Other("failed to paint the bitmap"))?;
+    }
+    let data = context
+        .get_image_data(0.0, 0.0, width as f64, height as f64)
+        .map_err(|_| Error::Other("failed to read the bitmap"))?;
+    // Canvas pixels are not premultiplied.
+    let mut pixels = data.data().0;
+    for pixel in pixels.chunks_exact_mut(4) {
+        let alpha = u16::from(pixel[3]);
+        for c in &mut pixel[..3] {
+            *c = (u16::from(*c) * alpha / 255) as u8;
+        }
+    }
+    Ok(pixels)
+}
@@ -0,0 +1,144 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blend modes for drawing into the context being painted.
+//!
+//! piet has no blend modes, so they are set on the native context under it:
+//! the operator of cairo, or the composite operation of a canvas. Direct2D
+//! render targets only blend normally, and neither do bitmaps on cairo,
+//! whose context piet keeps to itself.
+
+use std::cell::RefCell;
+
+use crate::piet::Piet;
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub(crate) type NativeContext = cairo::Context;
+
+#[cfg(target_arch = "wasm32")]
+pub(crate) type NativeContext = web_sys::CanvasRenderingContext2d;
+
+#[cfg(target_os = "windows")]
+pub(crate) type NativeContext = direct2d::render_target::GenericRenderTarget;
+
+thread_local! {
+    /// The native contexts of the piet contexts alive, innermost last, or
+    /// `None` for those that can't blend.
+    static CONTEXTS: RefCell<Vec<Option<NativeContext>>> = RefCell::new(Vec::new());
+}
+
+/// How the colors drawn are combined with those under them, as defined for
+/// CSS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay,
+    Darken,
+    Lighten,
+    ColorDodge,
+    ColorBurn,
+    HardLight,
+    SoftLight,
+    Difference,
+    Exclusion,
+}
+
+impl Default for BlendMode {
+    fn default() -> BlendMode {
+        BlendMode::Normal
+    }
+}
+
+/// Marks the piet context made from a native context as the one painted
+/// into, until dropped.
+pub(crate) struct Painting(());
+
+impl Painting {
+    pub(crate) fn new(context: Option<&NativeContext>) -> Painting {
+        CONTEXTS.with(|contexts| contexts.borrow_mut().push(context.cloned()));
+        Painting(())
+    }
+}
+
+impl Drop for Painting {
+    fn drop(&mut self) {
+        CONTEXTS.with(|contexts| contexts.borrow_mut().pop());
+    }
+}
+
+/// Blend what is drawn next into `piet` with `mode`, until the state saved
+/// before is restored. Returns `false` if the platform can't blend that
+/// context, which then keeps blending normally.
+///
+/// `piet` must be the context painted into last, such as the one passed to
+/// `WinHandler::paint` or to the callback of `bitmap::render_to_pixels`.
+pub fn set_blend_mode(_piet: &mut Piet, mode: BlendMode) -> bool {
+    with_native_context(|context| match context {
+        Some(context) => apply(context, mode),
+        None => mode == BlendMode::Normal,
+    })
+}
+
+/// Call `f` with the native context of the piet context painted into last,
+/// if there is one and it is known.
+pub(crate) fn with_native_context<R>(f: impl FnOnce(Option<&NativeContext>) -> R) -> R {
+    CONTEXTS.with(|contexts| f(contexts.borrow().last().and_then(Option::as_ref)))
+}
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn apply(context: &NativeContext, mode: BlendMode) -> bool {
+    use cairo::Operator;
+
+    context.set_operator(match mode {
+        BlendMode::Normal => Operator::Over,
+        BlendMode::Multiply => Operator::Multiply,
+        BlendMode::Screen => Operator::Screen,
+        BlendMode::Overlay => Operator::Overlay,
+        BlendMode::Darken => Operator::Darken,
+        BlendMode::Lighten => Operator::Lighten,
+        BlendMode::ColorDodge => Operator::ColorDodge,
+        BlendMode::ColorBurn => Operator::ColorBurn,
+        BlendMode::HardLight => Operator::HardLight,
+        BlendMode::SoftLight => Operator::SoftLight,
+        BlendMode::Difference => Operator::Difference,
+        BlendMode::Exclusion => Operator::Exclusion,
+    });
+    true
+}
+
+#[cfg(target_arch = "wasm32")]
+fn apply(context: &NativeContext, mode: BlendMode) -> bool {
+    let operation = match mode {
+        BlendMode::Normal => "source-over",
+        BlendMode::Multiply => "multiply",
+        BlendMode::Screen => "screen",
+        BlendMode::Overlay => "overlay",
+        BlendMode::Darken => "darken",
+        BlendMode::Lighten => "lighten",
+        BlendMode::ColorDodge => "color-dodge",
+        BlendMode::ColorBurn => "color-burn",
+        BlendMode::HardLight => "hard-light",
+        BlendMode::SoftLight => "soft-light",
+        BlendMode::Difference => "difference",
+        BlendMode::Exclusion => "exclusion",
+    };
+    context.set_global_composite_operation(operation).is_ok()
+}
+
+#[cfg(target_os = "windows")]
+fn apply(_context: &NativeContext, mode: BlendMode) -> bool {
+    mode == BlendMode::Normal
+}
@@ -0,0 +1,58 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The system clipboard, platform independent types.
+
+pub use crate::platform::clipboard::Clipboard;
+
+/// Identifies the format of clipboard data.
+///
+/// The well known formats are given as MIME types, and are mapped to the
+/// native equivalent of each platform. Any other identifier names a custom
+/// format; it is registered with the system under that name, so it should be
+/// specific to the application, such as `"org.example.editor.path"`.
+pub type FormatId = &'static str;
+
+/// One representation of the data put on the clipboard.
+///
+/// A single copy can put several representations, for example paths in an
+/// internal format along with SVG and PNG versions for other applications;
+/// the pasting side chooses the one it understands best.
+#[derive(Debug, Clone)]
+pub struct ClipboardFormat {
+    pub identifier: FormatId,
+    pub data: Vec<u8>,
+}
+
+impl ClipboardFormat {
+    /// UTF-8 text.
+    pub const TEXT: FormatId = "text/plain";
+    /// A PNG encoded image.
+    pub const PNG: FormatId = "image/png";
+    /// An SVG document.
+    pub const SVG: FormatId = "image/svg+xml";
+
+    pub fn new(identifier: FormatId, data: impl Into<Vec<u8>>) -> ClipboardFormat {
+        ClipboardFormat {
+            identifier,
+            data: data.into(),
+        }
+    }
+}
+
+impl From<String> for ClipboardFormat {
+    fn from(s: String) -> ClipboardFormat {
+        ClipboardFormat::new(ClipboardFormat::TEXT, s)
+    }
+}
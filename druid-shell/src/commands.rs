@@ -0,0 +1,33 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Ids of the standard commands, sent by the menus the platform provides.
+//!
+//! The ids are at the top of the range, out of the way of the ids chosen by
+//! the application.
+
+/// Undo the last edit.
+pub const UNDO: u32 = 0xffff_ff00;
+/// Redo the last undone edit.
+pub const REDO: u32 = 0xffff_ff01;
+/// Cut the selection to the clipboard.
+pub const CUT: u32 = 0xffff_ff02;
+/// Copy the selection to the clipboard.
+pub const COPY: u32 = 0xffff_ff03;
+/// Paste the contents of the clipboard.
+pub const PASTE: u32 = 0xffff_ff04;
+/// Select everything.
+pub const SELECT_ALL: u32 = 0xffff_ff05;
+/// Show the preferences of the application.
+pub const PREFERENCES: u32 = 0xffff_ff06;
@@ -0,0 +1,102 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! File open/save dialogs, platform independent types.
+
+use std::path::{Path, PathBuf};
+
+/// Type of file dialog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileDialogType {
+    /// File open dialog.
+    Open,
+    /// File save dialog.
+    Save,
+    /// Directory chooser, for example to pick a project folder.
+    Directory,
+}
+
+/// A kind of file the user can choose, for the file type list of a dialog.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FileSpec {
+    /// A human readable name, such as "Text file".
+    pub name: &'static str,
+    /// The extensions of this kind of file, without the leading dot.
+    pub extensions: &'static [&'static str],
+}
+
+/// A file chosen in a file dialog.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FileInfo {
+    pub path: PathBuf,
+}
+
+/// Options for file dialog.
+#[derive(Debug, Clone, Default)]
+pub struct FileDialogOptions {
+    pub(crate) show_hidden: bool,
+    pub(crate) multi_selection: bool,
+    pub(crate) select_directories: bool,
+    pub(crate) allowed_types: Vec<FileSpec>,
+    pub(crate) starting_directory: Option<PathBuf>,
+    pub(crate) default_name: Option<String>,
+}
+
+impl FileSpec {
+    pub const fn new(name: &'static str, extensions: &'static [&'static str]) -> FileSpec {
+        FileSpec { name, extensions }
+    }
+}
+
+impl FileInfo {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl FileDialogOptions {
+    /// Include system and hidden items.
+    pub fn set_show_hidden(&mut self) {
+        self.show_hidden = true;
+    }
+
+    /// Allow more than one file to be chosen in an open dialog.
+    pub fn set_multi_selection(&mut self) {
+        self.multi_selection = true;
+    }
+
+    /// Choose directories rather than files in an open dialog. To choose a
+    /// single directory, `FileDialogType::Directory` is usually clearer.
+    pub fn set_select_directories(&mut self) {
+        self.select_directories = true;
+    }
+
+    /// Restrict the files that can be chosen to the given types.
+    ///
+    /// In a save dialog, the extension of the first type is added to a file
+    /// name typed without one.
+    pub fn set_allowed_types(&mut self, types: Vec<FileSpec>) {
+        self.allowed_types = types;
+    }
+
+    /// Set the directory shown when the dialog opens.
+    pub fn set_starting_directory(&mut self, path: impl Into<PathBuf>) {
+        self.starting_directory = Some(path.into());
+    }
+
+    /// Set the file name initially filled in by a save dialog.
+    pub fn set_default_name(&mut self, name: impl Into<String>) {
+        self.default_name = Some(name.into());
+    }
+}
@@ -0,0 +1,86 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drags out of the application, platform independent types.
+
+use std::path::PathBuf;
+
+use crate::clipboard::ClipboardFormat;
+use crate::window::IconDesc;
+
+/// The data carried by a drag started with `WindowHandle::start_drag`.
+///
+/// A drag can carry files, and data in the same formats as the clipboard;
+/// the drop target takes the representation it understands best.
+#[derive(Debug, Clone, Default)]
+pub struct DragData {
+    pub(crate) files: Vec<PathBuf>,
+    pub(crate) formats: Vec<ClipboardFormat>,
+    pub(crate) image: Option<DragImage>,
+    pub(crate) allow_move: bool,
+}
+
+/// The image following the pointer during a drag.
+#[derive(Debug, Clone)]
+#[cfg_attr(any(target_arch = "wasm32", feature = "headless"), allow(dead_code))]
+pub(crate) struct DragImage {
+    pub image: IconDesc,
+    /// The position of the pointer in the image, in pixels.
+    pub x: usize,
+    pub y: usize,
+}
+
+/// What the drop target did with the data of a drag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropEffect {
+    /// The drag was cancelled, or the target refused the data.
+    None,
+    Copy,
+    /// The target took the data, so the source should delete its copy.
+    /// This only happens when the drag allows moving.
+    Move,
+}
+
+impl DragData {
+    pub fn new() -> DragData {
+        DragData::default()
+    }
+
+    /// Add a file, as a path to an existing file or directory.
+    pub fn add_file(&mut self, path: impl Into<PathBuf>) {
+        self.files.push(path.into());
+    }
+
+    pub fn add_text(&mut self, text: impl Into<String>) {
+        self.formats.push(text.into().into());
+    }
+
+    /// Add the data in another representation.
+    pub fn add_format(&mut self, format: ClipboardFormat) {
+        self.formats.push(format);
+    }
+
+    /// Show an image under the pointer during the drag, with the pointer at
+    /// `(x, y)` in the image. Without one, the platform shows a default
+    /// image, if any.
+    pub fn set_image(&mut self, image: IconDesc, x: usize, y: usize) {
+        self.image = Some(DragImage { image, x, y });
+    }
+
+    /// Let the drop target move the data rather than copy it, as when
+    /// dragging a file between folders. Drags only copy by default.
+    pub fn set_allow_move(&mut self, allow_move: bool) {
+        self.allow_move = allow_move;
+    }
+}
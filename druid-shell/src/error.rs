@@ -23,6 +23,8 @@ use winapi::shared::winerror::HRESULT;
 /// might change.
 pub enum Error {
     Null,
+    /// A request the platform rejected, with a short description.
+    Other(&'static str),
     #[cfg(target_os = "windows")]
     Hr(HRESULT),
     // Maybe include the full error from the direct2d crate.
@@ -37,6 +39,7 @@ impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match *self {
             Error::Null => write!(f, "Null error"),
+            Error::Other(msg) => write!(f, "{}", msg),
             #[cfg(target_os = "windows")]
             Error::Hr(hr) => write!(f, "HRESULT 0x{:x}", hr),
             #[cfg(target_os = "windows")]
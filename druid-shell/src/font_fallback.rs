@@ -0,0 +1,140 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Finding fonts for the characters a font has no glyphs for.
+//!
+//! DirectWrite and browsers fall back to other fonts by themselves; cairo
+//! draws each run of text in a single font, so text laid out for it is split
+//! into runs in fonts that have its glyphs.
+
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::cell::RefCell;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::collections::HashMap;
+
+/// The families tried, in order, for characters the font of a text lacks.
+#[cfg(target_os = "linux")]
+const FALLBACK_FAMILIES: &[&str] = &[
+    "DejaVu Sans",
+    "Noto Sans",
+    "Noto Sans CJK SC",
+    "Noto Sans CJK JP",
+    "Noto Sans CJK KR",
+    "WenQuanYi Micro Hei",
+    "Noto Sans Arabic",
+    "Noto Sans Hebrew",
+    "Noto Sans Devanagari",
+    "Noto Sans Thai",
+    "Noto Sans Symbols",
+    "Noto Sans Symbols2",
+    "Symbola",
+];
+
+#[cfg(target_os = "macos")]
+const FALLBACK_FAMILIES: &[&str] = &[
+    "Helvetica Neue",
+    "Lucida Grande",
+    "PingFang SC",
+    "Hiragino Sans",
+    "Apple SD Gothic Neo",
+    "Geeza Pro",
+    "Kohinoor Devanagari",
+    "Thonburi",
+    "Apple Symbols",
+];
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const FALLBACK_FAMILIES: &[&str] = &[];
+
+/// The families of color emoji, tried first for emoji.
+#[cfg(target_os = "linux")]
+const EMOJI_FAMILIES: &[&str] = &["Noto Color Emoji", "Twemoji", "Noto Emoji"];
+
+#[cfg(target_os = "macos")]
+const EMOJI_FAMILIES: &[&str] = &["Apple Color Emoji"];
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+const EMOJI_FAMILIES: &[&str] = &[];
+
+/// The family to draw `cluster`, a grapheme cluster, in when it is in text
+/// set in `family`: `family` itself if it has glyphs for it or the platform
+/// falls back by itself, otherwise the first fallback that has them. Emoji
+/// prefer color emoji fonts to the monochrome glyphs some text fonts have.
+pub fn family_for<'a>(family: &'a str, cluster: &str, emoji: bool) -> &'a str {
+    let preferred = if emoji { EMOJI_FAMILIES } else { &[] };
+    let candidates = preferred
+        .iter()
+        .chain(Some(&family))
+        .chain(FALLBACK_FAMILIES);
+    for candidate in candidates {
+        if has_glyphs(candidate, cluster) {
+            return candidate;
+        }
+    }
+    family
+}
+
+/// Whether the font matched for `family` has glyphs for all of `text`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn has_glyphs(family: &str, text: &str) -> bool {
+    use cairo::{FontFace, FontOptions, FontSlant, FontWeight, Matrix, MatrixTrait, ScaledFont};
+
+    thread_local! {
+        /// The fonts matched for families, and which text they have glyphs
+        /// for.
+        static COVERAGE: RefCell<HashMap<String, (ScaledFont, HashMap<String, bool>)>> =
+            RefCell::new(HashMap::new());
+    }
+
+    // Joiners and selectors pick glyphs rather than having their own, and
+    // controls have none; text cairo can't pass to C has none to find.
+    let text = text
+        .chars()
+        .filter(|&c| !c.is_control() && !is_ignorable(c))
+        .collect::<String>();
+    if text.is_empty() || text.contains('\0') {
+        return true;
+    }
+    COVERAGE.with(|coverage| {
+        let mut coverage = coverage.borrow_mut();
+        let (font, known) = coverage.entry(family.to_string()).or_insert_with(|| {
+            let face = FontFace::toy_create(family, FontSlant::Normal, FontWeight::Normal);
+            let matrix = Matrix::identity();
+            let font = ScaledFont::new(&face, &matrix, &matrix, &FontOptions::new());
+            (font, HashMap::new())
+        });
+        if let Some(&covered) = known.get(&text) {
+            return covered;
+        }
+        // Glyph 0 is the one drawn for a missing character.
+        let (glyphs, _) = font.text_to_glyphs(0.0, 0.0, &text);
+        let covered = !glyphs.is_empty() && glyphs.iter().all(|glyph| glyph.index != 0);
+        known.insert(text, covered);
+        covered
+    })
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn has_glyphs(_family: &str, _text: &str) -> bool {
+    true
+}
+
+/// Zero width joiners, variation selectors and tags.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn is_ignorable(c: char) -> bool {
+    match c as u32 {
+        0x200c..=0x200d | 0xfe00..=0xfe0f | 0xe0020..=0xe007f | 0xe0100..=0xe01ef => true,
+        _ => false,
+    }
+}
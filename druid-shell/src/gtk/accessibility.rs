@@ -0,0 +1,789 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The accessibility tree, GTK implementation.
+//!
+//! The drawing area is of a subclass whose accessible has the children of
+//! the root of the tree as its children. Each other node is an `AtkObject`
+//! of our own types, which answer from the last tree and send actions to
+//! the handler; the AT-SPI bridge of GTK exposes them to screen readers.
+
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::mem;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::ptr;
+
+use atk_sys::*;
+use gdk::WindowExt;
+use glib::translate::{from_glib_none, ToGlibPtr};
+use glib_sys::{gboolean, gpointer, GType, GFALSE, GTRUE};
+use gobject_sys::{GObject, GTypeInfo, GTypeQuery, GValue, G_TYPE_DOUBLE};
+use gtk_rs::prelude::*;
+use gtk_rs::DrawingArea;
+
+use super::WindowState;
+use crate::accessibility::{
+    AccessNode, AccessTree, Action, ActionRequest, NodeId, Politeness, Role,
+};
+
+/// The key of the `WindowState` of a drawing area in its object data.
+const WINDOW_STATE_KEY: &[u8] = b"druid-window-state\0";
+
+// Values of `AtkLive`, which atk-sys predates.
+const ATK_LIVE_POLITE: c_int = 1;
+const ATK_LIVE_ASSERTIVE: c_int = 2;
+
+/// The object of a node.
+#[repr(C)]
+struct NodeObject {
+    parent: AtkObject,
+    /// The window, or null once it is destroyed.
+    window: *const WindowState,
+    node: NodeId,
+}
+
+/// The tree last given to `update`, and the object of each of its nodes
+/// with its type.
+#[derive(Default)]
+pub(crate) struct AccessState {
+    tree: Option<AccessTree>,
+    objects: HashMap<NodeId, (GType, *mut AtkObject)>,
+}
+
+impl Drop for AccessState {
+    fn drop(&mut self) {
+        // Assistive technology may keep objects past the window.
+        for &(_, object) in self.objects.values() {
+            unsafe {
+                detach(object);
+                gobject_sys::g_object_unref(object as *mut GObject);
+            }
+        }
+    }
+}
+
+// GTypes are registered once, on first use.
+lazy_static! {
+    static ref AREA_TYPE: GType = unsafe {
+        register_type(
+            gtk_sys::gtk_drawing_area_get_type(),
+            b"DruidDrawingArea\0",
+            Some(area_class_init),
+            None,
+        )
+    };
+    static ref AREA_ACCESSIBLE_TYPE: GType = unsafe {
+        register_type(
+            gtk_sys::gtk_widget_accessible_get_type(),
+            b"DruidDrawingAreaAccessible\0",
+            Some(area_accessible_class_init),
+            None,
+        )
+    };
+    /// The type of nodes without a value or text.
+    static ref NODE_TYPE: GType = unsafe {
+        let ty = register_type(
+            atk_object_get_type(),
+            b"DruidAccessible\0",
+            Some(node_class_init),
+            Some(mem::size_of::<NodeObject>()),
+        );
+        add_interface(ty, atk_component_get_type(), component_init);
+        add_interface(ty, atk_action_get_type(), action_init);
+        ty
+    };
+    static ref VALUE_NODE_TYPE: GType = unsafe {
+        let ty = register_type(*NODE_TYPE, b"DruidValueAccessible\0", None, None);
+        add_interface(ty, atk_value_get_type(), value_init);
+        ty
+    };
+    static ref TEXT_NODE_TYPE: GType = unsafe {
+        let ty = register_type(*NODE_TYPE, b"DruidTextAccessible\0", None, None);
+        add_interface(ty, atk_text_get_type(), text_init);
+        add_interface(ty, atk_editable_text_get_type(), editable_text_init);
+        ty
+    };
+}
+
+unsafe fn register_type(
+    parent: GType,
+    name: &[u8],
+    class_init: gobject_sys::GClassInitFunc,
+    instance_size: Option<usize>,
+) -> GType {
+    let mut query: GTypeQuery = mem::zeroed();
+    gobject_sys::g_type_query(parent, &mut query);
+    let info = GTypeInfo {
+        class_size: query.class_size as u16,
+        base_init: None,
+        base_finalize: None,
+        class_init,
+        class_finalize: None,
+        class_data: ptr::null(),
+        instance_size: instance_size.unwrap_or(query.instance_size as usize) as u16,
+        n_preallocs: 0,
+        instance_init: None,
+        value_table: ptr::null(),
+    };
+    gobject_sys::g_type_register_static(parent, name.as_ptr() as *const c_char, &info, 0)
+}
+
+unsafe fn add_interface(
+    ty: GType,
+    interface: GType,
+    init: unsafe extern "C" fn(gpointer, gpointer),
+) {
+    let info = gobject_sys::GInterfaceInfo {
+        interface_init: Some(init),
+        interface_finalize: None,
+        interface_data: ptr::null_mut(),
+    };
+    gobject_sys::g_type_add_interface_static(ty, interface, &info);
+}
+
+/// Make the drawing area of a window, whose accessible exposes the tree.
+pub(crate) fn new_drawing_area() -> DrawingArea {
+    unsafe {
+        let area = gobject_sys::g_object_new(*AREA_TYPE, ptr::null());
+        from_glib_none(area as *mut gtk_sys::GtkDrawingArea)
+    }
+}
+
+/// Let the accessible of the drawing area find the window.
+pub(crate) fn attach(state: &WindowState) {
+    set_window_state(state, state as *const WindowState);
+}
+
+/// Cut the drawing area off from the window, as it is destroyed.
+pub(crate) fn detach_window(state: &WindowState) {
+    set_window_state(state, ptr::null());
+}
+
+fn set_window_state(state: &WindowState, ptr: *const WindowState) {
+    let area: *mut gtk_sys::GtkDrawingArea = state.drawing_area.to_glib_none().0;
+    unsafe {
+        gobject_sys::g_object_set_data(
+            area as *mut GObject,
+            WINDOW_STATE_KEY.as_ptr() as *const c_char,
+            ptr as gpointer,
+        );
+    }
+}
+
+/// Replace the tree, creating and updating the objects of its nodes, and
+/// tell assistive technology what changed.
+///
+/// Assistive technology may ask about the objects while it is told, so the
+/// signals are only emitted once the new tree is in place.
+pub(crate) fn update(state: &WindowState, tree: AccessTree) {
+    unsafe {
+        let area: *mut gtk_sys::GtkWidget = state.drawing_area.to_glib_none().0;
+        let area = gtk_sys::gtk_widget_get_accessible(area);
+        let mut access = state.access.borrow_mut();
+        let old_tree = access.tree.take().unwrap_or_default();
+        // Nodes that are gone, or change type, lose their object.
+        let removed: Vec<(NodeId, *mut AtkObject)> = access
+            .objects
+            .iter()
+            .filter(|&(&node_id, &(ty, _))| match tree.node(node_id) {
+                Some(node) => node_id == tree.root || node_type(node.role) != ty,
+                None => true,
+            })
+            .map(|(&node_id, &(_, object))| (node_id, object))
+            .collect();
+        for &(node_id, object) in &removed {
+            access.objects.remove(&node_id);
+            detach(object);
+        }
+        for (node_id, node) in &tree.nodes {
+            if *node_id != tree.root {
+                let ty = node_type(node.role);
+                access
+                    .objects
+                    .entry(*node_id)
+                    .or_insert_with(|| (ty, new_object(ty, state, *node_id)));
+            }
+        }
+        let object_of = |node_id: NodeId, root: NodeId, objects: &HashMap<_, _>| {
+            if node_id == root {
+                area
+            } else {
+                objects
+                    .get(&node_id)
+                    .map_or(ptr::null_mut(), |&(_, object)| object)
+            }
+        };
+        let old_object_of = |node_id: NodeId| match removed.iter().find(|r| r.0 == node_id) {
+            Some(&(_, object)) => object,
+            None => object_of(node_id, old_tree.root, &access.objects),
+        };
+        let mut removals = Vec::new();
+        for (node_id, old) in &old_tree.nodes {
+            let parent = old_object_of(*node_id);
+            let new_children = tree.node(*node_id).map(|node| &node.children);
+            for (index, child) in old.children.iter().enumerate() {
+                let child = old_object_of(*child);
+                let kept = new_children.map_or(false, |children| {
+                    children
+                        .iter()
+                        .any(|&c| object_of(c, tree.root, &access.objects) == child)
+                });
+                if !kept && !parent.is_null() && !child.is_null() {
+                    removals.push((parent, index, child));
+                }
+            }
+        }
+        let mut additions = Vec::new();
+        let mut properties = Vec::new();
+        for (node_id, node) in &tree.nodes {
+            let object = object_of(*node_id, tree.root, &access.objects);
+            for (index, child) in node.children.iter().enumerate() {
+                let child = object_of(*child, tree.root, &access.objects);
+                if !child.is_null() {
+                    additions.push((object, index, child));
+                }
+            }
+            if *node_id != tree.root {
+                let old = old_tree.node(*node_id).cloned();
+                properties.push((object, node.clone(), old));
+            }
+        }
+        let focus = (
+            old_tree.focus.map(|focus| old_object_of(focus)),
+            tree.focus
+                .map(|focus| object_of(focus, tree.root, &access.objects)),
+        );
+        let focus_changed = old_tree.focus != tree.focus;
+        access.tree = Some(tree);
+        drop(access);
+
+        for &(_, object) in &removed {
+            atk_object_notify_state_change(object, ATK_STATE_DEFUNCT as AtkState, GTRUE);
+        }
+        for (parent, index, child) in removals {
+            emit_children_changed(parent, b"children-changed::remove\0", index, child);
+        }
+        for (object, node, old) in properties {
+            set_properties(object, &node, old.as_ref());
+        }
+        for (parent, index, child) in additions {
+            // The object of a new child, or one that moves, is added.
+            if (*child).accessible_parent != parent {
+                atk_object_set_parent(child, parent);
+                emit_children_changed(parent, b"children-changed::add\0", index, child);
+            }
+        }
+        if focus_changed {
+            if let Some(object) = focus.0.filter(|object| !object.is_null()) {
+                atk_object_notify_state_change(object, ATK_STATE_FOCUSED as AtkState, GFALSE);
+            }
+            if let Some(object) = focus.1.filter(|object| !object.is_null()) {
+                atk_object_notify_state_change(object, ATK_STATE_FOCUSED as AtkState, GTRUE);
+            }
+        }
+        for (_, object) in removed {
+            gobject_sys::g_object_unref(object as *mut GObject);
+        }
+    }
+}
+
+/// Ask assistive technology to read out `text`, with the `notification`
+/// signal of ATK 2.50, or else the `announcement` signal of ATK 2.46,
+/// emitted on the accessible of the drawing area. Older ATK has neither,
+/// and then this has no effect.
+pub(crate) fn announce(state: &WindowState, text: &str, politeness: Politeness) {
+    let text = match CString::new(text) {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    unsafe {
+        let area: *mut gtk_sys::GtkWidget = state.drawing_area.to_glib_none().0;
+        let area = gtk_sys::gtk_widget_get_accessible(area) as *mut GObject;
+        let has_signal = |name: &[u8]| {
+            gobject_sys::g_signal_lookup(name.as_ptr() as *const c_char, atk_object_get_type()) != 0
+        };
+        if has_signal(b"notification\0") {
+            let politeness = match politeness {
+                Politeness::Polite => ATK_LIVE_POLITE,
+                Politeness::Assertive => ATK_LIVE_ASSERTIVE,
+            };
+            gobject_sys::g_signal_emit_by_name(
+                area,
+                b"notification\0".as_ptr() as *const c_char,
+                text.as_ptr(),
+                politeness,
+            );
+        } else if has_signal(b"announcement\0") {
+            gobject_sys::g_signal_emit_by_name(
+                area,
+                b"announcement\0".as_ptr() as *const c_char,
+                text.as_ptr(),
+            );
+        }
+    }
+}
+
+unsafe fn emit_children_changed(
+    parent: *mut AtkObject,
+    signal: &[u8],
+    index: usize,
+    child: *mut AtkObject,
+) {
+    gobject_sys::g_signal_emit_by_name(
+        parent as *mut GObject,
+        signal.as_ptr() as *const c_char,
+        index as c_uint,
+        child as gpointer,
+    );
+}
+
+fn node_type(role: Role) -> GType {
+    match role {
+        Role::Slider | Role::ProgressIndicator => *VALUE_NODE_TYPE,
+        Role::TextInput | Role::PasswordInput => *TEXT_NODE_TYPE,
+        _ => *NODE_TYPE,
+    }
+}
+
+unsafe fn new_object(ty: GType, state: &WindowState, node_id: NodeId) -> *mut AtkObject {
+    let object = gobject_sys::g_object_new(ty, ptr::null()) as *mut NodeObject;
+    (*object).window = state as *const WindowState;
+    (*object).node = node_id;
+    object as *mut AtkObject
+}
+
+/// Cut the object off from the window, after which it is defunct.
+unsafe fn detach(object: *mut AtkObject) {
+    (*(object as *mut NodeObject)).window = ptr::null();
+}
+
+unsafe fn set_properties(object: *mut AtkObject, node: &AccessNode, old: Option<&AccessNode>) {
+    let role = match node.role {
+        Role::Group | Role::Window => ATK_ROLE_PANEL,
+        Role::Label => ATK_ROLE_LABEL,
+        Role::Button => ATK_ROLE_PUSH_BUTTON,
+        Role::CheckBox => ATK_ROLE_CHECK_BOX,
+        Role::TextInput => ATK_ROLE_ENTRY,
+        Role::PasswordInput => ATK_ROLE_PASSWORD_TEXT,
+        Role::Slider => ATK_ROLE_SLIDER,
+        Role::ProgressIndicator => ATK_ROLE_PROGRESS_BAR,
+        Role::Image => ATK_ROLE_IMAGE,
+        Role::List => ATK_ROLE_LIST,
+        Role::ListItem => ATK_ROLE_LIST_ITEM,
+        Role::Menu => ATK_ROLE_MENU,
+        Role::MenuItem => ATK_ROLE_MENU_ITEM,
+    };
+    if (*object).role != role {
+        atk_object_set_role(object, role);
+    }
+    if old.map(|old| &old.name) != Some(&node.name) {
+        let name = CString::new(node.name.clone().unwrap_or_default()).unwrap_or_default();
+        atk_object_set_name(object, name.as_ptr());
+    }
+    let old = match old {
+        Some(old) => old,
+        None => return,
+    };
+    if old.numeric_value != node.numeric_value {
+        gobject_sys::g_object_notify(
+            object as *mut GObject,
+            b"accessible-value\0".as_ptr() as *const c_char,
+        );
+        if node.role == Role::CheckBox {
+            let checked = node.numeric_value == Some(1.0);
+            atk_object_notify_state_change(
+                object,
+                ATK_STATE_CHECKED as AtkState,
+                checked as gboolean,
+            );
+        }
+    }
+    if old.value != node.value && node_type(node.role) == *TEXT_NODE_TYPE {
+        let old_len = old.value.as_ref().map_or(0, |value| value.chars().count());
+        let new_len = node.value.as_ref().map_or(0, |value| value.chars().count());
+        for &(signal, len) in &[
+            (&b"text-changed::delete\0"[..], old_len),
+            (&b"text-changed::insert\0"[..], new_len),
+        ] {
+            gobject_sys::g_signal_emit_by_name(
+                object as *mut GObject,
+                signal.as_ptr() as *const c_char,
+                0 as c_int,
+                len as c_int,
+            );
+        }
+    }
+}
+
+/// The window and id of the node of an object, unless the window is gone.
+unsafe fn node_of<'a>(object: gpointer) -> Option<(&'a WindowState, NodeId)> {
+    let object = &*(object as *const NodeObject);
+    if object.window.is_null() {
+        None
+    } else {
+        Some((&*object.window, object.node))
+    }
+}
+
+/// Call `f` with the node of an object in the last tree.
+fn with_node<R>(object: gpointer, f: impl FnOnce(&AccessTree, &AccessNode) -> R) -> Option<R> {
+    unsafe {
+        let (state, node_id) = node_of(object)?;
+        let access = state.access.borrow();
+        let tree = access.tree.as_ref()?;
+        Some(f(tree, tree.node(node_id)?))
+    }
+}
+
+/// The object of a node, with its reference count incremented, as the
+/// `ref_` functions of ATK return.
+fn ref_object(state: &WindowState, node_id: NodeId) -> *mut AtkObject {
+    match state.access.borrow().objects.get(&node_id) {
+        Some(&(_, object)) => unsafe {
+            gobject_sys::g_object_ref(object as *mut GObject);
+            object
+        },
+        None => ptr::null_mut(),
+    }
+}
+
+/// Send an action to the handler, if the node supports it.
+fn perform(object: gpointer, action: Action, value: Option<String>) -> gboolean {
+    let supported = with_node(object, |_, node| node.supports(action)).unwrap_or(false);
+    let (state, node_id) = match unsafe { node_of(object) } {
+        Some(found) if supported => found,
+        _ => return GFALSE,
+    };
+    let request = ActionRequest {
+        node: node_id,
+        action,
+        value,
+    };
+    state.handler.accessibility_action(request) as gboolean
+}
+
+/// The window of the accessible of a drawing area.
+unsafe fn area_window_state<'a>(accessible: *mut AtkObject) -> Option<&'a WindowState> {
+    let area = gtk_sys::gtk_accessible_get_widget(accessible as *mut gtk_sys::GtkAccessible);
+    if area.is_null() {
+        return None;
+    }
+    let state = gobject_sys::g_object_get_data(
+        area as *mut GObject,
+        WINDOW_STATE_KEY.as_ptr() as *const c_char,
+    ) as *const WindowState;
+    state.as_ref()
+}
+
+unsafe extern "C" fn area_class_init(class: gpointer, _: gpointer) {
+    gtk_sys::gtk_widget_class_set_accessible_type(
+        class as *mut gtk_sys::GtkWidgetClass,
+        *AREA_ACCESSIBLE_TYPE,
+    );
+}
+
+unsafe extern "C" fn area_accessible_class_init(class: gpointer, _: gpointer) {
+    let class = &mut *(class as *mut AtkObjectClass);
+    class.get_n_children = Some(area_n_children);
+    class.ref_child = Some(area_ref_child);
+}
+
+/// The children of the root of the tree of the window.
+fn area_children(accessible: *mut AtkObject) -> Option<(&'static WindowState, Vec<NodeId>)> {
+    unsafe {
+        let state = area_window_state(accessible)?;
+        let access = state.access.borrow();
+        let tree = access.tree.as_ref()?;
+        Some((state, tree.node(tree.root)?.children.clone()))
+    }
+}
+
+unsafe extern "C" fn area_n_children(accessible: *mut AtkObject) -> c_int {
+    area_children(accessible).map_or(0, |(_, children)| children.len() as c_int)
+}
+
+unsafe extern "C" fn area_ref_child(accessible: *mut AtkObject, index: c_int) -> *mut AtkObject {
+    match area_children(accessible) {
+        Some((state, ref children)) if index >= 0 && (index as usize) < children.len() => {
+            ref_object(state, children[index as usize])
+        }
+        _ => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn node_class_init(class: gpointer, _: gpointer) {
+    let class = &mut *(class as *mut AtkObjectClass);
+    class.get_n_children = Some(node_n_children);
+    class.ref_child = Some(node_ref_child);
+    class.get_index_in_parent = Some(node_index_in_parent);
+    class.ref_state_set = Some(node_ref_state_set);
+}
+
+unsafe extern "C" fn node_n_children(object: *mut AtkObject) -> c_int {
+    with_node(object as gpointer, |_, node| node.children.len() as c_int).unwrap_or(0)
+}
+
+unsafe extern "C" fn node_ref_child(object: *mut AtkObject, index: c_int) -> *mut AtkObject {
+    let child = with_node(object as gpointer, |_, node| {
+        node.children.get(index as usize).cloned()
+    });
+    match (node_of(object as gpointer), child) {
+        (Some((state, _)), Some(Some(child))) if index >= 0 => ref_object(state, child),
+        _ => ptr::null_mut(),
+    }
+}
+
+unsafe extern "C" fn node_index_in_parent(object: *mut AtkObject) -> c_int {
+    let node_id = match node_of(object as gpointer) {
+        Some((_, node_id)) => node_id,
+        None => return -1,
+    };
+    with_node(object as gpointer, |tree, _| {
+        tree.nodes
+            .iter()
+            .filter_map(|(_, parent)| parent.children.iter().position(|&child| child == node_id))
+            .next()
+            .map_or(-1, |index| index as c_int)
+    })
+    .unwrap_or(-1)
+}
+
+unsafe extern "C" fn node_ref_state_set(object: *mut AtkObject) -> *mut AtkStateSet {
+    let set = atk_state_set_new();
+    let states = with_node(object as gpointer, |tree, node| {
+        let mut states = vec![
+            ATK_STATE_ENABLED,
+            ATK_STATE_SENSITIVE,
+            ATK_STATE_VISIBLE,
+            ATK_STATE_SHOWING,
+        ];
+        if node.supports(Action::Focus) {
+            states.push(ATK_STATE_FOCUSABLE);
+        }
+        let node_id = node_of(object as gpointer).map(|(_, node_id)| node_id);
+        if tree.focus.is_some() && tree.focus == node_id {
+            states.push(ATK_STATE_FOCUSED);
+        }
+        match node.role {
+            Role::CheckBox => {
+                states.push(ATK_STATE_CHECKABLE);
+                if node.numeric_value == Some(1.0) {
+                    states.push(ATK_STATE_CHECKED);
+                }
+            }
+            Role::TextInput | Role::PasswordInput if node.supports(Action::SetValue) => {
+                states.push(ATK_STATE_EDITABLE);
+            }
+            _ => (),
+        }
+        states
+    });
+    match states {
+        Some(mut states) => {
+            atk_state_set_add_states(set, states.as_mut_ptr(), states.len() as c_int)
+        }
+        None => {
+            atk_state_set_add_state(set, ATK_STATE_DEFUNCT);
+        }
+    }
+    set
+}
+
+unsafe extern "C" fn component_init(iface: gpointer, _: gpointer) {
+    let iface = &mut *(iface as *mut AtkComponentIface);
+    iface.get_extents = Some(component_extents);
+    iface.grab_focus = Some(component_grab_focus);
+}
+
+/// The bounds of the node, in the coordinates of the screen or of the
+/// window.
+unsafe extern "C" fn component_extents(
+    component: *mut AtkComponent,
+    x: *mut c_int,
+    y: *mut c_int,
+    width: *mut c_int,
+    height: *mut c_int,
+    coord_type: AtkCoordType,
+) {
+    let bounds = with_node(component as gpointer, |_, node| node.bounds);
+    let (state, bounds) = match (node_of(component as gpointer), bounds) {
+        (Some((state, _)), Some(bounds)) => (state, bounds),
+        _ => return,
+    };
+    let (left, top) = if coord_type == ATK_XY_SCREEN {
+        match state.drawing_area.get_window() {
+            Some(window) => {
+                let (_, left, top) = window.get_origin();
+                (left, top)
+            }
+            None => (0, 0),
+        }
+    } else {
+        state
+            .drawing_area
+            .translate_coordinates(&state.window, 0, 0)
+            .unwrap_or((0, 0))
+    };
+    *x = left + bounds.x0 as c_int;
+    *y = top + bounds.y0 as c_int;
+    *width = bounds.width() as c_int;
+    *height = bounds.height() as c_int;
+}
+
+unsafe extern "C" fn component_grab_focus(component: *mut AtkComponent) -> gboolean {
+    perform(component as gpointer, Action::Focus, None)
+}
+
+/// The actions exposed through `AtkAction`; focus and values have their
+/// own interfaces.
+fn node_actions(object: gpointer) -> Vec<Action> {
+    with_node(object, |_, node| {
+        node.actions
+            .iter()
+            .cloned()
+            .filter(|action| match action {
+                Action::Click | Action::Increment | Action::Decrement => true,
+                Action::Focus | Action::SetValue => false,
+            })
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+unsafe extern "C" fn action_init(iface: gpointer, _: gpointer) {
+    let iface = &mut *(iface as *mut AtkActionIface);
+    iface.get_n_actions = Some(action_n_actions);
+    iface.do_action = Some(action_do_action);
+    iface.get_name = Some(action_name);
+}
+
+unsafe extern "C" fn action_n_actions(action: *mut AtkAction) -> c_int {
+    node_actions(action as gpointer).len() as c_int
+}
+
+unsafe extern "C" fn action_do_action(action: *mut AtkAction, index: c_int) -> gboolean {
+    match node_actions(action as gpointer).get(index as usize) {
+        Some(&node_action) if index >= 0 => perform(action as gpointer, node_action, None),
+        _ => GFALSE,
+    }
+}
+
+unsafe extern "C" fn action_name(action: *mut AtkAction, index: c_int) -> *const c_char {
+    let name: &'static [u8] = match node_actions(action as gpointer).get(index as usize) {
+        Some(Action::Click) if index >= 0 => b"click\0",
+        Some(Action::Increment) if index >= 0 => b"increment\0",
+        Some(Action::Decrement) if index >= 0 => b"decrement\0",
+        _ => return ptr::null(),
+    };
+    name.as_ptr() as *const c_char
+}
+
+unsafe extern "C" fn value_init(iface: gpointer, _: gpointer) {
+    let iface = &mut *(iface as *mut AtkValueIface);
+    iface.get_current_value = Some(value_current);
+    iface.get_minimum_value = Some(value_minimum);
+    iface.get_maximum_value = Some(value_maximum);
+    iface.set_current_value = Some(value_set_current);
+}
+
+unsafe fn set_double(value: *mut GValue, v: Option<f64>) {
+    if let Some(v) = v {
+        gobject_sys::g_value_init(value, G_TYPE_DOUBLE);
+        gobject_sys::g_value_set_double(value, v);
+    }
+}
+
+unsafe extern "C" fn value_current(object: *mut AtkValue, value: *mut GValue) {
+    set_double(
+        value,
+        with_node(object as gpointer, |_, node| node.numeric_value).and_then(|v| v),
+    );
+}
+
+unsafe extern "C" fn value_minimum(object: *mut AtkValue, value: *mut GValue) {
+    set_double(
+        value,
+        with_node(object as gpointer, |_, node| node.min_value).and_then(|v| v),
+    );
+}
+
+unsafe extern "C" fn value_maximum(object: *mut AtkValue, value: *mut GValue) {
+    set_double(
+        value,
+        with_node(object as gpointer, |_, node| node.max_value).and_then(|v| v),
+    );
+}
+
+unsafe extern "C" fn value_set_current(object: *mut AtkValue, value: *const GValue) -> gboolean {
+    let value = gobject_sys::g_value_get_double(value);
+    perform(
+        object as gpointer,
+        Action::SetValue,
+        Some(value.to_string()),
+    )
+}
+
+unsafe extern "C" fn text_init(iface: gpointer, _: gpointer) {
+    let iface = &mut *(iface as *mut AtkTextIface);
+    iface.get_text = Some(text_get_text);
+    iface.get_character_count = Some(text_character_count);
+    iface.get_caret_offset = Some(text_caret_offset);
+}
+
+fn node_text(object: gpointer) -> String {
+    with_node(object, |_, node| node.value.clone())
+        .and_then(|text| text)
+        .unwrap_or_default()
+}
+
+/// The characters from `start` up to `end`, or the end of the text if
+/// `end` is -1, newly allocated as ATK expects.
+unsafe extern "C" fn text_get_text(text: *mut AtkText, start: c_int, end: c_int) -> *mut c_char {
+    let all = node_text(text as gpointer);
+    let start = start.max(0) as usize;
+    let count = if end < 0 {
+        all.len()
+    } else {
+        (end as usize).saturating_sub(start)
+    };
+    let part: String = all.chars().skip(start).take(count).collect();
+    let part = CString::new(part).unwrap_or_default();
+    glib_sys::g_strdup(part.as_ptr())
+}
+
+unsafe extern "C" fn text_character_count(text: *mut AtkText) -> c_int {
+    node_text(text as gpointer).chars().count() as c_int
+}
+
+/// The caret isn't part of the tree; it is reported at the end.
+unsafe extern "C" fn text_caret_offset(text: *mut AtkText) -> c_int {
+    text_character_count(text)
+}
+
+unsafe extern "C" fn editable_text_init(iface: gpointer, _: gpointer) {
+    let iface = &mut *(iface as *mut AtkEditableTextIface);
+    iface.set_text_contents = Some(editable_text_set_contents);
+}
+
+unsafe extern "C" fn editable_text_set_contents(
+    text: *mut AtkEditableText,
+    contents: *const c_char,
+) {
+    if contents.is_null() {
+        return;
+    }
+    let contents = CStr::from_ptr(contents).to_string_lossy().into_owned();
+    perform(text as gpointer, Action::SetValue, Some(contents));
+}
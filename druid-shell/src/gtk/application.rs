@@ -0,0 +1,318 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GTK implementation of features at the application scope.
+
+use std::ffi::{c_void, CString};
+use std::fs;
+use std::os::raw::c_char;
+use std::path::Path;
+use std::process::Command;
+use std::ptr::{null, null_mut};
+
+use gio_sys::{
+    GDBusConnection, G_BUS_TYPE_SESSION, G_BUS_TYPE_SYSTEM, G_DBUS_CALL_FLAGS_NONE,
+    G_DBUS_SIGNAL_FLAGS_NONE,
+};
+use glib_sys::GVariant;
+use gtk_rs::prelude::*;
+
+use crate::clipboard::Clipboard;
+use crate::piet::Color;
+use crate::window::{PowerSource, Theme};
+use crate::Error;
+
+const SCREENSAVER_NAME: &[u8] = b"org.freedesktop.ScreenSaver\0";
+const SCREENSAVER_PATH: &[u8] = b"/org/freedesktop/ScreenSaver\0";
+const UPOWER_NAME: &[u8] = b"org.freedesktop.UPower\0";
+const UPOWER_PATH: &[u8] = b"/org/freedesktop/UPower\0";
+
+pub struct Application;
+
+/// Keeps the display awake until dropped; see `Application::inhibit_sleep`.
+pub struct SleepInhibitor {
+    connection: *mut GDBusConnection,
+    cookie: u32,
+}
+
+impl Application {
+    pub fn quit() {
+        gtk_rs::main_quit();
+    }
+
+    /// The system clipboard.
+    pub fn clipboard() -> Clipboard {
+        Clipboard
+    }
+
+    /// Whether the GTK theme is dark, either by preference or by name.
+    pub fn theme() -> Theme {
+        let settings = match gtk_rs::Settings::get_default() {
+            Some(settings) => settings,
+            None => return Theme::Light,
+        };
+        let dark_name = settings
+            .get_property_gtk_theme_name()
+            .map_or(false, |name| name.to_lowercase().ends_with("-dark"));
+        if settings.get_property_gtk_application_prefer_dark_theme() || dark_name {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+
+    /// GTK has no accent color, so this is always `None`.
+    pub fn accent_color() -> Option<Color> {
+        None
+    }
+
+    /// GTK has no setting to reduce transparency, so this is always
+    /// `false`.
+    pub fn reduce_transparency() -> bool {
+        false
+    }
+
+    /// The font resolution set with `gtk-xft-dpi`, relative to 96 dpi. Desktops
+    /// scale it with the size of text chosen in their accessibility settings.
+    pub fn text_scale() -> f64 {
+        let dpi = gtk_rs::Settings::get_default().map_or(-1, |s| s.get_property_gtk_xft_dpi());
+        // The setting is in 1024ths of a dot per inch, -1 for the default.
+        if dpi > 0 {
+            f64::from(dpi) / (1024.0 * 96.0)
+        } else {
+            1.0
+        }
+    }
+
+    /// Whether the computer runs on battery, as reported by the kernel.
+    /// Batteries of peripherals, such as a wireless mouse, don't count.
+    pub fn power_source() -> PowerSource {
+        let supplies = match fs::read_dir("/sys/class/power_supply") {
+            Ok(supplies) => supplies,
+            Err(_) => return PowerSource::External,
+        };
+        let mut battery = false;
+        for supply in supplies.filter_map(Result::ok) {
+            let path = supply.path();
+            let read = |name: &str| {
+                fs::read_to_string(path.join(name))
+                    .map(|value| value.trim().to_string())
+                    .unwrap_or_default()
+            };
+            match read("type").as_str() {
+                "Mains" | "USB" if read("online") == "1" => return PowerSource::External,
+                "Battery" if read("scope") != "Device" => battery = true,
+                _ => (),
+            }
+        }
+        if battery {
+            PowerSource::Battery
+        } else {
+            PowerSource::External
+        }
+    }
+
+    /// Only the macOS Dock shows badges, so this does nothing.
+    pub fn set_badge(_label: Option<&str>) {}
+
+    /// Open a URL in the default browser, or with the application registered
+    /// for its scheme.
+    pub fn open_url(url: &str) -> Result<(), Error> {
+        gtk_rs::show_uri(None, url, gtk_rs::get_current_event_time())
+            .map_err(|_| Error::Other("no application to open the URL"))
+    }
+
+    /// Open the folder containing `path` in the file manager, with the item
+    /// selected if the file manager implements the freedesktop.org
+    /// `FileManager1` interface, and otherwise just the folder.
+    pub fn reveal_in_file_manager(path: &Path) -> Result<(), Error> {
+        let path = path
+            .canonicalize()
+            .map_err(|_| Error::Other("no such file"))?;
+        let uri = glib::filename_to_uri(&path, None)
+            .map_err(|_| Error::Other("path can't be made a URI"))?;
+        let shown = Command::new("dbus-send")
+            .args(&[
+                "--session",
+                "--dest=org.freedesktop.FileManager1",
+                "--type=method_call",
+                "/org/freedesktop/FileManager1",
+                "org.freedesktop.FileManager1.ShowItems",
+            ])
+            .arg(format!("array:string:{}", uri))
+            .arg("string:")
+            .status()
+            .map_or(false, |status| status.success());
+        if shown {
+            return Ok(());
+        }
+        match path.parent() {
+            Some(folder) => {
+                let uri = glib::filename_to_uri(folder, None)
+                    .map_err(|_| Error::Other("path can't be made a URI"))?;
+                Application::open_url(&uri)
+            }
+            None => Err(Error::Other("path has no containing folder")),
+        }
+    }
+
+    /// Keep the display from sleeping and the screensaver from starting,
+    /// for as long as the returned value is alive. This needs a desktop
+    /// implementing the freedesktop.org `ScreenSaver` interface, as GNOME
+    /// and KDE do.
+    pub fn inhibit_sleep(reason: &str) -> Result<SleepInhibitor, Error> {
+        let app_name = glib::get_prgname().unwrap_or_else(|| "druid".to_string());
+        let app_name = CString::new(app_name).map_err(|_| Error::Other("bad program name"))?;
+        let reason = CString::new(reason).map_err(|_| Error::Other("reason contains a NUL"))?;
+        unsafe {
+            let connection = g_bus_get_session()?;
+            let parameters = glib_sys::g_variant_new(
+                b"(ss)\0".as_ptr() as *const c_char,
+                app_name.as_ptr(),
+                reason.as_ptr(),
+            );
+            match call_screensaver(connection, b"Inhibit\0", parameters) {
+                Ok(reply) => {
+                    let mut cookie = 0u32;
+                    glib_sys::g_variant_get(
+                        reply,
+                        b"(u)\0".as_ptr() as *const c_char,
+                        &mut cookie as *mut u32,
+                    );
+                    glib_sys::g_variant_unref(reply);
+                    Ok(SleepInhibitor { connection, cookie })
+                }
+                Err(err) => {
+                    gobject_sys::g_object_unref(connection as *mut _);
+                    Err(err)
+                }
+            }
+        }
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        unsafe {
+            let parameters =
+                glib_sys::g_variant_new(b"(u)\0".as_ptr() as *const c_char, self.cookie);
+            if let Ok(reply) = call_screensaver(self.connection, b"UnInhibit\0", parameters) {
+                glib_sys::g_variant_unref(reply);
+            }
+            gobject_sys::g_object_unref(self.connection as *mut _);
+        }
+    }
+}
+
+/// A subscription to the changes of power source reported by UPower, until
+/// dropped.
+pub(crate) struct PowerSubscription {
+    connection: *mut GDBusConnection,
+    id: u32,
+}
+
+impl PowerSubscription {
+    /// Call `callback` whenever UPower reports a change of its properties,
+    /// such as whether the computer runs on battery.
+    pub(crate) fn new(callback: Box<dyn Fn()>) -> Result<PowerSubscription, Error> {
+        unsafe {
+            let mut error = null_mut();
+            let connection = gio_sys::g_bus_get_sync(G_BUS_TYPE_SYSTEM, null_mut(), &mut error);
+            if connection.is_null() {
+                glib_sys::g_error_free(error);
+                return Err(Error::Other("no D-Bus system bus"));
+            }
+            let user_data = Box::into_raw(Box::new(callback));
+            let id = gio_sys::g_dbus_connection_signal_subscribe(
+                connection,
+                UPOWER_NAME.as_ptr() as *const c_char,
+                b"org.freedesktop.DBus.Properties\0".as_ptr() as *const c_char,
+                b"PropertiesChanged\0".as_ptr() as *const c_char,
+                UPOWER_PATH.as_ptr() as *const c_char,
+                null(),
+                G_DBUS_SIGNAL_FLAGS_NONE,
+                Some(power_signal),
+                user_data as *mut c_void,
+                Some(free_power_callback),
+            );
+            Ok(PowerSubscription { connection, id })
+        }
+    }
+}
+
+impl Drop for PowerSubscription {
+    fn drop(&mut self) {
+        unsafe {
+            gio_sys::g_dbus_connection_signal_unsubscribe(self.connection, self.id);
+            gobject_sys::g_object_unref(self.connection as *mut _);
+        }
+    }
+}
+
+unsafe extern "C" fn power_signal(
+    _connection: *mut GDBusConnection,
+    _sender: *const c_char,
+    _path: *const c_char,
+    _interface: *const c_char,
+    _signal: *const c_char,
+    _parameters: *mut GVariant,
+    user_data: *mut c_void,
+) {
+    let callback = &*(user_data as *const Box<dyn Fn()>);
+    callback();
+}
+
+unsafe extern "C" fn free_power_callback(user_data: *mut c_void) {
+    drop(Box::from_raw(user_data as *mut Box<dyn Fn()>));
+}
+
+unsafe fn g_bus_get_session() -> Result<*mut GDBusConnection, Error> {
+    let mut error = null_mut();
+    let connection = gio_sys::g_bus_get_sync(G_BUS_TYPE_SESSION, null_mut(), &mut error);
+    if connection.is_null() {
+        glib_sys::g_error_free(error);
+        Err(Error::Other("no D-Bus session bus"))
+    } else {
+        Ok(connection)
+    }
+}
+
+/// Call a method of the screensaver, with NUL-terminated `method`. The call
+/// takes ownership of `parameters`.
+unsafe fn call_screensaver(
+    connection: *mut GDBusConnection,
+    method: &[u8],
+    parameters: *mut GVariant,
+) -> Result<*mut GVariant, Error> {
+    let mut error = null_mut();
+    let reply = gio_sys::g_dbus_connection_call_sync(
+        connection,
+        SCREENSAVER_NAME.as_ptr() as *const c_char,
+        SCREENSAVER_PATH.as_ptr() as *const c_char,
+        SCREENSAVER_NAME.as_ptr() as *const c_char,
+        method.as_ptr() as *const c_char,
+        parameters,
+        null(),
+        G_DBUS_CALL_FLAGS_NONE,
+        -1,
+        null_mut(),
+        &mut error,
+    );
+    if reply.is_null() {
+        glib_sys::g_error_free(error);
+        Err(Error::Other("no screensaver to inhibit"))
+    } else {
+        Ok(reply)
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interactions with the system clipboard, GTK implementation.
+
+use gdk::Atom;
+use glib::translate::ToGlibPtr;
+use gtk_rs::{ClipboardExt, ClipboardExtManual, SelectionData, TargetEntry, TargetFlags};
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+
+/// The targets other applications use to ask for text.
+const TEXT_TARGETS: &[&str] = &[
+    "UTF8_STRING",
+    "text/plain;charset=utf-8",
+    "text/plain",
+    "STRING",
+    "TEXT",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard;
+
+impl Clipboard {
+    /// Put a string on the clipboard, replacing its contents.
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        gtk_clipboard().set_text(s.as_ref());
+    }
+
+    /// Get the string on the clipboard, if there is one.
+    pub fn get_string(&self) -> Option<String> {
+        gtk_clipboard().wait_for_text()
+    }
+
+    /// Put data on the clipboard in several formats, replacing its contents.
+    ///
+    /// The data is kept until the clipboard is taken over, and handed out
+    /// when another application asks for it.
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        let entries = target_entries(formats);
+        let formats = formats.to_vec();
+        gtk_clipboard().set_with_data(&entries, move |_, selection, info| {
+            set_selection_data(selection, &formats[info as usize]);
+        });
+    }
+
+    /// Get the data on the clipboard in a format, if it is available.
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        if format == ClipboardFormat::TEXT {
+            return self.get_string().map(String::into_bytes);
+        }
+        gtk_clipboard()
+            .wait_for_contents(&Atom::intern(format))
+            .map(|data| data.get_data_with_length())
+    }
+
+    /// The first of `formats` that the clipboard has data for.
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        let targets: Vec<String> = gtk_clipboard()
+            .wait_for_targets()?
+            .into_iter()
+            .map(Atom::name)
+            .collect();
+        formats.iter().cloned().find(|&format| {
+            if format == ClipboardFormat::TEXT {
+                TEXT_TARGETS
+                    .iter()
+                    .any(|t| targets.iter().any(|name| name == t))
+            } else {
+                targets.iter().any(|name| name == format)
+            }
+        })
+    }
+}
+
+/// The targets offering `formats`, with the index of the format as the info.
+pub(crate) fn target_entries(formats: &[ClipboardFormat]) -> Vec<TargetEntry> {
+    let mut entries = Vec::new();
+    for (i, format) in formats.iter().enumerate() {
+        if format.identifier == ClipboardFormat::TEXT {
+            for target in TEXT_TARGETS {
+                entries.push(TargetEntry::new(target, TargetFlags::empty(), i as u32));
+            }
+        } else {
+            entries.push(TargetEntry::new(
+                format.identifier,
+                TargetFlags::empty(),
+                i as u32,
+            ));
+        }
+    }
+    entries
+}
+
+/// Hand out data in a format, for a target from `target_entries`.
+pub(crate) fn set_selection_data(selection: &SelectionData, format: &ClipboardFormat) {
+    if format.identifier == ClipboardFormat::TEXT {
+        selection.set_text(&String::from_utf8_lossy(&format.data));
+    } else {
+        let atom = Atom::intern(format.identifier);
+        unsafe {
+            gtk_sys::gtk_selection_data_set(
+                selection.to_glib_none().0 as *mut _,
+                atom.to_glib_none().0,
+                8,
+                format.data.as_ptr() as *mut u8,
+                format.data.len() as i32,
+            );
+        }
+    }
+}
+
+fn gtk_clipboard() -> gtk_rs::Clipboard {
+    gtk_rs::Clipboard::get(&gdk::SELECTION_CLIPBOARD)
+}
@@ -0,0 +1,90 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! File open/save dialogs, GTK implementation.
+
+use std::path::PathBuf;
+
+use gtk_rs::prelude::*;
+use gtk_rs::{FileChooserAction, FileChooserDialog, FileFilter, ResponseType, Window};
+
+use crate::dialog::{FileDialogOptions, FileDialogType};
+
+/// Run a modal file chooser, returning the chosen paths, or `None` if the
+/// dialog was cancelled.
+pub(crate) fn get_file_dialog_paths(
+    window: &Window,
+    ty: FileDialogType,
+    options: &FileDialogOptions,
+) -> Option<Vec<PathBuf>> {
+    let (title, action, accept) = match ty {
+        FileDialogType::Open if options.select_directories => {
+            ("Open", FileChooserAction::SelectFolder, "_Open")
+        }
+        FileDialogType::Open => ("Open", FileChooserAction::Open, "_Open"),
+        FileDialogType::Save => ("Save", FileChooserAction::Save, "_Save"),
+        FileDialogType::Directory => ("Select Folder", FileChooserAction::SelectFolder, "_Select"),
+    };
+    let dialog = FileChooserDialog::with_buttons(
+        Some(title),
+        Some(window),
+        action,
+        &[
+            ("_Cancel", ResponseType::Cancel),
+            (accept, ResponseType::Accept),
+        ],
+    );
+    dialog.set_select_multiple(ty == FileDialogType::Open && options.multi_selection);
+    dialog.set_show_hidden(options.show_hidden);
+    if ty == FileDialogType::Save {
+        dialog.set_do_overwrite_confirmation(true);
+        if let Some(ref name) = options.default_name {
+            dialog.set_current_name(name);
+        }
+    }
+    if let Some(ref dir) = options.starting_directory {
+        dialog.set_current_folder(dir);
+    }
+    if ty != FileDialogType::Directory {
+        for spec in &options.allowed_types {
+            let filter = FileFilter::new();
+            FileFilterExt::set_name(&filter, spec.name);
+            for ext in spec.extensions {
+                filter.add_pattern(&format!("*.{}", ext));
+            }
+            dialog.add_filter(&filter);
+        }
+    }
+
+    let response = ResponseType::from(dialog.run());
+    let mut paths = dialog.get_filenames();
+    dialog.destroy();
+    if response != ResponseType::Accept {
+        return None;
+    }
+    if ty == FileDialogType::Save {
+        let default_ext = options
+            .allowed_types
+            .first()
+            .and_then(|spec| spec.extensions.first());
+        if let Some(ext) = default_ext {
+            for path in &mut paths {
+                if path.extension().is_none() {
+                    path.set_extension(ext);
+                }
+            }
+        }
+    }
+    Some(paths)
+}
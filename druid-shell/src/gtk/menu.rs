@@ -0,0 +1,207 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GTK implementation of menus.
+//!
+//! GTK menu items are connected to the window they are shown in, so a
+//! `Menu` is only a description until it is attached to a window.
+
+use std::sync::Weak;
+
+use gdk::ModifierType;
+use gtk_rs::prelude::*;
+use gtk_rs::{AccelLabel, CheckMenuItem, MenuBar, MenuItem as GtkMenuItem, SeparatorMenuItem};
+
+use super::WindowState;
+use crate::keycodes::{KeySpec, MenuKey, M_ALT, M_CTRL, M_META, M_SHIFT};
+
+/// A menu object, which can be either a top-level menubar or a
+/// submenu.
+#[derive(Default)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+}
+
+enum MenuItem {
+    Entry {
+        id: u32,
+        text: String,
+        key: MenuKey,
+        enabled: bool,
+        checked: bool,
+    },
+    Separator,
+    SubMenu(String, Menu),
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu { items: Vec::new() }
+    }
+
+    /// Create a menu to be shown with `WindowHandle::show_context_menu`.
+    pub fn new_for_popup() -> Menu {
+        Menu::new()
+    }
+
+    /// Add a dropdown menu. The text may mark an access key with `&`.
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str) {
+        self.items.push(MenuItem::SubMenu(text.to_string(), menu));
+    }
+
+    /// Add an item to the menu.
+    ///
+    /// The key is displayed next to the item text; it is not registered as an
+    /// accelerator, so the key events should be handled (for example through
+    /// a keymap) by the application.
+    pub fn add_item(&mut self, id: u32, text: &str, key: impl Into<MenuKey>) {
+        self.add_item_with_state(id, text, key, true, false);
+    }
+
+    /// Add an item to the menu, which can be disabled or show a checkmark.
+    pub fn add_item_with_state(
+        &mut self,
+        id: u32,
+        text: &str,
+        key: impl Into<MenuKey>,
+        enabled: bool,
+        checked: bool,
+    ) {
+        self.items.push(MenuItem::Entry {
+            id,
+            text: text.to_string(),
+            key: key.into(),
+            enabled,
+            checked,
+        });
+    }
+
+    /// Add a separator to the menu.
+    pub fn add_separator(&mut self) {
+        self.items.push(MenuItem::Separator);
+    }
+
+    /// Build a menubar whose items send their commands to the handler of
+    /// `state`.
+    pub(crate) fn into_gtk_menubar(self, state: &Weak<WindowState>) -> MenuBar {
+        let menubar = MenuBar::new();
+        self.append_items_to(&menubar, state);
+        menubar
+    }
+
+    pub(crate) fn into_gtk_menu(self, state: &Weak<WindowState>) -> gtk_rs::Menu {
+        let menu = gtk_rs::Menu::new();
+        self.append_items_to(&menu, state);
+        menu
+    }
+
+    fn append_items_to<M>(self, shell: &M, state: &Weak<WindowState>)
+    where
+        M: IsA<gtk_rs::MenuShell> + IsA<glib::Object> + glib::ObjectExt,
+    {
+        for item in self.items {
+            match item {
+                MenuItem::Entry {
+                    id,
+                    text,
+                    key,
+                    enabled,
+                    checked,
+                } => {
+                    let mnemonic = make_mnemonic(&text);
+                    let item: GtkMenuItem = if checked {
+                        let item = CheckMenuItem::new_with_mnemonic(&mnemonic);
+                        item.set_active(true);
+                        item.upcast()
+                    } else {
+                        GtkMenuItem::new_with_mnemonic(&mnemonic)
+                    };
+                    item.set_sensitive(enabled);
+                    if let Some((keyval, mods)) = make_accelerator(&key) {
+                        let label = item
+                            .get_child()
+                            .and_then(|c| c.downcast::<AccelLabel>().ok());
+                        if let Some(label) = label {
+                            label.set_accel(keyval, mods);
+                        }
+                    }
+                    let state = state.clone();
+                    item.connect_activate(move |_| {
+                        if let Some(state) = state.upgrade() {
+                            state.handler.command(id);
+                        }
+                    });
+                    shell.append(&item);
+                }
+                MenuItem::Separator => shell.append(&SeparatorMenuItem::new()),
+                MenuItem::SubMenu(text, menu) => {
+                    let item = GtkMenuItem::new_with_mnemonic(&make_mnemonic(&text));
+                    item.set_submenu(&menu.into_gtk_menu(state));
+                    shell.append(&item);
+                }
+            }
+        }
+    }
+}
+
+/// Convert the access key syntax of menu text to a GTK mnemonic.
+///
+/// Changes "E&xit" to "E_xit". Actual ampersands are escaped as "&&", and
+/// underscores are escaped for GTK.
+fn make_mnemonic(raw_menu_text: &str) -> String {
+    let mut saw_ampersand = false;
+    let mut result = String::new();
+    for c in raw_menu_text.chars() {
+        match c {
+            '&' if saw_ampersand => {
+                result.push('&');
+                saw_ampersand = false;
+            }
+            '&' => saw_ampersand = true,
+            _ => {
+                if saw_ampersand {
+                    result.push('_');
+                    saw_ampersand = false;
+                }
+                if c == '_' {
+                    result.push('_');
+                }
+                result.push(c);
+            }
+        }
+    }
+    result
+}
+
+/// The key and modifiers for displaying a menu key.
+fn make_accelerator(key: &MenuKey) -> Option<(u32, ModifierType)> {
+    let keyval = match key.key {
+        KeySpec::Char(c) => gdk::unicode_to_keyval(c as u32),
+        KeySpec::None => return None,
+    };
+    let mut mods = ModifierType::empty();
+    if key.modifiers & M_SHIFT != 0 {
+        mods |= ModifierType::SHIFT_MASK;
+    }
+    if key.modifiers & M_ALT != 0 {
+        mods |= ModifierType::MOD1_MASK;
+    }
+    if key.modifiers & M_CTRL != 0 {
+        mods |= ModifierType::CONTROL_MASK;
+    }
+    if key.modifiers & M_META != 0 {
+        mods |= ModifierType::SUPER_MASK;
+    }
+    Some((keyval, mods))
+}
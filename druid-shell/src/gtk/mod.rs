@@ -0,0 +1,1769 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GTK implementation of window creation.
+
+mod accessibility;
+pub mod application;
+pub mod clipboard;
+mod dialog;
+pub mod menu;
+pub mod native_view;
+pub mod surface;
+pub mod util;
+pub mod win_main;
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::collections::HashSet;
+use std::ffi::{c_void, OsString};
+use std::mem;
+use std::ptr;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
+
+use gdk::{
+    DisplayExt, DragContextExt, EventMask, EventType, ModifierType, MonitorExt, ScrollDirection,
+    WindowExt as GdkWindowExt, WindowTypeHint,
+};
+use gdk_pixbuf::{Colorspace, Pixbuf};
+use glib::translate::{from_glib_full, ToGlibPtr};
+use glib::{Continue, ObjectExt};
+use gtk_rs::prelude::*;
+use gtk_rs::{
+    ButtonsType, DialogFlags, DrawingArea, IMMulticontext, Inhibit, MessageDialog, MessageType,
+    Orientation, ResponseType, StatusIcon, TargetList, WindowPosition, WindowType,
+};
+
+use piet_common::kurbo::Rect;
+use piet_common::{Piet, RenderContext};
+
+use crate::accessibility::{AccessTree, Politeness};
+use crate::alert::{AlertOptions, AlertResponse, AlertStyle};
+use crate::blend::Painting;
+use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::drag::{DragData, DropEffect};
+use crate::keyboard::{Key, KeyCode, KeyEvent, KeyModifiers, RawKeyCode};
+use crate::keycodes::MenuKey;
+use crate::timer::{TimerQueue, TimerToken, COALESCE_SLACK};
+use crate::window::{
+    self, CompositionEvent, Cursor, CursorDesc, IconDesc, MouseButton, MouseEvent, PowerSource,
+    RawDisplayHandle, RawWindowHandle, Theme, WheelEvent, WheelPhase, WheelUnit, WinHandler,
+    WindowBackdrop, WindowGeometry, WindowKind, WindowLevel,
+};
+use crate::Error;
+use application::Application;
+use dialog::get_file_dialog_paths;
+pub use menu::Menu;
+
+/// The target info of the file URIs of a drag. Other targets have the index
+/// of their format.
+const DRAG_FILES_INFO: u32 = std::u32::MAX;
+
+// GDK's X11 backend, for the native handles of windows.
+extern "C" {
+    fn gdk_x11_window_get_xid(window: *mut c_void) -> u64;
+    fn gdk_x11_display_get_xdisplay(display: *mut c_void) -> *mut c_void;
+    fn gdk_x11_window_foreign_new_for_display(display: *mut c_void, window: u64) -> *mut c_void;
+}
+
+#[derive(Clone, Default)]
+pub struct WindowHandle {
+    state: Weak<WindowState>,
+}
+
+/// Builder abstraction for creating new windows.
+pub struct WindowBuilder {
+    handler: Option<Box<dyn WinHandler>>,
+    title: String,
+    enable_mouse_move_events: bool,
+    menu: Option<Menu>,
+    kind: WindowKind,
+    level: WindowLevel,
+    owner: Option<WindowHandle>,
+    position: Option<(f64, f64)>,
+    centered: bool,
+    size: Option<(f64, f64)>,
+    icons: Vec<IconDesc>,
+    parent: Option<RawWindowHandle>,
+}
+
+/// A cursor made from an image.
+#[derive(Clone)]
+pub struct CustomCursor(gdk::Cursor);
+
+#[derive(Clone)]
+pub struct IdleHandle {
+    state: Weak<WindowState>,
+    idle_queue: Weak<Mutex<Vec<Box<dyn IdleCallback>>>>,
+}
+
+// TODO: move this out of platform-dependent section.
+trait IdleCallback: Send {
+    fn call(self: Box<Self>, a: &dyn Any);
+}
+
+impl<F: FnOnce(&dyn Any) + Send> IdleCallback for F {
+    fn call(self: Box<F>, a: &dyn Any) {
+        (*self)(a)
+    }
+}
+
+/// The state of a window, shared by its signal handlers.
+///
+/// The signal handlers only hold weak references; the strong reference is
+/// owned by the `destroy` handler, so the state lives as long as the window.
+/// It is only ever touched on the main thread.
+pub(crate) struct WindowState {
+    window: gtk_rs::Window,
+    vbox: gtk_rs::Box,
+    drawing_area: DrawingArea,
+    menubar: RefCell<Option<gtk_rs::MenuBar>>,
+    pub(crate) handler: Box<dyn WinHandler>,
+    idle_queue: Arc<Mutex<Vec<Box<dyn IdleCallback>>>>,
+    im_context: IMMulticontext,
+    /// Whether a key press is being passed through the input method.
+    filtering_key: Cell<bool>,
+    /// Text committed by the input method while filtering a key press.
+    committed: RefCell<String>,
+    /// Whether the input method is composing text.
+    composing: Cell<bool>,
+    /// Whether the composition is being discarded, so that the text the
+    /// input method commits meanwhile is dropped.
+    cancelling: Cell<bool>,
+    /// Hardware keycodes of the keys held down, to detect repeats.
+    keys_down: RefCell<HashSet<u16>>,
+    last_click: Cell<Option<Click>>,
+    window_state: Cell<window::WindowState>,
+    /// The frame of the window the last time it was neither maximized,
+    /// minimized nor fullscreen.
+    restored_frame: Cell<Rect>,
+    context_menu: RefCell<Option<gtk_rs::Menu>>,
+    tray_icon: RefCell<Option<(StatusIcon, Option<gtk_rs::Menu>)>>,
+    /// Whether a tick callback for `WinHandler::frame` is installed.
+    frame_requested: Cell<bool>,
+    /// The data of the drag started with `start_drag`, until it ends.
+    drag_data: RefCell<Option<DragData>>,
+    /// Whether the current drag failed, to report no effect when it ends.
+    drag_failed: Cell<bool>,
+    timers: RefCell<TimerQueue<Instant>>,
+    /// The timeout due at the earliest deadline of the timers.
+    timer_source: RefCell<Option<glib::SourceId>>,
+    /// The settings last reported to the handler.
+    theme: Cell<Theme>,
+    power_source: Cell<PowerSource>,
+    text_scale: Cell<f64>,
+    /// The handlers of setting changes, disconnected when the window is
+    /// destroyed.
+    settings_handlers: RefCell<Vec<glib::SignalHandlerId>>,
+    power_subscription: RefCell<Option<application::PowerSubscription>>,
+    access: RefCell<accessibility::AccessState>,
+}
+
+/// A button press, for counting multiple clicks.
+#[derive(Clone, Copy)]
+struct Click {
+    button: u32,
+    time: u32,
+    x: f64,
+    y: f64,
+    count: u32,
+}
+
+impl WindowBuilder {
+    pub fn new() -> WindowBuilder {
+        WindowBuilder {
+            handler: None,
+            title: String::new(),
+            enable_mouse_move_events: true,
+            menu: None,
+            kind: WindowKind::Normal,
+            level: WindowLevel::Normal,
+            owner: None,
+            position: None,
+            centered: false,
+            size: None,
+            icons: Vec::new(),
+            parent: None,
+        }
+    }
+
+    pub fn set_handler(&mut self, handler: Box<dyn WinHandler>) {
+        self.handler = Some(handler);
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    pub fn set_menu(&mut self, menu: Menu) {
+        self.menu = Some(menu);
+    }
+
+    pub fn set_enable_mouse_move_events(&mut self, to: bool) {
+        self.enable_mouse_move_events = to;
+    }
+
+    pub fn set_kind(&mut self, kind: WindowKind) {
+        self.kind = kind;
+    }
+
+    /// Set the stacking level of the window. A tooltip level window is
+    /// created as a popup without decorations; this can't be changed later.
+    pub fn set_level(&mut self, level: WindowLevel) {
+        self.level = level;
+    }
+
+    /// Set the window that owns this one. An owned window is kept above its
+    /// owner, and is destroyed along with it.
+    pub fn set_owner(&mut self, owner: &WindowHandle) {
+        self.owner = Some(owner.clone());
+    }
+
+    /// Set the initial position of the window, in px. The position is
+    /// relative to the top left corner of the owner window if there is one,
+    /// otherwise to the screen.
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.position = Some((x, y));
+    }
+
+    /// Center the window on its owner if there is one, otherwise on the
+    /// screen. This takes precedence over `set_position`.
+    pub fn set_centered(&mut self) {
+        self.centered = true;
+    }
+
+    /// Set the initial size of the window, in px.
+    pub fn set_size(&mut self, width: f64, height: f64) {
+        self.size = Some((width, height));
+    }
+
+    /// GTK always paints in step with the frame clock of the window, so
+    /// this has no effect.
+    pub fn set_vsync(&mut self, _vsync: bool) {}
+
+    /// GTK has no backdrops, so the window stays opaque.
+    pub fn set_backdrop(&mut self, _backdrop: WindowBackdrop) {}
+
+    /// Set the icon of the window, given in several resolutions. See
+    /// `WindowHandle::set_icon`.
+    pub fn set_icon(&mut self, icons: Vec<IconDesc>) {
+        self.icons = icons;
+    }
+
+    /// Build the window inside an X11 window of the host application,
+    /// rather than as a top-level window, as for a plugin UI.
+    ///
+    /// The window fills the parent unless given a size, and the host
+    /// resizes it with `set_size` as the parent changes. The position is
+    /// relative to the parent. This needs GTK to run on X11; the window
+    /// manager leaves embedded windows alone, so maximizing, minimizing and
+    /// fullscreen have no effect.
+    ///
+    /// # Safety
+    ///
+    /// `parent` must be the `Xlib` handle of a window that outlives this
+    /// one, or until it is closed.
+    pub unsafe fn set_parent(&mut self, parent: RawWindowHandle) {
+        self.parent = Some(parent);
+    }
+
+    pub fn build(self) -> Result<WindowHandle, Error> {
+        let foreign = match self.parent {
+            Some(parent) => Some(foreign_window(parent)?),
+            None => None,
+        };
+        let handler = self.handler.expect("handler");
+        let window = match self.level {
+            WindowLevel::Tooltip => gtk_rs::Window::new(WindowType::Popup),
+            _ => gtk_rs::Window::new(WindowType::Toplevel),
+        };
+        window.set_title(&self.title);
+        window.set_icon_list(&make_pixbufs(&self.icons));
+        let (width, height) = match (self.size, &foreign) {
+            (None, Some(foreign)) => (
+                f64::from(foreign.get_width()),
+                f64::from(foreign.get_height()),
+            ),
+            (size, _) => size.unwrap_or((500.0, 400.0)),
+        };
+        window.set_default_size(width as i32, height as i32);
+        match (self.kind, self.level) {
+            (_, WindowLevel::Tooltip) => {
+                window.set_type_hint(WindowTypeHint::Tooltip);
+                window.set_accept_focus(false);
+            }
+            (WindowKind::Palette, _) => {
+                window.set_type_hint(WindowTypeHint::Utility);
+                window.set_accept_focus(false);
+            }
+            (WindowKind::Normal, _) => (),
+        }
+        window.set_keep_above(self.level != WindowLevel::Normal);
+        let owner = self.owner.as_ref().and_then(|owner| owner.state.upgrade());
+        if let Some(ref owner) = owner {
+            window.set_transient_for(&owner.window);
+            window.set_destroy_with_parent(true);
+        }
+
+        let vbox = gtk_rs::Box::new(Orientation::Vertical, 0);
+        window.add(&vbox);
+        let drawing_area = accessibility::new_drawing_area();
+        drawing_area.set_can_focus(true);
+        let mut events = EventMask::BUTTON_PRESS_MASK
+            | EventMask::BUTTON_RELEASE_MASK
+            | EventMask::BUTTON_MOTION_MASK
+            | EventMask::KEY_PRESS_MASK
+            | EventMask::KEY_RELEASE_MASK
+            | EventMask::SCROLL_MASK
+            | EventMask::SMOOTH_SCROLL_MASK
+            | EventMask::FOCUS_CHANGE_MASK;
+        if self.enable_mouse_move_events {
+            events |= EventMask::POINTER_MOTION_MASK;
+        }
+        drawing_area.add_events(events.bits() as i32);
+        vbox.pack_end(&drawing_area, true, true, 0);
+
+        let state = Arc::new(WindowState {
+            window,
+            vbox,
+            drawing_area,
+            menubar: RefCell::new(None),
+            handler,
+            idle_queue: Arc::new(Mutex::new(Vec::new())),
+            im_context: IMMulticontext::new(),
+            filtering_key: Cell::new(false),
+            committed: RefCell::new(String::new()),
+            composing: Cell::new(false),
+            cancelling: Cell::new(false),
+            keys_down: RefCell::new(HashSet::new()),
+            last_click: Cell::new(None),
+            window_state: Cell::new(window::WindowState::Restored),
+            restored_frame: Cell::new(Rect::default()),
+            context_menu: RefCell::new(None),
+            tray_icon: RefCell::new(None),
+            frame_requested: Cell::new(false),
+            drag_data: RefCell::new(None),
+            drag_failed: Cell::new(false),
+            timers: RefCell::new(TimerQueue::new()),
+            timer_source: RefCell::new(None),
+            theme: Cell::new(Application::theme()),
+            power_source: Cell::new(Application::power_source()),
+            text_scale: Cell::new(Application::text_scale()),
+            settings_handlers: RefCell::new(Vec::new()),
+            power_subscription: RefCell::new(None),
+            access: Default::default(),
+        });
+        accessibility::attach(&state);
+        let handle = WindowHandle {
+            state: Arc::downgrade(&state),
+        };
+        if let Some(menu) = self.menu {
+            handle.set_menu(menu);
+        }
+        connect_signals(&state);
+        state.drawing_area.grab_focus();
+
+        if let Some(ref foreign) = foreign {
+            state.window.set_decorated(false);
+            state.window.realize();
+            if let Some(gdk_window) = state.window.get_window() {
+                let (x, y) = self.position.unwrap_or((0.0, 0.0));
+                gdk_window.reparent(foreign, x as i32, y as i32);
+            }
+        } else if self.centered {
+            match owner {
+                Some(_) => state.window.set_position(WindowPosition::CenterOnParent),
+                None => state.window.set_position(WindowPosition::Center),
+            }
+        } else if let Some((x, y)) = self.position {
+            let (left, top) = match owner {
+                Some(ref owner) => owner.window.get_position(),
+                None => (0, 0),
+            };
+            state.window.move_(left + x as i32, top + y as i32);
+        }
+
+        state.handler.connect(&crate::window::WindowHandle {
+            inner: handle.clone(),
+        });
+        let owned = state.clone();
+        state.window.connect_destroy(move |_| {
+            if let Some(settings) = gtk_rs::Settings::get_default() {
+                for handler in owned.settings_handlers.borrow_mut().drain(..) {
+                    settings.disconnect(handler);
+                }
+            }
+            owned.power_subscription.borrow_mut().take();
+            accessibility::detach_window(&owned);
+            owned.handler.destroy();
+        });
+        Ok(handle)
+    }
+}
+
+/// The GDK window of an X11 window of the host application, or of a view
+/// to host.
+fn foreign_window(handle: RawWindowHandle) -> Result<gdk::Window, Error> {
+    let xid = match handle {
+        RawWindowHandle::Xlib { window, .. } if window != 0 => window,
+        _ => return Err(Error::Other("the handle must be of an X11 window")),
+    };
+    let display = gdk::Display::get_default().ok_or(Error::Null)?;
+    if display.get_type().name() != "GdkX11Display" {
+        return Err(Error::Other("embedding needs GTK to run on X11"));
+    }
+    unsafe {
+        let display_ptr: *mut gdk_sys::GdkDisplay = display.to_glib_none().0;
+        let window = gdk_x11_window_foreign_new_for_display(display_ptr as *mut c_void, xid);
+        if window.is_null() {
+            return Err(Error::Other("the X11 window does not exist"));
+        }
+        Ok(from_glib_full(window as *mut gdk_sys::GdkWindow))
+    }
+}
+
+fn connect_signals(state: &Arc<WindowState>) {
+    if let Some(settings) = gtk_rs::Settings::get_default() {
+        let mut handlers = state.settings_handlers.borrow_mut();
+        let weak = Arc::downgrade(state);
+        handlers.push(settings.connect_property_gtk_theme_name_notify(move |_| {
+            if let Some(state) = weak.upgrade() {
+                state.update_theme();
+            }
+        }));
+        let weak = Arc::downgrade(state);
+        handlers.push(
+            settings.connect_property_gtk_application_prefer_dark_theme_notify(move |_| {
+                if let Some(state) = weak.upgrade() {
+                    state.update_theme();
+                }
+            }),
+        );
+        let weak = Arc::downgrade(state);
+        handlers.push(settings.connect_property_gtk_xft_dpi_notify(move |_| {
+            if let Some(state) = weak.upgrade() {
+                let scale = Application::text_scale();
+                if state.text_scale.replace(scale) != scale {
+                    state.handler.text_scale_changed(scale);
+                }
+            }
+        }));
+    }
+
+    let weak = Arc::downgrade(state);
+    let subscription = application::PowerSubscription::new(Box::new(move || {
+        if let Some(state) = weak.upgrade() {
+            let source = Application::power_source();
+            if state.power_source.replace(source) != source {
+                state.handler.power_source_changed(source);
+            }
+        }
+    }));
+    *state.power_subscription.borrow_mut() = subscription.ok();
+
+    let weak = Arc::downgrade(state);
+    state.window.connect_delete_event(move |_, _| {
+        let allow = match weak.upgrade() {
+            Some(state) => state.handler.request_close(),
+            None => true,
+        };
+        Inhibit(!allow)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.window.connect_window_state_event(move |_, event| {
+        if let Some(state) = weak.upgrade() {
+            let new = event.get_new_window_state();
+            let window_state = if new.contains(gdk::WindowState::FULLSCREEN) {
+                window::WindowState::Fullscreen
+            } else if new.contains(gdk::WindowState::ICONIFIED) {
+                window::WindowState::Minimized
+            } else if new.contains(gdk::WindowState::MAXIMIZED) {
+                window::WindowState::Maximized
+            } else {
+                window::WindowState::Restored
+            };
+            if state.window_state.replace(window_state) != window_state {
+                state.handler.window_state_changed(window_state);
+            }
+        }
+        Inhibit(false)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.window.connect_configure_event(move |window, _| {
+        if let Some(state) = weak.upgrade() {
+            if state.window_state.get() == window::WindowState::Restored {
+                let (x, y) = window.get_position();
+                let (width, height) = window.get_size();
+                let frame = Rect::new(x.into(), y.into(), (x + width).into(), (y + height).into());
+                state.restored_frame.set(frame);
+            }
+        }
+        false
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_draw(move |widget, context| {
+        if let Some(state) = weak.upgrade() {
+            let mut context = context.clone();
+            let _painting = Painting::new(Some(&context));
+            let mut piet_ctx = Piet::new(&mut context);
+            let anim = state.handler.paint(&mut piet_ctx);
+            if let Err(e) = piet_ctx.finish() {
+                eprintln!("Error: {}", e);
+            }
+            if anim {
+                widget.queue_draw();
+            }
+        }
+        Inhibit(false)
+    });
+
+    let weak = Arc::downgrade(state);
+    state
+        .drawing_area
+        .connect_size_allocate(move |_, allocation| {
+            if let Some(state) = weak.upgrade() {
+                state
+                    .handler
+                    .size(allocation.width as u32, allocation.height as u32);
+            }
+        });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_realize(move |widget| {
+        if let Some(state) = weak.upgrade() {
+            state
+                .im_context
+                .set_client_window(widget.get_window().as_ref());
+        }
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_focus_in_event(move |_, _| {
+        if let Some(state) = weak.upgrade() {
+            state.im_context.focus_in();
+        }
+        Inhibit(false)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_focus_out_event(move |_, _| {
+        if let Some(state) = weak.upgrade() {
+            state.im_context.focus_out();
+            state.keys_down.borrow_mut().clear();
+        }
+        Inhibit(false)
+    });
+
+    let weak = Arc::downgrade(state);
+    state
+        .drawing_area
+        .connect_button_press_event(move |widget, event| {
+            // GTK follows a second press with a `DoubleButtonPress`; clicks are
+            // counted here instead.
+            if event.get_event_type() != EventType::ButtonPress {
+                return Inhibit(true);
+            }
+            if let (Some(state), Some(button)) =
+                (weak.upgrade(), get_mouse_button(event.get_button()))
+            {
+                widget.grab_focus();
+                let (x, y) = event.get_position();
+                let count = state.count_click(event);
+                let event = MouseEvent {
+                    x: x as i32,
+                    y: y as i32,
+                    mods: make_modifiers(event.get_state()),
+                    count,
+                    button,
+                };
+                state.handler.mouse(&event);
+            }
+            Inhibit(true)
+        });
+
+    let weak = Arc::downgrade(state);
+    state
+        .drawing_area
+        .connect_button_release_event(move |_, event| {
+            if let (Some(state), Some(button)) =
+                (weak.upgrade(), get_mouse_button(event.get_button()))
+            {
+                let (x, y) = event.get_position();
+                let event = MouseEvent {
+                    x: x as i32,
+                    y: y as i32,
+                    mods: make_modifiers(event.get_state()),
+                    count: 0,
+                    button,
+                };
+                state.handler.mouse(&event);
+            }
+            Inhibit(true)
+        });
+
+    let weak = Arc::downgrade(state);
+    state
+        .drawing_area
+        .connect_motion_notify_event(move |_, event| {
+            if let Some(state) = weak.upgrade() {
+                let (x, y) = event.get_position();
+                let mods = event.get_state();
+                let event = MouseEvent {
+                    x: x as i32,
+                    y: y as i32,
+                    mods: make_modifiers(mods),
+                    count: 0,
+                    button: get_pressed_button(mods),
+                };
+                state.handler.mouse_move(&event);
+            }
+            Inhibit(true)
+        });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_scroll_event(move |_, event| {
+        if let Some(state) = weak.upgrade() {
+            // Deltas are in scroll steps, positive down and right.
+            let (dx, dy) = match event.get_direction() {
+                ScrollDirection::Up => (0.0, -1.0),
+                ScrollDirection::Down => (0.0, 1.0),
+                ScrollDirection::Left => (-1.0, 0.0),
+                ScrollDirection::Right => (1.0, 0.0),
+                ScrollDirection::Smooth => event.get_delta(),
+                _ => return Inhibit(false),
+            };
+            let phase = if event.is_scroll_stop_event() {
+                WheelPhase::Ended
+            } else {
+                WheelPhase::None
+            };
+            let event = WheelEvent {
+                dx,
+                dy,
+                unit: WheelUnit::Lines,
+                mods: make_modifiers(event.get_state()),
+                phase,
+            };
+            state.handler.wheel(&event);
+        }
+        Inhibit(true)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_key_press_event(move |_, key| {
+        let state = match weak.upgrade() {
+            Some(state) => state,
+            None => return Inhibit(false),
+        };
+        let repeat = !state
+            .keys_down
+            .borrow_mut()
+            .insert(key.get_hardware_keycode());
+        let was_composing = state.composing.get();
+        state.filtering_key.set(true);
+        let consumed = state.im_context.filter_keypress(key);
+        state.filtering_key.set(false);
+        let committed = mem::replace(&mut *state.committed.borrow_mut(), String::new());
+        if !consumed {
+            return Inhibit(state.handler.key_down(make_key_event(key, repeat, None)));
+        }
+        let mut chars = committed.chars();
+        match (chars.next(), chars.next()) {
+            // Reported as composition events.
+            (None, _) if was_composing || state.composing.get() => (),
+            (Some(c), None) => {
+                state.handler.key_down(make_key_event(key, repeat, Some(c)));
+            }
+            (None, _) => {
+                // A dead key, or a key composing text in the input method.
+                let mut event = make_key_event(key, repeat, None);
+                if event.code.is_printable() {
+                    event.key = Key::Dead;
+                }
+                state.handler.key_down(event);
+            }
+            (Some(_), Some(_)) => state.commit_text(&committed),
+        }
+        Inhibit(true)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_key_release_event(move |_, key| {
+        if let Some(state) = weak.upgrade() {
+            state.im_context.filter_keypress(key);
+            state
+                .keys_down
+                .borrow_mut()
+                .remove(&key.get_hardware_keycode());
+            state.handler.key_up(make_key_event(key, false, None));
+        }
+        Inhibit(true)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.im_context.connect_commit(move |_, text| {
+        if let Some(state) = weak.upgrade() {
+            if state.cancelling.get() {
+                return;
+            }
+            if state.composing.replace(false) {
+                let event = CompositionEvent::Commit(text.to_owned());
+                state.handler.composition(&event);
+            } else if state.filtering_key.get() {
+                state.committed.borrow_mut().push_str(text);
+            } else {
+                state.commit_text(text);
+            }
+        }
+    });
+
+    let weak = Arc::downgrade(state);
+    state.im_context.connect_preedit_changed(move |im_context| {
+        if let Some(state) = weak.upgrade() {
+            let (text, _, cursor) = im_context.get_preedit_string();
+            if !state.composing.get() {
+                if text.is_empty() {
+                    return;
+                }
+                state.composing.set(true);
+                state.handler.composition(&CompositionEvent::Start);
+            }
+            // The cursor is in characters.
+            let cursor = text
+                .char_indices()
+                .nth(cursor.max(0) as usize)
+                .map_or(text.len(), |(i, _)| i);
+            let event = CompositionEvent::Update { text, cursor };
+            state.handler.composition(&event);
+        }
+    });
+
+    let weak = Arc::downgrade(state);
+    state.im_context.connect_preedit_end(move |_| {
+        if let Some(state) = weak.upgrade() {
+            // Still composing if the composition was cancelled rather than
+            // committed.
+            if state.composing.replace(false) {
+                let event = CompositionEvent::Commit(String::new());
+                state.handler.composition(&event);
+            }
+        }
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_drag_begin(move |_, context| {
+        if let Some(state) = weak.upgrade() {
+            state.drag_failed.set(false);
+            if let Some(ref data) = *state.drag_data.borrow() {
+                if let Some(ref image) = data.image {
+                    let desc = &image.image;
+                    let pixbuf = make_pixbuf(&desc.image, desc.width, desc.height);
+                    context.drag_set_icon_pixbuf(&pixbuf, image.x as i32, image.y as i32);
+                }
+            }
+        }
+    });
+
+    let weak = Arc::downgrade(state);
+    state
+        .drawing_area
+        .connect_drag_data_get(move |_, _, selection, info, _| {
+            if let Some(state) = weak.upgrade() {
+                if let Some(ref data) = *state.drag_data.borrow() {
+                    if info == DRAG_FILES_INFO {
+                        let uris: Vec<String> = data
+                            .files
+                            .iter()
+                            .filter_map(|path| glib::filename_to_uri(path, None).ok())
+                            .collect();
+                        let uris: Vec<&str> = uris.iter().map(String::as_str).collect();
+                        selection.set_uris(&uris);
+                    } else if let Some(format) = data.formats.get(info as usize) {
+                        clipboard::set_selection_data(selection, format);
+                    }
+                }
+            }
+        });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_drag_failed(move |_, _, _| {
+        if let Some(state) = weak.upgrade() {
+            state.drag_failed.set(true);
+        }
+        Inhibit(false)
+    });
+
+    let weak = Arc::downgrade(state);
+    state.drawing_area.connect_drag_end(move |_, context| {
+        if let Some(state) = weak.upgrade() {
+            state.drag_data.borrow_mut().take();
+            let action = context.get_selected_action();
+            let effect = if state.drag_failed.get() {
+                DropEffect::None
+            } else if action.contains(gdk::DragAction::MOVE) {
+                DropEffect::Move
+            } else {
+                DropEffect::Copy
+            };
+            state.handler.drag_ended(effect);
+        }
+    });
+}
+
+impl WindowState {
+    /// Report a change of the theme, if there was one.
+    fn update_theme(&self) {
+        let theme = Application::theme();
+        if self.theme.replace(theme) != theme {
+            self.handler.theme_changed(theme);
+        }
+    }
+
+    /// Deliver text committed by the input method, other than for a single
+    /// key press, as key events carrying one character each.
+    fn commit_text(&self, text: &str) {
+        for c in text.chars() {
+            let code = KeyCode::Unknown(RawKeyCode::Linux(0));
+            let event = KeyEvent::new(code, false, KeyModifiers::default(), c, c);
+            self.handler.key_down(event);
+        }
+    }
+
+    /// The click count of a button press, following the system settings
+    /// for double clicks.
+    fn count_click(&self, event: &gdk::EventButton) -> u32 {
+        let (time, distance) = match gtk_rs::Settings::get_default() {
+            Some(settings) => (
+                settings.get_property_gtk_double_click_time() as u32,
+                f64::from(settings.get_property_gtk_double_click_distance()),
+            ),
+            None => (400, 5.0),
+        };
+        let (x, y) = event.get_position();
+        let count = match self.last_click.get() {
+            Some(last)
+                if last.button == event.get_button()
+                    && event.get_time().wrapping_sub(last.time) <= time
+                    && (x - last.x).abs() <= distance
+                    && (y - last.y).abs() <= distance =>
+            {
+                last.count + 1
+            }
+            _ => 1,
+        };
+        self.last_click.set(Some(Click {
+            button: event.get_button(),
+            time: event.get_time(),
+            x,
+            y,
+            count,
+        }));
+        count
+    }
+
+    /// The work area of the monitor showing the window, or of the monitor
+    /// at a point, in px.
+    fn monitor_workarea(&self, point: Option<(i32, i32)>) -> Option<Rect> {
+        let display = gdk::Display::get_default()?;
+        let monitor = match point {
+            Some((x, y)) => display.get_monitor_at_point(x, y),
+            None => display.get_monitor_at_window(&self.window.get_window()?),
+        }?;
+        Some(from_gdk_rect(monitor.get_workarea()))
+    }
+
+    /// The frame of the window, in px, in screen coordinates.
+    fn frame(&self) -> Rect {
+        let (x, y) = self.window.get_position();
+        let (width, height) = self.window.get_size();
+        Rect::new(x.into(), y.into(), (x + width).into(), (y + height).into())
+    }
+}
+
+fn from_gdk_rect(rect: gdk::Rectangle) -> Rect {
+    Rect::new(
+        rect.x.into(),
+        rect.y.into(),
+        (rect.x + rect.width).into(),
+        (rect.y + rect.height).into(),
+    )
+}
+
+fn get_mouse_button(button: u32) -> Option<MouseButton> {
+    match button {
+        1 => Some(MouseButton::Left),
+        2 => Some(MouseButton::Middle),
+        3 => Some(MouseButton::Right),
+        8 => Some(MouseButton::X1),
+        9 => Some(MouseButton::X2),
+        _ => None,
+    }
+}
+
+fn get_pressed_button(mods: ModifierType) -> MouseButton {
+    //TODO: this doesn't correctly handle multiple buttons being pressed.
+    if mods.contains(ModifierType::BUTTON3_MASK) {
+        MouseButton::Right
+    } else if mods.contains(ModifierType::BUTTON2_MASK) {
+        MouseButton::Middle
+    } else {
+        MouseButton::Left
+    }
+}
+
+fn make_modifiers(raw: ModifierType) -> KeyModifiers {
+    KeyModifiers {
+        shift: raw.contains(ModifierType::SHIFT_MASK),
+        alt: raw.contains(ModifierType::MOD1_MASK),
+        ctrl: raw.contains(ModifierType::CONTROL_MASK),
+        meta: raw.intersects(ModifierType::SUPER_MASK | ModifierType::META_MASK),
+    }
+}
+
+/// Make a key event, with text committed by the input method if there was
+/// any.
+fn make_key_event(key: &gdk::EventKey, is_repeat: bool, text: Option<char>) -> KeyEvent {
+    let keycode = key.get_hardware_keycode();
+    let code = KeyCode::from_hardware_keycode(keycode)
+        .unwrap_or_else(|| KeyCode::Unknown(RawKeyCode::Linux(keycode)));
+    let modifiers = make_modifiers(key.get_state());
+    // The keyval already has shift applied.
+    let unmodified = gdk::keyval_to_unicode(key.get_keyval()).filter(|c| !c.is_control());
+    let text = match text {
+        Some(c) => Some(c),
+        None if modifiers.ctrl || modifiers.alt || modifiers.meta => None,
+        None => unmodified,
+    };
+    KeyEvent::new(code, is_repeat, modifiers, text, unmodified)
+}
+
+/// Make a pixbuf from an RGBA image.
+fn make_pixbufs(icons: &[IconDesc]) -> Vec<Pixbuf> {
+    icons
+        .iter()
+        .map(|icon| make_pixbuf(&icon.image, icon.width, icon.height))
+        .collect()
+}
+
+fn make_pixbuf(pixels: &[u8], width: usize, height: usize) -> Pixbuf {
+    Pixbuf::new_from_vec(
+        pixels.to_vec(),
+        Colorspace::Rgb,
+        true,
+        8,
+        width as i32,
+        height as i32,
+        (width * 4) as i32,
+    )
+}
+
+impl WindowHandle {
+    pub fn show(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.show_all();
+            if state.window.get_accept_focus() {
+                state.window.present_with_time(0);
+            }
+        }
+    }
+
+    /// Replace the menubar of the window.
+    pub fn set_menu(&self, menu: Menu) {
+        if let Some(state) = self.state.upgrade() {
+            let menubar = menu.into_gtk_menubar(&self.state);
+            state.vbox.pack_start(&menubar, false, false, 0);
+            menubar.show_all();
+            if let Some(old) = state.menubar.replace(Some(menubar)) {
+                state.vbox.remove(&old);
+            }
+        }
+    }
+
+    pub fn set_title(&self, title: &str) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.set_title(title);
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        self.state
+            .upgrade()
+            .and_then(|state| state.window.get_title())
+            .unwrap_or_default()
+    }
+
+    /// The size of the content area, in px.
+    pub fn get_size(&self) -> (f64, f64) {
+        match self.state.upgrade() {
+            Some(state) => (
+                state.drawing_area.get_allocated_width().into(),
+                state.drawing_area.get_allocated_height().into(),
+            ),
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Resize the window so that its content area has the given size, in px.
+    pub fn set_size(&self, width: f64, height: f64) {
+        if let Some(state) = self.state.upgrade() {
+            // Leave room for the menubar.
+            let (window_width, window_height) = state.window.get_size();
+            let extra_width = window_width - state.drawing_area.get_allocated_width();
+            let extra_height = window_height - state.drawing_area.get_allocated_height();
+            state.window.resize(
+                width as i32 + extra_width.max(0),
+                height as i32 + extra_height.max(0),
+            );
+        }
+    }
+
+    /// Show an icon for the window in the system tray, replacing any previous
+    /// one.
+    ///
+    /// With a menu, clicking the icon shows the menu; otherwise it calls the
+    /// handler's `tray_icon_clicked`. The menu is also shown on right click.
+    // `GtkStatusIcon` is deprecated, but there is no replacement in GTK.
+    #[allow(deprecated)]
+    pub fn set_tray_icon(
+        &self,
+        icon: &IconDesc,
+        tooltip: &str,
+        menu: Option<Menu>,
+    ) -> Result<(), Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        let pixbuf = make_pixbuf(&icon.image, icon.width, icon.height);
+        let status_icon = StatusIcon::new_from_pixbuf(&pixbuf);
+        status_icon.set_tooltip_text(tooltip);
+        let menu = menu.map(|menu| menu.into_gtk_menu(&self.state));
+        match menu {
+            Some(ref menu) => {
+                let popup = menu.clone();
+                status_icon.connect_activate(move |_| {
+                    popup.popup_easy(1, gtk_rs::get_current_event_time())
+                });
+            }
+            None => {
+                let weak = self.state.clone();
+                status_icon.connect_activate(move |_| {
+                    if let Some(state) = weak.upgrade() {
+                        state.handler.tray_icon_clicked();
+                    }
+                });
+            }
+        }
+        if let Some(ref menu) = menu {
+            let popup = menu.clone();
+            status_icon.connect_popup_menu(move |_, button, time| popup.popup_easy(button, time));
+        }
+        if let Some((old, _)) = state.tray_icon.replace(Some((status_icon, menu))) {
+            old.set_visible(false);
+        }
+        Ok(())
+    }
+
+    /// Start a drag carrying `data`, out of the window or to another place
+    /// in it. Call this while handling a mouse move with a button held; the
+    /// handler's `drag_ended` is called once the drag is over.
+    pub fn start_drag(&self, data: DragData) -> Result<(), Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        let targets = TargetList::new(&clipboard::target_entries(&data.formats));
+        if !data.files.is_empty() {
+            targets.add_uri_targets(DRAG_FILES_INFO);
+        }
+        let mut actions = gdk::DragAction::COPY;
+        if data.allow_move {
+            actions |= gdk::DragAction::MOVE;
+        }
+        let event = gtk_rs::get_current_event();
+        let button = match event.as_ref().and_then(|event| event.get_state()) {
+            Some(modifiers) if modifiers.contains(ModifierType::BUTTON3_MASK) => 3,
+            Some(modifiers) if modifiers.contains(ModifierType::BUTTON2_MASK) => 2,
+            _ => 1,
+        };
+        state.drag_data.replace(Some(data));
+        // The coordinates of -1 start the drag at the pointer.
+        let context = state.drawing_area.drag_begin_with_coordinates(
+            &targets,
+            actions,
+            button,
+            event.as_ref(),
+            -1,
+            -1,
+        );
+        if context.is_none() {
+            state.drag_data.replace(None);
+            return Err(Error::Other("the drag could not be started"));
+        }
+        Ok(())
+    }
+
+    /// End the composition in progress, if any, discarding it without a
+    /// `CompositionEvent::Commit`, for when the application commits the
+    /// text itself, as when the focus moves away from it.
+    pub fn cancel_composition(&self) {
+        if let Some(state) = self.state.upgrade() {
+            // Some input methods commit the composition on a reset.
+            if state.composing.replace(false) {
+                state.cancelling.set(true);
+                state.im_context.reset();
+                state.cancelling.set(false);
+            }
+        }
+    }
+
+    /// Tell the input method where the caret is, in px, so that its
+    /// candidate window is shown next to it.
+    pub fn set_caret_rect(&self, rect: Rect) {
+        if let Some(state) = self.state.upgrade() {
+            let area = gdk::Rectangle {
+                x: rect.x0 as i32,
+                y: rect.y0 as i32,
+                width: rect.width() as i32,
+                height: rect.height() as i32,
+            };
+            state.im_context.set_cursor_location(&area);
+        }
+    }
+
+    /// On-screen keyboards follow the input method, which has the focus
+    /// whenever the window does, so this has no effect.
+    pub fn show_soft_keyboard(&self) {}
+
+    /// See `show_soft_keyboard`; this has no effect either.
+    pub fn hide_soft_keyboard(&self) {}
+
+    pub fn accessibility_enabled(&self) -> bool {
+        true
+    }
+
+    /// Expose the tree to ATK, with an object for each node as a
+    /// descendant of the accessible of the drawing area.
+    pub fn update_accessibility(&self, tree: AccessTree) {
+        if let Some(state) = self.state.upgrade() {
+            accessibility::update(&state, tree);
+        }
+    }
+
+    /// Emit an ATK announcement, which the AT-SPI bridge passes on to
+    /// screen readers.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        if let Some(state) = self.state.upgrade() {
+            accessibility::announce(&state, text, politeness);
+        }
+    }
+
+    /// Set the icon of the window, shown by the window manager in its title
+    /// bar, the task list and the window switcher. The images are
+    /// resolutions of the same icon, from which the best fit is chosen for
+    /// each size.
+    pub fn set_icon(&self, icons: &[IconDesc]) -> Result<(), Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        state.window.set_icon_list(&make_pixbufs(icons));
+        Ok(())
+    }
+
+    #[allow(deprecated)]
+    pub fn remove_tray_icon(&self) {
+        if let Some(state) = self.state.upgrade() {
+            if let Some((icon, _)) = state.tray_icon.replace(None) {
+                icon.set_visible(false);
+            }
+        }
+    }
+
+    /// The frame of the window when it is not maximized, and whether it is
+    /// maximized, for saving the window layout.
+    pub fn get_geometry(&self) -> WindowGeometry {
+        match self.state.upgrade() {
+            Some(state) => {
+                let window_state = state.window_state.get();
+                let frame = match window_state {
+                    window::WindowState::Restored => state.frame(),
+                    _ => state.restored_frame.get(),
+                };
+                WindowGeometry {
+                    frame,
+                    maximized: window_state == window::WindowState::Maximized,
+                }
+            }
+            None => WindowGeometry::default(),
+        }
+    }
+
+    /// Restore a frame and maximized state saved with `get_geometry`.
+    ///
+    /// The frame is moved and shrunk as needed to fit on a monitor, in case
+    /// the monitor layout has changed since.
+    pub fn set_geometry(&self, geometry: &WindowGeometry) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.unmaximize();
+            let center = geometry.frame.center();
+            let frame = match state.monitor_workarea(Some((center.x as i32, center.y as i32))) {
+                Some(area) => window::clamp_frame(geometry.frame, area),
+                None => geometry.frame,
+            };
+            state.window.move_(frame.x0 as i32, frame.y0 as i32);
+            state
+                .window
+                .resize(frame.width() as i32, frame.height() as i32);
+            state.restored_frame.set(frame);
+            if geometry.maximized {
+                state.window.maximize();
+            }
+        }
+    }
+
+    /// Hide the window, for example to leave only its tray icon. `show`
+    /// brings it back.
+    pub fn hide(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.hide();
+        }
+    }
+
+    /// Change the stacking level of the window. This only keeps the window
+    /// above others or not; see `WindowBuilder::set_level`.
+    pub fn set_level(&self, level: WindowLevel) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.set_keep_above(level != WindowLevel::Normal);
+        }
+    }
+
+    /// The position of the top left corner of the window, in px, in screen
+    /// coordinates.
+    pub fn get_position(&self) -> (f64, f64) {
+        match self.state.upgrade() {
+            Some(state) => {
+                let (x, y) = state.window.get_position();
+                (x.into(), y.into())
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Move the top left corner of the window to the given position, in the
+    /// coordinates of `get_position`.
+    pub fn set_position(&self, x: f64, y: f64) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.move_(x as i32, y as i32);
+        }
+    }
+
+    /// Center the window on its monitor, leaving out panels and docks.
+    pub fn center_on_screen(&self) {
+        if let Some(state) = self.state.upgrade() {
+            match state.monitor_workarea(None) {
+                Some(area) => center_window(&state.window, area),
+                None => state.window.set_position(WindowPosition::Center),
+            }
+        }
+    }
+
+    /// Center the window over another one, as for a dialog.
+    pub fn center_on(&self, other: &WindowHandle) {
+        if let (Some(state), Some(other)) = (self.state.upgrade(), other.state.upgrade()) {
+            center_window(&state.window, other.frame());
+        }
+    }
+
+    pub fn maximize(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.maximize();
+        }
+    }
+
+    pub fn minimize(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.iconify();
+        }
+    }
+
+    /// Return the window to its normal size and position, leaving fullscreen
+    /// if needed.
+    pub fn restore(&self) {
+        if let Some(state) = self.state.upgrade() {
+            match state.window_state.get() {
+                window::WindowState::Fullscreen => state.window.unfullscreen(),
+                window::WindowState::Minimized => state.window.deiconify(),
+                window::WindowState::Maximized => state.window.unmaximize(),
+                window::WindowState::Restored => (),
+            }
+        }
+    }
+
+    /// Enter or leave fullscreen.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(state) = self.state.upgrade() {
+            if fullscreen {
+                state.window.fullscreen();
+            } else {
+                state.window.unfullscreen();
+            }
+        }
+    }
+
+    /// Whether the window is maximized, minimized or fullscreen.
+    pub fn get_window_state(&self) -> window::WindowState {
+        match self.state.upgrade() {
+            Some(state) => state.window_state.get(),
+            None => window::WindowState::Restored,
+        }
+    }
+
+    /// The native handles of the window, for external renderers. This is
+    /// only available on X11, once the window has been shown.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        let state = self.state.upgrade()?;
+        let gdk_window = state.window.get_window()?;
+        let display = gdk_window.get_display();
+        if display.get_type().name() != "GdkX11Display" {
+            return None;
+        }
+        unsafe {
+            let window_ptr: *mut gdk_sys::GdkWindow = gdk_window.to_glib_none().0;
+            let display_ptr: *mut gdk_sys::GdkDisplay = display.to_glib_none().0;
+            Some(RawWindowHandle::Xlib {
+                window: gdk_x11_window_get_xid(window_ptr as *mut c_void),
+                display: gdk_x11_display_get_xdisplay(display_ptr as *mut c_void),
+            })
+        }
+    }
+
+    pub fn raw_display_handle(&self) -> RawDisplayHandle {
+        let display = match gdk::Display::get_default() {
+            Some(ref display) if display.get_type().name() == "GdkX11Display" => unsafe {
+                let display_ptr: *mut gdk_sys::GdkDisplay = display.to_glib_none().0;
+                gdk_x11_display_get_xdisplay(display_ptr as *mut c_void)
+            },
+            _ => ptr::null_mut(),
+        };
+        RawDisplayHandle::Xlib { display }
+    }
+
+    /// Set the cursor shown while the pointer is over the window.
+    pub fn set_cursor(&self, cursor: &Cursor) {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let gdk_window = match state.drawing_area.get_window() {
+            Some(gdk_window) => gdk_window,
+            None => return,
+        };
+        let display = gdk_window.get_display();
+        let name = match cursor {
+            Cursor::Arrow => "default",
+            Cursor::IBeam => "text",
+            Cursor::Crosshair => "crosshair",
+            Cursor::OpenHand => "grab",
+            Cursor::ClosedHand => "grabbing",
+            Cursor::NotAllowed => "not-allowed",
+            Cursor::ResizeLeftRight => "ew-resize",
+            Cursor::ResizeUpDown => "ns-resize",
+            Cursor::Custom(custom) => {
+                gdk_window.set_cursor(&custom.0);
+                return;
+            }
+        };
+        gdk_window.set_cursor(&gdk::Cursor::new_from_name(&display, name));
+    }
+
+    /// Create a cursor from an image.
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        let display = gdk::Display::get_default()?;
+        let pixbuf = make_pixbuf(&desc.image, desc.width, desc.height);
+        let cursor =
+            gdk::Cursor::new_from_pixbuf(&display, &pixbuf, desc.hot.0 as i32, desc.hot.1 as i32);
+        Some(Cursor::Custom(CustomCursor(cursor)))
+    }
+
+    /// Show a context menu at a point in the window, in px. The chosen item
+    /// is sent to the handler as a command.
+    pub fn show_context_menu(&self, menu: Menu, x: f64, y: f64) {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if let Some(gdk_window) = state.drawing_area.get_window() {
+            let menu = menu.into_gtk_menu(&self.state);
+            menu.show_all();
+            let rect = gdk::Rectangle {
+                x: x as i32,
+                y: y as i32,
+                width: 1,
+                height: 1,
+            };
+            menu.popup_at_rect(
+                &gdk_window,
+                &rect,
+                gdk::Gravity::NorthWest,
+                gdk::Gravity::NorthWest,
+                None,
+            );
+            // Keep the menu alive while it is shown.
+            state.context_menu.replace(Some(menu));
+        }
+    }
+
+    /// Close the window.
+    pub fn close(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.window.destroy();
+        }
+    }
+
+    // Request invalidation of the entire window contents.
+    /// Request a paint. Paints happen in the paint phase of the frame
+    /// clock, at most once per display refresh.
+    pub fn invalidate(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.drawing_area.queue_draw();
+        }
+    }
+
+    /// Request a call to `WinHandler::frame` at the next display refresh,
+    /// followed by a paint.
+    pub fn request_frame(&self) {
+        if let Some(state) = self.state.upgrade() {
+            if state.frame_requested.replace(true) {
+                return;
+            }
+            let weak = self.state.clone();
+            state.drawing_area.add_tick_callback(move |widget, _| {
+                if let Some(state) = weak.upgrade() {
+                    state.frame_requested.set(false);
+                    state.handler.frame();
+                    widget.queue_draw();
+                }
+                Continue(false)
+            });
+        }
+    }
+
+    /// Request a call to `WinHandler::timer` with the returned token after
+    /// `delay`, accurate to about a millisecond.
+    pub fn request_timer(&self, delay: Duration) -> TimerToken {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return TimerToken::next(),
+        };
+        let (token, earliest) = state.timers.borrow_mut().add(Instant::now() + delay);
+        if earliest {
+            self.schedule_timers();
+        }
+        token
+    }
+
+    /// Add a timeout for the earliest deadline of the timers, replacing the
+    /// previous one.
+    fn schedule_timers(&self) {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if let Some(source) = state.timer_source.borrow_mut().take() {
+            glib::source_remove(source);
+        }
+        let deadline = state.timers.borrow().next_deadline();
+        if let Some(deadline) = deadline {
+            let now = Instant::now();
+            let delay = if deadline > now {
+                deadline - now
+            } else {
+                Duration::from_secs(0)
+            };
+            // Round up, so the timeout doesn't fire before the deadline.
+            let millis =
+                delay.as_secs() * 1000 + u64::from((delay.subsec_nanos() + 999_999) / 1_000_000);
+            let handle = self.clone();
+            let source = gtk_rs::timeout_add(millis as u32, move || {
+                handle.run_timers();
+                Continue(false)
+            });
+            *state.timer_source.borrow_mut() = Some(source);
+        }
+    }
+
+    fn run_timers(&self) {
+        if let Some(state) = self.state.upgrade() {
+            // The timeout is finished, so it must not be removed.
+            state.timer_source.borrow_mut().take();
+            let due = state
+                .timers
+                .borrow_mut()
+                .take_due(Instant::now() + COALESCE_SLACK);
+            for token in due {
+                state.handler.timer(token);
+            }
+        }
+        self.schedule_timers();
+    }
+
+    /// GTK always paints in step with the frame clock of the window, so
+    /// this has no effect.
+    pub fn set_vsync(&self, _vsync: bool) {}
+
+    /// The backdrop shown behind the window, which is always `None`.
+    pub fn backdrop(&self) -> WindowBackdrop {
+        WindowBackdrop::None
+    }
+
+    /// Create a surface in the window for rendering with a GPU API directly.
+    pub fn create_gpu_surface(&self) -> Result<surface::GpuSurface, Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        // The drawing area only has a GDK window once realized.
+        state.drawing_area.realize();
+        let parent = state.drawing_area.get_window().ok_or(Error::Null)?;
+        Ok(surface::GpuSurface::new(&parent))
+    }
+
+    /// Host a native view of the application in the window. This needs GTK
+    /// to run on X11.
+    ///
+    /// # Safety
+    ///
+    /// `view` must be the `Xlib` handle of a window that outlives the
+    /// returned `NativeView`.
+    pub unsafe fn add_native_view(
+        &self,
+        view: RawWindowHandle,
+    ) -> Result<native_view::NativeView, Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        let view = foreign_window(view)?;
+        state.drawing_area.realize();
+        let parent = state.drawing_area.get_window().ok_or(Error::Null)?;
+        Ok(native_view::NativeView::new(&parent, view))
+    }
+
+    /// Capture the mouse, so that mouse events keep being delivered to this
+    /// window while the pointer is outside it, until `release_mouse`.
+    ///
+    /// GTK already grabs the pointer while a button is held down, so there
+    /// is nothing to do here.
+    pub fn capture_mouse(&self) {}
+
+    /// Release a mouse capture started with `capture_mouse`.
+    pub fn release_mouse(&self) {}
+
+    /// Register a system-wide hotkey. GTK has no way to do this.
+    pub fn register_hotkey(&self, _id: u32, _key: MenuKey) -> Result<(), Error> {
+        Err(Error::Other("global hotkeys are not supported with GTK"))
+    }
+
+    /// Unregister a hotkey previously registered with `register_hotkey`.
+    pub fn unregister_hotkey(&self, _id: u32) {}
+
+    /// Get a handle that can be used to schedule an idle task.
+    pub fn get_idle_handle(&self) -> Option<IdleHandle> {
+        self.state.upgrade().map(|state| IdleHandle {
+            state: self.state.clone(),
+            idle_queue: Arc::downgrade(&state.idle_queue),
+        })
+    }
+
+    /// Get the dpi of the window.
+    ///
+    /// GTK lays out and draws in px already, scaling for high dpi monitors
+    /// itself, so this is always nominal.
+    pub fn get_dpi(&self) -> f32 {
+        96.0
+    }
+
+    // TODO: the following methods are cut'n'paste code. A good way to DRY
+    // would be to have a platform-independent trait with these as methods with
+    // default implementations.
+
+    /// Convert a dimension in px units to physical pixels (rounding).
+    pub fn px_to_pixels(&self, x: f32) -> i32 {
+        (x * self.get_dpi() * (1.0 / 96.0)).round() as i32
+    }
+
+    /// Convert a point in px units to physical pixels (rounding).
+    pub fn px_to_pixels_xy(&self, x: f32, y: f32) -> (i32, i32) {
+        let scale = self.get_dpi() * (1.0 / 96.0);
+        ((x * scale).round() as i32, (y * scale).round() as i32)
+    }
+
+    /// Convert a dimension in physical pixels to px units.
+    pub fn pixels_to_px<T: Into<f64>>(&self, x: T) -> f32 {
+        (x.into() as f32) * 96.0 / self.get_dpi()
+    }
+
+    /// Convert a point in physical pixels to px units.
+    pub fn pixels_to_px_xy<T: Into<f64>>(&self, x: T, y: T) -> (f32, f32) {
+        let scale = 96.0 / self.get_dpi();
+        ((x.into() as f32) * scale, (y.into() as f32) * scale)
+    }
+
+    pub fn file_dialog(
+        &self,
+        ty: FileDialogType,
+        options: FileDialogOptions,
+    ) -> Result<OsString, Error> {
+        let mut paths = self
+            .get_file_dialog_paths(ty, &options)?
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(paths.swap_remove(0).into_os_string())
+    }
+
+    /// Show a modal file open dialog, returning the chosen files.
+    pub fn open_file_dialog(&self, options: &FileDialogOptions) -> Result<Vec<FileInfo>, Error> {
+        let paths = self
+            .get_file_dialog_paths(FileDialogType::Open, options)?
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        Ok(paths.into_iter().map(|path| FileInfo { path }).collect())
+    }
+
+    /// Show a modal file save dialog, returning the chosen file.
+    ///
+    /// The dialog asks for confirmation before an existing file is chosen.
+    pub fn save_file_dialog(&self, options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        let mut paths = self
+            .get_file_dialog_paths(FileDialogType::Save, options)?
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(FileInfo {
+            path: paths.swap_remove(0),
+        })
+    }
+
+    /// Show a modal directory chooser, returning the chosen directory.
+    pub fn open_directory_dialog(&self, options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        let mut paths = self
+            .get_file_dialog_paths(FileDialogType::Directory, options)?
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(FileInfo {
+            path: paths.swap_remove(0),
+        })
+    }
+
+    fn get_file_dialog_paths(
+        &self,
+        ty: FileDialogType,
+        options: &FileDialogOptions,
+    ) -> Result<Option<Vec<std::path::PathBuf>>, Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        Ok(get_file_dialog_paths(&state.window, ty, options))
+    }
+
+    /// Show a modal alert, returning the chosen button.
+    pub fn alert(&self, options: &AlertOptions) -> AlertResponse {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return AlertResponse::Cancel,
+        };
+        let message_type = match options.style {
+            AlertStyle::Info => MessageType::Info,
+            AlertStyle::Warning => MessageType::Warning,
+            AlertStyle::Error => MessageType::Error,
+        };
+        let dialog = MessageDialog::new(
+            Some(&state.window),
+            DialogFlags::MODAL | DialogFlags::DESTROY_WITH_PARENT,
+            message_type,
+            ButtonsType::None,
+            &options.message,
+        );
+        dialog.set_title(&options.title);
+        if let Some(ref detail) = options.detail {
+            dialog.set_property_secondary_text(Some(detail));
+        }
+        let responses = options.buttons.responses();
+        for &response in responses {
+            let label = match response {
+                AlertResponse::Ok => "_OK",
+                AlertResponse::Cancel => "_Cancel",
+                AlertResponse::Yes => "_Yes",
+                AlertResponse::No => "_No",
+            };
+            dialog.add_button(label, gtk_response(response).into());
+        }
+        dialog.set_default_response(gtk_response(responses[options.default_index()]).into());
+        let result = ResponseType::from(dialog.run());
+        dialog.destroy();
+        responses
+            .iter()
+            .cloned()
+            .find(|&response| gtk_response(response) == result)
+            .unwrap_or(AlertResponse::Cancel)
+    }
+}
+
+fn gtk_response(response: AlertResponse) -> ResponseType {
+    match response {
+        AlertResponse::Ok => ResponseType::Ok,
+        AlertResponse::Cancel => ResponseType::Cancel,
+        AlertResponse::Yes => ResponseType::Yes,
+        AlertResponse::No => ResponseType::No,
+    }
+}
+
+/// Move a window so that it is centered in `area`, in screen coordinates.
+fn center_window(window: &gtk_rs::Window, area: Rect) {
+    let (width, height) = window.get_size();
+    let x = area.x0 + (area.width() - f64::from(width)) / 2.0;
+    let y = area.y0 + (area.height() - f64::from(height)) / 2.0;
+    window.move_(x as i32, y as i32);
+}
+
+// The state is only used on the main thread, from the idle callback.
+unsafe impl Send for IdleHandle {}
+
+impl IdleHandle {
+    /// Add an idle handler, which is called (once) when the message loop
+    /// is empty. The idle handler will be run from the main UI thread, and
+    /// won't be scheduled if the associated window has been dropped.
+    ///
+    /// Note: the name "idle" suggests that it will be scheduled with a lower
+    /// priority than other UI events, but that's not necessarily the case.
+    pub fn add_idle<F>(&self, callback: F)
+    where
+        F: FnOnce(&dyn Any) + Send + 'static,
+    {
+        if let Some(queue) = self.idle_queue.upgrade() {
+            let mut queue = queue.lock().expect("queue lock");
+            if queue.is_empty() {
+                let handle = self.clone();
+                glib::idle_add(move || {
+                    handle.run_idle();
+                    Continue(false)
+                });
+            }
+            queue.push(Box::new(callback));
+        }
+    }
+
+    fn run_idle(&self) {
+        if let Some(state) = self.state.upgrade() {
+            let queue: Vec<_> =
+                mem::replace(&mut state.idle_queue.lock().expect("queue"), Vec::new());
+            let handler_as_any = state.handler.as_any();
+            for callback in queue {
+                callback.call(handler_as_any);
+            }
+        }
+    }
+}
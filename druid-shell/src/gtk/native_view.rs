@@ -0,0 +1,106 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native views hosted in a window, GTK implementation.
+//!
+//! The view is an X11 window of the application, reparented into a native
+//! container window of the drawing area. The container covers the visible
+//! part of the view and clips it.
+
+use gdk::{ScreenExt, WindowExt, WindowType, WindowWindowClass};
+
+use piet_common::kurbo::Rect;
+
+/// A native view of the application, such as a browser embedded by its X11
+/// window, shown in a window.
+///
+/// Dropping hands the view back to its owner, hidden and reparented to the
+/// root window.
+pub struct NativeView {
+    container: gdk::Window,
+    view: gdk::Window,
+}
+
+impl NativeView {
+    /// Reparent `view` into a hidden container over `parent`, the drawing
+    /// area's window.
+    pub(crate) fn new(parent: &gdk::Window, view: gdk::Window) -> NativeView {
+        let attributes = gdk::WindowAttr {
+            x: Some(0),
+            y: Some(0),
+            width: 1,
+            height: 1,
+            wclass: WindowWindowClass::InputOutput,
+            window_type: WindowType::Child,
+            ..Default::default()
+        };
+        let container = gdk::Window::new(Some(parent), &attributes);
+        // The view is an X window, so its parent must be one too.
+        container.ensure_native();
+        view.hide();
+        view.reparent(&container, 0, 0);
+        view.show_unraised();
+        NativeView { container, view }
+    }
+
+    /// Place the view at `frame` in the window's content area, showing only
+    /// the part inside `clip`, both in px.
+    pub fn set_frame(&self, frame: Rect, clip: Rect) {
+        let clip = clip.intersect(frame);
+        let x = clip.x0.round() as i32;
+        let y = clip.y0.round() as i32;
+        // GDK windows are at least one pixel in size.
+        let width = ((clip.x1.round() as i32) - x).max(1);
+        let height = ((clip.y1.round() as i32) - y).max(1);
+        self.container.move_resize(x, y, width, height);
+        self.view.move_resize(
+            frame.x0.round() as i32 - x,
+            frame.y0.round() as i32 - y,
+            (frame.width().round() as i32).max(1),
+            (frame.height().round() as i32).max(1),
+        );
+    }
+
+    /// Show or hide the view. Views start hidden, so that nothing appears
+    /// before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        if visible {
+            self.container.show_unraised();
+        } else {
+            self.container.hide();
+        }
+    }
+
+    /// Give the keyboard focus to the view.
+    pub fn focus(&self) {
+        self.view.focus(gdk_sys::GDK_CURRENT_TIME as u32);
+    }
+
+    /// Give the keyboard focus back to the window.
+    pub fn blur(&self) {
+        self.container
+            .get_toplevel()
+            .focus(gdk_sys::GDK_CURRENT_TIME as u32);
+    }
+}
+
+impl Drop for NativeView {
+    fn drop(&mut self) {
+        self.view.hide();
+        if let Some(root) = self.view.get_screen().get_root_window() {
+            self.view.reparent(&root, 0, 0);
+        }
+        self.container.destroy();
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GPU surfaces, GTK implementation.
+//!
+//! A surface is a native child window of the drawing area's GDK window, so
+//! that the X server composites it with the window. Input passes through to
+//! the drawing area.
+
+use std::ffi::c_void;
+
+use gdk::{WindowExt, WindowType, WindowWindowClass};
+use glib::translate::ToGlibPtr;
+use glib::ObjectExt;
+
+use piet_common::kurbo::Rect;
+
+use super::{gdk_x11_display_get_xdisplay, gdk_x11_window_get_xid};
+use crate::window::RawWindowHandle;
+
+/// A region of a window that is rendered with a GPU API of the caller's
+/// choice, such as OpenGL, Vulkan or wgpu, through `raw_window_handle`.
+///
+/// The surface is destroyed when dropped.
+pub struct GpuSurface {
+    window: gdk::Window,
+}
+
+impl GpuSurface {
+    /// Create a hidden surface over `parent`, the drawing area's window.
+    pub(crate) fn new(parent: &gdk::Window) -> GpuSurface {
+        let attributes = gdk::WindowAttr {
+            x: Some(0),
+            y: Some(0),
+            width: 1,
+            height: 1,
+            wclass: WindowWindowClass::InputOutput,
+            window_type: WindowType::Child,
+            ..Default::default()
+        };
+        let window = gdk::Window::new(Some(parent), &attributes);
+        // External renderers need an X window of their own.
+        window.ensure_native();
+        window.set_pass_through(true);
+        GpuSurface { window }
+    }
+
+    /// Place the surface at `rect` in the window's content area, in px.
+    pub fn set_frame(&self, rect: Rect) {
+        let x0 = rect.x0.round() as i32;
+        let y0 = rect.y0.round() as i32;
+        let x1 = rect.x1.round() as i32;
+        let y1 = rect.y1.round() as i32;
+        // GDK windows are at least one pixel in size.
+        self.window
+            .move_resize(x0, y0, (x1 - x0).max(1), (y1 - y0).max(1));
+    }
+
+    /// Show or hide the surface. Surfaces start hidden, so that nothing
+    /// appears before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        if visible {
+            self.window.show_unraised();
+        } else {
+            self.window.hide();
+        }
+    }
+
+    /// The native handles of the surface, for creating a GL context or
+    /// Vulkan surface on it. This is only available on X11.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        let display = self.window.get_display();
+        if display.get_type().name() != "GdkX11Display" {
+            return None;
+        }
+        unsafe {
+            let window_ptr: *mut gdk_sys::GdkWindow = self.window.to_glib_none().0;
+            let display_ptr: *mut gdk_sys::GdkDisplay = display.to_glib_none().0;
+            Some(RawWindowHandle::Xlib {
+                window: gdk_x11_window_get_xid(window_ptr as *mut c_void),
+                display: gdk_x11_display_get_xdisplay(display_ptr as *mut c_void),
+            })
+        }
+    }
+}
+
+impl Drop for GpuSurface {
+    fn drop(&mut self) {
+        self.window.destroy();
+    }
+}
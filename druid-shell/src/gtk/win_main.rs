@@ -0,0 +1,42 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GTK main loop.
+
+use glib::Continue;
+
+use crate::util;
+
+pub struct RunLoop;
+
+impl RunLoop {
+    pub fn new() -> RunLoop {
+        util::init();
+        RunLoop
+    }
+
+    pub fn run(&mut self) {
+        gtk_rs::main();
+    }
+}
+
+/// Request to quit the application, exiting the runloop.
+///
+/// This may be called from any thread.
+pub fn request_quit() {
+    glib::idle_add(|| {
+        gtk_rs::main_quit();
+        Continue(false)
+    });
+}
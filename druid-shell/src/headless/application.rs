@@ -0,0 +1,79 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless implementation of features at the application scope.
+
+use std::path::Path;
+
+use super::win_main;
+use crate::clipboard::Clipboard;
+use crate::piet::Color;
+use crate::window::{PowerSource, Theme};
+use crate::Error;
+
+pub struct Application;
+
+/// Keeps the display awake until dropped; see `Application::inhibit_sleep`.
+pub struct SleepInhibitor {
+    _private: (),
+}
+
+impl Application {
+    pub fn quit() {
+        win_main::request_quit();
+    }
+
+    /// The clipboard, kept in memory.
+    pub fn clipboard() -> Clipboard {
+        Clipboard
+    }
+
+    /// There are no system settings, so the theme is always light.
+    pub fn theme() -> Theme {
+        Theme::Light
+    }
+
+    pub fn accent_color() -> Option<Color> {
+        None
+    }
+
+    pub fn reduce_transparency() -> bool {
+        false
+    }
+
+    pub fn text_scale() -> f64 {
+        1.0
+    }
+
+    /// There is no battery, so the power source is always external.
+    pub fn power_source() -> PowerSource {
+        PowerSource::External
+    }
+
+    /// There is no dock to show badges, so this does nothing.
+    pub fn set_badge(_label: Option<&str>) {}
+
+    pub fn open_url(_url: &str) -> Result<(), Error> {
+        Err(Error::Other("there is no browser to open the URL"))
+    }
+
+    pub fn reveal_in_file_manager(_path: &Path) -> Result<(), Error> {
+        Err(Error::Other("there is no file manager"))
+    }
+
+    /// There is no display to keep awake, so this always succeeds.
+    pub fn inhibit_sleep(_reason: &str) -> Result<SleepInhibitor, Error> {
+        Ok(SleepInhibitor { _private: () })
+    }
+}
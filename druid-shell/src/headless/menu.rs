@@ -0,0 +1,56 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Menus, headless implementation.
+//!
+//! Menus are never shown. Tests choose an item by calling the handler's
+//! `command` directly, through `WindowHandle::with_handler`.
+
+use crate::keycodes::MenuKey;
+
+/// A menu object, which can be either a top-level menubar or a
+/// submenu.
+#[derive(Default)]
+pub struct Menu;
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu
+    }
+
+    /// Create a menu to be shown with `WindowHandle::show_context_menu`.
+    pub fn new_for_popup() -> Menu {
+        Menu
+    }
+
+    /// Add a dropdown menu.
+    pub fn add_dropdown(&mut self, _menu: Menu, _text: &str) {}
+
+    /// Add an item to the menu.
+    pub fn add_item(&mut self, _id: u32, _text: &str, _key: impl Into<MenuKey>) {}
+
+    /// Add an item to the menu, which can be disabled or show a checkmark.
+    pub fn add_item_with_state(
+        &mut self,
+        _id: u32,
+        _text: &str,
+        _key: impl Into<MenuKey>,
+        _enabled: bool,
+        _checked: bool,
+    ) {
+    }
+
+    /// Add a separator to the menu.
+    pub fn add_separator(&mut self) {}
+}
@@ -0,0 +1,883 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless implementation of window creation.
+//!
+//! Windows are never shown on a display. Each one paints into a bitmap in
+//! memory, which `WindowHandle::snapshot` reads back, and the run loop
+//! drives idle callbacks, timers, frames and paints as on other platforms.
+//! This lets integration tests and screenshots run without a display
+//! server, as in CI.
+//!
+//! The screen is a nominal 1920 by 1080 px, at 96 dpi. Dialogs and menus
+//! are never shown, and input is sent to the handler directly, through
+//! `WindowHandle::with_handler`.
+
+pub mod application;
+pub mod clipboard;
+pub mod menu;
+pub mod native_view;
+pub mod surface;
+pub mod util;
+pub mod win_main;
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::ffi::OsString;
+use std::mem;
+use std::ptr;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use cairo::{Context, Format, ImageSurface};
+
+use piet_common::kurbo::Rect;
+use piet_common::{Piet, RenderContext};
+
+use crate::accessibility::{AccessTree, Politeness};
+use crate::alert::{AlertOptions, AlertResponse};
+use crate::blend::Painting;
+use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::drag::DragData;
+use crate::keycodes::MenuKey;
+use crate::timer::{TimerQueue, TimerToken, COALESCE_SLACK};
+use crate::window::{
+    self, Cursor, CursorDesc, IconDesc, RawDisplayHandle, RawWindowHandle, WinHandler,
+    WindowBackdrop, WindowGeometry, WindowKind, WindowLevel,
+};
+use crate::Error;
+pub use menu::Menu;
+
+/// The nominal screen the windows are placed on, in px.
+const SCREEN: Rect = Rect {
+    x0: 0.0,
+    y0: 0.0,
+    x1: 1920.0,
+    y1: 1080.0,
+};
+
+thread_local! {
+    /// The open windows, which own their state.
+    static WINDOWS: RefCell<Vec<Rc<WindowState>>> = RefCell::new(Vec::new());
+}
+
+#[derive(Clone, Default)]
+pub struct WindowHandle {
+    state: Weak<WindowState>,
+}
+
+/// Builder abstraction for creating new windows.
+pub struct WindowBuilder {
+    handler: Option<Box<dyn WinHandler>>,
+    title: String,
+    owner: Option<WindowHandle>,
+    position: Option<(f64, f64)>,
+    centered: bool,
+    size: Option<(f64, f64)>,
+}
+
+/// A cursor made from an image. Headless windows show no cursor.
+#[derive(Clone)]
+pub struct CustomCursor;
+
+#[derive(Clone)]
+pub struct IdleHandle {
+    idle_queue: std::sync::Weak<Mutex<Vec<Box<dyn IdleCallback>>>>,
+}
+
+// TODO: move this out of platform-dependent section.
+trait IdleCallback: Send {
+    fn call(self: Box<Self>, a: &dyn Any);
+}
+
+impl<F: FnOnce(&dyn Any) + Send> IdleCallback for F {
+    fn call(self: Box<F>, a: &dyn Any) {
+        (*self)(a)
+    }
+}
+
+/// The contents of a window as last painted.
+#[derive(Debug, Clone)]
+pub struct Snapshot {
+    /// Pixels in RGBA order with 8 bits per channel, row by row.
+    pub image: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// The state of a window, owned by `WINDOWS` until it is closed.
+struct WindowState {
+    handler: Box<dyn WinHandler>,
+    title: RefCell<String>,
+    /// The frame of the window, in px, in screen coordinates. Headless
+    /// windows have no decorations, so this is also the content area.
+    frame: Cell<Rect>,
+    window_state: Cell<window::WindowState>,
+    /// The frame of the window the last time it was neither maximized,
+    /// minimized nor fullscreen.
+    restored_frame: Cell<Rect>,
+    visible: Cell<bool>,
+    closed: Cell<bool>,
+    /// Whether the size has changed since it was last sent to the handler.
+    size_changed: Cell<bool>,
+    /// Whether the window state has changed since it was last sent to the
+    /// handler.
+    state_changed: Cell<bool>,
+    /// Whether `WinHandler::frame` is to be called at the next refresh.
+    frame_requested: Cell<bool>,
+    /// Whether the window is to be painted at the next refresh.
+    invalid: Cell<bool>,
+    idle_queue: Arc<Mutex<Vec<Box<dyn IdleCallback>>>>,
+    timers: RefCell<TimerQueue<Instant>>,
+    /// The bitmap the window paints into, once it has been painted.
+    surface: RefCell<Option<ImageSurface>>,
+    accessibility: RefCell<Option<AccessTree>>,
+    announcements: RefCell<Vec<(String, Politeness)>>,
+}
+
+impl WindowBuilder {
+    pub fn new() -> WindowBuilder {
+        WindowBuilder {
+            handler: None,
+            title: String::new(),
+            owner: None,
+            position: None,
+            centered: false,
+            size: None,
+        }
+    }
+
+    pub fn set_handler(&mut self, handler: Box<dyn WinHandler>) {
+        self.handler = Some(handler);
+    }
+
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    /// Menus are never shown, so this has no effect.
+    pub fn set_menu(&mut self, _menu: Menu) {}
+
+    /// There is no mouse, so this has no effect.
+    pub fn set_enable_mouse_move_events(&mut self, _to: bool) {}
+
+    /// All windows behave alike, so this has no effect.
+    pub fn set_kind(&mut self, _kind: WindowKind) {}
+
+    /// Windows are not stacked, so this has no effect.
+    pub fn set_level(&mut self, _level: WindowLevel) {}
+
+    /// Set the window that owns this one. The position of an owned window
+    /// is relative to its owner.
+    pub fn set_owner(&mut self, owner: &WindowHandle) {
+        self.owner = Some(owner.clone());
+    }
+
+    /// Set the initial position of the window, in px. The position is
+    /// relative to the top left corner of the owner window if there is one,
+    /// otherwise to the screen.
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.position = Some((x, y));
+    }
+
+    /// Center the window on its owner if there is one, otherwise on the
+    /// screen. This takes precedence over `set_position`.
+    pub fn set_centered(&mut self) {
+        self.centered = true;
+    }
+
+    /// Set the initial size of the window, in px.
+    pub fn set_size(&mut self, width: f64, height: f64) {
+        self.size = Some((width, height));
+    }
+
+    /// Paints are always paced at the nominal refresh rate, so this has no
+    /// effect.
+    pub fn set_vsync(&mut self, _vsync: bool) {}
+
+    /// There is nothing behind the window, so it stays opaque.
+    pub fn set_backdrop(&mut self, _backdrop: WindowBackdrop) {}
+
+    /// There is no window manager to show icons, so this has no effect.
+    pub fn set_icon(&mut self, _icons: Vec<IconDesc>) {}
+
+    /// There are no native windows to build the window inside, so this has
+    /// no effect.
+    ///
+    /// # Safety
+    ///
+    /// This is safe to call with any handle; it is only unsafe to match the
+    /// other platforms.
+    pub unsafe fn set_parent(&mut self, _parent: RawWindowHandle) {}
+
+    pub fn build(self) -> Result<WindowHandle, Error> {
+        let handler = self.handler.expect("handler");
+        let (width, height) = self.size.unwrap_or((500.0, 400.0));
+        let owner = self.owner.as_ref().and_then(|owner| owner.state.upgrade());
+        let area = owner.as_ref().map_or(SCREEN, |owner| owner.frame.get());
+        let frame = Rect::new(0.0, 0.0, width, height);
+        let frame = match self.position {
+            _ if self.centered => centered(frame, area),
+            Some((x, y)) => frame.with_origin((area.x0 + x, area.y0 + y)),
+            None => frame,
+        };
+        let state = Rc::new(WindowState {
+            handler,
+            title: RefCell::new(self.title),
+            frame: Cell::new(frame),
+            window_state: Cell::new(window::WindowState::Restored),
+            restored_frame: Cell::new(frame),
+            visible: Cell::new(false),
+            closed: Cell::new(false),
+            size_changed: Cell::new(true),
+            state_changed: Cell::new(false),
+            frame_requested: Cell::new(false),
+            invalid: Cell::new(true),
+            idle_queue: Arc::new(Mutex::new(Vec::new())),
+            timers: RefCell::new(TimerQueue::new()),
+            surface: RefCell::new(None),
+            accessibility: RefCell::new(None),
+            announcements: RefCell::new(Vec::new()),
+        });
+        WINDOWS.with(|windows| windows.borrow_mut().push(state.clone()));
+        let handle = WindowHandle {
+            state: Rc::downgrade(&state),
+        };
+        state.handler.connect(&crate::window::WindowHandle {
+            inner: handle.clone(),
+        });
+        Ok(handle)
+    }
+}
+
+/// Run the pending work of all windows: idle callbacks, due timers, and
+/// changes of size and state. With `refresh`, also run the requested frames
+/// and paints.
+///
+/// Returns the earliest deadline of the timers left, and whether a window
+/// wants another refresh.
+pub(crate) fn run_pending(refresh: bool) -> (Option<Instant>, bool) {
+    // The callbacks may build or close windows, so they run on a copy.
+    let windows = WINDOWS.with(|windows| windows.borrow().clone());
+    for state in &windows {
+        state.run_idle();
+    }
+    for state in &windows {
+        state.run_timers();
+    }
+    for state in &windows {
+        state.send_changes();
+    }
+    if refresh {
+        for state in &windows {
+            state.refresh();
+        }
+    }
+    WINDOWS.with(|windows| {
+        let windows = windows.borrow();
+        let deadline = windows
+            .iter()
+            .filter_map(|state| state.timers.borrow().next_deadline())
+            .min();
+        let wants_refresh = windows.iter().any(|state| state.wants_refresh());
+        (deadline, wants_refresh)
+    })
+}
+
+impl WindowState {
+    fn run_idle(&self) {
+        if self.closed.get() {
+            return;
+        }
+        let queue: Vec<_> = mem::replace(&mut self.idle_queue.lock().expect("queue"), Vec::new());
+        let handler_as_any = self.handler.as_any();
+        for callback in queue {
+            callback.call(handler_as_any);
+        }
+    }
+
+    fn run_timers(&self) {
+        if self.closed.get() {
+            return;
+        }
+        let due = self
+            .timers
+            .borrow_mut()
+            .take_due(Instant::now() + COALESCE_SLACK);
+        for token in due {
+            self.handler.timer(token);
+        }
+    }
+
+    /// Tell the handler about changes of size and state made since the last
+    /// run. They are reported later rather than as they are made, as they
+    /// usually are made by the handler itself.
+    fn send_changes(&self) {
+        if self.closed.get() {
+            return;
+        }
+        if self.size_changed.replace(false) {
+            let frame = self.frame.get();
+            self.handler
+                .size(frame.width() as u32, frame.height() as u32);
+            self.invalid.set(true);
+        }
+        if self.state_changed.replace(false) {
+            self.handler.window_state_changed(self.window_state.get());
+        }
+    }
+
+    /// Whether the window is shown, and has a frame or paint requested.
+    fn wants_refresh(&self) -> bool {
+        self.visible.get() && (self.frame_requested.get() || self.invalid.get())
+    }
+
+    /// Run the requested frame and paint. Only shown windows are painted.
+    fn refresh(&self) {
+        if self.closed.get() || !self.visible.get() {
+            return;
+        }
+        if self.frame_requested.replace(false) {
+            self.handler.frame();
+            self.invalid.set(true);
+        }
+        if self.invalid.replace(false) {
+            let anim = self.paint();
+            self.invalid.set(anim);
+        }
+    }
+
+    /// Paint the window into its bitmap, returning whether it is animating.
+    fn paint(&self) -> bool {
+        let frame = self.frame.get();
+        // Cairo surfaces can't be empty.
+        let width = (frame.width().round() as i32).max(1);
+        let height = (frame.height().round() as i32).max(1);
+        let mut surface = self.surface.borrow_mut();
+        let stale = surface
+            .as_ref()
+            .map_or(true, |s| s.get_width() != width || s.get_height() != height);
+        if stale {
+            *surface = ImageSurface::create(Format::ARgb32, width, height).ok();
+        }
+        let surface = match *surface {
+            Some(ref surface) => surface,
+            None => return false,
+        };
+        let mut context = Context::new(surface);
+        let _painting = Painting::new(Some(&context));
+        let mut piet_ctx = Piet::new(&mut context);
+        let anim = self.handler.paint(&mut piet_ctx);
+        if let Err(e) = piet_ctx.finish() {
+            eprintln!("Error: {}", e);
+        }
+        anim
+    }
+
+    /// Change the window state, moving the window to `frame`.
+    fn set_window_state(&self, window_state: window::WindowState, frame: Rect) {
+        let old = self.window_state.replace(window_state);
+        if old == window::WindowState::Restored {
+            self.restored_frame.set(self.frame.get());
+        }
+        if old != window_state {
+            self.state_changed.set(true);
+        }
+        self.set_frame(frame);
+    }
+
+    fn set_frame(&self, frame: Rect) {
+        let old = self.frame.replace(frame);
+        if old.size() != frame.size() {
+            self.size_changed.set(true);
+            win_main::wake();
+        }
+    }
+}
+
+/// Center `frame` in `area`.
+fn centered(frame: Rect, area: Rect) -> Rect {
+    let x = area.x0 + (area.width() - frame.width()) / 2.0;
+    let y = area.y0 + (area.height() - frame.height()) / 2.0;
+    frame.with_origin((x, y))
+}
+
+impl WindowHandle {
+    pub fn show(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.visible.set(true);
+            state.invalid.set(true);
+            win_main::wake();
+        }
+    }
+
+    /// Menus are never shown, so this has no effect.
+    pub fn set_menu(&self, _menu: Menu) {}
+
+    pub fn set_title(&self, title: &str) {
+        if let Some(state) = self.state.upgrade() {
+            state.title.replace(title.to_string());
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        self.state
+            .upgrade()
+            .map(|state| state.title.borrow().clone())
+            .unwrap_or_default()
+    }
+
+    /// The size of the content area, in px.
+    pub fn get_size(&self) -> (f64, f64) {
+        match self.state.upgrade() {
+            Some(state) => state.frame.get().size().into(),
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Resize the window so that its content area has the given size, in px.
+    pub fn set_size(&self, width: f64, height: f64) {
+        if let Some(state) = self.state.upgrade() {
+            state.set_frame(state.frame.get().with_size((width, height)));
+        }
+    }
+
+    /// There is no system tray, so this has no effect.
+    pub fn set_tray_icon(
+        &self,
+        _icon: &IconDesc,
+        _tooltip: &str,
+        _menu: Option<Menu>,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+
+    pub fn start_drag(&self, _data: DragData) -> Result<(), Error> {
+        Err(Error::Other(
+            "there is nothing to drag to without a display",
+        ))
+    }
+
+    /// There is no input method, so this has no effect.
+    pub fn cancel_composition(&self) {}
+
+    /// There is no input method, so this has no effect.
+    pub fn set_caret_rect(&self, _rect: Rect) {}
+
+    /// There is no on-screen keyboard, so this has no effect.
+    pub fn show_soft_keyboard(&self) {}
+
+    pub fn hide_soft_keyboard(&self) {}
+
+    /// Trees given to `update_accessibility` are kept for
+    /// `accessibility_tree`, so this is `true`.
+    pub fn accessibility_enabled(&self) -> bool {
+        true
+    }
+
+    pub fn update_accessibility(&self, tree: AccessTree) {
+        if let Some(state) = self.state.upgrade() {
+            *state.accessibility.borrow_mut() = Some(tree);
+        }
+    }
+
+    /// Announcements are kept for `take_announcements`.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        if let Some(state) = self.state.upgrade() {
+            state
+                .announcements
+                .borrow_mut()
+                .push((text.to_string(), politeness));
+        }
+    }
+
+    /// There is no window manager to show icons, so this has no effect.
+    pub fn set_icon(&self, _icons: &[IconDesc]) -> Result<(), Error> {
+        Ok(())
+    }
+
+    pub fn remove_tray_icon(&self) {}
+
+    /// The frame of the window when it is not maximized, and whether it is
+    /// maximized, for saving the window layout.
+    pub fn get_geometry(&self) -> WindowGeometry {
+        match self.state.upgrade() {
+            Some(state) => {
+                let window_state = state.window_state.get();
+                let frame = match window_state {
+                    window::WindowState::Restored => state.frame.get(),
+                    _ => state.restored_frame.get(),
+                };
+                WindowGeometry {
+                    frame,
+                    maximized: window_state == window::WindowState::Maximized,
+                }
+            }
+            None => WindowGeometry::default(),
+        }
+    }
+
+    /// Restore a frame and maximized state saved with `get_geometry`.
+    ///
+    /// The frame is moved and shrunk as needed to fit on the screen.
+    pub fn set_geometry(&self, geometry: &WindowGeometry) {
+        if let Some(state) = self.state.upgrade() {
+            let frame = window::clamp_frame(geometry.frame, SCREEN);
+            state.set_window_state(window::WindowState::Restored, frame);
+            if geometry.maximized {
+                state.set_window_state(window::WindowState::Maximized, SCREEN);
+            }
+        }
+    }
+
+    /// Hide the window, which is not painted until `show` brings it back.
+    pub fn hide(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.visible.set(false);
+        }
+    }
+
+    /// Windows are not stacked, so this has no effect.
+    pub fn set_level(&self, _level: WindowLevel) {}
+
+    /// The position of the top left corner of the window, in px, in screen
+    /// coordinates.
+    pub fn get_position(&self) -> (f64, f64) {
+        match self.state.upgrade() {
+            Some(state) => state.frame.get().origin().into(),
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Move the top left corner of the window to the given position, in the
+    /// coordinates of `get_position`.
+    pub fn set_position(&self, x: f64, y: f64) {
+        if let Some(state) = self.state.upgrade() {
+            state.set_frame(state.frame.get().with_origin((x, y)));
+        }
+    }
+
+    /// Center the window on the screen.
+    pub fn center_on_screen(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.set_frame(centered(state.frame.get(), SCREEN));
+        }
+    }
+
+    /// Center the window over another one, as for a dialog.
+    pub fn center_on(&self, other: &WindowHandle) {
+        if let (Some(state), Some(other)) = (self.state.upgrade(), other.state.upgrade()) {
+            state.set_frame(centered(state.frame.get(), other.frame.get()));
+        }
+    }
+
+    /// Make the window fill the screen.
+    pub fn maximize(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.set_window_state(window::WindowState::Maximized, SCREEN);
+        }
+    }
+
+    /// Minimize the window. It keeps its frame, and is still painted.
+    pub fn minimize(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.set_window_state(window::WindowState::Minimized, state.frame.get());
+        }
+    }
+
+    /// Return the window to its normal size and position, leaving fullscreen
+    /// if needed.
+    pub fn restore(&self) {
+        if let Some(state) = self.state.upgrade() {
+            if state.window_state.get() != window::WindowState::Restored {
+                let frame = state.restored_frame.get();
+                state.set_window_state(window::WindowState::Restored, frame);
+            }
+        }
+    }
+
+    /// Enter or leave fullscreen, in which the window fills the screen.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(state) = self.state.upgrade() {
+            if fullscreen {
+                state.set_window_state(window::WindowState::Fullscreen, SCREEN);
+            } else if state.window_state.get() == window::WindowState::Fullscreen {
+                let frame = state.restored_frame.get();
+                state.set_window_state(window::WindowState::Restored, frame);
+            }
+        }
+    }
+
+    /// Whether the window is maximized, minimized or fullscreen.
+    pub fn get_window_state(&self) -> window::WindowState {
+        match self.state.upgrade() {
+            Some(state) => state.window_state.get(),
+            None => window::WindowState::Restored,
+        }
+    }
+
+    /// There are no native windows, so this is always `None`.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        None
+    }
+
+    /// There is no display, so the handle is null.
+    pub fn raw_display_handle(&self) -> RawDisplayHandle {
+        RawDisplayHandle::Xlib {
+            display: ptr::null_mut(),
+        }
+    }
+
+    /// There is no pointer, so this has no effect.
+    pub fn set_cursor(&self, _cursor: &Cursor) {}
+
+    /// Create a cursor from an image. It is never shown.
+    pub fn make_cursor(&self, _desc: &CursorDesc) -> Option<Cursor> {
+        Some(Cursor::Custom(CustomCursor))
+    }
+
+    /// Menus are never shown, so this has no effect.
+    pub fn show_context_menu(&self, _menu: Menu, _x: f64, _y: f64) {}
+
+    /// Close the window.
+    pub fn close(&self) {
+        if let Some(state) = self.state.upgrade() {
+            if state.closed.replace(true) {
+                return;
+            }
+            WINDOWS.with(|windows| {
+                windows
+                    .borrow_mut()
+                    .retain(|window| !Rc::ptr_eq(window, &state))
+            });
+            state.handler.destroy();
+        }
+    }
+
+    /// Request a paint, at the next refresh.
+    pub fn invalidate(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.invalid.set(true);
+            win_main::wake();
+        }
+    }
+
+    /// Request a call to `WinHandler::frame` at the next refresh, followed
+    /// by a paint.
+    pub fn request_frame(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.frame_requested.set(true);
+            win_main::wake();
+        }
+    }
+
+    /// Request a call to `WinHandler::timer` with the returned token after
+    /// `delay`.
+    pub fn request_timer(&self, delay: Duration) -> TimerToken {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return TimerToken::next(),
+        };
+        let (token, earliest) = state.timers.borrow_mut().add(Instant::now() + delay);
+        if earliest {
+            win_main::wake();
+        }
+        token
+    }
+
+    /// Paints are always paced at the nominal refresh rate, so this has no
+    /// effect.
+    pub fn set_vsync(&self, _vsync: bool) {}
+
+    /// The backdrop shown behind the window, which is always `None`.
+    pub fn backdrop(&self) -> WindowBackdrop {
+        WindowBackdrop::None
+    }
+
+    pub fn create_gpu_surface(&self) -> Result<surface::GpuSurface, Error> {
+        Err(Error::Other("headless windows have no GPU surfaces"))
+    }
+
+    /// There are no native views, so this always fails.
+    ///
+    /// # Safety
+    ///
+    /// This is safe to call with any handle; it is only unsafe to match the
+    /// other platforms.
+    pub unsafe fn add_native_view(
+        &self,
+        _view: RawWindowHandle,
+    ) -> Result<native_view::NativeView, Error> {
+        Err(Error::Other("headless windows can't host native views"))
+    }
+
+    /// There is no mouse, so this has no effect.
+    pub fn capture_mouse(&self) {}
+
+    pub fn release_mouse(&self) {}
+
+    pub fn register_hotkey(&self, _id: u32, _key: MenuKey) -> Result<(), Error> {
+        Err(Error::Other("there is no keyboard to register hotkeys on"))
+    }
+
+    pub fn unregister_hotkey(&self, _id: u32) {}
+
+    /// Get a handle that can be used to schedule an idle task.
+    pub fn get_idle_handle(&self) -> Option<IdleHandle> {
+        self.state.upgrade().map(|state| IdleHandle {
+            idle_queue: Arc::downgrade(&state.idle_queue),
+        })
+    }
+
+    /// Get the dpi of the window, which is always nominal.
+    pub fn get_dpi(&self) -> f32 {
+        96.0
+    }
+
+    // TODO: the following methods are cut'n'paste code. A good way to DRY
+    // would be to have a platform-independent trait with these as methods with
+    // default implementations.
+
+    /// Convert a dimension in px units to physical pixels (rounding).
+    pub fn px_to_pixels(&self, x: f32) -> i32 {
+        (x * self.get_dpi() * (1.0 / 96.0)).round() as i32
+    }
+
+    /// Convert a point in px units to physical pixels (rounding).
+    pub fn px_to_pixels_xy(&self, x: f32, y: f32) -> (i32, i32) {
+        let scale = self.get_dpi() * (1.0 / 96.0);
+        ((x * scale).round() as i32, (y * scale).round() as i32)
+    }
+
+    /// Convert a dimension in physical pixels to px units.
+    pub fn pixels_to_px<T: Into<f64>>(&self, x: T) -> f32 {
+        (x.into() as f32) * 96.0 / self.get_dpi()
+    }
+
+    /// Convert a point in physical pixels to px units.
+    pub fn pixels_to_px_xy<T: Into<f64>>(&self, x: T, y: T) -> (f32, f32) {
+        let scale = 96.0 / self.get_dpi();
+        ((x.into() as f32) * scale, (y.into() as f32) * scale)
+    }
+
+    /// Dialogs are never shown, so this always fails as if cancelled.
+    pub fn file_dialog(
+        &self,
+        _ty: FileDialogType,
+        _options: FileDialogOptions,
+    ) -> Result<OsString, Error> {
+        Err(Error::Other("file dialog cancelled"))
+    }
+
+    /// Dialogs are never shown, so this always fails as if cancelled.
+    pub fn open_file_dialog(&self, _options: &FileDialogOptions) -> Result<Vec<FileInfo>, Error> {
+        Err(Error::Other("file dialog cancelled"))
+    }
+
+    /// Dialogs are never shown, so this always fails as if cancelled.
+    pub fn save_file_dialog(&self, _options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        Err(Error::Other("file dialog cancelled"))
+    }
+
+    /// Dialogs are never shown, so this always fails as if cancelled.
+    pub fn open_directory_dialog(&self, _options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        Err(Error::Other("file dialog cancelled"))
+    }
+
+    /// Alerts are never shown, so this returns the default button, as if it
+    /// had been chosen.
+    pub fn alert(&self, options: &AlertOptions) -> AlertResponse {
+        options.buttons.responses()[options.default_index()]
+    }
+
+    /// The contents of the window as last painted, or `None` if it has not
+    /// been painted yet.
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        let state = self.state.upgrade()?;
+        let mut surface = state.surface.try_borrow_mut().ok()?;
+        let surface = surface.as_mut()?;
+        let width = surface.get_width() as usize;
+        let height = surface.get_height() as usize;
+        let stride = surface.get_stride() as usize;
+        let data = surface.get_data().ok()?;
+        let mut image = Vec::with_capacity(width * height * 4);
+        for row in data.chunks(stride).take(height) {
+            for pixel in row[..width * 4].chunks(4) {
+                // Cairo's pixels are premultiplied ARGB, in native byte order.
+                let argb = u32::from_ne_bytes([pixel[0], pixel[1], pixel[2], pixel[3]]);
+                let alpha = argb >> 24;
+                let unpremultiply = |c: u32| match alpha {
+                    0 => 0,
+                    _ => ((c * 255 + alpha / 2) / alpha).min(255) as u8,
+                };
+                image.push(unpremultiply((argb >> 16) & 0xff));
+                image.push(unpremultiply((argb >> 8) & 0xff));
+                image.push(unpremultiply(argb & 0xff));
+                image.push(alpha as u8);
+            }
+        }
+        Some(Snapshot {
+            image,
+            width,
+            height,
+        })
+    }
+
+    /// The tree last given to `update_accessibility`, as a screen reader
+    /// would see the window. Actions are sent with `with_handler`.
+    pub fn accessibility_tree(&self) -> Option<AccessTree> {
+        self.state.upgrade()?.accessibility.borrow().clone()
+    }
+
+    /// The announcements made since this was last called, oldest first, as
+    /// a screen reader would read them out.
+    pub fn take_announcements(&self) -> Vec<(String, Politeness)> {
+        match self.state.upgrade() {
+            Some(state) => state.announcements.borrow_mut().drain(..).collect(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Call `f` with the handler of the window, to send it input or menu
+    /// commands as the user would. Returns `None` if the window is closed.
+    ///
+    /// The handler may change the window as it does for real input; the
+    /// change of size or state is reported when the run loop next runs.
+    pub fn with_handler<R>(&self, f: impl FnOnce(&dyn WinHandler) -> R) -> Option<R> {
+        let state = self.state.upgrade()?;
+        if state.closed.get() {
+            return None;
+        }
+        Some(f(&*state.handler))
+    }
+}
+
+impl IdleHandle {
+    /// Add an idle handler, which is called (once) when the message loop
+    /// is empty. The idle handler will be run from the main UI thread, and
+    /// won't be scheduled if the associated window has been dropped.
+    ///
+    /// Note: the name "idle" suggests that it will be scheduled with a lower
+    /// priority than other UI events, but that's not necessarily the case.
+    pub fn add_idle<F>(&self, callback: F)
+    where
+        F: FnOnce(&dyn Any) + Send + 'static,
+    {
+        if let Some(queue) = self.idle_queue.upgrade() {
+            queue.lock().expect("queue lock").push(Box::new(callback));
+            win_main::wake();
+        }
+    }
+}
@@ -0,0 +1,34 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native views hosted in a window, headless implementation.
+//!
+//! There are no native views to host, so none is ever added.
+
+use piet_common::kurbo::Rect;
+
+/// A native view shown in a window. Headless windows have none.
+pub struct NativeView {
+    _private: (),
+}
+
+impl NativeView {
+    pub fn set_frame(&self, _frame: Rect, _clip: Rect) {}
+
+    pub fn set_visible(&self, _visible: bool) {}
+
+    pub fn focus(&self) {}
+
+    pub fn blur(&self) {}
+}
@@ -0,0 +1,37 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GPU surfaces, headless implementation.
+//!
+//! There are no native windows to render into, so no surface is ever
+//! created.
+
+use piet_common::kurbo::Rect;
+
+use crate::window::RawWindowHandle;
+
+/// A surface for rendering with a GPU API. Headless windows have none.
+pub struct GpuSurface {
+    _private: (),
+}
+
+impl GpuSurface {
+    pub fn set_frame(&self, _rect: Rect) {}
+
+    pub fn set_visible(&self, _visible: bool) {}
+
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        None
+    }
+}
@@ -0,0 +1,107 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Headless main loop.
+//!
+//! The loop runs the idle callbacks, timers, frames and paints of the
+//! windows, and sleeps until the next timer is due or other work arrives.
+//! Frames and paints are paced at a nominal display refresh rate.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use crate::util;
+
+/// The interval between display refreshes, for pacing animations.
+const REFRESH_INTERVAL: Duration = Duration::from_micros(16_667);
+
+static QUIT: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    /// Set when there is new work for the run loop, and signalled to wake
+    /// it.
+    static ref WAKE: (Mutex<bool>, Condvar) = (Mutex::new(false), Condvar::new());
+}
+
+pub struct RunLoop;
+
+impl RunLoop {
+    pub fn new() -> RunLoop {
+        util::init();
+        RunLoop
+    }
+
+    pub fn run(&mut self) {
+        let mut last_refresh: Option<Instant> = None;
+        while !QUIT.swap(false, Ordering::SeqCst) {
+            let now = Instant::now();
+            let refresh = last_refresh.map_or(true, |last| now >= last + REFRESH_INTERVAL);
+            if refresh {
+                last_refresh = Some(now);
+            }
+            let (mut deadline, wants_refresh) = super::run_pending(refresh);
+            if let (true, Some(last)) = (wants_refresh, last_refresh) {
+                let next = last + REFRESH_INTERVAL;
+                deadline = Some(deadline.map_or(next, |deadline| deadline.min(next)));
+            }
+            wait(deadline);
+        }
+    }
+
+    /// Run the idle callbacks, due timers, and requested frames and paints,
+    /// without waiting, and return. Tests can step the application with
+    /// this rather than `run`.
+    pub fn run_pending(&mut self) {
+        super::run_pending(true);
+    }
+}
+
+/// Request to quit the application, exiting the runloop.
+///
+/// This may be called from any thread.
+pub fn request_quit() {
+    QUIT.store(true, Ordering::SeqCst);
+    wake();
+}
+
+/// Wake the run loop to look for new work. This may be called from any
+/// thread.
+pub(crate) fn wake() {
+    let (ref woken, ref condvar) = *WAKE;
+    *woken.lock().expect("wake lock") = true;
+    condvar.notify_one();
+}
+
+/// Sleep until woken, or until `deadline`.
+fn wait(deadline: Option<Instant>) {
+    let (ref woken, ref condvar) = *WAKE;
+    let mut guard = woken.lock().expect("wake lock");
+    if !*guard {
+        guard = match deadline {
+            Some(deadline) => {
+                let now = Instant::now();
+                if deadline <= now {
+                    return;
+                }
+                condvar
+                    .wait_timeout(guard, deadline - now)
+                    .expect("wake lock")
+                    .0
+            }
+            None => condvar.wait(guard).expect("wake lock"),
+        };
+    }
+    *guard = false;
+}
@@ -19,7 +19,16 @@ use std::fmt;
 /// A keyboard event, generated on every key press and key release.
 #[derive(Debug, Clone, Copy)]
 pub struct KeyEvent {
+    /// The physical key, regardless of the keyboard layout. This suits
+    /// bindings that depend on the position of a key, such as WASD movement.
+    pub code: KeyCode,
+    /// The meaning of the key under the active keyboard layout.
+    pub key: Key,
+    /// Which of several keys with the same meaning was used, such as the
+    /// left or right shift key.
+    pub location: KeyLocation,
     /// The platform independent keycode.
+    #[deprecated(note = "please use `code` for the physical key, or `key` for its meaning.")]
     pub key_code: KeyCode,
     /// Whether or not this event is a repeat (the key was held down)
     pub is_repeat: bool,
@@ -39,6 +48,12 @@ pub struct KeyEvent {
 impl KeyEvent {
     /// Create a new `KeyEvent` struct. This accepts either &str or char for the last
     /// two arguments.
+    ///
+    /// The key code is used as the physical key, and the logical key is
+    /// derived from the unmodified text; platforms that know better overwrite
+    /// `code`, `key` and `location` afterwards.
+    #[allow(deprecated)]
+    #[cfg_attr(feature = "headless", allow(dead_code))]
     pub(crate) fn new(
         key_code: impl Into<KeyCode>,
         is_repeat: bool,
@@ -55,8 +70,12 @@ impl KeyEvent {
             StrOrChar::Str(s) => TinyStr::new(s),
         };
 
+        let key_code = key_code.into();
         KeyEvent {
-            key_code: key_code.into(),
+            code: key_code,
+            key: Key::from_text(unmodified_text.as_str(), key_code),
+            location: KeyLocation::from_key_code(key_code),
+            key_code,
             is_repeat,
             modifiers,
             text,
@@ -75,6 +94,7 @@ impl KeyEvent {
     }
 
     /// The unmodified input text for this event. On macOS, for opt+s, this is 's'.
+    #[deprecated(note = "please use `key`.")]
     pub fn unmod_text(&self) -> Option<&str> {
         if self.unmodified_text.len == 0 {
             None
@@ -84,6 +104,56 @@ impl KeyEvent {
     }
 }
 
+/// The meaning of a key, taking the keyboard layout into account.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    /// A key that produces a character. This is the character produced with
+    /// shift applied, but ignoring other modifiers, so that control+shift+s
+    /// is `Character('S')` on a US layout.
+    Character(char),
+    /// A key that does not produce a character, such as an arrow key.
+    Named(KeyCode),
+    /// A dead key, which combines with the next key to produce a character.
+    Dead,
+    Unidentified,
+}
+
+impl Key {
+    /// The logical key for the unmodified text of an event, falling back to
+    /// the key code for keys that don't produce a character.
+    fn from_text(text: &str, key_code: KeyCode) -> Key {
+        let mut chars = text.chars();
+        match (chars.next(), chars.next()) {
+            // Excludes the private use area, where macOS reports
+            // function keys.
+            (Some(c), None) if !c.is_control() && !('\u{F700}'..='\u{F8FF}').contains(&c) => {
+                Key::Character(c)
+            }
+            _ => Key::Named(key_code),
+        }
+    }
+}
+
+/// The location of a key on the keyboard, for keys that appear more than
+/// once.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum KeyLocation {
+    Standard,
+    Left,
+    Right,
+    Numpad,
+}
+
+impl KeyLocation {
+    fn from_key_code(key_code: KeyCode) -> KeyLocation {
+        if key_code.is_numpad() {
+            KeyLocation::Numpad
+        } else {
+            KeyLocation::Standard
+        }
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 pub struct KeyModifiers {
     pub shift: bool,
@@ -228,6 +298,10 @@ pub enum KeyCode {
 pub enum RawKeyCode {
     Windows(i32),
     Mac(u16),
+    /// An X11 hardware keycode, as reported by GDK.
+    Linux(u16),
+    /// The legacy `keyCode` of a DOM keyboard event.
+    Web(u32),
 }
 
 impl KeyCode {
@@ -245,6 +319,275 @@ impl KeyCode {
             _ => false,
         }
     }
+
+    /// Whether the key is on the numeric keypad.
+    pub fn is_numpad(self) -> bool {
+        use KeyCode::*;
+        match self {
+            Numpad0 | Numpad1 | Numpad2 | Numpad3 | Numpad4 | Numpad5 | Numpad6 | Numpad7
+            | Numpad8 | Numpad9 | NumpadEquals | NumpadSubtract | NumpadAdd | NumpadDecimal
+            | NumpadMultiply | NumpadDivide | NumLock | NumpadEnter => true,
+            _ => false,
+        }
+    }
+
+    /// The physical key for a Windows scancode, from bits 16 to 24 of the
+    /// lparam of a key message.
+    #[cfg(any(test, target_os = "windows", feature = "gtk"))]
+    pub(crate) fn from_scancode(scancode: u32, extended: bool) -> Option<KeyCode> {
+        use KeyCode::*;
+        let code = match (scancode, extended) {
+            (0x01, _) => Escape,
+            (0x02, _) => Key1,
+            (0x03, _) => Key2,
+            (0x04, _) => Key3,
+            (0x05, _) => Key4,
+            (0x06, _) => Key5,
+            (0x07, _) => Key6,
+            (0x08, _) => Key7,
+            (0x09, _) => Key8,
+            (0x0A, _) => Key9,
+            (0x0B, _) => Key0,
+            (0x0C, _) => Minus,
+            (0x0D, _) => Equals,
+            (0x0E, _) => Backspace,
+            (0x0F, _) => Tab,
+            (0x10, _) => KeyQ,
+            (0x11, _) => KeyW,
+            (0x12, _) => KeyE,
+            (0x13, _) => KeyR,
+            (0x14, _) => KeyT,
+            (0x15, _) => KeyY,
+            (0x16, _) => KeyU,
+            (0x17, _) => KeyI,
+            (0x18, _) => KeyO,
+            (0x19, _) => KeyP,
+            (0x1A, _) => LeftBracket,
+            (0x1B, _) => RightBracket,
+            (0x1C, false) => Return,
+            (0x1C, true) => NumpadEnter,
+            (0x1D, _) => Control,
+            (0x1E, _) => KeyA,
+            (0x1F, _) => KeyS,
+            (0x20, _) => KeyD,
+            (0x21, _) => KeyF,
+            (0x22, _) => KeyG,
+            (0x23, _) => KeyH,
+            (0x24, _) => KeyJ,
+            (0x25, _) => KeyK,
+            (0x26, _) => KeyL,
+            (0x27, _) => Semicolon,
+            (0x28, _) => Quote,
+            (0x29, _) => Backtick,
+            (0x2A, _) | (0x36, _) => Shift,
+            (0x2B, _) => Backslash,
+            (0x2C, _) => KeyZ,
+            (0x2D, _) => KeyX,
+            (0x2E, _) => KeyC,
+            (0x2F, _) => KeyV,
+            (0x30, _) => KeyB,
+            (0x31, _) => KeyN,
+            (0x32, _) => KeyM,
+            (0x33, _) => Comma,
+            (0x34, _) => Period,
+            (0x35, false) => Slash,
+            (0x35, true) => NumpadDivide,
+            (0x37, false) => NumpadMultiply,
+            (0x37, true) => PrintScreen,
+            (0x38, _) => Alt,
+            (0x39, _) => Space,
+            (0x3A, _) => CapsLock,
+            (0x3B, _) => F1,
+            (0x3C, _) => F2,
+            (0x3D, _) => F3,
+            (0x3E, _) => F4,
+            (0x3F, _) => F5,
+            (0x40, _) => F6,
+            (0x41, _) => F7,
+            (0x42, _) => F8,
+            (0x43, _) => F9,
+            (0x44, _) => F10,
+            (0x45, false) => Pause,
+            (0x45, true) => NumLock,
+            (0x46, _) => ScrollLock,
+            (0x47, false) => Numpad7,
+            (0x47, true) => Home,
+            (0x48, false) => Numpad8,
+            (0x48, true) => ArrowUp,
+            (0x49, false) => Numpad9,
+            (0x49, true) => PageUp,
+            (0x4A, _) => NumpadSubtract,
+            (0x4B, false) => Numpad4,
+            (0x4B, true) => ArrowLeft,
+            (0x4C, _) => Numpad5,
+            (0x4D, false) => Numpad6,
+            (0x4D, true) => ArrowRight,
+            (0x4E, _) => NumpadAdd,
+            (0x4F, false) => Numpad1,
+            (0x4F, true) => End,
+            (0x50, false) => Numpad2,
+            (0x50, true) => ArrowDown,
+            (0x51, false) => Numpad3,
+            (0x51, true) => PageDown,
+            (0x52, false) => Numpad0,
+            (0x52, true) => Insert,
+            (0x53, false) => NumpadDecimal,
+            (0x53, true) => Delete,
+            (0x57, _) => F11,
+            (0x58, _) => F12,
+            (0x59, _) => NumpadEquals,
+            (0x5B, true) | (0x5C, true) => Meta,
+            (0x5D, true) => Menu,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// The physical key for an X11 hardware keycode. These are Linux evdev
+    /// codes offset by 8, which match the Windows scancodes for most of the
+    /// keyboard.
+    #[cfg(any(test, feature = "gtk"))]
+    pub(crate) fn from_hardware_keycode(keycode: u16) -> Option<KeyCode> {
+        use KeyCode::*;
+        let code = match keycode.checked_sub(8)? {
+            0x37 => NumpadMultiply,
+            0x45 => NumLock,
+            evdev @ 0x01..=0x58 => return KeyCode::from_scancode(u32::from(evdev), false),
+            96 => NumpadEnter,
+            97 => Control,
+            98 => NumpadDivide,
+            99 => PrintScreen,
+            100 => Alt,
+            102 => Home,
+            103 => ArrowUp,
+            104 => PageUp,
+            105 => ArrowLeft,
+            106 => ArrowRight,
+            107 => End,
+            108 => ArrowDown,
+            109 => PageDown,
+            110 => Insert,
+            111 => Delete,
+            117 => NumpadEquals,
+            119 => Pause,
+            125 | 126 => Meta,
+            127 => Menu,
+            _ => return None,
+        };
+        Some(code)
+    }
+
+    /// The physical key for the `code` of a DOM keyboard event, which names
+    /// keys the same way as this enum.
+    #[cfg(any(test, target_arch = "wasm32"))]
+    pub(crate) fn from_dom_code(code: &str) -> Option<KeyCode> {
+        use KeyCode::*;
+        let code = match code {
+            "Escape" => Escape,
+            "Backquote" => Backtick,
+            "Digit0" => Key0,
+            "Digit1" => Key1,
+            "Digit2" => Key2,
+            "Digit3" => Key3,
+            "Digit4" => Key4,
+            "Digit5" => Key5,
+            "Digit6" => Key6,
+            "Digit7" => Key7,
+            "Digit8" => Key8,
+            "Digit9" => Key9,
+            "Minus" => Minus,
+            "Equal" => Equals,
+            "Backspace" => Backspace,
+            "Tab" => Tab,
+            "KeyQ" => KeyQ,
+            "KeyW" => KeyW,
+            "KeyE" => KeyE,
+            "KeyR" => KeyR,
+            "KeyT" => KeyT,
+            "KeyY" => KeyY,
+            "KeyU" => KeyU,
+            "KeyI" => KeyI,
+            "KeyO" => KeyO,
+            "KeyP" => KeyP,
+            "BracketLeft" => LeftBracket,
+            "BracketRight" => RightBracket,
+            "Enter" => Return,
+            "KeyA" => KeyA,
+            "KeyS" => KeyS,
+            "KeyD" => KeyD,
+            "KeyF" => KeyF,
+            "KeyG" => KeyG,
+            "KeyH" => KeyH,
+            "KeyJ" => KeyJ,
+            "KeyK" => KeyK,
+            "KeyL" => KeyL,
+            "Semicolon" => Semicolon,
+            "Quote" => Quote,
+            "Backslash" => Backslash,
+            "KeyZ" => KeyZ,
+            "KeyX" => KeyX,
+            "KeyC" => KeyC,
+            "KeyV" => KeyV,
+            "KeyB" => KeyB,
+            "KeyN" => KeyN,
+            "KeyM" => KeyM,
+            "Comma" => Comma,
+            "Period" => Period,
+            "Slash" => Slash,
+            "ControlLeft" | "ControlRight" => Control,
+            "AltLeft" | "AltRight" => Alt,
+            "ShiftLeft" | "ShiftRight" => Shift,
+            "MetaLeft" | "MetaRight" | "OSLeft" | "OSRight" => Meta,
+            "ContextMenu" => Menu,
+            "Space" => Space,
+            "CapsLock" => CapsLock,
+            "F1" => F1,
+            "F2" => F2,
+            "F3" => F3,
+            "F4" => F4,
+            "F5" => F5,
+            "F6" => F6,
+            "F7" => F7,
+            "F8" => F8,
+            "F9" => F9,
+            "F10" => F10,
+            "F11" => F11,
+            "F12" => F12,
+            "PrintScreen" => PrintScreen,
+            "ScrollLock" => ScrollLock,
+            "Pause" => Pause,
+            "Insert" => Insert,
+            "Delete" => Delete,
+            "Home" => Home,
+            "End" => End,
+            "PageUp" => PageUp,
+            "PageDown" => PageDown,
+            "Numpad0" => Numpad0,
+            "Numpad1" => Numpad1,
+            "Numpad2" => Numpad2,
+            "Numpad3" => Numpad3,
+            "Numpad4" => Numpad4,
+            "Numpad5" => Numpad5,
+            "Numpad6" => Numpad6,
+            "Numpad7" => Numpad7,
+            "Numpad8" => Numpad8,
+            "Numpad9" => Numpad9,
+            "NumpadEqual" => NumpadEquals,
+            "NumpadSubtract" => NumpadSubtract,
+            "NumpadAdd" => NumpadAdd,
+            "NumpadDecimal" => NumpadDecimal,
+            "NumpadMultiply" => NumpadMultiply,
+            "NumpadDivide" => NumpadDivide,
+            "NumLock" => NumLock,
+            "NumpadEnter" => NumpadEnter,
+            "ArrowUp" => ArrowUp,
+            "ArrowDown" => ArrowDown,
+            "ArrowLeft" => ArrowLeft,
+            "ArrowRight" => ArrowRight,
+            _ => return None,
+        };
+        Some(code)
+    }
 }
 
 #[cfg(any(test, target_os = "macos"))]
@@ -290,7 +633,7 @@ impl From<u16> for KeyCode {
             0x24 => KeyCode::Return,
             0x25 => KeyCode::KeyL,
             0x26 => KeyCode::KeyJ,
-            0x27 => KeyCode::Backtick,
+            0x27 => KeyCode::Quote,
             0x28 => KeyCode::KeyK,
             0x29 => KeyCode::Semicolon,
             0x2a => KeyCode::Backslash,
@@ -305,15 +648,11 @@ impl From<u16> for KeyCode {
             0x33 => KeyCode::Backspace,
             //0x34 => unkown,
             0x35 => KeyCode::Escape,
-            //0x36 => KeyCode::RLogo,
-            //0x37 => KeyCode::LLogo,
-            //0x38 => KeyCode::LShift,
+            0x36 | 0x37 => KeyCode::Meta,
+            0x38 | 0x3c => KeyCode::Shift,
             0x39 => KeyCode::CapsLock,
-            //0x3a => KeyCode::LAlt,
-            //0x3b => KeyCode::LControl,
-            //0x3c => KeyCode::RShift,
-            //0x3d => KeyCode::RAlt,
-            //0x3e => KeyCode::RControl,
+            0x3a | 0x3d => KeyCode::Alt,
+            0x3b | 0x3e => KeyCode::Control,
             //0x3f => Fn key,
             //0x40 => KeyCode::F17,
             0x41 => KeyCode::NumpadDecimal,
@@ -702,6 +1041,52 @@ mod tests {
         );
     }
 
+    #[test]
+    fn scancode() {
+        assert_eq!(KeyCode::from_scancode(0x11, false), Some(KeyCode::KeyW));
+        assert_eq!(
+            KeyCode::from_scancode(0x1C, true),
+            Some(KeyCode::NumpadEnter)
+        );
+        assert_eq!(KeyCode::from_scancode(0x48, true), Some(KeyCode::ArrowUp));
+        assert_eq!(KeyCode::from_scancode(0x48, false), Some(KeyCode::Numpad8));
+        assert_eq!(KeyCode::from_scancode(0x7F, false), None);
+    }
+
+    #[test]
+    fn hardware_keycode() {
+        assert_eq!(KeyCode::from_hardware_keycode(25), Some(KeyCode::KeyW));
+        assert_eq!(KeyCode::from_hardware_keycode(77), Some(KeyCode::NumLock));
+        assert_eq!(KeyCode::from_hardware_keycode(111), Some(KeyCode::ArrowUp));
+        assert_eq!(KeyCode::from_hardware_keycode(128), None);
+        assert_eq!(KeyCode::from_hardware_keycode(3), None);
+    }
+
+    #[test]
+    fn dom_code() {
+        assert_eq!(KeyCode::from_dom_code("KeyW"), Some(KeyCode::KeyW));
+        assert_eq!(KeyCode::from_dom_code("Digit1"), Some(KeyCode::Key1));
+        assert_eq!(KeyCode::from_dom_code("ShiftRight"), Some(KeyCode::Shift));
+        assert_eq!(KeyCode::from_dom_code("F13"), None);
+    }
+
+    #[test]
+    fn logical_key() {
+        let event = KeyEvent::new(KeyCode::KeyQ, false, Default::default(), "a", "a");
+        assert_eq!(event.code, KeyCode::KeyQ);
+        assert_eq!(event.key, Key::Character('a'));
+        let event = KeyEvent::new(
+            KeyCode::ArrowUp,
+            false,
+            Default::default(),
+            "\u{F700}",
+            "\u{F700}",
+        );
+        assert_eq!(event.key, Key::Named(KeyCode::ArrowUp));
+        let event = KeyEvent::new(KeyCode::NumpadEnter, false, Default::default(), "", "");
+        assert_eq!(event.location, KeyLocation::Numpad);
+    }
+
     #[test]
     #[cfg(target_os = "windows")]
     fn win_vk() {
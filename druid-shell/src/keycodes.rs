@@ -14,6 +14,10 @@
 
 //! Keycode constants.
 
+use std::fmt;
+
+use crate::keyboard::{Key, KeyCode, KeyEvent, KeyModifiers};
+
 /// The type for keyboard modifiers.
 // TODO: migrate this type to bitflags.
 pub type Modifiers = u32;
@@ -33,6 +37,11 @@ pub const M_SHIFT: u32 = 4;
 pub const M_META: u32 = 8;
 
 /// A specifier for a menu shortcut key.
+///
+/// This is also the type of a key chord in a keymap, so that the same
+/// description can be used for matching key events and for displaying the
+/// shortcut in a menu.
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub struct MenuKey {
     /// Modifiers of menu key.
     ///
@@ -43,6 +52,7 @@ pub struct MenuKey {
 }
 
 /// A specifier for a key (not including modifiers).
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum KeySpec {
     /// The key represents a single Unicode codepoint.
     Char(char),
@@ -84,7 +94,54 @@ pub const fn command_modifier() -> Modifiers {
     }
 }
 
+/// How the key of a shortcut is compared against key events.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum KeyMatch {
+    /// Compare against the character the key produces, so that shortcuts
+    /// follow the user's keyboard layout. Where the layout does not produce
+    /// an ASCII character, as for the letters of a Cyrillic layout, the
+    /// physical key also matches.
+    Auto,
+    /// Compare against the physical key that produces the character on a
+    /// US layout, whatever the layout; for keys chosen by their position.
+    Physical,
+    /// Compare against the character the key produces only.
+    Logical,
+}
+
+impl Default for KeyMatch {
+    fn default() -> KeyMatch {
+        KeyMatch::Auto
+    }
+}
+
+/// Get the modifier mask corresponding to the modifiers of a key event.
+pub fn modifiers_mask(mods: KeyModifiers) -> Modifiers {
+    let mut mask = 0;
+    if mods.alt {
+        mask |= M_ALT;
+    }
+    if mods.ctrl {
+        mask |= M_CTRL;
+    }
+    if mods.shift {
+        mask |= M_SHIFT;
+    }
+    if mods.meta {
+        mask |= M_META;
+    }
+    mask
+}
+
 impl MenuKey {
+    /// Create a new key specifier from modifiers and a key.
+    pub fn new(modifiers: Modifiers, key: impl Into<KeySpec>) -> MenuKey {
+        MenuKey {
+            modifiers,
+            key: key.into(),
+        }
+    }
+
     pub fn command(k: impl Into<KeySpec>) -> MenuKey {
         MenuKey {
             modifiers: command_modifier(),
@@ -112,6 +169,125 @@ impl MenuKey {
     }
 }
 
+impl MenuKey {
+    /// Determine whether a key event triggers this key specifier, comparing
+    /// the key as described for `KeyMatch::Auto`.
+    ///
+    /// Modifiers must match exactly. The physical key is also used when the
+    /// event does not carry a character, such as the control characters
+    /// produced while control is held.
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        self.matches_with(event, KeyMatch::Auto)
+    }
+
+    /// Determine whether a key event triggers this key specifier, comparing
+    /// the key as given by `mode`.
+    pub fn matches_with(&self, event: &KeyEvent, mode: KeyMatch) -> bool {
+        if modifiers_mask(event.modifiers) != self.modifiers {
+            return false;
+        }
+        let c = match self.key {
+            KeySpec::Char(c) => c,
+            KeySpec::None => return false,
+        };
+        let physical = key_code_for_char(c) == Some(event.code);
+        let key = match event.key {
+            Key::Character(key) if !key.is_control() => Some(key),
+            _ => None,
+        };
+        let logical = key.map_or(false, |key| key.to_lowercase().eq(c.to_lowercase()));
+        match mode {
+            KeyMatch::Auto => logical || (physical && key.map_or(true, |key| !key.is_ascii())),
+            KeyMatch::Physical => physical,
+            KeyMatch::Logical => logical,
+        }
+    }
+}
+
+/// Formats the key as it is conventionally displayed in menus on Windows,
+/// such as "Ctrl+Shift+Z".
+impl fmt::Display for MenuKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let c = match self.key {
+            KeySpec::Char(c) => c,
+            KeySpec::None => return Ok(()),
+        };
+        if self.modifiers & M_CTRL != 0 {
+            write!(f, "Ctrl+")?;
+        }
+        if self.modifiers & M_ALT != 0 {
+            write!(f, "Alt+")?;
+        }
+        if self.modifiers & M_SHIFT != 0 {
+            write!(f, "Shift+")?;
+        }
+        if self.modifiers & M_META != 0 {
+            write!(f, "Win+")?;
+        }
+        for upper in c.to_uppercase() {
+            write!(f, "{}", upper)?;
+        }
+        Ok(())
+    }
+}
+
+/// The key code of the key that produces a character on a US layout, for
+/// matching when the event text is not usable.
+pub(crate) fn key_code_for_char(c: char) -> Option<KeyCode> {
+    let code = match c.to_ascii_lowercase() {
+        'a' => KeyCode::KeyA,
+        'b' => KeyCode::KeyB,
+        'c' => KeyCode::KeyC,
+        'd' => KeyCode::KeyD,
+        'e' => KeyCode::KeyE,
+        'f' => KeyCode::KeyF,
+        'g' => KeyCode::KeyG,
+        'h' => KeyCode::KeyH,
+        'i' => KeyCode::KeyI,
+        'j' => KeyCode::KeyJ,
+        'k' => KeyCode::KeyK,
+        'l' => KeyCode::KeyL,
+        'm' => KeyCode::KeyM,
+        'n' => KeyCode::KeyN,
+        'o' => KeyCode::KeyO,
+        'p' => KeyCode::KeyP,
+        'q' => KeyCode::KeyQ,
+        'r' => KeyCode::KeyR,
+        's' => KeyCode::KeyS,
+        't' => KeyCode::KeyT,
+        'u' => KeyCode::KeyU,
+        'v' => KeyCode::KeyV,
+        'w' => KeyCode::KeyW,
+        'x' => KeyCode::KeyX,
+        'y' => KeyCode::KeyY,
+        'z' => KeyCode::KeyZ,
+        '0' => KeyCode::Key0,
+        '1' => KeyCode::Key1,
+        '2' => KeyCode::Key2,
+        '3' => KeyCode::Key3,
+        '4' => KeyCode::Key4,
+        '5' => KeyCode::Key5,
+        '6' => KeyCode::Key6,
+        '7' => KeyCode::Key7,
+        '8' => KeyCode::Key8,
+        '9' => KeyCode::Key9,
+        '`' => KeyCode::Backtick,
+        '-' => KeyCode::Minus,
+        '=' => KeyCode::Equals,
+        '[' => KeyCode::LeftBracket,
+        ']' => KeyCode::RightBracket,
+        '\\' => KeyCode::Backslash,
+        ';' => KeyCode::Semicolon,
+        '\'' => KeyCode::Quote,
+        ',' => KeyCode::Comma,
+        '.' => KeyCode::Period,
+        '/' => KeyCode::Slash,
+        ' ' => KeyCode::Space,
+        _ => return None,
+    };
+    Some(code)
+}
+
 // Most keys on Windows have VK_ values defined in use winapi::um::winuser,
 // but ASCII digits and letters are the exception. Fill those in here
 // (following https://github.com/Eljay/directx) so all keys have constants,
@@ -154,3 +330,55 @@ pub mod win_vks {
     pub const VK_Y: i32 = 0x59;
     pub const VK_Z: i32 = 0x5A;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn menu_key_matches() {
+        let save = MenuKey::new(M_CTRL, 's');
+        let event = KeyEvent::new(KeyCode::KeyS, false, mods(M_CTRL), "s", "s");
+        assert!(save.matches(&event));
+        // Windows delivers a control character as the text.
+        let event = KeyEvent::new(KeyCode::KeyS, false, mods(M_CTRL), '\x13', '\x13');
+        assert!(save.matches(&event));
+        let event = KeyEvent::new(KeyCode::KeyS, false, mods(M_CTRL | M_SHIFT), "S", "S");
+        assert!(!save.matches(&event));
+        // The layout produces 'a' from the physical Q key.
+        let quit = MenuKey::new(M_CTRL, 'q');
+        let event = KeyEvent::new(KeyCode::KeyQ, false, mods(M_CTRL), "a", "a");
+        assert!(!quit.matches(&event));
+    }
+
+    #[test]
+    fn menu_key_match_modes() {
+        let undo = MenuKey::new(M_CTRL, 'z');
+        // A Cyrillic layout produces 'я' from the physical Z key.
+        let event = KeyEvent::new(KeyCode::KeyZ, false, mods(M_CTRL), "я", "я");
+        assert!(undo.matches(&event));
+        assert!(undo.matches_with(&event, KeyMatch::Physical));
+        assert!(!undo.matches_with(&event, KeyMatch::Logical));
+        // An AZERTY layout produces 'z' from the physical W key.
+        let event = KeyEvent::new(KeyCode::KeyW, false, mods(M_CTRL), "z", "z");
+        assert!(undo.matches(&event));
+        assert!(!undo.matches_with(&event, KeyMatch::Physical));
+        assert!(undo.matches_with(&event, KeyMatch::Logical));
+    }
+
+    #[test]
+    fn menu_key_display() {
+        let redo = MenuKey::new(M_CTRL | M_SHIFT, 'z');
+        assert_eq!(redo.to_string(), "Ctrl+Shift+Z");
+        assert_eq!(MenuKey::from(()).to_string(), "");
+    }
+
+    fn mods(mask: Modifiers) -> KeyModifiers {
+        KeyModifiers {
+            shift: mask & M_SHIFT != 0,
+            alt: mask & M_ALT != 0,
+            ctrl: mask & M_CTRL != 0,
+            meta: mask & M_META != 0,
+        }
+    }
+}
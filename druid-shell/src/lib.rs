@@ -25,12 +25,33 @@ extern crate winapi;
 #[macro_use]
 extern crate objc;
 
-#[macro_use]
+#[cfg_attr(
+    any(
+        target_os = "windows",
+        target_os = "macos",
+        feature = "gtk",
+        feature = "headless"
+    ),
+    macro_use
+)]
 extern crate lazy_static;
 
+pub mod accessibility;
+pub mod alert;
+pub mod bidi;
+pub mod bitmap;
+pub mod blend;
+pub mod clipboard;
+pub mod commands;
+pub mod dialog;
+pub mod drag;
 pub mod error;
+pub mod font_fallback;
 pub mod keyboard;
 pub mod keycodes;
+pub mod text_rendering;
+pub mod timer;
+pub mod vector;
 pub mod window;
 
 #[cfg(target_os = "windows")]
@@ -45,11 +66,27 @@ pub mod mac;
 #[cfg(target_os = "macos")]
 pub use mac as platform;
 
+#[cfg(all(target_os = "linux", feature = "gtk", not(feature = "headless")))]
+pub mod gtk;
+#[cfg(all(target_os = "linux", feature = "gtk", not(feature = "headless")))]
+pub use gtk as platform;
+
+#[cfg(all(target_os = "linux", feature = "headless"))]
+pub mod headless;
+#[cfg(all(target_os = "linux", feature = "headless"))]
+pub use headless as platform;
+
+#[cfg(target_arch = "wasm32")]
+pub mod web;
+#[cfg(target_arch = "wasm32")]
+pub use web as platform;
+
 pub use error::Error;
 
 pub use platform::application;
-pub use platform::dialog;
 pub use platform::menu;
+pub use platform::native_view;
+pub use platform::surface;
 pub use platform::util;
 pub use platform::win_main as runloop; // TODO: rename to "runloop"
 pub use platform::WindowBuilder;
@@ -0,0 +1,476 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The accessibility tree, macOS implementation.
+//!
+//! Each node but the root is an `NSAccessibilityElement`, and the view
+//! stands in for the root. The elements answer their frame, focus and
+//! actions from the last tree, and actions are sent to the handler.
+
+use std::collections::HashMap;
+use std::ffi::c_void;
+
+use cocoa::appkit::NSApp;
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{
+    NSArray, NSAutoreleasePool, NSInteger, NSPoint, NSRect, NSSize, NSUInteger,
+};
+use objc::declare::ClassDecl;
+use objc::rc::StrongPtr;
+use objc::runtime::{Class, Object, Sel};
+
+use piet_common::kurbo::Point;
+
+use super::ViewState;
+use crate::accessibility::{
+    AccessNode, AccessTree, Action, ActionRequest, NodeId, Politeness, Role,
+};
+use crate::util::{from_nsstring, make_nsstring};
+
+#[link(name = "AppKit", kind = "framework")]
+extern "C" {
+    static NSAccessibilityFocusedUIElementChangedNotification: id;
+    static NSAccessibilityValueChangedNotification: id;
+    static NSAccessibilityUIElementDestroyedNotification: id;
+    static NSAccessibilityAnnouncementRequestedNotification: id;
+    static NSAccessibilityAnnouncementKey: id;
+    static NSAccessibilityPriorityKey: id;
+
+    fn NSAccessibilityFrameInView(view: id, frame: NSRect) -> NSRect;
+    fn NSAccessibilityPostNotification(element: id, notification: id);
+    fn NSAccessibilityPostNotificationWithUserInfo(element: id, notification: id, user_info: id);
+}
+
+// Values of `NSAccessibilityPriorityLevel`.
+const NS_ACCESSIBILITY_PRIORITY_MEDIUM: NSInteger = 50;
+const NS_ACCESSIBILITY_PRIORITY_HIGH: NSInteger = 90;
+
+/// The tree last given to `update`, and the element of each of its nodes.
+#[derive(Default)]
+pub(super) struct AccessState {
+    tree: Option<AccessTree>,
+    elements: HashMap<NodeId, StrongPtr>,
+}
+
+impl Drop for AccessState {
+    fn drop(&mut self) {
+        // Assistive technology may keep elements past the view.
+        for element in self.elements.values() {
+            unsafe { detach(**element) };
+        }
+    }
+}
+
+// Wrap pointer because lazy_static requires Sync.
+struct ElementClass(*const Class);
+unsafe impl Sync for ElementClass {}
+
+lazy_static! {
+    static ref ELEMENT_CLASS: ElementClass = unsafe {
+        let mut decl = ClassDecl::new("DruidAccessibilityElement", class!(NSAccessibilityElement))
+            .expect("Accessibility element class defined");
+        decl.add_ivar::<*mut c_void>("view");
+        decl.add_ivar::<u64>("nodeId");
+        decl.add_method(
+            sel!(accessibilityFrame),
+            element_frame as extern "C" fn(&Object, Sel) -> NSRect,
+        );
+        decl.add_method(
+            sel!(isAccessibilityFocused),
+            element_is_focused as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(setAccessibilityFocused:),
+            element_set_focused as extern "C" fn(&Object, Sel, BOOL),
+        );
+        decl.add_method(
+            sel!(accessibilityPerformPress),
+            element_press as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(accessibilityPerformIncrement),
+            element_increment as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(accessibilityPerformDecrement),
+            element_decrement as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(setAccessibilityValue:),
+            element_set_value as extern "C" fn(&Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(isAccessibilitySelectorAllowed:),
+            element_selector_allowed as extern "C" fn(&Object, Sel, Sel) -> BOOL,
+        );
+        ElementClass(decl.register())
+    };
+}
+
+pub(super) unsafe fn add_methods(decl: &mut ClassDecl) {
+    decl.add_method(
+        sel!(accessibilityChildren),
+        view_children as extern "C" fn(&mut Object, Sel) -> id,
+    );
+    decl.add_method(
+        sel!(accessibilityFocusedUIElement),
+        view_focused_element as extern "C" fn(&mut Object, Sel) -> id,
+    );
+    decl.add_method(
+        sel!(accessibilityHitTest:),
+        view_hit_test as extern "C" fn(&mut Object, Sel, NSPoint) -> id,
+    );
+}
+
+unsafe fn view_state<'a>(view: id) -> &'a mut ViewState {
+    let view_state: *mut c_void = *(*view).get_ivar("viewState");
+    &mut *(view_state as *mut ViewState)
+}
+
+/// Replace the tree, creating and updating the elements of its nodes, and
+/// tell assistive technology what changed.
+pub(super) unsafe fn update(view: id, tree: AccessTree) {
+    let state = &mut view_state(view).access;
+    let old_tree = state.tree.take().unwrap_or_default();
+    let removed: Vec<NodeId> = state
+        .elements
+        .keys()
+        .filter(|&&node_id| node_id == tree.root || tree.node(node_id).is_none())
+        .cloned()
+        .collect();
+    for node_id in removed {
+        if let Some(element) = state.elements.remove(&node_id) {
+            detach(*element);
+            NSAccessibilityPostNotification(
+                *element,
+                NSAccessibilityUIElementDestroyedNotification,
+            );
+        }
+    }
+    for (node_id, node) in &tree.nodes {
+        if *node_id == tree.root {
+            continue;
+        }
+        let element = **state
+            .elements
+            .entry(*node_id)
+            .or_insert_with(|| new_element(view, *node_id));
+        set_properties(element, node);
+        let changed = match old_tree.node(*node_id) {
+            Some(old) => old.value != node.value || old.numeric_value != node.numeric_value,
+            None => false,
+        };
+        if changed {
+            NSAccessibilityPostNotification(element, NSAccessibilityValueChangedNotification);
+        }
+    }
+    // The parents and children, now that all the elements exist.
+    for (node_id, node) in &tree.nodes {
+        let parent = state
+            .elements
+            .get(node_id)
+            .map_or(view, |element| **element);
+        let children: Vec<id> = node
+            .children
+            .iter()
+            .filter_map(|child| state.elements.get(child))
+            .map(|element| **element)
+            .collect();
+        for &child in &children {
+            let () = msg_send![child, setAccessibilityParent: parent];
+        }
+        if parent != view {
+            let children = NSArray::arrayWithObjects(nil, &children);
+            let () = msg_send![parent, setAccessibilityChildren: children];
+        }
+    }
+    let focus_changed = old_tree.focus != tree.focus;
+    state.tree = Some(tree);
+    if focus_changed {
+        NSAccessibilityPostNotification(
+            focused_element(view),
+            NSAccessibilityFocusedUIElementChangedNotification,
+        );
+    }
+}
+
+/// Ask assistive technology to read out `text`.
+pub(super) unsafe fn announce(text: &str, politeness: Politeness) {
+    let priority = match politeness {
+        Politeness::Polite => NS_ACCESSIBILITY_PRIORITY_MEDIUM,
+        Politeness::Assertive => NS_ACCESSIBILITY_PRIORITY_HIGH,
+    };
+    let priority: id = msg_send![class!(NSNumber), numberWithInteger: priority];
+    let keys = [NSAccessibilityAnnouncementKey, NSAccessibilityPriorityKey];
+    let values = [make_nsstring(text).autorelease(), priority];
+    let user_info: id = msg_send![class!(NSDictionary),
+        dictionaryWithObjects: values.as_ptr()
+        forKeys: keys.as_ptr()
+        count: keys.len() as NSUInteger];
+    NSAccessibilityPostNotificationWithUserInfo(
+        NSApp(),
+        NSAccessibilityAnnouncementRequestedNotification,
+        user_info,
+    );
+}
+
+unsafe fn new_element(view: id, node_id: NodeId) -> StrongPtr {
+    let element: id = msg_send![ELEMENT_CLASS.0, new];
+    (*element).set_ivar("view", view as *mut c_void);
+    (*element).set_ivar("nodeId", node_id);
+    let () = msg_send![element, setAccessibilityElement: YES];
+    StrongPtr::new(element)
+}
+
+/// Cut the element off from the view, after which it answers nothing.
+unsafe fn detach(element: id) {
+    (*element).set_ivar("view", nil as *mut c_void);
+}
+
+unsafe fn set_properties(element: id, node: &AccessNode) {
+    let (role, subrole) = match node.role {
+        Role::Group | Role::ListItem | Role::Window => ("AXGroup", None),
+        Role::Label => ("AXStaticText", None),
+        Role::Button => ("AXButton", None),
+        Role::CheckBox => ("AXCheckBox", None),
+        Role::TextInput => ("AXTextField", None),
+        Role::PasswordInput => ("AXTextField", Some("AXSecureTextField")),
+        Role::Slider => ("AXSlider", None),
+        Role::ProgressIndicator => ("AXProgressIndicator", None),
+        Role::Image => ("AXImage", None),
+        Role::List => ("AXList", None),
+        Role::Menu => ("AXMenu", None),
+        Role::MenuItem => ("AXMenuItem", None),
+    };
+    let () = msg_send![element, setAccessibilityRole: make_nsstring(role).autorelease()];
+    let subrole = subrole.map_or(nil, |subrole| make_nsstring(subrole).autorelease());
+    let () = msg_send![element, setAccessibilitySubrole: subrole];
+    let label = node
+        .name
+        .as_ref()
+        .map_or(nil, |name| make_nsstring(name).autorelease());
+    let () = msg_send![element, setAccessibilityLabel: label];
+    let value = match (node.role, node.numeric_value, &node.value) {
+        (Role::PasswordInput, _, _) => nil,
+        (_, Some(value), _) => msg_send![class!(NSNumber), numberWithDouble: value],
+        (_, None, Some(value)) => make_nsstring(value).autorelease(),
+        (_, None, None) => nil,
+    };
+    // The element's own `setAccessibilityValue:` is for assistive technology.
+    let () = msg_send![super(element, class!(NSAccessibilityElement)),
+        setAccessibilityValue: value];
+    let min_value = node.min_value.map_or(
+        nil,
+        |min| msg_send![class!(NSNumber), numberWithDouble: min],
+    );
+    let () = msg_send![element, setAccessibilityMinValue: min_value];
+    let max_value = node.max_value.map_or(
+        nil,
+        |max| msg_send![class!(NSNumber), numberWithDouble: max],
+    );
+    let () = msg_send![element, setAccessibilityMaxValue: max_value];
+}
+
+/// The view of the element and the node it stands for, in the last tree.
+unsafe fn element_node<'a>(element: &Object) -> Option<(id, NodeId, &'a AccessNode)> {
+    let view: *mut c_void = *element.get_ivar("view");
+    if view.is_null() {
+        return None;
+    }
+    let view = view as id;
+    let node_id: u64 = *element.get_ivar("nodeId");
+    let node = view_state(view).access.tree.as_ref()?.node(node_id)?;
+    Some((view, node_id, node))
+}
+
+/// Send an action to the handler, if the node supports it.
+fn perform(element: &Object, action: Action, value: Option<String>) -> BOOL {
+    unsafe {
+        let (view, node_id, node) = match element_node(element) {
+            Some(found) => found,
+            None => return NO,
+        };
+        if !node.supports(action) {
+            return NO;
+        }
+        let request = ActionRequest {
+            node: node_id,
+            action,
+            value,
+        };
+        if view_state(view).handler.accessibility_action(request) {
+            YES
+        } else {
+            NO
+        }
+    }
+}
+
+extern "C" fn element_frame(this: &Object, _: Sel) -> NSRect {
+    unsafe {
+        match element_node(this) {
+            // The view is flipped, like the bounds.
+            Some((view, _, node)) => {
+                let b = node.bounds;
+                let frame =
+                    NSRect::new(NSPoint::new(b.x0, b.y0), NSSize::new(b.width(), b.height()));
+                NSAccessibilityFrameInView(view, frame)
+            }
+            None => NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(0.0, 0.0)),
+        }
+    }
+}
+
+extern "C" fn element_is_focused(this: &Object, _: Sel) -> BOOL {
+    unsafe {
+        let focused = element_node(this).and_then(|(view, node_id, _)| {
+            let tree = view_state(view).access.tree.as_ref()?;
+            Some(tree.focus == Some(node_id))
+        });
+        if focused == Some(true) {
+            YES
+        } else {
+            NO
+        }
+    }
+}
+
+extern "C" fn element_set_focused(this: &Object, _: Sel, focused: BOOL) {
+    if focused == YES {
+        perform(this, Action::Focus, None);
+    }
+}
+
+extern "C" fn element_press(this: &Object, _: Sel) -> BOOL {
+    perform(this, Action::Click, None)
+}
+
+extern "C" fn element_increment(this: &Object, _: Sel) -> BOOL {
+    perform(this, Action::Increment, None)
+}
+
+extern "C" fn element_decrement(this: &Object, _: Sel) -> BOOL {
+    perform(this, Action::Decrement, None)
+}
+
+extern "C" fn element_set_value(this: &Object, _: Sel, value: id) {
+    // The value may be a number; the description of a string is itself.
+    let value = unsafe { from_nsstring(msg_send![value, description]) };
+    perform(this, Action::SetValue, Some(value));
+}
+
+/// Only offer the actions the node supports.
+extern "C" fn element_selector_allowed(this: &Object, _: Sel, selector: Sel) -> BOOL {
+    let action = if selector == sel!(accessibilityPerformPress) {
+        Some(Action::Click)
+    } else if selector == sel!(accessibilityPerformIncrement) {
+        Some(Action::Increment)
+    } else if selector == sel!(accessibilityPerformDecrement) {
+        Some(Action::Decrement)
+    } else if selector == sel!(setAccessibilityValue:) {
+        Some(Action::SetValue)
+    } else if selector == sel!(setAccessibilityFocused:) {
+        Some(Action::Focus)
+    } else {
+        None
+    };
+    unsafe {
+        match action {
+            Some(action) => match element_node(this) {
+                Some((_, _, node)) if node.supports(action) => YES,
+                _ => NO,
+            },
+            None => msg_send![super(this, class!(NSAccessibilityElement)),
+                isAccessibilitySelectorAllowed: selector],
+        }
+    }
+}
+
+/// The elements of the children of the root, after the subviews.
+extern "C" fn view_children(this: &mut Object, _: Sel) -> id {
+    unsafe {
+        let superclass = msg_send![this, superclass];
+        let subviews: id = msg_send![super(this, superclass), accessibilityChildren];
+        let state = &view_state(this).access;
+        let tree = match state.tree {
+            Some(ref tree) => tree,
+            None => return subviews,
+        };
+        let children: Vec<id> = tree
+            .node(tree.root)
+            .map(|root| root.children.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|child| state.elements.get(child))
+            .map(|element| **element)
+            .collect();
+        let children = NSArray::arrayWithObjects(nil, &children);
+        if subviews == nil {
+            children
+        } else {
+            msg_send![subviews, arrayByAddingObjectsFromArray: children]
+        }
+    }
+}
+
+unsafe fn focused_element(view: id) -> id {
+    let state = &view_state(view).access;
+    state
+        .tree
+        .as_ref()
+        .and_then(|tree| tree.focus)
+        .and_then(|focus| state.elements.get(&focus))
+        .map_or(view, |element| **element)
+}
+
+extern "C" fn view_focused_element(this: &mut Object, _: Sel) -> id {
+    unsafe { focused_element(this) }
+}
+
+/// The element of the innermost node under the point, in screen
+/// coordinates.
+extern "C" fn view_hit_test(this: &mut Object, _: Sel, point: NSPoint) -> id {
+    unsafe {
+        let window: id = msg_send![this, window];
+        let rect: NSRect = msg_send![window,
+            convertRectFromScreen: NSRect::new(point, NSSize::new(0.0, 0.0))];
+        let point: NSPoint = msg_send![this, convertPoint: rect.origin fromView: nil];
+        let point = Point::new(point.x, point.y);
+        let state = &view_state(this).access;
+        let tree = match state.tree {
+            Some(ref tree) => tree,
+            None => return this,
+        };
+        let mut hit = this as id;
+        let mut node = tree.node(tree.root);
+        while let Some(parent) = node {
+            node = None;
+            // The last child is painted on top.
+            for child in parent.children.iter().rev() {
+                let child_node = match tree.node(*child) {
+                    Some(child_node) => child_node,
+                    None => continue,
+                };
+                if child_node.bounds.contains(point) {
+                    if let Some(element) = state.elements.get(child) {
+                        hit = **element;
+                    }
+                    node = Some(child_node);
+                    break;
+                }
+            }
+        }
+        hit
+    }
+}
@@ -14,15 +14,240 @@
 
 //! macOS implementation of features at the application scope.
 
+use std::ffi::c_void;
+use std::path::Path;
+
 use cocoa::appkit::NSApp;
-use cocoa::base::nil;
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::NSArray;
+use objc::rc::StrongPtr;
+
+use crate::clipboard::Clipboard;
+use crate::piet::Color;
+use crate::util::make_nsstring;
+use crate::window::{PowerSource, Theme};
+use crate::Error;
+
+/// An `IOPMAssertionID`.
+type AssertionId = u32;
+
+// From IOPMLib.h.
+const ASSERTION_LEVEL_ON: u32 = 255;
+const ASSERTION_TYPE_NO_DISPLAY_SLEEP: &str = "PreventUserIdleDisplaySleep";
+
+#[link(name = "IOKit", kind = "framework")]
+extern "C" {
+    // The strings are `CFStringRef`s, which `NSString`s can be used as.
+    fn IOPMAssertionCreateWithName(
+        assertion_type: id,
+        level: u32,
+        name: id,
+        assertion: *mut AssertionId,
+    ) -> i32;
+    fn IOPMAssertionRelease(assertion: AssertionId) -> i32;
+    fn IOPSCopyPowerSourcesInfo() -> id;
+    fn IOPSGetProvidingPowerSourceType(snapshot: id) -> id;
+    fn IOPSNotificationCreateRunLoopSource(
+        callback: extern "C" fn(*mut c_void),
+        context: *mut c_void,
+    ) -> id;
+}
+
+#[link(name = "CoreFoundation", kind = "framework")]
+extern "C" {
+    fn CFRunLoopGetMain() -> id;
+    fn CFRunLoopAddSource(run_loop: id, source: id, mode: id);
+    fn CFRunLoopSourceInvalidate(source: id);
+}
 
 pub struct Application;
 
+/// Keeps the display awake until dropped; see `Application::inhibit_sleep`.
+pub struct SleepInhibitor {
+    assertion: AssertionId,
+}
+
 impl Application {
     pub fn quit() {
         unsafe {
             let () = msg_send![NSApp(), terminate: nil];
         }
     }
+
+    /// The system clipboard.
+    pub fn clipboard() -> Clipboard {
+        Clipboard
+    }
+
+    /// The light or dark appearance chosen in the system preferences.
+    pub fn theme() -> Theme {
+        unsafe {
+            let defaults: id = msg_send![class!(NSUserDefaults), standardUserDefaults];
+            let style: id = msg_send![defaults, stringForKey: make_nsstring("AppleInterfaceStyle")];
+            let dark: BOOL = msg_send![style, isEqualToString: make_nsstring("Dark")];
+            if style != nil && dark == YES {
+                Theme::Dark
+            } else {
+                Theme::Light
+            }
+        }
+    }
+
+    /// The accent color chosen in the system preferences.
+    pub fn accent_color() -> Option<Color> {
+        accent_rgba().map(Color::rgba32)
+    }
+
+    /// Whether "Reduce transparency" is turned on in the accessibility
+    /// preferences.
+    pub fn reduce_transparency() -> bool {
+        unsafe {
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let reduce: BOOL = msg_send![workspace, accessibilityDisplayShouldReduceTransparency];
+            reduce == YES
+        }
+    }
+
+    /// macOS has no system-wide size of text, so this is always 1.
+    pub fn text_scale() -> f64 {
+        1.0
+    }
+
+    /// Whether the Mac runs on battery.
+    pub fn power_source() -> PowerSource {
+        unsafe {
+            let info = IOPSCopyPowerSourcesInfo();
+            if info == nil {
+                return PowerSource::External;
+            }
+            // The type is not owned by the caller.
+            let kind = IOPSGetProvidingPowerSourceType(info);
+            let battery: BOOL = if kind == nil {
+                NO
+            } else {
+                msg_send![kind, isEqualToString: make_nsstring("Battery Power")]
+            };
+            let () = msg_send![info, release];
+            if battery == YES {
+                PowerSource::Battery
+            } else {
+                PowerSource::External
+            }
+        }
+    }
+
+    /// Show a short label, such as a count of unread items, on the Dock
+    /// icon, or remove it with `None`.
+    pub fn set_badge(label: Option<&str>) {
+        unsafe {
+            let tile: id = msg_send![NSApp(), dockTile];
+            let label = label.map_or(nil, make_nsstring);
+            let () = msg_send![tile, setBadgeLabel: label];
+        }
+    }
+
+    /// Open a URL in the default browser, or with the application registered
+    /// for its scheme.
+    pub fn open_url(url: &str) -> Result<(), Error> {
+        unsafe {
+            let url: id = msg_send![class!(NSURL), URLWithString: make_nsstring(url)];
+            if url == nil {
+                return Err(Error::Other("malformed URL"));
+            }
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let opened: BOOL = msg_send![workspace, openURL: url];
+            if opened == YES {
+                Ok(())
+            } else {
+                Err(Error::Other("no application to open the URL"))
+            }
+        }
+    }
+
+    /// Open the folder containing `path` in the Finder, with the item
+    /// selected.
+    pub fn reveal_in_file_manager(path: &Path) -> Result<(), Error> {
+        let path = path
+            .to_str()
+            .ok_or(Error::Other("path is not valid UTF-8"))?;
+        unsafe {
+            let url: id = msg_send![class!(NSURL), fileURLWithPath: make_nsstring(path)];
+            let urls = NSArray::arrayWithObject(nil, url);
+            let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+            let () = msg_send![workspace, activateFileViewerSelectingURLs: urls];
+        }
+        Ok(())
+    }
+
+    /// Keep the display from sleeping and the screensaver from starting,
+    /// for as long as the returned value is alive. The reason is shown by
+    /// `pmset -g assertions`.
+    pub fn inhibit_sleep(reason: &str) -> Result<SleepInhibitor, Error> {
+        let mut assertion = 0;
+        let result = unsafe {
+            IOPMAssertionCreateWithName(
+                make_nsstring(ASSERTION_TYPE_NO_DISPLAY_SLEEP),
+                ASSERTION_LEVEL_ON,
+                make_nsstring(reason),
+                &mut assertion,
+            )
+        };
+        if result == 0 {
+            Ok(SleepInhibitor { assertion })
+        } else {
+            Err(Error::Other("IOPMAssertionCreateWithName failed"))
+        }
+    }
+}
+
+/// The accent color as 8-bit RGBA. Versions before 10.14 have none.
+pub(crate) fn accent_rgba() -> Option<u32> {
+    unsafe {
+        let responds: BOOL =
+            msg_send![class!(NSColor), respondsToSelector: sel!(controlAccentColor)];
+        if responds != YES {
+            return None;
+        }
+        let color: id = msg_send![class!(NSColor), controlAccentColor];
+        let space: id = msg_send![class!(NSColorSpace), sRGBColorSpace];
+        let color: id = msg_send![color, colorUsingColorSpace: space];
+        if color == nil {
+            return None;
+        }
+        let (mut r, mut g, mut b, mut a) = (0.0f64, 0.0f64, 0.0f64, 0.0f64);
+        let () = msg_send![color, getRed: &mut r green: &mut g blue: &mut b alpha: &mut a];
+        let byte = |c: f64| (c.max(0.0).min(1.0) * 255.0).round() as u32;
+        Some((byte(r) << 24) | (byte(g) << 16) | (byte(b) << 8) | byte(a))
+    }
+}
+
+/// Call `callback` with `context` on the main run loop whenever the power
+/// source changes, until the returned source is passed to
+/// `remove_power_notification`.
+pub(crate) unsafe fn add_power_notification(
+    callback: extern "C" fn(*mut c_void),
+    context: *mut c_void,
+) -> Option<StrongPtr> {
+    let source = IOPSNotificationCreateRunLoopSource(callback, context);
+    if source == nil {
+        return None;
+    }
+    CFRunLoopAddSource(
+        CFRunLoopGetMain(),
+        source,
+        make_nsstring("kCFRunLoopDefaultMode"),
+    );
+    Some(StrongPtr::new(source))
+}
+
+pub(crate) unsafe fn remove_power_notification(source: &StrongPtr) {
+    CFRunLoopSourceInvalidate(**source);
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        unsafe {
+            IOPMAssertionRelease(self.assertion);
+        }
+    }
 }
@@ -0,0 +1,119 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interactions with the system pasteboard on macOS.
+
+use std::ffi::CStr;
+
+use cocoa::appkit::{NSPasteboard, NSPasteboardTypePNG, NSPasteboardTypeString};
+use cocoa::base::{id, nil, BOOL, YES};
+use cocoa::foundation::{NSArray, NSString, NSUInteger};
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::util::make_nsstring;
+
+/// The system clipboard.
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard;
+
+impl Clipboard {
+    /// Put a string on the clipboard, replacing its contents.
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            pasteboard.clearContents();
+            pasteboard.setString_forType(make_nsstring(s.as_ref()), NSPasteboardTypeString);
+        }
+    }
+
+    /// Get the string on the clipboard, if there is one.
+    pub fn get_string(&self) -> Option<String> {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            let contents: id = pasteboard.stringForType(NSPasteboardTypeString);
+            if contents == nil {
+                return None;
+            }
+            let contents = CStr::from_ptr(contents.UTF8String());
+            Some(contents.to_string_lossy().into_owned())
+        }
+    }
+
+    /// Put data on the clipboard in several formats, replacing its contents.
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            pasteboard.clearContents();
+            let types: Vec<id> = formats
+                .iter()
+                .map(|f| pasteboard_type(f.identifier))
+                .collect();
+            let () = msg_send![pasteboard,
+                declareTypes: NSArray::arrayWithObjects(nil, &types)
+                owner: nil];
+            for (format, ty) in formats.iter().zip(types) {
+                let data: id = msg_send![class!(NSData),
+                    dataWithBytes: format.data.as_ptr()
+                    length: format.data.len() as NSUInteger];
+                let _: BOOL = msg_send![pasteboard, setData: data forType: ty];
+            }
+        }
+    }
+
+    /// Get the data on the clipboard in a format, if it is available.
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            let data: id = pasteboard.dataForType(pasteboard_type(format));
+            if data == nil {
+                return None;
+            }
+            let len: NSUInteger = msg_send![data, length];
+            let bytes: *const u8 = msg_send![data, bytes];
+            Some(std::slice::from_raw_parts(bytes, len as usize).to_vec())
+        }
+    }
+
+    /// The first of `formats` that the clipboard has data for.
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        unsafe {
+            let pasteboard = NSPasteboard::generalPasteboard(nil);
+            let types: Vec<id> = formats.iter().map(|&f| pasteboard_type(f)).collect();
+            let available: id = msg_send![pasteboard,
+                availableTypeFromArray: NSArray::arrayWithObjects(nil, &types)];
+            if available == nil {
+                return None;
+            }
+            types
+                .iter()
+                .position(|&ty| {
+                    let equal: BOOL = msg_send![ty, isEqualToString: available];
+                    equal == YES
+                })
+                .map(|i| formats[i])
+        }
+    }
+}
+
+/// The pasteboard type (a UTI) for a format.
+pub(crate) fn pasteboard_type(format: FormatId) -> id {
+    unsafe {
+        match format {
+            ClipboardFormat::TEXT => NSPasteboardTypeString,
+            ClipboardFormat::PNG => NSPasteboardTypePNG,
+            ClipboardFormat::SVG => make_nsstring("public.svg-image"),
+            other => make_nsstring(other),
+        }
+    }
+}
@@ -14,22 +14,87 @@
 
 //! File open/save dialogs, macOS implementation.
 
-// TODO: maybe these types should be platform-independent?
+use std::ffi::CStr;
+use std::path::PathBuf;
 
-/// Type of file dialog.
-pub enum FileDialogType {
-    /// File open dialog.
-    Open,
-    /// File save dialog.
-    Save,
-}
+use cocoa::base::{id, nil, NO, YES};
+use cocoa::foundation::{NSArray, NSInteger, NSString, NSUInteger};
+
+use crate::dialog::{FileDialogOptions, FileDialogType};
+use crate::util::make_nsstring;
+
+const NS_MODAL_RESPONSE_OK: NSInteger = 1;
 
-/// Options for file dialog.
-#[derive(Default)]
-pub struct FileDialogOptions;
+/// Run a modal open, save or directory panel, returning the chosen paths, or `None` if
+/// the panel was cancelled.
+pub(crate) unsafe fn get_file_dialog_paths(
+    ty: FileDialogType,
+    options: &FileDialogOptions,
+) -> Option<Vec<PathBuf>> {
+    let panel: id = match ty {
+        FileDialogType::Open => {
+            let panel: id = msg_send![class!(NSOpenPanel), openPanel];
+            let multi = if options.multi_selection { YES } else { NO };
+            let () = msg_send![panel, setAllowsMultipleSelection: multi];
+            let dirs = if options.select_directories { YES } else { NO };
+            let files = if options.select_directories { NO } else { YES };
+            let () = msg_send![panel, setCanChooseDirectories: dirs];
+            let () = msg_send![panel, setCanChooseFiles: files];
+            panel
+        }
+        FileDialogType::Directory => {
+            let panel: id = msg_send![class!(NSOpenPanel), openPanel];
+            let () = msg_send![panel, setCanChooseDirectories: YES];
+            let () = msg_send![panel, setCanChooseFiles: NO];
+            let () = msg_send![panel, setCanCreateDirectories: YES];
+            panel
+        }
+        FileDialogType::Save => {
+            // The save panel always asks before replacing an existing file.
+            let panel: id = msg_send![class!(NSSavePanel), savePanel];
+            if let Some(ref name) = options.default_name {
+                let () = msg_send![panel, setNameFieldStringValue: make_nsstring(name)];
+            }
+            panel
+        }
+    };
+    let hidden = if options.show_hidden { YES } else { NO };
+    let () = msg_send![panel, setShowsHiddenFiles: hidden];
+    if !options.allowed_types.is_empty() && ty != FileDialogType::Directory {
+        let extensions: Vec<id> = options
+            .allowed_types
+            .iter()
+            .flat_map(|spec| spec.extensions.iter())
+            .map(|ext| make_nsstring(ext))
+            .collect();
+        let extensions = NSArray::arrayWithObjects(nil, &extensions);
+        let () = msg_send![panel, setAllowedFileTypes: extensions];
+    }
+    if let Some(ref dir) = options.starting_directory {
+        let path = make_nsstring(&dir.to_string_lossy());
+        let url: id = msg_send![class!(NSURL), fileURLWithPath: path isDirectory: YES];
+        let () = msg_send![panel, setDirectoryURL: url];
+    }
 
-impl FileDialogOptions {
-    pub fn set_show_hidden(&mut self) {
-        // TODO
+    let response: NSInteger = msg_send![panel, runModal];
+    if response != NS_MODAL_RESPONSE_OK {
+        return None;
     }
+    let urls: id = match ty {
+        FileDialogType::Open | FileDialogType::Directory => msg_send![panel, URLs],
+        FileDialogType::Save => {
+            let url: id = msg_send![panel, URL];
+            NSArray::arrayWithObject(nil, url)
+        }
+    };
+    let count: NSUInteger = msg_send![urls, count];
+    let paths = (0..count)
+        .map(|i| {
+            let url: id = msg_send![urls, objectAtIndex: i];
+            let path: id = msg_send![url, path];
+            let path = CStr::from_ptr(path.UTF8String());
+            PathBuf::from(path.to_string_lossy().into_owned())
+        })
+        .collect();
+    Some(paths)
 }
@@ -0,0 +1,128 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A clock ticking with the display refresh, macOS implementation.
+//!
+//! A `CVDisplayLink` calls back on its own thread at each refresh, and the
+//! callback sends `handleFrame` to the view on the main thread. The link
+//! only runs while the view has asked for a frame.
+
+use std::ffi::c_void;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use cocoa::base::{id, nil, NO};
+
+type CVReturn = i32;
+type CVDisplayLinkRef = *mut c_void;
+type CVDisplayLinkOutputCallback = extern "C" fn(
+    CVDisplayLinkRef,
+    *const c_void,
+    *const c_void,
+    u64,
+    *mut u64,
+    *mut c_void,
+) -> CVReturn;
+
+#[link(name = "CoreVideo", kind = "framework")]
+extern "C" {
+    fn CVDisplayLinkCreateWithActiveCGDisplays(link: *mut CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkSetOutputCallback(
+        link: CVDisplayLinkRef,
+        callback: CVDisplayLinkOutputCallback,
+        user_info: *mut c_void,
+    ) -> CVReturn;
+    fn CVDisplayLinkStart(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkStop(link: CVDisplayLinkRef) -> CVReturn;
+    fn CVDisplayLinkIsRunning(link: CVDisplayLinkRef) -> u8;
+    fn CVDisplayLinkRelease(link: CVDisplayLinkRef);
+}
+
+pub(crate) struct DisplayLink {
+    link: CVDisplayLinkRef,
+    context: Box<LinkContext>,
+}
+
+struct LinkContext {
+    view: id,
+    /// Set while a `handleFrame` is on its way, so that a busy main thread
+    /// doesn't get a backlog of them.
+    pending: AtomicBool,
+}
+
+impl DisplayLink {
+    /// Create a stopped link, sending `handleFrame` to `view`.
+    pub(crate) fn new(view: id) -> Option<DisplayLink> {
+        let context = Box::new(LinkContext {
+            view,
+            pending: AtomicBool::new(false),
+        });
+        unsafe {
+            let mut link = null_mut();
+            if CVDisplayLinkCreateWithActiveCGDisplays(&mut link) != 0 {
+                return None;
+            }
+            let user_info = &*context as *const LinkContext as *mut c_void;
+            CVDisplayLinkSetOutputCallback(link, output_callback, user_info);
+            Some(DisplayLink { link, context })
+        }
+    }
+
+    pub(crate) fn start(&self) {
+        unsafe {
+            if CVDisplayLinkIsRunning(self.link) == 0 {
+                CVDisplayLinkStart(self.link);
+            }
+        }
+    }
+
+    pub(crate) fn stop(&self) {
+        unsafe {
+            CVDisplayLinkStop(self.link);
+        }
+    }
+
+    /// Called from `handleFrame`, to allow the next one to be sent.
+    pub(crate) fn frame_delivered(&self) {
+        self.context.pending.store(false, Ordering::SeqCst);
+    }
+}
+
+impl Drop for DisplayLink {
+    fn drop(&mut self) {
+        unsafe {
+            // This waits for a running callback to finish.
+            CVDisplayLinkStop(self.link);
+            CVDisplayLinkRelease(self.link);
+        }
+    }
+}
+
+extern "C" fn output_callback(
+    _link: CVDisplayLinkRef,
+    _now: *const c_void,
+    _output_time: *const c_void,
+    _flags: u64,
+    _flags_out: *mut u64,
+    user_info: *mut c_void,
+) -> CVReturn {
+    let context = unsafe { &*(user_info as *const LinkContext) };
+    if !context.pending.swap(true, Ordering::SeqCst) {
+        unsafe {
+            let () = msg_send![context.view, performSelectorOnMainThread: sel!(handleFrame)
+                withObject: nil waitUntilDone: NO];
+        }
+    }
+    0
+}
@@ -0,0 +1,163 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drags out of the application, macOS implementation.
+//!
+//! The view is the `NSDraggingSource` of the sessions it begins. Files are
+//! dragged as one item each, and the other formats together in one
+//! pasteboard item.
+
+use std::ffi::c_void;
+
+use cocoa::appkit::NSApp;
+use cocoa::base::{id, nil, BOOL};
+use cocoa::foundation::{NSArray, NSPoint, NSRect, NSSize, NSUInteger};
+use objc::declare::ClassDecl;
+use objc::runtime::{Object, Protocol, Sel};
+
+use super::clipboard::pasteboard_type;
+use super::{make_nsimage, ViewState};
+use crate::drag::{DragData, DropEffect};
+use crate::util::make_nsstring;
+use crate::Error;
+
+const NS_DRAG_OPERATION_NONE: NSUInteger = 0;
+const NS_DRAG_OPERATION_COPY: NSUInteger = 1;
+const NS_DRAG_OPERATION_MOVE: NSUInteger = 16;
+
+// From NSEvent.h.
+const NS_LEFT_MOUSE_DRAGGED: NSUInteger = 6;
+const NS_RIGHT_MOUSE_DRAGGED: NSUInteger = 7;
+const NS_OTHER_MOUSE_DRAGGED: NSUInteger = 27;
+
+pub(super) unsafe fn add_methods(decl: &mut ClassDecl) {
+    if let Some(protocol) = Protocol::get("NSDraggingSource") {
+        decl.add_protocol(protocol);
+    }
+    decl.add_method(
+        sel!(draggingSession:sourceOperationMaskForDraggingContext:),
+        source_operation_mask as extern "C" fn(&mut Object, Sel, id, NSUInteger) -> NSUInteger,
+    );
+    decl.add_method(
+        sel!(draggingSession:endedAtPoint:operation:),
+        dragging_ended as extern "C" fn(&mut Object, Sel, id, NSPoint, NSUInteger),
+    );
+}
+
+fn view_state(this: &mut Object) -> &mut ViewState {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    }
+}
+
+/// Begin a dragging session from the view, with the mouse event being
+/// handled.
+pub(super) unsafe fn start_drag(view: id, data: DragData) -> Result<(), Error> {
+    let event: id = msg_send![NSApp(), currentEvent];
+    let event_type: NSUInteger = if event == nil {
+        0
+    } else {
+        msg_send![event, type]
+    };
+    match event_type {
+        NS_LEFT_MOUSE_DRAGGED | NS_RIGHT_MOUSE_DRAGGED | NS_OTHER_MOUSE_DRAGGED => (),
+        _ => return Err(Error::Other("drags must start while the mouse is dragged")),
+    }
+
+    let mut writers: Vec<id> = data
+        .files
+        .iter()
+        .filter_map(|path| path.to_str())
+        .map(|path| -> id { msg_send![class!(NSURL), fileURLWithPath: make_nsstring(path)] })
+        .collect();
+    if !data.formats.is_empty() {
+        let item: id = msg_send![class!(NSPasteboardItem), new];
+        for format in &data.formats {
+            let bytes: id = msg_send![class!(NSData),
+                dataWithBytes: format.data.as_ptr()
+                length: format.data.len() as NSUInteger];
+            let _: BOOL =
+                msg_send![item, setData: bytes forType: pasteboard_type(format.identifier)];
+        }
+        let _: id = msg_send![item, autorelease];
+        writers.push(item);
+    }
+    if writers.is_empty() {
+        return Err(Error::Other("nothing to drag"));
+    }
+
+    // All items are shown at the pointer, the first with the drag image.
+    let window_point: NSPoint = msg_send![event, locationInWindow];
+    let point: NSPoint = msg_send![view, convertPoint: window_point fromView: nil];
+    let image = data
+        .image
+        .as_ref()
+        .and_then(|image| make_nsimage(&image.image.image, image.image.width, image.image.height));
+    let mut items = Vec::with_capacity(writers.len());
+    for (i, writer) in writers.into_iter().enumerate() {
+        let item: id = msg_send![class!(NSDraggingItem), alloc];
+        let item: id = msg_send![item, initWithPasteboardWriter: writer];
+        let (frame, contents) = match (i, &data.image, &image) {
+            (0, Some(desc), Some(image)) => {
+                // The view is flipped, so the image extends down from its
+                // origin.
+                let frame = NSRect::new(
+                    NSPoint::new(point.x - desc.x as f64, point.y - desc.y as f64),
+                    NSSize::new(desc.image.width as f64, desc.image.height as f64),
+                );
+                (frame, **image)
+            }
+            _ => (NSRect::new(point, NSSize::new(1.0, 1.0)), nil),
+        };
+        let () = msg_send![item, setDraggingFrame: frame contents: contents];
+        let _: id = msg_send![item, autorelease];
+        items.push(item);
+    }
+
+    view_state(&mut *view).drag_allow_move = data.allow_move;
+    let items = NSArray::arrayWithObjects(nil, &items);
+    let _: id = msg_send![view, beginDraggingSessionWithItems: items event: event source: view];
+    Ok(())
+}
+
+extern "C" fn source_operation_mask(
+    this: &mut Object,
+    _: Sel,
+    _session: id,
+    _context: NSUInteger,
+) -> NSUInteger {
+    if view_state(this).drag_allow_move {
+        NS_DRAG_OPERATION_COPY | NS_DRAG_OPERATION_MOVE
+    } else {
+        NS_DRAG_OPERATION_COPY
+    }
+}
+
+extern "C" fn dragging_ended(
+    this: &mut Object,
+    _: Sel,
+    _session: id,
+    _point: NSPoint,
+    operation: NSUInteger,
+) {
+    let effect = if operation & NS_DRAG_OPERATION_MOVE != NS_DRAG_OPERATION_NONE {
+        DropEffect::Move
+    } else if operation != NS_DRAG_OPERATION_NONE {
+        DropEffect::Copy
+    } else {
+        DropEffect::None
+    };
+    view_state(this).handler.drag_ended(effect);
+}
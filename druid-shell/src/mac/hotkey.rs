@@ -0,0 +1,231 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! System-wide hotkeys, using the Carbon event manager.
+//!
+//! Carbon is still the only public API that can register a hotkey without
+//! accessibility permissions.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::c_void;
+use std::mem;
+use std::ptr::null_mut;
+
+use objc::rc::WeakPtr;
+use objc::runtime::Object;
+
+use super::ViewState;
+use crate::keyboard::KeyCode;
+use crate::keycodes::{key_code_for_char, KeySpec, MenuKey, M_ALT, M_CTRL, M_META, M_SHIFT};
+use crate::Error;
+
+type OSStatus = i32;
+type EventTargetRef = *mut c_void;
+type EventHandlerRef = *mut c_void;
+type EventHandlerCallRef = *mut c_void;
+type EventRef = *mut c_void;
+type EventHotKeyRef = *mut c_void;
+type EventHandlerProc = extern "C" fn(EventHandlerCallRef, EventRef, *mut c_void) -> OSStatus;
+
+#[repr(C)]
+struct EventTypeSpec {
+    event_class: u32,
+    event_kind: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct EventHotKeyID {
+    signature: u32,
+    id: u32,
+}
+
+const NO_ERR: OSStatus = 0;
+const EVENT_NOT_HANDLED_ERR: OSStatus = -9874;
+
+const EVENT_CLASS_KEYBOARD: u32 = 0x6b65_7962; // 'keyb'
+const EVENT_HOT_KEY_PRESSED: u32 = 5;
+const EVENT_PARAM_DIRECT_OBJECT: u32 = 0x2d2d_2d2d; // '----'
+const TYPE_EVENT_HOT_KEY_ID: u32 = 0x686b_6964; // 'hkid'
+
+const CMD_KEY: u32 = 1 << 8;
+const SHIFT_KEY: u32 = 1 << 9;
+const OPTION_KEY: u32 = 1 << 11;
+const CONTROL_KEY: u32 = 1 << 12;
+
+/// Identifies our hotkeys in the shared Carbon namespace.
+const SIGNATURE: u32 = 0x6472_6964; // 'drid'
+
+#[link(name = "Carbon", kind = "framework")]
+extern "C" {
+    fn GetApplicationEventTarget() -> EventTargetRef;
+    fn InstallEventHandler(
+        target: EventTargetRef,
+        handler: EventHandlerProc,
+        num_types: u32,
+        list: *const EventTypeSpec,
+        user_data: *mut c_void,
+        out_ref: *mut EventHandlerRef,
+    ) -> OSStatus;
+    fn RegisterEventHotKey(
+        key_code: u32,
+        modifiers: u32,
+        hot_key_id: EventHotKeyID,
+        target: EventTargetRef,
+        options: u32,
+        out_ref: *mut EventHotKeyRef,
+    ) -> OSStatus;
+    fn UnregisterEventHotKey(hot_key: EventHotKeyRef) -> OSStatus;
+    fn GetEventParameter(
+        event: EventRef,
+        name: u32,
+        desired_type: u32,
+        out_actual_type: *mut u32,
+        buffer_size: usize,
+        out_actual_size: *mut usize,
+        out_data: *mut c_void,
+    ) -> OSStatus;
+}
+
+struct HotKey {
+    hot_key: EventHotKeyRef,
+    nsview: WeakPtr,
+}
+
+thread_local! {
+    /// The registered hotkeys by command id. Carbon events are delivered on
+    /// the main thread, which is also the only thread allowed to register.
+    static HOTKEYS: RefCell<HashMap<u32, HotKey>> = RefCell::new(HashMap::new());
+    static HANDLER: RefCell<Option<EventHandlerRef>> = RefCell::new(None);
+}
+
+pub(crate) fn register(nsview: &WeakPtr, id: u32, key: MenuKey) -> Result<(), Error> {
+    let key_code = match key.key {
+        KeySpec::Char(c) => mac_key_code(c).ok_or(Error::Other("hotkey key not supported"))?,
+        KeySpec::None => return Err(Error::Other("hotkey has no key")),
+    };
+    let mut modifiers = 0;
+    if key.modifiers & M_ALT != 0 {
+        modifiers |= OPTION_KEY;
+    }
+    if key.modifiers & M_CTRL != 0 {
+        modifiers |= CONTROL_KEY;
+    }
+    if key.modifiers & M_SHIFT != 0 {
+        modifiers |= SHIFT_KEY;
+    }
+    if key.modifiers & M_META != 0 {
+        modifiers |= CMD_KEY;
+    }
+    install_handler()?;
+    unregister(id);
+    let hot_key_id = EventHotKeyID {
+        signature: SIGNATURE,
+        id,
+    };
+    let mut hot_key = null_mut();
+    let status = unsafe {
+        RegisterEventHotKey(
+            key_code as u32,
+            modifiers,
+            hot_key_id,
+            GetApplicationEventTarget(),
+            0,
+            &mut hot_key,
+        )
+    };
+    if status != NO_ERR {
+        return Err(Error::Other("hotkey registration failed"));
+    }
+    let nsview = nsview.clone();
+    HOTKEYS.with(|h| h.borrow_mut().insert(id, HotKey { hot_key, nsview }));
+    Ok(())
+}
+
+pub(crate) fn unregister(id: u32) {
+    if let Some(entry) = HOTKEYS.with(|h| h.borrow_mut().remove(&id)) {
+        unsafe {
+            UnregisterEventHotKey(entry.hot_key);
+        }
+    }
+}
+
+fn install_handler() -> Result<(), Error> {
+    HANDLER.with(|handler| {
+        if handler.borrow().is_some() {
+            return Ok(());
+        }
+        let spec = EventTypeSpec {
+            event_class: EVENT_CLASS_KEYBOARD,
+            event_kind: EVENT_HOT_KEY_PRESSED,
+        };
+        let mut handler_ref = null_mut();
+        let status = unsafe {
+            InstallEventHandler(
+                GetApplicationEventTarget(),
+                hot_key_handler,
+                1,
+                &spec,
+                null_mut(),
+                &mut handler_ref,
+            )
+        };
+        if status != NO_ERR {
+            return Err(Error::Other("could not install hotkey handler"));
+        }
+        *handler.borrow_mut() = Some(handler_ref);
+        Ok(())
+    })
+}
+
+extern "C" fn hot_key_handler(_: EventHandlerCallRef, event: EventRef, _: *mut c_void) -> OSStatus {
+    let mut hot_key_id = EventHotKeyID::default();
+    let status = unsafe {
+        GetEventParameter(
+            event,
+            EVENT_PARAM_DIRECT_OBJECT,
+            TYPE_EVENT_HOT_KEY_ID,
+            null_mut(),
+            mem::size_of::<EventHotKeyID>(),
+            null_mut(),
+            &mut hot_key_id as *mut EventHotKeyID as *mut c_void,
+        )
+    };
+    if status != NO_ERR || hot_key_id.signature != SIGNATURE {
+        return EVENT_NOT_HANDLED_ERR;
+    }
+    let id = hot_key_id.id;
+    let nsview = HOTKEYS.with(|h| h.borrow().get(&id).map(|entry| entry.nsview.clone()));
+    let view = match nsview {
+        Some(nsview) => nsview.load(),
+        None => return EVENT_NOT_HANDLED_ERR,
+    };
+    if view.is_null() {
+        return EVENT_NOT_HANDLED_ERR;
+    }
+    unsafe {
+        let view: &Object = &**view;
+        let view_state: *mut c_void = *view.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        (*view_state).handler.command(id);
+    }
+    NO_ERR
+}
+
+/// The virtual key code of the key that produces a character on a US layout.
+fn mac_key_code(c: char) -> Option<u16> {
+    let code = key_code_for_char(c)?;
+    (0..0x80).find(|&raw| KeyCode::from(raw) == code)
+}
@@ -13,36 +13,23 @@
 // limitations under the License.
 
 //! macOS implementation of menus.
-use cocoa::appkit::{NSMenu, NSMenuItem};
-use cocoa::base::{id, nil};
-use cocoa::foundation::NSAutoreleasePool;
-use lazy_static::lazy_static;
-use objc::declare::ClassDecl;
-use objc::runtime::{Class, Object, Sel};
+//!
+//! Menu items send the `handleMenuItem:` action up the responder chain, with
+//! the command id as the item's tag, so the selection is delivered to the
+//! view of the key window.
+//!
+//! The menubar always starts with the standard application menu, and gets a
+//! standard Edit menu sending the commands in `druid_shell::commands` when
+//! the application does not provide one.
 
-use crate::keycodes::{KeySpec, MenuKey};
-use crate::util::make_nsstring;
+use cocoa::appkit::{NSApp, NSApplication, NSEventModifierFlags, NSMenu, NSMenuItem};
+use cocoa::base::{id, nil, NO, YES};
+use cocoa::foundation::{NSAutoreleasePool, NSInteger};
+use objc::runtime::Sel;
 
-struct MenuItemProxyClass(*const Class);
-unsafe impl Sync for MenuItemProxyClass {}
-
-lazy_static! {
-    static ref MENU_ITEM_PROXY_CLASS: MenuItemProxyClass = {
-        unsafe {
-            let mut decl =
-                ClassDecl::new("DruidMenuItemProxy", class!(NSObject)).expect("Menu class defined");
-            decl.add_ivar::<u32>("menu_id");
-            decl.add_method(sel!(trigger), trigger as extern "C" fn(&Object, Sel));
-            extern "C" fn trigger(this: &Object, _: Sel) {
-                unsafe {
-                    let menu_id: u32 = *this.get_ivar("menu_id");
-                    println!("triggered menu item with id {}", menu_id);
-                }
-            }
-            MenuItemProxyClass(decl.register())
-        }
-    };
-}
+use crate::commands;
+use crate::keycodes::{KeySpec, MenuKey, M_ALT, M_CTRL, M_META, M_SHIFT};
+use crate::util::{from_nsstring, make_nsstring};
 
 pub struct Menu {
     pub menu: id,
@@ -50,15 +37,36 @@ pub struct Menu {
 
 /// Make a string in the syntax expected as the keyEquivalent argument to
 /// NSMenuItem initWithTitle:action:keyEquivalent:
-fn make_key_equivalent(key: impl Into<MenuKey>) -> String {
-    // TODO: handle modifiers and other fun things
-    let key = key.into();
+fn make_key_equivalent(key: &MenuKey) -> String {
     match key.key {
         KeySpec::Char(c) => c.to_string(),
         KeySpec::None => "".to_string(),
     }
 }
 
+/// Make the modifier mask for a menu key.
+///
+/// A key with no modifiers keeps the Cocoa default of the command key.
+fn make_modifier_mask(key: &MenuKey) -> Option<NSEventModifierFlags> {
+    if key.modifiers == 0 {
+        return None;
+    }
+    let mut mask = NSEventModifierFlags::empty();
+    if key.modifiers & M_SHIFT != 0 {
+        mask |= NSEventModifierFlags::NSShiftKeyMask;
+    }
+    if key.modifiers & M_ALT != 0 {
+        mask |= NSEventModifierFlags::NSAlternateKeyMask;
+    }
+    if key.modifiers & M_CTRL != 0 {
+        mask |= NSEventModifierFlags::NSControlKeyMask;
+    }
+    if key.modifiers & M_META != 0 {
+        mask |= NSEventModifierFlags::NSCommandKeyMask;
+    }
+    Some(mask)
+}
+
 /// Strip the access keys from the menu strong.
 ///
 /// Changes "E&xit" to "Exit". Actual ampersands are escaped as "&&".
@@ -79,33 +87,29 @@ fn strip_access_key(raw_menu_text: &str) -> String {
     result
 }
 
-fn make_basic_menu_item(_id: u32, text: &str, key: impl Into<MenuKey>) -> id {
-    let key_equivalent = make_key_equivalent(key);
+fn make_basic_menu_item(action: Sel, text: &str, key: impl Into<MenuKey>) -> id {
+    let key = key.into();
+    let key_equivalent = make_key_equivalent(&key);
     let stripped_text = strip_access_key(text);
     unsafe {
-        NSMenuItem::alloc(nil)
+        let menu_item = NSMenuItem::alloc(nil)
             .initWithTitle_action_keyEquivalent_(
                 make_nsstring(&stripped_text),
-                sel!(trigger),
+                action,
                 make_nsstring(&key_equivalent),
             )
-            .autorelease()
-    }
-}
-
-fn make_proxy(id: u32) -> id {
-    unsafe {
-        let target: id = msg_send![MENU_ITEM_PROXY_CLASS.0, alloc];
-        (*target).set_ivar("menu_id", id);
-        target.autorelease()
+            .autorelease();
+        if let Some(mask) = make_modifier_mask(&key) {
+            menu_item.setKeyEquivalentModifierMask_(mask);
+        }
+        menu_item
     }
 }
 
 fn make_menu_item(id: u32, text: &str, key: impl Into<MenuKey>) -> id {
-    let target = make_proxy(id);
-    let menu_item = make_basic_menu_item(id, text, key);
+    let menu_item = make_basic_menu_item(sel!(handleMenuItem:), text, key);
     unsafe {
-        msg_send![menu_item, setTarget: target];
+        let () = msg_send![menu_item, setTag: id as NSInteger];
     }
     menu_item
 }
@@ -113,44 +117,157 @@ fn make_menu_item(id: u32, text: &str, key: impl Into<MenuKey>) -> id {
 impl Menu {
     pub fn new() -> Menu {
         unsafe {
-            Menu {
-                menu: NSMenu::alloc(nil),
-            }
+            let menu = NSMenu::new(nil).autorelease();
+            // Items are enabled explicitly rather than by validation.
+            menu.setAutoenablesItems(NO);
+            Menu { menu }
         }
     }
 
-    // TODO: how do we use the text here?
-    pub fn add_dropdown(&mut self, menu: Menu, _text: &str) {
+    /// Create a menu to be shown with `WindowHandle::show_context_menu`.
+    pub fn new_for_popup() -> Menu {
+        Menu::new()
+    }
+
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str) {
         unsafe {
+            let title = make_nsstring(&strip_access_key(text));
+            let () = msg_send![menu.menu, setTitle: title];
             let menu_item = NSMenuItem::new(nil).autorelease();
+            let () = msg_send![menu_item, setTitle: title];
             menu_item.setSubmenu_(menu.menu);
             self.menu.addItem_(menu_item);
         }
     }
 
     pub fn add_item(&mut self, id: u32, text: &str, key: impl Into<MenuKey>) {
+        self.add_item_with_state(id, text, key, true, false);
+    }
+
+    /// Add an item to the menu, which can be disabled or show a checkmark.
+    pub fn add_item_with_state(
+        &mut self,
+        id: u32,
+        text: &str,
+        key: impl Into<MenuKey>,
+        enabled: bool,
+        checked: bool,
+    ) {
         let menu_item = make_menu_item(id, text, key);
         unsafe {
+            let () = msg_send![menu_item, setEnabled: if enabled { YES } else { NO }];
+            let state: NSInteger = if checked { 1 } else { 0 };
+            let () = msg_send![menu_item, setState: state];
             self.menu.addItem_(menu_item);
         }
     }
 
+    /// Add an item sending a standard Cocoa action rather than a command.
+    fn add_basic_item(&mut self, action: Sel, text: &str, key: impl Into<MenuKey>) {
+        unsafe {
+            self.menu.addItem_(make_basic_menu_item(action, text, key));
+        }
+    }
+
     pub fn add_separator(&mut self) {
         unsafe {
             let sep = id::separatorItem(self.menu);
             self.menu.addItem_(sep);
         }
     }
+
+    /// Send the items to `view` rather than up the responder chain, for
+    /// menus shown while the view's window may not be key.
+    pub(crate) fn set_target(&self, view: id) {
+        unsafe { set_target(self.menu, view) }
+    }
+}
+
+unsafe fn set_target(menu: id, view: id) {
+    let count: NSInteger = msg_send![menu, numberOfItems];
+    for i in 0..count {
+        let item: id = msg_send![menu, itemAtIndex: i];
+        let submenu: id = msg_send![item, submenu];
+        if submenu != nil {
+            set_target(submenu, view);
+        }
+        let action: Sel = msg_send![item, action];
+        if action == sel!(handleMenuItem:) {
+            let () = msg_send![item, setTarget: view];
+        }
+    }
 }
 
 impl Default for Menu {
     fn default() -> Menu {
-        // The top level menu is just to contain the menus
-        let mut menu = Menu::new();
-        // this one is our actual menu
-        let mut submenu = Menu::new();
-        submenu.add_item(1, "Quit", 'q');
-        menu.add_dropdown(submenu, "Application");
-        menu
+        // The application menu is added when the menubar is installed.
+        Menu::new()
+    }
+}
+
+/// Make `menu` the menubar of the application, adding the standard
+/// application menu in front, and an Edit menu unless the app has its own.
+pub(crate) fn set_main_menu(menu: Menu) {
+    unsafe {
+        let menubar = menu.menu;
+        let name: id = msg_send![class!(NSProcessInfo), processInfo];
+        let name: id = msg_send![name, processName];
+        let name = from_nsstring(name);
+
+        let mut app_menu = Menu::new();
+        app_menu.add_basic_item(
+            sel!(orderFrontStandardAboutPanel:),
+            &format!("About {}", name),
+            (),
+        );
+        app_menu.add_separator();
+        app_menu.add_item(commands::PREFERENCES, "Preferences…", ',');
+        app_menu.add_separator();
+        app_menu.add_basic_item(sel!(hide:), &format!("Hide {}", name), 'h');
+        app_menu.add_basic_item(
+            sel!(hideOtherApplications:),
+            "Hide Others",
+            MenuKey::new(M_ALT | M_META, 'h'),
+        );
+        app_menu.add_basic_item(sel!(unhideAllApplications:), "Show All", ());
+        app_menu.add_separator();
+        app_menu.add_basic_item(sel!(terminate:), &format!("Quit {}", name), 'q');
+        insert_dropdown(menubar, app_menu, &name, 0);
+
+        let mut edit_index = 1;
+        let count: NSInteger = msg_send![menubar, numberOfItems];
+        for i in 1..count {
+            let item: id = msg_send![menubar, itemAtIndex: i];
+            let title = from_nsstring(msg_send![item, title]);
+            if title == "Edit" {
+                edit_index = 0;
+                break;
+            }
+            if title == "File" {
+                edit_index = i + 1;
+            }
+        }
+        if edit_index != 0 {
+            let mut edit_menu = Menu::new();
+            edit_menu.add_item(commands::UNDO, "Undo", 'z');
+            edit_menu.add_item(commands::REDO, "Redo", MenuKey::new(M_SHIFT | M_META, 'z'));
+            edit_menu.add_separator();
+            edit_menu.add_item(commands::CUT, "Cut", 'x');
+            edit_menu.add_item(commands::COPY, "Copy", 'c');
+            edit_menu.add_item(commands::PASTE, "Paste", 'v');
+            edit_menu.add_item(commands::SELECT_ALL, "Select All", 'a');
+            insert_dropdown(menubar, edit_menu, "Edit", edit_index);
+        }
+
+        NSApp().setMainMenu_(menubar);
     }
 }
+
+unsafe fn insert_dropdown(menubar: id, menu: Menu, title: &str, index: NSInteger) {
+    let title = make_nsstring(title);
+    let () = msg_send![menu.menu, setTitle: title];
+    let menu_item = NSMenuItem::new(nil).autorelease();
+    let () = msg_send![menu_item, setTitle: title];
+    menu_item.setSubmenu_(menu.menu);
+    let () = msg_send![menubar, insertItem: menu_item atIndex: index];
+}
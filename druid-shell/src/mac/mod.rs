@@ -15,38 +15,65 @@
 //! macOS implementation of window creation.
 #![allow(non_snake_case)]
 
+mod accessibility;
 pub mod application;
-pub mod dialog;
+pub mod clipboard;
+mod dialog;
+mod display_link;
+mod drag_source;
+mod hotkey;
 pub mod menu;
+pub mod native_view;
+pub mod surface;
+mod text_input;
 pub mod util;
 pub mod win_main;
 
 use cocoa::appkit::{
-    NSApp, NSApplication, NSApplicationActivateIgnoringOtherApps, NSAutoresizingMaskOptions,
-    NSBackingStoreBuffered, NSEvent, NSEventModifierFlags, NSRunningApplication, NSView,
-    NSViewHeightSizable, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
+    NSApp, NSApplicationActivateIgnoringOtherApps, NSAutoresizingMaskOptions,
+    NSBackingStoreBuffered, NSEvent, NSEventModifierFlags, NSEventPhase, NSFilenamesPboardType,
+    NSRunningApplication, NSView, NSViewHeightSizable, NSViewNotSizable, NSViewWidthSizable,
+    NSWindow, NSWindowStyleMask,
 };
 use cocoa::base::{id, nil, BOOL, NO, YES};
-use cocoa::foundation::{NSAutoreleasePool, NSPoint, NSRect, NSSize, NSString};
+use cocoa::foundation::{
+    NSArray, NSAutoreleasePool, NSInteger, NSPoint, NSRect, NSSize, NSString, NSUInteger,
+};
 pub use menu::Menu;
 use objc::declare::ClassDecl;
-use objc::rc::WeakPtr;
+use objc::rc::{StrongPtr, WeakPtr};
 use objc::runtime::{Class, Object, Sel};
 use std::any::Any;
 use std::ffi::c_void;
-use std::ffi::OsString;
+use std::ffi::{CStr, OsString};
 use std::mem;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex, Weak};
+use std::time::{Duration, Instant};
 
 use cairo::{Context, QuartzSurface};
 
-use piet_common::{Piet, RenderContext};
+use piet_common::kurbo::Rect;
+use piet_common::{Color, Piet, RenderContext};
 
-use crate::keyboard::{KeyEvent, KeyModifiers};
-use crate::platform::dialog::{FileDialogOptions, FileDialogType};
+use crate::accessibility::{AccessTree, Politeness};
+use crate::alert::{AlertOptions, AlertResponse, AlertStyle};
+use crate::blend::Painting;
+use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::drag::DragData;
+use crate::keyboard::{Key, KeyEvent, KeyLocation, KeyModifiers};
+use crate::keycodes::MenuKey;
+use crate::timer::{TimerQueue, TimerToken, COALESCE_SLACK};
 use crate::util::make_nsstring;
-use crate::window::{MouseButton, MouseEvent, WinHandler};
+use crate::window::{
+    self, Cursor, CursorDesc, GesturePhase, IconDesc, MouseButton, MouseEvent, PenEvent, PenPhase,
+    PowerSource, RawDisplayHandle, RawWindowHandle, Theme, TrackpadGesture, TrackpadGestureKind,
+    WheelEvent, WheelPhase, WheelUnit, WinHandler, WindowBackdrop, WindowGeometry, WindowKind,
+    WindowLevel, WindowState,
+};
 use crate::Error;
+use application::Application;
+use dialog::get_file_dialog_paths;
 
 use util::assert_main_thread;
 
@@ -67,8 +94,44 @@ pub struct WindowBuilder {
     title: String,
     enable_mouse_move_events: bool,
     menu: Option<Menu>,
+    kind: WindowKind,
+    level: WindowLevel,
+    owner: Option<WindowHandle>,
+    position: Option<(f64, f64)>,
+    centered: bool,
+    size: Option<(f64, f64)>,
+    vsync: bool,
+    icons: Vec<IconDesc>,
+    backdrop: WindowBackdrop,
+    parent: Option<RawWindowHandle>,
 }
 
+// Style mask bits for NSPanel that the cocoa crate doesn't define.
+const NS_UTILITY_WINDOW_MASK: NSUInteger = 1 << 4;
+const NS_NONACTIVATING_PANEL_MASK: NSUInteger = 1 << 7;
+const NS_WINDOW_ABOVE: NSInteger = 1;
+
+const NS_VARIABLE_STATUS_ITEM_LENGTH: f64 = -1.0;
+
+// From NSVisualEffectView.h.
+const NS_VISUAL_EFFECT_MATERIAL_SIDEBAR: NSInteger = 7;
+const NS_VISUAL_EFFECT_MATERIAL_UNDER_WINDOW_BACKGROUND: NSInteger = 21;
+const NS_VISUAL_EFFECT_BLENDING_MODE_BEHIND_WINDOW: NSInteger = 0;
+
+const NS_ALERT_STYLE_WARNING: NSUInteger = 0;
+const NS_ALERT_STYLE_INFORMATIONAL: NSUInteger = 1;
+const NS_ALERT_STYLE_CRITICAL: NSUInteger = 2;
+const NS_ALERT_FIRST_BUTTON_RETURN: NSInteger = 1000;
+
+// Window levels, from CGWindowLevel.h.
+const NS_NORMAL_WINDOW_LEVEL: NSInteger = 0;
+const NS_FLOATING_WINDOW_LEVEL: NSInteger = 3;
+const NS_POP_UP_MENU_WINDOW_LEVEL: NSInteger = 101;
+
+/// A cursor made from an image.
+#[derive(Clone)]
+pub struct CustomCursor(StrongPtr);
+
 #[derive(Clone)]
 pub struct IdleHandle {
     nsview: WeakPtr,
@@ -89,6 +152,90 @@ impl<F: FnOnce(&dyn Any) + Send> IdleCallback for F {
 struct ViewState {
     handler: Box<dyn WinHandler>,
     idle_queue: Arc<Mutex<Vec<Box<dyn IdleCallback>>>>,
+    /// Whether the pen in proximity of the tablet is using its eraser end.
+    pen_eraser: bool,
+    /// The window state last reported to the handler.
+    window_state: WindowState,
+    /// The status bar item added with `set_tray_icon`.
+    tray_item: Option<StrongPtr>,
+    /// The key down event being interpreted by the input method.
+    pending_key: Option<KeyEvent>,
+    /// The text being composed with the input method, if any.
+    marked_text: Option<String>,
+    /// The caret rectangle for the input method, in px.
+    caret_rect: Option<Rect>,
+    /// Whether painting is paced by the display refresh.
+    vsync: bool,
+    /// Whether `WinHandler::frame` is due at the next refresh.
+    frame_requested: bool,
+    /// Whether the view is to be painted at the next refresh.
+    paint_requested: bool,
+    display_link: Option<display_link::DisplayLink>,
+    /// Whether the drag begun by the view allows moving its data.
+    drag_allow_move: bool,
+    /// The material of the visual effect view behind the view.
+    backdrop: WindowBackdrop,
+    timers: TimerQueue<Instant>,
+    /// The `NSTimer` due at the earliest deadline of the timers.
+    timer: Option<StrongPtr>,
+    /// Whether the view is embedded in a view of the host application,
+    /// rather than being the content of its own window.
+    embedded: bool,
+    /// The system settings and power source last reported to the handler.
+    theme: Theme,
+    accent_color: Option<u32>,
+    reduce_transparency: bool,
+    power_source: PowerSource,
+    /// The run loop source calling `power_source_changed`.
+    power_notification: Option<StrongPtr>,
+    access: accessibility::AccessState,
+}
+
+impl ViewState {
+    /// Ask for `handleFrame` at the next display refresh.
+    fn request_tick(&mut self, view: id) {
+        if self.display_link.is_none() {
+            self.display_link = display_link::DisplayLink::new(view);
+        }
+        match self.display_link {
+            Some(ref link) => link.start(),
+            // Without a display link, paint right away.
+            None => unsafe {
+                let () = msg_send![view, setNeedsDisplay: YES];
+            },
+        }
+    }
+
+    /// Schedule `handleTimer:` at the earliest deadline of the timers. The
+    /// timer runs in the common modes, so it also fires during live resize
+    /// and menu tracking.
+    fn schedule_timers(&mut self, view: id) {
+        unsafe {
+            if let Some(timer) = self.timer.take() {
+                let () = msg_send![*timer, invalidate];
+            }
+            if let Some(deadline) = self.timers.next_deadline() {
+                let now = Instant::now();
+                let delay = if deadline > now {
+                    deadline - now
+                } else {
+                    Duration::from_secs(0)
+                };
+                let interval = delay.as_secs() as f64 + f64::from(delay.subsec_nanos()) * 1e-9;
+                let timer: id = msg_send![class!(NSTimer),
+                    timerWithTimeInterval: interval
+                    target: view
+                    selector: sel!(handleTimer:)
+                    userInfo: nil
+                    repeats: NO];
+                let run_loop: id = msg_send![class!(NSRunLoop), currentRunLoop];
+                let () = msg_send![run_loop,
+                    addTimer: timer
+                    forMode: make_nsstring("kCFRunLoopCommonModes")];
+                self.timer = Some(StrongPtr::retain(timer));
+            }
+        }
+    }
 }
 
 impl WindowBuilder {
@@ -98,6 +245,16 @@ impl WindowBuilder {
             title: String::new(),
             enable_mouse_move_events: true,
             menu: Some(Menu::default()),
+            kind: WindowKind::Normal,
+            level: WindowLevel::Normal,
+            owner: None,
+            position: None,
+            centered: false,
+            size: None,
+            vsync: true,
+            icons: Vec::new(),
+            backdrop: WindowBackdrop::None,
+            parent: None,
         }
     }
 
@@ -111,30 +268,142 @@ impl WindowBuilder {
 
     pub fn set_menu(&mut self, menu: Menu) {
         self.menu = Some(menu);
-        // TODO
     }
     pub fn set_enable_mouse_move_events(&mut self, to: bool) {
         self.enable_mouse_move_events = to;
     }
 
+    pub fn set_kind(&mut self, kind: WindowKind) {
+        self.kind = kind;
+    }
+
+    pub fn set_level(&mut self, level: WindowLevel) {
+        self.level = level;
+    }
+
+    /// Set the window that owns this one. An owned window is attached as a
+    /// child window, so it moves, hides and shows along with its owner.
+    pub fn set_owner(&mut self, owner: &WindowHandle) {
+        self.owner = Some(owner.clone());
+    }
+
+    /// Set the initial position of the window, in px. The position is
+    /// relative to the top left corner of the owner window if there is one,
+    /// otherwise to the main screen.
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.position = Some((x, y));
+    }
+
+    /// Center the window on its owner if there is one, otherwise on the
+    /// screen. This takes precedence over `set_position`.
+    pub fn set_centered(&mut self) {
+        self.centered = true;
+    }
+
+    /// Set the initial size of the window, in px.
+    pub fn set_size(&mut self, width: f64, height: f64) {
+        self.size = Some((width, height));
+    }
+
+    /// Set the icon given in several resolutions. See
+    /// `WindowHandle::set_icon`.
+    pub fn set_icon(&mut self, icons: Vec<IconDesc>) {
+        self.icons = icons;
+    }
+
+    /// Set whether painting is paced by the display refresh. On by default.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+    }
+
+    /// Show a vibrant material behind the window, where it paints
+    /// transparent pixels.
+    pub fn set_backdrop(&mut self, backdrop: WindowBackdrop) {
+        self.backdrop = backdrop;
+    }
+
+    /// Build the view as a subview of a view of the host application,
+    /// rather than in a window of its own, as for a plugin UI.
+    ///
+    /// The view fills the parent and follows its size, unless given a size.
+    /// The position is in the coordinates of the parent. The title, menu,
+    /// kind, level and backdrop are ignored, and the methods of the handle
+    /// that act on the window as a whole do nothing.
+    ///
+    /// # Safety
+    ///
+    /// `parent` must be the `AppKit` handle of a view that outlives the
+    /// window, or until the window is closed.
+    pub unsafe fn set_parent(&mut self, parent: RawWindowHandle) {
+        self.parent = Some(parent);
+    }
+
     pub fn build(self) -> Result<WindowHandle, Error> {
         assert_main_thread();
+        if let Some(parent) = self.parent {
+            return unsafe { self.build_embedded(parent) };
+        }
         unsafe {
             let style_mask = NSWindowStyleMask::NSTitledWindowMask
                 | NSWindowStyleMask::NSClosableWindowMask
                 | NSWindowStyleMask::NSMiniaturizableWindowMask
                 | NSWindowStyleMask::NSResizableWindowMask;
-            let rect = NSRect::new(NSPoint::new(0., 0.), NSSize::new(500., 400.));
+            let (width, height) = self.size.unwrap_or((500.0, 400.0));
+            let rect = NSRect::new(NSPoint::new(0., 0.), NSSize::new(width, height));
 
-            let window = NSWindow::alloc(nil).initWithContentRect_styleMask_backing_defer_(
-                rect,
-                style_mask,
-                NSBackingStoreBuffered,
-                NO,
-            );
+            let window: id = match self.kind {
+                WindowKind::Normal => NSWindow::alloc(nil)
+                    .initWithContentRect_styleMask_backing_defer_(
+                        rect,
+                        style_mask,
+                        NSBackingStoreBuffered,
+                        NO,
+                    ),
+                WindowKind::Palette => {
+                    let style_mask = NSWindowStyleMask::NSTitledWindowMask.bits()
+                        | NSWindowStyleMask::NSClosableWindowMask.bits()
+                        | NSWindowStyleMask::NSResizableWindowMask.bits()
+                        | NS_UTILITY_WINDOW_MASK
+                        | NS_NONACTIVATING_PANEL_MASK;
+                    let panel: id = msg_send![class!(NSPanel), alloc];
+                    let panel: id = msg_send![panel,
+                        initWithContentRect: rect
+                        styleMask: style_mask
+                        backing: NSBackingStoreBuffered
+                        defer: NO];
+                    let () = msg_send![panel, setFloatingPanel: YES];
+                    let () = msg_send![panel, setBecomesKeyOnlyIfNeeded: YES];
+                    panel
+                }
+            };
 
             window.autorelease();
-            window.cascadeTopLeftFromPoint_(NSPoint::new(20.0, 20.0));
+            // Palettes already float; leave their level alone.
+            if self.level != WindowLevel::Normal {
+                let () = msg_send![window, setLevel: ns_window_level(self.level)];
+            }
+            let owner_window: Option<id> = self
+                .owner
+                .as_ref()
+                .and_then(|owner| owner.nsview.as_ref())
+                .map(|nsview| msg_send![*nsview.load(), window]);
+            match self.position {
+                Some((x, y)) => {
+                    let frame: NSRect = match owner_window {
+                        Some(owner) => NSWindow::frame(owner),
+                        None => {
+                            let screen: id = msg_send![class!(NSScreen), mainScreen];
+                            msg_send![screen, frame]
+                        }
+                    };
+                    // Cocoa screen coordinates have their origin at the bottom left.
+                    let top = frame.origin.y + frame.size.height;
+                    window.setFrameTopLeftPoint_(NSPoint::new(frame.origin.x + x, top - y));
+                }
+                None => {
+                    window.cascadeTopLeftFromPoint_(NSPoint::new(20.0, 20.0));
+                }
+            }
             window.setTitle_(make_nsstring(&self.title));
             // TODO: this should probably be a tracking area instead
             window.setAcceptsMouseMovedEvents_(YES);
@@ -144,26 +413,109 @@ impl WindowBuilder {
             let frame = NSView::frame(content_view);
             view.initWithFrame_(frame);
             match self.menu {
-                Some(menu) => NSApp().setMainMenu_(menu.menu),
+                // Palettes share the menu bar of their owner.
+                Some(menu) if self.kind == WindowKind::Normal => menu::set_main_menu(menu),
                 _ => (),
             }
+            let material = match self.backdrop {
+                WindowBackdrop::None => None,
+                WindowBackdrop::Blur => Some(NS_VISUAL_EFFECT_MATERIAL_UNDER_WINDOW_BACKGROUND),
+                WindowBackdrop::Sidebar => Some(NS_VISUAL_EFFECT_MATERIAL_SIDEBAR),
+            };
+            if let Some(material) = material {
+                let effect_view: id = msg_send![class!(NSVisualEffectView), alloc];
+                let effect_view: id = msg_send![effect_view, initWithFrame: frame];
+                let () = msg_send![effect_view, setMaterial: material];
+                let () = msg_send![effect_view,
+                    setBlendingMode: NS_VISUAL_EFFECT_BLENDING_MODE_BEHIND_WINDOW];
+                effect_view.setAutoresizingMask_(NSViewWidthSizable | NSViewHeightSizable);
+                content_view.addSubview_(effect_view);
+                let _: id = msg_send![effect_view, autorelease];
+            }
             content_view.addSubview_(view);
+            let () = msg_send![window, makeFirstResponder: view];
+            // The view answers `windowShouldClose:`.
+            let () = msg_send![window, setDelegate: view];
+            let center: id = msg_send![class!(NSNotificationCenter), defaultCenter];
+            for name in &[
+                "NSWindowDidMiniaturizeNotification",
+                "NSWindowDidDeminiaturizeNotification",
+                "NSWindowDidEnterFullScreenNotification",
+                "NSWindowDidExitFullScreenNotification",
+            ] {
+                let () = msg_send![center,
+                    addObserver: view
+                    selector: sel!(windowStateChanged:)
+                    name: make_nsstring(name)
+                    object: window];
+            }
+            if let Some(owner) = owner_window {
+                let () = msg_send![owner, addChildWindow: window ordered: NS_WINDOW_ABOVE];
+            }
             let handle = WindowHandle {
                 nsview: Some(WeakPtr::new(view)),
                 idle_queue,
             };
+            if self.centered {
+                match self.owner {
+                    Some(ref owner) => handle.center_on(owner),
+                    None => handle.center_on_screen(),
+                }
+            }
             let view_state: *mut c_void = *(*view).get_ivar("viewState");
             let view_state = &mut *(view_state as *mut ViewState);
+            (*view_state).vsync = self.vsync;
+            (*view_state).backdrop = self.backdrop;
             (*view_state).handler.connect(&crate::window::WindowHandle {
                 inner: handle.clone(),
             });
             (*view_state)
                 .handler
                 .size(frame.size.width as u32, frame.size.height as u32);
+            if !self.icons.is_empty() {
+                if let Err(e) = handle.set_icon(&self.icons) {
+                    println!("Error setting the icon: {:?}", e);
+                }
+            }
 
             Ok(handle)
         }
     }
+
+    unsafe fn build_embedded(self, parent: RawWindowHandle) -> Result<WindowHandle, Error> {
+        let parent = match parent {
+            RawWindowHandle::AppKit { ns_view, .. } if !ns_view.is_null() => ns_view as id,
+            _ => return Err(Error::Other("the parent must be an AppKit view")),
+        };
+        let (view, idle_queue) = make_view(self.handler.expect("view"));
+        let (x, y) = self.position.unwrap_or((0.0, 0.0));
+        let frame = match self.size {
+            Some((width, height)) => {
+                view.setAutoresizingMask_(NSViewNotSizable);
+                NSRect::new(NSPoint::new(x, y), NSSize::new(width, height))
+            }
+            None => NSView::bounds(parent),
+        };
+        view.initWithFrame_(frame);
+        // Like windows, the view stays hidden until shown.
+        let () = msg_send![view, setHidden: YES];
+        parent.addSubview_(view);
+        let handle = WindowHandle {
+            nsview: Some(WeakPtr::new(view)),
+            idle_queue,
+        };
+        let view_state: *mut c_void = *(*view).get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        view_state.vsync = self.vsync;
+        view_state.embedded = true;
+        view_state.handler.connect(&crate::window::WindowHandle {
+            inner: handle.clone(),
+        });
+        view_state
+            .handler
+            .size(frame.size.width as u32, frame.size.height as u32);
+        Ok(handle)
+    }
 }
 
 // Wrap pointer because lazy_static requires Sync.
@@ -189,14 +541,48 @@ lazy_static! {
         extern "C" fn acceptsFirstResponder(_this: &Object, _sel: Sel) -> BOOL {
             YES
         }
+        decl.add_method(
+            sel!(handleMenuItem:),
+            handle_menu_item as extern "C" fn(&mut Object, Sel, id),
+        );
         decl.add_method(sel!(dealloc), dealloc as extern "C" fn(&Object, Sel));
         extern "C" fn dealloc(this: &Object, _sel: Sel) {
             eprintln!("view is dealloc'ed");
             unsafe {
+                for center in &system_notification_centers() {
+                    let () = msg_send![*center, removeObserver: this];
+                }
                 let view_state: *mut c_void = *this.get_ivar("viewState");
-                Box::from_raw(view_state as *mut ViewState);
+                let view_state = Box::from_raw(view_state as *mut ViewState);
+                if let Some(ref source) = view_state.power_notification {
+                    application::remove_power_notification(source);
+                }
+                if let Some(ref item) = view_state.tray_item {
+                    remove_status_item(**item);
+                }
+                view_state.handler.destroy();
             }
         }
+        decl.add_method(
+            sel!(windowShouldClose:),
+            window_should_close as extern "C" fn(&mut Object, Sel, id) -> BOOL,
+        );
+        decl.add_method(
+            sel!(windowWillClose:),
+            window_will_close as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(handleTrayClick:),
+            handle_tray_click as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(windowStateChanged:),
+            window_state_changed as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(systemSettingsChanged:),
+            system_settings_changed as extern "C" fn(&mut Object, Sel, id),
+        );
         decl.add_method(
             sel!(setFrameSize:),
             set_frame_size as extern "C" fn(&mut Object, Sel, NSSize),
@@ -223,7 +609,19 @@ lazy_static! {
         );
         decl.add_method(
             sel!(mouseDragged:),
-            mouse_move as extern "C" fn(&mut Object, Sel, id),
+            mouse_dragged as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(magnifyWithEvent:),
+            magnify as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(rotateWithEvent:),
+            rotate as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(tabletProximity:),
+            tablet_proximity as extern "C" fn(&mut Object, Sel, id),
         );
         decl.add_method(
             sel!(scrollWheel:),
@@ -234,12 +632,39 @@ lazy_static! {
             key_down as extern "C" fn(&mut Object, Sel, id),
         );
         decl.add_method(sel!(keyUp:), key_up as extern "C" fn(&mut Object, Sel, id));
+        text_input::add_methods(&mut decl);
+        drag_source::add_methods(&mut decl);
+        accessibility::add_methods(&mut decl);
+        decl.add_method(
+            sel!(draggingEntered:),
+            dragging_entered as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+        );
+        decl.add_method(
+            sel!(draggingUpdated:),
+            dragging_updated as extern "C" fn(&mut Object, Sel, id) -> NSUInteger,
+        );
+        decl.add_method(
+            sel!(draggingExited:),
+            dragging_exited as extern "C" fn(&mut Object, Sel, id),
+        );
+        decl.add_method(
+            sel!(performDragOperation:),
+            perform_drag_operation as extern "C" fn(&mut Object, Sel, id) -> BOOL,
+        );
         decl.add_method(
             sel!(drawRect:),
             draw_rect as extern "C" fn(&mut Object, Sel, NSRect),
         );
         decl.add_method(sel!(runIdle), run_idle as extern "C" fn(&mut Object, Sel));
         decl.add_method(sel!(redraw), redraw as extern "C" fn(&mut Object, Sel));
+        decl.add_method(
+            sel!(handleFrame),
+            handle_frame as extern "C" fn(&mut Object, Sel),
+        );
+        decl.add_method(
+            sel!(handleTimer:),
+            handle_timer as extern "C" fn(&mut Object, Sel, id),
+        );
         ViewClass(decl.register())
     };
 }
@@ -250,17 +675,86 @@ fn make_view(handler: Box<dyn WinHandler>) -> (id, Weak<Mutex<Vec<Box<dyn IdleCa
     let state = ViewState {
         handler,
         idle_queue,
+        pen_eraser: false,
+        window_state: WindowState::Restored,
+        tray_item: None,
+        pending_key: None,
+        marked_text: None,
+        caret_rect: None,
+        vsync: true,
+        frame_requested: false,
+        paint_requested: false,
+        display_link: None,
+        drag_allow_move: false,
+        backdrop: WindowBackdrop::None,
+        timers: TimerQueue::new(),
+        timer: None,
+        embedded: false,
+        theme: Application::theme(),
+        accent_color: application::accent_rgba(),
+        reduce_transparency: Application::reduce_transparency(),
+        power_source: Application::power_source(),
+        power_notification: None,
+        access: Default::default(),
     };
     let state_ptr = Box::into_raw(Box::new(state));
     unsafe {
         let view: id = msg_send![VIEW_CLASS.0, new];
         (*view).set_ivar("viewState", state_ptr as *mut c_void);
+        observe_system_settings(view);
+        (*state_ptr).power_notification =
+            application::add_power_notification(power_source_changed, view as *mut c_void);
+        let drag_types = NSArray::arrayWithObject(nil, NSFilenamesPboardType);
+        let () = msg_send![view, registerForDraggedTypes: drag_types];
         let options: NSAutoresizingMaskOptions = NSViewWidthSizable | NSViewHeightSizable;
         view.setAutoresizingMask_(options);
         (view.autorelease(), queue_handle)
     }
 }
 
+/// The action of all our menu items; the tag of the item is its command id.
+extern "C" fn handle_menu_item(this: &mut Object, _: Sel, item: id) {
+    unsafe {
+        let tag: NSInteger = msg_send![item, tag];
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        (*view_state).handler.command(tag as u32);
+    }
+}
+
+extern "C" fn window_should_close(this: &mut Object, _: Sel, _sender: id) -> BOOL {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        if (*view_state).handler.request_close() {
+            YES
+        } else {
+            NO
+        }
+    }
+}
+
+/// The window may outlive the view, so stop being its delegate.
+extern "C" fn window_will_close(_this: &mut Object, _: Sel, notification: id) {
+    unsafe {
+        let window: id = msg_send![notification, object];
+        let () = msg_send![window, setDelegate: nil];
+    }
+}
+
+extern "C" fn handle_tray_click(this: &mut Object, _: Sel, _sender: id) {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        (*view_state).handler.tray_icon_clicked();
+    }
+}
+
+unsafe fn remove_status_item(item: id) {
+    let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+    let () = msg_send![status_bar, removeStatusItem: item];
+}
+
 extern "C" fn set_frame_size(this: &mut Object, _: Sel, size: NSSize) {
     println!("size: {}x{}", size.width, size.height);
     unsafe {
@@ -272,6 +766,211 @@ extern "C" fn set_frame_size(this: &mut Object, _: Sel, size: NSSize) {
         let superclass = msg_send![this, superclass];
         let () = msg_send![super(this, superclass), setFrameSize: size];
     }
+    // Zooming only shows up as a resize.
+    update_window_state(this);
+}
+
+extern "C" fn window_state_changed(this: &mut Object, _: Sel, _notification: id) {
+    update_window_state(this);
+}
+
+/// Report a change of window state, if there was one.
+fn update_window_state(view: &mut Object) {
+    unsafe {
+        let window: id = msg_send![view, window];
+        if window == nil {
+            return;
+        }
+        let state = get_window_state(window);
+        let view_state: *mut c_void = *view.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        if (*view_state).window_state != state {
+            (*view_state).window_state = state;
+            (*view_state).handler.window_state_changed(state);
+        }
+    }
+}
+
+/// The notification centers posting changes of the system settings: the
+/// default one, the workspace's, and the distributed one.
+unsafe fn system_notification_centers() -> [id; 3] {
+    let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+    [
+        msg_send![class!(NSNotificationCenter), defaultCenter],
+        msg_send![workspace, notificationCenter],
+        msg_send![class!(NSDistributedNotificationCenter), defaultCenter],
+    ]
+}
+
+/// Call `systemSettingsChanged:` on `view` when the theme, the accent
+/// color or the transparency setting changes.
+unsafe fn observe_system_settings(view: id) {
+    let [center, workspace_center, distributed_center] = system_notification_centers();
+    for &(center, name) in &[
+        (center, "NSSystemColorsDidChangeNotification"),
+        (
+            workspace_center,
+            "NSWorkspaceAccessibilityDisplayOptionsDidChangeNotification",
+        ),
+        (distributed_center, "AppleInterfaceThemeChangedNotification"),
+    ] {
+        let () = msg_send![center,
+            addObserver: view
+            selector: sel!(systemSettingsChanged:)
+            name: make_nsstring(name)
+            object: nil];
+    }
+}
+
+extern "C" fn system_settings_changed(this: &mut Object, _: Sel, _notification: id) {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        let theme = Application::theme();
+        if view_state.theme != theme {
+            view_state.theme = theme;
+            view_state.handler.theme_changed(theme);
+        }
+        let accent_color = application::accent_rgba();
+        if view_state.accent_color != accent_color {
+            view_state.accent_color = accent_color;
+            let color = accent_color.map(Color::rgba32);
+            view_state.handler.accent_color_changed(color);
+        }
+        let reduce = Application::reduce_transparency();
+        if view_state.reduce_transparency != reduce {
+            view_state.reduce_transparency = reduce;
+            view_state.handler.reduce_transparency_changed(reduce);
+        }
+    }
+}
+
+/// Called by IOKit with the view as the context.
+extern "C" fn power_source_changed(context: *mut c_void) {
+    unsafe {
+        let view = context as id;
+        let view_state: *mut c_void = *(*view).get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        let source = Application::power_source();
+        if view_state.power_source != source {
+            view_state.power_source = source;
+            view_state.handler.power_source_changed(source);
+        }
+    }
+}
+
+fn ns_window_level(level: WindowLevel) -> NSInteger {
+    match level {
+        WindowLevel::Normal => NS_NORMAL_WINDOW_LEVEL,
+        WindowLevel::Floating => NS_FLOATING_WINDOW_LEVEL,
+        WindowLevel::Tooltip => NS_POP_UP_MENU_WINDOW_LEVEL,
+    }
+}
+
+/// Make an NSImage from an RGBA image.
+unsafe fn make_nsimage(pixels: &[u8], width: usize, height: usize) -> Option<StrongPtr> {
+    let rep = make_bitmap_rep(pixels, width, height)?;
+    let size = NSSize::new(width as f64, height as f64);
+    let image: id = msg_send![class!(NSImage), alloc];
+    let image = StrongPtr::new(msg_send![image, initWithSize: size]);
+    let () = msg_send![*image, addRepresentation: *rep];
+    Some(image)
+}
+
+/// Make an NSImage holding each resolution of an icon, sized like the
+/// largest one.
+unsafe fn make_icon_nsimage(icons: &[IconDesc]) -> Option<StrongPtr> {
+    let largest = IconDesc::best_fit(icons, usize::max_value())?;
+    let size = NSSize::new(largest.width as f64, largest.height as f64);
+    let image: id = msg_send![class!(NSImage), alloc];
+    let image = StrongPtr::new(msg_send![image, initWithSize: size]);
+    for icon in icons {
+        let rep = make_bitmap_rep(&icon.image, icon.width, icon.height)?;
+        let () = msg_send![*image, addRepresentation: *rep];
+    }
+    Some(image)
+}
+
+unsafe fn make_bitmap_rep(pixels: &[u8], width: usize, height: usize) -> Option<StrongPtr> {
+    let rep: id = msg_send![class!(NSBitmapImageRep), alloc];
+    let rep: id = msg_send![rep,
+        initWithBitmapDataPlanes: std::ptr::null_mut::<*mut u8>()
+        pixelsWide: width as NSInteger
+        pixelsHigh: height as NSInteger
+        bitsPerSample: 8 as NSInteger
+        samplesPerPixel: 4 as NSInteger
+        hasAlpha: YES
+        isPlanar: NO
+        colorSpaceName: make_nsstring("NSDeviceRGBColorSpace")
+        bytesPerRow: (width * 4) as NSInteger
+        bitsPerPixel: 32 as NSInteger];
+    if rep == nil {
+        return None;
+    }
+    let rep = StrongPtr::new(rep);
+    // The image rep expects premultiplied alpha.
+    let data: *mut u8 = msg_send![*rep, bitmapData];
+    let dst = std::slice::from_raw_parts_mut(data, pixels.len());
+    for (dst, src) in dst.chunks_exact_mut(4).zip(pixels.chunks_exact(4)) {
+        let alpha = u16::from(src[3]);
+        for (d, &s) in dst[..3].iter_mut().zip(&src[..3]) {
+            *d = (u16::from(s) * alpha / 255) as u8;
+        }
+        dst[3] = src[3];
+    }
+    Some(rep)
+}
+
+/// Convert a rect in Cocoa screen coordinates to one with the origin at the
+/// top left of the primary screen.
+unsafe fn from_ns_rect(rect: NSRect) -> Rect {
+    let top = primary_screen_height() - (rect.origin.y + rect.size.height);
+    Rect::new(
+        rect.origin.x,
+        top,
+        rect.origin.x + rect.size.width,
+        top + rect.size.height,
+    )
+}
+
+unsafe fn to_ns_rect(rect: Rect) -> NSRect {
+    NSRect::new(
+        NSPoint::new(rect.x0, primary_screen_height() - rect.y1),
+        NSSize::new(rect.width(), rect.height()),
+    )
+}
+
+/// The height of the screen holding the menu bar, which defines the origin
+/// of screen coordinates.
+unsafe fn primary_screen_height() -> f64 {
+    let screens: id = msg_send![class!(NSScreen), screens];
+    let screen: id = msg_send![screens, objectAtIndex: 0 as NSUInteger];
+    let frame: NSRect = msg_send![screen, frame];
+    frame.size.height
+}
+
+/// Move a window so that it is centered in `area`, in Cocoa screen
+/// coordinates.
+unsafe fn center_window(window: id, area: NSRect) {
+    let frame = NSWindow::frame(window);
+    let x = area.origin.x + (area.size.width - frame.size.width) / 2.0;
+    let y = area.origin.y + (area.size.height - frame.size.height) / 2.0;
+    window.setFrameOrigin_(NSPoint::new(x, y));
+}
+
+unsafe fn get_window_state(window: id) -> WindowState {
+    let style_mask: NSUInteger = msg_send![window, styleMask];
+    let miniaturized: BOOL = msg_send![window, isMiniaturized];
+    let zoomed: BOOL = msg_send![window, isZoomed];
+    if style_mask & NSWindowStyleMask::NSFullScreenWindowMask.bits() != 0 {
+        WindowState::Fullscreen
+    } else if miniaturized == YES {
+        WindowState::Minimized
+    } else if zoomed == YES {
+        WindowState::Maximized
+    } else {
+        WindowState::Restored
+    }
 }
 
 // NOTE: If we know the button (because of the origin call) we pass it through,
@@ -325,6 +1024,9 @@ fn mouse_down(this: &mut Object, nsevent: id, button: MouseButton) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        if pen_event(view_state, this as id, nsevent, PenPhase::Down) {
+            return;
+        }
         let event = mouse_event(nsevent, this as id, true, Some(button));
         (*view_state).handler.mouse(&event);
     }
@@ -342,54 +1044,165 @@ fn mouse_up(this: &mut Object, nsevent: id, button: MouseButton) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        if pen_event(view_state, this as id, nsevent, PenPhase::Up) {
+            return;
+        }
         let event = mouse_event(nsevent, this as id, false, Some(button));
         (*view_state).handler.mouse(&event);
     }
 }
 
 extern "C" fn mouse_move(this: &mut Object, _: Sel, nsevent: id) {
+    mouse_moved(this, nsevent, PenPhase::Hover)
+}
+
+extern "C" fn mouse_dragged(this: &mut Object, _: Sel, nsevent: id) {
+    mouse_moved(this, nsevent, PenPhase::Move)
+}
+
+fn mouse_moved(this: &mut Object, nsevent: id, pen_phase: PenPhase) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
+        if pen_event(view_state, this as id, nsevent, pen_phase) {
+            return;
+        }
         let event = mouse_event(nsevent, this as id, false, None);
         (*view_state).handler.mouse_move(&event);
     }
 }
 
+const NS_TABLET_POINT_EVENT_SUBTYPE: i16 = 1;
+const NS_ERASER_POINTING_DEVICE: NSUInteger = 3;
+const NS_PEN_LOWER_SIDE_MASK: NSUInteger = 2;
+const NS_PEN_UPPER_SIDE_MASK: NSUInteger = 4;
+
+/// Deliver a mouse event that comes from a tablet pen to the handler as a
+/// pen event. Returns true if it was handled.
+fn pen_event(view_state: &mut ViewState, view: id, nsevent: id, phase: PenPhase) -> bool {
+    unsafe {
+        let subtype: i16 = msg_send![nsevent, subtype];
+        if subtype != NS_TABLET_POINT_EVENT_SUBTYPE {
+            return false;
+        }
+        let point = view.convertPoint_fromView_(nsevent.locationInWindow(), nil);
+        // Tilt is reported from -1 to 1, with positive y away from the user.
+        let tilt = nsevent.tilt();
+        let buttons: NSUInteger = msg_send![nsevent, buttonMask];
+        let event = PenEvent {
+            x: point.x as i32,
+            y: point.y as i32,
+            phase,
+            pressure: nsevent.pressure() as f64,
+            tilt_x: tilt.x * 90.0,
+            tilt_y: -tilt.y * 90.0,
+            barrel: buttons & (NS_PEN_LOWER_SIDE_MASK | NS_PEN_UPPER_SIDE_MASK) != 0,
+            eraser: view_state.pen_eraser,
+            mods: make_modifiers(nsevent.modifierFlags()),
+        };
+        view_state.handler.pen(&event)
+    }
+}
+
+extern "C" fn tablet_proximity(this: &mut Object, _: Sel, nsevent: id) {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        let device: NSUInteger = msg_send![nsevent, pointingDeviceType];
+        view_state.pen_eraser = device == NS_ERASER_POINTING_DEVICE;
+    }
+}
+
 extern "C" fn scroll_wheel(this: &mut Object, _: Sel, nsevent: id) {
     unsafe {
         let view_state: *mut c_void = *this.get_ivar("viewState");
         let view_state = &mut *(view_state as *mut ViewState);
-        let (dx, dy) = {
-            let dx = nsevent.scrollingDeltaX() as i32;
-            let dy = -nsevent.scrollingDeltaY() as i32;
-            if nsevent.hasPreciseScrollingDeltas() == cocoa::base::YES {
-                (dx, dy)
-            } else {
-                (dx * 32, dy * 32)
-            }
+        // Cocoa deltas are the direction the content moves, and already
+        // account for the "natural scrolling" setting.
+        let dx = -nsevent.scrollingDeltaX() as f64;
+        let dy = -nsevent.scrollingDeltaY() as f64;
+        let unit = if nsevent.hasPreciseScrollingDeltas() == cocoa::base::YES {
+            WheelUnit::Pixels
+        } else {
+            WheelUnit::Lines
         };
         let mods = nsevent.modifierFlags();
         let mods = make_modifiers(mods);
+        let event = WheelEvent {
+            dx,
+            dy,
+            unit,
+            mods,
+            phase: make_wheel_phase(nsevent.phase(), nsevent.momentumPhase()),
+        };
+        (*view_state).handler.wheel(&event);
+    }
+}
 
-        if dx != 0 {
-            (*view_state).handler.mouse_hwheel(dx, mods);
-        }
+extern "C" fn magnify(this: &mut Object, _: Sel, nsevent: id) {
+    let magnification: f64 = unsafe { msg_send![nsevent, magnification] };
+    trackpad_gesture(this, nsevent, TrackpadGestureKind::Magnify(magnification));
+}
 
-        if dy != 0 {
-            (*view_state).handler.mouse_wheel(dy, mods);
-        }
+extern "C" fn rotate(this: &mut Object, _: Sel, nsevent: id) {
+    // Cocoa reports degrees, counterclockwise.
+    let rotation: f32 = unsafe { msg_send![nsevent, rotation] };
+    let rotation = -(rotation as f64).to_radians();
+    trackpad_gesture(this, nsevent, TrackpadGestureKind::Rotate(rotation));
+}
+
+fn trackpad_gesture(this: &mut Object, nsevent: id, kind: TrackpadGestureKind) {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        let phase = nsevent.phase();
+        let phase = if phase.contains(NSEventPhase::NSEventPhaseBegan) {
+            GesturePhase::Began
+        } else if phase
+            .intersects(NSEventPhase::NSEventPhaseEnded | NSEventPhase::NSEventPhaseCancelled)
+        {
+            GesturePhase::Ended
+        } else {
+            GesturePhase::Changed
+        };
+        let view = this as *mut Object as id;
+        let point = view.convertPoint_fromView_(nsevent.locationInWindow(), nil);
+        let event = TrackpadGesture {
+            x: point.x as i32,
+            y: point.y as i32,
+            kind,
+            phase,
+        };
+        (*view_state).handler.trackpad_gesture(&event);
     }
 }
 
-extern "C" fn key_down(this: &mut Object, _: Sel, nsevent: id) {
-    let event = make_key_event(nsevent);
+fn make_wheel_phase(phase: NSEventPhase, momentum: NSEventPhase) -> WheelPhase {
+    if momentum.contains(NSEventPhase::NSEventPhaseBegan) {
+        WheelPhase::MomentumBegan
+    } else if momentum
+        .intersects(NSEventPhase::NSEventPhaseEnded | NSEventPhase::NSEventPhaseCancelled)
+    {
+        WheelPhase::MomentumEnded
+    } else if !momentum.is_empty() {
+        WheelPhase::Momentum
+    } else if phase.contains(NSEventPhase::NSEventPhaseBegan) {
+        WheelPhase::Began
+    } else if phase.contains(NSEventPhase::NSEventPhaseEnded) {
+        WheelPhase::Ended
+    } else if phase.contains(NSEventPhase::NSEventPhaseCancelled) {
+        WheelPhase::Cancelled
+    } else if phase
+        .intersects(NSEventPhase::NSEventPhaseChanged | NSEventPhase::NSEventPhaseStationary)
+    {
+        WheelPhase::Changed
+    } else {
+        WheelPhase::None
+    }
+}
 
-    let view_state = unsafe {
-        let view_state: *mut c_void = *this.get_ivar("viewState");
-        &mut *(view_state as *mut ViewState)
-    };
-    (*view_state).handler.key_down(event);
+extern "C" fn key_down(this: &mut Object, _: Sel, nsevent: id) {
+    text_input::key_down(this, nsevent);
 }
 
 extern "C" fn key_up(this: &mut Object, _: Sel, nsevent: id) {
@@ -401,38 +1214,123 @@ extern "C" fn key_up(this: &mut Object, _: Sel, nsevent: id) {
     (*view_state).handler.key_up(event);
 }
 
-extern "C" fn draw_rect(this: &mut Object, _: Sel, dirtyRect: NSRect) {
-    unsafe {
-        let context: id = msg_send![class![NSGraphicsContext], currentContext];
-        // TODO: probably should use a better type than void pointer, but it's not obvious what's best.
-        // cairo_sys::CGContextRef would be better documentation-wise, but it's a type alias.
-        let cgcontext: *mut c_void = msg_send![context, CGContext];
-        // TODO: use width and height from view size
-        let frame = NSView::frame(this as *mut _);
-        let width = frame.size.width as u32;
-        let height = frame.size.height as u32;
-        let cairo_surface =
-            QuartzSurface::create_for_cg_context(cgcontext, width, height).expect("cairo surface");
-        let mut cairo_ctx = Context::new(&cairo_surface);
-        cairo_ctx.set_source_rgb(0.0, 0.5, 0.0);
-        cairo_ctx.paint();
-        let mut piet_ctx = Piet::new(&mut cairo_ctx);
-        let view_state: *mut c_void = *this.get_ivar("viewState");
-        let view_state = &mut *(view_state as *mut ViewState);
-        let anim = (*view_state).handler.paint(&mut piet_ctx);
-        if let Err(e) = piet_ctx.finish() {
-            eprintln!("Error: {}", e);
-        }
-        // TODO: log errors
-
-        if anim {
-            // TODO: synchronize with screen refresh rate using CVDisplayLink instead.
-            let () = msg_send!(this as *const _, performSelectorOnMainThread: sel!(redraw)
-                withObject: nil waitUntilDone: NO);
-        }
+const NS_DRAG_OPERATION_NONE: NSUInteger = 0;
+const NS_DRAG_OPERATION_COPY: NSUInteger = 1;
 
-        let superclass = msg_send![this, superclass];
-        let () = msg_send![super(this, superclass), drawRect: dirtyRect];
+/// The location of a drag, relative to the view.
+fn dragging_location(view: id, sender: id) -> (i32, i32) {
+    unsafe {
+        let point: NSPoint = msg_send![sender, draggingLocation];
+        let view_point = view.convertPoint_fromView_(point, nil);
+        (view_point.x as i32, view_point.y as i32)
+    }
+}
+
+/// The file paths on the dragging pasteboard, if any.
+fn dragging_paths(sender: id) -> Vec<PathBuf> {
+    unsafe {
+        let pasteboard: id = msg_send![sender, draggingPasteboard];
+        let filenames: id = msg_send![pasteboard, propertyListForType: NSFilenamesPboardType];
+        if filenames == nil {
+            return Vec::new();
+        }
+        let count: NSUInteger = msg_send![filenames, count];
+        (0..count)
+            .map(|i| {
+                let filename: id = msg_send![filenames, objectAtIndex: i];
+                let filename = CStr::from_ptr(filename.UTF8String());
+                PathBuf::from(filename.to_string_lossy().into_owned())
+            })
+            .collect()
+    }
+}
+
+fn drag_operation(accepted: bool) -> NSUInteger {
+    if accepted {
+        NS_DRAG_OPERATION_COPY
+    } else {
+        NS_DRAG_OPERATION_NONE
+    }
+}
+
+extern "C" fn dragging_entered(this: &mut Object, _: Sel, sender: id) -> NSUInteger {
+    let paths = dragging_paths(sender);
+    let (x, y) = dragging_location(this as *mut Object as id, sender);
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    drag_operation(!paths.is_empty() && (*view_state).handler.file_drag_enter(&paths, x, y))
+}
+
+extern "C" fn dragging_updated(this: &mut Object, _: Sel, sender: id) -> NSUInteger {
+    let (x, y) = dragging_location(this as *mut Object as id, sender);
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    drag_operation((*view_state).handler.file_drag_over(x, y))
+}
+
+extern "C" fn dragging_exited(this: &mut Object, _: Sel, _sender: id) {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    (*view_state).handler.file_drag_leave();
+}
+
+extern "C" fn perform_drag_operation(this: &mut Object, _: Sel, sender: id) -> BOOL {
+    let paths = dragging_paths(sender);
+    let (x, y) = dragging_location(this as *mut Object as id, sender);
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    if !paths.is_empty() && (*view_state).handler.file_drop(paths, x, y) {
+        YES
+    } else {
+        NO
+    }
+}
+
+extern "C" fn draw_rect(this: &mut Object, _: Sel, dirtyRect: NSRect) {
+    unsafe {
+        let context: id = msg_send![class![NSGraphicsContext], currentContext];
+        // TODO: probably should use a better type than void pointer, but it's not obvious what's best.
+        // cairo_sys::CGContextRef would be better documentation-wise, but it's a type alias.
+        let cgcontext: *mut c_void = msg_send![context, CGContext];
+        // TODO: use width and height from view size
+        let frame = NSView::frame(this as *mut _);
+        let width = frame.size.width as u32;
+        let height = frame.size.height as u32;
+        let cairo_surface =
+            QuartzSurface::create_for_cg_context(cgcontext, width, height).expect("cairo surface");
+        let mut cairo_ctx = Context::new(&cairo_surface);
+        cairo_ctx.set_source_rgb(0.0, 0.5, 0.0);
+        cairo_ctx.paint();
+        let _painting = Painting::new(Some(&cairo_ctx));
+        let mut piet_ctx = Piet::new(&mut cairo_ctx);
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        let view_state = &mut *(view_state as *mut ViewState);
+        let anim = (*view_state).handler.paint(&mut piet_ctx);
+        if let Err(e) = piet_ctx.finish() {
+            eprintln!("Error: {}", e);
+        }
+        // TODO: log errors
+
+        if anim {
+            if (*view_state).vsync {
+                (*view_state).paint_requested = true;
+                (*view_state).request_tick(this);
+            } else {
+                let () = msg_send!(this as *const _, performSelectorOnMainThread: sel!(redraw)
+                    withObject: nil waitUntilDone: NO);
+            }
+        }
+
+        let superclass = msg_send![this, superclass];
+        let () = msg_send![super(this, superclass), drawRect: dirtyRect];
     }
 }
 
@@ -451,6 +1349,44 @@ extern "C" fn run_idle(this: &mut Object, _: Sel) {
     }
 }
 
+extern "C" fn handle_frame(this: &mut Object, _: Sel) {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    if let Some(ref link) = view_state.display_link {
+        link.frame_delivered();
+    }
+    let frame_requested = mem::replace(&mut view_state.frame_requested, false);
+    if frame_requested {
+        view_state.handler.frame();
+    }
+    // The handler may have invalidated the view.
+    if mem::replace(&mut view_state.paint_requested, false) {
+        unsafe {
+            let () = msg_send![this as *const _, setNeedsDisplay: YES];
+        }
+    } else if !frame_requested {
+        // Nothing happened since the last refresh.
+        if let Some(ref link) = view_state.display_link {
+            link.stop();
+        }
+    }
+}
+
+extern "C" fn handle_timer(this: &mut Object, _: Sel, _timer: id) {
+    let view_state = unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    };
+    view_state.timer = None;
+    let due = view_state.timers.take_due(Instant::now() + COALESCE_SLACK);
+    for token in due {
+        view_state.handler.timer(token);
+    }
+    view_state.schedule_timers(this as *mut Object as id);
+}
+
 extern "C" fn redraw(this: &mut Object, _: Sel) {
     unsafe {
         let () = msg_send![this as *const _, setNeedsDisplay: YES];
@@ -460,35 +1396,642 @@ extern "C" fn redraw(this: &mut Object, _: Sel) {
 impl WindowHandle {
     pub fn show(&self) {
         unsafe {
+            if let Some(view) = self.embedded_view() {
+                let () = msg_send![view, setHidden: NO];
+                return;
+            }
+            if let Some(window) = self.nswindow() {
+                let level: NSInteger = msg_send![window, level];
+                if level == NS_POP_UP_MENU_WINDOW_LEVEL {
+                    // Tooltips leave the active application alone.
+                    window.orderFront_(nil);
+                    return;
+                }
+            }
             let current_app = NSRunningApplication::currentApplication(nil);
             current_app.activateWithOptions_(NSApplicationActivateIgnoringOtherApps);
-            if let Some(ref nsview) = self.nsview {
-                let window: id = msg_send![*nsview.load(), window];
-                window.makeKeyAndOrderFront_(nil)
+            if let Some(window) = self.nswindow() {
+                let is_panel: BOOL = msg_send![window, isKindOfClass: class!(NSPanel)];
+                if is_panel == YES {
+                    window.orderFront_(nil)
+                } else {
+                    window.makeKeyAndOrderFront_(nil)
+                }
             }
         }
     }
 
-    /// Close the window.
-    pub fn close(&self) {
+    /// Replace the menubar. On macOS the menubar belongs to the application,
+    /// so this affects all windows.
+    pub fn set_menu(&self, menu: Menu) {
+        menu::set_main_menu(menu);
+    }
+
+    pub fn set_title(&self, title: &str) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                window.setTitle_(make_nsstring(title));
+            }
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        match self.nswindow() {
+            Some(window) => unsafe {
+                let title: id = msg_send![window, title];
+                CStr::from_ptr(title.UTF8String())
+                    .to_string_lossy()
+                    .into_owned()
+            },
+            None => String::new(),
+        }
+    }
+
+    /// The size of the content area, in px.
+    pub fn get_size(&self) -> (f64, f64) {
+        match self.nsview {
+            Some(ref nsview) => unsafe {
+                let frame = NSView::frame(*nsview.load());
+                (frame.size.width, frame.size.height)
+            },
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Resize the window so that its content area has the given size, in px.
+    pub fn set_size(&self, width: f64, height: f64) {
+        unsafe {
+            if let Some(view) = self.embedded_view() {
+                let () = msg_send![view, setFrameSize: NSSize::new(width, height)];
+            } else if let Some(window) = self.nswindow() {
+                window.setContentSize_(NSSize::new(width, height));
+            }
+        }
+    }
+
+    /// Show an icon for the window in the status bar, replacing any previous
+    /// one.
+    ///
+    /// With a menu, clicking the icon shows the menu; otherwise it calls the
+    /// handler's `tray_icon_clicked`.
+    pub fn set_tray_icon(
+        &self,
+        icon: &IconDesc,
+        tooltip: &str,
+        menu: Option<Menu>,
+    ) -> Result<(), Error> {
+        let nsview = self.nsview.as_ref().ok_or(Error::Null)?;
+        unsafe {
+            let view = *nsview.load();
+            let image = make_nsimage(&icon.image, icon.width, icon.height).ok_or(Error::Null)?;
+            // Status bar icons are 18 points high; keep the aspect ratio.
+            let height = 18.0;
+            let width = height * icon.width as f64 / icon.height as f64;
+            let () = msg_send![*image, setSize: NSSize::new(width, height)];
+            let status_bar: id = msg_send![class!(NSStatusBar), systemStatusBar];
+            let item: id =
+                msg_send![status_bar, statusItemWithLength: NS_VARIABLE_STATUS_ITEM_LENGTH];
+            let item = StrongPtr::retain(item);
+            let button: id = msg_send![*item, button];
+            let () = msg_send![button, setImage: *image];
+            let () = msg_send![button, setToolTip: make_nsstring(tooltip)];
+            match menu {
+                Some(menu) => {
+                    menu.set_target(view);
+                    let () = msg_send![*item, setMenu: menu.menu];
+                }
+                None => {
+                    let () = msg_send![button, setTarget: view];
+                    let () = msg_send![button, setAction: sel!(handleTrayClick:)];
+                }
+            }
+            let view_state: *mut c_void = *(*view).get_ivar("viewState");
+            let view_state = &mut *(view_state as *mut ViewState);
+            if let Some(old) = (*view_state).tray_item.replace(item) {
+                remove_status_item(*old);
+            }
+        }
+        Ok(())
+    }
+
+    /// Tell the input method where the caret is, in px, so that its
+    /// candidate window is shown next to it.
+    /// Start a drag carrying `data`, out of the window or to another place
+    /// in it. Call this while handling a mouse move with a button held; the
+    /// handler's `drag_ended` is called once the drag is over.
+    pub fn start_drag(&self, data: DragData) -> Result<(), Error> {
+        let nsview = self.nsview.as_ref().ok_or(Error::Null)?;
+        unsafe { drag_source::start_drag(*nsview.load(), data) }
+    }
+
+    /// End the composition in progress, if any, discarding it without a
+    /// `CompositionEvent::Commit`, for when the application commits the
+    /// text itself, as when the focus moves away from it.
+    pub fn cancel_composition(&self) {
+        if let Some(ref nsview) = self.nsview {
+            unsafe {
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                // Without marked text, discarding it sends no events.
+                if (*view_state).marked_text.take().is_some() {
+                    let input_context: id = msg_send![view, inputContext];
+                    let () = msg_send![input_context, discardMarkedText];
+                }
+            }
+        }
+    }
+
+    pub fn set_caret_rect(&self, rect: Rect) {
+        if let Some(ref nsview) = self.nsview {
+            unsafe {
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                (*view_state).caret_rect = Some(rect);
+                let input_context: id = msg_send![view, inputContext];
+                let () = msg_send![input_context, invalidateCharacterCoordinates];
+            }
+        }
+    }
+
+    /// Macs have no on-screen keyboard for applications to show, so this has
+    /// no effect.
+    pub fn show_soft_keyboard(&self) {}
+
+    pub fn hide_soft_keyboard(&self) {}
+
+    pub fn accessibility_enabled(&self) -> bool {
+        true
+    }
+
+    /// Expose the tree to NSAccessibility, with an element for each node
+    /// as a child of the view.
+    pub fn update_accessibility(&self, tree: AccessTree) {
+        if let Some(ref nsview) = self.nsview {
+            unsafe { accessibility::update(*nsview.load(), tree) }
+        }
+    }
+
+    /// Post an announcement request, which VoiceOver reads out.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        unsafe { accessibility::announce(text, politeness) }
+    }
+
+    pub fn remove_tray_icon(&self) {
+        if let Some(ref nsview) = self.nsview {
+            unsafe {
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                if let Some(item) = (*view_state).tray_item.take() {
+                    remove_status_item(*item);
+                }
+            }
+        }
+    }
+
+    /// Set the icon of the application in the Dock and the app switcher.
+    /// Windows on macOS have no icons of their own, so this applies to the
+    /// whole application. The images are resolutions of the same icon.
+    pub fn set_icon(&self, icons: &[IconDesc]) -> Result<(), Error> {
+        unsafe {
+            let image = make_icon_nsimage(icons).ok_or(Error::Null)?;
+            let () = msg_send![NSApp(), setApplicationIconImage: *image];
+        }
+        Ok(())
+    }
+
+    /// The frame of the window, and whether it is zoomed, for saving the
+    /// window layout.
+    pub fn get_geometry(&self) -> WindowGeometry {
+        match self.nswindow() {
+            Some(window) => unsafe {
+                let zoomed: BOOL = msg_send![window, isZoomed];
+                WindowGeometry {
+                    frame: from_ns_rect(NSWindow::frame(window)),
+                    maximized: zoomed == YES,
+                }
+            },
+            None => WindowGeometry::default(),
+        }
+    }
+
+    /// Restore a frame and zoomed state saved with `get_geometry`.
+    ///
+    /// The frame is moved and shrunk as needed to fit on a screen, in case
+    /// the screen layout has changed since.
+    pub fn set_geometry(&self, geometry: &WindowGeometry) {
+        let window = match self.nswindow() {
+            Some(window) => window,
+            None => return,
+        };
+        unsafe {
+            let zoomed: BOOL = msg_send![window, isZoomed];
+            if zoomed == YES {
+                let () = msg_send![window, zoom: nil];
+            }
+            // Use the screen holding the center of the frame, if any.
+            let center = geometry.frame.center();
+            let screens: id = msg_send![class!(NSScreen), screens];
+            let count: NSUInteger = msg_send![screens, count];
+            let mut area = None;
+            for i in 0..count {
+                let screen: id = msg_send![screens, objectAtIndex: i];
+                let visible = from_ns_rect(msg_send![screen, visibleFrame]);
+                if area.is_none() || visible.contains(center) {
+                    area = Some(visible);
+                }
+            }
+            let frame = match area {
+                Some(area) => window::clamp_frame(geometry.frame, area),
+                None => geometry.frame,
+            };
+            let () = msg_send![window, setFrame: to_ns_rect(frame) display: YES];
+            if geometry.maximized {
+                let () = msg_send![window, zoom: nil];
+            }
+        }
+    }
+
+    /// Hide the window, for example to leave only its tray icon. `show`
+    /// brings it back.
+    pub fn hide(&self) {
+        unsafe {
+            if let Some(view) = self.embedded_view() {
+                let () = msg_send![view, setHidden: YES];
+            } else if let Some(window) = self.nswindow() {
+                window.orderOut_(nil);
+            }
+        }
+    }
+
+    /// Change the stacking level of the window.
+    pub fn set_level(&self, level: WindowLevel) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                let () = msg_send![window, setLevel: ns_window_level(level)];
+            }
+        }
+    }
+
+    /// The position of the top left corner of the window frame, in px, in
+    /// screen coordinates with the origin at the top left of the primary
+    /// screen.
+    pub fn get_position(&self) -> (f64, f64) {
+        match self.nswindow() {
+            Some(window) => unsafe {
+                let frame = NSWindow::frame(window);
+                let top = frame.origin.y + frame.size.height;
+                (frame.origin.x, primary_screen_height() - top)
+            },
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Move the top left corner of the window frame to the given position, in
+    /// the coordinates of `get_position`.
+    pub fn set_position(&self, x: f64, y: f64) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                window.setFrameTopLeftPoint_(NSPoint::new(x, primary_screen_height() - y));
+            }
+        }
+    }
+
+    /// Center the window on its screen, leaving out the menu bar and dock.
+    pub fn center_on_screen(&self) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                let mut screen: id = msg_send![window, screen];
+                if screen == nil {
+                    screen = msg_send![class!(NSScreen), mainScreen];
+                }
+                let area: NSRect = msg_send![screen, visibleFrame];
+                center_window(window, area);
+            }
+        }
+    }
+
+    /// Center the window over another one, as for a dialog.
+    pub fn center_on(&self, other: &WindowHandle) {
+        if let (Some(window), Some(other)) = (self.nswindow(), other.nswindow()) {
+            unsafe {
+                center_window(window, NSWindow::frame(other));
+            }
+        }
+    }
+
+    /// Zoom the window, the macOS counterpart of maximizing.
+    pub fn maximize(&self) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                if get_window_state(window) == WindowState::Restored {
+                    let () = msg_send![window, zoom: nil];
+                }
+            }
+        }
+    }
+
+    pub fn minimize(&self) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                let () = msg_send![window, miniaturize: nil];
+            }
+        }
+    }
+
+    /// Return the window to its normal size and position, leaving fullscreen
+    /// if needed.
+    pub fn restore(&self) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                match get_window_state(window) {
+                    WindowState::Fullscreen => {
+                        let () = msg_send![window, toggleFullScreen: nil];
+                    }
+                    WindowState::Minimized => {
+                        let () = msg_send![window, deminiaturize: nil];
+                    }
+                    WindowState::Maximized => {
+                        let () = msg_send![window, zoom: nil];
+                    }
+                    WindowState::Restored => (),
+                }
+            }
+        }
+    }
+
+    /// Enter or leave fullscreen. The window gets a space of its own, as
+    /// with the green title bar button.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(window) = self.nswindow() {
+            unsafe {
+                let is_fullscreen = get_window_state(window) == WindowState::Fullscreen;
+                if fullscreen != is_fullscreen {
+                    let () = msg_send![window, toggleFullScreen: nil];
+                }
+            }
+        }
+    }
+
+    /// Whether the window is maximized, minimized or fullscreen.
+    pub fn get_window_state(&self) -> WindowState {
+        match self.nswindow() {
+            Some(window) => unsafe { get_window_state(window) },
+            None => WindowState::Restored,
+        }
+    }
+
+    /// The native handles of the window, for external renderers. The view
+    /// is the one druid draws into.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        let nsview = self.nsview.as_ref()?;
+        unsafe {
+            let view = *nsview.load();
+            let window: id = msg_send![view, window];
+            Some(RawWindowHandle::AppKit {
+                ns_window: window as *mut c_void,
+                ns_view: view as *mut c_void,
+            })
+        }
+    }
+
+    pub fn raw_display_handle(&self) -> RawDisplayHandle {
+        RawDisplayHandle::AppKit
+    }
+
+    /// The window of the view, unless the view is embedded in a view of the
+    /// host application, whose window is not ours to change.
+    fn nswindow(&self) -> Option<id> {
+        if self.embedded_view().is_some() {
+            return None;
+        }
+        self.nsview
+            .as_ref()
+            .map(|nsview| unsafe { msg_send![*nsview.load(), window] })
+    }
+
+    /// The view, if it is embedded in a view of the host application.
+    fn embedded_view(&self) -> Option<id> {
+        let nsview = self.nsview.as_ref()?;
+        unsafe {
+            let view = *nsview.load();
+            if view == nil {
+                return None;
+            }
+            let view_state: *mut c_void = *(*view).get_ivar("viewState");
+            if (*(view_state as *const ViewState)).embedded {
+                Some(view)
+            } else {
+                None
+            }
+        }
+    }
+
+    /// Set the cursor shown while the pointer is over the window.
+    pub fn set_cursor(&self, cursor: &Cursor) {
+        unsafe {
+            let nscursor: id = match cursor {
+                Cursor::Arrow => msg_send![class!(NSCursor), arrowCursor],
+                Cursor::IBeam => msg_send![class!(NSCursor), IBeamCursor],
+                Cursor::Crosshair => msg_send![class!(NSCursor), crosshairCursor],
+                Cursor::OpenHand => msg_send![class!(NSCursor), openHandCursor],
+                Cursor::ClosedHand => msg_send![class!(NSCursor), closedHandCursor],
+                Cursor::NotAllowed => msg_send![class!(NSCursor), operationNotAllowedCursor],
+                Cursor::ResizeLeftRight => msg_send![class!(NSCursor), resizeLeftRightCursor],
+                Cursor::ResizeUpDown => msg_send![class!(NSCursor), resizeUpDownCursor],
+                Cursor::Custom(custom) => *custom.0,
+            };
+            let () = msg_send![nscursor, set];
+        }
+    }
+
+    /// Create a cursor from an image.
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        unsafe {
+            let image = make_nsimage(&desc.image, desc.width, desc.height)?;
+            let hot = NSPoint::new(desc.hot.0 as f64, desc.hot.1 as f64);
+            let cursor: id = msg_send![class!(NSCursor), alloc];
+            let cursor: id = msg_send![cursor, initWithImage: *image hotSpot: hot];
+            if cursor == nil {
+                return None;
+            }
+            Some(Cursor::Custom(CustomCursor(StrongPtr::new(cursor))))
+        }
+    }
+
+    /// Show a context menu at a point in the window, in px. The chosen item
+    /// is sent to the handler as a command.
+    ///
+    /// This runs a modal loop until the menu is dismissed.
+    pub fn show_context_menu(&self, menu: Menu, x: f64, y: f64) {
         if let Some(ref nsview) = self.nsview {
             unsafe {
-                let window: id = msg_send![*nsview.load(), window];
+                let view = *nsview.load();
+                // The view is flipped, so the location is in our coordinates.
+                let location = NSPoint::new(x, y);
+                let _: BOOL = msg_send![menu.menu,
+                    popUpMenuPositioningItem: nil
+                    atLocation: location
+                    inView: view];
+            }
+        }
+    }
+
+    /// Close the window.
+    pub fn close(&self) {
+        unsafe {
+            if let Some(view) = self.embedded_view() {
+                // The parent holds the only reference, so this releases the
+                // view and destroys the handler.
+                let () = msg_send![view, removeFromSuperview];
+            } else if let Some(window) = self.nswindow() {
                 window.close();
             }
         }
     }
 
     // Request invalidation of the entire window contents.
+    /// Request a paint. With vsync, the view is painted at the next
+    /// display refresh, at most once however often this is called.
     pub fn invalidate(&self) {
         if let Some(ref nsview) = self.nsview {
             unsafe {
-                // We could share impl with redraw, but we'd need to deal with nil.
-                let () = msg_send![*nsview.load(), setNeedsDisplay: YES];
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                if view_state.vsync {
+                    view_state.paint_requested = true;
+                    view_state.request_tick(view);
+                } else {
+                    // We could share impl with redraw, but we'd need to deal with nil.
+                    let () = msg_send![view, setNeedsDisplay: YES];
+                }
+            }
+        }
+    }
+
+    /// Request a call to `WinHandler::frame` at the next display refresh,
+    /// followed by a paint.
+    pub fn request_frame(&self) {
+        if let Some(ref nsview) = self.nsview {
+            unsafe {
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                let view_state = &mut *(view_state as *mut ViewState);
+                view_state.frame_requested = true;
+                view_state.paint_requested = true;
+                view_state.request_tick(view);
+            }
+        }
+    }
+
+    /// Request a call to `WinHandler::timer` with the returned token after
+    /// `delay`, accurate to about a millisecond.
+    pub fn request_timer(&self, delay: Duration) -> TimerToken {
+        let nsview = match self.nsview {
+            Some(ref nsview) => nsview,
+            None => return TimerToken::next(),
+        };
+        unsafe {
+            let view = *nsview.load();
+            let view_state: *mut c_void = *(*view).get_ivar("viewState");
+            let view_state = &mut *(view_state as *mut ViewState);
+            let (token, earliest) = view_state.timers.add(Instant::now() + delay);
+            if earliest {
+                view_state.schedule_timers(view);
+            }
+            token
+        }
+    }
+
+    /// Set whether painting is paced by the display refresh. The window
+    /// server always presents in sync with the display on macOS; this only
+    /// controls whether paints wait for the next refresh.
+    pub fn set_vsync(&self, vsync: bool) {
+        if let Some(ref nsview) = self.nsview {
+            unsafe {
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                (*(view_state as *mut ViewState)).vsync = vsync;
+            }
+        }
+    }
+
+    /// The backdrop shown behind the window.
+    pub fn backdrop(&self) -> WindowBackdrop {
+        match self.nsview {
+            Some(ref nsview) => unsafe {
+                let view = *nsview.load();
+                let view_state: *mut c_void = *(*view).get_ivar("viewState");
+                (*(view_state as *const ViewState)).backdrop
+            },
+            None => WindowBackdrop::None,
+        }
+    }
+
+    /// Create a surface in the window for rendering with a GPU API directly.
+    pub fn create_gpu_surface(&self) -> Result<surface::GpuSurface, Error> {
+        let nsview = self.nsview.as_ref().ok_or(Error::Null)?;
+        unsafe {
+            let view = *nsview.load();
+            if view == nil {
+                return Err(Error::Null);
+            }
+            Ok(surface::GpuSurface::new(view))
+        }
+    }
+
+    /// Host a native view of the application, such as a `WKWebView`, in the
+    /// window.
+    ///
+    /// # Safety
+    ///
+    /// `view` must be the `AppKit` handle of a view, which is retained while
+    /// hosted.
+    pub unsafe fn add_native_view(
+        &self,
+        view: RawWindowHandle,
+    ) -> Result<native_view::NativeView, Error> {
+        let nsview = self.nsview.as_ref().ok_or(Error::Null)?;
+        let parent = *nsview.load();
+        if parent == nil {
+            return Err(Error::Null);
+        }
+        match view {
+            RawWindowHandle::AppKit { ns_view, .. } if !ns_view.is_null() => {
+                Ok(native_view::NativeView::new(parent, ns_view as id))
             }
+            _ => Err(Error::Other("the view must be an AppKit view")),
         }
     }
 
+    /// Capture the mouse, so that mouse events keep being delivered to this
+    /// window while the pointer is outside it, until `release_mouse`.
+    ///
+    /// Cocoa already sends drags to the view that received the mouse down,
+    /// wherever the pointer goes, so there is nothing to do here.
+    pub fn capture_mouse(&self) {}
+
+    /// Release a mouse capture started with `capture_mouse`.
+    pub fn release_mouse(&self) {}
+
+    /// Register a system-wide hotkey.
+    ///
+    /// While registered, pressing the key chord anywhere in the system
+    /// delivers `id` to the handler's `command` method, even when the
+    /// application is not active.
+    pub fn register_hotkey(&self, id: u32, key: MenuKey) -> Result<(), Error> {
+        let nsview = self.nsview.as_ref().ok_or(Error::Null)?;
+        hotkey::register(nsview, id, key)
+    }
+
+    /// Unregister a hotkey previously registered with `register_hotkey`.
+    pub fn unregister_hotkey(&self, id: u32) {
+        hotkey::unregister(id);
+    }
+
     /// Get a handle that can be used to schedule an idle task.
     pub fn get_idle_handle(&self) -> Option<IdleHandle> {
         // TODO: maybe try harder to return None if window has been dropped.
@@ -535,10 +2078,91 @@ impl WindowHandle {
 
     pub fn file_dialog(
         &self,
-        _ty: FileDialogType,
-        _options: FileDialogOptions,
+        ty: FileDialogType,
+        options: FileDialogOptions,
     ) -> Result<OsString, Error> {
-        unimplemented!()
+        let mut paths = unsafe { get_file_dialog_paths(ty, &options) }
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(paths.swap_remove(0).into_os_string())
+    }
+
+    /// Show a modal file open dialog, returning the chosen files.
+    pub fn open_file_dialog(&self, options: &FileDialogOptions) -> Result<Vec<FileInfo>, Error> {
+        let paths = unsafe { get_file_dialog_paths(FileDialogType::Open, options) }
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        Ok(paths.into_iter().map(|path| FileInfo { path }).collect())
+    }
+
+    /// Show a modal file save dialog, returning the chosen file.
+    ///
+    /// The dialog asks for confirmation before an existing file is chosen.
+    pub fn save_file_dialog(&self, options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        let mut paths = unsafe { get_file_dialog_paths(FileDialogType::Save, options) }
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(FileInfo {
+            path: paths.swap_remove(0),
+        })
+    }
+
+    /// Show a modal directory chooser, returning the chosen directory.
+    pub fn open_directory_dialog(&self, options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        let mut paths = unsafe { get_file_dialog_paths(FileDialogType::Directory, options) }
+            .ok_or(Error::Other("file dialog cancelled"))?;
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(FileInfo {
+            path: paths.swap_remove(0),
+        })
+    }
+
+    /// Show a modal alert, returning the chosen button.
+    pub fn alert(&self, options: &AlertOptions) -> AlertResponse {
+        unsafe {
+            let alert: id = msg_send![class!(NSAlert), new];
+            let alert = StrongPtr::new(alert);
+            let style: NSUInteger = match options.style {
+                AlertStyle::Warning => NS_ALERT_STYLE_WARNING,
+                AlertStyle::Info => NS_ALERT_STYLE_INFORMATIONAL,
+                AlertStyle::Error => NS_ALERT_STYLE_CRITICAL,
+            };
+            let () = msg_send![*alert, setAlertStyle: style];
+            let () = msg_send![*alert, setMessageText: make_nsstring(&options.message)];
+            if let Some(ref detail) = options.detail {
+                let () = msg_send![*alert, setInformativeText: make_nsstring(detail)];
+            }
+            let responses = options.buttons.responses();
+            for response in responses {
+                let title = match response {
+                    AlertResponse::Ok => "OK",
+                    AlertResponse::Cancel => "Cancel",
+                    AlertResponse::Yes => "Yes",
+                    AlertResponse::No => "No",
+                };
+                let _: id = msg_send![*alert, addButtonWithTitle: make_nsstring(title)];
+            }
+            // The first button answers to Return unless told otherwise.
+            let default = options.default_index();
+            if default != 0 {
+                let buttons: id = msg_send![*alert, buttons];
+                let first: id = msg_send![buttons, objectAtIndex: 0 as NSUInteger];
+                let chosen: id = msg_send![buttons, objectAtIndex: default as NSUInteger];
+                let () = msg_send![first, setKeyEquivalent: make_nsstring("")];
+                let () = msg_send![chosen, setKeyEquivalent: make_nsstring("\r")];
+            }
+            let result: NSInteger = msg_send![*alert, runModal];
+            let index = (result - NS_ALERT_FIRST_BUTTON_RETURN) as usize;
+            responses
+                .get(index)
+                .cloned()
+                .unwrap_or(AlertResponse::Cancel)
+        }
     }
 }
 
@@ -587,7 +2211,16 @@ fn make_key_event(event: id) -> KeyEvent {
         let is_repeat: bool = msg_send!(event, isARepeat);
         let modifiers = event.modifierFlags();
         let modifiers = make_modifiers(modifiers);
-        KeyEvent::new(virtual_key, is_repeat, modifiers, text, unmodified_text)
+        let mut event = KeyEvent::new(virtual_key, is_repeat, modifiers, text, unmodified_text);
+        if text.is_empty() && event.code.is_printable() {
+            event.key = Key::Dead;
+        }
+        match virtual_key {
+            0x37 | 0x38 | 0x3a | 0x3b => event.location = KeyLocation::Left,
+            0x36 | 0x3c | 0x3d | 0x3e => event.location = KeyLocation::Right,
+            _ => (),
+        }
+        event
     }
 }
 
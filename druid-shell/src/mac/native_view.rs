@@ -0,0 +1,133 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native views hosted in a window, macOS implementation.
+//!
+//! The view is put in a container subview of the druid view. The container
+//! covers the visible part of the view and clips it.
+
+use cocoa::base::{id, BOOL, NO, YES};
+use cocoa::foundation::{NSPoint, NSRect, NSSize};
+use objc::declare::ClassDecl;
+use objc::rc::StrongPtr;
+use objc::runtime::{Class, Object, Sel};
+
+use piet_common::kurbo::Rect;
+
+struct ContainerClass(*const Class);
+unsafe impl Sync for ContainerClass {}
+
+lazy_static! {
+    static ref CONTAINER_CLASS: ContainerClass = unsafe {
+        let mut decl = ClassDecl::new("DruidNativeHostView", class!(NSView))
+            .expect("Native host view class defined");
+        decl.add_method(
+            sel!(isFlipped),
+            is_flipped as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        ContainerClass(decl.register())
+    };
+}
+
+extern "C" fn is_flipped(_this: &Object, _sel: Sel) -> BOOL {
+    YES
+}
+
+fn ns_rect(rect: Rect) -> NSRect {
+    NSRect::new(
+        NSPoint::new(rect.x0, rect.y0),
+        NSSize::new(rect.width(), rect.height()),
+    )
+}
+
+/// A native view of the application, such as a `WKWebView`, shown in a
+/// window.
+///
+/// Dropping removes the view from the window, handing it back to its owner.
+pub struct NativeView {
+    container: StrongPtr,
+    view: StrongPtr,
+}
+
+impl NativeView {
+    /// Put `view` in a hidden container over the druid view `parent`.
+    pub(crate) unsafe fn new(parent: id, view: id) -> NativeView {
+        let container: id = msg_send![CONTAINER_CLASS.0, alloc];
+        let container: id = msg_send![container, initWithFrame: ns_rect(Rect::default())];
+        let () = msg_send![container, setWantsLayer: YES];
+        let layer: id = msg_send![container, layer];
+        let () = msg_send![layer, setMasksToBounds: YES];
+        let () = msg_send![container, setHidden: YES];
+        let view = StrongPtr::retain(view);
+        let () = msg_send![container, addSubview: *view];
+        let () = msg_send![parent, addSubview: container];
+        NativeView {
+            container: StrongPtr::new(container),
+            view,
+        }
+    }
+
+    /// Place the view at `frame` in the window's content area, showing only
+    /// the part inside `clip`, both in px.
+    pub fn set_frame(&self, frame: Rect, clip: Rect) {
+        let clip = clip.intersect(frame);
+        let inner = frame - clip.origin().to_vec2();
+        unsafe {
+            let () = msg_send![*self.container, setFrame: ns_rect(clip)];
+            let () = msg_send![*self.view, setFrame: ns_rect(inner)];
+        }
+    }
+
+    /// Show or hide the view. Views start hidden, so that nothing appears
+    /// before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        let hidden = if visible { NO } else { YES };
+        unsafe {
+            let () = msg_send![*self.container, setHidden: hidden];
+        }
+    }
+
+    /// Give the keyboard focus to the view.
+    pub fn focus(&self) {
+        unsafe {
+            let window: id = msg_send![*self.view, window];
+            let _: BOOL = msg_send![window, makeFirstResponder: *self.view];
+        }
+    }
+
+    /// Give the keyboard focus back to the druid view, if the view has it.
+    pub fn blur(&self) {
+        unsafe {
+            let window: id = msg_send![*self.view, window];
+            let responder: id = msg_send![window, firstResponder];
+            let has_focus: BOOL = msg_send![responder, isKindOfClass: class!(NSView)];
+            if has_focus == YES {
+                let inside: BOOL = msg_send![responder, isDescendantOf: *self.view];
+                if inside == YES {
+                    let parent: id = msg_send![*self.container, superview];
+                    let _: BOOL = msg_send![window, makeFirstResponder: parent];
+                }
+            }
+        }
+    }
+}
+
+impl Drop for NativeView {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![*self.view, removeFromSuperview];
+            let () = msg_send![*self.container, removeFromSuperview];
+        }
+    }
+}
@@ -0,0 +1,123 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GPU surfaces, macOS implementation.
+//!
+//! A surface is a layer-backed subview of the druid view, so that Core
+//! Animation composites its layer with the window. A Metal or OpenGL
+//! renderer attaches to the view or its layer. The view ignores the mouse,
+//! leaving events to the druid view.
+
+use std::ffi::c_void;
+
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{NSPoint, NSRect, NSSize};
+use objc::declare::ClassDecl;
+use objc::rc::StrongPtr;
+use objc::runtime::{Class, Object, Sel};
+
+use piet_common::kurbo::Rect;
+
+use crate::window::RawWindowHandle;
+
+struct SurfaceClass(*const Class);
+unsafe impl Sync for SurfaceClass {}
+
+lazy_static! {
+    static ref SURFACE_CLASS: SurfaceClass = unsafe {
+        let mut decl = ClassDecl::new("DruidGpuSurfaceView", class!(NSView))
+            .expect("Surface view class defined");
+        // Flipped like the druid view, so frames carry over unchanged.
+        decl.add_method(
+            sel!(isFlipped),
+            is_flipped as extern "C" fn(&Object, Sel) -> BOOL,
+        );
+        decl.add_method(
+            sel!(hitTest:),
+            hit_test as extern "C" fn(&Object, Sel, NSPoint) -> id,
+        );
+        SurfaceClass(decl.register())
+    };
+}
+
+extern "C" fn is_flipped(_this: &Object, _sel: Sel) -> BOOL {
+    YES
+}
+
+extern "C" fn hit_test(_this: &Object, _sel: Sel, _point: NSPoint) -> id {
+    nil
+}
+
+/// A region of a window that is rendered with a GPU API of the caller's
+/// choice, such as Metal, OpenGL or wgpu, through `raw_window_handle`.
+///
+/// The surface is removed from the window when dropped.
+pub struct GpuSurface {
+    view: StrongPtr,
+}
+
+impl GpuSurface {
+    /// Create a hidden surface over the druid view `parent`.
+    pub(crate) unsafe fn new(parent: id) -> GpuSurface {
+        let view: id = msg_send![SURFACE_CLASS.0, alloc];
+        let frame = NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(0.0, 0.0));
+        let view: id = msg_send![view, initWithFrame: frame];
+        let () = msg_send![view, setWantsLayer: YES];
+        let () = msg_send![view, setHidden: YES];
+        let () = msg_send![parent, addSubview: view];
+        GpuSurface {
+            view: StrongPtr::new(view),
+        }
+    }
+
+    /// Place the surface at `rect` in the window's content area, in px.
+    pub fn set_frame(&self, rect: Rect) {
+        let frame = NSRect::new(
+            NSPoint::new(rect.x0, rect.y0),
+            NSSize::new(rect.width(), rect.height()),
+        );
+        unsafe {
+            let () = msg_send![*self.view, setFrame: frame];
+        }
+    }
+
+    /// Show or hide the surface. Surfaces start hidden, so that nothing
+    /// appears before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        let hidden = if visible { NO } else { YES };
+        unsafe {
+            let () = msg_send![*self.view, setHidden: hidden];
+        }
+    }
+
+    /// The native handles of the surface, for creating a Metal layer or GL
+    /// context on it.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        unsafe {
+            let window: id = msg_send![*self.view, window];
+            Some(RawWindowHandle::AppKit {
+                ns_window: window as *mut c_void,
+                ns_view: *self.view as *mut c_void,
+            })
+        }
+    }
+}
+
+impl Drop for GpuSurface {
+    fn drop(&mut self) {
+        unsafe {
+            let () = msg_send![*self.view, removeFromSuperview];
+        }
+    }
+}
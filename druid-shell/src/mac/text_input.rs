@@ -0,0 +1,281 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input method support, macOS implementation.
+//!
+//! The view implements `NSTextInputClient`, and passes key events through
+//! `interpretKeyEvents:`. Keys that the input method does not take are
+//! delivered to `key_down` from the callbacks, in `insertText:` or
+//! `doCommandBySelector:`.
+
+use std::ffi::c_void;
+
+use cocoa::base::{id, nil, BOOL, NO, YES};
+use cocoa::foundation::{NSArray, NSPoint, NSRect, NSSize, NSUInteger};
+use objc::declare::ClassDecl;
+use objc::runtime::{Object, Protocol, Sel};
+use objc::{Encode, Encoding};
+
+use super::ViewState;
+use crate::util::from_nsstring;
+use crate::window::CompositionEvent;
+
+const NS_NOT_FOUND: NSUInteger = std::isize::MAX as NSUInteger;
+
+/// A range of UTF-16 code units, as `NSRange`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct NSRange {
+    location: NSUInteger,
+    length: NSUInteger,
+}
+
+unsafe impl Encode for NSRange {
+    fn encode() -> Encoding {
+        let encoding = format!(
+            "{{_NSRange={}{}}}",
+            NSUInteger::encode().as_str(),
+            NSUInteger::encode().as_str()
+        );
+        unsafe { Encoding::from_str(&encoding) }
+    }
+}
+
+pub(super) unsafe fn add_methods(decl: &mut ClassDecl) {
+    if let Some(protocol) = Protocol::get("NSTextInputClient") {
+        decl.add_protocol(protocol);
+    }
+    decl.add_method(
+        sel!(hasMarkedText),
+        has_marked_text as extern "C" fn(&mut Object, Sel) -> BOOL,
+    );
+    decl.add_method(
+        sel!(markedRange),
+        marked_range as extern "C" fn(&mut Object, Sel) -> NSRange,
+    );
+    decl.add_method(
+        sel!(selectedRange),
+        selected_range as extern "C" fn(&mut Object, Sel) -> NSRange,
+    );
+    decl.add_method(
+        sel!(setMarkedText:selectedRange:replacementRange:),
+        set_marked_text as extern "C" fn(&mut Object, Sel, id, NSRange, NSRange),
+    );
+    decl.add_method(
+        sel!(unmarkText),
+        unmark_text as extern "C" fn(&mut Object, Sel),
+    );
+    decl.add_method(
+        sel!(validAttributesForMarkedText),
+        valid_attributes_for_marked_text as extern "C" fn(&mut Object, Sel) -> id,
+    );
+    decl.add_method(
+        sel!(attributedSubstringForProposedRange:actualRange:),
+        attributed_substring_for_proposed_range
+            as extern "C" fn(&mut Object, Sel, NSRange, *mut c_void) -> id,
+    );
+    decl.add_method(
+        sel!(insertText:replacementRange:),
+        insert_text as extern "C" fn(&mut Object, Sel, id, NSRange),
+    );
+    decl.add_method(
+        sel!(characterIndexForPoint:),
+        character_index_for_point as extern "C" fn(&mut Object, Sel, NSPoint) -> NSUInteger,
+    );
+    decl.add_method(
+        sel!(firstRectForCharacterRange:actualRange:),
+        first_rect_for_character_range
+            as extern "C" fn(&mut Object, Sel, NSRange, *mut c_void) -> NSRect,
+    );
+    decl.add_method(
+        sel!(doCommandBySelector:),
+        do_command_by_selector as extern "C" fn(&mut Object, Sel, Sel),
+    );
+}
+
+fn view_state(this: &mut Object) -> &mut ViewState {
+    unsafe {
+        let view_state: *mut c_void = *this.get_ivar("viewState");
+        &mut *(view_state as *mut ViewState)
+    }
+}
+
+/// Pass a key down event to the input method, or to the handler if the
+/// input method does not take it.
+pub(super) fn key_down(this: &mut Object, nsevent: id) {
+    view_state(this).pending_key = Some(super::make_key_event(nsevent));
+    unsafe {
+        let events = NSArray::arrayWithObject(nil, nsevent);
+        let () = msg_send![this, interpretKeyEvents: events];
+    }
+    let view_state = view_state(this);
+    if let Some(event) = view_state.pending_key.take() {
+        if view_state.marked_text.is_none() {
+            view_state.handler.key_down(event);
+        }
+    }
+}
+
+/// The contents of an `NSString` or `NSAttributedString`.
+unsafe fn get_string(text: id) -> String {
+    let is_attributed: BOOL = msg_send![text, isKindOfClass: class!(NSAttributedString)];
+    if is_attributed == YES {
+        from_nsstring(msg_send![text, string])
+    } else {
+        from_nsstring(text)
+    }
+}
+
+/// Convert an offset in UTF-16 code units to one in bytes.
+fn utf16_to_byte_offset(text: &str, offset: usize) -> usize {
+    let mut utf16 = 0;
+    for (i, c) in text.char_indices() {
+        if utf16 >= offset {
+            return i;
+        }
+        utf16 += c.len_utf16();
+    }
+    text.len()
+}
+
+extern "C" fn has_marked_text(this: &mut Object, _: Sel) -> BOOL {
+    if view_state(this).marked_text.is_some() {
+        YES
+    } else {
+        NO
+    }
+}
+
+extern "C" fn marked_range(this: &mut Object, _: Sel) -> NSRange {
+    match view_state(this).marked_text {
+        Some(ref text) => NSRange {
+            location: 0,
+            length: text.encode_utf16().count() as NSUInteger,
+        },
+        None => NSRange {
+            location: NS_NOT_FOUND,
+            length: 0,
+        },
+    }
+}
+
+extern "C" fn selected_range(_this: &mut Object, _: Sel) -> NSRange {
+    // The text around the caret is not known here.
+    NSRange {
+        location: NS_NOT_FOUND,
+        length: 0,
+    }
+}
+
+extern "C" fn set_marked_text(
+    this: &mut Object,
+    _: Sel,
+    text: id,
+    selected: NSRange,
+    _replacement: NSRange,
+) {
+    let text = unsafe { get_string(text) };
+    let view_state = view_state(this);
+    // The key was taken by the input method.
+    view_state.pending_key = None;
+    if text.is_empty() {
+        if view_state.marked_text.take().is_some() {
+            let event = CompositionEvent::Commit(String::new());
+            view_state.handler.composition(&event);
+        }
+        return;
+    }
+    if view_state.marked_text.is_none() {
+        view_state.handler.composition(&CompositionEvent::Start);
+    }
+    let cursor = utf16_to_byte_offset(&text, (selected.location + selected.length) as usize);
+    view_state.marked_text = Some(text.clone());
+    let event = CompositionEvent::Update { text, cursor };
+    view_state.handler.composition(&event);
+}
+
+extern "C" fn unmark_text(this: &mut Object, _: Sel) {
+    let view_state = view_state(this);
+    if let Some(text) = view_state.marked_text.take() {
+        view_state
+            .handler
+            .composition(&CompositionEvent::Commit(text));
+    }
+}
+
+extern "C" fn valid_attributes_for_marked_text(_this: &mut Object, _: Sel) -> id {
+    unsafe { msg_send![class!(NSArray), array] }
+}
+
+extern "C" fn attributed_substring_for_proposed_range(
+    _this: &mut Object,
+    _: Sel,
+    _range: NSRange,
+    _actual: *mut c_void,
+) -> id {
+    nil
+}
+
+extern "C" fn insert_text(this: &mut Object, _: Sel, text: id, _replacement: NSRange) {
+    let text = unsafe { get_string(text) };
+    let view_state = view_state(this);
+    if view_state.marked_text.take().is_some() {
+        view_state.pending_key = None;
+        view_state
+            .handler
+            .composition(&CompositionEvent::Commit(text));
+    } else if let Some(event) = view_state.pending_key.take() {
+        // An ordinary key press; its event carries the same text.
+        view_state.handler.key_down(event);
+    } else {
+        // For example from the character viewer.
+        view_state.handler.composition(&CompositionEvent::Start);
+        view_state
+            .handler
+            .composition(&CompositionEvent::Commit(text));
+    }
+}
+
+extern "C" fn character_index_for_point(_this: &mut Object, _: Sel, _point: NSPoint) -> NSUInteger {
+    NS_NOT_FOUND
+}
+
+extern "C" fn first_rect_for_character_range(
+    this: &mut Object,
+    _: Sel,
+    _range: NSRange,
+    _actual: *mut c_void,
+) -> NSRect {
+    let rect = match view_state(this).caret_rect {
+        Some(rect) => NSRect::new(
+            NSPoint::new(rect.x0, rect.y0),
+            NSSize::new(rect.width(), rect.height()),
+        ),
+        None => NSRect::new(NSPoint::new(0.0, 0.0), NSSize::new(0.0, 0.0)),
+    };
+    unsafe {
+        let window: id = msg_send![this, window];
+        let rect: NSRect = msg_send![this, convertRect: rect toView: nil];
+        msg_send![window, convertRectToScreen: rect]
+    }
+}
+
+extern "C" fn do_command_by_selector(this: &mut Object, _: Sel, _command: Sel) {
+    // Keys such as the arrows and backspace are handled by the handler
+    // rather than by the standard key bindings.
+    let view_state = view_state(this);
+    if let Some(event) = view_state.pending_key.take() {
+        view_state.handler.key_down(event);
+    }
+}
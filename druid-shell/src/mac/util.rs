@@ -16,6 +16,7 @@
 
 use cocoa::base::{id, nil, BOOL, YES};
 use cocoa::foundation::NSString;
+use std::ffi::CStr;
 
 pub fn init() {}
 
@@ -34,3 +35,12 @@ pub fn assert_main_thread() {
 pub(crate) fn make_nsstring(s: &str) -> id {
     unsafe { NSString::alloc(nil).init_str(s) }
 }
+
+pub(crate) unsafe fn from_nsstring(s: id) -> String {
+    if s == nil {
+        return String::new();
+    }
+    CStr::from_ptr(s.UTF8String())
+        .to_string_lossy()
+        .into_owned()
+}
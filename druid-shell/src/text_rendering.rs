@@ -0,0 +1,112 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! How text is antialiased and hinted in the context being painted.
+//!
+//! Like blend modes, the options are set on the native context under piet.
+//! Only the antialiasing of Direct2D render targets can be chosen so far;
+//! cairo, canvases and bitmaps on Direct2D keep their defaults.
+
+use crate::blend::{with_native_context, NativeContext};
+use crate::piet::Piet;
+
+/// How the edges of glyphs are smoothed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextAntialias {
+    /// The platform's choice, usually that of the user's settings.
+    Default,
+    None,
+    Grayscale,
+    /// Smoothing with the color channels of LCD pixels. It fringes on
+    /// backgrounds that aren't opaque, or that move under the text.
+    Subpixel,
+}
+
+/// How strongly glyph outlines are fitted to the pixel grid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TextHinting {
+    Default,
+    None,
+    Slight,
+    Medium,
+    Full,
+}
+
+/// The options for drawing text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextRendering {
+    pub antialias: TextAntialias,
+    pub hinting: TextHinting,
+}
+
+impl Default for TextRendering {
+    fn default() -> TextRendering {
+        TextRendering {
+            antialias: TextAntialias::Default,
+            hinting: TextHinting::Default,
+        }
+    }
+}
+
+impl TextRendering {
+    /// The same options for text drawn over transparency, such as into a
+    /// bitmap, with grayscale antialiasing unless there is none.
+    pub fn grayscale(self) -> TextRendering {
+        let antialias = match self.antialias {
+            TextAntialias::None => TextAntialias::None,
+            _ => TextAntialias::Grayscale,
+        };
+        TextRendering { antialias, ..self }
+    }
+}
+
+/// Draw the text drawn next into `piet` with `rendering`. Returns `false`
+/// if the platform can't change the options of that context, which then
+/// keeps drawing text as before.
+///
+/// `piet` must be the context painted into last, as for
+/// `blend::set_blend_mode`.
+pub fn set_text_rendering(_piet: &mut Piet, rendering: TextRendering) -> bool {
+    with_native_context(|context| match context {
+        Some(context) => apply(context, rendering),
+        None => rendering == TextRendering::default(),
+    })
+}
+
+// piet draws text on cairo with fonts made with the default options, which
+// replace those of the context.
+#[cfg(any(target_os = "linux", target_os = "macos", target_arch = "wasm32"))]
+fn apply(_context: &NativeContext, rendering: TextRendering) -> bool {
+    rendering == TextRendering::default()
+}
+
+#[cfg(target_os = "windows")]
+fn apply(context: &NativeContext, rendering: TextRendering) -> bool {
+    use direct2d::render_target::RenderTarget;
+    use winapi::um::d2d1::{
+        D2D1_TEXT_ANTIALIAS_MODE_ALIASED, D2D1_TEXT_ANTIALIAS_MODE_CLEARTYPE,
+        D2D1_TEXT_ANTIALIAS_MODE_DEFAULT, D2D1_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+    };
+
+    let mode = match rendering.antialias {
+        TextAntialias::Default => D2D1_TEXT_ANTIALIAS_MODE_DEFAULT,
+        TextAntialias::None => D2D1_TEXT_ANTIALIAS_MODE_ALIASED,
+        TextAntialias::Grayscale => D2D1_TEXT_ANTIALIAS_MODE_GRAYSCALE,
+        TextAntialias::Subpixel => D2D1_TEXT_ANTIALIAS_MODE_CLEARTYPE,
+    };
+    unsafe {
+        context.rt().SetTextAntialiasMode(mode);
+    }
+    rendering.hinting == TextHinting::Default
+}
@@ -0,0 +1,94 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timers, platform independent parts.
+//!
+//! Each window keeps its timers in a queue, and the platform only wakes it
+//! for the earliest one. Timers due within `COALESCE_SLACK` of each other
+//! fire together.
+
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+/// How late a timer may fire, to fire along with an earlier one.
+pub(crate) const COALESCE_SLACK: Duration = Duration::from_millis(1);
+
+/// Identifies a timer requested with `WindowHandle::request_timer`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TimerToken(usize);
+
+impl TimerToken {
+    pub(crate) fn next() -> TimerToken {
+        static NEXT: AtomicUsize = AtomicUsize::new(1);
+        TimerToken(NEXT.fetch_add(1, Ordering::Relaxed))
+    }
+}
+
+/// The pending timers of a window, by deadline.
+pub(crate) struct TimerQueue<T: Ord> {
+    timers: BinaryHeap<Reverse<(T, TimerToken)>>,
+}
+
+impl<T: Ord + Copy> TimerQueue<T> {
+    pub fn new() -> TimerQueue<T> {
+        TimerQueue {
+            timers: BinaryHeap::new(),
+        }
+    }
+
+    /// Add a timer, returning its token and whether it is now the earliest.
+    pub fn add(&mut self, deadline: T) -> (TimerToken, bool) {
+        let token = TimerToken::next();
+        let earliest = self.next_deadline().map_or(true, |next| deadline < next);
+        self.timers.push(Reverse((deadline, token)));
+        (token, earliest)
+    }
+
+    pub fn next_deadline(&self) -> Option<T> {
+        self.timers.peek().map(|Reverse((deadline, _))| *deadline)
+    }
+
+    /// Remove the timers due by `now`, returning them in deadline order.
+    pub fn take_due(&mut self, now: T) -> Vec<TimerToken> {
+        let mut due = Vec::new();
+        while self.next_deadline().map_or(false, |next| next <= now) {
+            let Reverse((_, token)) = self.timers.pop().unwrap();
+            due.push(token);
+        }
+        due
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn due_in_deadline_order() {
+        let mut queue = TimerQueue::new();
+        let (late, earliest) = queue.add(30);
+        assert!(earliest);
+        let (early, earliest) = queue.add(10);
+        assert!(earliest);
+        let (middle, earliest) = queue.add(20);
+        assert!(!earliest);
+        assert_eq!(queue.take_due(5), vec![]);
+        assert_eq!(queue.take_due(21), vec![early, middle]);
+        assert_eq!(queue.next_deadline(), Some(30));
+        assert_eq!(queue.take_due(30), vec![late]);
+        assert_eq!(queue.next_deadline(), None);
+    }
+}
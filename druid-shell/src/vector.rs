@@ -0,0 +1,148 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Painting into vector documents, such as for printing.
+//!
+//! The documents are painted with piet's cairo backend, so they can only
+//! be written on Linux and macOS. On Windows and on the web, where piet
+//! paints with Direct2D and a canvas, neither of which writes SVG or PDF,
+//! `render_to_file` always fails.
+
+use std::path::Path;
+
+use crate::piet::Piet;
+use crate::Error;
+
+/// The size of a px in points, so that documents print at the size
+/// widgets show on a screen with no scaling.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const PT_PER_PX: f64 = 72.0 / 96.0;
+
+/// The kind of a vector document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorFormat {
+    Svg,
+    Pdf,
+}
+
+impl VectorFormat {
+    /// The format named by the extension of `path`, `svg` or `pdf` in any
+    /// case.
+    pub fn from_path(path: &Path) -> Option<VectorFormat> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "svg" => Some(VectorFormat::Svg),
+            "pdf" => Some(VectorFormat::Pdf),
+            _ => None,
+        }
+    }
+}
+
+/// Paint with `f` into a document of one `width` by `height` px page, and
+/// write it to `path` in `format`.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+pub fn render_to_file<F>(
+    path: &Path,
+    format: VectorFormat,
+    width: f64,
+    height: f64,
+    f: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&mut Piet),
+{
+    use cairo::prelude::SurfaceExt;
+    use cairo::{Context, Status};
+
+    use crate::blend::Painting;
+    use crate::piet::RenderContext;
+
+    let (page_width, page_height) = (width * PT_PER_PX, height * PT_PER_PX);
+    let surface = create_surface(path, format, page_width, page_height)?;
+    if surface.status() != Status::Success {
+        return Err(Error::Other("failed to create the document"));
+    }
+    {
+        let mut context = Context::new(&surface);
+        context.scale(PT_PER_PX, PT_PER_PX);
+        let _painting = Painting::new(Some(&context));
+        let mut piet_ctx = Piet::new(&mut context);
+        f(&mut piet_ctx);
+        piet_ctx
+            .finish()
+            .map_err(|_| Error::Other("failed to paint the document"))?;
+    }
+    surface.finish();
+    if surface.status() != Status::Success {
+        return Err(Error::Other("failed to write the document"));
+    }
+    Ok(())
+}
+
+/// Paint with `f` into a document of one `width` by `height` px page, and
+/// write it to `path` in `format`.
+///
+/// This platform can't write vector documents, so this returns an error
+/// without calling `f` or creating `path`.
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn render_to_file<F>(
+    _path: &Path,
+    _format: VectorFormat,
+    _width: f64,
+    _height: f64,
+    _f: F,
+) -> Result<(), Error>
+where
+    F: FnOnce(&mut Piet),
+{
+    Err(Error::Other(
+        "vector documents are only supported on Linux and macOS",
+    ))
+}
+
+/// A cairo surface writing to `path`. cairo-rs has no binding for SVG
+/// surfaces, and its PDF surfaces panic when the file can't be opened.
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+fn create_surface(
+    path: &Path,
+    format: VectorFormat,
+    width: f64,
+    height: f64,
+) -> Result<cairo::Surface, Error> {
+    use std::ffi::CString;
+    use std::os::raw::{c_char, c_double, c_void};
+
+    extern "C" {
+        fn cairo_svg_surface_create(
+            filename: *const c_char,
+            width_in_points: c_double,
+            height_in_points: c_double,
+        ) -> *mut c_void;
+        fn cairo_pdf_surface_create(
+            filename: *const c_char,
+            width_in_points: c_double,
+            height_in_points: c_double,
+        ) -> *mut c_void;
+    }
+
+    let path = CString::new(path.to_string_lossy().into_owned())
+        .map_err(|_| Error::Other("invalid file name"))?;
+    unsafe {
+        let surface = match format {
+            VectorFormat::Svg => cairo_svg_surface_create(path.as_ptr(), width, height),
+            VectorFormat::Pdf => cairo_pdf_surface_create(path.as_ptr(), width, height),
+        };
+        Ok(cairo::Surface::from_raw_full(surface as *mut _))
+    }
+}
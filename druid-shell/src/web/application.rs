@@ -0,0 +1,175 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Web implementation of features at the application scope.
+
+use std::cell::RefCell;
+use std::path::Path;
+use std::rc::Rc;
+
+use js_sys::{Function, Promise, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+use crate::clipboard::Clipboard;
+use crate::piet::Color;
+use crate::util::window;
+use crate::window::{PowerSource, Theme};
+use crate::Error;
+
+pub(crate) const DARK_QUERY: &str = "(prefers-color-scheme: dark)";
+pub(crate) const REDUCE_TRANSPARENCY_QUERY: &str = "(prefers-reduced-transparency: reduce)";
+
+pub struct Application;
+
+/// Keeps the display awake until dropped; see `Application::inhibit_sleep`.
+pub struct SleepInhibitor {
+    lock: Rc<RefCell<WakeLock>>,
+}
+
+/// The state of a wake lock request, which the browser grants
+/// asynchronously.
+#[derive(Default)]
+struct WakeLock {
+    sentinel: Option<JsValue>,
+    released: bool,
+}
+
+impl Application {
+    /// A page can't close itself, so this does nothing.
+    pub fn quit() {}
+
+    /// The system clipboard.
+    pub fn clipboard() -> Clipboard {
+        Clipboard
+    }
+
+    /// The browser's `prefers-color-scheme`.
+    pub fn theme() -> Theme {
+        if media_matches(DARK_QUERY) {
+            Theme::Dark
+        } else {
+            Theme::Light
+        }
+    }
+
+    /// Pages can't read the system's accent color, so this is always
+    /// `None`.
+    pub fn accent_color() -> Option<Color> {
+        None
+    }
+
+    /// The browser's `prefers-reduced-transparency`.
+    pub fn reduce_transparency() -> bool {
+        media_matches(REDUCE_TRANSPARENCY_QUERY)
+    }
+
+    /// The font size of the page's root element relative to the default of
+    /// 16px, which follows the font size chosen in the browser's settings.
+    /// Browsers don't report changes of it.
+    pub fn text_scale() -> f64 {
+        let root = window().document().and_then(|d| d.document_element());
+        let size = root
+            .and_then(|root| window().get_computed_style(&root).ok())
+            .and_then(|style| style)
+            .and_then(|style| style.get_property_value("font-size").ok())
+            .and_then(|size| size.trim_end_matches("px").parse::<f64>().ok());
+        match size {
+            Some(size) if size > 0.0 => size / 16.0,
+            _ => 1.0,
+        }
+    }
+
+    /// Browsers no longer reliably tell pages about batteries, so this is
+    /// always `PowerSource::External`.
+    pub fn power_source() -> PowerSource {
+        PowerSource::External
+    }
+
+    /// Only the macOS Dock shows badges, so this does nothing.
+    pub fn set_badge(_label: Option<&str>) {}
+
+    /// Open a URL in a new browser tab. Browsers may block this outside of
+    /// the handling of a click or key press.
+    pub fn open_url(url: &str) -> Result<(), Error> {
+        match window().open_with_url_and_target(url, "_blank") {
+            Ok(Some(_)) => Ok(()),
+            _ => Err(Error::Other("the browser blocked opening the URL")),
+        }
+    }
+
+    /// A page has no access to a file manager, so this always fails.
+    pub fn reveal_in_file_manager(_path: &Path) -> Result<(), Error> {
+        Err(Error::Other("no file manager in the browser"))
+    }
+
+    /// Keep the display from sleeping and the screensaver from starting,
+    /// for as long as the returned value is alive, with the Screen Wake Lock
+    /// API. The browser drops the lock while the page is hidden.
+    pub fn inhibit_sleep(_reason: &str) -> Result<SleepInhibitor, Error> {
+        let wake_lock = Reflect::get(&window().navigator(), &"wakeLock".into())
+            .ok()
+            .filter(|wake_lock| !wake_lock.is_undefined())
+            .ok_or(Error::Other("the browser has no wake lock"))?;
+        let request: Function = Reflect::get(&wake_lock, &"request".into())
+            .ok()
+            .and_then(|request| request.dyn_into().ok())
+            .ok_or(Error::Other("the browser has no wake lock"))?;
+        let promise: Promise = request
+            .call1(&wake_lock, &"screen".into())
+            .ok()
+            .and_then(|promise| promise.dyn_into().ok())
+            .ok_or(Error::Other("wake lock request failed"))?;
+        let lock = Rc::new(RefCell::new(WakeLock::default()));
+        let lock_clone = lock.clone();
+        let granted = Closure::wrap(Box::new(move |sentinel: JsValue| {
+            let mut lock = lock_clone.borrow_mut();
+            if lock.released {
+                release_wake_lock(&sentinel);
+            } else {
+                lock.sentinel = Some(sentinel);
+            }
+        }) as Box<dyn FnMut(JsValue)>);
+        let _ = promise.then(&granted);
+        granted.forget();
+        Ok(SleepInhibitor { lock })
+    }
+}
+
+/// Whether a media query matches, `false` if the browser doesn't know it.
+pub(crate) fn media_matches(query: &str) -> bool {
+    window()
+        .match_media(query)
+        .ok()
+        .and_then(|query| query)
+        .map_or(false, |query| query.matches())
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        let mut lock = self.lock.borrow_mut();
+        lock.released = true;
+        if let Some(sentinel) = lock.sentinel.take() {
+            release_wake_lock(&sentinel);
+        }
+    }
+}
+
+fn release_wake_lock(sentinel: &JsValue) {
+    if let Some(release) = Reflect::get(sentinel, &"release".into())
+        .ok()
+        .and_then(|release| release.dyn_into::<Function>().ok())
+    {
+        let _ = release.call0(sentinel);
+    }
+}
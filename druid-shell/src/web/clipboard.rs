@@ -0,0 +1,83 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Interactions with the clipboard on the web.
+//!
+//! Browsers only give asynchronous access to the system clipboard, and only
+//! for text, so the contents are kept in the page. Strings are also written
+//! to the system clipboard, for pasting into other applications.
+
+use std::cell::RefCell;
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::util::window;
+
+thread_local! {
+    static CONTENTS: RefCell<Vec<ClipboardFormat>> = RefCell::new(Vec::new());
+}
+
+/// The system clipboard.
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard;
+
+impl Clipboard {
+    /// Put a string on the clipboard, replacing its contents.
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        self.put_formats(&[ClipboardFormat::new(ClipboardFormat::TEXT, s.as_ref())]);
+    }
+
+    /// Get the string on the clipboard, if there is one.
+    ///
+    /// This is the last string put on the clipboard by the page; text copied
+    /// in other applications can't be read synchronously.
+    pub fn get_string(&self) -> Option<String> {
+        self.get_format(ClipboardFormat::TEXT)
+            .and_then(|data| String::from_utf8(data).ok())
+    }
+
+    /// Put data on the clipboard in several formats, replacing its contents.
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        let text = formats
+            .iter()
+            .find(|f| f.identifier == ClipboardFormat::TEXT)
+            .and_then(|f| std::str::from_utf8(&f.data).ok());
+        if let Some(text) = text {
+            // The returned promise is rejected if the page is not focused;
+            // the contents are still available within the page.
+            let _ = window().navigator().clipboard().write_text(text);
+        }
+        CONTENTS.with(|contents| *contents.borrow_mut() = formats.to_vec());
+    }
+
+    /// Get the data on the clipboard in a format, if it is available.
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        CONTENTS.with(|contents| {
+            contents
+                .borrow()
+                .iter()
+                .find(|f| f.identifier == format)
+                .map(|f| f.data.clone())
+        })
+    }
+
+    /// The first of `formats` that the clipboard has data for.
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        CONTENTS.with(|contents| {
+            let contents = contents.borrow();
+            formats
+                .iter()
+                .cloned()
+                .find(|&format| contents.iter().any(|f| f.identifier == format))
+        })
+    }
+}
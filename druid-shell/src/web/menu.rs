@@ -0,0 +1,77 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Web implementation of menus.
+//!
+//! Pages have no native menus, so menus are only recorded; they are not
+//! shown.
+
+use crate::keycodes::MenuKey;
+
+#[derive(Default)]
+pub struct Menu {
+    items: Vec<MenuItem>,
+}
+
+#[allow(dead_code)]
+enum MenuItem {
+    Entry {
+        id: u32,
+        text: String,
+        key: MenuKey,
+        enabled: bool,
+        checked: bool,
+    },
+    Separator,
+    SubMenu(String, Menu),
+}
+
+impl Menu {
+    pub fn new() -> Menu {
+        Menu::default()
+    }
+
+    pub fn new_for_popup() -> Menu {
+        Menu::default()
+    }
+
+    pub fn add_dropdown(&mut self, menu: Menu, text: &str) {
+        self.items.push(MenuItem::SubMenu(text.to_string(), menu));
+    }
+
+    pub fn add_item(&mut self, id: u32, text: &str, key: impl Into<MenuKey>) {
+        self.add_item_with_state(id, text, key, true, false);
+    }
+
+    /// Add an item, enabled or disabled and with or without a checkmark.
+    pub fn add_item_with_state(
+        &mut self,
+        id: u32,
+        text: &str,
+        key: impl Into<MenuKey>,
+        enabled: bool,
+        checked: bool,
+    ) {
+        self.items.push(MenuItem::Entry {
+            id,
+            text: text.to_string(),
+            key: key.into(),
+            enabled,
+            checked,
+        });
+    }
+
+    pub fn add_separator(&mut self) {
+        self.items.push(MenuItem::Separator);
+    }
+}
@@ -0,0 +1,1484 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Web implementation of window creation.
+//!
+//! A window is a canvas, fixed in the viewport of the page. Events come from
+//! DOM listeners on the canvas and the page, and painting happens in
+//! animation frames requested with `requestAnimationFrame`. Screen readers
+//! see the accessibility tree as fallback content of the canvas.
+
+pub mod application;
+pub mod clipboard;
+pub mod menu;
+pub mod native_view;
+pub mod surface;
+pub mod util;
+pub mod win_main;
+
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+use std::ffi::OsString;
+use std::mem;
+use std::rc::{Rc, Weak};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use js_sys::{Function, Reflect};
+use wasm_bindgen::convert::FromWasmAbi;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::{Clamped, JsCast};
+use web_sys::{CanvasRenderingContext2d, EventTarget, HtmlCanvasElement, ImageData, KeyboardEvent};
+
+use piet_common::kurbo::Rect;
+use piet_common::{Piet, RenderContext};
+
+use crate::accessibility::{AccessTree, Action, ActionRequest, NodeId, Politeness, Role};
+use crate::alert::{AlertButtons, AlertOptions, AlertResponse};
+use crate::blend::Painting;
+use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::drag::DragData;
+use crate::keyboard::{Key, KeyCode, KeyEvent, KeyLocation, KeyModifiers, RawKeyCode};
+use crate::keycodes::MenuKey;
+use crate::timer::{TimerQueue, TimerToken, COALESCE_SLACK};
+use crate::window::{
+    self, Cursor, CursorDesc, IconDesc, MouseButton, MouseEvent, RawDisplayHandle, RawWindowHandle,
+    WheelEvent, WheelPhase, WheelUnit, WinHandler, WindowBackdrop, WindowGeometry, WindowKind,
+    WindowLevel,
+};
+use crate::Error;
+use application::Application;
+pub use menu::Menu;
+use util::window;
+
+/// The id of the `<link>` element holding the icon set with `set_icon`.
+const ICON_LINK_ID: &str = "druid-icon";
+
+thread_local! {
+    /// The open windows. Event listeners only hold weak references, so this
+    /// keeps a window alive until it is closed.
+    static WINDOWS: RefCell<Vec<Rc<WindowState>>> = RefCell::new(Vec::new());
+    static NEXT_ID: Cell<u32> = Cell::new(1);
+}
+
+#[derive(Clone, Default)]
+pub struct WindowHandle {
+    state: Weak<WindowState>,
+}
+
+/// Builder abstraction for creating new windows.
+pub struct WindowBuilder {
+    handler: Option<Box<dyn WinHandler>>,
+    title: String,
+    enable_mouse_move_events: bool,
+    menu: Option<Menu>,
+    kind: WindowKind,
+    level: WindowLevel,
+    owner: Option<WindowHandle>,
+    position: Option<(f64, f64)>,
+    centered: bool,
+    size: Option<(f64, f64)>,
+    icons: Vec<IconDesc>,
+    parent: Option<RawWindowHandle>,
+}
+
+/// A cursor made from an image, as a CSS cursor value.
+#[derive(Clone)]
+pub struct CustomCursor(String);
+
+#[derive(Clone)]
+pub struct IdleHandle {
+    state: Weak<WindowState>,
+    idle_queue: std::sync::Weak<Mutex<Vec<Box<dyn IdleCallback>>>>,
+}
+
+// TODO: move this out of platform-dependent section.
+trait IdleCallback: Send {
+    fn call(self: Box<Self>, a: &dyn Any);
+}
+
+impl<F: FnOnce(&dyn Any) + Send> IdleCallback for F {
+    fn call(self: Box<F>, a: &dyn Any) {
+        (*self)(a)
+    }
+}
+
+struct WindowState {
+    /// Identifies the canvas in its `data-raw-handle` attribute.
+    id: u32,
+    canvas: HtmlCanvasElement,
+    context: CanvasRenderingContext2d,
+    handler: Box<dyn WinHandler>,
+    idle_queue: Arc<Mutex<Vec<Box<dyn IdleCallback>>>>,
+    frame_requested: Cell<bool>,
+    /// Whether `WinHandler::frame` is called in the next animation frame.
+    handler_frame_requested: Cell<bool>,
+    /// The frame of the canvas in the viewport, in px.
+    frame: Cell<Rect>,
+    /// The frame before the window was maximized or made fullscreen.
+    restored_frame: Cell<Rect>,
+    window_state: Cell<window::WindowState>,
+    /// Whether a mouse button was pressed in the canvas and is still held, so
+    /// that moves and releases outside of it are delivered too.
+    pressed: Cell<bool>,
+    enable_mouse_move_events: bool,
+    /// The timers, with deadlines as given by `timer_clock`.
+    timers: RefCell<TimerQueue<u64>>,
+    /// The `setTimeout` handle due at the earliest deadline of the timers.
+    timeout: Cell<Option<i32>>,
+}
+
+impl WindowBuilder {
+    pub fn new() -> WindowBuilder {
+        WindowBuilder {
+            handler: None,
+            title: String::new(),
+            enable_mouse_move_events: true,
+            menu: None,
+            kind: WindowKind::Normal,
+            level: WindowLevel::Normal,
+            owner: None,
+            position: None,
+            centered: false,
+            size: None,
+            icons: Vec::new(),
+            parent: None,
+        }
+    }
+
+    pub fn set_handler(&mut self, handler: Box<dyn WinHandler>) {
+        self.handler = Some(handler);
+    }
+
+    /// Set the title of the window. This becomes the title of the page.
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = title.into();
+    }
+
+    /// Set the menu of the window. Pages have no native menus, so it is not
+    /// shown.
+    pub fn set_menu(&mut self, menu: Menu) {
+        self.menu = Some(menu);
+    }
+
+    pub fn set_enable_mouse_move_events(&mut self, to: bool) {
+        self.enable_mouse_move_events = to;
+    }
+
+    /// Set the kind of window. All windows are plain canvases on the web.
+    pub fn set_kind(&mut self, kind: WindowKind) {
+        self.kind = kind;
+    }
+
+    /// Set the stacking level of the window, as the z-index of its canvas.
+    pub fn set_level(&mut self, level: WindowLevel) {
+        self.level = level;
+    }
+
+    /// Set the window that owns this one. The position of an owned window is
+    /// relative to its owner.
+    pub fn set_owner(&mut self, owner: &WindowHandle) {
+        self.owner = Some(owner.clone());
+    }
+
+    /// Set the initial position of the window, in px. The position is
+    /// relative to the top left corner of the owner window if there is one,
+    /// otherwise to the viewport.
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.position = Some((x, y));
+    }
+
+    /// Center the window on its owner if there is one, otherwise in the
+    /// viewport. This takes precedence over `set_position`.
+    pub fn set_centered(&mut self) {
+        self.centered = true;
+    }
+
+    /// Set the initial size of the window, in px. Without a size, the window
+    /// fills the viewport, following its size.
+    pub fn set_size(&mut self, width: f64, height: f64) {
+        self.size = Some((width, height));
+    }
+
+    /// The browser always paints in step with the display refresh, so this
+    /// has no effect.
+    pub fn set_vsync(&mut self, _vsync: bool) {}
+
+    /// The browser has no backdrops, so the window stays opaque.
+    pub fn set_backdrop(&mut self, _backdrop: WindowBackdrop) {}
+
+    /// Set the icon given in several resolutions. See
+    /// `WindowHandle::set_icon`.
+    pub fn set_icon(&mut self, icons: Vec<IconDesc>) {
+        self.icons = icons;
+    }
+
+    /// Build the window over an element of the host page, rather than over
+    /// the viewport, as for a panel of a larger web application. The
+    /// element is the one whose `data-raw-handle` attribute holds the id of
+    /// the `Web` handle.
+    ///
+    /// The window covers the element unless given a size, and the position
+    /// is relative to the element. The canvas doesn't follow the element as
+    /// the page scrolls or reflows; the host moves it with `set_position`.
+    ///
+    /// # Safety
+    ///
+    /// This is safe on the web, and only unsafe for the sake of the other
+    /// platforms, where `parent` holds pointers.
+    pub unsafe fn set_parent(&mut self, parent: RawWindowHandle) {
+        self.parent = Some(parent);
+    }
+
+    pub fn build(self) -> Result<WindowHandle, Error> {
+        let handler = self.handler.expect("handler");
+        let document = window().document().ok_or(Error::Null)?;
+        let parent_frame = match self.parent {
+            None => None,
+            Some(RawWindowHandle::Web { id }) => Some(element_frame(&document, id)?),
+            Some(_) => return Err(Error::Other("the parent must be a web element")),
+        };
+        let canvas: HtmlCanvasElement = document
+            .create_element("canvas")
+            .map_err(|_| Error::Other("could not create a canvas"))?
+            .unchecked_into();
+        let context: CanvasRenderingContext2d = canvas
+            .get_context("2d")
+            .ok()
+            .and_then(|context| context)
+            .ok_or(Error::Other("could not get a 2d context"))?
+            .unchecked_into();
+        let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+        let _ = canvas.set_attribute("data-raw-handle", &id.to_string());
+        // Make the canvas focusable, for keyboard events.
+        canvas.set_tab_index(0);
+        let style = canvas.style();
+        let _ = style.set_property("position", "fixed");
+        let _ = style.set_property("outline", "none");
+        let _ = style.set_property("touch-action", "none");
+        let z_index = match self.level {
+            WindowLevel::Normal => "0",
+            WindowLevel::Floating => "1",
+            WindowLevel::Tooltip => "2",
+        };
+        let _ = style.set_property("z-index", z_index);
+        // Windows stay hidden until shown.
+        let _ = style.set_property("display", "none");
+        let body = document.body().ok_or(Error::Null)?;
+        body.append_child(&canvas)
+            .map_err(|_| Error::Other("could not add the canvas to the page"))?;
+        if !self.title.is_empty() {
+            document.set_title(&self.title);
+        }
+
+        let owner_frame = self
+            .owner
+            .as_ref()
+            .and_then(|owner| owner.state.upgrade())
+            .map(|owner| owner.frame.get());
+        let (width, height) = match (self.size, parent_frame) {
+            (None, Some(parent)) => (parent.width(), parent.height()),
+            (size, _) => size.unwrap_or_else(viewport_size),
+        };
+        let origin = parent_frame.or(owner_frame).unwrap_or_else(viewport);
+        let (x, y) = if self.centered {
+            (
+                origin.x0 + (origin.width() - width) / 2.0,
+                origin.y0 + (origin.height() - height) / 2.0,
+            )
+        } else {
+            let (x, y) = self.position.unwrap_or((0.0, 0.0));
+            (origin.x0 + x, origin.y0 + y)
+        };
+        let frame = Rect::new(x, y, x + width, y + height);
+
+        let state = Rc::new(WindowState {
+            id,
+            canvas,
+            context,
+            handler,
+            idle_queue: Arc::new(Mutex::new(Vec::new())),
+            frame_requested: Cell::new(false),
+            handler_frame_requested: Cell::new(false),
+            // Set below, reporting the size to the handler.
+            frame: Cell::new(Rect::default()),
+            restored_frame: Cell::new(frame),
+            window_state: Cell::new(if self.size.is_none() && parent_frame.is_none() {
+                window::WindowState::Maximized
+            } else {
+                window::WindowState::Restored
+            }),
+            pressed: Cell::new(false),
+            enable_mouse_move_events: self.enable_mouse_move_events,
+            timers: RefCell::new(TimerQueue::new()),
+            timeout: Cell::new(None),
+        });
+        WINDOWS.with(|windows| windows.borrow_mut().push(state.clone()));
+        add_listeners(&state);
+        let handle = WindowHandle {
+            state: Rc::downgrade(&state),
+        };
+        state.handler.connect(&crate::window::WindowHandle {
+            inner: handle.clone(),
+        });
+        state.set_frame(frame);
+        if !self.icons.is_empty() {
+            handle.set_icon(&self.icons)?;
+        }
+        Ok(handle)
+    }
+}
+
+/// Encode an RGBA image as a PNG `data:` URL, by way of a canvas.
+fn image_data_url(pixels: &[u8], width: usize, height: usize) -> Option<String> {
+    let document = window().document()?;
+    let canvas: HtmlCanvasElement = document.create_element("canvas").ok()?.unchecked_into();
+    canvas.set_width(width as u32);
+    canvas.set_height(height as u32);
+    let context: CanvasRenderingContext2d = canvas.get_context("2d").ok()??.unchecked_into();
+    let image =
+        ImageData::new_with_u8_clamped_array_and_sh(Clamped(pixels), width as u32, height as u32)
+            .ok()?;
+    context.put_image_data(&image, 0.0, 0.0).ok()?;
+    canvas.to_data_url().ok()
+}
+
+/// The viewport of the page, in px.
+/// The time since the page loaded in µs, the clock of the timers.
+fn timer_clock() -> u64 {
+    let millis = window().performance().map_or(0.0, |p| p.now());
+    (millis * 1000.0) as u64
+}
+
+/// The element whose `data-raw-handle` attribute holds `id`.
+fn find_element(document: &web_sys::Document, id: u32) -> Result<web_sys::Element, Error> {
+    document
+        .query_selector(&format!("[data-raw-handle=\"{}\"]", id))
+        .ok()
+        .and_then(|element| element)
+        .ok_or(Error::Other("no element has the handle"))
+}
+
+/// The frame, in px, of the element whose `data-raw-handle` attribute
+/// holds `id`.
+fn element_frame(document: &web_sys::Document, id: u32) -> Result<Rect, Error> {
+    let rect = find_element(document, id)?.get_bounding_client_rect();
+    Ok(Rect::new(
+        rect.left(),
+        rect.top(),
+        rect.right(),
+        rect.bottom(),
+    ))
+}
+
+fn viewport() -> Rect {
+    let (width, height) = viewport_size();
+    Rect::new(0.0, 0.0, width, height)
+}
+
+fn viewport_size() -> (f64, f64) {
+    let window = window();
+    let width = window.inner_width().ok().and_then(|w| w.as_f64());
+    let height = window.inner_height().ok().and_then(|h| h.as_f64());
+    (width.unwrap_or(0.0), height.unwrap_or(0.0))
+}
+
+/// Call a method of `navigator.virtualKeyboard`, if the browser has it.
+fn call_virtual_keyboard(method: &str) {
+    let keyboard = match Reflect::get(&window().navigator(), &"virtualKeyboard".into()) {
+        Ok(keyboard) if !keyboard.is_undefined() => keyboard,
+        _ => return,
+    };
+    if let Ok(function) = Reflect::get(&keyboard, &method.into()) {
+        if let Ok(function) = function.dyn_into::<Function>() {
+            let _ = function.call0(&keyboard);
+        }
+    }
+}
+
+/// Add an event listener that stays for the lifetime of the page.
+fn add_listener<E, F>(target: &EventTarget, event: &str, listener: F)
+where
+    E: FromWasmAbi + 'static,
+    F: FnMut(E) + 'static,
+{
+    let closure = Closure::wrap(Box::new(listener) as Box<dyn FnMut(E)>);
+    let _ = target.add_event_listener_with_callback(event, closure.as_ref().unchecked_ref());
+    closure.forget();
+}
+
+/// Call `listener` with whether the media query matches whenever that
+/// changes.
+fn add_media_listener<F>(query: &str, mut listener: F)
+where
+    F: FnMut(bool) + 'static,
+{
+    if let Ok(Some(list)) = window().match_media(query) {
+        let target: &EventTarget = list.as_ref();
+        let list_clone = list.clone();
+        add_listener(target, "change", move |_: web_sys::Event| {
+            listener(list_clone.matches())
+        });
+    }
+}
+
+fn add_listeners(state: &Rc<WindowState>) {
+    let canvas: &EventTarget = state.canvas.as_ref();
+    let page: EventTarget = window().into();
+
+    let weak = Rc::downgrade(state);
+    add_listener(&page, "resize", move |_: web_sys::Event| {
+        if let Some(state) = weak.upgrade() {
+            if state.window_state.get() != window::WindowState::Restored {
+                state.set_frame(viewport());
+            }
+        }
+    });
+
+    // Media queries notify of changes to whether they match.
+    let weak = Rc::downgrade(state);
+    add_media_listener(application::DARK_QUERY, move |_| {
+        if let Some(state) = weak.upgrade() {
+            state.handler.theme_changed(Application::theme());
+        }
+    });
+
+    let weak = Rc::downgrade(state);
+    add_media_listener(application::REDUCE_TRANSPARENCY_QUERY, move |reduce| {
+        if let Some(state) = weak.upgrade() {
+            state.handler.reduce_transparency_changed(reduce);
+        }
+    });
+
+    // Screen readers click and focus the fallback elements of the
+    // accessibility tree.
+    let weak = Rc::downgrade(state);
+    add_listener(canvas, "click", move |event: web_sys::Event| {
+        if let (Some(state), Some(node)) = (weak.upgrade(), access_target(&event)) {
+            state.handler.accessibility_action(ActionRequest {
+                node,
+                action: Action::Click,
+                value: None,
+            });
+        }
+    });
+
+    let weak = Rc::downgrade(state);
+    add_listener(canvas, "focusin", move |event: web_sys::Event| {
+        if let (Some(state), Some(node)) = (weak.upgrade(), access_target(&event)) {
+            state.handler.accessibility_action(ActionRequest {
+                node,
+                action: Action::Focus,
+                value: None,
+            });
+        }
+    });
+
+    let weak = Rc::downgrade(state);
+    add_listener(canvas, "mousedown", move |event: web_sys::MouseEvent| {
+        if let (Some(state), Some(button)) = (weak.upgrade(), get_mouse_button(event.button())) {
+            let _ = state.canvas.focus();
+            state.pressed.set(true);
+            let (x, y) = state.mouse_position(&event);
+            let mouse_event = MouseEvent {
+                x,
+                y,
+                mods: make_modifiers(&event),
+                count: event.detail().max(1) as u32,
+                button,
+            };
+            state.handler.mouse(&mouse_event);
+            event.prevent_default();
+        }
+    });
+
+    // Moves and releases are listened for on the page, so that a drag that
+    // leaves the canvas keeps being delivered.
+    let weak = Rc::downgrade(state);
+    add_listener(&page, "mouseup", move |event: web_sys::MouseEvent| {
+        if let (Some(state), Some(button)) = (weak.upgrade(), get_mouse_button(event.button())) {
+            if !state.pressed.get() && !state.is_target(&event) {
+                return;
+            }
+            if event.buttons() == 0 {
+                state.pressed.set(false);
+            }
+            let (x, y) = state.mouse_position(&event);
+            let mouse_event = MouseEvent {
+                x,
+                y,
+                mods: make_modifiers(&event),
+                count: 0,
+                button,
+            };
+            state.handler.mouse(&mouse_event);
+        }
+    });
+
+    let weak = Rc::downgrade(state);
+    add_listener(&page, "mousemove", move |event: web_sys::MouseEvent| {
+        if let Some(state) = weak.upgrade() {
+            if !state.pressed.get() && !state.is_target(&event) {
+                return;
+            }
+            if !state.enable_mouse_move_events && event.buttons() == 0 {
+                return;
+            }
+            let (x, y) = state.mouse_position(&event);
+            let mouse_event = MouseEvent {
+                x,
+                y,
+                mods: make_modifiers(&event),
+                count: 0,
+                button: get_pressed_button(event.buttons()),
+            };
+            state.handler.mouse_move(&mouse_event);
+        }
+    });
+
+    add_listener(canvas, "contextmenu", move |event: web_sys::Event| {
+        event.prevent_default();
+    });
+
+    let weak = Rc::downgrade(state);
+    add_listener(canvas, "wheel", move |event: web_sys::WheelEvent| {
+        if let Some(state) = weak.upgrade() {
+            let unit = match event.delta_mode() {
+                web_sys::WheelEvent::DOM_DELTA_LINE => WheelUnit::Lines,
+                web_sys::WheelEvent::DOM_DELTA_PAGE => WheelUnit::Pages,
+                _ => WheelUnit::Pixels,
+            };
+            let wheel_event = WheelEvent {
+                dx: event.delta_x(),
+                dy: event.delta_y(),
+                unit,
+                mods: make_modifiers(&event),
+                phase: WheelPhase::None,
+            };
+            state.handler.wheel(&wheel_event);
+            event.prevent_default();
+        }
+    });
+
+    let weak = Rc::downgrade(state);
+    add_listener(canvas, "keydown", move |event: KeyboardEvent| {
+        if let Some(state) = weak.upgrade() {
+            if state.handler.key_down(make_key_event(&event)) {
+                // Keep the browser from acting on a handled shortcut.
+                event.prevent_default();
+            }
+        }
+    });
+
+    let weak = Rc::downgrade(state);
+    add_listener(canvas, "keyup", move |event: KeyboardEvent| {
+        if let Some(state) = weak.upgrade() {
+            state.handler.key_up(make_key_event(&event));
+        }
+    });
+}
+
+impl WindowState {
+    /// Move and resize the canvas, in px, resizing its backing store to
+    /// match the device pixel ratio.
+    fn set_frame(self: &Rc<Self>, frame: Rect) {
+        let old = self.frame.replace(frame);
+        let style = self.canvas.style();
+        let _ = style.set_property("left", &format!("{}px", frame.x0));
+        let _ = style.set_property("top", &format!("{}px", frame.y0));
+        let _ = style.set_property("width", &format!("{}px", frame.width()));
+        let _ = style.set_property("height", &format!("{}px", frame.height()));
+        let scale = window().device_pixel_ratio();
+        self.canvas
+            .set_width((frame.width() * scale).round() as u32);
+        self.canvas
+            .set_height((frame.height() * scale).round() as u32);
+        if old.width() != frame.width() || old.height() != frame.height() {
+            self.handler
+                .size(frame.width() as u32, frame.height() as u32);
+        }
+        self.request_frame();
+    }
+
+    /// Paint in the next animation frame, if that's not already scheduled.
+    fn request_frame(self: &Rc<Self>) {
+        if self.frame_requested.replace(true) {
+            return;
+        }
+        let weak = Rc::downgrade(self);
+        let callback = Closure::once_into_js(move || {
+            if let Some(state) = weak.upgrade() {
+                state.frame_requested.set(false);
+                if state.handler_frame_requested.replace(false) {
+                    state.handler.frame();
+                }
+                state.paint();
+            }
+        });
+        let _ = window().request_animation_frame(callback.unchecked_ref());
+    }
+
+    fn paint(self: &Rc<Self>) {
+        let scale = window().device_pixel_ratio();
+        let mut context = self.context.clone();
+        let _ = context.set_transform(scale, 0.0, 0.0, scale, 0.0, 0.0);
+        let frame = self.frame.get();
+        context.clear_rect(0.0, 0.0, frame.width(), frame.height());
+        let window = window();
+        let anim = {
+            let _painting = Painting::new(Some(&context));
+            let mut piet_ctx = Piet::new(&mut context, &window);
+            let anim = self.handler.paint(&mut piet_ctx);
+            if let Err(e) = piet_ctx.finish() {
+                web_sys::console::error_1(&format!("Error: {}", e).into());
+            }
+            anim
+        };
+        if anim {
+            self.request_frame();
+        }
+    }
+
+    /// Whether a mouse event happened over the canvas.
+    fn is_target(&self, event: &web_sys::MouseEvent) -> bool {
+        let target: Option<EventTarget> = event.target();
+        let canvas: &EventTarget = self.canvas.as_ref();
+        target.as_ref() == Some(canvas)
+    }
+
+    /// The position of a mouse event relative to the canvas, in px.
+    fn mouse_position(&self, event: &web_sys::MouseEvent) -> (i32, i32) {
+        let frame = self.frame.get();
+        (
+            event.client_x() - frame.x0 as i32,
+            event.client_y() - frame.y0 as i32,
+        )
+    }
+}
+
+/// The id of the fallback element of node `node` of window `window`.
+fn access_id(window_id: u32, node: NodeId) -> String {
+    format!("druid-{}-{}", window_id, node)
+}
+
+/// The id of the live region of window `window_id` for announcements of
+/// `politeness`.
+fn live_region_id(window_id: u32, politeness: Politeness) -> String {
+    format!("druid-{}-live-{:?}", window_id, politeness)
+}
+
+/// The fallback element of node `id` of `tree` in window `window_id`, holding the elements of its
+/// children.
+fn access_element(window_id: u32, tree: &AccessTree, id: NodeId) -> Option<web_sys::Element> {
+    let node = tree.node(id)?;
+    let element = window().document()?.create_element("div").ok()?;
+    let _ = element.set_attribute("id", &access_id(window_id, id));
+    let _ = element.set_attribute("data-access-node", &id.to_string());
+    let role = match node.role {
+        Role::Group | Role::Window | Role::Label => None,
+        Role::Button => Some("button"),
+        Role::CheckBox => Some("checkbox"),
+        Role::TextInput | Role::PasswordInput => Some("textbox"),
+        Role::Slider => Some("slider"),
+        Role::ProgressIndicator => Some("progressbar"),
+        Role::Image => Some("img"),
+        Role::List => Some("list"),
+        Role::ListItem => Some("listitem"),
+        Role::Menu => Some("menu"),
+        Role::MenuItem => Some("menuitem"),
+    };
+    if let Some(role) = role {
+        let _ = element.set_attribute("role", role);
+    }
+    match (node.role, &node.name) {
+        // Labels are read as text.
+        (Role::Label, Some(name)) => element.set_text_content(Some(name)),
+        (_, Some(name)) => {
+            let _ = element.set_attribute("aria-label", name);
+        }
+        (_, None) => (),
+    }
+    match (node.role, &node.value) {
+        (Role::TextInput, Some(value)) => element.set_text_content(Some(value)),
+        (Role::PasswordInput, _) | (_, None) => (),
+        (_, Some(value)) => {
+            let _ = element.set_attribute("aria-valuetext", value);
+        }
+    }
+    let numbers = [
+        ("aria-valuenow", node.numeric_value),
+        ("aria-valuemin", node.min_value),
+        ("aria-valuemax", node.max_value),
+    ];
+    for (name, number) in numbers.iter() {
+        if let Some(number) = number {
+            let _ = element.set_attribute(name, &number.to_string());
+        }
+    }
+    if !node.actions.is_empty() {
+        // Focusable by screen readers, but not in the tab order.
+        let _ = element.set_attribute("tabindex", "-1");
+    }
+    for &child in &node.children {
+        if let Some(child) = access_element(window_id, tree, child) {
+            let _ = element.append_child(&child);
+        }
+    }
+    Some(element)
+}
+
+/// The node whose fallback element is the target of `event`.
+fn access_target(event: &web_sys::Event) -> Option<NodeId> {
+    let element: web_sys::Element = event.target()?.dyn_into().ok()?;
+    element.get_attribute("data-access-node")?.parse().ok()
+}
+
+fn get_mouse_button(button: i16) -> Option<MouseButton> {
+    match button {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        3 => Some(MouseButton::X1),
+        4 => Some(MouseButton::X2),
+        _ => None,
+    }
+}
+
+fn get_pressed_button(buttons: u16) -> MouseButton {
+    //TODO: this doesn't correctly handle multiple buttons being pressed.
+    if buttons & 2 != 0 {
+        MouseButton::Right
+    } else if buttons & 4 != 0 {
+        MouseButton::Middle
+    } else {
+        MouseButton::Left
+    }
+}
+
+fn make_modifiers(event: &web_sys::MouseEvent) -> KeyModifiers {
+    KeyModifiers {
+        shift: event.shift_key(),
+        alt: event.alt_key(),
+        ctrl: event.ctrl_key(),
+        meta: event.meta_key(),
+    }
+}
+
+fn make_key_event(event: &KeyboardEvent) -> KeyEvent {
+    let code = KeyCode::from_dom_code(&event.code())
+        .unwrap_or_else(|| KeyCode::Unknown(RawKeyCode::Web(event.key_code())));
+    let modifiers = KeyModifiers {
+        shift: event.shift_key(),
+        alt: event.alt_key(),
+        ctrl: event.ctrl_key(),
+        meta: event.meta_key(),
+    };
+    // The key is the character produced with shift applied, or the name of
+    // a key without one, such as "ArrowUp".
+    let key = event.key();
+    let mut chars = key.chars();
+    let unmodified = match (chars.next(), chars.next()) {
+        (Some(c), None) => Some(c),
+        _ => None,
+    };
+    let text = if modifiers.ctrl || modifiers.meta {
+        None
+    } else {
+        unmodified
+    };
+    let mut key_event = KeyEvent::new(code, event.repeat(), modifiers, text, unmodified);
+    if key == "Dead" {
+        key_event.key = Key::Dead;
+    }
+    key_event.location = match event.location() {
+        KeyboardEvent::DOM_KEY_LOCATION_LEFT => KeyLocation::Left,
+        KeyboardEvent::DOM_KEY_LOCATION_RIGHT => KeyLocation::Right,
+        KeyboardEvent::DOM_KEY_LOCATION_NUMPAD => KeyLocation::Numpad,
+        _ => KeyLocation::Standard,
+    };
+    key_event
+}
+
+impl WindowHandle {
+    pub fn show(&self) {
+        if let Some(state) = self.state.upgrade() {
+            let _ = state.canvas.style().set_property("display", "block");
+            let _ = state.canvas.focus();
+        }
+    }
+
+    /// Set the menu of the window. Pages have no native menus, so it is not
+    /// shown.
+    pub fn set_menu(&self, _menu: Menu) {}
+
+    /// Set the title of the page.
+    pub fn set_title(&self, title: &str) {
+        if let Some(document) = window().document() {
+            document.set_title(title);
+        }
+    }
+
+    /// The title of the page.
+    pub fn get_title(&self) -> String {
+        window()
+            .document()
+            .map(|document| document.title())
+            .unwrap_or_default()
+    }
+
+    /// The size of the canvas, in px.
+    pub fn get_size(&self) -> (f64, f64) {
+        match self.state.upgrade() {
+            Some(state) => {
+                let frame = state.frame.get();
+                (frame.width(), frame.height())
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Resize the canvas, in px. A window filling the viewport stops following
+    /// its size.
+    pub fn set_size(&self, width: f64, height: f64) {
+        if let Some(state) = self.state.upgrade() {
+            state.window_state.set(window::WindowState::Restored);
+            let origin = state.frame.get().origin();
+            state.set_frame(Rect::new(
+                origin.x,
+                origin.y,
+                origin.x + width,
+                origin.y + height,
+            ));
+        }
+    }
+
+    /// Pages can't show icons in the system tray.
+    pub fn set_tray_icon(
+        &self,
+        _icon: &IconDesc,
+        _tooltip: &str,
+        _menu: Option<Menu>,
+    ) -> Result<(), Error> {
+        Err(Error::Other("tray icons are not supported on the web"))
+    }
+
+    pub fn remove_tray_icon(&self) {}
+
+    /// Pages can only drag elements marked as draggable in advance, so drags
+    /// can't be started from the canvas.
+    pub fn start_drag(&self, _data: DragData) -> Result<(), Error> {
+        Err(Error::Other("drags are not supported on the web"))
+    }
+
+    /// Set the icon of the page, shown in its browser tab. It belongs to the
+    /// page rather than the canvas, so it is shared by all windows.
+    pub fn set_icon(&self, icons: &[IconDesc]) -> Result<(), Error> {
+        // Tabs show icons at 16 points.
+        let size = (16.0 * window().device_pixel_ratio()).ceil() as usize;
+        let icon = IconDesc::best_fit(icons, size).ok_or(Error::Null)?;
+        let url = image_data_url(&icon.image, icon.width, icon.height).ok_or(Error::Null)?;
+        let document = window().document().ok_or(Error::Null)?;
+        let link = match document.get_element_by_id(ICON_LINK_ID) {
+            Some(link) => link,
+            None => {
+                let link = document.create_element("link").map_err(|_| Error::Null)?;
+                link.set_id(ICON_LINK_ID);
+                let _ = link.set_attribute("rel", "icon");
+                let head = document
+                    .query_selector("head")
+                    .ok()
+                    .and_then(|head| head)
+                    .ok_or(Error::Null)?;
+                head.append_child(&link).map_err(|_| Error::Null)?;
+                link
+            }
+        };
+        let _ = link.set_attribute("href", &url);
+        Ok(())
+    }
+
+    /// Compositions are not reported, so this has no effect.
+    pub fn cancel_composition(&self) {}
+
+    /// Input methods need an editable element, so composition is not
+    /// reported yet and the caret position is unused.
+    pub fn set_caret_rect(&self, _rect: Rect) {}
+
+    /// Show the on-screen keyboard, with the VirtualKeyboard API where the
+    /// browser has it. The canvas is made editable meanwhile, since browsers
+    /// only show the keyboard for editable elements.
+    pub fn show_soft_keyboard(&self) {
+        if let Some(state) = self.state.upgrade() {
+            let _ = state
+                .canvas
+                .set_attribute("virtualkeyboardpolicy", "manual");
+            let _ = state.canvas.set_attribute("contenteditable", "true");
+            call_virtual_keyboard("show");
+        }
+    }
+
+    /// Hide the on-screen keyboard, and make the canvas uneditable again.
+    pub fn hide_soft_keyboard(&self) {
+        if let Some(state) = self.state.upgrade() {
+            call_virtual_keyboard("hide");
+            let _ = state.canvas.remove_attribute("contenteditable");
+        }
+    }
+
+    pub fn accessibility_enabled(&self) -> bool {
+        true
+    }
+
+    /// Replace the fallback content of the canvas with elements mirroring
+    /// the tree, with ARIA roles and properties, which screen readers
+    /// present in place of the canvas. The focused node is the active
+    /// descendant of the canvas, which keeps the keyboard focus.
+    pub fn update_accessibility(&self, tree: AccessTree) {
+        if let Some(state) = self.state.upgrade() {
+            state.canvas.set_inner_html("");
+            if let Some(element) = access_element(state.id, &tree, tree.root) {
+                let _ = state.canvas.append_child(&element);
+            }
+            let _ = match tree.focus {
+                Some(focus) => state
+                    .canvas
+                    .set_attribute("aria-activedescendant", &access_id(state.id, focus)),
+                None => state.canvas.remove_attribute("aria-activedescendant"),
+            };
+        }
+    }
+
+    /// Read out `text` with a live region of the window, an element of the
+    /// page hidden from view whose changes screen readers announce.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        let document = match window().document() {
+            Some(document) => document,
+            None => return,
+        };
+        let live = match politeness {
+            Politeness::Polite => "polite",
+            Politeness::Assertive => "assertive",
+        };
+        let id = live_region_id(state.id, politeness);
+        let region = match document.get_element_by_id(&id) {
+            Some(region) => region,
+            None => match (document.create_element("div"), document.body()) {
+                (Ok(region), Some(body)) => {
+                    let _ = region.set_attribute("id", &id);
+                    let _ = region.set_attribute("aria-live", live);
+                    let _ = region.set_attribute("aria-atomic", "true");
+                    let _ = region.set_attribute(
+                        "style",
+                        "position: fixed; width: 1px; height: 1px; overflow: hidden; \
+                         clip: rect(0 0 0 0); white-space: nowrap",
+                    );
+                    let _ = body.append_child(&region);
+                    region
+                }
+                _ => return,
+            },
+        };
+        // A new element is announced even if the text is the same as the
+        // last.
+        region.set_text_content(None);
+        if let Ok(message) = document.create_element("div") {
+            message.set_text_content(Some(text));
+            let _ = region.append_child(&message);
+        }
+    }
+
+    /// The frame of the canvas when it is not maximized, and whether it is
+    /// maximized, for saving the window layout.
+    pub fn get_geometry(&self) -> WindowGeometry {
+        match self.state.upgrade() {
+            Some(state) => {
+                let window_state = state.window_state.get();
+                let frame = match window_state {
+                    window::WindowState::Restored => state.frame.get(),
+                    _ => state.restored_frame.get(),
+                };
+                WindowGeometry {
+                    frame,
+                    maximized: window_state == window::WindowState::Maximized,
+                }
+            }
+            None => WindowGeometry::default(),
+        }
+    }
+
+    /// Restore a frame and maximized state saved with `get_geometry`.
+    ///
+    /// The frame is moved and shrunk as needed to fit in the viewport, in
+    /// case the page has been resized since.
+    pub fn set_geometry(&self, geometry: &WindowGeometry) {
+        if let Some(state) = self.state.upgrade() {
+            let frame = window::clamp_frame(geometry.frame, viewport());
+            state.restored_frame.set(frame);
+            if geometry.maximized {
+                state.window_state.set(window::WindowState::Maximized);
+                state.set_frame(viewport());
+            } else {
+                state.window_state.set(window::WindowState::Restored);
+                state.set_frame(frame);
+            }
+        }
+    }
+
+    pub fn hide(&self) {
+        if let Some(state) = self.state.upgrade() {
+            let _ = state.canvas.style().set_property("display", "none");
+        }
+    }
+
+    /// Change the stacking level of the window, as the z-index of its canvas.
+    pub fn set_level(&self, level: WindowLevel) {
+        if let Some(state) = self.state.upgrade() {
+            let z_index = match level {
+                WindowLevel::Normal => "0",
+                WindowLevel::Floating => "1",
+                WindowLevel::Tooltip => "2",
+            };
+            let _ = state.canvas.style().set_property("z-index", z_index);
+        }
+    }
+
+    /// The position of the top left corner of the canvas in the viewport, in
+    /// px.
+    pub fn get_position(&self) -> (f64, f64) {
+        match self.state.upgrade() {
+            Some(state) => {
+                let frame = state.frame.get();
+                (frame.x0, frame.y0)
+            }
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// Move the top left corner of the canvas to the given position, in the
+    /// coordinates of `get_position`.
+    pub fn set_position(&self, x: f64, y: f64) {
+        if let Some(state) = self.state.upgrade() {
+            let frame = state.frame.get();
+            state.set_frame(frame.with_origin((x, y)));
+        }
+    }
+
+    /// Center the canvas in the viewport.
+    pub fn center_on_screen(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.center_in(viewport());
+        }
+    }
+
+    /// Center the canvas over another window, as for a dialog.
+    pub fn center_on(&self, other: &WindowHandle) {
+        if let (Some(state), Some(other)) = (self.state.upgrade(), other.state.upgrade()) {
+            state.center_in(other.frame.get());
+        }
+    }
+
+    /// Make the canvas fill the viewport.
+    pub fn maximize(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.leave_restored(window::WindowState::Maximized);
+            state.set_frame(viewport());
+        }
+    }
+
+    /// A page can't minimize itself, so this does nothing.
+    pub fn minimize(&self) {}
+
+    /// Return the canvas to its normal size and position, leaving fullscreen
+    /// if needed.
+    pub fn restore(&self) {
+        if let Some(state) = self.state.upgrade() {
+            let old = state.window_state.replace(window::WindowState::Restored);
+            if old == window::WindowState::Fullscreen {
+                if let Some(document) = window().document() {
+                    document.exit_fullscreen();
+                }
+            }
+            if old != window::WindowState::Restored {
+                state.set_frame(state.restored_frame.get());
+                state
+                    .handler
+                    .window_state_changed(window::WindowState::Restored);
+            }
+        }
+    }
+
+    /// Enter or leave fullscreen. Browsers only allow entering fullscreen
+    /// while handling a user action, such as a click or key press.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        if let Some(state) = self.state.upgrade() {
+            if !fullscreen {
+                if state.window_state.get() == window::WindowState::Fullscreen {
+                    self.restore();
+                }
+            } else if state.canvas.request_fullscreen().is_ok() {
+                state.leave_restored(window::WindowState::Fullscreen);
+            }
+        }
+    }
+
+    pub fn get_window_state(&self) -> window::WindowState {
+        match self.state.upgrade() {
+            Some(state) => state.window_state.get(),
+            None => window::WindowState::Restored,
+        }
+    }
+
+    /// The handle of the canvas, for external renderers.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        self.state
+            .upgrade()
+            .map(|state| RawWindowHandle::Web { id: state.id })
+    }
+
+    pub fn raw_display_handle(&self) -> RawDisplayHandle {
+        RawDisplayHandle::Web
+    }
+
+    /// Set the cursor shown while the pointer is over the canvas.
+    pub fn set_cursor(&self, cursor: &Cursor) {
+        if let Some(state) = self.state.upgrade() {
+            let value = match cursor {
+                Cursor::Arrow => "default",
+                Cursor::IBeam => "text",
+                Cursor::Crosshair => "crosshair",
+                Cursor::OpenHand => "grab",
+                Cursor::ClosedHand => "grabbing",
+                Cursor::NotAllowed => "not-allowed",
+                Cursor::ResizeLeftRight => "ew-resize",
+                Cursor::ResizeUpDown => "ns-resize",
+                Cursor::Custom(custom) => &custom.0,
+            };
+            let _ = state.canvas.style().set_property("cursor", value);
+        }
+    }
+
+    /// Create a cursor from an image.
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        let url = image_data_url(&desc.image, desc.width, desc.height)?;
+        let value = format!("url({}) {} {}, auto", url, desc.hot.0, desc.hot.1);
+        Some(Cursor::Custom(CustomCursor(value)))
+    }
+
+    /// Pages have no native menus, so this does nothing.
+    pub fn show_context_menu(&self, _menu: Menu, _x: f64, _y: f64) {}
+
+    /// Close the window, removing its canvas from the page.
+    pub fn close(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.canvas.remove();
+            if let Some(document) = window().document() {
+                for &politeness in &[Politeness::Polite, Politeness::Assertive] {
+                    let id = live_region_id(state.id, politeness);
+                    if let Some(region) = document.get_element_by_id(&id) {
+                        region.remove();
+                    }
+                }
+            }
+            WINDOWS.with(|windows| {
+                windows
+                    .borrow_mut()
+                    .retain(|window| !Rc::ptr_eq(window, &state))
+            });
+            state.handler.destroy();
+        }
+    }
+
+    // Request invalidation of the entire window contents.
+    pub fn invalidate(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.request_frame();
+        }
+    }
+
+    /// Request a call to `WinHandler::frame` in the next animation frame,
+    /// followed by a paint.
+    pub fn request_frame(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.handler_frame_requested.set(true);
+            state.request_frame();
+        }
+    }
+
+    /// Request a call to `WinHandler::timer` with the returned token after
+    /// `delay`. Browsers may delay timers by a few milliseconds, and by much
+    /// more while the page is hidden.
+    pub fn request_timer(&self, delay: Duration) -> TimerToken {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return TimerToken::next(),
+        };
+        let deadline =
+            timer_clock() + delay.as_secs() * 1_000_000 + u64::from(delay.subsec_micros());
+        let (token, earliest) = state.timers.borrow_mut().add(deadline);
+        if earliest {
+            self.schedule_timers();
+        }
+        token
+    }
+
+    /// Set a timeout for the earliest deadline of the timers, replacing the
+    /// previous one.
+    fn schedule_timers(&self) {
+        let state = match self.state.upgrade() {
+            Some(state) => state,
+            None => return,
+        };
+        if let Some(timeout) = state.timeout.take() {
+            window().clear_timeout_with_handle(timeout);
+        }
+        let deadline = state.timers.borrow().next_deadline();
+        if let Some(deadline) = deadline {
+            // Round up, so the timeout doesn't fire before the deadline.
+            let millis = (deadline.saturating_sub(timer_clock()) + 999) / 1000;
+            let handle = self.clone();
+            let callback = Closure::once_into_js(move || handle.run_timers());
+            if let Ok(timeout) = window().set_timeout_with_callback_and_timeout_and_arguments_0(
+                callback.unchecked_ref(),
+                millis as i32,
+            ) {
+                state.timeout.set(Some(timeout));
+            }
+        }
+    }
+
+    fn run_timers(&self) {
+        if let Some(state) = self.state.upgrade() {
+            state.timeout.set(None);
+            let due = state
+                .timers
+                .borrow_mut()
+                .take_due(timer_clock() + COALESCE_SLACK.as_micros() as u64);
+            for token in due {
+                state.handler.timer(token);
+            }
+        }
+        self.schedule_timers();
+    }
+
+    /// The browser always paints in step with the display refresh, so this
+    /// has no effect.
+    pub fn set_vsync(&self, _vsync: bool) {}
+
+    /// The backdrop shown behind the window, which is always `None`.
+    pub fn backdrop(&self) -> WindowBackdrop {
+        WindowBackdrop::None
+    }
+
+    /// Create a surface in the window for rendering with a GPU API directly.
+    pub fn create_gpu_surface(&self) -> Result<surface::GpuSurface, Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        surface::GpuSurface::new(&state)
+    }
+
+    /// Host an element of the page, such as an `<iframe>`, in the window.
+    /// The element is the one whose `data-raw-handle` attribute holds the id
+    /// of the `Web` handle.
+    ///
+    /// # Safety
+    ///
+    /// This is safe on the web, and only unsafe for the sake of the other
+    /// platforms, where `view` holds pointers.
+    pub unsafe fn add_native_view(
+        &self,
+        view: RawWindowHandle,
+    ) -> Result<native_view::NativeView, Error> {
+        let state = self.state.upgrade().ok_or(Error::Null)?;
+        let document = window().document().ok_or(Error::Null)?;
+        match view {
+            RawWindowHandle::Web { id } => {
+                native_view::NativeView::new(&state, find_element(&document, id)?)
+            }
+            _ => Err(Error::Other("the view must be a web element")),
+        }
+    }
+
+    /// Capture the mouse, so that mouse events keep being delivered to this
+    /// window while the pointer is outside it, until `release_mouse`.
+    ///
+    /// Moves and releases are already delivered while a button pressed in
+    /// the canvas is held down, so there is nothing to do here.
+    pub fn capture_mouse(&self) {}
+
+    /// Release a mouse capture started with `capture_mouse`.
+    pub fn release_mouse(&self) {}
+
+    /// Register a system-wide hotkey. Pages have no way to do this.
+    pub fn register_hotkey(&self, _id: u32, _key: MenuKey) -> Result<(), Error> {
+        Err(Error::Other("global hotkeys are not supported on the web"))
+    }
+
+    /// Unregister a hotkey previously registered with `register_hotkey`.
+    pub fn unregister_hotkey(&self, _id: u32) {}
+
+    /// Get a handle that can be used to schedule an idle task.
+    pub fn get_idle_handle(&self) -> Option<IdleHandle> {
+        self.state.upgrade().map(|state| IdleHandle {
+            state: self.state.clone(),
+            idle_queue: Arc::downgrade(&state.idle_queue),
+        })
+    }
+
+    /// Get the dpi of the window.
+    ///
+    /// Drawing is in CSS px, scaled for the device pixel ratio by the
+    /// canvas, so this is always nominal.
+    pub fn get_dpi(&self) -> f32 {
+        96.0
+    }
+
+    // TODO: the following methods are cut'n'paste code. A good way to DRY
+    // would be to have a platform-independent trait with these as methods with
+    // default implementations.
+
+    /// Convert a dimension in px units to physical pixels (rounding).
+    pub fn px_to_pixels(&self, x: f32) -> i32 {
+        (x * self.get_dpi() * (1.0 / 96.0)).round() as i32
+    }
+
+    /// Convert a point in px units to physical pixels (rounding).
+    pub fn px_to_pixels_xy(&self, x: f32, y: f32) -> (i32, i32) {
+        let scale = self.get_dpi() * (1.0 / 96.0);
+        ((x * scale).round() as i32, (y * scale).round() as i32)
+    }
+
+    /// Convert a dimension in physical pixels to px units.
+    pub fn pixels_to_px<T: Into<f64>>(&self, x: T) -> f32 {
+        (x.into() as f32) * 96.0 / self.get_dpi()
+    }
+
+    /// Convert a point in physical pixels to px units.
+    pub fn pixels_to_px_xy<T: Into<f64>>(&self, x: T, y: T) -> (f32, f32) {
+        let scale = 96.0 / self.get_dpi();
+        ((x.into() as f32) * scale, (y.into() as f32) * scale)
+    }
+
+    /// Pages can't show modal file dialogs.
+    pub fn file_dialog(
+        &self,
+        _ty: FileDialogType,
+        _options: FileDialogOptions,
+    ) -> Result<OsString, Error> {
+        Err(Error::Other("file dialogs are not supported on the web"))
+    }
+
+    /// Pages can't show modal file dialogs.
+    pub fn open_file_dialog(&self, _options: &FileDialogOptions) -> Result<Vec<FileInfo>, Error> {
+        Err(Error::Other("file dialogs are not supported on the web"))
+    }
+
+    /// Pages can't show modal file dialogs.
+    pub fn save_file_dialog(&self, _options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        Err(Error::Other("file dialogs are not supported on the web"))
+    }
+
+    /// Pages can't show modal file dialogs.
+    pub fn open_directory_dialog(&self, _options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        Err(Error::Other("file dialogs are not supported on the web"))
+    }
+
+    /// Show a modal alert, returning the chosen button.
+    ///
+    /// This uses the browser's `alert` and `confirm` dialogs, which have no
+    /// title and only offer one or two buttons, so `YesNoCancel` can't
+    /// answer `Cancel`.
+    pub fn alert(&self, options: &AlertOptions) -> AlertResponse {
+        let message = match options.detail {
+            Some(ref detail) => format!("{}\n\n{}", options.message, detail),
+            None => options.message.clone(),
+        };
+        let window = window();
+        let confirmed = match options.buttons {
+            AlertButtons::Ok => {
+                let _ = window.alert_with_message(&message);
+                return AlertResponse::Ok;
+            }
+            _ => window.confirm_with_message(&message).unwrap_or(false),
+        };
+        match (options.buttons, confirmed) {
+            (AlertButtons::OkCancel, true) => AlertResponse::Ok,
+            (AlertButtons::OkCancel, false) => AlertResponse::Cancel,
+            (_, true) => AlertResponse::Yes,
+            (_, false) => AlertResponse::No,
+        }
+    }
+}
+
+impl WindowState {
+    fn center_in(self: &Rc<Self>, area: Rect) {
+        let frame = self.frame.get();
+        let x = area.x0 + (area.width() - frame.width()) / 2.0;
+        let y = area.y0 + (area.height() - frame.height()) / 2.0;
+        self.set_frame(frame.with_origin((x, y)));
+    }
+
+    /// Record the restored frame before maximizing or entering fullscreen.
+    fn leave_restored(&self, window_state: window::WindowState) {
+        let old = self.window_state.replace(window_state);
+        if old == window::WindowState::Restored {
+            self.restored_frame.set(self.frame.get());
+        }
+        if old != window_state {
+            self.handler.window_state_changed(window_state);
+        }
+    }
+}
+
+// There is only one thread on the web.
+unsafe impl Send for IdleHandle {}
+
+impl IdleHandle {
+    /// Add an idle handler, which is called (once) when the message loop
+    /// is empty. The idle handler will be run from the main UI thread, and
+    /// won't be scheduled if the associated window has been dropped.
+    ///
+    /// Note: the name "idle" suggests that it will be scheduled with a lower
+    /// priority than other UI events, but that's not necessarily the case.
+    pub fn add_idle<F>(&self, callback: F)
+    where
+        F: FnOnce(&dyn Any) + Send + 'static,
+    {
+        if let Some(queue) = self.idle_queue.upgrade() {
+            let mut queue = queue.lock().expect("queue lock");
+            if queue.is_empty() {
+                let handle = self.clone();
+                let callback = Closure::once_into_js(move || handle.run_idle());
+                let _ = window().set_timeout_with_callback_and_timeout_and_arguments_0(
+                    callback.unchecked_ref(),
+                    0,
+                );
+            }
+            queue.push(Box::new(callback));
+        }
+    }
+
+    fn run_idle(&self) {
+        if let Some(state) = self.state.upgrade() {
+            let queue: Vec<_> =
+                mem::replace(&mut state.idle_queue.lock().expect("queue"), Vec::new());
+            let handler_as_any = state.handler.as_any();
+            for callback in queue {
+                callback.call(handler_as_any);
+            }
+        }
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native views hosted in a window, web implementation.
+//!
+//! The view is an element of the page, positioned over the window's canvas
+//! and clipped with `clip-path`.
+
+use std::rc::{Rc, Weak};
+
+use wasm_bindgen::JsCast;
+use web_sys::{Element, HtmlElement};
+
+use piet_common::kurbo::Rect;
+
+use super::WindowState;
+use crate::Error;
+
+/// An element of the page, such as an `<iframe>`, shown in a window.
+///
+/// Dropping hands the element back to the page, with the style it had.
+pub struct NativeView {
+    element: HtmlElement,
+    /// The `style` attribute of the element before it was hosted.
+    style: Option<String>,
+    window: Weak<WindowState>,
+}
+
+impl NativeView {
+    /// Stack `element`, hidden, over the canvas of `state`.
+    pub(super) fn new(state: &Rc<WindowState>, element: Element) -> Result<NativeView, Error> {
+        let element: HtmlElement = element
+            .dyn_into()
+            .map_err(|_| Error::Other("the view must be an HTML element"))?;
+        let style = element.get_attribute("style");
+        let css = element.style();
+        let _ = css.set_property("position", "fixed");
+        let _ = css.set_property("box-sizing", "border-box");
+        let _ = css.set_property("display", "none");
+        // Later siblings with the same z-index are stacked on top.
+        let z_index = state
+            .canvas
+            .style()
+            .get_property_value("z-index")
+            .unwrap_or_default();
+        let _ = css.set_property("z-index", &z_index);
+        if let Some(body) = super::util::window().document().and_then(|d| d.body()) {
+            let _ = body.append_child(&element);
+        }
+        Ok(NativeView {
+            element,
+            style,
+            window: Rc::downgrade(state),
+        })
+    }
+
+    /// Place the view at `frame` in the window's content area, showing only
+    /// the part inside `clip`, both in px.
+    ///
+    /// The element follows the window only when this is called again, as
+    /// druid does on each paint.
+    pub fn set_frame(&self, frame: Rect, clip: Rect) {
+        let origin = match self.window.upgrade() {
+            Some(state) => state.frame.get().origin(),
+            None => return,
+        };
+        let css = self.element.style();
+        let _ = css.set_property("left", &format!("{}px", origin.x + frame.x0));
+        let _ = css.set_property("top", &format!("{}px", origin.y + frame.y0));
+        let _ = css.set_property("width", &format!("{}px", frame.width()));
+        let _ = css.set_property("height", &format!("{}px", frame.height()));
+        let inset = format!(
+            "inset({}px {}px {}px {}px)",
+            (clip.y0 - frame.y0).max(0.0),
+            (frame.x1 - clip.x1).max(0.0),
+            (frame.y1 - clip.y1).max(0.0),
+            (clip.x0 - frame.x0).max(0.0),
+        );
+        let _ = css.set_property("clip-path", &inset);
+    }
+
+    /// Show or hide the view. Views start hidden, so that nothing appears
+    /// before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        let display = if visible { "block" } else { "none" };
+        let _ = self.element.style().set_property("display", display);
+    }
+
+    /// Give the keyboard focus to the view.
+    pub fn focus(&self) {
+        let _ = self.element.focus();
+    }
+
+    /// Give the keyboard focus back to the window, if the view has it.
+    pub fn blur(&self) {
+        let document = match super::util::window().document() {
+            Some(document) => document,
+            None => return,
+        };
+        let focused = document.active_element();
+        let inside = focused.map_or(false, |focused| self.element.contains(Some(&focused)));
+        if let (true, Some(state)) = (inside, self.window.upgrade()) {
+            let _ = state.canvas.focus();
+        }
+    }
+}
+
+impl Drop for NativeView {
+    fn drop(&mut self) {
+        let _ = match self.style {
+            Some(ref style) => self.element.set_attribute("style", style),
+            None => self.element.remove_attribute("style"),
+        };
+    }
+}
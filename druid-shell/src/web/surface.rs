@@ -0,0 +1,119 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GPU surfaces, web implementation.
+//!
+//! A surface is a canvas of its own, stacked over the window's canvas, for a
+//! WebGL or WebGPU context. Pointer events pass through to the window.
+
+use std::rc::{Rc, Weak};
+
+use wasm_bindgen::JsCast;
+use web_sys::HtmlCanvasElement;
+
+use piet_common::kurbo::Rect;
+
+use super::util::window;
+use super::{WindowState, NEXT_ID};
+use crate::window::RawWindowHandle;
+use crate::Error;
+
+/// A region of a window that is rendered with a GPU API of the caller's
+/// choice, such as WebGL or wgpu, through `raw_window_handle`.
+///
+/// The canvas is removed from the page when dropped.
+pub struct GpuSurface {
+    id: u32,
+    canvas: HtmlCanvasElement,
+    window: Weak<WindowState>,
+}
+
+impl GpuSurface {
+    /// Create a hidden surface over the canvas of `state`.
+    pub(super) fn new(state: &Rc<WindowState>) -> Result<GpuSurface, Error> {
+        let document = window().document().ok_or(Error::Null)?;
+        let canvas: HtmlCanvasElement = document
+            .create_element("canvas")
+            .map_err(|_| Error::Other("could not create a canvas"))?
+            .unchecked_into();
+        let id = NEXT_ID.with(|next| next.replace(next.get() + 1));
+        let _ = canvas.set_attribute("data-raw-handle", &id.to_string());
+        let style = canvas.style();
+        let _ = style.set_property("position", "fixed");
+        let _ = style.set_property("pointer-events", "none");
+        // Later siblings with the same z-index are stacked on top.
+        let z_index = state
+            .canvas
+            .style()
+            .get_property_value("z-index")
+            .unwrap_or_default();
+        let _ = style.set_property("z-index", &z_index);
+        let _ = style.set_property("display", "none");
+        let body = document.body().ok_or(Error::Null)?;
+        body.append_child(&canvas)
+            .map_err(|_| Error::Other("could not add the canvas to the page"))?;
+        Ok(GpuSurface {
+            id,
+            canvas,
+            window: Rc::downgrade(state),
+        })
+    }
+
+    /// Place the surface at `rect` in the window's content area, in px.
+    ///
+    /// The canvas follows the window only when this is called again, as
+    /// druid does on each paint.
+    pub fn set_frame(&self, rect: Rect) {
+        let origin = match self.window.upgrade() {
+            Some(state) => state.frame.get().origin(),
+            None => return,
+        };
+        let style = self.canvas.style();
+        let _ = style.set_property("left", &format!("{}px", origin.x + rect.x0));
+        let _ = style.set_property("top", &format!("{}px", origin.y + rect.y0));
+        let _ = style.set_property("width", &format!("{}px", rect.width()));
+        let _ = style.set_property("height", &format!("{}px", rect.height()));
+        // Resizing the backing store clears it, so leave it be if unchanged.
+        let scale = window().device_pixel_ratio();
+        let width = (rect.width() * scale).round() as u32;
+        let height = (rect.height() * scale).round() as u32;
+        if self.canvas.width() != width {
+            self.canvas.set_width(width);
+        }
+        if self.canvas.height() != height {
+            self.canvas.set_height(height);
+        }
+    }
+
+    /// Show or hide the surface. Surfaces start hidden, so that nothing
+    /// appears before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        let display = if visible { "block" } else { "none" };
+        let _ = self.canvas.style().set_property("display", display);
+    }
+
+    /// The handle of the surface's canvas, for creating a WebGL or WebGPU
+    /// context on it.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        Some(RawWindowHandle::Web { id: self.id })
+    }
+}
+
+impl Drop for GpuSurface {
+    fn drop(&mut self) {
+        if let Some(parent) = self.canvas.parent_node() {
+            let _ = parent.remove_child(&self.canvas);
+        }
+    }
+}
@@ -0,0 +1,41 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Web utilities.
+
+use std::time::Duration;
+
+pub fn init() {}
+
+pub(crate) fn window() -> web_sys::Window {
+    web_sys::window().expect("no global window")
+}
+
+/// A point in time, for measuring animation intervals.
+///
+/// `std::time::Instant` is not available on `wasm32-unknown-unknown`, so
+/// this uses the page's high resolution clock.
+#[derive(Clone, Copy, Debug)]
+pub struct Instant(f64);
+
+impl Instant {
+    pub fn now() -> Instant {
+        let now = window().performance().map(|p| p.now()).unwrap_or(0.0);
+        Instant(now)
+    }
+
+    pub fn duration_since(&self, earlier: Instant) -> Duration {
+        let millis = (self.0 - earlier.0).max(0.0);
+        Duration::from_micros((millis * 1000.0) as u64)
+    }
+}
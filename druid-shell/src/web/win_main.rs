@@ -0,0 +1,31 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//! Web implementation of the run loop.
+
+/// The browser's event loop, which drives the windows.
+pub struct RunLoop;
+
+impl RunLoop {
+    pub fn new() -> RunLoop {
+        RunLoop
+    }
+
+    /// Return to the browser, which then delivers events and animation
+    /// frames to the windows. Unlike the other platforms, this does not
+    /// block; the windows stay alive after the function calling it returns.
+    pub fn run(&mut self) {}
+}
+
+/// A page can't close itself, so this does nothing.
+pub fn request_quit() {}
@@ -15,10 +15,17 @@
 //! Platform independent window types.
 
 use std::any::Any;
+use std::ffi::c_void;
 use std::ops::Deref;
+use std::path::PathBuf;
 
+use crate::accessibility::ActionRequest;
+use crate::drag::DropEffect;
 use crate::keyboard::{KeyEvent, KeyModifiers};
+use crate::kurbo::Rect;
+use crate::piet::Color;
 use crate::platform;
+use crate::timer::TimerToken;
 
 // Handle to Window Level Utilities
 #[derive(Clone, Default)]
@@ -34,6 +41,40 @@ impl Deref for WindowHandle {
     }
 }
 
+/// The native handles of a window, for attaching an external renderer such
+/// as an OpenGL or wgpu surface, or a video sink.
+///
+/// The variants mirror those of the `raw-window-handle` crate, so that an
+/// implementation of its `HasRawWindowHandle` trait is a direct translation.
+#[derive(Debug, Clone, Copy)]
+pub enum RawWindowHandle {
+    Win32 {
+        hwnd: *mut c_void,
+        hinstance: *mut c_void,
+    },
+    AppKit {
+        ns_window: *mut c_void,
+        ns_view: *mut c_void,
+    },
+    /// An X11 window, with GTK running on X11.
+    Xlib { window: u64, display: *mut c_void },
+    /// A canvas, marked with a `data-raw-handle` attribute holding `id`.
+    Web { id: u32 },
+}
+
+/// The native display connection, mirroring `raw-window-handle`. Neither
+/// Windows nor macOS has one, so there is nothing to carry.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RawDisplayHandle {
+    Windows,
+    AppKit,
+    /// The X11 display; null when GTK is not running on X11.
+    Xlib {
+        display: *mut c_void,
+    },
+    Web,
+}
+
 /// App behavior, supplied by the app.
 ///
 /// Many of the "window procedure" messages map to calls to this trait.
@@ -50,6 +91,46 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn size(&self, width: u32, height: u32) {}
 
+    /// Called when the window is maximized, minimized, restored, or enters
+    /// or leaves fullscreen.
+    #[allow(unused_variables)]
+    fn window_state_changed(&self, state: WindowState) {}
+
+    /// Called when the user switches between the light and dark system
+    /// theme.
+    #[allow(unused_variables)]
+    fn theme_changed(&self, theme: Theme) {}
+
+    /// Called when the user changes the accent color in the system settings.
+    /// See `Application::accent_color`.
+    #[allow(unused_variables)]
+    fn accent_color_changed(&self, color: Option<Color>) {}
+
+    /// Called when the user turns the setting to reduce transparency on or
+    /// off. See `Application::reduce_transparency`.
+    #[allow(unused_variables)]
+    fn reduce_transparency_changed(&self, reduce: bool) {}
+
+    /// Called when the device switches between battery and external power,
+    /// so that background work can be put off while on battery.
+    #[allow(unused_variables)]
+    fn power_source_changed(&self, source: PowerSource) {}
+
+    /// Called when the user changes the size of text in the accessibility
+    /// settings. See `Application::text_scale`.
+    #[allow(unused_variables)]
+    fn text_scale_changed(&self, scale: f64) {}
+
+    /// Called at a display refresh after `WindowHandle::request_frame`,
+    /// just before the window is painted, to advance animations. The
+    /// refresh is timed by the compositor where the platform has one.
+    fn frame(&self) {}
+
+    /// Called when a timer requested with `WindowHandle::request_timer`
+    /// fires.
+    #[allow(unused_variables)]
+    fn timer(&self, token: TimerToken) {}
+
     /// Request the handler to paint the window contents. Return value
     /// indicates whether window is animating, i.e. whether another paint
     /// should be scheduled for the next animation frame.
@@ -59,7 +140,8 @@ pub trait WinHandler {
     fn rebuild_resources(&self) {}
 
     #[allow(unused_variables)]
-    /// Called when a menu item is selected.
+    /// Called when a menu item is selected, or when a global hotkey
+    /// registered with `register_hotkey` is pressed.
     fn command(&self, id: u32) {}
 
     /// Called on a key down event.
@@ -75,21 +157,21 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn key_up(&self, event: KeyEvent) {}
 
-    /// Called on a mouse wheel event. This corresponds to a
-    /// [WM_MOUSEWHEEL](https://msdn.microsoft.com/en-us/library/windows/desktop/ms645617(v=vs.85).aspx)
-    /// message.
+    /// Called while the user composes text with an input method, as when
+    /// typing Chinese or Japanese. The keys pressed meanwhile are not sent
+    /// to `key_down`.
     ///
-    /// The modifiers are the same as WM_MOUSEWHEEL.
+    /// The handler should keep the caret position reported with
+    /// `WindowHandle::set_caret_rect` up to date, so that the candidate
+    /// window appears next to the composition.
     #[allow(unused_variables)]
-    fn mouse_wheel(&self, delta: i32, mods: KeyModifiers) {}
+    fn composition(&self, event: &CompositionEvent) {}
 
-    /// Called on a mouse horizontal wheel event. This corresponds to a
-    /// [WM_MOUSEHWHEEL](https://msdn.microsoft.com/en-us/library/windows/desktop/ms645614(v=vs.85).aspx)
-    /// message.
-    ///
-    /// The modifiers are the same as WM_MOUSEHWHEEL.
+    /// Called on a mouse wheel or trackpad scroll event. This corresponds
+    /// to the WM_MOUSEWHEEL and WM_MOUSEHWHEEL messages on Windows, or
+    /// scrollWheel(with:) on macOS.
     #[allow(unused_variables)]
-    fn mouse_hwheel(&self, delta: i32, mods: KeyModifiers) {}
+    fn wheel(&self, event: &WheelEvent) {}
 
     /// Called when the mouse moves. Note that the x, y coordinates are
     /// in absolute pixels.
@@ -103,6 +185,90 @@ pub trait WinHandler {
     #[allow(unused_variables)]
     fn mouse(&self, event: &MouseEvent) {}
 
+    /// Called when a finger touches, moves on, or leaves a touch screen.
+    /// Note that the x, y coordinates are in absolute pixels.
+    ///
+    /// The system may additionally deliver touches as mouse events.
+    #[allow(unused_variables)]
+    fn touch(&self, event: &TouchEvent) {}
+
+    /// Called on a pinch or rotation gesture on a trackpad. Note that the
+    /// x, y coordinates are in absolute pixels.
+    #[allow(unused_variables)]
+    fn trackpad_gesture(&self, event: &TrackpadGesture) {}
+
+    /// Called on pen or stylus input from a tablet or touch screen. Note that
+    /// the x, y coordinates are in absolute pixels.
+    ///
+    /// Return `true` if the event is handled. Unhandled pen input is
+    /// delivered as mouse events instead.
+    #[allow(unused_variables)]
+    fn pen(&self, event: &PenEvent) -> bool {
+        false
+    }
+
+    /// Called when the window loses a mouse capture started with
+    /// `capture_mouse`, other than by calling `release_mouse`; for example
+    /// when another window is activated during a drag.
+    fn mouse_capture_lost(&self) {}
+
+    /// Called when the tray icon of the window is clicked. Choosing an item
+    /// of its menu calls `command` instead.
+    fn tray_icon_clicked(&self) {}
+
+    /// Called when files dragged from another application enter the window.
+    /// Note that the x, y coordinates are in absolute pixels.
+    ///
+    /// Return `true` if the files would be accepted at this position.
+    #[allow(unused_variables)]
+    fn file_drag_enter(&self, paths: &[PathBuf], x: i32, y: i32) -> bool {
+        false
+    }
+
+    /// Called when files dragged from another application move within the
+    /// window.
+    ///
+    /// Return `true` if the files would be accepted at this position.
+    #[allow(unused_variables)]
+    fn file_drag_over(&self, x: i32, y: i32) -> bool {
+        false
+    }
+
+    /// Called when dragged files leave the window without being dropped.
+    fn file_drag_leave(&self) {}
+
+    /// Called when files from another application are dropped on the window.
+    ///
+    /// Return `true` if the files were accepted.
+    #[allow(unused_variables)]
+    fn file_drop(&self, paths: Vec<PathBuf>, x: i32, y: i32) -> bool {
+        false
+    }
+
+    /// Called when a drag started with `WindowHandle::start_drag` ends,
+    /// with what the drop target did with the data.
+    #[allow(unused_variables)]
+    fn drag_ended(&self, effect: DropEffect) {}
+
+    /// Called when assistive technology, such as a screen reader, asks a
+    /// node of the tree given to `WindowHandle::update_accessibility` to
+    /// perform an action.
+    ///
+    /// Return `true` if the action was performed.
+    #[allow(unused_variables)]
+    fn accessibility_action(&self, request: ActionRequest) -> bool {
+        false
+    }
+
+    /// Called when the user asks to close the window, for example with its
+    /// close button. Return `false` to keep the window open, for instance to
+    /// ask about unsaved changes first.
+    ///
+    /// This is not called for `WindowHandle::close`.
+    fn request_close(&self) -> bool {
+        true
+    }
+
     /// Called when the window is being destroyed. Note that this happens
     /// earlier in the sequence than drop (at WM_DESTROY, while the latter is
     /// WM_NCDESTROY).
@@ -129,6 +295,117 @@ pub struct MouseEvent {
     pub button: MouseButton,
 }
 
+/// A touch screen event.
+#[derive(Debug, Clone)]
+pub struct TouchEvent {
+    /// Identifies the touch, from `Down` until the touch ends.
+    pub id: u64,
+    /// X coordinate in absolute pixels.
+    pub x: i32,
+    /// Y coordinate in absolute pixels.
+    pub y: i32,
+    pub phase: TouchPhase,
+}
+
+/// The stage of a touch.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TouchPhase {
+    /// The finger touched the screen.
+    Down,
+    /// The finger moved.
+    Move,
+    /// The finger was lifted. This ends the touch.
+    Up,
+    /// The system took over the touch, for example for a gesture. This ends
+    /// the touch.
+    Cancel,
+}
+
+/// A pinch or rotation gesture performed on a trackpad.
+///
+/// Pinching and rotating are reported separately, and may overlap.
+#[derive(Debug, Clone)]
+pub struct TrackpadGesture {
+    /// X coordinate of the pointer in absolute pixels.
+    pub x: i32,
+    /// Y coordinate of the pointer in absolute pixels.
+    pub y: i32,
+    pub kind: TrackpadGestureKind,
+    pub phase: GesturePhase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TrackpadGestureKind {
+    /// The change in scale since the last event; the new scale is the old
+    /// scale multiplied by one plus this value.
+    Magnify(f64),
+    /// The change in angle since the last event, in radians; positive is
+    /// clockwise on screen.
+    Rotate(f64),
+}
+
+/// The stage of a gesture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GesturePhase {
+    Began,
+    Changed,
+    Ended,
+}
+
+/// A pen or stylus event.
+#[derive(Debug, Clone)]
+pub struct PenEvent {
+    /// X coordinate in absolute pixels.
+    pub x: i32,
+    /// Y coordinate in absolute pixels.
+    pub y: i32,
+    pub phase: PenPhase,
+    /// The pressure of the tip, from 0 to 1. This is 0.5 while in contact
+    /// for pens that can't sense pressure.
+    pub pressure: f64,
+    /// The angle between the pen and the screen normal on the x axis, in
+    /// degrees from -90 to 90; positive towards the right.
+    pub tilt_x: f64,
+    /// The angle between the pen and the screen normal on the y axis, in
+    /// degrees from -90 to 90; positive towards the user.
+    pub tilt_y: f64,
+    /// Whether a barrel button is pressed.
+    pub barrel: bool,
+    /// Whether the eraser end of the pen is being used.
+    pub eraser: bool,
+    pub mods: KeyModifiers,
+}
+
+/// The stage of pen input.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PenPhase {
+    /// The pen touched the surface.
+    Down,
+    /// The pen moved while touching the surface.
+    Move,
+    /// The pen was lifted.
+    Up,
+    /// The pen moved above the surface, within range of the digitizer.
+    Hover,
+}
+
+/// A stage of composing text with an input method.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CompositionEvent {
+    /// A composition started.
+    Start,
+    /// The text being composed changed. It should be shown at the caret, but
+    /// is not part of the document yet.
+    Update {
+        text: String,
+        /// The position of the cursor within the text, in bytes.
+        cursor: usize,
+    },
+    /// The composition ended, and the text is to be inserted at the caret.
+    /// The text is empty when the composition was cancelled.
+    Commit(String),
+}
+
 /// An indicator of which mouse button was pressed.
 #[derive(PartialEq, Eq, Clone, Copy, Debug)]
 pub enum MouseButton {
@@ -144,19 +421,270 @@ pub enum MouseButton {
     X2,
 }
 
-/// Standard cursor types. This is only a subset, others can be added as needed.
+/// The role a window plays relative to the other windows of the application.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowKind {
+    /// A regular top-level window.
+    Normal,
+    /// A utility window such as a tool palette or inspector.
+    ///
+    /// A palette is given an owner window. It floats above its owner, does
+    /// not take activation away from it when clicked, and is hidden and shown
+    /// along with it.
+    Palette,
+}
+
+impl Default for WindowKind {
+    fn default() -> WindowKind {
+        WindowKind::Normal
+    }
+}
+
+/// How a window is stacked relative to the windows of other applications.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowLevel {
+    Normal,
+    /// Above all normal windows, even when the application is inactive.
+    Floating,
+    /// Above floating windows, and not activated when shown; for tooltips
+    /// and similar transient popups.
+    Tooltip,
+}
+
+impl Default for WindowLevel {
+    fn default() -> WindowLevel {
+        WindowLevel::Normal
+    }
+}
+
+/// A translucent material the platform draws behind the content of a
+/// window. It shows through wherever the window paints transparent pixels.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowBackdrop {
+    /// An opaque window.
+    None,
+    /// A blur of what is behind the window: acrylic on Windows, the vibrant
+    /// under-window material on macOS.
+    Blur,
+    /// The material of sidebars and window backgrounds: Mica on Windows 11,
+    /// the vibrant sidebar material on macOS.
+    Sidebar,
+}
+
+impl Default for WindowBackdrop {
+    fn default() -> WindowBackdrop {
+        WindowBackdrop::None
+    }
+}
+
+/// Whether a window is maximized, minimized or fullscreen.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WindowState {
+    /// Neither maximized, minimized nor fullscreen.
+    Restored,
+    Maximized,
+    Minimized,
+    /// Covering the whole screen, without decorations.
+    Fullscreen,
+}
+
+/// The light or dark appearance chosen in the system settings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Theme {
+    Light,
+    Dark,
+}
+
+impl Default for Theme {
+    fn default() -> Theme {
+        Theme::Light
+    }
+}
+
+/// Whether the device runs on battery or on external power.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PowerSource {
+    /// Plugged in, or a device without a battery.
+    External,
+    Battery,
+}
+
+impl Default for PowerSource {
+    fn default() -> PowerSource {
+        PowerSource::External
+    }
+}
+
+/// Mouse cursors.
+#[derive(Clone)]
 pub enum Cursor {
     Arrow,
     IBeam,
+    Crosshair,
+    /// An open hand, showing that the content can be grabbed.
+    OpenHand,
+    /// A closed hand, while the content is grabbed.
+    ClosedHand,
+    NotAllowed,
+    ResizeLeftRight,
+    ResizeUpDown,
+    /// A cursor created with `WindowHandle::make_cursor`.
+    Custom(platform::CustomCursor),
 }
 
-/// A scroll wheel event.
-#[derive(Debug)]
-pub struct ScrollEvent {
-    /// The scroll wheel’s horizontal delta.
+/// The image and hotspot of a custom cursor.
+#[derive(Debug, Clone)]
+pub struct CursorDesc {
+    /// Pixels in RGBA order with 8 bits per channel, row by row.
+    pub image: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+    /// The point of the image, in pixels, that is at the pointer position.
+    pub hot: (usize, usize),
+}
+
+impl CursorDesc {
+    pub fn new(image: Vec<u8>, width: usize, height: usize, hot: (usize, usize)) -> CursorDesc {
+        assert_eq!(image.len(), width * height * 4, "image must be RGBA");
+        CursorDesc {
+            image,
+            width,
+            height,
+            hot,
+        }
+    }
+}
+
+/// The frame of a window and whether it is maximized, for saving and
+/// restoring the window layout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WindowGeometry {
+    /// The frame of the window when it is not maximized, in px, in screen
+    /// coordinates.
+    pub frame: Rect,
+    pub maximized: bool,
+}
+
+/// Shrink and move `frame` so that it lies within `area`.
+pub(crate) fn clamp_frame(frame: Rect, area: Rect) -> Rect {
+    let width = frame.width().min(area.width());
+    let height = frame.height().min(area.height());
+    let x = frame.x0.max(area.x0).min(area.x1 - width);
+    let y = frame.y0.max(area.y0).min(area.y1 - height);
+    Rect::new(x, y, x + width, y + height)
+}
+
+/// An image for an icon, such as a tray icon.
+///
+/// Where a list of images is taken, as for the window icon, they are
+/// different resolutions of the same icon, and the platform picks the best
+/// fit for each size it shows.
+#[derive(Debug, Clone)]
+pub struct IconDesc {
+    /// Pixels in RGBA order with 8 bits per channel, row by row.
+    pub image: Vec<u8>,
+    pub width: usize,
+    pub height: usize,
+}
+
+impl IconDesc {
+    pub fn new(image: Vec<u8>, width: usize, height: usize) -> IconDesc {
+        assert_eq!(image.len(), width * height * 4, "image must be RGBA");
+        IconDesc {
+            image,
+            width,
+            height,
+        }
+    }
+
+    /// From a list of resolutions of an icon, the one to scale to `size`
+    /// pixels: the smallest at least that large, or else the largest.
+    pub fn best_fit(icons: &[IconDesc], size: usize) -> Option<&IconDesc> {
+        let size_of = |icon: &IconDesc| icon.width.max(icon.height);
+        icons
+            .iter()
+            .filter(|icon| size_of(icon) >= size)
+            .min_by_key(|icon| size_of(icon))
+            .or_else(|| icons.iter().max_by_key(|icon| size_of(icon)))
+    }
+}
+
+/// A mouse wheel or trackpad scroll event.
+///
+/// Positive deltas scroll the content towards its end: down for `dy`,
+/// right for `dx`.
+#[derive(Debug, Clone)]
+pub struct WheelEvent {
+    /// The horizontal delta, in `unit`s.
     pub dx: f64,
-    /// The scroll wheel’s vertical delta.
+    /// The vertical delta, in `unit`s.
     pub dy: f64,
+    /// The unit of the deltas.
+    pub unit: WheelUnit,
     /// Modifiers, as in raw WM message
     pub mods: KeyModifiers,
+    /// The stage of the scroll gesture, for devices that report it.
+    pub phase: WheelPhase,
+}
+
+/// This type is being renamed.
+#[deprecated(note = "please use `WheelEvent`.")]
+pub type ScrollEvent = WheelEvent;
+
+/// The unit of the deltas in a `WheelEvent`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WheelUnit {
+    /// Precise deltas, in px, as from a trackpad.
+    Pixels,
+    /// Lines of text, as from a notched mouse wheel. The number of lines per
+    /// notch follows the system settings.
+    Lines,
+    /// Whole pages, when the system is set to scroll a page per notch.
+    Pages,
+}
+
+/// The stage of a scroll gesture.
+///
+/// Trackpads on macOS report when the fingers touch and leave the pad, and
+/// then continue with momentum events while the content keeps moving. Other
+/// devices report `None`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum WheelPhase {
+    /// The event is not part of a gesture.
+    None,
+    /// The fingers touched the trackpad and started scrolling.
+    Began,
+    /// The fingers moved.
+    Changed,
+    /// The fingers left the trackpad.
+    Ended,
+    /// The gesture was cancelled by the system.
+    Cancelled,
+    /// Inertial scrolling started after the fingers were lifted.
+    MomentumBegan,
+    /// Inertial scrolling continued.
+    Momentum,
+    /// Inertial scrolling came to an end.
+    MomentumEnded,
+}
+
+impl WheelEvent {
+    /// The deltas converted to px, given the height of a line and of a page.
+    pub fn to_pixels(&self, line_height: f64, page_height: f64) -> (f64, f64) {
+        let scale = match self.unit {
+            WheelUnit::Pixels => 1.0,
+            WheelUnit::Lines => line_height,
+            WheelUnit::Pages => page_height,
+        };
+        (self.dx * scale, self.dy * scale)
+    }
+
+    /// Whether this event is part of inertial scrolling, rather than
+    /// direct user input.
+    pub fn is_momentum(&self) -> bool {
+        match self.phase {
+            WheelPhase::MomentumBegan | WheelPhase::Momentum | WheelPhase::MomentumEnded => true,
+            _ => false,
+        }
+    }
 }
@@ -0,0 +1,1239 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! UI Automation, Windows implementation.
+//!
+//! Each node but the root has a fragment provider, and the window answers
+//! `WM_GETOBJECT` with the provider of the root, the fragment root. The
+//! providers answer from the last tree, and actions are sent to the window
+//! procedure as `XI_ACCESS_ACTION` messages, so that the handler is only
+//! ever called from the window procedure.
+//!
+//! The providers use COM threading, so UI Automation calls them on the
+//! thread of the window, except to add and release references.
+
+#![allow(non_upper_case_globals)]
+
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::mem;
+use std::ptr::{self, null_mut};
+use std::sync::atomic::{self, AtomicU32, Ordering};
+
+use winapi::ctypes::{c_int, c_void};
+use winapi::shared::guiddef::{IsEqualIID, REFIID};
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::shared::winerror::*;
+use winapi::shared::wtypes::{BSTR, VARIANT_TRUE, VARTYPE, VT_BOOL, VT_BSTR, VT_I4, VT_R8};
+use winapi::um::oaidl::{SAFEARRAY, VARIANT};
+use winapi::um::oleauto::{
+    SafeArrayAccessData, SafeArrayCreateVector, SafeArrayUnaccessData, SysAllocString,
+    SysFreeString, VariantClear,
+};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winnt::{HRESULT, LPCWSTR, LPWSTR};
+use winapi::um::winuser::{
+    ClientToScreen, GetWindowLongPtrW, ScreenToClient, SendMessageW, GWLP_USERDATA,
+};
+use winapi::Interface;
+
+use piet_common::kurbo::Point;
+
+use super::util::{FromWide, ToWide, OPTIONAL_FUNCTIONS};
+use super::{WindowState, XI_ACCESS_ACTION};
+use crate::accessibility::{
+    AccessNode, AccessTree, Action, ActionRequest, NodeId, Politeness, Role,
+};
+
+RIDL! {#[uuid(0xd6dd_68d1, 0x86fd, 0x4332, 0x86, 0x66, 0x9a, 0xbe, 0xde, 0xa2, 0xd2, 0x4c)]
+interface IRawElementProviderSimple(IRawElementProviderSimpleVtbl): IUnknown(IUnknownVtbl) {
+    fn get_ProviderOptions(
+        pRetVal: *mut c_int,
+    ) -> HRESULT,
+    fn GetPatternProvider(
+        patternId: c_int,
+        pRetVal: *mut *mut IUnknown,
+    ) -> HRESULT,
+    fn GetPropertyValue(
+        propertyId: c_int,
+        pRetVal: *mut VARIANT,
+    ) -> HRESULT,
+    fn get_HostRawElementProvider(
+        pRetVal: *mut *mut IRawElementProviderSimple,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xf706_3da8, 0x8359, 0x439c, 0x92, 0x97, 0xbb, 0xc5, 0x29, 0x9a, 0x7d, 0x87)]
+interface IRawElementProviderFragment(IRawElementProviderFragmentVtbl):
+    IUnknown(IUnknownVtbl) {
+    fn Navigate(
+        direction: c_int,
+        pRetVal: *mut *mut IRawElementProviderFragment,
+    ) -> HRESULT,
+    fn GetRuntimeId(
+        pRetVal: *mut *mut SAFEARRAY,
+    ) -> HRESULT,
+    fn get_BoundingRectangle(
+        pRetVal: *mut UiaRect,
+    ) -> HRESULT,
+    fn GetEmbeddedFragmentRoots(
+        pRetVal: *mut *mut SAFEARRAY,
+    ) -> HRESULT,
+    fn SetFocus() -> HRESULT,
+    fn get_FragmentRoot(
+        pRetVal: *mut *mut IRawElementProviderFragmentRoot,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0x620c_e2a5, 0xab8f, 0x40a9, 0x86, 0xcb, 0xde, 0x3c, 0x75, 0x59, 0x9b, 0x58)]
+interface IRawElementProviderFragmentRoot(IRawElementProviderFragmentRootVtbl):
+    IUnknown(IUnknownVtbl) {
+    fn ElementProviderFromPoint(
+        x: f64,
+        y: f64,
+        pRetVal: *mut *mut IRawElementProviderFragment,
+    ) -> HRESULT,
+    fn GetFocus(
+        pRetVal: *mut *mut IRawElementProviderFragment,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0x54fc_b24b, 0xe18e, 0x47a2, 0xb4, 0xd3, 0xec, 0xcb, 0xe7, 0x75, 0x99, 0xa2)]
+interface IInvokeProvider(IInvokeProviderVtbl): IUnknown(IUnknownVtbl) {
+    fn Invoke() -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xc793_5180, 0x6fb3, 0x4201, 0xb1, 0x74, 0x7d, 0xf7, 0x3a, 0xdb, 0xf6, 0x4a)]
+interface IValueProvider(IValueProviderVtbl): IUnknown(IUnknownVtbl) {
+    fn SetValue(
+        val: LPCWSTR,
+    ) -> HRESULT,
+    fn get_Value(
+        pRetVal: *mut BSTR,
+    ) -> HRESULT,
+    fn get_IsReadOnly(
+        pRetVal: *mut BOOL,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0x36dc_7aef, 0x33e6, 0x4691, 0xaf, 0xe1, 0x2b, 0xe7, 0x27, 0x4b, 0x3d, 0x33)]
+interface IRangeValueProvider(IRangeValueProviderVtbl): IUnknown(IUnknownVtbl) {
+    fn SetValue(
+        val: f64,
+    ) -> HRESULT,
+    fn get_Value(
+        pRetVal: *mut f64,
+    ) -> HRESULT,
+    fn get_IsReadOnly(
+        pRetVal: *mut BOOL,
+    ) -> HRESULT,
+    fn get_Maximum(
+        pRetVal: *mut f64,
+    ) -> HRESULT,
+    fn get_Minimum(
+        pRetVal: *mut f64,
+    ) -> HRESULT,
+    fn get_LargeChange(
+        pRetVal: *mut f64,
+    ) -> HRESULT,
+    fn get_SmallChange(
+        pRetVal: *mut f64,
+    ) -> HRESULT,
+}}
+
+/// A rectangle in screen coordinates, in physical pixels.
+#[repr(C)]
+#[derive(Default)]
+pub struct UiaRect {
+    left: f64,
+    top: f64,
+    width: f64,
+    height: f64,
+}
+
+/// The object id of `WM_GETOBJECT` asking for a UI Automation provider.
+const UiaRootObjectId: c_int = -25;
+/// The first part of the runtime id of an element of a fragment.
+const UiaAppendRuntimeId: c_int = 3;
+
+const UIA_E_ELEMENTNOTAVAILABLE: HRESULT = 0x8004_0201u32 as HRESULT;
+const UIA_E_INVALIDOPERATION: HRESULT = 0x8013_1509u32 as HRESULT;
+
+// Values of `ProviderOptions`.
+const ProviderOptions_ServerSideProvider: c_int = 0x1;
+const ProviderOptions_UseComThreading: c_int = 0x20;
+
+// Values of `NavigateDirection`.
+const NavigateDirection_Parent: c_int = 0;
+const NavigateDirection_NextSibling: c_int = 1;
+const NavigateDirection_PreviousSibling: c_int = 2;
+const NavigateDirection_FirstChild: c_int = 3;
+const NavigateDirection_LastChild: c_int = 4;
+
+const StructureChangeType_ChildrenInvalidated: c_int = 2;
+
+// Values of `NotificationKind` and `NotificationProcessing`.
+const NotificationKind_Other: c_int = 4;
+const NotificationProcessing_ImportantMostRecent: c_int = 1;
+const NotificationProcessing_All: c_int = 2;
+
+const UIA_InvokePatternId: c_int = 10000;
+const UIA_ValuePatternId: c_int = 10002;
+const UIA_RangeValuePatternId: c_int = 10003;
+
+const UIA_AutomationFocusChangedEventId: c_int = 20005;
+
+const UIA_ControlTypePropertyId: c_int = 30003;
+const UIA_NamePropertyId: c_int = 30005;
+const UIA_HasKeyboardFocusPropertyId: c_int = 30008;
+const UIA_IsKeyboardFocusablePropertyId: c_int = 30009;
+const UIA_IsEnabledPropertyId: c_int = 30010;
+const UIA_IsPasswordPropertyId: c_int = 30019;
+const UIA_ValueValuePropertyId: c_int = 30045;
+const UIA_RangeValueValuePropertyId: c_int = 30047;
+
+const UIA_ButtonControlTypeId: c_int = 50000;
+const UIA_CheckBoxControlTypeId: c_int = 50002;
+const UIA_EditControlTypeId: c_int = 50004;
+const UIA_ImageControlTypeId: c_int = 50006;
+const UIA_ListItemControlTypeId: c_int = 50007;
+const UIA_ListControlTypeId: c_int = 50008;
+const UIA_MenuControlTypeId: c_int = 50009;
+const UIA_MenuItemControlTypeId: c_int = 50011;
+const UIA_ProgressBarControlTypeId: c_int = 50012;
+const UIA_SliderControlTypeId: c_int = 50015;
+const UIA_TextControlTypeId: c_int = 50020;
+const UIA_GroupControlTypeId: c_int = 50026;
+const UIA_WindowControlTypeId: c_int = 50032;
+
+/// Identifies our announcements to screen readers, so that an assertive
+/// one supersedes the one before.
+const ACTIVITY_ID: &str = "druid-announcement";
+
+// The interfaces of an `Element`, by the index of their vtable.
+const SIMPLE: usize = 0;
+const FRAGMENT: usize = 1;
+const FRAGMENT_ROOT: usize = 2;
+const INVOKE: usize = 3;
+const VALUE: usize = 4;
+const RANGE_VALUE: usize = 5;
+
+/// The payload of `XI_ACCESS_ACTION`, passed by pointer in the lparam.
+pub(crate) struct ActionMessage {
+    pub(crate) request: ActionRequest,
+    /// Set by the window procedure, to whether the handler performed it.
+    pub(crate) performed: bool,
+}
+
+/// The provider of a node, or of the window for the root.
+#[repr(C)]
+struct Element {
+    /// The vtable of each interface, in the order of `SIMPLE` and the
+    /// others; a pointer to an interface points at its vtable here.
+    vtbls: [*const c_void; 6],
+    /// Atomic, because UI Automation may add and release references from
+    /// its own threads.
+    refcount: AtomicU32,
+    /// The window, or null once the element is detached from it.
+    hwnd: Cell<HWND>,
+    /// The node, or `None` for the root.
+    node: Option<NodeId>,
+}
+
+/// A counted reference to an `Element`.
+struct ElementRef(*mut Element);
+
+/// The tree last given to `update`, and the element of each of its nodes.
+#[derive(Default)]
+pub(crate) struct AccessState {
+    tree: Option<AccessTree>,
+    /// The element of the window, made when first asked for.
+    root: Option<ElementRef>,
+    elements: HashMap<NodeId, ElementRef>,
+    /// The parent of each node but the root.
+    parents: HashMap<NodeId, NodeId>,
+}
+
+/// Something to tell UI Automation after an update.
+enum Event {
+    Focus,
+    /// The children of the element changed.
+    Structure,
+    /// A property changed, from the first value to the second.
+    Property(c_int, VARIANT, VARIANT),
+}
+
+/// The `IUnknown` methods of the interface whose vtable is at `$slot`.
+macro_rules! unknown_vtbl {
+    ($slot:expr) => {{
+        unsafe extern "system" fn query_interface(
+            this: *mut IUnknown,
+            riid: REFIID,
+            ppv: *mut *mut c_void,
+        ) -> HRESULT {
+            element_query_interface(element(this, $slot), riid, ppv)
+        }
+        unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+            element_add_ref(element(this, $slot))
+        }
+        unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+            element_release(element(this, $slot))
+        }
+        IUnknownVtbl {
+            QueryInterface: query_interface,
+            AddRef: add_ref,
+            Release: release,
+        }
+    }};
+}
+
+static SIMPLE_VTBL: IRawElementProviderSimpleVtbl = IRawElementProviderSimpleVtbl {
+    parent: unknown_vtbl!(SIMPLE),
+    get_ProviderOptions: get_provider_options,
+    GetPatternProvider: get_pattern_provider,
+    GetPropertyValue: get_property_value,
+    get_HostRawElementProvider: get_host_raw_element_provider,
+};
+
+static FRAGMENT_VTBL: IRawElementProviderFragmentVtbl = IRawElementProviderFragmentVtbl {
+    parent: unknown_vtbl!(FRAGMENT),
+    Navigate: navigate,
+    GetRuntimeId: get_runtime_id,
+    get_BoundingRectangle: get_bounding_rectangle,
+    GetEmbeddedFragmentRoots: get_embedded_fragment_roots,
+    SetFocus: set_focus,
+    get_FragmentRoot: get_fragment_root,
+};
+
+static FRAGMENT_ROOT_VTBL: IRawElementProviderFragmentRootVtbl =
+    IRawElementProviderFragmentRootVtbl {
+        parent: unknown_vtbl!(FRAGMENT_ROOT),
+        ElementProviderFromPoint: element_provider_from_point,
+        GetFocus: get_focus,
+    };
+
+static INVOKE_VTBL: IInvokeProviderVtbl = IInvokeProviderVtbl {
+    parent: unknown_vtbl!(INVOKE),
+    Invoke: invoke,
+};
+
+static VALUE_VTBL: IValueProviderVtbl = IValueProviderVtbl {
+    parent: unknown_vtbl!(VALUE),
+    SetValue: value_set_value,
+    get_Value: value_get_value,
+    get_IsReadOnly: value_get_is_read_only,
+};
+
+static RANGE_VALUE_VTBL: IRangeValueProviderVtbl = IRangeValueProviderVtbl {
+    parent: unknown_vtbl!(RANGE_VALUE),
+    SetValue: range_set_value,
+    get_Value: range_get_value,
+    get_IsReadOnly: range_get_is_read_only,
+    get_Maximum: range_get_maximum,
+    get_Minimum: range_get_minimum,
+    get_LargeChange: range_get_large_change,
+    get_SmallChange: range_get_small_change,
+};
+
+impl ElementRef {
+    fn new(hwnd: HWND, node: Option<NodeId>) -> ElementRef {
+        let vtbls = [
+            &SIMPLE_VTBL as *const _ as *const c_void,
+            &FRAGMENT_VTBL as *const _ as *const c_void,
+            &FRAGMENT_ROOT_VTBL as *const _ as *const c_void,
+            &INVOKE_VTBL as *const _ as *const c_void,
+            &VALUE_VTBL as *const _ as *const c_void,
+            &RANGE_VALUE_VTBL as *const _ as *const c_void,
+        ];
+        ElementRef(Box::into_raw(Box::new(Element {
+            vtbls,
+            refcount: AtomicU32::new(1),
+            hwnd: Cell::new(hwnd),
+            node,
+        })))
+    }
+
+    fn element(&self) -> &Element {
+        unsafe { &*self.0 }
+    }
+
+    /// The interface at `slot`, without a reference of its own.
+    fn interface<T>(&self, slot: usize) -> *mut T {
+        interface(self.0, slot)
+    }
+
+    /// The interface at `slot`, with a reference for the caller to release.
+    fn into_interface<T>(self, slot: usize) -> *mut T {
+        let interface = self.interface(slot);
+        mem::forget(self);
+        interface
+    }
+
+    /// Cut the element off from the window, after which it answers nothing.
+    fn detach(&self) {
+        self.element().hwnd.set(null_mut());
+    }
+}
+
+impl Clone for ElementRef {
+    fn clone(&self) -> ElementRef {
+        unsafe { element_add_ref(self.0) };
+        ElementRef(self.0)
+    }
+}
+
+impl Drop for ElementRef {
+    fn drop(&mut self) {
+        unsafe { element_release(self.0) };
+    }
+}
+
+impl AccessState {
+    /// The element of the window, made if there isn't one yet.
+    fn root(&mut self, hwnd: HWND) -> ElementRef {
+        self.root
+            .get_or_insert_with(|| ElementRef::new(hwnd, None))
+            .clone()
+    }
+
+    /// The node of an element, the root for the element of the window.
+    fn node(&self, element: &Element) -> Option<&AccessNode> {
+        let tree = self.tree.as_ref()?;
+        tree.node(element.node.unwrap_or(tree.root))
+    }
+
+    /// The element of a node, that of the window for the root.
+    fn element(&self, node_id: NodeId) -> Option<&ElementRef> {
+        match self.tree {
+            Some(ref tree) if tree.root == node_id => self.root.as_ref(),
+            _ => self.elements.get(&node_id),
+        }
+    }
+
+    /// The children of a node that have elements.
+    fn children(&self, node_id: NodeId) -> Vec<NodeId> {
+        let tree = match self.tree {
+            Some(ref tree) => tree,
+            None => return Vec::new(),
+        };
+        tree.node(node_id)
+            .map(|node| node.children.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .filter(|child| self.elements.contains_key(child))
+            .cloned()
+            .collect()
+    }
+
+    fn detach(&mut self) {
+        for element in self.elements.values().chain(&self.root) {
+            element.detach();
+        }
+        self.elements.clear();
+        self.parents.clear();
+        self.tree = None;
+    }
+}
+
+impl Drop for AccessState {
+    fn drop(&mut self) {
+        // UI Automation may keep elements past the window.
+        self.detach();
+    }
+}
+
+/// Answer `WM_GETOBJECT` with the provider of the window, when UI
+/// Automation asks for it.
+pub(crate) unsafe fn get_object(
+    window: &WindowState,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> Option<LRESULT> {
+    if lparam as DWORD as c_int != UiaRootObjectId {
+        return None;
+    }
+    let return_provider = OPTIONAL_FUNCTIONS.UiaReturnRawElementProvider?;
+    let hwnd = window.hwnd.get();
+    let root = window.access.try_borrow_mut().ok()?.root(hwnd);
+    Some(return_provider(
+        hwnd,
+        wparam,
+        lparam,
+        root.interface(SIMPLE),
+    ))
+}
+
+/// Cut the elements off from the window being destroyed, and tell UI
+/// Automation to let go of them.
+pub(crate) unsafe fn disconnect(window: &WindowState) {
+    window.access.borrow_mut().detach();
+    if let Some(return_provider) = OPTIONAL_FUNCTIONS.UiaReturnRawElementProvider {
+        return_provider(window.hwnd.get(), 0, 0, null_mut());
+    }
+}
+
+/// Replace the tree, creating and detaching the elements of its nodes, and
+/// tell UI Automation what changed.
+pub(crate) unsafe fn update(window: &WindowState, tree: AccessTree) {
+    let hwnd = window.hwnd.get();
+    let listening = match OPTIONAL_FUNCTIONS.UiaClientsAreListening {
+        Some(clients_are_listening) => clients_are_listening() != FALSE,
+        None => false,
+    };
+    let mut events = Vec::new();
+    {
+        let mut state = window.access.borrow_mut();
+        let state = &mut *state;
+        let old_tree = state.tree.take().unwrap_or_default();
+        let root = state.root(hwnd);
+        state.elements.retain(|node_id, element| {
+            let keep = *node_id != tree.root && tree.node(*node_id).is_some();
+            if !keep {
+                element.detach();
+            }
+            keep
+        });
+        state.parents.clear();
+        for (node_id, node) in &tree.nodes {
+            for child in &node.children {
+                state.parents.insert(*child, *node_id);
+            }
+            let (element, old) = if *node_id == tree.root {
+                (root.clone(), old_tree.node(old_tree.root))
+            } else {
+                let element = state
+                    .elements
+                    .entry(*node_id)
+                    .or_insert_with(|| ElementRef::new(hwnd, Some(*node_id)));
+                (element.clone(), old_tree.node(*node_id))
+            };
+            // A new node is announced by the change to its parent.
+            match old {
+                Some(old) if listening => {
+                    changes(old, node, |event| events.push((element.clone(), event)))
+                }
+                _ => (),
+            }
+        }
+        let focus_changed = old_tree.focus != tree.focus;
+        state.tree = Some(tree);
+        if listening && focus_changed {
+            let focus = state.tree.as_ref().and_then(|tree| tree.focus);
+            if let Some(element) = focus.and_then(|focus| state.element(focus)) {
+                events.push((element.clone(), Event::Focus));
+            }
+        }
+    }
+    // UI Automation may call the providers back, so this is done without
+    // holding on to the state.
+    for (element, event) in events {
+        raise(&element, event);
+    }
+}
+
+/// The events for the changes from `old` to `node`.
+unsafe fn changes<F: FnMut(Event)>(old: &AccessNode, node: &AccessNode, mut push: F) {
+    if old.children != node.children {
+        push(Event::Structure);
+    }
+    if old.name != node.name {
+        let name = |node: &AccessNode| string_variant(node.name.as_ref().map_or("", |n| n));
+        push(Event::Property(UIA_NamePropertyId, name(old), name(node)));
+    }
+    if old.value != node.value && node.role != Role::PasswordInput {
+        let value = |node: &AccessNode| string_variant(node.value.as_ref().map_or("", |v| v));
+        push(Event::Property(
+            UIA_ValueValuePropertyId,
+            value(old),
+            value(node),
+        ));
+    }
+    if let (Some(old_value), Some(value)) = (old.numeric_value, node.numeric_value) {
+        if old_value != value {
+            push(Event::Property(
+                UIA_RangeValueValuePropertyId,
+                double_variant(old_value),
+                double_variant(value),
+            ));
+        }
+    }
+}
+
+unsafe fn raise(element: &ElementRef, event: Event) {
+    let functions = &*OPTIONAL_FUNCTIONS;
+    let provider = element.interface(SIMPLE);
+    let hr = match event {
+        Event::Focus => match functions.UiaRaiseAutomationEvent {
+            Some(raise) => raise(provider, UIA_AutomationFocusChangedEventId),
+            None => S_OK,
+        },
+        Event::Structure => match functions.UiaRaiseStructureChangedEvent {
+            Some(raise) => {
+                let mut id = element.element().node.map(runtime_id);
+                let (id_ptr, len) = match id {
+                    Some(ref mut id) => (id.as_mut_ptr(), id.len() as c_int),
+                    None => (null_mut(), 0),
+                };
+                raise(
+                    provider,
+                    StructureChangeType_ChildrenInvalidated,
+                    id_ptr,
+                    len,
+                )
+            }
+            None => S_OK,
+        },
+        Event::Property(property_id, mut old, mut new) => {
+            let hr = match functions.UiaRaiseAutomationPropertyChangedEvent {
+                Some(raise) => raise(provider, property_id, old, new),
+                None => S_OK,
+            };
+            VariantClear(&mut old);
+            VariantClear(&mut new);
+            hr
+        }
+    };
+    if !SUCCEEDED(hr) {
+        println!("Raising a UI Automation event failed: 0x{:x}", hr);
+    }
+}
+
+/// Ask assistive technology to read out `text`. This needs Windows 10
+/// 1709 or later, and has no effect before.
+pub(crate) unsafe fn announce(window: &WindowState, text: &str, politeness: Politeness) {
+    let raise = match OPTIONAL_FUNCTIONS.UiaRaiseNotificationEvent {
+        Some(raise) => raise,
+        None => return,
+    };
+    let processing = match politeness {
+        Politeness::Polite => NotificationProcessing_All,
+        Politeness::Assertive => NotificationProcessing_ImportantMostRecent,
+    };
+    let root = match window.access.try_borrow_mut() {
+        Ok(mut state) => state.root(window.hwnd.get()),
+        Err(_) => return,
+    };
+    let text = SysAllocString(text.to_wide().as_ptr());
+    let activity_id = SysAllocString(ACTIVITY_ID.to_wide().as_ptr());
+    let hr = raise(
+        root.interface(SIMPLE),
+        NotificationKind_Other,
+        processing,
+        text,
+        activity_id,
+    );
+    if !SUCCEEDED(hr) {
+        println!("UiaRaiseNotificationEvent failed: 0x{:x}", hr);
+    }
+    SysFreeString(text);
+    SysFreeString(activity_id);
+}
+
+/// The element an interface pointer at `slot` points into.
+unsafe fn element<'a, T>(this: *mut T, slot: usize) -> &'a Element {
+    &*((this as *mut *const c_void).sub(slot) as *const Element)
+}
+
+fn interface<T>(element: *const Element, slot: usize) -> *mut T {
+    unsafe { (element as *mut *const c_void).add(slot) as *mut T }
+}
+
+/// Call `f` with the window of the element and the state of its tree. This
+/// is `None` if the element is detached, or if the state is in use, as in
+/// an update.
+unsafe fn with_state<R, F>(element: &Element, f: F) -> Option<R>
+where
+    F: FnOnce(&WindowState, &AccessState) -> Option<R>,
+{
+    let hwnd = element.hwnd.get();
+    if hwnd.is_null() {
+        return None;
+    }
+    let window = (GetWindowLongPtrW(hwnd, GWLP_USERDATA) as *const WindowState).as_ref()?;
+    let state = window.access.try_borrow().ok()?;
+    f(window, &state)
+}
+
+/// Call `f` with the state and the node of a node's element, answering
+/// that the element is not available if it is gone.
+unsafe fn with_node<F>(element: &Element, f: F) -> HRESULT
+where
+    F: FnOnce(&AccessState, &AccessNode),
+{
+    let found = with_state(element, |_, state| {
+        element.node?;
+        state.node(element).map(|node| f(state, node))
+    });
+    match found {
+        Some(()) => S_OK,
+        None => UIA_E_ELEMENTNOTAVAILABLE,
+    }
+}
+
+/// Send an action to the handler, if the node supports it.
+unsafe fn perform(element: &Element, action: Action, value: Option<String>) -> HRESULT {
+    let node_id = match element.node {
+        Some(node_id) => node_id,
+        None => return UIA_E_INVALIDOPERATION,
+    };
+    match with_state(element, |_, state| {
+        Some(state.node(element)?.supports(action))
+    }) {
+        Some(true) => (),
+        Some(false) => return UIA_E_INVALIDOPERATION,
+        None => return UIA_E_ELEMENTNOTAVAILABLE,
+    }
+    let mut message = ActionMessage {
+        request: ActionRequest {
+            node: node_id,
+            action,
+            value,
+        },
+        performed: false,
+    };
+    SendMessageW(
+        element.hwnd.get(),
+        XI_ACCESS_ACTION,
+        0,
+        &mut message as *mut ActionMessage as LPARAM,
+    );
+    if message.performed {
+        S_OK
+    } else {
+        UIA_E_INVALIDOPERATION
+    }
+}
+
+/// The runtime id of the element of a node, unique within the window.
+fn runtime_id(node_id: NodeId) -> [c_int; 3] {
+    [
+        UiaAppendRuntimeId,
+        node_id as u32 as c_int,
+        (node_id >> 32) as u32 as c_int,
+    ]
+}
+
+/// A variant holding a copy of `text`, to be cleared with `VariantClear`.
+unsafe fn string_variant(text: &str) -> VARIANT {
+    let mut variant: VARIANT = mem::zeroed();
+    variant.n1.n2_mut().vt = VT_BSTR as VARTYPE;
+    *variant.n1.n2_mut().n3.bstrVal_mut() = SysAllocString(text.to_wide().as_ptr());
+    variant
+}
+
+unsafe fn double_variant(value: f64) -> VARIANT {
+    let mut variant: VARIANT = mem::zeroed();
+    variant.n1.n2_mut().vt = VT_R8 as VARTYPE;
+    *variant.n1.n2_mut().n3.dblVal_mut() = value;
+    variant
+}
+
+unsafe fn int_variant(value: c_int) -> VARIANT {
+    let mut variant: VARIANT = mem::zeroed();
+    variant.n1.n2_mut().vt = VT_I4 as VARTYPE;
+    *variant.n1.n2_mut().n3.lVal_mut() = value;
+    variant
+}
+
+unsafe fn bool_variant(value: bool) -> VARIANT {
+    let mut variant: VARIANT = mem::zeroed();
+    variant.n1.n2_mut().vt = VT_BOOL as VARTYPE;
+    *variant.n1.n2_mut().n3.boolVal_mut() = if value { VARIANT_TRUE } else { 0 };
+    variant
+}
+
+fn to_bool(value: bool) -> BOOL {
+    if value {
+        TRUE
+    } else {
+        FALSE
+    }
+}
+
+fn control_type(role: Role) -> c_int {
+    match role {
+        Role::Group => UIA_GroupControlTypeId,
+        Role::Window => UIA_WindowControlTypeId,
+        Role::Label => UIA_TextControlTypeId,
+        Role::Button => UIA_ButtonControlTypeId,
+        Role::CheckBox => UIA_CheckBoxControlTypeId,
+        Role::TextInput | Role::PasswordInput => UIA_EditControlTypeId,
+        Role::Slider => UIA_SliderControlTypeId,
+        Role::ProgressIndicator => UIA_ProgressBarControlTypeId,
+        Role::Image => UIA_ImageControlTypeId,
+        Role::List => UIA_ListControlTypeId,
+        Role::ListItem => UIA_ListItemControlTypeId,
+        Role::Menu => UIA_MenuControlTypeId,
+        Role::MenuItem => UIA_MenuItemControlTypeId,
+    }
+}
+
+unsafe fn element_query_interface(
+    element: &Element,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    let riid = &*riid;
+    let slot = if IsEqualIID(riid, &IUnknown::uuidof())
+        || IsEqualIID(riid, &IRawElementProviderSimple::uuidof())
+    {
+        Some(SIMPLE)
+    } else if IsEqualIID(riid, &IRawElementProviderFragment::uuidof()) {
+        Some(FRAGMENT)
+    } else if IsEqualIID(riid, &IRawElementProviderFragmentRoot::uuidof()) && element.node.is_none()
+    {
+        Some(FRAGMENT_ROOT)
+    } else if IsEqualIID(riid, &IInvokeProvider::uuidof()) {
+        Some(INVOKE)
+    } else if IsEqualIID(riid, &IValueProvider::uuidof()) {
+        Some(VALUE)
+    } else if IsEqualIID(riid, &IRangeValueProvider::uuidof()) {
+        Some(RANGE_VALUE)
+    } else {
+        None
+    };
+    match slot {
+        Some(slot) => {
+            element_add_ref(element);
+            *ppv = interface(element, slot);
+            S_OK
+        }
+        None => {
+            *ppv = null_mut();
+            E_NOINTERFACE
+        }
+    }
+}
+
+unsafe fn element_add_ref(element: *const Element) -> ULONG {
+    (*element).refcount.fetch_add(1, Ordering::Relaxed) + 1
+}
+
+unsafe fn element_release(element: *const Element) -> ULONG {
+    let count = (*element).refcount.fetch_sub(1, Ordering::Release) - 1;
+    if count == 0 {
+        // See that all uses on other threads are done before the drop.
+        atomic::fence(Ordering::Acquire);
+        mem::drop(Box::from_raw(element as *mut Element));
+    }
+    count
+}
+
+unsafe extern "system" fn get_provider_options(
+    _this: *mut IRawElementProviderSimple,
+    options: *mut c_int,
+) -> HRESULT {
+    *options = ProviderOptions_ServerSideProvider | ProviderOptions_UseComThreading;
+    S_OK
+}
+
+unsafe extern "system" fn get_pattern_provider(
+    this: *mut IRawElementProviderSimple,
+    pattern_id: c_int,
+    pattern: *mut *mut IUnknown,
+) -> HRESULT {
+    *pattern = null_mut();
+    let element = element(this, SIMPLE);
+    // The window's patterns are those of its host provider.
+    if element.node.is_none() {
+        return S_OK;
+    }
+    with_node(element, |_, node| {
+        let slot = match pattern_id {
+            UIA_InvokePatternId if node.supports(Action::Click) => INVOKE,
+            UIA_RangeValuePatternId if node.numeric_value.is_some() => RANGE_VALUE,
+            UIA_ValuePatternId
+                if node.numeric_value.is_none()
+                    && (node.value.is_some() || node.supports(Action::SetValue)) =>
+            {
+                VALUE
+            }
+            _ => return,
+        };
+        element_add_ref(element);
+        *pattern = interface(element, slot);
+    })
+}
+
+unsafe extern "system" fn get_property_value(
+    this: *mut IRawElementProviderSimple,
+    property_id: c_int,
+    value: *mut VARIANT,
+) -> HRESULT {
+    // A zeroed variant is `VT_EMPTY`, which leaves the property to the
+    // host provider, or to the default.
+    ptr::write_bytes(value, 0, 1);
+    let element = element(this, SIMPLE);
+    if element.node.is_none() {
+        return S_OK;
+    }
+    with_node(element, |state, node| {
+        let focused = state.tree.as_ref().and_then(|tree| tree.focus) == element.node;
+        *value = match property_id {
+            UIA_ControlTypePropertyId => int_variant(control_type(node.role)),
+            UIA_NamePropertyId => match node.name {
+                Some(ref name) => string_variant(name),
+                None => return,
+            },
+            UIA_HasKeyboardFocusPropertyId => bool_variant(focused),
+            UIA_IsKeyboardFocusablePropertyId => bool_variant(node.supports(Action::Focus)),
+            UIA_IsEnabledPropertyId => bool_variant(true),
+            UIA_IsPasswordPropertyId => bool_variant(node.role == Role::PasswordInput),
+            _ => return,
+        };
+    })
+}
+
+unsafe extern "system" fn get_host_raw_element_provider(
+    this: *mut IRawElementProviderSimple,
+    host: *mut *mut IRawElementProviderSimple,
+) -> HRESULT {
+    *host = null_mut();
+    let element = element(this, SIMPLE);
+    let hwnd = element.hwnd.get();
+    if element.node.is_some() {
+        return S_OK;
+    }
+    if hwnd.is_null() {
+        return UIA_E_ELEMENTNOTAVAILABLE;
+    }
+    match OPTIONAL_FUNCTIONS.UiaHostProviderFromHwnd {
+        Some(host_provider) => host_provider(hwnd, host),
+        None => E_FAIL,
+    }
+}
+
+unsafe extern "system" fn navigate(
+    this: *mut IRawElementProviderFragment,
+    direction: c_int,
+    found: *mut *mut IRawElementProviderFragment,
+) -> HRESULT {
+    *found = null_mut();
+    let element = element(this, FRAGMENT);
+    let target = with_state(element, |_, state| {
+        let tree = state.tree.as_ref()?;
+        let node_id = element.node.unwrap_or(tree.root);
+        // UI Automation finds the parent and siblings of the window itself.
+        let parent = match element.node {
+            Some(node_id) => state.parents.get(&node_id).cloned(),
+            None => None,
+        };
+        let target = match direction {
+            NavigateDirection_Parent => parent,
+            NavigateDirection_NextSibling | NavigateDirection_PreviousSibling => {
+                let siblings = state.children(parent?);
+                let index = siblings.iter().position(|&id| id == node_id)?;
+                if direction == NavigateDirection_NextSibling {
+                    siblings.get(index + 1).cloned()
+                } else {
+                    index.checked_sub(1).map(|index| siblings[index])
+                }
+            }
+            NavigateDirection_FirstChild => state.children(node_id).first().cloned(),
+            NavigateDirection_LastChild => state.children(node_id).last().cloned(),
+            _ => None,
+        };
+        Some(target.and_then(|target| state.element(target)).cloned())
+    });
+    match target {
+        Some(Some(target)) => {
+            *found = target.into_interface(FRAGMENT);
+            S_OK
+        }
+        Some(None) => S_OK,
+        None => UIA_E_ELEMENTNOTAVAILABLE,
+    }
+}
+
+unsafe extern "system" fn get_runtime_id(
+    this: *mut IRawElementProviderFragment,
+    id: *mut *mut SAFEARRAY,
+) -> HRESULT {
+    *id = null_mut();
+    // The window has the runtime id of its host provider.
+    let node_id = match element(this, FRAGMENT).node {
+        Some(node_id) => node_id,
+        None => return S_OK,
+    };
+    let parts = runtime_id(node_id);
+    let array = SafeArrayCreateVector(VT_I4 as VARTYPE, 0, parts.len() as ULONG);
+    if array.is_null() {
+        return E_OUTOFMEMORY;
+    }
+    let mut data = null_mut();
+    let hr = SafeArrayAccessData(array, &mut data);
+    if !SUCCEEDED(hr) {
+        return hr;
+    }
+    ptr::copy_nonoverlapping(parts.as_ptr(), data as *mut c_int, parts.len());
+    SafeArrayUnaccessData(array);
+    *id = array;
+    S_OK
+}
+
+unsafe extern "system" fn get_bounding_rectangle(
+    this: *mut IRawElementProviderFragment,
+    rect: *mut UiaRect,
+) -> HRESULT {
+    *rect = UiaRect::default();
+    let element = element(this, FRAGMENT);
+    // The window has the bounds of its host provider.
+    if element.node.is_none() {
+        return S_OK;
+    }
+    let bounds = with_state(element, |window, state| {
+        Some((state.node(element)?.bounds, window.dpi.get()))
+    });
+    let (bounds, dpi) = match bounds {
+        Some(bounds) => bounds,
+        None => return UIA_E_ELEMENTNOTAVAILABLE,
+    };
+    let scale = f64::from(dpi / 96.0);
+    let mut origin = POINT { x: 0, y: 0 };
+    ClientToScreen(element.hwnd.get(), &mut origin);
+    *rect = UiaRect {
+        left: f64::from(origin.x) + bounds.x0 * scale,
+        top: f64::from(origin.y) + bounds.y0 * scale,
+        width: bounds.width() * scale,
+        height: bounds.height() * scale,
+    };
+    S_OK
+}
+
+unsafe extern "system" fn get_embedded_fragment_roots(
+    _this: *mut IRawElementProviderFragment,
+    roots: *mut *mut SAFEARRAY,
+) -> HRESULT {
+    *roots = null_mut();
+    S_OK
+}
+
+unsafe extern "system" fn set_focus(this: *mut IRawElementProviderFragment) -> HRESULT {
+    let element = element(this, FRAGMENT);
+    if element.node.is_none() {
+        return S_OK;
+    }
+    perform(element, Action::Focus, None)
+}
+
+unsafe extern "system" fn get_fragment_root(
+    this: *mut IRawElementProviderFragment,
+    root: *mut *mut IRawElementProviderFragmentRoot,
+) -> HRESULT {
+    *root = null_mut();
+    match with_state(element(this, FRAGMENT), |_, state| state.root.clone()) {
+        Some(element) => {
+            *root = element.into_interface(FRAGMENT_ROOT);
+            S_OK
+        }
+        None => UIA_E_ELEMENTNOTAVAILABLE,
+    }
+}
+
+/// The element of the innermost node under the point, in screen
+/// coordinates.
+unsafe extern "system" fn element_provider_from_point(
+    this: *mut IRawElementProviderFragmentRoot,
+    x: f64,
+    y: f64,
+    found: *mut *mut IRawElementProviderFragment,
+) -> HRESULT {
+    *found = null_mut();
+    let element = element(this, FRAGMENT_ROOT);
+    let hit = with_state(element, |window, state| {
+        let mut point = POINT {
+            x: x as i32,
+            y: y as i32,
+        };
+        ScreenToClient(window.hwnd.get(), &mut point);
+        let scale = f64::from(window.dpi.get() / 96.0);
+        let point = Point::new(f64::from(point.x) / scale, f64::from(point.y) / scale);
+        let tree = state.tree.as_ref()?;
+        let mut hit = state.root.clone()?;
+        let mut node = tree.node(tree.root);
+        while let Some(parent) = node {
+            node = None;
+            // The last child is painted on top.
+            for child in parent.children.iter().rev() {
+                let child_node = match tree.node(*child) {
+                    Some(child_node) => child_node,
+                    None => continue,
+                };
+                if child_node.bounds.contains(point) {
+                    if let Some(element) = state.elements.get(child) {
+                        hit = element.clone();
+                    }
+                    node = Some(child_node);
+                    break;
+                }
+            }
+        }
+        Some(hit)
+    });
+    match hit {
+        Some(hit) => {
+            *found = hit.into_interface(FRAGMENT);
+            S_OK
+        }
+        None => UIA_E_ELEMENTNOTAVAILABLE,
+    }
+}
+
+unsafe extern "system" fn get_focus(
+    this: *mut IRawElementProviderFragmentRoot,
+    found: *mut *mut IRawElementProviderFragment,
+) -> HRESULT {
+    *found = null_mut();
+    let focus = with_state(element(this, FRAGMENT_ROOT), |_, state| {
+        let focus = state.tree.as_ref()?.focus;
+        // No element when the window itself has the focus.
+        Some(focus.and_then(|focus| state.elements.get(&focus)).cloned())
+    });
+    match focus {
+        Some(Some(focus)) => {
+            *found = focus.into_interface(FRAGMENT);
+            S_OK
+        }
+        Some(None) => S_OK,
+        None => UIA_E_ELEMENTNOTAVAILABLE,
+    }
+}
+
+unsafe extern "system" fn invoke(this: *mut IInvokeProvider) -> HRESULT {
+    perform(element(this, INVOKE), Action::Click, None)
+}
+
+unsafe extern "system" fn value_set_value(this: *mut IValueProvider, value: LPCWSTR) -> HRESULT {
+    let value = (value as LPWSTR).from_wide().unwrap_or_default();
+    perform(element(this, VALUE), Action::SetValue, Some(value))
+}
+
+unsafe extern "system" fn value_get_value(this: *mut IValueProvider, value: *mut BSTR) -> HRESULT {
+    *value = null_mut();
+    with_node(element(this, VALUE), |_, node| {
+        let text = match (node.role, &node.value) {
+            (Role::PasswordInput, _) | (_, None) => "",
+            (_, Some(text)) => text,
+        };
+        *value = SysAllocString(text.to_wide().as_ptr());
+    })
+}
+
+unsafe extern "system" fn value_get_is_read_only(
+    this: *mut IValueProvider,
+    read_only: *mut BOOL,
+) -> HRESULT {
+    *read_only = TRUE;
+    with_node(element(this, VALUE), |_, node| {
+        *read_only = to_bool(!node.supports(Action::SetValue));
+    })
+}
+
+/// Set the value directly if the node supports it, and otherwise step it
+/// towards `value`.
+unsafe extern "system" fn range_set_value(this: *mut IRangeValueProvider, value: f64) -> HRESULT {
+    let element = element(this, RANGE_VALUE);
+    let mut action = None;
+    let hr = with_node(element, |_, node| {
+        let current = node.numeric_value.unwrap_or_default();
+        action = if node.supports(Action::SetValue) {
+            Some(Action::SetValue)
+        } else if value > current {
+            Some(Action::Increment)
+        } else if value < current {
+            Some(Action::Decrement)
+        } else {
+            None
+        };
+    });
+    match action {
+        Some(Action::SetValue) => perform(element, Action::SetValue, Some(value.to_string())),
+        Some(action) => perform(element, action, None),
+        None => hr,
+    }
+}
+
+unsafe extern "system" fn range_get_value(
+    this: *mut IRangeValueProvider,
+    value: *mut f64,
+) -> HRESULT {
+    *value = 0.0;
+    with_node(element(this, RANGE_VALUE), |_, node| {
+        *value = node.numeric_value.unwrap_or_default();
+    })
+}
+
+unsafe extern "system" fn range_get_is_read_only(
+    this: *mut IRangeValueProvider,
+    read_only: *mut BOOL,
+) -> HRESULT {
+    *read_only = TRUE;
+    with_node(element(this, RANGE_VALUE), |_, node| {
+        let settable = [Action::SetValue, Action::Increment, Action::Decrement]
+            .iter()
+            .any(|&action| node.supports(action));
+        *read_only = to_bool(!settable);
+    })
+}
+
+unsafe extern "system" fn range_get_maximum(
+    this: *mut IRangeValueProvider,
+    value: *mut f64,
+) -> HRESULT {
+    *value = 0.0;
+    with_node(element(this, RANGE_VALUE), |_, node| {
+        *value = node.max_value.or(node.numeric_value).unwrap_or_default();
+    })
+}
+
+unsafe extern "system" fn range_get_minimum(
+    this: *mut IRangeValueProvider,
+    value: *mut f64,
+) -> HRESULT {
+    *value = 0.0;
+    with_node(element(this, RANGE_VALUE), |_, node| {
+        *value = node.min_value.or(node.numeric_value).unwrap_or_default();
+    })
+}
+
+/// A tenth of the range, as the tree has no steps.
+unsafe extern "system" fn range_get_large_change(
+    this: *mut IRangeValueProvider,
+    value: *mut f64,
+) -> HRESULT {
+    *value = 0.0;
+    with_node(element(this, RANGE_VALUE), |_, node| {
+        *value = range(node) / 10.0;
+    })
+}
+
+/// A hundredth of the range.
+unsafe extern "system" fn range_get_small_change(
+    this: *mut IRangeValueProvider,
+    value: *mut f64,
+) -> HRESULT {
+    *value = 0.0;
+    with_node(element(this, RANGE_VALUE), |_, node| {
+        *value = range(node) / 100.0;
+    })
+}
+
+/// The size of the range of a node's value, or 1 if it isn't known.
+fn range(node: &AccessNode) -> f64 {
+    match (node.min_value, node.max_value) {
+        (Some(min), Some(max)) if max > min => max - min,
+        _ => 1.0,
+    }
+}
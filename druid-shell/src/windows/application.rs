@@ -14,10 +14,162 @@
 
 //! Windows implementation of features at the application scope.
 
+use std::mem;
+use std::path::Path;
+use std::ptr::null_mut;
+
+use winapi::shared::minwindef::UINT;
+use winapi::shared::ntdef::{HANDLE, LPCWSTR};
+use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+use winapi::um::minwinbase::REASON_CONTEXT;
+use winapi::um::shellapi::ShellExecuteW;
+use winapi::um::shlobj::SHOpenFolderAndSelectItems;
+use winapi::um::shtypes::{PCIDLIST_ABSOLUTE, PIDLIST_ABSOLUTE};
+use winapi::um::winbase::{
+    GetSystemPowerStatus, PowerClearRequest, PowerCreateRequest, PowerSetRequest,
+    SYSTEM_POWER_STATUS,
+};
+use winapi::um::winnt::{
+    PowerRequestDisplayRequired, PowerRequestSystemRequired, POWER_REQUEST_CONTEXT_SIMPLE_STRING,
+    POWER_REQUEST_CONTEXT_VERSION,
+};
+use winapi::um::winuser::SW_SHOWNORMAL;
+
+use super::theme;
+use super::util::{as_result, ToWide};
+use crate::clipboard::Clipboard;
+use crate::piet::Color;
+use crate::window::{PowerSource, Theme};
+use crate::Error;
+
+#[link(name = "shell32")]
+extern "system" {
+    fn ILCreateFromPathW(path: LPCWSTR) -> PIDLIST_ABSOLUTE;
+    fn ILFree(pidl: PIDLIST_ABSOLUTE);
+}
+
 pub struct Application;
 
+/// Keeps the display awake until dropped; see `Application::inhibit_sleep`.
+pub struct SleepInhibitor {
+    request: HANDLE,
+}
+
 impl Application {
     pub fn quit() {
         crate::runloop::request_quit();
     }
+
+    /// The system clipboard.
+    pub fn clipboard() -> Clipboard {
+        Clipboard
+    }
+
+    /// The light or dark theme chosen for apps in the system settings.
+    pub fn theme() -> Theme {
+        theme::system_theme()
+    }
+
+    /// The accent color chosen in the personalization settings, as used for
+    /// window frames.
+    pub fn accent_color() -> Option<Color> {
+        theme::accent_color().map(Color::rgb24)
+    }
+
+    /// Whether transparency effects are turned off in the personalization
+    /// settings.
+    pub fn reduce_transparency() -> bool {
+        theme::reduce_transparency()
+    }
+
+    /// The size of text chosen in the accessibility settings, relative to the
+    /// default.
+    pub fn text_scale() -> f64 {
+        theme::text_scale()
+    }
+
+    /// Whether the device runs on battery. Devices without one, or whose
+    /// state is unknown, are taken to be on external power.
+    pub fn power_source() -> PowerSource {
+        let mut status: SYSTEM_POWER_STATUS = unsafe { mem::zeroed() };
+        let known = unsafe { GetSystemPowerStatus(&mut status) } != 0;
+        if known && status.ACLineStatus == 0 {
+            PowerSource::Battery
+        } else {
+            PowerSource::External
+        }
+    }
+
+    /// Only the macOS Dock shows badges, so this does nothing.
+    pub fn set_badge(_label: Option<&str>) {}
+
+    /// Open a URL in the default browser, or with the application registered
+    /// for its scheme.
+    pub fn open_url(url: &str) -> Result<(), Error> {
+        let result = unsafe {
+            ShellExecuteW(
+                null_mut(),
+                "open".to_wide().as_ptr(),
+                url.to_wide().as_ptr(),
+                null_mut(),
+                null_mut(),
+                SW_SHOWNORMAL,
+            )
+        };
+        // Values above 32 indicate success.
+        if result as usize > 32 {
+            Ok(())
+        } else {
+            Err(Error::Other("ShellExecute failed"))
+        }
+    }
+
+    /// Open the folder containing `path` in Explorer, with the item selected.
+    pub fn reveal_in_file_manager(path: &Path) -> Result<(), Error> {
+        unsafe {
+            let pidl = ILCreateFromPathW(path.to_wide().as_ptr());
+            if pidl.is_null() {
+                return Err(Error::Other("no such file"));
+            }
+            let hr =
+                SHOpenFolderAndSelectItems(pidl as PCIDLIST_ABSOLUTE, 0 as UINT, null_mut(), 0);
+            ILFree(pidl);
+            as_result(hr)
+        }
+    }
+
+    /// Keep the display from sleeping and the screensaver from starting,
+    /// for as long as the returned value is alive. The reason is shown by
+    /// `powercfg /requests`.
+    pub fn inhibit_sleep(reason: &str) -> Result<SleepInhibitor, Error> {
+        unsafe {
+            let mut reason = reason.to_wide();
+            let mut context: REASON_CONTEXT = mem::zeroed();
+            context.Version = POWER_REQUEST_CONTEXT_VERSION;
+            context.Flags = POWER_REQUEST_CONTEXT_SIMPLE_STRING;
+            *context.Reason.SimpleReasonString_mut() = reason.as_mut_ptr();
+            let request = PowerCreateRequest(&mut context);
+            if request == INVALID_HANDLE_VALUE {
+                return Err(Error::Other("PowerCreateRequest failed"));
+            }
+            let inhibitor = SleepInhibitor { request };
+            if PowerSetRequest(request, PowerRequestDisplayRequired) == 0
+                || PowerSetRequest(request, PowerRequestSystemRequired) == 0
+            {
+                return Err(Error::Other("PowerSetRequest failed"));
+            }
+            Ok(inhibitor)
+        }
+    }
+}
+
+impl Drop for SleepInhibitor {
+    fn drop(&mut self) {
+        unsafe {
+            // Clearing a request that was never set fails harmlessly.
+            PowerClearRequest(self.request, PowerRequestDisplayRequired);
+            PowerClearRequest(self.request, PowerRequestSystemRequired);
+            CloseHandle(self.request);
+        }
+    }
 }
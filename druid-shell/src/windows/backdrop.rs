@@ -0,0 +1,100 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Translucent window backdrops, Windows implementation.
+//!
+//! Windows 11 has system backdrops for Mica and acrylic. Windows 10 only has
+//! acrylic, through an undocumented attribute.
+
+use std::mem;
+
+use winapi::ctypes::c_void;
+use winapi::shared::basetsd::SIZE_T;
+use winapi::shared::minwindef::DWORD;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::SUCCEEDED;
+use winapi::um::dwmapi::{DwmExtendFrameIntoClientArea, DwmSetWindowAttribute};
+use winapi::um::uxtheme::MARGINS;
+
+use super::util::OPTIONAL_FUNCTIONS;
+use crate::window::WindowBackdrop;
+
+// From dwmapi.h in the Windows 11 SDK, which winapi predates.
+const DWMWA_SYSTEMBACKDROP_TYPE: DWORD = 38;
+const DWMSBT_MAINWINDOW: DWORD = 2;
+const DWMSBT_TRANSIENTWINDOW: DWORD = 3;
+
+const WCA_ACCENT_POLICY: DWORD = 19;
+const ACCENT_ENABLE_ACRYLICBLURBEHIND: DWORD = 4;
+
+#[repr(C)]
+struct AccentPolicy {
+    accent_state: DWORD,
+    accent_flags: DWORD,
+    /// The tint, as ABGR.
+    gradient_color: DWORD,
+    animation_id: DWORD,
+}
+
+/// The argument of `SetWindowCompositionAttribute`.
+#[repr(C)]
+pub struct WindowCompositionAttribData {
+    attrib: DWORD,
+    data: *mut c_void,
+    size: SIZE_T,
+}
+
+/// Show a backdrop behind the client area, which must then be painted
+/// with alpha. Returns whether this version of Windows has the backdrop.
+pub(crate) unsafe fn apply(hwnd: HWND, backdrop: WindowBackdrop) -> bool {
+    let backdrop_type = match backdrop {
+        WindowBackdrop::None => return false,
+        WindowBackdrop::Blur => DWMSBT_TRANSIENTWINDOW,
+        WindowBackdrop::Sidebar => DWMSBT_MAINWINDOW,
+    };
+    let hr = DwmSetWindowAttribute(
+        hwnd,
+        DWMWA_SYSTEMBACKDROP_TYPE,
+        &backdrop_type as *const DWORD as *const _,
+        mem::size_of::<DWORD>() as DWORD,
+    );
+    if SUCCEEDED(hr) {
+        // System backdrops only show through the frame, so it covers the
+        // whole window.
+        let margins = MARGINS {
+            cxLeftWidth: -1,
+            cxRightWidth: -1,
+            cyTopHeight: -1,
+            cyBottomHeight: -1,
+        };
+        return SUCCEEDED(DwmExtendFrameIntoClientArea(hwnd, &margins));
+    }
+    let set_attribute = match OPTIONAL_FUNCTIONS.SetWindowCompositionAttribute {
+        Some(set_attribute) if backdrop == WindowBackdrop::Blur => set_attribute,
+        _ => return false,
+    };
+    let mut policy = AccentPolicy {
+        accent_state: ACCENT_ENABLE_ACRYLICBLURBEHIND,
+        accent_flags: 0,
+        // Acrylic needs a tint, so this is the faintest one.
+        gradient_color: 0x0100_0000,
+        animation_id: 0,
+    };
+    let mut data = WindowCompositionAttribData {
+        attrib: WCA_ACCENT_POLICY,
+        data: &mut policy as *mut AccentPolicy as *mut c_void,
+        size: mem::size_of::<AccentPolicy>() as SIZE_T,
+    };
+    set_attribute(hwnd, &mut data) != 0
+}
@@ -0,0 +1,179 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Interactions with the system clipboard on Windows.
+
+use std::ptr::{copy_nonoverlapping, null_mut};
+
+use winapi::shared::minwindef::{HGLOBAL, UINT};
+use winapi::um::winbase::{
+    GlobalAlloc, GlobalFree, GlobalLock, GlobalSize, GlobalUnlock, GMEM_MOVEABLE,
+};
+use winapi::um::winuser::{
+    CloseClipboard, EmptyClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+    RegisterClipboardFormatW, SetClipboardData, CF_UNICODETEXT,
+};
+
+use crate::clipboard::{ClipboardFormat, FormatId};
+use crate::util::{FromWide, ToWide};
+
+/// The system clipboard.
+#[derive(Debug, Clone, Default)]
+pub struct Clipboard;
+
+impl Clipboard {
+    /// Put a string on the clipboard, replacing its contents.
+    pub fn put_string(&mut self, s: impl AsRef<str>) {
+        let wide = s.as_ref().to_wide();
+        unsafe {
+            if OpenClipboard(null_mut()) == 0 {
+                return;
+            }
+            EmptyClipboard();
+            if !set_data(CF_UNICODETEXT, wide.as_ptr() as *const u8, wide.len() * 2) {
+                println!("failed to set clipboard data");
+            }
+            CloseClipboard();
+        }
+    }
+
+    /// Get the string on the clipboard, if there is one.
+    pub fn get_string(&self) -> Option<String> {
+        unsafe {
+            if IsClipboardFormatAvailable(CF_UNICODETEXT) == 0 || OpenClipboard(null_mut()) == 0 {
+                return None;
+            }
+            let result = get_data(CF_UNICODETEXT).and_then(|bytes| {
+                let wide: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_ne_bytes([c[0], c[1]]))
+                    .collect();
+                let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+                wide[..len].from_wide()
+            });
+            CloseClipboard();
+            result
+        }
+    }
+
+    /// Put data on the clipboard in several formats, replacing its contents.
+    pub fn put_formats(&mut self, formats: &[ClipboardFormat]) {
+        unsafe {
+            if OpenClipboard(null_mut()) == 0 {
+                return;
+            }
+            EmptyClipboard();
+            for format in formats {
+                let ok = if format.identifier == ClipboardFormat::TEXT {
+                    let wide = String::from_utf8_lossy(&format.data).as_ref().to_wide();
+                    set_data(CF_UNICODETEXT, wide.as_ptr() as *const u8, wide.len() * 2)
+                } else {
+                    let id = register_format(format.identifier);
+                    id != 0 && set_data(id, format.data.as_ptr(), format.data.len())
+                };
+                if !ok {
+                    println!("failed to set clipboard data for {}", format.identifier);
+                }
+            }
+            CloseClipboard();
+        }
+    }
+
+    /// Get the data on the clipboard in a format, if it is available.
+    ///
+    /// Data in custom formats may be padded at the end, since Windows rounds
+    /// up the size of the memory holding it.
+    pub fn get_format(&self, format: FormatId) -> Option<Vec<u8>> {
+        if format == ClipboardFormat::TEXT {
+            return self.get_string().map(String::into_bytes);
+        }
+        unsafe {
+            let id = register_format(format);
+            if id == 0 || IsClipboardFormatAvailable(id) == 0 || OpenClipboard(null_mut()) == 0 {
+                return None;
+            }
+            let result = get_data(id);
+            CloseClipboard();
+            result
+        }
+    }
+
+    /// The first of `formats` that the clipboard has data for.
+    pub fn preferred_format(&self, formats: &[FormatId]) -> Option<FormatId> {
+        formats.iter().cloned().find(|&format| unsafe {
+            let id = if format == ClipboardFormat::TEXT {
+                CF_UNICODETEXT
+            } else {
+                register_format(format)
+            };
+            id != 0 && IsClipboardFormatAvailable(id) != 0
+        })
+    }
+}
+
+/// The clipboard format id for a format. Registering a name that is
+/// already registered returns the existing id.
+pub(crate) fn register_format(format: FormatId) -> UINT {
+    // Other applications know PNG data by this name.
+    let name = if format == ClipboardFormat::PNG {
+        "PNG"
+    } else {
+        format
+    };
+    unsafe { RegisterClipboardFormatW(name.to_wide().as_ptr()) }
+}
+
+/// Copy data into a global memory object owned by the clipboard. The
+/// clipboard must be open.
+pub(crate) unsafe fn set_data(format: UINT, data: *const u8, len: usize) -> bool {
+    let handle = copy_to_global(data, len);
+    if handle.is_null() {
+        return false;
+    }
+    if SetClipboardData(format, handle).is_null() {
+        GlobalFree(handle);
+        return false;
+    }
+    true
+}
+
+/// Copy data into a new global memory object, returning null on failure.
+pub(crate) unsafe fn copy_to_global(data: *const u8, len: usize) -> HGLOBAL {
+    let handle = GlobalAlloc(GMEM_MOVEABLE, len);
+    if handle.is_null() {
+        return handle;
+    }
+    let dst = GlobalLock(handle) as *mut u8;
+    copy_nonoverlapping(data, dst, len);
+    GlobalUnlock(handle);
+    handle
+}
+
+/// Copy the data of a format out of the clipboard. The clipboard must be
+/// open.
+pub(crate) unsafe fn get_data(format: UINT) -> Option<Vec<u8>> {
+    let handle = GetClipboardData(format);
+    if handle.is_null() {
+        return None;
+    }
+    let src = GlobalLock(handle) as *const u8;
+    if src.is_null() {
+        return None;
+    }
+    let len = GlobalSize(handle);
+    let mut bytes = vec![0u8; len];
+    copy_nonoverlapping(src, bytes.as_mut_ptr(), len);
+    GlobalUnlock(handle);
+    Some(bytes)
+}
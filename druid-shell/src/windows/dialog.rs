@@ -16,62 +16,59 @@
 
 #![allow(non_upper_case_globals)]
 
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::REFIID;
 use winapi::shared::minwindef::*;
-use winapi::shared::ntdef::LPWSTR;
+use winapi::shared::ntdef::{LPWSTR, PCWSTR};
 use winapi::shared::windef::*;
+use winapi::shared::winerror::SUCCEEDED;
 use winapi::shared::wtypesbase::*;
 use winapi::um::combaseapi::*;
+use winapi::um::objidl::IBindCtx;
 use winapi::um::shobjidl::*;
 use winapi::um::shobjidl_core::*;
+use winapi::um::shtypes::COMDLG_FILTERSPEC;
+use winapi::um::winnt::HRESULT;
 use winapi::Interface;
 use wio::com::ComPtr;
 
-use crate::util::{as_result, FromWide};
+use crate::dialog::{FileDialogOptions, FileDialogType};
+use crate::util::{as_result, FromWide, ToWide};
 use crate::Error;
 use std::ffi::OsString;
 use std::ptr::null_mut;
 
-/// Type of file dialog.
-pub enum FileDialogType {
-    /// File open dialog.
-    Open,
-    /// File save dialog.
-    Save,
-}
-
-/// Options for file dialog.
-///
-/// See documentation for
-/// [_FILEOPENDIALOGOPTIONS](https://docs.microsoft.com/en-us/windows/desktop/api/shobjidl_core/ne-shobjidl_core-_fileopendialogoptions)
-/// for more information on the winapi implementation.
-#[derive(Default)]
-pub struct FileDialogOptions(DWORD);
-
-impl FileDialogOptions {
-    /// Include system and hidden items.
-    ///
-    /// Maps to `FOS_FORCESHOWHIDDEN` in winapi.
-    pub fn set_show_hidden(&mut self) {
-        self.0 |= FOS_FORCESHOWHIDDEN;
-    }
-
-    // TODO: more options as needed
-}
-
 // TODO: remove these when they get added to winapi
 DEFINE_GUID! {CLSID_FileOpenDialog,
 0xDC1C5A9C, 0xE88A, 0x4DDE, 0xA5, 0xA1, 0x60, 0xF8, 0x2A, 0x20, 0xAE, 0xF7}
 DEFINE_GUID! {CLSID_FileSaveDialog,
 0xC0B4E2F3, 0xBA21, 0x4773, 0x8D, 0xBA, 0x33, 0x5E, 0xC9, 0x46, 0xEB, 0x8B}
 
-pub(crate) unsafe fn get_file_dialog_path(
+#[link(name = "shell32")]
+extern "system" {
+    fn SHCreateItemFromParsingName(
+        pszPath: PCWSTR,
+        pbc: *mut IBindCtx,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+/// Show a modal file dialog, returning the chosen paths.
+///
+/// See documentation for
+/// [_FILEOPENDIALOGOPTIONS](https://docs.microsoft.com/en-us/windows/desktop/api/shobjidl_core/ne-shobjidl_core-_fileopendialogoptions)
+/// for more information on how the options map to winapi.
+pub(crate) unsafe fn get_file_dialog_paths(
     hwnd_owner: HWND,
     ty: FileDialogType,
-    options: FileDialogOptions,
-) -> Result<OsString, Error> {
+    options: &FileDialogOptions,
+) -> Result<Vec<OsString>, Error> {
     let mut pfd: *mut IFileDialog = null_mut();
     let (class, id) = match ty {
-        FileDialogType::Open => (&CLSID_FileOpenDialog, IFileOpenDialog::uuidof()),
+        FileDialogType::Open | FileDialogType::Directory => {
+            (&CLSID_FileOpenDialog, IFileOpenDialog::uuidof())
+        }
         FileDialogType::Save => (&CLSID_FileSaveDialog, IFileSaveDialog::uuidof()),
     };
     as_result(CoCreateInstance(
@@ -82,15 +79,102 @@ pub(crate) unsafe fn get_file_dialog_path(
         &mut pfd as *mut *mut IFileDialog as *mut LPVOID,
     ))?;
     let file_dialog = ComPtr::from_raw(pfd);
-    as_result(file_dialog.SetOptions(options.0))?;
+
+    let mut flags: DWORD = 0;
+    as_result(file_dialog.GetOptions(&mut flags))?;
+    if options.show_hidden {
+        flags |= FOS_FORCESHOWHIDDEN;
+    }
+    if ty == FileDialogType::Directory {
+        flags |= FOS_PICKFOLDERS;
+    } else if ty == FileDialogType::Open {
+        if options.multi_selection {
+            flags |= FOS_ALLOWMULTISELECT;
+        }
+        if options.select_directories {
+            flags |= FOS_PICKFOLDERS;
+        }
+    } else {
+        // Ask before replacing an existing file.
+        flags |= FOS_OVERWRITEPROMPT;
+    }
+    as_result(file_dialog.SetOptions(flags))?;
+
+    // The strings must outlive the call to SetFileTypes.
+    let specs: Vec<(Vec<u16>, Vec<u16>)> = options
+        .allowed_types
+        .iter()
+        .map(|spec| {
+            let patterns: Vec<String> =
+                spec.extensions.iter().map(|e| format!("*.{}", e)).collect();
+            (spec.name.to_wide(), patterns.join(";").to_wide())
+        })
+        .collect();
+    if !specs.is_empty() && ty != FileDialogType::Directory {
+        let filters: Vec<COMDLG_FILTERSPEC> = specs
+            .iter()
+            .map(|(name, spec)| COMDLG_FILTERSPEC {
+                pszName: name.as_ptr(),
+                pszSpec: spec.as_ptr(),
+            })
+            .collect();
+        as_result(file_dialog.SetFileTypes(filters.len() as UINT, filters.as_ptr()))?;
+    }
+    if ty == FileDialogType::Save {
+        let default_ext = options
+            .allowed_types
+            .first()
+            .and_then(|spec| spec.extensions.first());
+        if let Some(ext) = default_ext {
+            as_result(file_dialog.SetDefaultExtension(ext.to_wide().as_ptr()))?;
+        }
+        if let Some(ref name) = options.default_name {
+            as_result(file_dialog.SetFileName(name.to_wide().as_ptr()))?;
+        }
+    }
+
+    if let Some(ref dir) = options.starting_directory {
+        let mut item: *mut IShellItem = null_mut();
+        let hr = SHCreateItemFromParsingName(
+            dir.to_wide().as_ptr(),
+            null_mut(),
+            &IShellItem::uuidof(),
+            &mut item as *mut *mut IShellItem as *mut *mut c_void,
+        );
+        // A missing directory just leaves the default in place.
+        if SUCCEEDED(hr) {
+            let item = ComPtr::from_raw(item);
+            file_dialog.SetFolder(item.as_raw());
+        }
+    }
+
     as_result(file_dialog.Show(hwnd_owner))?;
-    let mut result_ptr: *mut IShellItem = null_mut();
-    as_result(file_dialog.GetResult(&mut result_ptr))?;
-    let shell_item = ComPtr::from_raw(result_ptr);
+
+    if ty != FileDialogType::Save {
+        let open_dialog = file_dialog.cast::<IFileOpenDialog>()?;
+        let mut results: *mut IShellItemArray = null_mut();
+        as_result(open_dialog.GetResults(&mut results))?;
+        let results = ComPtr::from_raw(results);
+        let mut count: DWORD = 0;
+        as_result(results.GetCount(&mut count))?;
+        let mut paths = Vec::with_capacity(count as usize);
+        for i in 0..count {
+            let mut item: *mut IShellItem = null_mut();
+            as_result(results.GetItemAt(i, &mut item))?;
+            paths.push(shell_item_path(&ComPtr::from_raw(item))?);
+        }
+        Ok(paths)
+    } else {
+        let mut result_ptr: *mut IShellItem = null_mut();
+        as_result(file_dialog.GetResult(&mut result_ptr))?;
+        Ok(vec![shell_item_path(&ComPtr::from_raw(result_ptr))?])
+    }
+}
+
+unsafe fn shell_item_path(shell_item: &ComPtr<IShellItem>) -> Result<OsString, Error> {
     let mut display_name: LPWSTR = null_mut();
     as_result(shell_item.GetDisplayName(SIGDN_FILESYSPATH, &mut display_name))?;
     let filename = display_name.to_os_string();
     CoTaskMemFree(display_name as LPVOID);
-
     Ok(filename)
 }
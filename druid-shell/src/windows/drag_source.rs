@@ -0,0 +1,352 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drags out of the application, Windows implementation.
+//!
+//! The data goes in a shell data object, which also keeps the drag image
+//! for the drag and drop helper, and `DoDragDrop` runs the drag in a modal
+//! loop.
+
+#![allow(non_upper_case_globals)]
+
+use std::cell::Cell;
+use std::mem;
+use std::ptr::{null, null_mut};
+use std::slice;
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualIID, REFIID};
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::shared::winerror::*;
+use winapi::shared::wtypes::DVASPECT_CONTENT;
+use winapi::shared::wtypesbase::CLSCTX_INPROC_SERVER;
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::objidl::{IDataObject, STGMEDIUM_u, FORMATETC, STGMEDIUM, TYMED_HGLOBAL};
+use winapi::um::oleidl::{DROPEFFECT_COPY, DROPEFFECT_MOVE, DROPEFFECT_NONE};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winbase::GlobalFree;
+use winapi::um::wingdi::{CreateBitmap, DeleteObject};
+use winapi::um::winnt::HRESULT;
+use winapi::um::winuser::{CF_HDROP, CF_UNICODETEXT, MK_LBUTTON, MK_RBUTTON};
+use winapi::Interface;
+
+use super::clipboard::{copy_to_global, register_format};
+use super::util::{as_result, ToWide};
+use crate::clipboard::ClipboardFormat;
+use crate::drag::{DragData, DragImage, DropEffect};
+use crate::Error;
+
+RIDL! {#[uuid(0x0000_0121, 0x0000, 0x0000, 0xc0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46)]
+interface IDropSource(IDropSourceVtbl): IUnknown(IUnknownVtbl) {
+    fn QueryContinueDrag(
+        fEscapePressed: BOOL,
+        grfKeyState: DWORD,
+    ) -> HRESULT,
+    fn GiveFeedback(
+        dwEffect: DWORD,
+    ) -> HRESULT,
+}}
+
+RIDL! {#[uuid(0xde5b_f786, 0x477a, 0x11d2, 0x83, 0x9d, 0x00, 0xc0, 0x4f, 0xd9, 0x18, 0xd0)]
+interface IDragSourceHelper(IDragSourceHelperVtbl): IUnknown(IUnknownVtbl) {
+    fn InitializeFromBitmap(
+        pshdi: *const SHDRAGIMAGE,
+        pDataObject: *mut IDataObject,
+    ) -> HRESULT,
+    fn InitializeFromWindow(
+        hwnd: HWND,
+        ppt: *const POINT,
+        pDataObject: *mut IDataObject,
+    ) -> HRESULT,
+}}
+
+DEFINE_GUID! {CLSID_DragDropHelper,
+0x4657_278a, 0x411b, 0x11d2, 0x83, 0x9a, 0x00, 0xc0, 0x4f, 0xd9, 0x18, 0xd0}
+
+#[repr(C)]
+#[allow(non_snake_case)]
+struct SHDRAGIMAGE {
+    sizeDragImage: SIZE,
+    ptOffset: POINT,
+    hbmpDragImage: HBITMAP,
+    crColorKey: COLORREF,
+}
+
+/// The header of `CF_HDROP` data, followed by the paths.
+#[repr(C)]
+#[allow(non_snake_case)]
+struct DROPFILES {
+    pFiles: DWORD,
+    pt: POINT,
+    fNC: BOOL,
+    fWide: BOOL,
+}
+
+/// Use the alpha channel of the drag image rather than a color key.
+const CLR_NONE: COLORREF = 0xFFFF_FFFF;
+
+#[link(name = "ole32")]
+extern "system" {
+    fn DoDragDrop(
+        data: *mut IDataObject,
+        source: *mut IDropSource,
+        ok_effects: DWORD,
+        effect: *mut DWORD,
+    ) -> HRESULT;
+}
+
+#[link(name = "shell32")]
+extern "system" {
+    fn SHCreateDataObject(
+        folder: *const c_void,
+        count: UINT,
+        items: *const *const c_void,
+        inner: *mut IDataObject,
+        riid: REFIID,
+        ppv: *mut *mut c_void,
+    ) -> HRESULT;
+}
+
+#[repr(C)]
+struct DropSource {
+    vtbl: *const IDropSourceVtbl,
+    refcount: Cell<ULONG>,
+}
+
+static DROP_SOURCE_VTBL: IDropSourceVtbl = IDropSourceVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    },
+    QueryContinueDrag: query_continue_drag,
+    GiveFeedback: give_feedback,
+};
+
+/// Run a drag carrying `data`, returning once it is over.
+pub(crate) unsafe fn do_drag(data: &DragData) -> Result<DropEffect, Error> {
+    let mut object: *mut IDataObject = null_mut();
+    as_result(SHCreateDataObject(
+        null(),
+        0,
+        null(),
+        null_mut(),
+        &IDataObject::uuidof(),
+        &mut object as *mut *mut IDataObject as *mut *mut c_void,
+    ))?;
+    let result = fill_data_object(object, data).and_then(|()| {
+        if let Some(ref image) = data.image {
+            set_drag_image(object, image);
+        }
+        run_drag(object, data.allow_move)
+    });
+    (*object).Release();
+    result
+}
+
+unsafe fn fill_data_object(object: *mut IDataObject, data: &DragData) -> Result<(), Error> {
+    if !data.files.is_empty() {
+        let header = DROPFILES {
+            pFiles: mem::size_of::<DROPFILES>() as DWORD,
+            pt: POINT { x: 0, y: 0 },
+            fNC: FALSE,
+            fWide: TRUE,
+        };
+        let mut bytes = slice::from_raw_parts(
+            &header as *const DROPFILES as *const u8,
+            mem::size_of::<DROPFILES>(),
+        )
+        .to_vec();
+        // Each path is null terminated, and the list ends with another null.
+        for path in &data.files {
+            let wide = path.to_wide();
+            bytes.extend(wide.iter().flat_map(|c| c.to_ne_bytes().to_vec()));
+        }
+        bytes.extend(&[0, 0]);
+        set_hglobal(object, CF_HDROP, &bytes)?;
+    }
+    for format in &data.formats {
+        if format.identifier == ClipboardFormat::TEXT {
+            let wide = String::from_utf8_lossy(&format.data).as_ref().to_wide();
+            let bytes: Vec<u8> = wide.iter().flat_map(|c| c.to_ne_bytes().to_vec()).collect();
+            set_hglobal(object, CF_UNICODETEXT, &bytes)?;
+        } else {
+            let id = register_format(format.identifier);
+            if id == 0 {
+                return Err(Error::Null);
+            }
+            set_hglobal(object, id, &format.data)?;
+        }
+    }
+    Ok(())
+}
+
+/// Put a copy of `bytes` in the data object, in global memory.
+unsafe fn set_hglobal(object: *mut IDataObject, format: UINT, bytes: &[u8]) -> Result<(), Error> {
+    let handle = copy_to_global(bytes.as_ptr(), bytes.len());
+    if handle.is_null() {
+        return Err(Error::Null);
+    }
+    let format = FORMATETC {
+        cfFormat: format as u16,
+        ptd: null(),
+        dwAspect: DVASPECT_CONTENT,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL,
+    };
+    let medium = STGMEDIUM {
+        tymed: TYMED_HGLOBAL,
+        u: handle as *mut STGMEDIUM_u,
+        pUnkForRelease: null_mut(),
+    };
+    // winapi declares the medium argument as a FORMATETC. With `fRelease`,
+    // the data object owns the memory on success.
+    let hr = (*object).SetData(
+        &format,
+        &medium as *const STGMEDIUM as *const FORMATETC,
+        TRUE,
+    );
+    if !SUCCEEDED(hr) {
+        GlobalFree(handle);
+    }
+    as_result(hr)
+}
+
+/// Give the drag and drop helper the image to show. Without it, the drag
+/// just shows the cursor, so failures are not reported.
+unsafe fn set_drag_image(object: *mut IDataObject, image: &DragImage) {
+    let mut helper: *mut IDragSourceHelper = null_mut();
+    let hr = CoCreateInstance(
+        &CLSID_DragDropHelper,
+        null_mut(),
+        CLSCTX_INPROC_SERVER,
+        &IDragSourceHelper::uuidof(),
+        &mut helper as *mut *mut IDragSourceHelper as *mut *mut c_void,
+    );
+    if !SUCCEEDED(hr) {
+        return;
+    }
+    // The helper expects premultiplied BGRA.
+    let icon = &image.image;
+    let bgra: Vec<u8> = icon
+        .image
+        .chunks_exact(4)
+        .flat_map(|p| {
+            let alpha = u16::from(p[3]);
+            let premul = |c: u8| (u16::from(c) * alpha / 255) as u8;
+            vec![premul(p[2]), premul(p[1]), premul(p[0]), p[3]]
+        })
+        .collect();
+    let bitmap = CreateBitmap(
+        icon.width as i32,
+        icon.height as i32,
+        1,
+        32,
+        bgra.as_ptr() as *const c_void,
+    );
+    if !bitmap.is_null() {
+        let info = SHDRAGIMAGE {
+            sizeDragImage: SIZE {
+                cx: icon.width as i32,
+                cy: icon.height as i32,
+            },
+            ptOffset: POINT {
+                x: image.x as i32,
+                y: image.y as i32,
+            },
+            hbmpDragImage: bitmap,
+            crColorKey: CLR_NONE,
+        };
+        // On success, the helper owns the bitmap.
+        if !SUCCEEDED((*helper).InitializeFromBitmap(&info, object)) {
+            DeleteObject(bitmap as _);
+        }
+    }
+    (*helper).Release();
+}
+
+unsafe fn run_drag(object: *mut IDataObject, allow_move: bool) -> Result<DropEffect, Error> {
+    let source = Box::into_raw(Box::new(DropSource {
+        vtbl: &DROP_SOURCE_VTBL,
+        refcount: Cell::new(1),
+    }));
+    let ok_effects = if allow_move {
+        DROPEFFECT_COPY | DROPEFFECT_MOVE
+    } else {
+        DROPEFFECT_COPY
+    };
+    let mut effect = DROPEFFECT_NONE;
+    let hr = DoDragDrop(object, source as *mut IDropSource, ok_effects, &mut effect);
+    release(source as *mut IUnknown);
+    match hr {
+        DRAGDROP_S_DROP if effect & DROPEFFECT_MOVE != 0 => Ok(DropEffect::Move),
+        DRAGDROP_S_DROP if effect & DROPEFFECT_COPY != 0 => Ok(DropEffect::Copy),
+        DRAGDROP_S_DROP | DRAGDROP_S_CANCEL => Ok(DropEffect::None),
+        _ => Err(Error::Hr(hr)),
+    }
+}
+
+unsafe extern "system" fn query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if IsEqualIID(&*riid, &IUnknown::uuidof()) || IsEqualIID(&*riid, &IDropSource::uuidof()) {
+        add_ref(this);
+        *ppv = this as *mut c_void;
+        S_OK
+    } else {
+        *ppv = null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+    let source = &*(this as *const DropSource);
+    let count = source.refcount.get() + 1;
+    source.refcount.set(count);
+    count
+}
+
+unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+    let count = {
+        let source = &*(this as *const DropSource);
+        let count = source.refcount.get() - 1;
+        source.refcount.set(count);
+        count
+    };
+    if count == 0 {
+        mem::drop(Box::from_raw(this as *mut DropSource));
+    }
+    count
+}
+
+unsafe extern "system" fn query_continue_drag(
+    _this: *mut IDropSource,
+    escape_pressed: BOOL,
+    key_state: DWORD,
+) -> HRESULT {
+    if escape_pressed != FALSE {
+        DRAGDROP_S_CANCEL
+    } else if key_state & (MK_LBUTTON | MK_RBUTTON) as DWORD == 0 {
+        DRAGDROP_S_DROP
+    } else {
+        S_OK
+    }
+}
+
+unsafe extern "system" fn give_feedback(_this: *mut IDropSource, _effect: DWORD) -> HRESULT {
+    DRAGDROP_S_USEDEFAULTCURSORS
+}
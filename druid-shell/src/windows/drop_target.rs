@@ -0,0 +1,251 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Files dropped from other applications, Windows implementation.
+//!
+//! The OLE drop target forwards each stage of the drag to the window
+//! procedure as an `XI_FILE_DRAG` message, so that the handler is only ever
+//! called from the window procedure.
+
+use std::cell::Cell;
+use std::ffi::OsString;
+use std::mem;
+use std::os::windows::ffi::OsStringExt;
+use std::path::PathBuf;
+use std::ptr::{null, null_mut};
+
+use winapi::ctypes::c_void;
+use winapi::shared::guiddef::{IsEqualIID, REFIID};
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::shared::winerror::*;
+use winapi::shared::wtypes::DVASPECT_CONTENT;
+use winapi::um::objidl::{IDataObject, FORMATETC, STGMEDIUM, TYMED_HGLOBAL};
+use winapi::um::ole2::{OleInitialize, RegisterDragDrop, RevokeDragDrop};
+use winapi::um::oleidl::{IDropTarget, IDropTargetVtbl, DROPEFFECT_COPY, DROPEFFECT_NONE};
+use winapi::um::shellapi::{DragQueryFileW, HDROP};
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winnt::HRESULT;
+use winapi::um::winuser::{ScreenToClient, SendMessageW, CF_HDROP};
+use winapi::Interface;
+
+use super::XI_FILE_DRAG;
+
+#[link(name = "ole32")]
+extern "system" {
+    fn ReleaseStgMedium(medium: *mut STGMEDIUM);
+}
+
+/// The stage of a file drag, sent as the wparam of `XI_FILE_DRAG`.
+pub(crate) const FILE_DRAG_ENTER: WPARAM = 0;
+pub(crate) const FILE_DRAG_OVER: WPARAM = 1;
+pub(crate) const FILE_DRAG_LEAVE: WPARAM = 2;
+pub(crate) const FILE_DROP: WPARAM = 3;
+
+/// The payload of `XI_FILE_DRAG`, passed by pointer in the lparam.
+pub(crate) struct FileDrag {
+    pub paths: Vec<PathBuf>,
+    /// Position in client area pixels.
+    pub x: i32,
+    pub y: i32,
+    /// Set by the window procedure when the handler accepts the files.
+    pub accepted: bool,
+}
+
+#[repr(C)]
+struct DropTarget {
+    vtbl: *const IDropTargetVtbl,
+    refcount: Cell<ULONG>,
+    hwnd: HWND,
+}
+
+static DROP_TARGET_VTBL: IDropTargetVtbl = IDropTargetVtbl {
+    parent: IUnknownVtbl {
+        QueryInterface: query_interface,
+        AddRef: add_ref,
+        Release: release,
+    },
+    DragEnter: drag_enter,
+    DragOver: drag_over,
+    DragLeave: drag_leave,
+    Drop: drop,
+};
+
+/// Make the window accept files dropped from other applications.
+pub(crate) unsafe fn register(hwnd: HWND) {
+    // This is a no-op if OLE is already initialized on the thread.
+    OleInitialize(null_mut());
+    let target = Box::into_raw(Box::new(DropTarget {
+        vtbl: &DROP_TARGET_VTBL,
+        refcount: Cell::new(1),
+        hwnd,
+    }));
+    let hr = RegisterDragDrop(hwnd, target as *mut IDropTarget);
+    if !SUCCEEDED(hr) {
+        println!("RegisterDragDrop failed: 0x{:x}", hr);
+    }
+    // On success, OLE holds its own reference until `revoke`.
+    release(target as *mut IUnknown);
+}
+
+pub(crate) unsafe fn revoke(hwnd: HWND) {
+    RevokeDragDrop(hwnd);
+}
+
+unsafe extern "system" fn query_interface(
+    this: *mut IUnknown,
+    riid: REFIID,
+    ppv: *mut *mut c_void,
+) -> HRESULT {
+    if IsEqualIID(&*riid, &IUnknown::uuidof()) || IsEqualIID(&*riid, &IDropTarget::uuidof()) {
+        add_ref(this);
+        *ppv = this as *mut c_void;
+        S_OK
+    } else {
+        *ppv = null_mut();
+        E_NOINTERFACE
+    }
+}
+
+unsafe extern "system" fn add_ref(this: *mut IUnknown) -> ULONG {
+    let target = &*(this as *const DropTarget);
+    let count = target.refcount.get() + 1;
+    target.refcount.set(count);
+    count
+}
+
+unsafe extern "system" fn release(this: *mut IUnknown) -> ULONG {
+    let count = {
+        let target = &*(this as *const DropTarget);
+        let count = target.refcount.get() - 1;
+        target.refcount.set(count);
+        count
+    };
+    if count == 0 {
+        mem::drop(Box::from_raw(this as *mut DropTarget));
+    }
+    count
+}
+
+unsafe extern "system" fn drag_enter(
+    this: *mut IDropTarget,
+    data: *const IDataObject,
+    _key_state: DWORD,
+    pt: *const POINTL,
+    effect: *mut DWORD,
+) -> HRESULT {
+    let paths = get_paths(data);
+    let accepted = !paths.is_empty() && send(this, FILE_DRAG_ENTER, paths, pt);
+    *effect = if accepted {
+        DROPEFFECT_COPY
+    } else {
+        DROPEFFECT_NONE
+    };
+    S_OK
+}
+
+unsafe extern "system" fn drag_over(
+    this: *mut IDropTarget,
+    _key_state: DWORD,
+    pt: *const POINTL,
+    effect: *mut DWORD,
+) -> HRESULT {
+    let accepted = send(this, FILE_DRAG_OVER, Vec::new(), pt);
+    *effect = if accepted {
+        DROPEFFECT_COPY
+    } else {
+        DROPEFFECT_NONE
+    };
+    S_OK
+}
+
+unsafe extern "system" fn drag_leave(this: *mut IDropTarget) -> HRESULT {
+    send(this, FILE_DRAG_LEAVE, Vec::new(), null());
+    S_OK
+}
+
+unsafe extern "system" fn drop(
+    this: *mut IDropTarget,
+    data: *const IDataObject,
+    _key_state: DWORD,
+    pt: *const POINTL,
+    effect: *mut DWORD,
+) -> HRESULT {
+    let paths = get_paths(data);
+    let accepted = !paths.is_empty() && send(this, FILE_DROP, paths, pt);
+    *effect = if accepted {
+        DROPEFFECT_COPY
+    } else {
+        DROPEFFECT_NONE
+    };
+    S_OK
+}
+
+/// Forward a stage of the drag to the window procedure, returning whether
+/// the handler accepted it.
+unsafe fn send(
+    this: *mut IDropTarget,
+    stage: WPARAM,
+    paths: Vec<PathBuf>,
+    pt: *const POINTL,
+) -> bool {
+    let hwnd = (*(this as *const DropTarget)).hwnd;
+    let mut point = POINT { x: 0, y: 0 };
+    if !pt.is_null() {
+        point.x = (*pt).x;
+        point.y = (*pt).y;
+        ScreenToClient(hwnd, &mut point);
+    }
+    let mut drag = FileDrag {
+        paths,
+        x: point.x,
+        y: point.y,
+        accepted: false,
+    };
+    SendMessageW(
+        hwnd,
+        XI_FILE_DRAG,
+        stage,
+        &mut drag as *mut FileDrag as LPARAM,
+    );
+    drag.accepted
+}
+
+/// The file paths in a data object, if it holds any.
+unsafe fn get_paths(data: *const IDataObject) -> Vec<PathBuf> {
+    let format = FORMATETC {
+        cfFormat: CF_HDROP as u16,
+        ptd: null(),
+        dwAspect: DVASPECT_CONTENT,
+        lindex: -1,
+        tymed: TYMED_HGLOBAL,
+    };
+    let mut medium: STGMEDIUM = mem::zeroed();
+    if !SUCCEEDED((*data).GetData(&format, &mut medium)) {
+        return Vec::new();
+    }
+    // The union in winapi's STGMEDIUM is declared as a pointer; for
+    // TYMED_HGLOBAL its value is the HGLOBAL itself.
+    let hdrop = medium.u as HDROP;
+    let count = DragQueryFileW(hdrop, 0xFFFF_FFFF, null_mut(), 0);
+    let mut paths = Vec::with_capacity(count as usize);
+    for i in 0..count {
+        let len = DragQueryFileW(hdrop, i, null_mut(), 0) as usize;
+        let mut buf = vec![0u16; len + 1];
+        DragQueryFileW(hdrop, i, buf.as_mut_ptr(), buf.len() as UINT);
+        paths.push(PathBuf::from(OsString::from_wide(&buf[..len])));
+    }
+    ReleaseStgMedium(&mut medium);
+    paths
+}
@@ -0,0 +1,94 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A clock ticking with the display refresh, Windows implementation.
+//!
+//! A thread waits for the next composition with `DwmFlush` and then posts
+//! `XI_FRAME` to the window. It only ticks while a frame has been requested,
+//! and requests made during one refresh are combined into a single tick.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::SUCCEEDED;
+use winapi::um::winuser::PostMessageW;
+
+use super::{DwmFlush, XI_FRAME};
+
+pub(crate) struct FrameClock {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<ClockState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct ClockState {
+    requested: bool,
+    stopped: bool,
+}
+
+impl FrameClock {
+    pub(crate) fn new(hwnd: HWND) -> FrameClock {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(ClockState::default()),
+            cond: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        // Window handles may be used from any thread, but aren't `Send`.
+        let hwnd = hwnd as usize;
+        thread::spawn(move || run(&thread_shared, hwnd as HWND));
+        FrameClock { shared }
+    }
+
+    /// Post `XI_FRAME` to the window at the next display refresh.
+    pub(crate) fn request(&self) {
+        self.shared.state.lock().unwrap().requested = true;
+        self.shared.cond.notify_one();
+    }
+}
+
+impl Drop for FrameClock {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.cond.notify_one();
+    }
+}
+
+fn run(shared: &Shared, hwnd: HWND) {
+    loop {
+        {
+            let mut state = shared.state.lock().unwrap();
+            while !state.requested && !state.stopped {
+                state = shared.cond.wait(state).unwrap();
+            }
+            if state.stopped {
+                return;
+            }
+            state.requested = false;
+        }
+        // This fails when desktop composition is off, as on Windows 7 with
+        // the basic theme; fall back to the usual refresh rate.
+        if !SUCCEEDED(unsafe { DwmFlush() }) {
+            thread::sleep(Duration::from_millis(16));
+        }
+        unsafe {
+            PostMessageW(hwnd, XI_FRAME, 0, 0);
+        }
+    }
+}
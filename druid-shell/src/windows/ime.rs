@@ -0,0 +1,151 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Input method support, Windows implementation.
+//!
+//! This uses the IMM32 API, which input methods built on the Text Services
+//! Framework support as well. The application draws the composition itself,
+//! so the composition window of the input method is hidden; only its
+//! candidate window is shown, next to the caret rectangle.
+
+#![allow(non_snake_case)]
+
+use std::ptr::null_mut;
+
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::um::winnt::{HANDLE, LONG};
+
+type HIMC = HANDLE;
+
+#[repr(C)]
+struct COMPOSITIONFORM {
+    dwStyle: DWORD,
+    ptCurrentPos: POINT,
+    rcArea: RECT,
+}
+
+#[repr(C)]
+struct CANDIDATEFORM {
+    dwIndex: DWORD,
+    dwStyle: DWORD,
+    ptCurrentPos: POINT,
+    rcArea: RECT,
+}
+
+#[link(name = "imm32")]
+extern "system" {
+    fn ImmGetContext(hwnd: HWND) -> HIMC;
+    fn ImmReleaseContext(hwnd: HWND, himc: HIMC) -> BOOL;
+    fn ImmGetCompositionStringW(himc: HIMC, index: DWORD, buf: LPVOID, len: DWORD) -> LONG;
+    fn ImmSetCompositionWindow(himc: HIMC, form: *const COMPOSITIONFORM) -> BOOL;
+    fn ImmSetCandidateWindow(himc: HIMC, form: *const CANDIDATEFORM) -> BOOL;
+    fn ImmNotifyIME(himc: HIMC, action: DWORD, index: DWORD, value: DWORD) -> BOOL;
+}
+
+/// Flags in the lparam of `WM_IME_COMPOSITION`.
+pub(crate) const GCS_COMPSTR: LPARAM = 0x0008;
+const GCS_CURSORPOS: LPARAM = 0x0080;
+pub(crate) const GCS_RESULTSTR: LPARAM = 0x0800;
+
+/// Flag in the lparam of `WM_IME_SETCONTEXT`.
+pub(crate) const ISC_SHOWUICOMPOSITIONWINDOW: LPARAM = 0x8000_0000;
+
+/// The action and index of `ImmNotifyIME` to cancel the composition.
+const NI_COMPOSITIONSTR: DWORD = 0x0015;
+const CPS_CANCEL: DWORD = 0x0004;
+
+const CFS_POINT: DWORD = 0x0002;
+const CFS_EXCLUDE: DWORD = 0x0080;
+
+/// The text committed by the current `WM_IME_COMPOSITION` message.
+pub(crate) unsafe fn get_result(hwnd: HWND) -> String {
+    let himc = ImmGetContext(hwnd);
+    if himc.is_null() {
+        return String::new();
+    }
+    let text = String::from_utf16_lossy(&get_string(himc, GCS_RESULTSTR));
+    ImmReleaseContext(hwnd, himc);
+    text
+}
+
+/// The text being composed, and the position of the cursor in it in bytes.
+pub(crate) unsafe fn get_composition(hwnd: HWND) -> (String, usize) {
+    let himc = ImmGetContext(hwnd);
+    if himc.is_null() {
+        return (String::new(), 0);
+    }
+    let wide = get_string(himc, GCS_COMPSTR);
+    let cursor = ImmGetCompositionStringW(himc, GCS_CURSORPOS as DWORD, null_mut(), 0);
+    ImmReleaseContext(hwnd, himc);
+    let cursor = (cursor.max(0) as usize).min(wide.len());
+    let cursor = String::from_utf16_lossy(&wide[..cursor]).len();
+    (String::from_utf16_lossy(&wide), cursor)
+}
+
+unsafe fn get_string(himc: HIMC, index: LPARAM) -> Vec<u16> {
+    // The length is in bytes.
+    let len = ImmGetCompositionStringW(himc, index as DWORD, null_mut(), 0);
+    if len <= 0 {
+        return Vec::new();
+    }
+    let mut buf = vec![0u16; len as usize / 2];
+    ImmGetCompositionStringW(
+        himc,
+        index as DWORD,
+        buf.as_mut_ptr() as LPVOID,
+        len as DWORD,
+    );
+    buf
+}
+
+/// Discard the composition of the input method, which sends the messages
+/// ending it before returning.
+pub(crate) unsafe fn cancel_composition(hwnd: HWND) {
+    let himc = ImmGetContext(hwnd);
+    if himc.is_null() {
+        return;
+    }
+    ImmNotifyIME(himc, NI_COMPOSITIONSTR, CPS_CANCEL, 0);
+    ImmReleaseContext(hwnd, himc);
+}
+
+/// Place the windows of the input method next to the caret, given in
+/// client area pixels.
+pub(crate) unsafe fn set_caret_rect(hwnd: HWND, caret: RECT) {
+    let himc = ImmGetContext(hwnd);
+    if himc.is_null() {
+        return;
+    }
+    let composition = COMPOSITIONFORM {
+        dwStyle: CFS_POINT,
+        ptCurrentPos: POINT {
+            x: caret.left,
+            y: caret.top,
+        },
+        rcArea: caret,
+    };
+    ImmSetCompositionWindow(himc, &composition);
+    let candidate = CANDIDATEFORM {
+        dwIndex: 0,
+        dwStyle: CFS_EXCLUDE,
+        ptCurrentPos: POINT {
+            x: caret.left,
+            y: caret.bottom,
+        },
+        rcArea: caret,
+    };
+    ImmSetCandidateWindow(himc, &candidate);
+    ImmReleaseContext(hwnd, himc);
+}
@@ -21,7 +21,7 @@ use winapi::shared::basetsd::*;
 use winapi::shared::windef::*;
 use winapi::um::winuser::*;
 
-use crate::keycodes::MenuKey;
+use crate::keycodes::{KeySpec, MenuKey};
 use crate::util::ToWide;
 
 /// A menu object, which can be either a top-level menubar or a
@@ -46,6 +46,18 @@ impl Menu {
         }
     }
 
+    /// Create a menu to be shown with `WindowHandle::show_context_menu`.
+    pub fn new_for_popup() -> Menu {
+        unsafe {
+            let hmenu = CreatePopupMenu();
+            Menu { hmenu }
+        }
+    }
+
+    pub(crate) fn hmenu(&self) -> HMENU {
+        self.hmenu
+    }
+
     pub fn into_hmenu(self) -> HMENU {
         let hmenu = self.hmenu;
         mem::forget(self);
@@ -68,15 +80,37 @@ impl Menu {
     }
 
     /// Add an item to the menu.
-    pub fn add_item(&mut self, id: u32, text: &str, _key: impl Into<MenuKey>) {
-        // TODO: actually wire up accelerators for key.
+    ///
+    /// The key is displayed next to the item text; it is not registered as an
+    /// accelerator, so the key events should be handled (for example through
+    /// a keymap) by the application.
+    pub fn add_item(&mut self, id: u32, text: &str, key: impl Into<MenuKey>) {
+        self.add_item_with_state(id, text, key, true, false);
+    }
+
+    /// Add an item to the menu, which can be disabled or show a checkmark.
+    pub fn add_item_with_state(
+        &mut self,
+        id: u32,
+        text: &str,
+        key: impl Into<MenuKey>,
+        enabled: bool,
+        checked: bool,
+    ) {
+        let key = key.into();
+        let text = match key.key {
+            KeySpec::None => text.to_string(),
+            _ => format!("{}\t{}", text, key),
+        };
+        let mut flags = MF_STRING;
+        if !enabled {
+            flags |= MF_GRAYED;
+        }
+        if checked {
+            flags |= MF_CHECKED;
+        }
         unsafe {
-            AppendMenuW(
-                self.hmenu,
-                MF_STRING,
-                id as UINT_PTR,
-                text.to_wide().as_ptr(),
-            );
+            AppendMenuW(self.hmenu, flags, id as UINT_PTR, text.to_wide().as_ptr());
         }
     }
 
@@ -16,11 +16,24 @@
 
 #![allow(non_snake_case)]
 
+mod accessibility;
 pub mod application;
+mod backdrop;
+pub mod clipboard;
 pub mod dcomp;
-pub mod dialog;
+mod dialog;
+mod drag_source;
+mod drop_target;
+mod frame_clock;
+mod ime;
 pub mod menu;
+pub mod native_view;
 pub mod paint;
+mod soft_keyboard;
+pub mod surface;
+mod theme;
+mod timer;
+mod tray;
 pub mod util;
 pub mod win_main;
 
@@ -31,6 +44,7 @@ use std::mem;
 use std::ptr::{null, null_mut};
 use std::rc::{Rc, Weak};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 use winapi::ctypes::{c_int, c_void};
 use winapi::shared::basetsd::*;
@@ -42,6 +56,7 @@ use winapi::shared::minwindef::*;
 use winapi::shared::windef::*;
 use winapi::shared::winerror::*;
 use winapi::um::d2d1::*;
+use winapi::um::errhandlingapi::GetLastError;
 use winapi::um::unknwnbase::*;
 use winapi::um::wingdi::*;
 use winapi::um::winnt::*;
@@ -52,19 +67,33 @@ use direct2d;
 use direct2d::math::SizeU;
 use direct2d::render_target::{GenericRenderTarget, HwndRenderTarget, RenderTarget};
 
-use piet_common::{Piet, RenderContext};
+use piet_common::kurbo::Rect;
+use piet_common::{Color, Piet, RenderContext};
 
+use crate::accessibility::{AccessTree, Politeness};
+use crate::alert::{AlertButtons, AlertOptions, AlertResponse, AlertStyle};
+use crate::blend::Painting;
+use crate::dialog::{FileDialogOptions, FileDialogType, FileInfo};
+use crate::drag::{DragData, DropEffect};
+use crate::keycodes::{KeySpec, MenuKey, M_ALT, M_CTRL, M_META, M_SHIFT};
 use crate::menu::Menu;
+use crate::timer::{TimerQueue, TimerToken, COALESCE_SLACK};
 use crate::util::{as_result, FromWide, ToWide, OPTIONAL_FUNCTIONS};
 use crate::Error;
+use application::Application;
 use dcomp::{D3D11Device, DCompositionDevice, DCompositionTarget, DCompositionVisual};
-use dialog::{get_file_dialog_path, FileDialogOptions, FileDialogType};
+use dialog::get_file_dialog_paths;
 
-use crate::keyboard::{KeyCode, KeyEvent, KeyModifiers};
-use crate::window::{self, Cursor, MouseButton, MouseEvent, WinHandler};
+use crate::keyboard::{Key, KeyCode, KeyEvent, KeyLocation, KeyModifiers};
+use crate::window::{
+    self, CompositionEvent, Cursor, CursorDesc, IconDesc, MouseButton, MouseEvent, PenEvent,
+    PenPhase, PowerSource, RawDisplayHandle, RawWindowHandle, Theme, TouchEvent, TouchPhase,
+    WheelEvent, WheelPhase, WheelUnit, WinHandler, WindowBackdrop, WindowGeometry, WindowKind,
+    WindowLevel,
+};
 
 extern "system" {
-    pub fn DwmFlush();
+    pub fn DwmFlush() -> winapi::um::winnt::HRESULT;
 }
 
 /// Builder abstraction for creating new windows.
@@ -75,6 +104,16 @@ pub struct WindowBuilder {
     cursor: Cursor,
     menu: Option<Menu>,
     present_strategy: PresentStrategy,
+    kind: WindowKind,
+    level: WindowLevel,
+    owner: Option<WindowHandle>,
+    position: Option<(f64, f64)>,
+    centered: bool,
+    size: Option<(f64, f64)>,
+    vsync: bool,
+    icons: Vec<IconDesc>,
+    backdrop: WindowBackdrop,
+    parent: Option<RawWindowHandle>,
 }
 
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -132,6 +171,92 @@ struct WindowState {
     dpi: Cell<f32>,
     wndproc: Box<dyn WndProc>,
     idle_queue: Arc<Mutex<Vec<Box<dyn IdleCallback>>>>,
+    /// Set while we release the mouse capture ourselves, so that the
+    /// resulting `WM_CAPTURECHANGED` is not reported to the handler.
+    releasing_capture: Cell<bool>,
+    /// The cursor shown while the pointer is in the client area.
+    cursor: RefCell<Cursor>,
+    /// The state last reported to the handler.
+    window_state: Cell<window::WindowState>,
+    /// While fullscreen, the style and placement to return to.
+    fullscreen_restore: Cell<Option<(DWORD, WINDOWPLACEMENT)>>,
+    tray: RefCell<Option<tray::TrayIcon>>,
+    /// The system theme last applied to the window.
+    theme: Cell<Theme>,
+    /// The appearance settings and power source last reported to the
+    /// handler.
+    accent_color: Cell<Option<u32>>,
+    reduce_transparency: Cell<bool>,
+    power_source: Cell<PowerSource>,
+    text_scale: Cell<f64>,
+    /// The caret rectangle for the input method, in px.
+    caret_rect: Cell<Option<Rect>>,
+    /// Whether presenting waits for the display refresh.
+    vsync: Cell<bool>,
+    /// Whether `WinHandler::frame` is due at the next refresh.
+    frame_requested: Cell<bool>,
+    /// Whether the window is to be painted at the next refresh.
+    paint_requested: Cell<bool>,
+    frame_clock: RefCell<Option<frame_clock::FrameClock>>,
+    /// The big and small icons set with `set_icon`, destroyed with the window.
+    icons: Cell<(HICON, HICON)>,
+    /// The backdrop shown behind the client area.
+    backdrop: Cell<WindowBackdrop>,
+    timers: RefCell<TimerQueue<Instant>>,
+    timer_thread: RefCell<Option<timer::TimerThread>>,
+    /// Whether the window presents through DirectComposition.
+    composited: Cell<bool>,
+    /// Whether the window is a child of a window of the host application.
+    embedded: bool,
+    /// The tree given to `update_accessibility`, and its UI Automation
+    /// providers.
+    access: RefCell<accessibility::AccessState>,
+}
+
+impl WindowState {
+    /// Ask for `XI_TIMER` at the earliest deadline of the timers.
+    fn schedule_timers(&self) {
+        let deadline = self.timers.borrow().next_deadline();
+        let mut thread = self.timer_thread.borrow_mut();
+        thread
+            .get_or_insert_with(|| timer::TimerThread::new(self.hwnd.get()))
+            .set_deadline(deadline);
+    }
+
+    /// Ask for `XI_FRAME` at the next display refresh.
+    fn request_tick(&self) {
+        let mut clock = self.frame_clock.borrow_mut();
+        clock
+            .get_or_insert_with(|| frame_clock::FrameClock::new(self.hwnd.get()))
+            .request();
+    }
+
+    fn apply_caret_rect(&self) {
+        if let Some(rect) = self.caret_rect.get() {
+            let scale = f64::from(self.dpi.get() / 96.0);
+            let caret = RECT {
+                left: (rect.x0 * scale) as i32,
+                top: (rect.y0 * scale) as i32,
+                right: (rect.x1 * scale) as i32,
+                bottom: (rect.y1 * scale) as i32,
+            };
+            unsafe { ime::set_caret_rect(self.hwnd.get(), caret) };
+        }
+    }
+}
+
+/// A cursor made from an image, destroyed when the last clone is dropped.
+#[derive(Clone)]
+pub struct CustomCursor(Rc<HCursor>);
+
+struct HCursor(HCURSOR);
+
+impl Drop for HCursor {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyCursor(self.0);
+        }
+    }
 }
 
 /// Generic handler trait for the winapi window procedure entry point.
@@ -159,9 +284,18 @@ struct WndState {
     /// The `KeyCode` of the last `WM_KEYDOWN` event. We stash this so we can
     /// include it when handling `WM_CHAR` events.
     stashed_key_code: KeyCode,
+    /// The logical key of the last `WM_KEYDOWN` event, for `WM_CHAR` events
+    /// whose character is a control character, as when control is held.
+    stashed_key: Key,
     /// The `char` of the last `WM_CHAR` event, if there has not already been
     /// a `WM_KEYUP` event.
     stashed_char: Option<char>,
+    /// Whether an input method composition is in progress.
+    composing: bool,
+    /// The vsync setting the render target was made for.
+    vsync: bool,
+    /// Whether the render target keeps alpha, for a backdrop to show through.
+    transparent: bool,
     //TODO: track surrogate orphan
 }
 
@@ -179,6 +313,29 @@ struct DCompState {
 /// Message indicating there are idle tasks to run.
 const XI_RUN_IDLE: UINT = WM_USER;
 
+/// Message carrying a stage of a file drag from another application.
+const XI_FILE_DRAG: UINT = WM_USER + 1;
+
+/// Message carrying a mouse event on the tray icon, in the lparam.
+const XI_TRAY: UINT = WM_USER + 2;
+
+/// Message sent by the frame clock at a display refresh.
+const XI_FRAME: UINT = WM_USER + 3;
+
+/// Message carrying a boxed `DragData` to start a drag with, in the lparam.
+const XI_START_DRAG: UINT = WM_USER + 4;
+
+/// Message sent by the timer thread when the earliest timer is due.
+const XI_TIMER: UINT = WM_USER + 5;
+
+/// Message asking to cancel the composition of the input method, posted so
+/// that it isn't ended while the handler is running.
+const XI_CANCEL_COMPOSITION: UINT = WM_USER + 6;
+
+/// Message carrying an action asked for by assistive technology, as a
+/// pointer to an `ActionMessage` in the lparam.
+const XI_ACCESS_ACTION: UINT = WM_USER + 7;
+
 impl Default for PresentStrategy {
     fn default() -> PresentStrategy {
         // We probably want to change this, but we need GDI to work. Too bad about
@@ -187,6 +344,65 @@ impl Default for PresentStrategy {
     }
 }
 
+/// The pen state of a pointer, or `None` if it's not a pen. Requires
+/// Windows 8 or later.
+unsafe fn get_pen_info(pointer_id: UINT32) -> Option<POINTER_PEN_INFO> {
+    let get_type = OPTIONAL_FUNCTIONS.GetPointerType?;
+    let get_pen_info = OPTIONAL_FUNCTIONS.GetPointerPenInfo?;
+    let mut pointer_type = 0;
+    if get_type(pointer_id, &mut pointer_type) == 0 || pointer_type != PT_PEN {
+        return None;
+    }
+    let mut info: POINTER_PEN_INFO = mem::zeroed();
+    if get_pen_info(pointer_id, &mut info) == 0 {
+        return None;
+    }
+    Some(info)
+}
+
+/// Fill in the physical key of a key event, and its location, from the
+/// scancode in the lparam of a key message.
+fn set_physical_key(event: &mut KeyEvent, lparam: LPARAM) {
+    let scancode = ((lparam >> 16) & 0xFF) as u32;
+    let extended = lparam & (1 << 24) != 0;
+    if let Some(code) = KeyCode::from_scancode(scancode, extended) {
+        event.code = code;
+    }
+    event.location = match (event.code, scancode, extended) {
+        (KeyCode::Shift, 0x2A, _)
+        | (KeyCode::Control, _, false)
+        | (KeyCode::Alt, _, false)
+        | (KeyCode::Meta, 0x5B, _) => KeyLocation::Left,
+        (KeyCode::Shift, ..)
+        | (KeyCode::Control, ..)
+        | (KeyCode::Alt, ..)
+        | (KeyCode::Meta, ..) => KeyLocation::Right,
+        (code, ..) if code.is_numpad() => KeyLocation::Numpad,
+        _ => KeyLocation::Standard,
+    };
+}
+
+/// The logical key of a virtual key under the active layout, ignoring
+/// control and alt.
+fn vk_to_key(vk: i32, key_code: KeyCode, shift: bool) -> Key {
+    // The top bit marks dead keys. Only the unshifted character is reported,
+    // so shifted digits and punctuation come out as their base character.
+    let raw = unsafe { MapVirtualKeyW(vk as UINT, MAPVK_VK_TO_CHAR) };
+    if raw & 0x8000_0000 != 0 {
+        return Key::Dead;
+    }
+    match std::char::from_u32(raw) {
+        Some(c) if c != '\0' && !c.is_control() => {
+            if shift {
+                Key::Character(c)
+            } else {
+                Key::Character(c.to_lowercase().next().unwrap_or(c))
+            }
+        }
+        _ => Key::Named(key_code),
+    }
+}
+
 /// Must only be called while handling an input message.
 /// This queries the keyboard state at the time of message delivery.
 fn get_mod_state() -> KeyModifiers {
@@ -226,6 +442,7 @@ impl MyWndProc {
         rt.begin_draw();
         let anim;
         {
+            let _painting = Painting::new(Some(&rt.clone()));
             let mut piet_ctx = Piet::new(&self.d2d_factory, &self.dwrite_factory, rt);
             anim = self.handler.paint(&mut piet_ctx);
             if let Err(e) = piet_ctx.finish() {
@@ -239,12 +456,40 @@ impl MyWndProc {
             println!("EndDraw error: {:?}", e);
         }
         if anim {
+            if let Some(w) = self.handle.borrow().0.upgrade() {
+                if w.vsync.get() {
+                    w.paint_requested.set(true);
+                    w.request_tick();
+                    return;
+                }
+            }
             let handle = self.handle.borrow().get_idle_handle().unwrap();
             // Note: maybe add WindowHandle as arg to idle handler so we don't need this.
             let handle2 = handle.clone();
             handle.add_idle(move |_| handle2.invalidate());
         }
     }
+
+    /// Report a change of window state, given the `wparam` of `WM_SIZE`.
+    fn update_window_state(&self, size_type: WPARAM) {
+        let w = match self.handle.borrow().0.upgrade() {
+            Some(w) => w,
+            None => return,
+        };
+        let state = match size_type {
+            SIZE_MAXIMIZED => window::WindowState::Maximized,
+            SIZE_MINIMIZED => window::WindowState::Minimized,
+            SIZE_RESTORED if w.fullscreen_restore.get().is_some() => {
+                window::WindowState::Fullscreen
+            }
+            SIZE_RESTORED => window::WindowState::Restored,
+            // Sent when some other window is maximized or restored.
+            _ => return,
+        };
+        if w.window_state.replace(state) != state {
+            self.handler.window_state_changed(state);
+        }
+    }
 }
 
 impl WndProc for MyWndProc {
@@ -267,6 +512,19 @@ impl WndProc for MyWndProc {
         match msg {
             WM_ERASEBKGND => Some(0),
             WM_PAINT => unsafe {
+                let vsync = match self.handle.borrow().0.upgrade() {
+                    Some(w) => w.vsync.get(),
+                    None => true,
+                };
+                {
+                    // Hwnd render targets take the present option when created.
+                    let mut state = self.state.borrow_mut();
+                    let s = state.as_mut().unwrap();
+                    if s.dcomp_state.is_none() && s.vsync != vsync {
+                        s.render_target = None;
+                    }
+                    s.vsync = vsync;
+                }
                 if self
                     .state
                     .borrow()
@@ -275,8 +533,10 @@ impl WndProc for MyWndProc {
                     .render_target
                     .is_none()
                 {
-                    let rt = paint::create_render_target(&self.d2d_factory, hwnd)
-                        .map(|rt| rt.as_generic());
+                    let transparent = self.state.borrow().as_ref().unwrap().transparent;
+                    let rt =
+                        paint::create_render_target(&self.d2d_factory, hwnd, vsync, transparent)
+                            .map(|rt| rt.as_generic());
                     self.state.borrow_mut().as_mut().unwrap().render_target = rt.ok();
                 }
                 self.render();
@@ -284,7 +544,7 @@ impl WndProc for MyWndProc {
                 let s = state.as_mut().unwrap();
                 if let Some(ref mut ds) = s.dcomp_state {
                     if !ds.sizing {
-                        (*ds.swap_chain).Present(1, 0);
+                        (*ds.swap_chain).Present(if vsync { 1 } else { 0 }, 0);
                         let _ = ds.dcomp_device.commit();
                     }
                 }
@@ -293,7 +553,9 @@ impl WndProc for MyWndProc {
             },
             WM_ENTERSIZEMOVE => unsafe {
                 if self.state.borrow().as_ref().unwrap().dcomp_state.is_some() {
-                    let rt = paint::create_render_target(&self.d2d_factory, hwnd)
+                    let vsync = self.state.borrow().as_ref().unwrap().vsync;
+                    // Windows with a swapchain have no backdrop.
+                    let rt = paint::create_render_target(&self.d2d_factory, hwnd, vsync, false)
                         .map(|rt| rt.as_generic());
                     self.state.borrow_mut().as_mut().unwrap().render_target = rt.ok();
                     self.handler.rebuild_resources();
@@ -354,6 +616,7 @@ impl WndProc for MyWndProc {
                 let width = LOWORD(lparam as u32) as u32;
                 let height = HIWORD(lparam as u32) as u32;
                 self.handler.size(width, height);
+                self.update_window_state(wparam);
                 let use_hwnd = if let Some(ref dcomp_state) =
                     self.state.borrow().as_ref().unwrap().dcomp_state
                 {
@@ -407,6 +670,10 @@ impl WndProc for MyWndProc {
                 self.handler.command(LOWORD(wparam as u32) as u32);
                 Some(0)
             }
+            WM_HOTKEY => {
+                self.handler.command(wparam as u32);
+                Some(0)
+            }
             WM_CHAR => {
                 let mut state = self.state.borrow_mut();
                 let mut s = state.as_mut().unwrap();
@@ -423,7 +690,11 @@ impl WndProc for MyWndProc {
 
                 let modifiers = get_mod_state();
                 let is_repeat = (lparam & 0xFFFF) > 0;
-                let event = KeyEvent::new(key_code, is_repeat, modifiers, text, text);
+                let mut event = KeyEvent::new(key_code, is_repeat, modifiers, text, text);
+                if text.is_control() {
+                    event.key = s.stashed_key;
+                }
+                set_physical_key(&mut event, lparam);
 
                 if self.handler.key_down(event) {
                     Some(0)
@@ -435,17 +706,19 @@ impl WndProc for MyWndProc {
                 let mut state = self.state.borrow_mut();
                 let mut s = state.as_mut().unwrap();
                 let key_code: KeyCode = (wparam as i32).into();
+                let modifiers = get_mod_state();
                 s.stashed_key_code = key_code;
+                s.stashed_key = vk_to_key(wparam as i32, key_code, modifiers.shift);
                 if key_code.is_printable() {
                     //FIXME: this will fail to propogate key combinations such as alt+s
                     return None;
                 }
 
-                let modifiers = get_mod_state();
                 // bits 0-15 of iparam are the repeat count:
                 // https://docs.microsoft.com/en-ca/windows/desktop/inputdev/wm-keydown
                 let is_repeat = (lparam & 0xFFFF) > 0;
-                let event = KeyEvent::new(key_code, is_repeat, modifiers, "", "");
+                let mut event = KeyEvent::new(key_code, is_repeat, modifiers, "", "");
+                set_physical_key(&mut event, lparam);
 
                 if self.handler.key_down(event) {
                     Some(0)
@@ -453,6 +726,52 @@ impl WndProc for MyWndProc {
                     None
                 }
             }
+            WM_IME_SETCONTEXT => {
+                // The composition is drawn by the handler.
+                let lparam = lparam & !ime::ISC_SHOWUICOMPOSITIONWINDOW;
+                unsafe { Some(DefWindowProcW(hwnd, msg, wparam, lparam)) }
+            }
+            WM_IME_STARTCOMPOSITION => {
+                self.state.borrow_mut().as_mut().unwrap().composing = true;
+                self.handler.composition(&CompositionEvent::Start);
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    w.apply_caret_rect();
+                }
+                Some(0)
+            }
+            WM_IME_COMPOSITION => {
+                let mut state = self.state.borrow_mut();
+                let s = state.as_mut().unwrap();
+                if lparam & ime::GCS_RESULTSTR != 0 {
+                    let text = unsafe { ime::get_result(hwnd) };
+                    s.composing = false;
+                    self.handler.composition(&CompositionEvent::Commit(text));
+                }
+                if lparam & ime::GCS_COMPSTR != 0 {
+                    let (text, cursor) = unsafe { ime::get_composition(hwnd) };
+                    if !s.composing && !text.is_empty() {
+                        s.composing = true;
+                        self.handler.composition(&CompositionEvent::Start);
+                    }
+                    if s.composing {
+                        self.handler
+                            .composition(&CompositionEvent::Update { text, cursor });
+                    }
+                }
+                // Not passed on, so that no `WM_IME_CHAR` messages are sent.
+                Some(0)
+            }
+            WM_IME_ENDCOMPOSITION => {
+                let mut state = self.state.borrow_mut();
+                let s = state.as_mut().unwrap();
+                if s.composing {
+                    // Cancelled, since the result string ends the composition.
+                    s.composing = false;
+                    self.handler
+                        .composition(&CompositionEvent::Commit(String::new()));
+                }
+                Some(0)
+            }
             WM_KEYUP => {
                 let mut state = self.state.borrow_mut();
                 let s = state.as_mut().unwrap();
@@ -460,21 +779,46 @@ impl WndProc for MyWndProc {
                 let modifiers = get_mod_state();
                 let is_repeat = false;
                 let text = s.stashed_char.take();
-                let event = KeyEvent::new(key_code, is_repeat, modifiers, text, text);
+                let mut event = KeyEvent::new(key_code, is_repeat, modifiers, text, text);
+                if key_code.is_printable() && text.map_or(true, char::is_control) {
+                    event.key = vk_to_key(wparam as i32, key_code, modifiers.shift);
+                }
+                set_physical_key(&mut event, lparam);
                 self.handler.key_up(event);
                 Some(0)
             }
             //TODO: WM_SYSCOMMAND
-            WM_MOUSEWHEEL => {
-                let delta = HIWORD(wparam as u32) as i16 as i32;
-                let mods = get_mod_state();
-                self.handler.mouse_wheel(delta, mods);
-                Some(0)
-            }
-            WM_MOUSEHWHEEL => {
-                let delta = HIWORD(wparam as u32) as i16 as i32;
-                let mods = get_mod_state();
-                self.handler.mouse_hwheel(delta, mods);
+            WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
+                let notches = HIWORD(wparam as u32) as i16 as f64 / WHEEL_DELTA as f64;
+                let setting = if msg == WM_MOUSEWHEEL {
+                    SPI_GETWHEELSCROLLLINES
+                } else {
+                    SPI_GETWHEELSCROLLCHARS
+                };
+                let mut per_notch: UINT = 3;
+                unsafe {
+                    SystemParametersInfoW(setting, 0, &mut per_notch as *mut UINT as *mut _, 0);
+                }
+                let (delta, unit) = if per_notch == WHEEL_PAGESCROLL {
+                    (notches, WheelUnit::Pages)
+                } else {
+                    (notches * per_notch as f64, WheelUnit::Lines)
+                };
+                // A positive WM_MOUSEWHEEL delta means the wheel was rotated
+                // away from the user, which scrolls up.
+                let (dx, dy) = if msg == WM_MOUSEWHEEL {
+                    (0.0, -delta)
+                } else {
+                    (delta, 0.0)
+                };
+                let event = WheelEvent {
+                    dx,
+                    dy,
+                    unit,
+                    mods: get_mod_state(),
+                    phase: WheelPhase::None,
+                };
+                self.handler.wheel(&event);
                 Some(0)
             }
             WM_MOUSEMOVE => {
@@ -540,10 +884,251 @@ impl WndProc for MyWndProc {
                 self.handler.mouse(&event);
                 Some(0)
             }
+            WM_POINTERDOWN | WM_POINTERUPDATE | WM_POINTERUP => unsafe {
+                // Only pen input is handled here; other pointer types, and pen
+                // input the handler declines, become mouse (or touch) messages
+                // in DefWindowProc.
+                let info = get_pen_info(LOWORD(wparam as u32) as UINT32)?;
+                let in_contact = info.pointerInfo.pointerFlags & POINTER_FLAG_INCONTACT != 0;
+                let phase = match msg {
+                    WM_POINTERDOWN => PenPhase::Down,
+                    WM_POINTERUP => PenPhase::Up,
+                    _ if in_contact => PenPhase::Move,
+                    _ => PenPhase::Hover,
+                };
+                let pressure = if info.penMask & PEN_MASK_PRESSURE != 0 {
+                    info.pressure as f64 / 1024.0
+                } else if in_contact {
+                    0.5
+                } else {
+                    0.0
+                };
+                let mut point = info.pointerInfo.ptPixelLocation;
+                ScreenToClient(hwnd, &mut point);
+                let event = PenEvent {
+                    x: point.x,
+                    y: point.y,
+                    phase,
+                    pressure,
+                    tilt_x: info.tiltX as f64,
+                    tilt_y: info.tiltY as f64,
+                    barrel: info.penFlags & PEN_FLAG_BARREL != 0,
+                    eraser: info.penFlags & (PEN_FLAG_ERASER | PEN_FLAG_INVERTED) != 0,
+                    mods: get_mod_state(),
+                };
+                if self.handler.pen(&event) {
+                    Some(0)
+                } else {
+                    None
+                }
+            },
+            WM_TOUCH => unsafe {
+                let count = LOWORD(wparam as u32) as usize;
+                let touch_handle = lparam as HTOUCHINPUT;
+                let mut inputs: Vec<TOUCHINPUT> = vec![mem::zeroed(); count];
+                let size = mem::size_of::<TOUCHINPUT>() as c_int;
+                if GetTouchInputInfo(touch_handle, count as UINT, inputs.as_mut_ptr(), size) == 0 {
+                    return None;
+                }
+                for input in &inputs {
+                    let phase = if input.dwFlags & TOUCHEVENTF_DOWN != 0 {
+                        TouchPhase::Down
+                    } else if input.dwFlags & TOUCHEVENTF_UP != 0 {
+                        TouchPhase::Up
+                    } else {
+                        TouchPhase::Move
+                    };
+                    // Touch positions are in hundredths of a pixel, in screen
+                    // coordinates.
+                    let mut point = POINT {
+                        x: input.x / 100,
+                        y: input.y / 100,
+                    };
+                    ScreenToClient(hwnd, &mut point);
+                    let event = TouchEvent {
+                        id: input.dwID as u64,
+                        x: point.x,
+                        y: point.y,
+                        phase,
+                    };
+                    self.handler.touch(&event);
+                }
+                CloseTouchInputHandle(touch_handle);
+                Some(0)
+            },
+            WM_SETCURSOR => {
+                if LOWORD(lparam as u32) as LRESULT == HTCLIENT {
+                    if let Some(w) = self.handle.borrow().0.upgrade() {
+                        unsafe {
+                            SetCursor(w.cursor.borrow().hcursor());
+                        }
+                        return Some(TRUE as LRESULT);
+                    }
+                }
+                None
+            }
+            WM_CAPTURECHANGED => {
+                let releasing = match self.handle.borrow().0.upgrade() {
+                    Some(w) => w.releasing_capture.get(),
+                    None => false,
+                };
+                if !releasing {
+                    self.handler.mouse_capture_lost();
+                }
+                Some(0)
+            }
+            WM_SETTINGCHANGE => {
+                // Switching between light and dark mode broadcasts this.
+                let area = if lparam != 0 {
+                    (lparam as LPWSTR).from_wide()
+                } else {
+                    None
+                };
+                // So does changing the text size, with no particular area.
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    let scale = theme::text_scale();
+                    if w.text_scale.replace(scale) != scale {
+                        self.handler.text_scale_changed(scale);
+                    }
+                }
+                if area.as_ref().map(String::as_str) == Some("ImmersiveColorSet") {
+                    if let Some(w) = self.handle.borrow().0.upgrade() {
+                        let theme = theme::system_theme();
+                        if w.theme.replace(theme) != theme {
+                            unsafe { theme::apply(hwnd, theme) };
+                            self.handler.theme_changed(theme);
+                        }
+                        let reduce = theme::reduce_transparency();
+                        if w.reduce_transparency.replace(reduce) != reduce {
+                            self.handler.reduce_transparency_changed(reduce);
+                        }
+                    }
+                }
+                None
+            }
+            WM_DWMCOLORIZATIONCOLORCHANGED => {
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    let color = theme::accent_color();
+                    if w.accent_color.replace(color) != color {
+                        self.handler.accent_color_changed(color.map(Color::rgb24));
+                    }
+                }
+                None
+            }
+            WM_POWERBROADCAST if wparam == PBT_APMPOWERSTATUSCHANGE => {
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    let source = Application::power_source();
+                    if w.power_source.replace(source) != source {
+                        self.handler.power_source_changed(source);
+                    }
+                }
+                Some(TRUE as LRESULT)
+            }
+            WM_CLOSE => {
+                if self.handler.request_close() {
+                    // The default handling destroys the window.
+                    None
+                } else {
+                    Some(0)
+                }
+            }
             WM_DESTROY => {
+                unsafe {
+                    drop_target::revoke(hwnd);
+                }
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    unsafe { accessibility::disconnect(&w) };
+                    w.tray.borrow_mut().take();
+                    w.frame_clock.borrow_mut().take();
+                    w.timer_thread.borrow_mut().take();
+                    unsafe {
+                        destroy_icons(w.icons.replace((null_mut(), null_mut())));
+                    }
+                }
                 self.handler.destroy();
                 None
             }
+            XI_FILE_DRAG => {
+                let drag = unsafe { &mut *(lparam as *mut drop_target::FileDrag) };
+                let (x, y) = (drag.x, drag.y);
+                drag.accepted = match wparam {
+                    drop_target::FILE_DRAG_ENTER => self.handler.file_drag_enter(&drag.paths, x, y),
+                    drop_target::FILE_DRAG_OVER => self.handler.file_drag_over(x, y),
+                    drop_target::FILE_DROP => {
+                        let paths = mem::replace(&mut drag.paths, Vec::new());
+                        self.handler.file_drop(paths, x, y)
+                    }
+                    _ => {
+                        self.handler.file_drag_leave();
+                        false
+                    }
+                };
+                Some(0)
+            }
+            XI_FRAME => {
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    if w.frame_requested.replace(false) {
+                        self.handler.frame();
+                    }
+                    // The handler may have invalidated the window.
+                    if w.paint_requested.replace(false) {
+                        unsafe { InvalidateRect(hwnd, null(), FALSE) };
+                    }
+                }
+                Some(0)
+            }
+            XI_CANCEL_COMPOSITION => {
+                // Not composing anymore, so the messages ending the
+                // composition aren't passed on.
+                self.state.borrow_mut().as_mut().unwrap().composing = false;
+                unsafe { ime::cancel_composition(hwnd) };
+                Some(0)
+            }
+            XI_TIMER => {
+                if let Some(w) = self.handle.borrow().0.upgrade() {
+                    let due = w
+                        .timers
+                        .borrow_mut()
+                        .take_due(Instant::now() + COALESCE_SLACK);
+                    for token in due {
+                        self.handler.timer(token);
+                    }
+                    w.schedule_timers();
+                }
+                Some(0)
+            }
+            XI_START_DRAG => {
+                let data = unsafe { Box::from_raw(lparam as *mut DragData) };
+                let effect = unsafe { drag_source::do_drag(&data) }.unwrap_or_else(|e| {
+                    println!("Error running drag: {:?}", e);
+                    DropEffect::None
+                });
+                self.handler.drag_ended(effect);
+                Some(0)
+            }
+            XI_TRAY => {
+                match lparam as UINT {
+                    WM_LBUTTONUP => self.handler.tray_icon_clicked(),
+                    WM_RBUTTONUP => {
+                        if let Some(w) = self.handle.borrow().0.upgrade() {
+                            if let Some(ref tray) = *w.tray.borrow() {
+                                unsafe { tray.show_menu() };
+                            }
+                        }
+                    }
+                    _ => (),
+                }
+                Some(0)
+            }
+            WM_GETOBJECT => match self.handle.borrow().0.upgrade() {
+                Some(w) => unsafe { accessibility::get_object(&w, wparam, lparam) },
+                None => None,
+            },
+            XI_ACCESS_ACTION => {
+                let action = unsafe { &mut *(lparam as *mut accessibility::ActionMessage) };
+                action.performed = self.handler.accessibility_action(action.request.clone());
+                Some(0)
+            }
             XI_RUN_IDLE => {
                 let queue = self.handle.borrow().take_idle_queue();
                 let handler_as_any = self.handler.as_any();
@@ -566,6 +1151,16 @@ impl WindowBuilder {
             cursor: Cursor::Arrow,
             menu: None,
             present_strategy: Default::default(),
+            kind: WindowKind::Normal,
+            level: WindowLevel::Normal,
+            owner: None,
+            position: None,
+            centered: false,
+            size: None,
+            vsync: true,
+            icons: Vec::new(),
+            backdrop: WindowBackdrop::None,
+            parent: None,
         }
     }
 
@@ -597,11 +1192,85 @@ impl WindowBuilder {
         self.menu = Some(menu);
     }
 
+    /// Set whether presenting waits for the display refresh, which avoids
+    /// tearing. On by default.
+    pub fn set_vsync(&mut self, vsync: bool) {
+        self.vsync = vsync;
+    }
+
     pub fn set_present_strategy(&mut self, present_strategy: PresentStrategy) {
         self.present_strategy = present_strategy;
     }
 
+    pub fn set_kind(&mut self, kind: WindowKind) {
+        self.kind = kind;
+    }
+
+    pub fn set_level(&mut self, level: WindowLevel) {
+        self.level = level;
+    }
+
+    /// Set the window that owns this one. An owned window stays above its
+    /// owner and is minimized and restored along with it.
+    pub fn set_owner(&mut self, owner: &WindowHandle) {
+        self.owner = Some(owner.clone());
+    }
+
+    /// Set the initial position of the window, in px. The position is
+    /// relative to the top left corner of the owner window if there is one,
+    /// otherwise to the screen.
+    pub fn set_position(&mut self, x: f64, y: f64) {
+        self.position = Some((x, y));
+    }
+
+    /// Center the window on its owner if there is one, otherwise on the
+    /// screen. This takes precedence over `set_position`.
+    pub fn set_centered(&mut self) {
+        self.centered = true;
+    }
+
+    /// Set the initial size of the window, in px.
+    pub fn set_size(&mut self, width: f64, height: f64) {
+        self.size = Some((width, height));
+    }
+
+    /// Set the icon of the window, given in several resolutions. See
+    /// `WindowHandle::set_icon`.
+    pub fn set_icon(&mut self, icons: Vec<IconDesc>) {
+        self.icons = icons;
+    }
+
+    /// Show a translucent material behind the window, where it paints
+    /// transparent pixels. Windows with a backdrop are presented with the
+    /// `Hwnd` strategy, as the others ignore alpha.
+    pub fn set_backdrop(&mut self, backdrop: WindowBackdrop) {
+        self.backdrop = backdrop;
+    }
+
+    /// Build the window as a child of a window of the host application,
+    /// rather than as a top-level window, as for a plugin UI.
+    ///
+    /// The window fills the client area of the parent unless given a size,
+    /// and the host resizes it with `set_size` as the parent changes. The
+    /// position is relative to the client area of the parent. The menu,
+    /// kind, level and backdrop are ignored, the window is presented with
+    /// the `Hwnd` strategy, and maximizing, minimizing and fullscreen do
+    /// nothing.
+    ///
+    /// # Safety
+    ///
+    /// `parent` must be the `Win32` handle of a window that outlives this
+    /// one, or until it is closed.
+    pub unsafe fn set_parent(&mut self, parent: RawWindowHandle) {
+        self.parent = Some(parent);
+    }
+
     pub fn build(self) -> Result<WindowHandle, Error> {
+        let parent = match self.parent {
+            None => None,
+            Some(RawWindowHandle::Win32 { hwnd, .. }) if !hwnd.is_null() => Some(hwnd as HWND),
+            Some(_) => return Err(Error::Other("the parent must be a Win32 window")),
+        };
         unsafe {
             // Maybe separate registration in build api? Probably only need to
             // register once even for multiple window creation.
@@ -609,7 +1278,7 @@ impl WindowBuilder {
             // TODO: probably want configurable class name.
             let class_name = "Xi Editor".to_wide();
             let icon = LoadIconW(0 as HINSTANCE, IDI_APPLICATION);
-            let cursor = LoadCursorW(0 as HINSTANCE, self.cursor.get_lpcwstr());
+            let cursor = self.cursor.hcursor();
             let brush = CreateSolidBrush(0xffffff);
             let wnd = WNDCLASSW {
                 style: 0,
@@ -624,7 +1293,9 @@ impl WindowBuilder {
                 lpszClassName: class_name.as_ptr(),
             };
             let class_atom = RegisterClassW(&wnd);
-            if class_atom == 0 {
+            // The class is shared by all windows, so it is already registered
+            // when building any window after the first.
+            if class_atom == 0 && GetLastError() != ERROR_CLASS_ALREADY_EXISTS {
                 return Err(Error::Null);
             }
 
@@ -641,6 +1312,28 @@ impl WindowBuilder {
                 dpi: Cell::new(0.0),
                 wndproc: Box::new(wndproc),
                 idle_queue: Default::default(),
+                releasing_capture: Cell::new(false),
+                cursor: RefCell::new(self.cursor),
+                window_state: Cell::new(window::WindowState::Restored),
+                fullscreen_restore: Cell::new(None),
+                tray: RefCell::new(None),
+                theme: Cell::new(theme::system_theme()),
+                accent_color: Cell::new(theme::accent_color()),
+                reduce_transparency: Cell::new(theme::reduce_transparency()),
+                power_source: Cell::new(Application::power_source()),
+                text_scale: Cell::new(Application::text_scale()),
+                caret_rect: Cell::new(None),
+                vsync: Cell::new(self.vsync),
+                frame_requested: Cell::new(false),
+                paint_requested: Cell::new(false),
+                frame_clock: RefCell::new(None),
+                icons: Cell::new((null_mut(), null_mut())),
+                backdrop: Cell::new(WindowBackdrop::None),
+                timers: RefCell::new(TimerQueue::new()),
+                timer_thread: RefCell::new(None),
+                composited: Cell::new(false),
+                embedded: parent.is_some(),
+                access: RefCell::new(Default::default()),
             };
             let win = Rc::new(window);
             let handle = WindowHandle(Rc::downgrade(&win));
@@ -655,27 +1348,100 @@ impl WindowBuilder {
                 96.0
             };
             win.dpi.set(dpi);
-            let width = (500.0 * (dpi / 96.0)) as i32;
-            let height = (400.0 * (dpi / 96.0)) as i32;
+            let scale = f64::from(dpi / 96.0);
+            let (width, height) = match (self.size, parent) {
+                (None, Some(parent)) => {
+                    let mut rect: RECT = mem::zeroed();
+                    GetClientRect(parent, &mut rect);
+                    (rect.right - rect.left, rect.bottom - rect.top)
+                }
+                (size, _) => {
+                    let (width, height) = size.unwrap_or((500.0, 400.0));
+                    ((width * scale) as i32, (height * scale) as i32)
+                }
+            };
 
+            let owner = self
+                .owner
+                .as_ref()
+                .and_then(|owner| owner.0.upgrade())
+                .map(|owner| owner.hwnd.get())
+                .unwrap_or(0 as HWND);
+            let (x, y) = match self.position {
+                _ if parent.is_some() => {
+                    let (x, y) = self.position.unwrap_or((0.0, 0.0));
+                    ((x * scale) as i32, (y * scale) as i32)
+                }
+                Some((x, y)) => {
+                    let mut origin = RECT {
+                        left: 0,
+                        top: 0,
+                        right: 0,
+                        bottom: 0,
+                    };
+                    if !owner.is_null() {
+                        GetWindowRect(owner, &mut origin);
+                    }
+                    (
+                        origin.left + (x * scale) as i32,
+                        origin.top + (y * scale) as i32,
+                    )
+                }
+                None => (CW_USEDEFAULT, CW_USEDEFAULT),
+            };
+
+            // The menu handle of a child window is its id instead.
             let hmenu = match self.menu {
-                Some(menu) => menu.into_hmenu(),
-                None => 0 as HMENU,
+                Some(menu) if parent.is_none() => menu.into_hmenu(),
+                _ => 0 as HMENU,
+            };
+            let backdrop = match parent {
+                Some(_) => WindowBackdrop::None,
+                None => self.backdrop,
+            };
+            let present_strategy = if backdrop == WindowBackdrop::None && parent.is_none() {
+                self.present_strategy
+            } else {
+                PresentStrategy::Hwnd
             };
             let mut dwExStyle = 0;
-            if self.present_strategy == PresentStrategy::Flip {
+            if present_strategy == PresentStrategy::Flip {
                 dwExStyle |= WS_EX_NOREDIRECTIONBITMAP;
             }
+            // GPU surfaces are child windows, which painting must not cover.
+            let mut dwStyle = self.dwStyle | WS_CLIPCHILDREN;
+            if self.kind == WindowKind::Palette {
+                // A small caption and no taskbar button; clicking the palette
+                // leaves the owner window active.
+                dwStyle = WS_POPUP
+                    | WS_CAPTION
+                    | WS_SYSMENU
+                    | WS_THICKFRAME
+                    | WS_CLIPCHILDREN
+                    | (self.dwStyle & (WS_HSCROLL | WS_VSCROLL));
+                dwExStyle |= WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE;
+            }
+            match self.level {
+                WindowLevel::Normal => (),
+                WindowLevel::Floating => dwExStyle |= WS_EX_TOPMOST,
+                WindowLevel::Tooltip => {
+                    dwExStyle |= WS_EX_TOPMOST | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE
+                }
+            }
+            if parent.is_some() {
+                dwStyle = WS_CHILD | WS_CLIPCHILDREN | WS_CLIPSIBLINGS;
+                dwExStyle = 0;
+            }
             let hwnd = create_window(
                 dwExStyle,
                 class_name.as_ptr(),
                 self.title.to_wide().as_ptr(),
-                self.dwStyle,
-                CW_USEDEFAULT,
-                CW_USEDEFAULT,
+                dwStyle,
+                x,
+                y,
                 width,
                 height,
-                0 as HWND,
+                parent.unwrap_or(owner),
                 hmenu,
                 0 as HINSTANCE,
                 win.clone(),
@@ -684,26 +1450,131 @@ impl WindowBuilder {
                 return Err(Error::Null);
             }
 
-            let dcomp_state = create_dcomp_state(self.present_strategy, hwnd).unwrap_or_else(|e| {
+            let dcomp_state = create_dcomp_state(present_strategy, hwnd).unwrap_or_else(|e| {
                 println!("Error creating swapchain, falling back to hwnd: {:?}", e);
                 None
             });
 
             win.hwnd.set(hwnd);
+            win.composited.set(dcomp_state.is_some());
+            theme::apply(hwnd, win.theme.get());
+            if backdrop::apply(hwnd, backdrop) {
+                win.backdrop.set(backdrop);
+            }
+            if self.centered && parent.is_none() {
+                match self.owner {
+                    Some(ref owner) => handle.center_on(owner),
+                    None => handle.center_on_screen(),
+                }
+            }
+            drop_target::register(hwnd);
+            RegisterTouchWindow(hwnd, 0);
             let state = WndState {
                 render_target: None,
                 dcomp_state,
                 dpi,
                 stashed_key_code: KeyCode::Unknown(0.into()),
+                stashed_key: Key::Unidentified,
                 stashed_char: None,
+                composing: false,
+                vsync: self.vsync,
+                transparent: win.backdrop.get() != WindowBackdrop::None,
             };
             win.wndproc.connect(&handle, state);
             mem::drop(win);
+            if !self.icons.is_empty() {
+                if let Err(e) = handle.set_icon(&self.icons) {
+                    println!("Error setting window icon: {:?}", e);
+                }
+            }
             Ok(handle)
         }
     }
 }
 
+/// Create an icon, or a cursor if there is a hotspot, from an RGBA image.
+unsafe fn create_icon(
+    image: &[u8],
+    width: usize,
+    height: usize,
+    hot: Option<(usize, usize)>,
+) -> HICON {
+    // The color bitmap is BGRA; with an alpha channel the mask is unused,
+    // but it must still be given.
+    let bgra: Vec<u8> = image
+        .chunks_exact(4)
+        .flat_map(|p| vec![p[2], p[1], p[0], p[3]])
+        .collect();
+    let mask_stride = (width + 15) / 16 * 2;
+    let mask = vec![0u8; mask_stride * height];
+    let hbm_color = CreateBitmap(
+        width as c_int,
+        height as c_int,
+        1,
+        32,
+        bgra.as_ptr() as *const c_void,
+    );
+    let hbm_mask = CreateBitmap(
+        width as c_int,
+        height as c_int,
+        1,
+        1,
+        mask.as_ptr() as *const c_void,
+    );
+    let (x_hot, y_hot) = hot.unwrap_or((0, 0));
+    let mut info = ICONINFO {
+        fIcon: if hot.is_none() { TRUE } else { FALSE },
+        xHotspot: x_hot as DWORD,
+        yHotspot: y_hot as DWORD,
+        hbmMask: hbm_mask,
+        hbmColor: hbm_color,
+    };
+    let hicon = CreateIconIndirect(&mut info);
+    DeleteObject(hbm_color as _);
+    DeleteObject(hbm_mask as _);
+    hicon
+}
+
+/// Create the icon to show at `size` pixels from the best fitting image.
+unsafe fn create_window_icon(icons: &[IconDesc], size: c_int) -> HICON {
+    match IconDesc::best_fit(icons, size as usize) {
+        Some(icon) => create_icon(&icon.image, icon.width, icon.height, None),
+        None => null_mut(),
+    }
+}
+
+unsafe fn destroy_icons((big, small): (HICON, HICON)) {
+    for icon in &[big, small] {
+        if !icon.is_null() {
+            DestroyIcon(*icon);
+        }
+    }
+}
+
+unsafe fn monitor_info(monitor: HMONITOR) -> MONITORINFO {
+    let mut info: MONITORINFO = mem::zeroed();
+    info.cbSize = mem::size_of::<MONITORINFO>() as DWORD;
+    GetMonitorInfoW(monitor, &mut info);
+    info
+}
+
+/// Move a window so that it is centered in `area`, in screen pixels.
+unsafe fn center_window(hwnd: HWND, area: RECT) {
+    let mut rect: RECT = mem::zeroed();
+    GetWindowRect(hwnd, &mut rect);
+    let width = rect.right - rect.left;
+    let height = rect.bottom - rect.top;
+    SetWindowPos(
+        hwnd,
+        null_mut(),
+        area.left + (area.right - area.left - width) / 2,
+        area.top + (area.bottom - area.top - height) / 2,
+        0,
+        0,
+        SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+    );
+}
+
 /// Choose an adapter. Here the heuristic is to choose the adapter with the
 /// largest video memory, which will generally be the discrete adapter. It's
 /// possible that on some systems the integrated adapter might be a better
@@ -868,11 +1739,19 @@ unsafe fn create_window(
 }
 
 impl Cursor {
-    fn get_lpcwstr(&self) -> LPCWSTR {
-        match self {
+    fn hcursor(&self) -> HCURSOR {
+        let name = match self {
             Cursor::Arrow => IDC_ARROW,
             Cursor::IBeam => IDC_IBEAM,
-        }
+            Cursor::Crosshair => IDC_CROSS,
+            // Windows has no grab cursors.
+            Cursor::OpenHand | Cursor::ClosedHand => IDC_HAND,
+            Cursor::NotAllowed => IDC_NO,
+            Cursor::ResizeLeftRight => IDC_SIZEWE,
+            Cursor::ResizeUpDown => IDC_SIZENS,
+            Cursor::Custom(custom) => return (custom.0).0,
+        };
+        unsafe { LoadCursorW(0 as HINSTANCE, name) }
     }
 }
 
@@ -881,12 +1760,526 @@ impl WindowHandle {
         if let Some(w) = self.0.upgrade() {
             let hwnd = w.hwnd.get();
             unsafe {
-                ShowWindow(hwnd, SW_SHOWNORMAL);
+                let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as DWORD;
+                if ex_style & WS_EX_NOACTIVATE != 0 {
+                    ShowWindow(hwnd, SW_SHOWNOACTIVATE);
+                } else {
+                    ShowWindow(hwnd, SW_SHOWNORMAL);
+                }
                 UpdateWindow(hwnd);
             }
         }
     }
 
+    /// Replace the menubar of the window.
+    pub fn set_menu(&self, menu: Menu) {
+        if let Some(w) = self.0.upgrade() {
+            let hwnd = w.hwnd.get();
+            unsafe {
+                let old_menu = GetMenu(hwnd);
+                SetMenu(hwnd, menu.into_hmenu());
+                if !old_menu.is_null() {
+                    DestroyMenu(old_menu);
+                }
+                DrawMenuBar(hwnd);
+            }
+        }
+    }
+
+    pub fn set_title(&self, title: &str) {
+        if let Some(w) = self.0.upgrade() {
+            unsafe {
+                SetWindowTextW(w.hwnd.get(), title.to_wide().as_ptr());
+            }
+        }
+    }
+
+    pub fn get_title(&self) -> String {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return String::new(),
+        };
+        unsafe {
+            let len = GetWindowTextLengthW(hwnd);
+            let mut buf = vec![0u16; len as usize + 1];
+            let len = GetWindowTextW(hwnd, buf.as_mut_ptr(), buf.len() as c_int);
+            buf[..len as usize].from_wide().unwrap_or_default()
+        }
+    }
+
+    /// The size of the client area, in px.
+    pub fn get_size(&self) -> (f64, f64) {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return (0.0, 0.0),
+        };
+        unsafe {
+            let mut rect: RECT = mem::zeroed();
+            GetClientRect(hwnd, &mut rect);
+            let (width, height) =
+                self.pixels_to_px_xy(rect.right - rect.left, rect.bottom - rect.top);
+            (f64::from(width), f64::from(height))
+        }
+    }
+
+    /// Resize the window so that its client area has the given size, in px.
+    pub fn set_size(&self, width: f64, height: f64) {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        let (width, height) = self.px_to_pixels_xy(width as f32, height as f32);
+        unsafe {
+            let mut rect = RECT {
+                left: 0,
+                top: 0,
+                right: width,
+                bottom: height,
+            };
+            let style = GetWindowLongW(hwnd, GWL_STYLE) as DWORD;
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as DWORD;
+            let has_menu = if GetMenu(hwnd).is_null() { FALSE } else { TRUE };
+            AdjustWindowRectEx(&mut rect, style, has_menu, ex_style);
+            SetWindowPos(
+                hwnd,
+                null_mut(),
+                0,
+                0,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// Change the stacking level of the window. The taskbar button and
+    /// activation behavior of a tooltip are only set up at creation.
+    pub fn set_level(&self, level: WindowLevel) {
+        let hwnd = match self.toplevel_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        unsafe {
+            let insert_after = match level {
+                WindowLevel::Normal => HWND_NOTOPMOST,
+                _ => HWND_TOPMOST,
+            };
+            SetWindowPos(
+                hwnd,
+                insert_after,
+                0,
+                0,
+                0,
+                0,
+                SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// The position of the top left corner of the window frame, in px, in
+    /// screen coordinates.
+    pub fn get_position(&self) -> (f64, f64) {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return (0.0, 0.0),
+        };
+        unsafe {
+            let mut rect: RECT = mem::zeroed();
+            GetWindowRect(hwnd, &mut rect);
+            let (x, y) = self.pixels_to_px_xy(rect.left, rect.top);
+            (f64::from(x), f64::from(y))
+        }
+    }
+
+    /// Move the top left corner of the window frame to the given position, in
+    /// px, in screen coordinates.
+    pub fn set_position(&self, x: f64, y: f64) {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        let (x, y) = self.px_to_pixels_xy(x as f32, y as f32);
+        unsafe {
+            SetWindowPos(
+                hwnd,
+                null_mut(),
+                x,
+                y,
+                0,
+                0,
+                SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// Center the window on its monitor, leaving out the taskbar.
+    pub fn center_on_screen(&self) {
+        if let Some(hwnd) = self.toplevel_hwnd() {
+            unsafe {
+                let info = monitor_info(MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST));
+                center_window(hwnd, info.rcWork);
+            }
+        }
+    }
+
+    /// Center the window over another one, as for a dialog.
+    pub fn center_on(&self, other: &WindowHandle) {
+        if let (Some(hwnd), Some(other)) = (self.get_hwnd(), other.get_hwnd()) {
+            unsafe {
+                let mut rect: RECT = mem::zeroed();
+                GetWindowRect(other, &mut rect);
+                center_window(hwnd, rect);
+            }
+        }
+    }
+
+    /// The frame of the window when restored, and whether it is maximized,
+    /// for saving the window layout.
+    pub fn get_geometry(&self) -> WindowGeometry {
+        let w = match self.0.upgrade() {
+            Some(w) => w,
+            None => return WindowGeometry::default(),
+        };
+        let hwnd = w.hwnd.get();
+        unsafe {
+            // While fullscreen, the placement to return to is saved.
+            let placement = match w.fullscreen_restore.get() {
+                Some((_, placement)) => placement,
+                None => {
+                    let mut placement: WINDOWPLACEMENT = mem::zeroed();
+                    placement.length = mem::size_of::<WINDOWPLACEMENT>() as UINT;
+                    GetWindowPlacement(hwnd, &mut placement);
+                    placement
+                }
+            };
+            let mut rect = placement.rcNormalPosition;
+            // Except for tool windows, the normal position is in workspace
+            // coordinates, which leave out a taskbar at the top or left.
+            let ex_style = GetWindowLongW(hwnd, GWL_EXSTYLE) as DWORD;
+            if ex_style & WS_EX_TOOLWINDOW == 0 {
+                let info = monitor_info(MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST));
+                OffsetRect(
+                    &mut rect,
+                    info.rcWork.left - info.rcMonitor.left,
+                    info.rcWork.top - info.rcMonitor.top,
+                );
+            }
+            let maximized = placement.showCmd == SW_SHOWMAXIMIZED as UINT
+                || (placement.showCmd == SW_SHOWMINIMIZED as UINT
+                    && placement.flags & WPF_RESTORETOMAXIMIZED != 0);
+            WindowGeometry {
+                frame: self.rect_to_px(&rect),
+                maximized,
+            }
+        }
+    }
+
+    /// Restore a frame and maximized state saved with `get_geometry`.
+    ///
+    /// The frame is moved and shrunk as needed to fit in the work area of
+    /// the nearest monitor, in case the monitor layout has changed since.
+    pub fn set_geometry(&self, geometry: &WindowGeometry) {
+        let hwnd = match self.get_hwnd() {
+            Some(hwnd) => hwnd,
+            None => return,
+        };
+        let frame = geometry.frame;
+        let (x0, y0) = self.px_to_pixels_xy(frame.x0 as f32, frame.y0 as f32);
+        let (x1, y1) = self.px_to_pixels_xy(frame.x1 as f32, frame.y1 as f32);
+        let rect = RECT {
+            left: x0,
+            top: y0,
+            right: x1,
+            bottom: y1,
+        };
+        unsafe {
+            let info = monitor_info(MonitorFromRect(&rect, MONITOR_DEFAULTTONEAREST));
+            let frame = window::clamp_frame(frame, self.rect_to_px(&info.rcWork));
+            let (x, y) = self.px_to_pixels_xy(frame.x0 as f32, frame.y0 as f32);
+            let (width, height) = self.px_to_pixels_xy(frame.width() as f32, frame.height() as f32);
+            self.restore();
+            SetWindowPos(
+                hwnd,
+                null_mut(),
+                x,
+                y,
+                width,
+                height,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+            if geometry.maximized {
+                ShowWindow(hwnd, SW_MAXIMIZE);
+            }
+        }
+    }
+
+    fn rect_to_px(&self, rect: &RECT) -> Rect {
+        let (x0, y0) = self.pixels_to_px_xy(rect.left, rect.top);
+        let (x1, y1) = self.pixels_to_px_xy(rect.right, rect.bottom);
+        Rect::new(x0.into(), y0.into(), x1.into(), y1.into())
+    }
+
+    /// Hide the window, for example to leave only its tray icon. `show`
+    /// brings it back.
+    pub fn hide(&self) {
+        if let Some(hwnd) = self.get_hwnd() {
+            unsafe {
+                ShowWindow(hwnd, SW_HIDE);
+            }
+        }
+    }
+
+    pub fn maximize(&self) {
+        self.set_fullscreen(false);
+        if let Some(hwnd) = self.toplevel_hwnd() {
+            unsafe {
+                ShowWindow(hwnd, SW_MAXIMIZE);
+            }
+        }
+    }
+
+    pub fn minimize(&self) {
+        if let Some(hwnd) = self.toplevel_hwnd() {
+            unsafe {
+                ShowWindow(hwnd, SW_MINIMIZE);
+            }
+        }
+    }
+
+    /// Return the window to its normal size and position, leaving fullscreen
+    /// if needed.
+    pub fn restore(&self) {
+        self.set_fullscreen(false);
+        if let Some(hwnd) = self.toplevel_hwnd() {
+            unsafe {
+                ShowWindow(hwnd, SW_RESTORE);
+            }
+        }
+    }
+
+    /// Enter or leave borderless fullscreen, covering the monitor the window
+    /// is on.
+    pub fn set_fullscreen(&self, fullscreen: bool) {
+        let w = match self.0.upgrade() {
+            Some(w) if !w.embedded => w,
+            _ => return,
+        };
+        if fullscreen == w.fullscreen_restore.get().is_some() {
+            return;
+        }
+        let hwnd = w.hwnd.get();
+        unsafe {
+            if fullscreen {
+                let style = GetWindowLongW(hwnd, GWL_STYLE) as DWORD;
+                let mut placement: WINDOWPLACEMENT = mem::zeroed();
+                placement.length = mem::size_of::<WINDOWPLACEMENT>() as UINT;
+                GetWindowPlacement(hwnd, &mut placement);
+                let info = monitor_info(MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST));
+                // Set first, so that the resulting `WM_SIZE` reports fullscreen.
+                w.fullscreen_restore.set(Some((style, placement)));
+                SetWindowLongW(hwnd, GWL_STYLE, (style & !WS_OVERLAPPEDWINDOW) as LONG);
+                let rect = info.rcMonitor;
+                SetWindowPos(
+                    hwnd,
+                    HWND_TOP,
+                    rect.left,
+                    rect.top,
+                    rect.right - rect.left,
+                    rect.bottom - rect.top,
+                    SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+                );
+            } else if let Some((style, placement)) = w.fullscreen_restore.take() {
+                SetWindowLongW(hwnd, GWL_STYLE, style as LONG);
+                SetWindowPlacement(hwnd, &placement);
+                SetWindowPos(
+                    hwnd,
+                    null_mut(),
+                    0,
+                    0,
+                    0,
+                    0,
+                    SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOOWNERZORDER | SWP_FRAMECHANGED,
+                );
+            }
+        }
+    }
+
+    /// Whether the window is maximized, minimized or fullscreen.
+    pub fn get_window_state(&self) -> window::WindowState {
+        self.0
+            .upgrade()
+            .map(|w| w.window_state.get())
+            .unwrap_or(window::WindowState::Restored)
+    }
+
+    /// Set the cursor shown while the pointer is over the window.
+    pub fn set_cursor(&self, cursor: &Cursor) {
+        if let Some(w) = self.0.upgrade() {
+            unsafe {
+                SetCursor(cursor.hcursor());
+            }
+            *w.cursor.borrow_mut() = cursor.clone();
+        }
+    }
+
+    /// Create a cursor from an image.
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        let hcursor = unsafe { create_icon(&desc.image, desc.width, desc.height, Some(desc.hot)) };
+        if hcursor.is_null() {
+            return None;
+        }
+        Some(Cursor::Custom(CustomCursor(Rc::new(HCursor(hcursor)))))
+    }
+
+    /// Show an icon for the window in the notification area, replacing any
+    /// previous one.
+    ///
+    /// Clicking the icon calls the handler's `tray_icon_clicked`; right
+    /// clicking shows `menu`, which should be made with `Menu::new_for_popup`.
+    pub fn set_tray_icon(
+        &self,
+        icon: &IconDesc,
+        tooltip: &str,
+        menu: Option<Menu>,
+    ) -> Result<(), Error> {
+        let w = self.0.upgrade().ok_or(Error::Null)?;
+        // Remove the old icon first, since the new one gets the same id.
+        w.tray.borrow_mut().take();
+        unsafe {
+            let hicon = create_icon(&icon.image, icon.width, icon.height, None);
+            if hicon.is_null() {
+                return Err(Error::Null);
+            }
+            let tray =
+                tray::TrayIcon::new(w.hwnd.get(), hicon, tooltip, menu).ok_or(Error::Null)?;
+            *w.tray.borrow_mut() = Some(tray);
+        }
+        Ok(())
+    }
+
+    pub fn remove_tray_icon(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.tray.borrow_mut().take();
+        }
+    }
+
+    /// Start a drag carrying `data`, out of the window or to another place
+    /// in it. Call this while handling a mouse move with a button held; the
+    /// handler's `drag_ended` is called once the drag is over.
+    pub fn start_drag(&self, data: DragData) -> Result<(), Error> {
+        let hwnd = self.get_hwnd().ok_or(Error::Null)?;
+        // The drag runs in a modal loop, so it is started from the window
+        // procedure rather than inside the handler that asked for it.
+        let data = Box::into_raw(Box::new(data));
+        unsafe {
+            if PostMessageW(hwnd, XI_START_DRAG, 0, data as LPARAM) == FALSE {
+                mem::drop(Box::from_raw(data));
+                return Err(Error::Null);
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the icon of the window, shown in its title bar, its taskbar
+    /// button and the task switcher. The images are resolutions of the same
+    /// icon; the best fit is chosen for each size Windows shows.
+    pub fn set_icon(&self, icons: &[IconDesc]) -> Result<(), Error> {
+        let w = self.0.upgrade().ok_or(Error::Null)?;
+        unsafe {
+            let big = create_window_icon(icons, GetSystemMetrics(SM_CXICON));
+            let small = create_window_icon(icons, GetSystemMetrics(SM_CXSMICON));
+            if big.is_null() || small.is_null() {
+                destroy_icons((big, small));
+                return Err(Error::Null);
+            }
+            let hwnd = w.hwnd.get();
+            SendMessageW(hwnd, WM_SETICON, ICON_BIG as WPARAM, big as LPARAM);
+            SendMessageW(hwnd, WM_SETICON, ICON_SMALL as WPARAM, small as LPARAM);
+            destroy_icons(w.icons.replace((big, small)));
+        }
+        Ok(())
+    }
+
+    /// Show a context menu at a point in the window, in px. The chosen item
+    /// is sent to the handler as a command.
+    ///
+    /// This runs a modal loop until the menu is dismissed.
+    pub fn show_context_menu(&self, menu: Menu, x: f64, y: f64) {
+        if let Some(w) = self.0.upgrade() {
+            let hwnd = w.hwnd.get();
+            let scale = f64::from(w.dpi.get() / 96.0);
+            let mut point = POINT {
+                x: (x * scale) as i32,
+                y: (y * scale) as i32,
+            };
+            unsafe {
+                ClientToScreen(hwnd, &mut point);
+                let hmenu = menu.into_hmenu();
+                TrackPopupMenu(
+                    hmenu,
+                    TPM_LEFTALIGN | TPM_TOPALIGN | TPM_RIGHTBUTTON,
+                    point.x,
+                    point.y,
+                    0,
+                    hwnd,
+                    null(),
+                );
+                DestroyMenu(hmenu);
+            }
+        }
+    }
+
+    /// Tell the input method where the caret is, in px, so that its
+    /// candidate window is shown next to it.
+    pub fn set_caret_rect(&self, rect: Rect) {
+        if let Some(w) = self.0.upgrade() {
+            w.caret_rect.set(Some(rect));
+            w.apply_caret_rect();
+        }
+    }
+
+    /// End the composition in progress, if any, discarding it without a
+    /// `CompositionEvent::Commit`, for when the application commits the
+    /// text itself, as when the focus moves away from it.
+    pub fn cancel_composition(&self) {
+        if let Some(w) = self.0.upgrade() {
+            unsafe {
+                PostMessageW(w.hwnd.get(), XI_CANCEL_COMPOSITION, 0, 0);
+            }
+        }
+    }
+
+    /// Show the touch keyboard, for text input on devices without a
+    /// keyboard.
+    pub fn show_soft_keyboard(&self) {
+        soft_keyboard::show();
+    }
+
+    /// Hide the touch keyboard, if it is up.
+    pub fn hide_soft_keyboard(&self) {
+        soft_keyboard::hide();
+    }
+
+    pub fn accessibility_enabled(&self) -> bool {
+        true
+    }
+
+    /// Expose the tree to UI Automation, with a fragment provider for each
+    /// node and the window as the fragment root.
+    pub fn update_accessibility(&self, tree: AccessTree) {
+        if let Some(w) = self.0.upgrade() {
+            unsafe { accessibility::update(&w, tree) }
+        }
+    }
+
+    /// Raise a UI Automation notification event, which Narrator and other
+    /// screen readers read out.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        if let Some(w) = self.0.upgrade() {
+            unsafe { accessibility::announce(&w, text, politeness) }
+        }
+    }
+
     pub fn close(&self) {
         if let Some(w) = self.0.upgrade() {
             let hwnd = w.hwnd.get();
@@ -896,28 +2289,295 @@ impl WindowHandle {
         }
     }
 
+    /// Request a paint. With vsync, the window is painted at the next
+    /// display refresh, at most once however often this is called.
     pub fn invalidate(&self) {
         if let Some(w) = self.0.upgrade() {
-            let hwnd = w.hwnd.get();
+            if w.vsync.get() {
+                w.paint_requested.set(true);
+                w.request_tick();
+            } else {
+                unsafe {
+                    InvalidateRect(w.hwnd.get(), null(), FALSE);
+                }
+            }
+        }
+    }
+
+    /// Request a call to `WinHandler::frame` at the next display refresh,
+    /// followed by a paint.
+    pub fn request_frame(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.frame_requested.set(true);
+            w.paint_requested.set(true);
+            w.request_tick();
+        }
+    }
+
+    /// Request a call to `WinHandler::timer` with the returned token after
+    /// `delay`, accurate to about a millisecond.
+    pub fn request_timer(&self, delay: Duration) -> TimerToken {
+        let w = match self.0.upgrade() {
+            Some(w) => w,
+            None => return TimerToken::next(),
+        };
+        let (token, earliest) = w.timers.borrow_mut().add(Instant::now() + delay);
+        if earliest {
+            w.schedule_timers();
+        }
+        token
+    }
+
+    /// Set whether presenting waits for the display refresh.
+    pub fn set_vsync(&self, vsync: bool) {
+        if let Some(w) = self.0.upgrade() {
+            w.vsync.set(vsync);
+            self.invalidate();
+        }
+    }
+
+    /// The backdrop shown behind the window, which is `None` if this
+    /// version of Windows lacks the one set on the builder.
+    pub fn backdrop(&self) -> WindowBackdrop {
+        self.0
+            .upgrade()
+            .map_or(WindowBackdrop::None, |w| w.backdrop.get())
+    }
+
+    /// Create a surface in the window for rendering with a GPU API directly.
+    ///
+    /// The surface is a child window, which DirectComposition would draw over,
+    /// so this fails unless the window uses `PresentStrategy::Hwnd`.
+    pub fn create_gpu_surface(&self) -> Result<surface::GpuSurface, Error> {
+        let w = self.0.upgrade().ok_or(Error::Null)?;
+        if w.composited.get() {
+            return Err(Error::Other(
+                "GPU surfaces need a window with PresentStrategy::Hwnd",
+            ));
+        }
+        unsafe { surface::GpuSurface::new(w.hwnd.get(), w.dpi.get()) }
+    }
+
+    /// Host a native view of the application, such as a WebView2, in the
+    /// window. Like GPU surfaces, this needs `PresentStrategy::Hwnd`.
+    ///
+    /// # Safety
+    ///
+    /// `view` must be the `Win32` handle of a window that outlives the
+    /// returned `NativeView`.
+    pub unsafe fn add_native_view(
+        &self,
+        view: RawWindowHandle,
+    ) -> Result<native_view::NativeView, Error> {
+        let w = self.0.upgrade().ok_or(Error::Null)?;
+        if w.composited.get() {
+            return Err(Error::Other(
+                "native views need a window with PresentStrategy::Hwnd",
+            ));
+        }
+        match view {
+            RawWindowHandle::Win32 { hwnd, .. } if !hwnd.is_null() => {
+                native_view::NativeView::new(w.hwnd.get(), hwnd as HWND, w.dpi.get())
+            }
+            _ => Err(Error::Other("the view must be a Win32 window")),
+        }
+    }
+
+    /// Capture the mouse, so that mouse events keep being delivered to this
+    /// window while the pointer is outside it, until `release_mouse`.
+    pub fn capture_mouse(&self) {
+        if let Some(w) = self.0.upgrade() {
+            unsafe {
+                SetCapture(w.hwnd.get());
+            }
+        }
+    }
+
+    /// Release a mouse capture started with `capture_mouse`.
+    pub fn release_mouse(&self) {
+        if let Some(w) = self.0.upgrade() {
+            w.releasing_capture.set(true);
             unsafe {
-                InvalidateRect(hwnd, null(), FALSE);
+                if GetCapture() == w.hwnd.get() {
+                    ReleaseCapture();
+                }
             }
+            w.releasing_capture.set(false);
         }
     }
 
     /// Get the raw HWND handle, for uses that are not wrapped in
     /// druid_win_shell.
+    /// The native handles of the window, for external renderers.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        let hwnd = self.get_hwnd()?;
+        unsafe {
+            let hinstance = GetWindowLongPtrW(hwnd, GWLP_HINSTANCE);
+            Some(RawWindowHandle::Win32 {
+                hwnd: hwnd as *mut c_void,
+                hinstance: hinstance as *mut c_void,
+            })
+        }
+    }
+
+    pub fn raw_display_handle(&self) -> RawDisplayHandle {
+        RawDisplayHandle::Windows
+    }
+
     pub fn get_hwnd(&self) -> Option<HWND> {
         self.0.upgrade().map(|w| w.hwnd.get())
     }
 
+    /// The HWND, unless the window is a child of a window of the host
+    /// application, whose placement is not ours to change.
+    fn toplevel_hwnd(&self) -> Option<HWND> {
+        self.0
+            .upgrade()
+            .filter(|w| !w.embedded)
+            .map(|w| w.hwnd.get())
+    }
+
     pub fn file_dialog(
         &self,
         ty: FileDialogType,
         options: FileDialogOptions,
     ) -> Result<OsString, Error> {
         let hwnd = self.get_hwnd().ok_or(Error::Null)?;
-        unsafe { get_file_dialog_path(hwnd, ty, options) }
+        let mut paths = unsafe { get_file_dialog_paths(hwnd, ty, &options)? };
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(paths.swap_remove(0))
+    }
+
+    /// Show a modal file open dialog, returning the chosen files.
+    pub fn open_file_dialog(&self, options: &FileDialogOptions) -> Result<Vec<FileInfo>, Error> {
+        let hwnd = self.get_hwnd().ok_or(Error::Null)?;
+        let paths = unsafe { get_file_dialog_paths(hwnd, FileDialogType::Open, options)? };
+        Ok(paths
+            .into_iter()
+            .map(|path| FileInfo { path: path.into() })
+            .collect())
+    }
+
+    /// Show a modal file save dialog, returning the chosen file.
+    ///
+    /// The dialog asks for confirmation before an existing file is chosen.
+    pub fn save_file_dialog(&self, options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        let hwnd = self.get_hwnd().ok_or(Error::Null)?;
+        let mut paths = unsafe { get_file_dialog_paths(hwnd, FileDialogType::Save, options)? };
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(FileInfo {
+            path: paths.swap_remove(0).into(),
+        })
+    }
+
+    /// Show a modal directory chooser, returning the chosen directory.
+    pub fn open_directory_dialog(&self, options: &FileDialogOptions) -> Result<FileInfo, Error> {
+        let hwnd = self.get_hwnd().ok_or(Error::Null)?;
+        let mut paths = unsafe { get_file_dialog_paths(hwnd, FileDialogType::Directory, options)? };
+        if paths.is_empty() {
+            return Err(Error::Null);
+        }
+        Ok(FileInfo {
+            path: paths.swap_remove(0).into(),
+        })
+    }
+
+    /// Show a modal message box, returning the chosen button.
+    pub fn alert(&self, options: &AlertOptions) -> AlertResponse {
+        let hwnd = self.get_hwnd().unwrap_or(null_mut());
+        let mut text = options.message.clone();
+        if let Some(ref detail) = options.detail {
+            text.push_str("\n\n");
+            text.push_str(detail);
+        }
+        let icon = match options.style {
+            AlertStyle::Info => MB_ICONINFORMATION,
+            AlertStyle::Warning => MB_ICONWARNING,
+            AlertStyle::Error => MB_ICONERROR,
+        };
+        let buttons = match options.buttons {
+            AlertButtons::Ok => MB_OK,
+            AlertButtons::OkCancel => MB_OKCANCEL,
+            AlertButtons::YesNo => MB_YESNO,
+            AlertButtons::YesNoCancel => MB_YESNOCANCEL,
+        };
+        let default = match options.default_index() {
+            0 => MB_DEFBUTTON1,
+            1 => MB_DEFBUTTON2,
+            _ => MB_DEFBUTTON3,
+        };
+        let result = unsafe {
+            MessageBoxW(
+                hwnd,
+                text.to_wide().as_ptr(),
+                options.title.to_wide().as_ptr(),
+                icon | buttons | default,
+            )
+        };
+        match result {
+            IDOK => AlertResponse::Ok,
+            IDYES => AlertResponse::Yes,
+            IDNO => AlertResponse::No,
+            _ => AlertResponse::Cancel,
+        }
+    }
+
+    /// Register a system-wide hotkey.
+    ///
+    /// While registered, pressing the key chord anywhere in the system
+    /// delivers `id` to the handler's `command` method, even when the window
+    /// is not focused. Ids must be below `0xC000`. Registration fails if
+    /// another application already owns the chord.
+    pub fn register_hotkey(&self, id: u32, key: MenuKey) -> Result<(), Error> {
+        let hwnd = self.get_hwnd().ok_or(Error::Null)?;
+        if id >= 0xC000 {
+            return Err(Error::Other("hotkey id out of range"));
+        }
+        let c = match key.key {
+            KeySpec::Char(c) => c,
+            KeySpec::None => return Err(Error::Other("hotkey has no key")),
+        };
+        let mut buf = [0u16; 2];
+        let scan = match c.encode_utf16(&mut buf) {
+            [unit] => unsafe { VkKeyScanW(*unit) },
+            _ => -1,
+        };
+        if scan == -1 {
+            return Err(Error::Other("hotkey key not on the keyboard layout"));
+        }
+        let vk = (scan & 0xff) as UINT;
+        let mut mods = MOD_NOREPEAT;
+        if key.modifiers & M_ALT != 0 {
+            mods |= MOD_ALT;
+        }
+        if key.modifiers & M_CTRL != 0 {
+            mods |= MOD_CONTROL;
+        }
+        if key.modifiers & M_SHIFT != 0 {
+            mods |= MOD_SHIFT;
+        }
+        if key.modifiers & M_META != 0 {
+            mods |= MOD_WIN;
+        }
+        unsafe {
+            if RegisterHotKey(hwnd, id as c_int, mods as UINT, vk) == 0 {
+                return Err(Error::Hr(HRESULT_FROM_WIN32(GetLastError())));
+            }
+        }
+        Ok(())
+    }
+
+    /// Unregister a hotkey previously registered with `register_hotkey`.
+    pub fn unregister_hotkey(&self, id: u32) {
+        if let Some(hwnd) = self.get_hwnd() {
+            unsafe {
+                UnregisterHotKey(hwnd, id as c_int);
+            }
+        }
     }
 
     /// Get a handle that can be used to schedule an idle task.
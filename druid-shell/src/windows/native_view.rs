@@ -0,0 +1,138 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Native views hosted in a window, Windows implementation.
+//!
+//! The view's window is made a child of ours for as long as it is hosted,
+//! and clipped with a window region.
+
+use std::ptr::null_mut;
+
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::um::errhandlingapi::{GetLastError, SetLastError};
+use winapi::um::wingdi::CreateRectRgn;
+use winapi::um::winnt::LONG;
+use winapi::um::winuser::*;
+
+use piet_common::kurbo::Rect;
+
+use crate::Error;
+
+/// A native view of the application, such as a WebView2 or a common
+/// control, shown in a window.
+///
+/// Dropping hands the view back to its owner, hidden and detached from the
+/// window.
+pub struct NativeView {
+    hwnd: HWND,
+    dpi: f32,
+    /// The style of the view before it was hosted.
+    style: DWORD,
+}
+
+impl NativeView {
+    /// Make `view` a hidden child of `parent`.
+    pub(crate) unsafe fn new(parent: HWND, view: HWND, dpi: f32) -> Result<NativeView, Error> {
+        let style = GetWindowLongW(view, GWL_STYLE) as DWORD;
+        let child_style = (style & !(WS_POPUP | WS_OVERLAPPEDWINDOW)) | WS_CHILD;
+        ShowWindow(view, SW_HIDE);
+        SetWindowLongW(view, GWL_STYLE, child_style as LONG);
+        // `SetParent` returns null both on failure and for a view that had
+        // no parent, so failure is told apart by the error code.
+        SetLastError(0);
+        if SetParent(view, parent).is_null() && GetLastError() != 0 {
+            SetWindowLongW(view, GWL_STYLE, style as LONG);
+            return Err(Error::Null);
+        }
+        Ok(NativeView {
+            hwnd: view,
+            dpi,
+            style,
+        })
+    }
+
+    /// Place the view at `frame` in the window's content area, showing only
+    /// the part inside `clip`, both in px.
+    pub fn set_frame(&self, frame: Rect, clip: Rect) {
+        let scale = f64::from(self.dpi / 96.0);
+        let px = |v: f64| (v * scale).round() as i32;
+        let (x0, y0) = (px(frame.x0), px(frame.y0));
+        unsafe {
+            SetWindowPos(
+                self.hwnd,
+                null_mut(),
+                x0,
+                y0,
+                px(frame.x1) - x0,
+                px(frame.y1) - y0,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+            // The region is in the coordinates of the view, and is owned by
+            // the system once set.
+            let region = if clip.x0 <= frame.x0
+                && clip.y0 <= frame.y0
+                && clip.x1 >= frame.x1
+                && clip.y1 >= frame.y1
+            {
+                null_mut()
+            } else {
+                CreateRectRgn(
+                    px(clip.x0) - x0,
+                    px(clip.y0) - y0,
+                    px(clip.x1) - x0,
+                    px(clip.y1) - y0,
+                )
+            };
+            SetWindowRgn(self.hwnd, region, TRUE);
+        }
+    }
+
+    /// Show or hide the view. Views start hidden, so that nothing appears
+    /// before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        let cmd = if visible { SW_SHOWNA } else { SW_HIDE };
+        unsafe {
+            ShowWindow(self.hwnd, cmd);
+        }
+    }
+
+    /// Give the keyboard focus to the view.
+    pub fn focus(&self) {
+        unsafe {
+            SetFocus(self.hwnd);
+        }
+    }
+
+    /// Give the keyboard focus back to the window, if the view has it.
+    pub fn blur(&self) {
+        unsafe {
+            let focus = GetFocus();
+            if focus == self.hwnd || IsChild(self.hwnd, focus) != FALSE {
+                SetFocus(GetParent(self.hwnd));
+            }
+        }
+    }
+}
+
+impl Drop for NativeView {
+    fn drop(&mut self) {
+        unsafe {
+            ShowWindow(self.hwnd, SW_HIDE);
+            SetWindowRgn(self.hwnd, null_mut(), FALSE);
+            SetParent(self.hwnd, null_mut());
+            SetWindowLongW(self.hwnd, GWL_STYLE, self.style as LONG);
+        }
+    }
+}
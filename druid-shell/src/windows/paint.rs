@@ -34,7 +34,7 @@ use winapi::um::winuser::*;
 use winapi::Interface;
 
 use direct2d;
-use direct2d::enums::{AlphaMode, RenderTargetType};
+use direct2d::enums::{AlphaMode, PresentOptions, RenderTargetType};
 use direct2d::render_target::{DxgiSurfaceRenderTarget, GenericRenderTarget, HwndRenderTarget};
 
 use crate::util::as_result;
@@ -46,9 +46,14 @@ pub struct PaintCtx<'a> {
     pub(crate) render_target: &'a mut GenericRenderTarget,
 }
 
+/// Create a render target for the window. Without vsync, presenting does
+/// not wait for the display refresh. A transparent target keeps the alpha
+/// of what is painted, at the cost of ClearType text.
 pub(crate) unsafe fn create_render_target(
     d2d_factory: &direct2d::Factory,
     hwnd: HWND,
+    vsync: bool,
+    transparent: bool,
 ) -> Result<HwndRenderTarget, Error> {
     let mut rect: RECT = mem::uninitialized();
     GetClientRect(hwnd, &mut rect);
@@ -57,8 +62,17 @@ pub(crate) unsafe fn create_render_target(
     let res = HwndRenderTarget::create(d2d_factory)
         .with_hwnd(hwnd)
         .with_target_type(RenderTargetType::Default)
-        .with_alpha_mode(AlphaMode::Unknown)
+        .with_alpha_mode(if transparent {
+            AlphaMode::Premultiplied
+        } else {
+            AlphaMode::Unknown
+        })
         .with_pixel_size(width, height)
+        .with_present_options(if vsync {
+            PresentOptions::NONE
+        } else {
+            PresentOptions::IMMEDIATELY
+        })
         .build();
     if let Err(ref e) = res {
         println!("Error creating hwnd render target: {:?}", e);
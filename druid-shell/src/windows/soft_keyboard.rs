@@ -0,0 +1,94 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The on-screen keyboard, Windows implementation.
+//!
+//! Windows shows the touch keyboard by itself only for its own controls.
+//! Other applications toggle it through the `ITipInvocation` interface of
+//! the shell, and tell whether it is up from its window.
+
+#![allow(non_upper_case_globals)]
+
+use std::ptr::null_mut;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::SUCCEEDED;
+use winapi::shared::wtypesbase::{CLSCTX_INPROC_SERVER, CLSCTX_LOCAL_SERVER};
+use winapi::um::combaseapi::CoCreateInstance;
+use winapi::um::unknwnbase::{IUnknown, IUnknownVtbl};
+use winapi::um::winnt::HRESULT;
+use winapi::um::winuser::*;
+use winapi::Interface;
+
+use super::util::ToWide;
+
+RIDL! {#[uuid(0x37c9_94e7, 0x432b, 0x4834, 0xa2, 0xf7, 0xdc, 0xe1, 0xf1, 0x3b, 0x83, 0x4b)]
+interface ITipInvocation(ITipInvocationVtbl): IUnknown(IUnknownVtbl) {
+    fn Toggle(
+        wnd: HWND,
+    ) -> HRESULT,
+}}
+
+DEFINE_GUID! {CLSID_UIHostNoLaunch,
+0x4ce5_76fa, 0x83dc, 0x4f88, 0x95, 0x1c, 0x9d, 0x07, 0x82, 0xb4, 0xe3, 0x76}
+
+/// The window of the touch keyboard, if it has been shown before.
+fn keyboard_window() -> HWND {
+    let class = "IPTip_Main_Window".to_wide();
+    unsafe { FindWindowW(class.as_ptr(), null_mut()) }
+}
+
+fn is_shown() -> bool {
+    let hwnd = keyboard_window();
+    if hwnd.is_null() {
+        return false;
+    }
+    unsafe {
+        let style = GetWindowLongW(hwnd, GWL_STYLE) as DWORD;
+        IsWindowVisible(hwnd) != FALSE && style & WS_DISABLED == 0
+    }
+}
+
+/// Show the touch keyboard, if it isn't up already.
+pub(crate) fn show() {
+    if is_shown() {
+        return;
+    }
+    unsafe {
+        let mut invocation: *mut ITipInvocation = null_mut();
+        let hr = CoCreateInstance(
+            &CLSID_UIHostNoLaunch,
+            null_mut(),
+            CLSCTX_INPROC_SERVER | CLSCTX_LOCAL_SERVER,
+            &ITipInvocation::uuidof(),
+            &mut invocation as *mut *mut ITipInvocation as *mut *mut c_void,
+        );
+        // Without the touch keyboard service there is nothing to show.
+        if SUCCEEDED(hr) {
+            (*invocation).Toggle(GetDesktopWindow());
+            (*invocation).Release();
+        }
+    }
+}
+
+/// Hide the touch keyboard, if it is up.
+pub(crate) fn hide() {
+    if is_shown() {
+        unsafe {
+            PostMessageW(keyboard_window(), WM_SYSCOMMAND, SC_CLOSE, 0);
+        }
+    }
+}
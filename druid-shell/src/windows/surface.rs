@@ -0,0 +1,150 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! GPU surfaces, Windows implementation.
+//!
+//! A surface is a child window of its own, which the system composites with
+//! the parent without either redrawing the other. The parent clips its own
+//! painting around it. Mouse input passes through to the parent.
+
+use std::ptr::null_mut;
+
+use winapi::ctypes::c_void;
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::shared::winerror::ERROR_CLASS_ALREADY_EXISTS;
+use winapi::um::errhandlingapi::GetLastError;
+use winapi::um::winuser::*;
+
+use piet_common::kurbo::Rect;
+
+use crate::util::ToWide;
+use crate::window::RawWindowHandle;
+use crate::Error;
+
+const CLASS_NAME: &str = "Druid GPU Surface";
+
+/// A region of a window that is rendered with a GPU API of the caller's
+/// choice, such as OpenGL, Direct3D or wgpu, through `raw_window_handle`.
+///
+/// The surface is destroyed when dropped.
+pub struct GpuSurface {
+    hwnd: HWND,
+    dpi: f32,
+}
+
+impl GpuSurface {
+    /// Create a hidden surface over the client area of `parent`.
+    pub(crate) unsafe fn new(parent: HWND, dpi: f32) -> Result<GpuSurface, Error> {
+        let class_name = CLASS_NAME.to_wide();
+        let wnd = WNDCLASSW {
+            // Renderers such as OpenGL keep their context on the window's DC.
+            style: CS_OWNDC,
+            lpfnWndProc: Some(surface_proc),
+            cbClsExtra: 0,
+            cbWndExtra: 0,
+            hInstance: 0 as HINSTANCE,
+            hIcon: null_mut(),
+            hCursor: null_mut(),
+            hbrBackground: null_mut(),
+            lpszMenuName: null_mut(),
+            lpszClassName: class_name.as_ptr(),
+        };
+        if RegisterClassW(&wnd) == 0 && GetLastError() != ERROR_CLASS_ALREADY_EXISTS {
+            return Err(Error::Null);
+        }
+        let hwnd = CreateWindowExW(
+            0,
+            class_name.as_ptr(),
+            null_mut(),
+            WS_CHILD | WS_CLIPSIBLINGS,
+            0,
+            0,
+            0,
+            0,
+            parent,
+            null_mut(),
+            0 as HINSTANCE,
+            null_mut(),
+        );
+        if hwnd.is_null() {
+            return Err(Error::Null);
+        }
+        Ok(GpuSurface { hwnd, dpi })
+    }
+
+    /// Place the surface at `rect` in the window's content area, in px.
+    pub fn set_frame(&self, rect: Rect) {
+        let scale = f64::from(self.dpi / 96.0);
+        let x0 = (rect.x0 * scale).round() as i32;
+        let y0 = (rect.y0 * scale).round() as i32;
+        let x1 = (rect.x1 * scale).round() as i32;
+        let y1 = (rect.y1 * scale).round() as i32;
+        unsafe {
+            SetWindowPos(
+                self.hwnd,
+                null_mut(),
+                x0,
+                y0,
+                x1 - x0,
+                y1 - y0,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    /// Show or hide the surface. Surfaces start hidden, so that nothing
+    /// appears before the first `set_frame`.
+    pub fn set_visible(&self, visible: bool) {
+        let cmd = if visible { SW_SHOWNA } else { SW_HIDE };
+        unsafe {
+            ShowWindow(self.hwnd, cmd);
+        }
+    }
+
+    /// The native handles of the surface, for creating a swapchain or GL
+    /// context on it.
+    pub fn raw_window_handle(&self) -> Option<RawWindowHandle> {
+        unsafe {
+            let hinstance = GetWindowLongPtrW(self.hwnd, GWLP_HINSTANCE);
+            Some(RawWindowHandle::Win32 {
+                hwnd: self.hwnd as *mut c_void,
+                hinstance: hinstance as *mut c_void,
+            })
+        }
+    }
+}
+
+impl Drop for GpuSurface {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyWindow(self.hwnd);
+        }
+    }
+}
+
+unsafe extern "system" fn surface_proc(
+    hwnd: HWND,
+    msg: UINT,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match msg {
+        // Let the parent handle the mouse over the surface.
+        WM_NCHITTEST => HTTRANSPARENT,
+        // The renderer covers every pixel, so erasing would only flicker.
+        WM_ERASEBKGND => 1,
+        _ => DefWindowProcW(hwnd, msg, wparam, lparam),
+    }
+}
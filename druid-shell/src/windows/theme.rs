@@ -0,0 +1,115 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The light or dark system theme and other appearance and accessibility
+//! settings, Windows implementation.
+
+use std::mem;
+use std::ptr::null_mut;
+
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::HWND;
+use winapi::shared::winerror::{ERROR_SUCCESS, SUCCEEDED};
+use winapi::um::dwmapi::{DwmGetColorizationColor, DwmSetWindowAttribute};
+use winapi::um::winreg::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+use crate::util::ToWide;
+use crate::window::Theme;
+
+// DWMWA_USE_IMMERSIVE_DARK_MODE, which winapi doesn't define. Before
+// Windows 10 20H1 the attribute was undocumented and numbered 19.
+const DWMWA_USE_IMMERSIVE_DARK_MODE: DWORD = 20;
+const DWMWA_USE_IMMERSIVE_DARK_MODE_OLD: DWORD = 19;
+
+/// The theme chosen for apps in the personalization settings. Versions of
+/// Windows without a dark mode are light.
+pub(crate) fn system_theme() -> Theme {
+    if personalize_setting("AppsUseLightTheme") == Some(0) {
+        Theme::Dark
+    } else {
+        Theme::Light
+    }
+}
+
+/// Whether transparency effects are turned off in the personalization
+/// settings.
+pub(crate) fn reduce_transparency() -> bool {
+    personalize_setting("EnableTransparency") == Some(0)
+}
+
+/// The size of text chosen in the accessibility settings, relative to the
+/// default. Versions of Windows before 10 have no such setting.
+pub(crate) fn text_scale() -> f64 {
+    let percent = user_setting("Software\\Microsoft\\Accessibility", "TextScaleFactor");
+    match percent {
+        Some(percent) if percent >= 100 => f64::from(percent) / 100.0,
+        _ => 1.0,
+    }
+}
+
+/// The color of window frames, which follows the accent color, as 24-bit
+/// RGB. Windows without composition has none.
+pub(crate) fn accent_color() -> Option<u32> {
+    let mut argb: DWORD = 0;
+    let mut opaque: BOOL = FALSE;
+    let hr = unsafe { DwmGetColorizationColor(&mut argb, &mut opaque) };
+    if SUCCEEDED(hr) {
+        Some(argb & 0x00ff_ffff)
+    } else {
+        None
+    }
+}
+
+/// A value of the personalization settings of the current user.
+fn personalize_setting(name: &str) -> Option<DWORD> {
+    user_setting(
+        "Software\\Microsoft\\Windows\\CurrentVersion\\Themes\\Personalize",
+        name,
+    )
+}
+
+/// A `DWORD` value of a registry key of the current user.
+fn user_setting(key: &str, name: &str) -> Option<DWORD> {
+    let key = key.to_wide();
+    let value = name.to_wide();
+    let mut data: DWORD = 0;
+    let mut size = mem::size_of::<DWORD>() as DWORD;
+    let status = unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            null_mut(),
+            &mut data as *mut DWORD as *mut _,
+            &mut size,
+        )
+    };
+    if status as DWORD == ERROR_SUCCESS {
+        Some(data)
+    } else {
+        None
+    }
+}
+
+/// Draw the title bar and borders of a window in the theme.
+pub(crate) unsafe fn apply(hwnd: HWND, theme: Theme) {
+    let dark: BOOL = if theme == Theme::Dark { TRUE } else { FALSE };
+    let size = mem::size_of::<BOOL>() as DWORD;
+    let ptr = &dark as *const BOOL as *const _;
+    let hr = DwmSetWindowAttribute(hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE, ptr, size);
+    if !SUCCEEDED(hr) {
+        DwmSetWindowAttribute(hwnd, DWMWA_USE_IMMERSIVE_DARK_MODE_OLD, ptr, size);
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Timers, Windows implementation.
+//!
+//! `SetTimer` only has the resolution of the system tick, 15.6 ms by
+//! default. Instead a thread waits for the earliest deadline of the window
+//! and then posts `XI_TIMER`; the system timer resolution is raised to 1 ms
+//! while it waits.
+
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use winapi::shared::windef::HWND;
+use winapi::um::timeapi::{timeBeginPeriod, timeEndPeriod};
+use winapi::um::winuser::PostMessageW;
+
+use super::XI_TIMER;
+
+pub(crate) struct TimerThread {
+    shared: Arc<Shared>,
+}
+
+struct Shared {
+    state: Mutex<ThreadState>,
+    cond: Condvar,
+}
+
+#[derive(Default)]
+struct ThreadState {
+    deadline: Option<Instant>,
+    stopped: bool,
+}
+
+impl TimerThread {
+    pub(crate) fn new(hwnd: HWND) -> TimerThread {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(ThreadState::default()),
+            cond: Condvar::new(),
+        });
+        let thread_shared = shared.clone();
+        // Window handles may be used from any thread, but aren't `Send`.
+        let hwnd = hwnd as usize;
+        thread::spawn(move || run(&thread_shared, hwnd as HWND));
+        TimerThread { shared }
+    }
+
+    /// Post `XI_TIMER` to the window at `deadline`, replacing any earlier
+    /// request.
+    pub(crate) fn set_deadline(&self, deadline: Option<Instant>) {
+        self.shared.state.lock().unwrap().deadline = deadline;
+        self.shared.cond.notify_one();
+    }
+}
+
+impl Drop for TimerThread {
+    fn drop(&mut self) {
+        self.shared.state.lock().unwrap().stopped = true;
+        self.shared.cond.notify_one();
+    }
+}
+
+fn run(shared: &Shared, hwnd: HWND) {
+    let mut state = shared.state.lock().unwrap();
+    loop {
+        while state.deadline.is_none() && !state.stopped {
+            state = shared.cond.wait(state).unwrap();
+        }
+        if state.stopped {
+            return;
+        }
+        let deadline = state.deadline.unwrap();
+        let now = Instant::now();
+        if deadline <= now {
+            state.deadline = None;
+            unsafe {
+                PostMessageW(hwnd, XI_TIMER, 0, 0);
+            }
+            continue;
+        }
+        unsafe { timeBeginPeriod(1) };
+        state = shared.cond.wait_timeout(state, deadline - now).unwrap().0;
+        unsafe { timeEndPeriod(1) };
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! System tray icons, Windows implementation.
+
+use std::mem;
+use std::ptr::null;
+
+use winapi::shared::minwindef::*;
+use winapi::shared::windef::*;
+use winapi::um::shellapi::{
+    Shell_NotifyIconW, NIF_ICON, NIF_MESSAGE, NIF_TIP, NIM_ADD, NIM_DELETE, NOTIFYICONDATAW,
+};
+use winapi::um::winuser::*;
+
+use super::menu::Menu;
+use super::XI_TRAY;
+use crate::util::ToWide;
+
+/// A window has at most one tray icon, so the id is always the same.
+const TRAY_ICON_ID: UINT = 1;
+
+/// The tray icon of a window, removed from the notification area when
+/// dropped.
+pub(crate) struct TrayIcon {
+    hwnd: HWND,
+    hicon: HICON,
+    menu: Option<Menu>,
+}
+
+impl TrayIcon {
+    /// Add an icon to the notification area. Mouse events on it are sent to
+    /// `hwnd` as `XI_TRAY` messages. This takes ownership of `hicon`.
+    pub(crate) unsafe fn new(
+        hwnd: HWND,
+        hicon: HICON,
+        tooltip: &str,
+        menu: Option<Menu>,
+    ) -> Option<TrayIcon> {
+        let icon = TrayIcon { hwnd, hicon, menu };
+        let mut data = icon.data();
+        data.uFlags = NIF_ICON | NIF_TIP | NIF_MESSAGE;
+        data.uCallbackMessage = XI_TRAY;
+        data.hIcon = hicon;
+        let tip = tooltip.to_wide();
+        // Leave room for the terminating nul, which `to_wide` includes.
+        let len = tip.len().min(data.szTip.len()) - 1;
+        data.szTip[..len].copy_from_slice(&tip[..len]);
+        if Shell_NotifyIconW(NIM_ADD, &mut data) == FALSE {
+            return None;
+        }
+        Some(icon)
+    }
+
+    /// Show the menu at the pointer, as for a right click.
+    pub(crate) unsafe fn show_menu(&self) {
+        if let Some(ref menu) = self.menu {
+            let mut point: POINT = mem::zeroed();
+            GetCursorPos(&mut point);
+            // Without this, the menu doesn't close when clicking elsewhere.
+            SetForegroundWindow(self.hwnd);
+            TrackPopupMenu(
+                menu.hmenu(),
+                TPM_RIGHTBUTTON,
+                point.x,
+                point.y,
+                0,
+                self.hwnd,
+                null(),
+            );
+            PostMessageW(self.hwnd, WM_NULL, 0, 0);
+        }
+    }
+
+    unsafe fn data(&self) -> NOTIFYICONDATAW {
+        let mut data: NOTIFYICONDATAW = mem::zeroed();
+        data.cbSize = mem::size_of::<NOTIFYICONDATAW>() as DWORD;
+        data.hWnd = self.hwnd;
+        data.uID = TRAY_ICON_ID;
+        data
+    }
+}
+
+impl Drop for TrayIcon {
+    fn drop(&mut self) {
+        unsafe {
+            Shell_NotifyIconW(NIM_DELETE, &mut self.data());
+            DestroyIcon(self.hicon);
+        }
+    }
+}
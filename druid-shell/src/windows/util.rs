@@ -22,25 +22,31 @@ use std::mem;
 use std::os::windows::ffi::{OsStrExt, OsStringExt};
 use std::ptr;
 use std::slice;
-use winapi::ctypes::c_void;
+use winapi::ctypes::{c_int, c_void};
+use winapi::shared::basetsd::UINT32;
 use winapi::shared::guiddef::REFIID;
 use winapi::shared::minwindef::*;
 use winapi::shared::ntdef::*;
 use winapi::shared::windef::*;
 use winapi::shared::winerror::SUCCEEDED;
+use winapi::shared::wtypes::BSTR;
 use winapi::um::fileapi::*;
 use winapi::um::handleapi::*;
 use winapi::um::libloaderapi::*;
+use winapi::um::oaidl::VARIANT;
 use winapi::um::processenv::*;
 use winapi::um::shellscalingapi::*;
 use winapi::um::unknwnbase::IUnknown;
 use winapi::um::winbase::*;
 use winapi::um::wincon::*;
+use winapi::um::winuser::{POINTER_INPUT_TYPE, POINTER_PEN_INFO};
 // This needs to be explicit, otherwise HRESULT will conflict
 use winapi::um::winnt::{FILE_SHARE_WRITE, GENERIC_READ, GENERIC_WRITE};
 
 use direct2d::enums::DrawTextOptions;
 
+use super::accessibility::IRawElementProviderSimple;
+use super::backdrop::WindowCompositionAttribData;
 use crate::Error;
 
 pub fn as_result(hr: HRESULT) -> Result<(), Error> {
@@ -117,6 +123,41 @@ type DCompositionCreateDevice2 = unsafe extern "system" fn(
 ) -> HRESULT;
 type CreateDXGIFactory2 =
     unsafe extern "system" fn(Flags: UINT, riid: REFIID, ppFactory: *mut *mut c_void) -> HRESULT;
+type GetPointerType = unsafe extern "system" fn(UINT32, *mut POINTER_INPUT_TYPE) -> BOOL;
+type GetPointerPenInfo = unsafe extern "system" fn(UINT32, *mut POINTER_PEN_INFO) -> BOOL;
+type SetWindowCompositionAttribute =
+    unsafe extern "system" fn(HWND, *mut WindowCompositionAttribData) -> BOOL;
+// from uiautomationcore.dll
+type UiaHostProviderFromHwnd =
+    unsafe extern "system" fn(HWND, *mut *mut IRawElementProviderSimple) -> HRESULT;
+type UiaRaiseNotificationEvent = unsafe extern "system" fn(
+    provider: *mut IRawElementProviderSimple,
+    notificationKind: c_int,
+    notificationProcessing: c_int,
+    displayString: BSTR,
+    activityId: BSTR,
+) -> HRESULT;
+type UiaReturnRawElementProvider = unsafe extern "system" fn(
+    hwnd: HWND,
+    wParam: WPARAM,
+    lParam: LPARAM,
+    el: *mut IRawElementProviderSimple,
+) -> LRESULT;
+type UiaClientsAreListening = unsafe extern "system" fn() -> BOOL;
+type UiaRaiseAutomationEvent =
+    unsafe extern "system" fn(provider: *mut IRawElementProviderSimple, id: c_int) -> HRESULT;
+type UiaRaiseAutomationPropertyChangedEvent = unsafe extern "system" fn(
+    provider: *mut IRawElementProviderSimple,
+    id: c_int,
+    oldValue: VARIANT,
+    newValue: VARIANT,
+) -> HRESULT;
+type UiaRaiseStructureChangedEvent = unsafe extern "system" fn(
+    provider: *mut IRawElementProviderSimple,
+    structureChangeType: c_int,
+    pRuntimeId: *mut c_int,
+    RuntimeIdLen: c_int,
+) -> HRESULT;
 
 #[allow(non_snake_case)] // For member fields
 pub struct OptionalFunctions {
@@ -125,6 +166,16 @@ pub struct OptionalFunctions {
     pub SetProcessDpiAwareness: Option<SetProcessDpiAwareness>,
     pub DCompositionCreateDevice2: Option<DCompositionCreateDevice2>,
     pub CreateDXGIFactory2: Option<CreateDXGIFactory2>,
+    pub GetPointerType: Option<GetPointerType>,
+    pub GetPointerPenInfo: Option<GetPointerPenInfo>,
+    pub SetWindowCompositionAttribute: Option<SetWindowCompositionAttribute>,
+    pub UiaHostProviderFromHwnd: Option<UiaHostProviderFromHwnd>,
+    pub UiaRaiseNotificationEvent: Option<UiaRaiseNotificationEvent>,
+    pub UiaReturnRawElementProvider: Option<UiaReturnRawElementProvider>,
+    pub UiaClientsAreListening: Option<UiaClientsAreListening>,
+    pub UiaRaiseAutomationEvent: Option<UiaRaiseAutomationEvent>,
+    pub UiaRaiseAutomationPropertyChangedEvent: Option<UiaRaiseAutomationPropertyChangedEvent>,
+    pub UiaRaiseStructureChangedEvent: Option<UiaRaiseStructureChangedEvent>,
 }
 
 #[allow(non_snake_case)] // For local variables
@@ -172,12 +223,23 @@ fn load_optional_functions() -> OptionalFunctions {
     let user32 = load_library("user32.dll");
     let dcomp = load_library("dcomp.dll");
     let dxgi = load_library("dxgi.dll");
+    let uiautomationcore = load_library("uiautomationcore.dll");
 
     let mut GetDpiForSystem = None;
     let mut GetDpiForMonitor = None;
     let mut SetProcessDpiAwareness = None;
     let mut DCompositionCreateDevice2 = None;
     let mut CreateDXGIFactory2 = None;
+    let mut GetPointerType = None;
+    let mut GetPointerPenInfo = None;
+    let mut SetWindowCompositionAttribute = None;
+    let mut UiaHostProviderFromHwnd = None;
+    let mut UiaRaiseNotificationEvent = None;
+    let mut UiaReturnRawElementProvider = None;
+    let mut UiaClientsAreListening = None;
+    let mut UiaRaiseAutomationEvent = None;
+    let mut UiaRaiseAutomationPropertyChangedEvent = None;
+    let mut UiaRaiseStructureChangedEvent = None;
 
     if shcore.is_null() {
         println!("No shcore.dll");
@@ -190,6 +252,9 @@ fn load_optional_functions() -> OptionalFunctions {
         println!("No user32.dll");
     } else {
         load_function!(user32, GetDpiForSystem, "10");
+        load_function!(user32, GetPointerType, "8");
+        load_function!(user32, GetPointerPenInfo, "8");
+        load_function!(user32, SetWindowCompositionAttribute, "10");
     }
 
     if !dcomp.is_null() {
@@ -200,12 +265,36 @@ fn load_optional_functions() -> OptionalFunctions {
         load_function!(dxgi, CreateDXGIFactory2, "8.1");
     }
 
+    if !uiautomationcore.is_null() {
+        load_function!(uiautomationcore, UiaHostProviderFromHwnd, "Vista");
+        load_function!(uiautomationcore, UiaRaiseNotificationEvent, "10");
+        load_function!(uiautomationcore, UiaReturnRawElementProvider, "Vista");
+        load_function!(uiautomationcore, UiaClientsAreListening, "Vista");
+        load_function!(uiautomationcore, UiaRaiseAutomationEvent, "Vista");
+        load_function!(
+            uiautomationcore,
+            UiaRaiseAutomationPropertyChangedEvent,
+            "Vista"
+        );
+        load_function!(uiautomationcore, UiaRaiseStructureChangedEvent, "Vista");
+    }
+
     OptionalFunctions {
         GetDpiForSystem,
         GetDpiForMonitor,
         SetProcessDpiAwareness,
         DCompositionCreateDevice2,
         CreateDXGIFactory2,
+        GetPointerType,
+        GetPointerPenInfo,
+        SetWindowCompositionAttribute,
+        UiaHostProviderFromHwnd,
+        UiaRaiseNotificationEvent,
+        UiaReturnRawElementProvider,
+        UiaClientsAreListening,
+        UiaRaiseAutomationEvent,
+        UiaRaiseAutomationPropertyChangedEvent,
+        UiaRaiseStructureChangedEvent,
     }
 }
 
@@ -240,9 +240,9 @@ fn build_calc(ui: &mut UiState) {
 fn action_for_key(event: &KeyEvent) -> Option<CalcAction> {
     eprintln!("{:?}", event);
     match event {
-        e if e.key_code == KeyCode::Return => Some(CalcAction::Op('=')),
-        e if e.key_code == KeyCode::Backspace => Some(CalcAction::Op('⌫')),
-        e if e.key_code.is_printable() => {
+        e if e.code == KeyCode::Return => Some(CalcAction::Op('=')),
+        e if e.code == KeyCode::Backspace => Some(CalcAction::Op('⌫')),
+        e if e.code.is_printable() => {
             let ch = e.text().and_then(|s| s.chars().next()).unwrap_or('\u{0}');
             match ch {
                 '0'..='9' => Some(CalcAction::Digit(ch as u8 - b'0')),
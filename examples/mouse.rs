@@ -18,7 +18,7 @@ use druid::kurbo::{Point, Rect, Size};
 use druid::piet::{Color, FillRule, RenderContext};
 
 use druid::shell::{runloop, WindowBuilder};
-use druid::widget::{ScrollEvent, Widget};
+use druid::widget::{WheelEvent, Widget};
 use druid::{
     BoxConstraints, HandlerCtx, Id, LayoutCtx, LayoutResult, PaintCtx, Ui, UiMain, UiState,
 };
@@ -54,9 +54,10 @@ impl Widget for FooWidget {
         LayoutResult::Size(bc.constrain((100.0, 100.0)))
     }
 
-    fn scroll(&mut self, event: &ScrollEvent, ctx: &mut HandlerCtx) {
-        self.size.width += event.dx as f64;
-        self.size.height += event.dy as f64;
+    fn wheel(&mut self, event: &WheelEvent, ctx: &mut HandlerCtx) {
+        let (dx, dy) = event.to_pixels(16.0, 100.0);
+        self.size.width += dx;
+        self.size.height += dy;
         ctx.invalidate();
     }
 
@@ -23,13 +23,14 @@ use druid::shell::{runloop, WindowBuilder};
 
 use druid::widget::{Button, Padding, Row, Widget};
 use druid::{
-    BoxConstraints, FileDialogOptions, FileDialogType, Id, LayoutCtx, LayoutResult, PaintCtx, Ui,
+    BoxConstraints, FileDialogOptions, FileInfo, Id, Keymap, LayoutCtx, LayoutResult, PaintCtx, Ui,
     UiMain, UiState,
 };
 
 const STROKECOLOR: Color = Color::rgb24(0xfb_f8_ef);
 const COMMAND_EXIT: u32 = 0x100;
 const COMMAND_OPEN: u32 = 0x101;
+const COMMAND_FILES_CHOSEN: u32 = 0x102;
 
 /// A very simple custom widget.
 struct FooWidget;
@@ -65,9 +66,12 @@ impl FooWidget {
 fn main() {
     druid_shell::init();
 
+    let mut keymap = Keymap::new();
+    keymap.add(MenuKey::command('o'), COMMAND_OPEN);
+
     let mut file_menu = Menu::new();
     file_menu.add_item(COMMAND_EXIT, "E&xit", MenuKey::std_quit());
-    file_menu.add_item(COMMAND_OPEN, "O&pen", 'o');
+    keymap.add_menu_item(&mut file_menu, COMMAND_OPEN, "O&pen");
     let mut menubar = Menu::new();
     menubar.add_dropdown(file_menu, "&File");
 
@@ -91,12 +95,17 @@ fn main() {
     state.add_listener(button2, move |_: &mut bool, mut ctx| {
         ctx.poke(button2, &mut "Naughty naughty".to_string());
     });
+    state.set_keymap(keymap);
     state.set_command_listener(|cmd, mut ctx| match cmd {
         COMMAND_EXIT => ctx.close(),
         COMMAND_OPEN => {
-            let options = FileDialogOptions::default();
-            let result = ctx.file_dialog(FileDialogType::Open, options);
-            println!("result = {:?}", result);
+            let mut options = FileDialogOptions::default();
+            options.set_multi_selection();
+            ctx.open_file_dialog(options, COMMAND_FILES_CHOSEN);
+        }
+        COMMAND_FILES_CHOSEN => {
+            let files = ctx.command_payload::<Vec<FileInfo>>();
+            println!("result = {:?}", files);
         }
         _ => println!("unexpected command {}", cmd),
     });
@@ -90,8 +90,8 @@ fn build_typing(ui: &mut UiState) {
 
 fn action_for_key(event: &KeyEvent) -> Option<TypingAction> {
     match event {
-        e if e.key_code == KeyCode::Backspace => Some(TypingAction::Delete),
-        e if e.key_code.is_printable() => Some(TypingAction::Append(
+        e if e.code == KeyCode::Backspace => Some(TypingAction::Delete),
+        e if e.code.is_printable() => Some(TypingAction::Append(
             e.text().map(String::from).unwrap_or_default(),
         )),
         _ => None,
@@ -0,0 +1,165 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Solid and gradient fills, placed relative to the rectangle they fill.
+
+use crate::kurbo::{Rect, Shape, Vec2};
+use crate::piet::{self, Color, FillRule, Gradient, GradientStop};
+use crate::PaintCtx;
+
+/// A point in a rectangle, with `(0, 0)` its top left corner and `(1, 1)`
+/// its bottom right one, so that a gradient fits widgets of any size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UnitPoint {
+    pub x: f64,
+    pub y: f64,
+}
+
+impl UnitPoint {
+    pub const TOP_LEFT: UnitPoint = UnitPoint::new(0.0, 0.0);
+    pub const TOP: UnitPoint = UnitPoint::new(0.5, 0.0);
+    pub const TOP_RIGHT: UnitPoint = UnitPoint::new(1.0, 0.0);
+    pub const LEFT: UnitPoint = UnitPoint::new(0.0, 0.5);
+    pub const CENTER: UnitPoint = UnitPoint::new(0.5, 0.5);
+    pub const RIGHT: UnitPoint = UnitPoint::new(1.0, 0.5);
+    pub const BOTTOM_LEFT: UnitPoint = UnitPoint::new(0.0, 1.0);
+    pub const BOTTOM: UnitPoint = UnitPoint::new(0.5, 1.0);
+    pub const BOTTOM_RIGHT: UnitPoint = UnitPoint::new(1.0, 1.0);
+
+    pub const fn new(x: f64, y: f64) -> UnitPoint {
+        UnitPoint { x, y }
+    }
+
+    /// The point in `rect`.
+    pub fn resolve(self, rect: &Rect) -> Vec2 {
+        Vec2::new(
+            rect.x0 + self.x * rect.width(),
+            rect.y0 + self.y * rect.height(),
+        )
+    }
+}
+
+/// A gradient along the line from `start` to `end`.
+#[derive(Clone)]
+pub struct LinearGradient {
+    pub start: UnitPoint,
+    pub end: UnitPoint,
+    /// The stops, at least two, with positions from 0 at `start` to 1 at
+    /// `end`.
+    pub stops: Vec<GradientStop>,
+}
+
+impl LinearGradient {
+    pub fn new(start: UnitPoint, end: UnitPoint, stops: Vec<GradientStop>) -> LinearGradient {
+        LinearGradient { start, end, stops }
+    }
+
+    /// The gradient for `rect`.
+    pub fn resolve(&self, rect: &Rect) -> Gradient {
+        Gradient::Linear(piet::LinearGradient {
+            start: self.start.resolve(rect),
+            end: self.end.resolve(rect),
+            stops: self.stops.clone(),
+        })
+    }
+}
+
+/// A gradient in circles around `center`.
+#[derive(Clone)]
+pub struct RadialGradient {
+    pub center: UnitPoint,
+    /// The radius of the circle at position 1, relative to half the
+    /// diagonal of the rectangle, so that 1 reaches its corners from the
+    /// center.
+    pub radius: f64,
+    /// The stops, at least two, with positions from 0 at the center to 1 at
+    /// the radius.
+    pub stops: Vec<GradientStop>,
+}
+
+impl RadialGradient {
+    pub fn new(center: UnitPoint, radius: f64, stops: Vec<GradientStop>) -> RadialGradient {
+        RadialGradient {
+            center,
+            radius,
+            stops,
+        }
+    }
+
+    /// The gradient for `rect`.
+    pub fn resolve(&self, rect: &Rect) -> Gradient {
+        let half_diagonal = rect.width().hypot(rect.height()) / 2.0;
+        Gradient::Radial(piet::RadialGradient {
+            center: self.center.resolve(rect),
+            origin_offset: Vec2::new(0.0, 0.0),
+            radius: self.radius * half_diagonal,
+            stops: self.stops.clone(),
+        })
+    }
+}
+
+/// Stops for `colors` spaced evenly from 0 to 1.
+pub fn even_stops(colors: &[Color]) -> Vec<GradientStop> {
+    let last = colors.len().saturating_sub(1).max(1) as f32;
+    colors
+        .iter()
+        .enumerate()
+        .map(|(i, color)| GradientStop {
+            pos: i as f32 / last,
+            color: color.clone(),
+        })
+        .collect()
+}
+
+/// How to fill the background of a widget.
+#[derive(Clone)]
+pub enum Background {
+    Color(Color),
+    Linear(LinearGradient),
+    Radial(RadialGradient),
+}
+
+impl Background {
+    /// Fill `shape`, with gradients placed relative to `rect`, usually the
+    /// geometry of the widget.
+    pub fn fill(&self, paint_ctx: &mut PaintCtx, shape: impl Shape, rect: &Rect) {
+        let gradient = match self {
+            Background::Color(color) => {
+                paint_ctx.fill_color(shape, color, FillRule::NonZero);
+                return;
+            }
+            Background::Linear(gradient) => gradient.resolve(rect),
+            Background::Radial(gradient) => gradient.resolve(rect),
+        };
+        paint_ctx.fill_gradient(shape, &gradient, FillRule::NonZero);
+    }
+}
+
+impl From<Color> for Background {
+    fn from(color: Color) -> Background {
+        Background::Color(color)
+    }
+}
+
+impl From<LinearGradient> for Background {
+    fn from(gradient: LinearGradient) -> Background {
+        Background::Linear(gradient)
+    }
+}
+
+impl From<RadialGradient> for Background {
+    fn from(gradient: RadialGradient) -> Background {
+        Background::Radial(gradient)
+    }
+}
@@ -0,0 +1,114 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Brushes kept across paints, so widgets filling with the same colors and
+//! gradients each frame don't make new ones each time.
+
+use std::collections::hash_map::{Entry, HashMap};
+
+use crate::kurbo::Vec2;
+use crate::piet::{Color, Gradient, GradientStop, Piet, RenderContext};
+
+pub(crate) type Brush = <Piet<'static> as RenderContext>::Brush;
+
+/// The number of frames a brush is kept for without being used.
+const KEEP_FRAMES: u64 = 60;
+
+/// What a brush paints with.
+#[derive(Clone, Copy)]
+pub(crate) enum BrushSource<'a> {
+    Solid(&'a Color),
+    Gradient(&'a Gradient),
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum BrushKey {
+    Solid(u32),
+    /// The bits of the numbers describing the gradient.
+    Gradient(Vec<u64>),
+}
+
+/// The brushes made for the render context of a window.
+#[derive(Default)]
+pub(crate) struct BrushCache {
+    /// Each brush, with the frame it was last used in.
+    brushes: HashMap<BrushKey, (u64, Brush)>,
+    frame: u64,
+}
+
+impl BrushCache {
+    /// Drop all brushes, for when the render context is replaced.
+    pub(crate) fn clear(&mut self) {
+        self.brushes.clear();
+    }
+
+    /// The brush for `source`, made with `render_ctx` if it isn't cached.
+    pub(crate) fn get(&mut self, render_ctx: &mut Piet, source: BrushSource) -> Option<&Brush> {
+        let key = match source {
+            BrushSource::Solid(color) => BrushKey::Solid(color.as_rgba32()),
+            BrushSource::Gradient(gradient) => BrushKey::Gradient(gradient_key(gradient)),
+        };
+        let entry = match self.brushes.entry(key) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert((0, make_brush(render_ctx, source)?)),
+        };
+        entry.0 = self.frame;
+        Some(&entry.1)
+    }
+
+    /// Finish a frame, dropping the brushes that haven't been used for a
+    /// while, such as gradients of widgets that have since been resized.
+    pub(crate) fn end_frame(&mut self) {
+        let frame = self.frame;
+        self.brushes
+            .retain(|_, (used, _)| frame - *used < KEEP_FRAMES);
+        self.frame += 1;
+    }
+}
+
+/// Make a brush for `render_ctx` from `source`, without caching it. Invalid
+/// gradients, such as those with a single stop, have none.
+pub(crate) fn make_brush(render_ctx: &mut Piet, source: BrushSource) -> Option<Brush> {
+    match source {
+        BrushSource::Solid(color) => Some(render_ctx.solid_brush(color.clone())),
+        BrushSource::Gradient(gradient) => render_ctx.gradient(gradient.clone()).ok(),
+    }
+}
+
+fn gradient_key(gradient: &Gradient) -> Vec<u64> {
+    fn push_vec(key: &mut Vec<u64>, v: Vec2) {
+        key.push(v.x.to_bits());
+        key.push(v.y.to_bits());
+    }
+    let mut key = Vec::new();
+    let stops = match gradient {
+        Gradient::Linear(linear) => {
+            key.push(0);
+            push_vec(&mut key, linear.start);
+            push_vec(&mut key, linear.end);
+            &linear.stops
+        }
+        Gradient::Radial(radial) => {
+            key.push(1);
+            push_vec(&mut key, radial.center);
+            push_vec(&mut key, radial.origin_offset);
+            key.push(radial.radius.to_bits());
+            &radial.stops
+        }
+    };
+    for &GradientStop { pos, ref color } in stops {
+        key.push(u64::from(pos.to_bits()) << 32 | u64::from(color.as_rgba32()));
+    }
+    key
+}
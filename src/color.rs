@@ -0,0 +1,259 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Constructing, converting and mixing colors.
+
+use std::error::Error;
+use std::fmt;
+
+use crate::piet::Color;
+
+/// The reason a string given to `ColorExt::from_hex_str` is not a color.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorParseError {
+    /// The string is not 3, 4, 6 or 8 digits long, after an optional `#`.
+    WrongLength(usize),
+    /// The string contains a character that isn't a hex digit.
+    NotHex(char),
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ColorParseError::WrongLength(len) => {
+                write!(f, "expected 3, 4, 6 or 8 hex digits, found {}", len)
+            }
+            ColorParseError::NotHex(c) => write!(f, "{:?} is not a hex digit", c),
+        }
+    }
+}
+
+impl Error for ColorParseError {}
+
+/// Methods on piet's `Color`, beyond the packed integers it is made from.
+///
+/// Components are in the range 0 to 1, except hues, which are in degrees
+/// from 0 to 360; values out of range are clamped. Colors are in sRGB, with
+/// straight (not premultiplied) alpha.
+pub trait ColorExt {
+    /// A color from 8-bit red, green and blue, fully opaque.
+    fn rgb8(r: u8, g: u8, b: u8) -> Color;
+
+    fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Color;
+
+    /// Parse a color written in hex as in CSS, such as `"#1e40d8"`. The `#`
+    /// is optional, and the forms `rgb`, `rgba`, `rrggbb` and `rrggbbaa` are
+    /// accepted.
+    fn from_hex_str(hex: &str) -> Result<Color, ColorParseError>;
+
+    /// A color from hue, saturation and lightness, fully opaque.
+    fn hsl(h: f64, s: f64, l: f64) -> Color;
+
+    fn hsla(h: f64, s: f64, l: f64, a: f64) -> Color;
+
+    /// A color from hue, saturation and value, fully opaque.
+    fn hsv(h: f64, s: f64, v: f64) -> Color;
+
+    fn hsva(h: f64, s: f64, v: f64, a: f64) -> Color;
+
+    /// The red, green, blue and alpha components.
+    fn as_rgba(&self) -> (f64, f64, f64, f64);
+
+    /// The hue, saturation and lightness. Grays have a hue of 0.
+    fn as_hsl(&self) -> (f64, f64, f64);
+
+    /// The hue, saturation and value. Grays have a hue of 0.
+    fn as_hsv(&self) -> (f64, f64, f64);
+
+    fn alpha(&self) -> f64;
+
+    /// The same color with its alpha replaced by `alpha`.
+    fn with_alpha(&self, alpha: f64) -> Color;
+
+    /// The color `t` of the way from `self` to `other`, for `t` from 0 to 1.
+    ///
+    /// The components are interpolated premultiplied by alpha, so fading to
+    /// a transparent color doesn't darken on the way.
+    fn lerp(&self, other: &Color, t: f64) -> Color;
+}
+
+impl ColorExt for Color {
+    fn rgb8(r: u8, g: u8, b: u8) -> Color {
+        Color::rgba8(r, g, b, 0xff)
+    }
+
+    fn rgba8(r: u8, g: u8, b: u8, a: u8) -> Color {
+        Color::rgba32(u32::from(r) << 24 | u32::from(g) << 16 | u32::from(b) << 8 | u32::from(a))
+    }
+
+    fn from_hex_str(hex: &str) -> Result<Color, ColorParseError> {
+        let hex = hex.strip_prefix('#').unwrap_or(hex);
+        let mut digits = Vec::with_capacity(8);
+        for c in hex.chars() {
+            digits.push(c.to_digit(16).ok_or(ColorParseError::NotHex(c))?);
+        }
+        let digits = match digits.len() {
+            3 | 4 => digits
+                .iter()
+                .flat_map(|&digit| vec![digit, digit])
+                .collect(),
+            6 | 8 => digits,
+            len => return Err(ColorParseError::WrongLength(len)),
+        };
+        let rgba = digits.iter().fold(0, |rgba, &digit| rgba << 4 | digit);
+        let rgba = if digits.len() == 6 {
+            rgba << 8 | 0xff
+        } else {
+            rgba
+        };
+        Ok(Color::rgba32(rgba))
+    }
+
+    fn hsl(h: f64, s: f64, l: f64) -> Color {
+        Color::hsla(h, s, l, 1.0)
+    }
+
+    fn hsla(h: f64, s: f64, l: f64, a: f64) -> Color {
+        let (s, l) = (clamp(s), clamp(l));
+        let chroma = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        from_hue(h, chroma, l - chroma / 2.0, a)
+    }
+
+    fn hsv(h: f64, s: f64, v: f64) -> Color {
+        Color::hsva(h, s, v, 1.0)
+    }
+
+    fn hsva(h: f64, s: f64, v: f64, a: f64) -> Color {
+        let chroma = clamp(v) * clamp(s);
+        from_hue(h, chroma, clamp(v) - chroma, a)
+    }
+
+    fn as_rgba(&self) -> (f64, f64, f64, f64) {
+        let rgba = self.as_rgba32();
+        let channel = |shift: u32| f64::from((rgba >> shift) & 0xff) / 255.0;
+        (channel(24), channel(16), channel(8), channel(0))
+    }
+
+    fn as_hsl(&self) -> (f64, f64, f64) {
+        let (h, max, min) = hue(self);
+        let l = (max + min) / 2.0;
+        let s = if max == min {
+            0.0
+        } else {
+            (max - min) / (1.0 - (2.0 * l - 1.0).abs())
+        };
+        (h, s, l)
+    }
+
+    fn as_hsv(&self) -> (f64, f64, f64) {
+        let (h, max, min) = hue(self);
+        let s = if max == 0.0 { 0.0 } else { (max - min) / max };
+        (h, s, max)
+    }
+
+    fn alpha(&self) -> f64 {
+        self.as_rgba().3
+    }
+
+    fn with_alpha(&self, alpha: f64) -> Color {
+        let (r, g, b, _) = self.as_rgba();
+        Color::rgba(r, g, b, clamp(alpha))
+    }
+
+    fn lerp(&self, other: &Color, t: f64) -> Color {
+        let t = clamp(t);
+        let (r0, g0, b0, a0) = self.as_rgba();
+        let (r1, g1, b1, a1) = other.as_rgba();
+        let a = a0 + (a1 - a0) * t;
+        if a == 0.0 {
+            return Color::rgba32(0);
+        }
+        let mix = |c0: f64, c1: f64| (c0 * a0 + (c1 * a1 - c0 * a0) * t) / a;
+        Color::rgba(mix(r0, r1), mix(g0, g1), mix(b0, b1), a)
+    }
+}
+
+fn clamp(value: f64) -> f64 {
+    value.clamp(0.0, 1.0)
+}
+
+/// The color of hue `h` and the given chroma, with `m` added to each
+/// component.
+fn from_hue(h: f64, chroma: f64, m: f64, a: f64) -> Color {
+    let sector = h.rem_euclid(360.0) / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    Color::rgba(clamp(r + m), clamp(g + m), clamp(b + m), clamp(a))
+}
+
+/// The hue of `color`, and its largest and smallest components.
+fn hue(color: &Color) -> (f64, f64, f64) {
+    let (r, g, b, _) = color.as_rgba();
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let chroma = max - min;
+    let sector = if chroma == 0.0 {
+        0.0
+    } else if max == r {
+        ((g - b) / chroma).rem_euclid(6.0)
+    } else if max == g {
+        (b - r) / chroma + 2.0
+    } else {
+        (r - g) / chroma + 4.0
+    };
+    (sector * 60.0, max, min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hex() {
+        let parse = |hex| Color::from_hex_str(hex).map(|color| color.as_rgba32());
+        assert_eq!(parse("#1e40d8"), Ok(0x1e_40_d8_ff));
+        assert_eq!(parse("1e40d880"), Ok(0x1e_40_d8_80));
+        assert_eq!(parse("#f80"), Ok(0xff_88_00_ff));
+        assert_eq!(parse("#f808"), Ok(0xff_88_00_88));
+        assert_eq!(parse("#1e40d"), Err(ColorParseError::WrongLength(5)));
+        assert_eq!(parse("#1e40dg"), Err(ColorParseError::NotHex('g')));
+    }
+
+    #[test]
+    fn hsl_hsv_round_trip() {
+        let color = Color::from_hex_str("#1e40d8").unwrap();
+        let (h, s, l) = color.as_hsl();
+        assert_eq!(Color::hsl(h, s, l).as_rgba32(), color.as_rgba32());
+        let (h, s, v) = color.as_hsv();
+        assert_eq!(Color::hsv(h, s, v).as_rgba32(), color.as_rgba32());
+        assert_eq!(Color::hsl(120.0, 1.0, 0.5).as_rgba32(), 0x00_ff_00_ff);
+        assert_eq!(Color::hsv(-120.0, 1.0, 1.0).as_rgba32(), 0x00_00_ff_ff);
+    }
+
+    #[test]
+    fn lerp() {
+        let red = Color::rgb8(0xff, 0, 0);
+        let blue = Color::rgb8(0, 0, 0xff);
+        assert_eq!(red.lerp(&blue, 0.5).as_rgba32(), 0x80_00_80_ff);
+        let clear = Color::rgba8(0, 0, 0, 0);
+        assert_eq!(red.lerp(&clear, 0.5).as_rgba32(), 0xff_00_00_80);
+    }
+}
@@ -0,0 +1,129 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The queue of commands waiting to be delivered to the command listener.
+
+use std::any::Any;
+use std::collections::{BTreeSet, VecDeque};
+
+/// How soon a submitted command is delivered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandPriority {
+    /// Delivered before any pending command of normal priority.
+    High,
+    Normal,
+}
+
+/// Commands are delivered one at a time, once the events queued by the
+/// current input have been handled.
+#[derive(Default)]
+pub(crate) struct CommandQueue {
+    high: VecDeque<(u32, Option<Box<dyn Any>>)>,
+    normal: VecDeque<(u32, Option<Box<dyn Any>>)>,
+    /// Commands for which only the latest submission matters.
+    coalesced: BTreeSet<u32>,
+}
+
+impl CommandQueue {
+    pub fn push(&mut self, cmd: u32, priority: CommandPriority) {
+        self.push_with_payload(cmd, None, priority);
+    }
+
+    pub fn push_with_payload(
+        &mut self,
+        cmd: u32,
+        payload: Option<Box<dyn Any>>,
+        priority: CommandPriority,
+    ) {
+        if self.coalesced.contains(&cmd) {
+            self.high.retain(|&(c, _)| c != cmd);
+            self.normal.retain(|&(c, _)| c != cmd);
+        }
+        match priority {
+            CommandPriority::High => self.high.push_back((cmd, payload)),
+            CommandPriority::Normal => self.normal.push_back((cmd, payload)),
+        }
+    }
+
+    pub fn pop(&mut self) -> Option<(u32, Option<Box<dyn Any>>)> {
+        self.high.pop_front().or_else(|| self.normal.pop_front())
+    }
+
+    pub fn set_coalesced(&mut self, cmd: u32, coalesced: bool) {
+        if coalesced {
+            self.coalesced.insert(cmd);
+        } else {
+            self.coalesced.remove(&cmd);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(queue: &mut CommandQueue) -> Vec<u32> {
+        let mut cmds = Vec::new();
+        while let Some((cmd, _)) = queue.pop() {
+            cmds.push(cmd);
+        }
+        cmds
+    }
+
+    #[test]
+    fn high_priority_first() {
+        let mut queue = CommandQueue::default();
+        queue.push(1, CommandPriority::Normal);
+        queue.push(2, CommandPriority::High);
+        queue.push(3, CommandPriority::Normal);
+        queue.push(4, CommandPriority::High);
+        assert_eq!(drain(&mut queue), vec![2, 4, 1, 3]);
+    }
+
+    #[test]
+    fn fifo_within_priority() {
+        let mut queue = CommandQueue::default();
+        for cmd in 0..5 {
+            queue.push(cmd, CommandPriority::Normal);
+        }
+        // Repeats are kept unless coalesced.
+        queue.push(2, CommandPriority::Normal);
+        assert_eq!(drain(&mut queue), vec![0, 1, 2, 3, 4, 2]);
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn coalescing_replaces_pending() {
+        let mut queue = CommandQueue::default();
+        queue.set_coalesced(7, true);
+        queue.push_with_payload(7, Some(Box::new("old")), CommandPriority::Normal);
+        queue.push(1, CommandPriority::Normal);
+        queue.push_with_payload(7, Some(Box::new("new")), CommandPriority::Normal);
+        let (cmd, _) = queue.pop().unwrap();
+        assert_eq!(cmd, 1);
+        let (cmd, payload) = queue.pop().unwrap();
+        assert_eq!(cmd, 7);
+        assert_eq!(payload.unwrap().downcast_ref::<&str>(), Some(&"new"));
+        assert!(queue.pop().is_none());
+        // A high priority submission replaces a pending normal one.
+        queue.push(7, CommandPriority::Normal);
+        queue.push(1, CommandPriority::High);
+        queue.push(7, CommandPriority::High);
+        assert_eq!(drain(&mut queue), vec![1, 7]);
+        queue.set_coalesced(7, false);
+        queue.push(7, CommandPriority::Normal);
+        queue.push(7, CommandPriority::Normal);
+        assert_eq!(drain(&mut queue), vec![7, 7]);
+    }
+}
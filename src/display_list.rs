@@ -0,0 +1,123 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The recorded painting of retained widgets; see `Ui::set_retained`.
+
+use std::rc::Rc;
+
+use crate::kurbo::{Affine, BezPath, Point, Rect};
+use crate::piet::{Color, FillRule, Gradient, InterpolationMode, Piet, RenderContext};
+use crate::{FitMode, ImageBuf, PaintCtx, Stroke};
+
+pub(crate) type PietTextLayout = <Piet<'static> as RenderContext>::TextLayout;
+
+/// How an image was drawn, by the `PaintCtx` method of the same name with
+/// `draw_` in front.
+pub(crate) enum ImageOp {
+    Image(Rect),
+    ImageArea(Rect, Rect),
+    ImageFit(Rect, FitMode),
+    NineSlice(Rect, Rect),
+}
+
+/// A call to a painting method of `PaintCtx`, with shapes as paths.
+pub(crate) enum Op {
+    /// The start of the painting of a widget, or of `with_save`, whose
+    /// clips and transforms last until the matching `End`.
+    Begin,
+    End,
+    Clip(BezPath),
+    Transform(Affine),
+    FillColor(BezPath, Color, FillRule),
+    FillGradient(BezPath, Gradient, FillRule),
+    StrokeColor(BezPath, Color, Stroke),
+    DrawText(Rc<PietTextLayout>, Point, Color),
+    Image(ImageBuf, ImageOp, InterpolationMode),
+}
+
+/// The painting of a widget and its children, recorded in window
+/// coordinates.
+#[derive(Default)]
+pub(crate) struct DisplayList {
+    pub ops: Vec<Op>,
+    /// The geometry of the widget and the scale it was recorded at.
+    pub rect: Rect,
+    pub scale: f64,
+    /// Whether `ops` hold all of the painting: false before recording, and
+    /// once something that can't be recorded was painted.
+    pub complete: bool,
+}
+
+impl DisplayList {
+    pub fn new(rect: Rect, scale: f64) -> DisplayList {
+        DisplayList {
+            ops: Vec::new(),
+            rect,
+            scale,
+            complete: true,
+        }
+    }
+
+    /// Whether the list was recorded whole for a widget at `rect`, painted
+    /// at `scale`.
+    pub fn is_current(&self, rect: Rect, scale: f64) -> bool {
+        let r = self.rect;
+        self.complete
+            && (r.x0, r.y0, r.x1, r.y1) == (rect.x0, rect.y0, rect.x1, rect.y1)
+            && self.scale == scale
+    }
+
+    /// Paint the recorded ops into `paint_ctx`.
+    pub fn replay(&self, paint_ctx: &mut PaintCtx) {
+        let mut scopes = Vec::new();
+        for op in &self.ops {
+            match op {
+                Op::Begin => scopes.push(paint_ctx.begin_scope()),
+                Op::End => {
+                    if let Some(scope) = scopes.pop() {
+                        paint_ctx.end_scope(scope);
+                    }
+                }
+                Op::Clip(path) => paint_ctx.clip(path),
+                Op::Transform(transform) => paint_ctx.transform(*transform),
+                Op::FillColor(path, color, fill_rule) => {
+                    paint_ctx.fill_color(path, color, *fill_rule)
+                }
+                Op::FillGradient(path, gradient, fill_rule) => {
+                    paint_ctx.fill_gradient(path, gradient, *fill_rule)
+                }
+                Op::StrokeColor(path, color, stroke) => paint_ctx.stroke_color(path, color, stroke),
+                Op::DrawText(layout, pos, color) => {
+                    paint_ctx.draw_text(layout.clone(), *pos, color)
+                }
+                Op::Image(image, image_op, interp) => match *image_op {
+                    ImageOp::Image(rect) => paint_ctx.draw_image(image, rect, *interp),
+                    ImageOp::ImageArea(src, dst) => {
+                        paint_ctx.draw_image_area(image, src, dst, *interp)
+                    }
+                    ImageOp::ImageFit(rect, fit) => {
+                        paint_ctx.draw_image_fit(image, rect, fit, *interp)
+                    }
+                    ImageOp::NineSlice(center, rect) => {
+                        paint_ctx.draw_nine_slice(image, center, rect, *interp)
+                    }
+                },
+            }
+        }
+        // A list recorded whole has an `End` for every `Begin`.
+        for scope in scopes.into_iter().rev() {
+            paint_ctx.end_scope(scope);
+        }
+    }
+}
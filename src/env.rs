@@ -0,0 +1,254 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Values widgets look up by key, such as the colors and fonts of the theme.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::rc::Rc;
+
+use crate::piet::Color;
+use crate::LayoutDirection;
+
+/// The key of a value of type `T` in an `Env`.
+pub struct EnvKey<T> {
+    name: &'static str,
+    value_type: PhantomData<T>,
+}
+
+impl<T> EnvKey<T> {
+    /// A key named `name`, which should be unique, such as by starting with
+    /// the name of the crate defining it.
+    pub const fn new(name: &'static str) -> EnvKey<T> {
+        EnvKey {
+            name,
+            value_type: PhantomData,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<T> Clone for EnvKey<T> {
+    fn clone(&self) -> EnvKey<T> {
+        *self
+    }
+}
+
+impl<T> Copy for EnvKey<T> {}
+
+/// A value in an `Env`.
+#[derive(Clone)]
+pub enum Value {
+    Bool(bool),
+    Float(f64),
+    Color(Color),
+    String(String),
+    LayoutDirection(LayoutDirection),
+}
+
+/// The types of the values in an `Env`.
+pub trait ValueType: Sized {
+    fn into_value(self) -> Value;
+
+    /// The value of this type in `value`, if it holds one.
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl ValueType for bool {
+    fn into_value(self) -> Value {
+        Value::Bool(self)
+    }
+
+    fn from_value(value: &Value) -> Option<bool> {
+        match value {
+            Value::Bool(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl ValueType for f64 {
+    fn into_value(self) -> Value {
+        Value::Float(self)
+    }
+
+    fn from_value(value: &Value) -> Option<f64> {
+        match value {
+            Value::Float(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl ValueType for Color {
+    fn into_value(self) -> Value {
+        Value::Color(self)
+    }
+
+    fn from_value(value: &Value) -> Option<Color> {
+        match value {
+            Value::Color(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl ValueType for String {
+    fn into_value(self) -> Value {
+        Value::String(self)
+    }
+
+    fn from_value(value: &Value) -> Option<String> {
+        match value {
+            Value::String(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl ValueType for LayoutDirection {
+    fn into_value(self) -> Value {
+        Value::LayoutDirection(self)
+    }
+
+    fn from_value(value: &Value) -> Option<LayoutDirection> {
+        match value {
+            Value::LayoutDirection(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+/// Values looked up by key, such as those of `theme`, which the built-in
+/// widgets are drawn with. Cloning an environment is cheap; the values are
+/// copied when a clone is changed.
+#[derive(Clone, Default)]
+pub struct Env {
+    values: Rc<HashMap<&'static str, Value>>,
+}
+
+impl Env {
+    /// An environment with no values.
+    pub fn empty() -> Env {
+        Default::default()
+    }
+
+    /// The value of `key`.
+    ///
+    /// Panics if the key isn't set, or is set to a value of another type.
+    /// The keys of `theme` are set in the environment a `Ui` starts with.
+    pub fn get<T: ValueType>(&self, key: EnvKey<T>) -> T {
+        match self.values.get(key.name) {
+            Some(value) => T::from_value(value)
+                .unwrap_or_else(|| panic!("the value of {} has the wrong type", key.name)),
+            None => panic!("{} is not set in the environment", key.name),
+        }
+    }
+
+    /// The value of `key`, if it is set to a value of its type.
+    pub fn try_get<T: ValueType>(&self, key: EnvKey<T>) -> Option<T> {
+        self.values.get(key.name).and_then(T::from_value)
+    }
+
+    pub fn set<T: ValueType>(&mut self, key: EnvKey<T>, value: impl Into<T>) {
+        let value = value.into().into_value();
+        Rc::make_mut(&mut self.values).insert(key.name, value);
+    }
+
+    /// Whether the environments are the same one, rather than equal values.
+    pub(crate) fn same(&self, other: &Env) -> bool {
+        Rc::ptr_eq(&self.values, &other.values)
+    }
+
+    /// The environment with `key` set to `value`, for setting several.
+    pub fn adding<T: ValueType>(mut self, key: EnvKey<T>, value: impl Into<T>) -> Env {
+        self.set(key, value);
+        self
+    }
+
+    /// The values of `self` that `base` doesn't have, or has other values
+    /// for.
+    pub(crate) fn changes_from(&self, base: &Env) -> Env {
+        let values = self
+            .values
+            .iter()
+            .filter(|(name, value)| !base.values.get(*name).is_some_and(|b| b.same_as(value)))
+            .map(|(name, value)| (*name, value.clone()))
+            .collect();
+        Env {
+            values: Rc::new(values),
+        }
+    }
+
+    /// Set the values of `other` over those of `self`.
+    pub(crate) fn extend(&mut self, other: &Env) {
+        if !other.values.is_empty() {
+            let values = Rc::make_mut(&mut self.values);
+            values.extend(
+                other
+                    .values
+                    .iter()
+                    .map(|(name, value)| (*name, value.clone())),
+            );
+        }
+    }
+}
+
+impl Value {
+    fn same_as(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Bool(a), Value::Bool(b)) => a == b,
+            (Value::Float(a), Value::Float(b)) => a == b,
+            (Value::Color(a), Value::Color(b)) => a.as_rgba32() == b.as_rgba32(),
+            (Value::String(a), Value::String(b)) => a == b,
+            (Value::LayoutDirection(a), Value::LayoutDirection(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: EnvKey<f64> = EnvKey::new("test.size");
+    const NAME: EnvKey<String> = EnvKey::new("test.name");
+
+    #[test]
+    fn get_and_set() {
+        let env = Env::empty().adding(SIZE, 12.0);
+        let mut changed = env.clone();
+        changed.set(SIZE, 14.0);
+        assert_eq!(env.get(SIZE), 12.0);
+        assert_eq!(changed.get(SIZE), 14.0);
+        assert_eq!(env.try_get(NAME), None);
+        // A key of another type with the same name.
+        assert_eq!(env.try_get(EnvKey::<bool>::new("test.size")), None);
+    }
+
+    #[test]
+    fn changes_over_another_env() {
+        let base = Env::empty().adding(SIZE, 12.0).adding(NAME, "a");
+        let changes = base.clone().adding(SIZE, 14.0).changes_from(&base);
+        assert_eq!(changes.try_get(SIZE), Some(14.0));
+        assert_eq!(changes.try_get(NAME), None);
+        let mut other = Env::empty().adding(SIZE, 10.0).adding(NAME, "b");
+        other.extend(&changes);
+        assert_eq!(other.get(SIZE), 14.0);
+        assert_eq!(other.get(NAME), "b");
+    }
+}
@@ -0,0 +1,496 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Numbers, amounts of money, dates, durations and lists written as usual
+//! in a locale, and the `Formatter`s of `ValueTextBox`.
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const NBSP: char = '\u{a0}';
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateOrder {
+    Mdy,
+    Dmy,
+    Ymd,
+}
+
+/// How a language, or the language of a region, writes numbers and lists.
+#[derive(Debug, Clone, Copy)]
+struct Conventions {
+    decimal: char,
+    group: char,
+    /// The fewest digits before the decimal separator that are grouped.
+    min_grouping: usize,
+    date_order: DateOrder,
+    date_separator: &'static str,
+    currency_first: bool,
+    list_separator: &'static str,
+    /// What joins the items of a list of two, and the last two of longer
+    /// lists.
+    list_and: &'static str,
+    list_last: &'static str,
+}
+
+const ENGLISH: Conventions = Conventions {
+    decimal: '.',
+    group: ',',
+    min_grouping: 4,
+    date_order: DateOrder::Mdy,
+    date_separator: "/",
+    currency_first: true,
+    list_separator: ", ",
+    list_and: " and ",
+    list_last: ", and ",
+};
+
+fn conventions(locale: &str) -> Conventions {
+    let mut subtags = locale.split(|c| c == '-' || c == '_');
+    let language = subtags.next().unwrap_or("");
+    // Skip scripts, such as the `Hans` of `zh-Hans-CN`.
+    let region = subtags.find(|s| s.len() == 2).unwrap_or("");
+    let comma = |group, and| Conventions {
+        decimal: ',',
+        group,
+        date_order: DateOrder::Dmy,
+        date_separator: ".",
+        currency_first: false,
+        list_and: and,
+        list_last: and,
+        ..ENGLISH
+    };
+    let ymd = |separator| Conventions {
+        date_order: DateOrder::Ymd,
+        date_separator: separator,
+        ..ENGLISH
+    };
+    match language {
+        "en" => match region {
+            "" | "US" | "PH" => ENGLISH,
+            "CA" => ymd("-"),
+            _ => Conventions {
+                date_order: DateOrder::Dmy,
+                ..ENGLISH
+            },
+        },
+        "ja" => Conventions {
+            list_separator: "、",
+            list_and: "、",
+            list_last: "、",
+            ..ymd("/")
+        },
+        "zh" => Conventions {
+            list_separator: "、",
+            list_and: "和",
+            list_last: "和",
+            ..ymd("/")
+        },
+        "de" => comma('.', " und "),
+        "nl" => Conventions {
+            date_separator: "-",
+            ..comma('.', " en ")
+        },
+        "fr" => Conventions {
+            date_separator: "/",
+            ..comma('\u{202f}', " et ")
+        },
+        "es" => Conventions {
+            min_grouping: 5,
+            date_separator: "/",
+            ..comma('.', " y ")
+        },
+        "it" => Conventions {
+            date_separator: "/",
+            ..comma('.', " e ")
+        },
+        "pt" => Conventions {
+            date_separator: "/",
+            ..comma('.', " e ")
+        },
+        "ru" => comma(NBSP, " и "),
+        "uk" => comma(NBSP, " і "),
+        "pl" => Conventions {
+            min_grouping: 5,
+            ..comma(NBSP, " i ")
+        },
+        "sv" => Conventions {
+            date_order: DateOrder::Ymd,
+            date_separator: "-",
+            ..comma(NBSP, " och ")
+        },
+        "fi" => comma(NBSP, " ja "),
+        "nb" | "no" => comma(NBSP, " og "),
+        _ => ymd("-"),
+    }
+}
+
+/// A day of the Gregorian calendar.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    /// The month, from 1 to 12.
+    pub month: u32,
+    /// The day of the month, from 1.
+    pub day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Date {
+        Date { year, month, day }
+    }
+
+    /// The day of `time` in UTC.
+    pub fn from_system_time(time: SystemTime) -> Date {
+        let secs = match time.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_secs() as i64,
+            Err(e) => -(e.duration().as_secs() as i64) - 1,
+        };
+        // The days from the epoch to the civil date, by the algorithm of
+        // Howard Hinnant, with years starting in March.
+        let days = secs.div_euclid(86_400) + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date::new(year as i32, month, day)
+    }
+}
+
+/// Writes values as usual in a locale.
+///
+/// English, Chinese, Japanese and the common European languages are
+/// covered; others are written as in English, with dates as in ISO 8601.
+/// Numbers are written with Western digits.
+#[derive(Debug, Clone)]
+pub struct LocaleFormat {
+    locale: String,
+    conventions: Conventions,
+}
+
+impl LocaleFormat {
+    /// The format of `locale`, such as `"de-DE"`.
+    pub fn new(locale: &str) -> LocaleFormat {
+        LocaleFormat {
+            locale: locale.to_string(),
+            conventions: conventions(locale),
+        }
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// `n` with `decimals` digits after the decimal separator, and those
+    /// before it grouped by thousands.
+    pub fn number(&self, n: f64, decimals: usize) -> String {
+        self.write_number(n, Some(decimals), true)
+    }
+
+    /// `n` with as few digits as it can be written exactly with.
+    pub fn decimal(&self, n: f64) -> String {
+        self.write_number(n, None, true)
+    }
+
+    /// `n` as `number` or `decimal` write it, without grouping, as it is
+    /// easier to edit.
+    pub fn number_ungrouped(&self, n: f64, decimals: Option<usize>) -> String {
+        self.write_number(n, decimals, false)
+    }
+
+    fn write_number(&self, n: f64, decimals: Option<usize>, grouped: bool) -> String {
+        if !n.is_finite() {
+            return n.to_string();
+        }
+        let digits = match decimals {
+            Some(decimals) => format!("{:.*}", decimals, n.abs()),
+            None => n.abs().to_string(),
+        };
+        let mut parts = digits.splitn(2, '.');
+        let int = parts.next().unwrap_or("");
+        let mut out = String::new();
+        // No sign for numbers rounded to zero.
+        if n < 0.0 && digits.bytes().any(|b| b.is_ascii_digit() && b != b'0') {
+            out.push('-');
+        }
+        let c = &self.conventions;
+        for (i, digit) in int.chars().enumerate() {
+            let left = int.len() - i;
+            if grouped && i > 0 && left % 3 == 0 && int.len() >= c.min_grouping {
+                out.push(c.group);
+            }
+            out.push(digit);
+        }
+        if let Some(fraction) = parts.next() {
+            out.push(c.decimal);
+            out.push_str(fraction);
+        }
+        out
+    }
+
+    /// `amount` in the currency of the ISO 4217 `code`, such as `"EUR"`,
+    /// with the digits usual for it.
+    pub fn currency(&self, amount: f64, code: &str) -> String {
+        let number = self.number(amount.abs(), currency_decimals(code));
+        let symbol = currency_symbol(code);
+        let rounded_to_zero = !number.bytes().any(|b| b.is_ascii_digit() && b != b'0');
+        let sign = if amount < 0.0 && !rounded_to_zero {
+            "-"
+        } else {
+            ""
+        };
+        // Codes, unlike symbols, are spaced from the number.
+        let space = if symbol.chars().all(|c| c.is_ascii_uppercase()) {
+            NBSP.to_string()
+        } else {
+            String::new()
+        };
+        if self.conventions.currency_first {
+            format!("{}{}{}{}", sign, symbol, space, number)
+        } else {
+            format!("{}{}{}{}", sign, number, NBSP, symbol)
+        }
+    }
+
+    /// `date` in the short, numeric form, such as `10/14/2026` in the US.
+    pub fn date(&self, date: Date) -> String {
+        let c = &self.conventions;
+        let sep = c.date_separator;
+        let (y, m, d) = (date.year, date.month, date.day);
+        match c.date_order {
+            DateOrder::Mdy => format!("{}{}{}{}{}", m, sep, d, sep, y),
+            DateOrder::Dmy => format!("{:02}{}{:02}{}{}", d, sep, m, sep, y),
+            DateOrder::Ymd => format!("{}{}{:02}{}{:02}", y, sep, m, sep, d),
+        }
+    }
+
+    /// `duration` as on a clock, such as `1:05:09`, or `5:09` under an
+    /// hour, rounded down to seconds.
+    pub fn duration(&self, duration: Duration) -> String {
+        let secs = duration.as_secs();
+        let (h, m, s) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if h > 0 {
+            format!("{}:{:02}:{:02}", h, m, s)
+        } else {
+            format!("{}:{:02}", m, s)
+        }
+    }
+
+    /// The items joined into a list, such as `a, b, and c` in English.
+    pub fn list<S: AsRef<str>>(&self, items: &[S]) -> String {
+        let c = &self.conventions;
+        let mut out = String::new();
+        for (i, item) in items.iter().enumerate() {
+            if i > 0 {
+                out.push_str(if i + 1 < items.len() {
+                    c.list_separator
+                } else if items.len() == 2 {
+                    c.list_and
+                } else {
+                    c.list_last
+                });
+            }
+            out.push_str(item.as_ref());
+        }
+        out
+    }
+
+    /// The number written in `text`, with or without grouping. Spaces, a
+    /// leading minus and, in the locales grouping with a period, periods
+    /// between digits are ignored as group separators.
+    pub fn parse_number(&self, text: &str) -> Option<f64> {
+        let c = &self.conventions;
+        let mut number = String::new();
+        for ch in text.trim().chars() {
+            match ch {
+                ch if ch.is_whitespace() || ch == NBSP || ch == '\u{202f}' => (),
+                ch if ch == c.group => (),
+                ch if ch == c.decimal => number.push('.'),
+                '-' | '\u{2212}' if number.is_empty() => number.push('-'),
+                ch if ch.is_ascii_digit() => number.push(ch),
+                _ => return None,
+            }
+        }
+        number.parse().ok()
+    }
+}
+
+fn currency_decimals(code: &str) -> usize {
+    match code {
+        "JPY" | "KRW" | "VND" | "ISK" | "CLP" => 0,
+        _ => 2,
+    }
+}
+
+fn currency_symbol(code: &str) -> &str {
+    match code {
+        "USD" => "$",
+        "EUR" => "€",
+        "GBP" => "£",
+        "JPY" => "¥",
+        "CNY" => "CN¥",
+        "INR" => "₹",
+        "KRW" => "₩",
+        _ => code,
+    }
+}
+
+/// How a `ValueTextBox` writes its value, and reads back what was typed.
+pub trait Formatter<T> {
+    fn format(&self, value: &T, locale: &LocaleFormat) -> String;
+
+    /// The text the value is edited as, `format`'s unless it is harder to
+    /// edit.
+    fn format_for_editing(&self, value: &T, locale: &LocaleFormat) -> String {
+        self.format(value, locale)
+    }
+
+    /// The value written in `text`, if any.
+    fn parse(&self, text: &str, locale: &LocaleFormat) -> Option<T>;
+}
+
+/// Writes numbers as `LocaleFormat::decimal` does, or with a fixed number
+/// of decimals.
+#[derive(Debug, Clone, Default)]
+pub struct NumberFormatter {
+    decimals: Option<usize>,
+}
+
+impl NumberFormatter {
+    pub fn new() -> NumberFormatter {
+        Default::default()
+    }
+
+    pub fn decimals(mut self, decimals: usize) -> NumberFormatter {
+        self.decimals = Some(decimals);
+        self
+    }
+}
+
+impl Formatter<f64> for NumberFormatter {
+    fn format(&self, value: &f64, locale: &LocaleFormat) -> String {
+        match self.decimals {
+            Some(decimals) => locale.number(*value, decimals),
+            None => locale.decimal(*value),
+        }
+    }
+
+    fn format_for_editing(&self, value: &f64, locale: &LocaleFormat) -> String {
+        locale.number_ungrouped(*value, self.decimals)
+    }
+
+    fn parse(&self, text: &str, locale: &LocaleFormat) -> Option<f64> {
+        locale.parse_number(text)
+    }
+}
+
+/// Writes amounts of a currency as `LocaleFormat::currency` does; they are
+/// edited as numbers, and read with or without the symbol.
+#[derive(Debug, Clone)]
+pub struct CurrencyFormatter {
+    code: String,
+}
+
+impl CurrencyFormatter {
+    /// A formatter of amounts of the currency of the ISO 4217 `code`.
+    pub fn new(code: &str) -> CurrencyFormatter {
+        CurrencyFormatter {
+            code: code.to_string(),
+        }
+    }
+}
+
+impl Formatter<f64> for CurrencyFormatter {
+    fn format(&self, value: &f64, locale: &LocaleFormat) -> String {
+        locale.currency(*value, &self.code)
+    }
+
+    fn format_for_editing(&self, value: &f64, locale: &LocaleFormat) -> String {
+        let decimals = currency_decimals(&self.code);
+        locale.number_ungrouped(*value, Some(decimals))
+    }
+
+    fn parse(&self, text: &str, locale: &LocaleFormat) -> Option<f64> {
+        let text = text
+            .replace(currency_symbol(&self.code), "")
+            .replace(self.code.as_str(), "");
+        locale.parse_number(&text)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        let en = LocaleFormat::new("en-US");
+        let de = LocaleFormat::new("de-DE");
+        let fr = LocaleFormat::new("fr-FR");
+        let es = LocaleFormat::new("es");
+        assert_eq!(en.number(1234567.891, 2), "1,234,567.89");
+        assert_eq!(de.number(1234567.891, 2), "1.234.567,89");
+        assert_eq!(fr.decimal(-1234.5), "-1\u{202f}234,5");
+        assert_eq!(es.decimal(1234.0), "1234");
+        assert_eq!(es.decimal(12345.0), "12.345");
+        assert_eq!(en.number(-0.001, 2), "0.00");
+        assert_eq!(en.number_ungrouped(1234.5, None), "1234.5");
+
+        assert_eq!(de.parse_number("1.234,5"), Some(1234.5));
+        assert_eq!(en.parse_number(" -1,234.5 "), Some(-1234.5));
+        assert_eq!(en.parse_number("12a"), None);
+    }
+
+    #[test]
+    fn currencies() {
+        let en = LocaleFormat::new("en-US");
+        let de = LocaleFormat::new("de-DE");
+        assert_eq!(en.currency(-1234.5, "USD"), "-$1,234.50");
+        assert_eq!(de.currency(1234.5, "EUR"), "1.234,50\u{a0}€");
+        assert_eq!(en.currency(1234.6, "JPY"), "¥1,235");
+        assert_eq!(en.currency(5.0, "CHF"), "CHF\u{a0}5.00");
+        let formatter = CurrencyFormatter::new("EUR");
+        assert_eq!(formatter.parse("1.234,50 €", &de), Some(1234.5));
+    }
+
+    #[test]
+    fn dates_and_lists() {
+        let date = Date::new(2026, 3, 7);
+        assert_eq!(LocaleFormat::new("en-US").date(date), "3/7/2026");
+        assert_eq!(LocaleFormat::new("en-GB").date(date), "07/03/2026");
+        assert_eq!(LocaleFormat::new("de").date(date), "07.03.2026");
+        assert_eq!(LocaleFormat::new("ja").date(date), "2026/03/07");
+        assert_eq!(LocaleFormat::new("xx").date(date), "2026-03-07");
+
+        let day = Duration::from_secs(20_519 * 86_400 + 3600);
+        assert_eq!(
+            Date::from_system_time(UNIX_EPOCH + day),
+            Date::new(2026, 3, 7)
+        );
+        let before = UNIX_EPOCH - Duration::from_secs(1);
+        assert_eq!(Date::from_system_time(before), Date::new(1969, 12, 31));
+
+        let en = LocaleFormat::new("en");
+        assert_eq!(en.duration(Duration::from_secs(3909)), "1:05:09");
+        assert_eq!(en.duration(Duration::from_secs(309)), "5:09");
+        assert_eq!(en.list(&["a", "b", "c"]), "a, b, and c");
+        assert_eq!(en.list(&["a", "b"]), "a and b");
+        assert_eq!(LocaleFormat::new("fr").list(&["a", "b", "c"]), "a, b et c");
+        assert_eq!(LocaleFormat::new("ja").list(&["a", "b"]), "a、b");
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Saving window layouts across launches.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use druid_shell::window::WindowGeometry;
+
+use crate::kurbo::Rect;
+
+/// Window geometries stored in a file, keyed by a name the app gives each
+/// kind of window, such as `"main"`.
+///
+/// A typical app loads the store at startup and restores each window with
+/// `Ui::set_window_geometry`, then records `Ui::window_geometry` from the
+/// close request listener, while the window is still open, and saves.
+pub struct GeometryStore {
+    path: PathBuf,
+    entries: BTreeMap<String, WindowGeometry>,
+}
+
+impl GeometryStore {
+    /// Load the store from `path`. A missing or unreadable file gives an
+    /// empty store, so the first launch uses the default layout.
+    pub fn load(path: impl Into<PathBuf>) -> GeometryStore {
+        let path = path.into();
+        let entries = fs::read_to_string(&path)
+            .map(|text| parse(&text))
+            .unwrap_or_default();
+        GeometryStore { path, entries }
+    }
+
+    pub fn get(&self, key: &str) -> Option<WindowGeometry> {
+        self.entries.get(key).cloned()
+    }
+
+    pub fn set(&mut self, key: impl Into<String>, geometry: WindowGeometry) {
+        self.entries.insert(key.into(), geometry);
+    }
+
+    /// Write the store back to its file.
+    pub fn save(&self) -> io::Result<()> {
+        fs::write(&self.path, format(&self.entries))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// One line per window: the frame, whether it is maximized, then the key,
+/// which may contain spaces.
+fn format(entries: &BTreeMap<String, WindowGeometry>) -> String {
+    let mut text = String::new();
+    for (key, geometry) in entries {
+        let frame = geometry.frame;
+        text.push_str(&format!(
+            "{} {} {} {} {} {}\n",
+            frame.x0, frame.y0, frame.x1, frame.y1, geometry.maximized, key
+        ));
+    }
+    text
+}
+
+/// Parse the lines written by `format`, skipping any that are malformed.
+fn parse(text: &str) -> BTreeMap<String, WindowGeometry> {
+    let mut entries = BTreeMap::new();
+    for line in text.lines() {
+        let fields: Vec<&str> = line.splitn(6, ' ').collect();
+        if fields.len() != 6 {
+            continue;
+        }
+        let coords: Result<Vec<f64>, _> = fields[..4].iter().map(|s| s.parse()).collect();
+        let (coords, maximized) = match (coords, fields[4].parse()) {
+            (Ok(coords), Ok(maximized)) => (coords, maximized),
+            _ => continue,
+        };
+        let frame = Rect::new(coords[0], coords[1], coords[2], coords[3]);
+        entries.insert(fields[5].to_string(), WindowGeometry { frame, maximized });
+    }
+    entries
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn geometry_round_trip() {
+        let mut entries = BTreeMap::new();
+        let frame = Rect::new(10.0, 20.5, 810.0, 620.5);
+        let geometry = WindowGeometry {
+            frame,
+            maximized: true,
+        };
+        entries.insert("main window".to_string(), geometry);
+        let text = format(&entries) + "garbage line\n";
+        let parsed = parse(&text);
+        assert_eq!(parsed.len(), 1);
+        let restored = parsed["main window"];
+        assert_eq!(restored.frame.x0, frame.x0);
+        assert_eq!(restored.frame.y1, frame.y1);
+        assert!(restored.maximized);
+    }
+}
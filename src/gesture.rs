@@ -0,0 +1,299 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recognition of pinch, rotate and pan gestures from touches and trackpads.
+
+use std::collections::BTreeMap;
+use std::f64::consts::PI;
+
+use druid_shell::window::TrackpadGestureKind;
+
+use crate::kurbo::{Point, Vec2};
+use crate::widget::{GesturePhase, TouchPhase};
+
+/// A recognized gesture, in window coordinates.
+pub(crate) struct Gesture {
+    pub phase: GesturePhase,
+    pub center: Point,
+    pub scale: f64,
+    pub rotation: f64,
+    pub translation: Vec2,
+}
+
+/// Synthesizes gestures from raw touch and trackpad events.
+///
+/// A touch gesture is formed by the first two touches down at once, and ends
+/// when either of them is lifted.
+#[derive(Default)]
+pub(crate) struct GestureRecognizer {
+    /// The current position of each touch in progress.
+    touches: BTreeMap<u64, Point>,
+    pair: Option<TouchPair>,
+    trackpad: Option<TrackpadGesture>,
+}
+
+struct TouchPair {
+    ids: (u64, u64),
+    start_center: Point,
+    start_distance: f64,
+    /// The angle of the line between the touches at the last event.
+    angle: f64,
+    /// The accumulated rotation, which may exceed a full turn.
+    rotation: f64,
+}
+
+struct TrackpadGesture {
+    start_center: Point,
+    scale: f64,
+    rotation: f64,
+    magnifying: bool,
+    rotating: bool,
+}
+
+impl GestureRecognizer {
+    /// Track a touch, returning the gesture it advances, if any.
+    pub fn touch(&mut self, id: u64, pos: Point, phase: TouchPhase) -> Option<Gesture> {
+        match phase {
+            TouchPhase::Down => {
+                self.touches.insert(id, pos);
+                if self.pair.is_some() {
+                    return None;
+                }
+                let (&other, &other_pos) = self.touches.iter().find(|&(&t, _)| t != id)?;
+                let span = pos - other_pos;
+                self.pair = Some(TouchPair {
+                    ids: (other, id),
+                    start_center: other_pos.midpoint(pos),
+                    start_distance: span.hypot(),
+                    angle: span.atan2(),
+                    rotation: 0.0,
+                });
+                Some(self.pair_gesture(GesturePhase::Began))
+            }
+            TouchPhase::Move => {
+                if let Some(p) = self.touches.get_mut(&id) {
+                    *p = pos;
+                }
+                if self.is_paired(id) {
+                    Some(self.pair_gesture(GesturePhase::Changed))
+                } else {
+                    None
+                }
+            }
+            TouchPhase::Up | TouchPhase::Cancel => {
+                let gesture = if self.is_paired(id) {
+                    let gesture = self.pair_gesture(GesturePhase::Ended);
+                    self.pair = None;
+                    Some(gesture)
+                } else {
+                    None
+                };
+                self.touches.remove(&id);
+                gesture
+            }
+        }
+    }
+
+    /// Track a trackpad pinch or rotation, returning the gesture it advances.
+    ///
+    /// Overlapping pinches and rotations are combined into one gesture.
+    pub fn trackpad(
+        &mut self,
+        center: Point,
+        kind: TrackpadGestureKind,
+        phase: GesturePhase,
+    ) -> Option<Gesture> {
+        let began = self.trackpad.is_none();
+        if began && phase != GesturePhase::Began {
+            return None;
+        }
+        let tp = self.trackpad.get_or_insert_with(|| TrackpadGesture {
+            start_center: center,
+            scale: 1.0,
+            rotation: 0.0,
+            magnifying: false,
+            rotating: false,
+        });
+        let active = phase != GesturePhase::Ended;
+        match kind {
+            TrackpadGestureKind::Magnify(delta) => {
+                tp.scale *= 1.0 + delta;
+                tp.magnifying = active;
+            }
+            TrackpadGestureKind::Rotate(delta) => {
+                tp.rotation += delta;
+                tp.rotating = active;
+            }
+        }
+        let phase = if began {
+            GesturePhase::Began
+        } else if !tp.magnifying && !tp.rotating {
+            GesturePhase::Ended
+        } else {
+            GesturePhase::Changed
+        };
+        let gesture = Gesture {
+            phase,
+            center,
+            scale: tp.scale,
+            rotation: tp.rotation,
+            translation: center - tp.start_center,
+        };
+        if phase == GesturePhase::Ended {
+            self.trackpad = None;
+        }
+        Some(gesture)
+    }
+
+    fn is_paired(&self, id: u64) -> bool {
+        match self.pair {
+            Some(TouchPair { ids: (a, b), .. }) => id == a || id == b,
+            None => false,
+        }
+    }
+
+    fn pair_gesture(&mut self, phase: GesturePhase) -> Gesture {
+        let pair = self.pair.as_mut().unwrap();
+        let a = self.touches[&pair.ids.0];
+        let b = self.touches[&pair.ids.1];
+        let span = b - a;
+        let center = a.midpoint(b);
+        // Accumulate the smallest change in angle, so that rotations past a
+        // half turn keep going rather than wrapping around.
+        let angle = span.atan2();
+        let mut delta = angle - pair.angle;
+        if delta > PI {
+            delta -= 2.0 * PI;
+        } else if delta < -PI {
+            delta += 2.0 * PI;
+        }
+        pair.angle = angle;
+        pair.rotation += delta;
+        let scale = if pair.start_distance > 0.0 {
+            span.hypot() / pair.start_distance
+        } else {
+            1.0
+        };
+        Gesture {
+            phase,
+            center,
+            scale,
+            rotation: pair.rotation,
+            translation: center - pair.start_center,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn two_touch_pinch() {
+        let mut gr = GestureRecognizer::default();
+        assert!(gr
+            .touch(1, Point::new(0.0, 0.0), TouchPhase::Down)
+            .is_none());
+        let g = gr
+            .touch(2, Point::new(10.0, 0.0), TouchPhase::Down)
+            .unwrap();
+        assert_eq!(g.phase, GesturePhase::Began);
+        assert!(close(g.scale, 1.0));
+        // A third touch doesn't join the gesture.
+        assert!(gr
+            .touch(3, Point::new(50.0, 50.0), TouchPhase::Down)
+            .is_none());
+        assert!(gr
+            .touch(3, Point::new(60.0, 50.0), TouchPhase::Move)
+            .is_none());
+        let g = gr
+            .touch(2, Point::new(20.0, 0.0), TouchPhase::Move)
+            .unwrap();
+        assert_eq!(g.phase, GesturePhase::Changed);
+        assert!(close(g.scale, 2.0));
+        assert!(close(g.rotation, 0.0));
+        assert!(close(g.center.x, 10.0) && close(g.center.y, 0.0));
+        assert!(close(g.translation.x, 5.0) && close(g.translation.y, 0.0));
+        let g = gr.touch(1, Point::new(0.0, 0.0), TouchPhase::Up).unwrap();
+        assert_eq!(g.phase, GesturePhase::Ended);
+        assert!(gr
+            .touch(2, Point::new(30.0, 0.0), TouchPhase::Move)
+            .is_none());
+    }
+
+    #[test]
+    fn two_touch_rotate() {
+        let mut gr = GestureRecognizer::default();
+        gr.touch(1, Point::new(0.0, 0.0), TouchPhase::Down);
+        gr.touch(2, Point::new(10.0, 0.0), TouchPhase::Down);
+        let g = gr
+            .touch(2, Point::new(0.0, 10.0), TouchPhase::Move)
+            .unwrap();
+        assert!(close(g.rotation, PI / 2.0));
+        assert!(close(g.scale, 1.0));
+        let g = gr
+            .touch(2, Point::new(0.0, 10.0), TouchPhase::Cancel)
+            .unwrap();
+        assert_eq!(g.phase, GesturePhase::Ended);
+        assert!(close(g.rotation, PI / 2.0));
+    }
+
+    #[test]
+    fn rotation_wraps_past_half_turn() {
+        let mut gr = GestureRecognizer::default();
+        let at = |angle: f64| Point::new(10.0 * angle.cos(), 10.0 * angle.sin());
+        gr.touch(1, Point::ORIGIN, TouchPhase::Down);
+        gr.touch(2, at(0.0), TouchPhase::Down);
+        gr.touch(2, at(0.75 * PI), TouchPhase::Move);
+        // atan2 jumps from 3/4 to -3/4 of a half turn here; the rotation
+        // keeps going.
+        let g = gr.touch(2, at(1.25 * PI), TouchPhase::Move).unwrap();
+        assert!(close(g.rotation, 1.25 * PI));
+        gr.touch(2, at(1.75 * PI), TouchPhase::Move);
+        let g = gr.touch(2, at(2.5 * PI), TouchPhase::Move).unwrap();
+        assert!(close(g.rotation, 2.5 * PI));
+        // And the same counterclockwise, past -π.
+        for &angle in &[1.75, 1.0, 0.25, -0.5, -1.25] {
+            gr.touch(2, at(angle * PI), TouchPhase::Move);
+        }
+        let g = gr.touch(2, at(-1.25 * PI), TouchPhase::Up).unwrap();
+        assert!(close(g.rotation, -1.25 * PI));
+    }
+
+    #[test]
+    fn trackpad_combines_pinch_and_rotate() {
+        let mut gr = GestureRecognizer::default();
+        let center = Point::new(5.0, 5.0);
+        let magnify = TrackpadGestureKind::Magnify(0.5);
+        let rotate = TrackpadGestureKind::Rotate(0.25);
+        assert!(gr
+            .trackpad(center, magnify, GesturePhase::Changed)
+            .is_none());
+        let g = gr.trackpad(center, magnify, GesturePhase::Began).unwrap();
+        assert_eq!(g.phase, GesturePhase::Began);
+        assert!(close(g.scale, 1.5));
+        let g = gr.trackpad(center, rotate, GesturePhase::Began).unwrap();
+        assert_eq!(g.phase, GesturePhase::Changed);
+        let g = gr.trackpad(center, magnify, GesturePhase::Ended).unwrap();
+        assert_eq!(g.phase, GesturePhase::Changed);
+        assert!(close(g.scale, 2.25) && close(g.rotation, 0.25));
+        let g = gr.trackpad(center, rotate, GesturePhase::Ended).unwrap();
+        assert_eq!(g.phase, GesturePhase::Ended);
+        assert!(close(g.rotation, 0.5));
+    }
+}
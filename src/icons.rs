@@ -0,0 +1,71 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Standard icons, as paths in a square of `SIZE` px to fill with the
+//! nonzero rule, for use with `widget::Icon`.
+
+use crate::kurbo::BezPath;
+use crate::parse_path;
+
+/// The size of the square the icons are drawn in.
+pub const SIZE: f64 = 24.0;
+
+const CHEVRON_UP: &str = "M6 15 L7.4 16.4 L12 11.8 L16.6 16.4 L18 15 L12 9 Z";
+const CHEVRON_DOWN: &str = "M6 9 L7.4 7.6 L12 12.2 L16.6 7.6 L18 9 L12 15 Z";
+const CHEVRON_LEFT: &str = "M15 6 L16.4 7.4 L11.8 12 L16.4 16.6 L15 18 L9 12 Z";
+const CHEVRON_RIGHT: &str = "M9 6 L7.6 7.4 L12.2 12 L7.6 16.6 L9 18 L15 12 Z";
+const CLOSE: &str = "M6.4 5 L12 10.6 L17.6 5 L19 6.4 L13.4 12 L19 17.6 L17.6 19 L12 13.4 \
+                     L6.4 19 L5 17.6 L10.6 12 L5 6.4 Z";
+// The lens is a ring, its inside wound the other way round.
+const SEARCH: &str = "M3.5 10 A6.5 6.5 0 1 1 16.5 10 A6.5 6.5 0 1 1 3.5 10 Z \
+                      M5.5 10 A4.5 4.5 0 1 0 14.5 10 A4.5 4.5 0 1 0 5.5 10 Z \
+                      M13.5 14.9 L14.9 13.5 L20.5 19.1 L19.1 20.5 Z";
+const WARNING: &str = "M12 2 L23 21 L1 21 Z M11 9 L11 15 L13 15 L13 9 Z \
+                       M11 17 L11 19 L13 19 L13 17 Z";
+
+fn parse(svg: &str) -> BezPath {
+    // The paths above are known to parse.
+    parse_path(svg).unwrap()
+}
+
+pub fn chevron_up() -> BezPath {
+    parse(CHEVRON_UP)
+}
+
+pub fn chevron_down() -> BezPath {
+    parse(CHEVRON_DOWN)
+}
+
+pub fn chevron_left() -> BezPath {
+    parse(CHEVRON_LEFT)
+}
+
+pub fn chevron_right() -> BezPath {
+    parse(CHEVRON_RIGHT)
+}
+
+/// A diagonal cross, for closing and clearing.
+pub fn close() -> BezPath {
+    parse(CLOSE)
+}
+
+/// A magnifying glass.
+pub fn search() -> BezPath {
+    parse(SEARCH)
+}
+
+/// An exclamation mark in a triangle.
+pub fn warning() -> BezPath {
+    parse(WARNING)
+}
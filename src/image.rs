@@ -0,0 +1,209 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitmaps in memory and the images made from them for a window.
+
+use std::collections::HashMap;
+use std::rc::{Rc, Weak};
+
+use crate::kurbo::{Rect, Size};
+use crate::piet::{ImageFormat, Piet, RenderContext};
+use crate::RgbaImage;
+
+/// The pixels of a decoded image, cheap to clone.
+///
+/// Each window makes an image for the render context from the pixels the
+/// first time they are drawn, and keeps it for as long as a clone of the
+/// buffer is alive.
+#[derive(Clone)]
+pub struct ImageBuf {
+    pixels: Rc<[u8]>,
+    format: ImageFormat,
+    width: usize,
+    height: usize,
+}
+
+impl ImageBuf {
+    /// An image of `width` by `height` pixels, row by row in `format`.
+    ///
+    /// Panics if `pixels` is not the right length.
+    pub fn from_raw(
+        pixels: impl Into<Rc<[u8]>>,
+        format: ImageFormat,
+        width: usize,
+        height: usize,
+    ) -> ImageBuf {
+        let pixels = pixels.into();
+        let bytes_per_pixel = match format {
+            ImageFormat::Rgb => 3,
+            _ => 4,
+        };
+        assert_eq!(pixels.len(), width * height * bytes_per_pixel);
+        ImageBuf {
+            pixels,
+            format,
+            width,
+            height,
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// The size of the image, drawn with one pixel per px.
+    pub fn size(&self) -> Size {
+        Size::new(self.width as f64, self.height as f64)
+    }
+
+    pub fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The key of the buffer in caches, shared by its clones.
+    fn key(&self) -> usize {
+        self.pixels.as_ptr() as usize
+    }
+}
+
+impl From<RgbaImage> for ImageBuf {
+    fn from(image: RgbaImage) -> ImageBuf {
+        ImageBuf::from_raw(
+            image.image,
+            ImageFormat::RgbaSeparate,
+            image.width,
+            image.height,
+        )
+    }
+}
+
+/// How an image is sized to fit a rectangle of another shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FitMode {
+    /// Stretch the image to the rectangle.
+    Fill,
+    /// Scale the image to the largest size inside the rectangle, keeping
+    /// its aspect ratio.
+    Contain,
+    /// Scale the image to the smallest size covering the rectangle, keeping
+    /// its aspect ratio, and crop what is outside.
+    Cover,
+    /// Keep the image at one pixel per px, and crop what is outside.
+    None,
+    /// Like `Contain`, but never scale the image up.
+    ScaleDown,
+}
+
+impl FitMode {
+    /// The rectangle to draw an image of `size` in, centered on `rect`.
+    pub fn fit(self, size: Size, rect: Rect) -> Rect {
+        let scale_x = rect.width() / size.width;
+        let scale_y = rect.height() / size.height;
+        let scale = match self {
+            FitMode::Fill => return rect,
+            FitMode::Contain => scale_x.min(scale_y),
+            FitMode::Cover => scale_x.max(scale_y),
+            FitMode::None => 1.0,
+            FitMode::ScaleDown => scale_x.min(scale_y).min(1.0),
+        };
+        let (width, height) = (size.width * scale, size.height * scale);
+        let center = rect.center();
+        Rect::new(
+            center.x - width / 2.0,
+            center.y - height / 2.0,
+            center.x + width / 2.0,
+            center.y + height / 2.0,
+        )
+    }
+}
+
+/// The rectangle to draw the whole of an image of `size` in, so that the
+/// part of it at `src`, in pixels, lands on `dst`.
+pub(crate) fn map_area(size: Size, src: Rect, dst: Rect) -> Rect {
+    let scale_x = dst.width() / src.width();
+    let scale_y = dst.height() / src.height();
+    let x0 = dst.x0 - src.x0 * scale_x;
+    let y0 = dst.y0 - src.y0 * scale_y;
+    Rect::new(
+        x0,
+        y0,
+        x0 + size.width * scale_x,
+        y0 + size.height * scale_y,
+    )
+}
+
+/// Split `outer` into nine rectangles, as `inner` splits it, row by row.
+pub(crate) fn nine_slices(outer: Rect, inner: Rect) -> [Rect; 9] {
+    let xs = [outer.x0, inner.x0, inner.x1, outer.x1];
+    let ys = [outer.y0, inner.y0, inner.y1, outer.y1];
+    let mut slices = [Rect::new(0.0, 0.0, 0.0, 0.0); 9];
+    for (i, slice) in slices.iter_mut().enumerate() {
+        let (col, row) = (i % 3, i / 3);
+        *slice = Rect::new(xs[col], ys[row], xs[col + 1], ys[row + 1]);
+    }
+    slices
+}
+
+/// The images made from `ImageBuf`s for the render context of a window.
+#[derive(Default)]
+pub(crate) struct ImageCache {
+    images: HashMap<usize, (Weak<[u8]>, <Piet<'static> as RenderContext>::Image)>,
+}
+
+impl ImageCache {
+    /// Drop all images, for when the render context is replaced.
+    pub(crate) fn clear(&mut self) {
+        self.images.clear();
+    }
+
+    /// The image for `buf`, made with `render_ctx` if it isn't cached.
+    pub(crate) fn get(
+        &mut self,
+        render_ctx: &mut Piet,
+        buf: &ImageBuf,
+    ) -> Option<&<Piet<'static> as RenderContext>::Image> {
+        let key = buf.key();
+        let cached = self
+            .images
+            .get(&key)
+            .map_or(false, |(pixels, _)| pixels.upgrade().is_some());
+        if !cached {
+            // Images of dropped buffers are dropped along with any stale
+            // entry under the same address.
+            self.images
+                .retain(|_, (pixels, _)| pixels.upgrade().is_some());
+            let image = make_image(render_ctx, buf)?;
+            self.images.insert(key, (Rc::downgrade(&buf.pixels), image));
+        }
+        self.images.get(&key).map(|(_, image)| image)
+    }
+}
+
+/// Make an image for `render_ctx` from `buf`, without caching it.
+pub(crate) fn make_image(
+    render_ctx: &mut Piet,
+    buf: &ImageBuf,
+) -> Option<<Piet<'static> as RenderContext>::Image> {
+    render_ctx
+        .make_image(buf.width, buf.height, &buf.pixels, buf.format)
+        .ok()
+}
@@ -0,0 +1,87 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Keyboard shortcuts mapping key chords to commands.
+
+use druid_shell::keyboard::KeyEvent;
+use druid_shell::keycodes::{KeyMatch, MenuKey};
+use druid_shell::menu::Menu;
+
+/// A table of keyboard shortcuts.
+///
+/// Each binding maps a key chord to a command id, the same kind of id that
+/// is used for menu items. The keymap is consulted before key events are
+/// delivered to the focused widget; a matching key is dispatched to the
+/// command listener instead.
+///
+/// Chords built with [`MenuKey::command`] resolve to Command on macOS and
+/// Control elsewhere, so a single keymap can serve all platforms.
+///
+/// By default a binding matches the character its key produces, falling
+/// back to the physical key on layouts without Latin letters, so that
+/// Ctrl+Z is undo on AZERTY, Dvorak and Cyrillic layouts alike. Bindings
+/// added with [`add_with_match`] can match the physical key only, for keys
+/// chosen by their position, or the character only.
+///
+/// [`MenuKey::command`]: ../druid_shell/keycodes/struct.MenuKey.html#method.command
+/// [`add_with_match`]: #method.add_with_match
+#[derive(Default)]
+pub struct Keymap {
+    bindings: Vec<(MenuKey, KeyMatch, u32)>,
+}
+
+impl Keymap {
+    pub fn new() -> Keymap {
+        Default::default()
+    }
+
+    /// Bind a key chord to a command.
+    ///
+    /// If the chord is already bound, the new binding replaces it.
+    pub fn add(&mut self, key: impl Into<MenuKey>, command: u32) {
+        self.add_with_match(key, KeyMatch::Auto, command);
+    }
+
+    /// Bind a key chord to a command, choosing how its key is compared
+    /// against key events.
+    pub fn add_with_match(&mut self, key: impl Into<MenuKey>, mode: KeyMatch, command: u32) {
+        let key = key.into();
+        self.bindings.retain(|(k, _, _)| *k != key);
+        self.bindings.push((key, mode, command));
+    }
+
+    /// Find the command bound to the key event, if any.
+    pub fn lookup(&self, event: &KeyEvent) -> Option<u32> {
+        self.bindings
+            .iter()
+            .find(|(key, mode, _)| key.matches_with(event, *mode))
+            .map(|&(_, _, command)| command)
+    }
+
+    /// The first key chord bound to a command, if any.
+    pub fn key_for_command(&self, command: u32) -> Option<MenuKey> {
+        self.bindings
+            .iter()
+            .find(|&&(_, _, cmd)| cmd == command)
+            .map(|&(key, _, _)| key)
+    }
+
+    /// Add an item to a menu, displaying the key chord bound to its command.
+    pub fn add_menu_item(&self, menu: &mut Menu, command: u32, text: &str) {
+        match self.key_for_command(command) {
+            Some(key) => menu.add_item(command, text, key),
+            None => menu.add_item(command, text, ()),
+        }
+    }
+}
@@ -14,43 +14,136 @@
 
 //! Simple entity-component-system based GUI.
 
-pub use druid_shell::{self as shell, kurbo, piet};
+pub use druid_shell::{self as shell, commands, kurbo, piet};
 
 use std::any::Any;
-use std::cell::RefCell;
-use std::collections::BTreeMap;
+use std::cell::{RefCell, RefMut};
+use std::collections::{BTreeMap, HashMap};
 use std::ffi::OsString;
 use std::mem;
 use std::ops::{Deref, DerefMut};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
 use std::time::Instant;
 
-use kurbo::{Point, Rect, Size, Vec2};
-use piet::{Color, Piet, RenderContext};
+#[cfg(target_arch = "wasm32")]
+use druid_shell::util::Instant;
 
+use kurbo::{Affine, BezPath, Line, Point, Rect, Shape, Size, Vec2};
+use piet::{Color, FillRule, Gradient, ImageFormat, InterpolationMode, Piet, RenderContext};
+
+pub use druid_shell::accessibility::{self, AccessNode, AccessTree, ActionRequest, Politeness};
+use druid_shell::accessibility::{Action, NodeId, Role};
+pub use druid_shell::alert::{AlertButtons, AlertOptions, AlertResponse, AlertStyle};
 use druid_shell::application::Application;
-pub use druid_shell::dialog::{FileDialogOptions, FileDialogType};
-pub use druid_shell::keyboard::{KeyCode, KeyEvent, KeyModifiers};
+use druid_shell::bitmap;
+pub use druid_shell::bitmap::RgbaImage;
+use druid_shell::blend;
+pub use druid_shell::blend::BlendMode;
+pub use druid_shell::clipboard::{Clipboard, ClipboardFormat, FormatId};
+pub use druid_shell::dialog::{FileDialogOptions, FileDialogType, FileInfo, FileSpec};
+pub use druid_shell::drag::{DragData, DropEffect};
+pub use druid_shell::keyboard::{Key, KeyCode, KeyEvent, KeyLocation, KeyModifiers};
+use druid_shell::keycodes::MenuKey;
+use druid_shell::menu::Menu;
 use druid_shell::platform::IdleHandle;
-use druid_shell::window::{self, WinHandler, WindowHandle};
+use druid_shell::text_rendering;
+pub use druid_shell::text_rendering::{TextAntialias, TextHinting, TextRendering};
+pub use druid_shell::timer::TimerToken;
+use druid_shell::vector;
+pub use druid_shell::vector::VectorFormat;
+use druid_shell::window::{self, WinHandler, WindowHandle, WindowKind};
+pub use druid_shell::window::{
+    Cursor, CursorDesc, IconDesc, PowerSource, Theme, WindowBackdrop, WindowGeometry, WindowLevel,
+    WindowState,
+};
+use druid_shell::WindowBuilder;
 
+mod background;
+mod brush;
+mod color;
+mod command;
+mod display_list;
+mod env;
+mod format;
+mod geometry;
+mod gesture;
 mod graph;
+pub mod icons;
+mod image;
+mod keymap;
+mod localization;
+mod menu;
+mod shadow;
+mod stats;
+mod stroke;
+mod svg;
+mod text;
+pub mod theme;
 pub mod widget;
 
+pub use background::{even_stops, Background, LinearGradient, RadialGradient, UnitPoint};
+use brush::{BrushCache, BrushSource};
+pub use color::{ColorExt, ColorParseError};
+pub use command::CommandPriority;
+use command::CommandQueue;
+use display_list::{DisplayList, ImageOp, Op, PietTextLayout};
+pub use env::{Env, EnvKey, Value, ValueType};
+pub use format::{CurrencyFormatter, Date, Formatter, LocaleFormat, NumberFormatter};
+pub use geometry::GeometryStore;
+use gesture::{Gesture, GestureRecognizer};
 use graph::Graph;
-use widget::NullWidget;
-pub use widget::{MouseEvent, Widget};
+use image::ImageCache;
+pub use image::{FitMode, ImageBuf};
+pub use keymap::Keymap;
+pub use localization::{ArgValue, FtlError, LayoutDirection, Localization, LocalizedString};
+pub use menu::{MenuDesc, MenuItem};
+pub use shadow::BoxShadow;
+use stats::FrameStats;
+pub use stroke::Stroke;
+pub use svg::{parse_path, Svg, SvgError, SvgPaint, SvgShape};
+pub use text::editing::{self, EditHistory, EditKind, EditableText, Movement, Selection};
+pub use text::keybindings::{EditAction, EditKeymap, KeyChord};
+pub use text::rich::{
+    Attribute, AttributesAdder, FontStyle, FontWeight, RichText, RichTextBuilder,
+};
+use text::TextOp;
+pub use text::{HitTestPoint, LineBreaking, LineMetrics, TextLayout};
+pub use widget::{
+    CompositionEvent, DragEvent, DragKind, GestureEvent, LifeCycle, MouseEvent, Notification,
+    PenEvent, TouchEvent, WheelEvent, Widget, WidgetExt,
+};
+use widget::{GesturePhase, NullWidget, PenPhase, TouchPhase};
+
+/// The smallest height of controls in touch mode, in px.
+pub const TOUCH_TARGET_SIZE: f64 = 44.0;
 
-//FIXME: this should come from a theme or environment at some point.
-const BACKGROUND_COLOR: Color = Color::rgb24(0x27_28_22);
+/// How far outside of its bounds a tap still hits a widget in touch mode,
+/// in px.
+const TOUCH_SLOP: f64 = 8.0;
 
 /// The top-level handler for the UI.
 ///
 /// This struct ultimately has ownership of all components within the UI.
 /// It implements the `WinHandler` trait of druid-win-shell, and, after the
 /// UI is built, ownership is transferred to the window, through `set_handler`
-/// in the druid-win-shell window building sequence.
+/// in the druid-win-shell window building sequence. Windows opened later with
+/// [`Ui::new_window`] get handlers of their own that share the same state.
+///
+/// [`Ui::new_window`]: struct.Ui.html#method.new_window
 pub struct UiMain {
-    state: RefCell<UiState>,
+    /// Shared by the handlers of all the windows of the UI.
+    state: Rc<RefCell<UiState>>,
+
+    /// The window this handler belongs to.
+    window: WindowId,
+
+    /// Shared with the state, so that commands can be queued even while the
+    /// state is borrowed.
+    commands: Rc<RefCell<CommandQueue>>,
 }
 
 /// An identifier for widgets, scoped to a UiMain instance. This is the
@@ -62,6 +155,32 @@ pub struct UiState {
 
     command_listener: Option<Box<dyn FnMut(u32, ListenerCtx)>>,
 
+    window_close_listener: Option<Box<dyn FnMut(WindowId, ListenerCtx)>>,
+
+    window_state_listener: Option<Box<dyn FnMut(WindowId, WindowState, ListenerCtx)>>,
+
+    theme_listener: Option<Box<dyn FnMut(Theme, ListenerCtx)>>,
+
+    accent_color_listener: Option<Box<dyn FnMut(Option<Color>, ListenerCtx)>>,
+
+    reduce_transparency_listener: Option<Box<dyn FnMut(bool, ListenerCtx)>>,
+
+    power_source_listener: Option<Box<dyn FnMut(PowerSource, ListenerCtx)>>,
+
+    text_scale_listener: Option<Box<dyn FnMut(f64, ListenerCtx)>>,
+
+    /// Changes the app makes to the theme, applied again when the system
+    /// appearance changes.
+    env_config: Option<Box<dyn FnMut(Theme, Option<&Color>, &mut Env)>>,
+
+    close_request_listener: Option<Box<dyn FnMut(WindowId, ListenerCtx) -> bool>>,
+
+    /// Whether closing the last window quits the application.
+    quit_on_last_window_closed: bool,
+
+    /// Keyboard shortcuts, checked before key events reach the focused widget.
+    keymap: Keymap,
+
     /// The widget tree and associated state is split off into a separate struct
     /// so that we can use a mutable reference to it as the listener context.
     inner: Ui,
@@ -82,12 +201,30 @@ pub struct Ui {
     /// The state (other than widget tree) is a separate object, so that a
     /// mutable reference to it can be used as a layout context.
     layout_ctx: LayoutCtx,
+
+    /// The state of each open window other than the current one.
+    windows: BTreeMap<WindowId, PerWindowState>,
+
+    /// Windows that have been requested but not yet built.
+    pending_windows: Vec<PendingWindow>,
+
+    /// A context menu waiting to be shown, with its window and position.
+    pending_context_menu: Option<(WindowId, Menu, Point)>,
+
+    /// A file dialog waiting to be shown.
+    pending_file_dialog: Option<PendingFileDialog>,
+
+    /// An alert waiting to be shown, with its window and command.
+    pending_alert: Option<(WindowId, AlertOptions, u32)>,
+
+    /// The payload of the command being delivered to the command listener.
+    command_payload: Option<Box<dyn Any>>,
+
+    next_window_id: usize,
 }
 
 /// The context given to layout methods.
 pub struct LayoutCtx {
-    handle: WindowHandle,
-
     /// Bounding box of each widget. The position is relative to the parent.
     geom: Vec<Rect>,
 
@@ -97,26 +234,153 @@ pub struct LayoutCtx {
     /// this is the general SOA vs AOS discussion.
     per_widget: Vec<PerWidgetState>,
 
+    /// Queue of events to dispatch after build or handler.
+    event_q: Vec<Event>,
+
+    /// Commands waiting to be delivered to the command listener.
+    commands: Rc<RefCell<CommandQueue>>,
+
+    /// The window currently handling an event.
+    window_id: WindowId,
+
+    /// The state of the current window. The state of the other windows is
+    /// kept in `Ui::windows`, and swapped in when they receive an event.
+    window: PerWindowState,
+
+    /// The light or dark system theme.
+    theme: Theme,
+
+    /// The values of the theme, and any others set by the app.
+    env: Env,
+
+    /// The values the app changed with `Ui::set_env`, set again over the
+    /// theme whenever the environment is made again.
+    env_overrides: Env,
+
+    /// The messages of `LocalizedString`s, and the locale shown.
+    localization: Localization,
+
+    /// The system's accent color, if it has one.
+    accent_color: Option<Color>,
+
+    /// Whether the user asked for less transparency.
+    reduce_transparency: bool,
+
+    /// Whether the computer runs on battery.
+    power_source: PowerSource,
+
+    /// The size of text chosen in the accessibility settings, relative to
+    /// the default.
+    text_scale: f64,
+
+    /// Whether the UI is laid out for touch rather than a mouse.
+    touch_mode: bool,
+
+    /// Whether windows show the overlay of `FrameStats`.
+    show_frame_stats: bool,
+}
+
+/// The state of a single window.
+struct PerWindowState {
+    handle: WindowHandle,
+
+    /// The root widget of the window.
+    root: Id,
+
     /// State of animation requests.
     anim_state: AnimState,
 
-    /// The time of the last paint cycle.
+    /// The time of the last animation frame, while animating.
     prev_paint_time: Option<Instant>,
 
-    /// Queue of events to dispatch after build or handler.
-    event_q: Vec<Event>,
+    /// How the time since the last paint was spent.
+    stats: FrameStats,
 
     /// Which widget is currently focused, if any.
     focused: Option<Id>,
 
+    /// A pending change of focus, applied after the current event is handled.
+    focus_req: Option<FocusChange>,
+
     /// Which widget is active (mouse is pressed), if any.
     active: Option<Id>,
 
     /// Which widget is hot (hovered), if any.
     hot: Option<Id>,
 
+    /// The drag-and-drop session in progress, if any.
+    drag: Option<DragState>,
+
+    /// The command the drop target resolved the current drop into, if any.
+    drop_command: Option<(u32, CommandPriority)>,
+
+    /// The widget that started the drag out of the application in progress,
+    /// if any.
+    system_drag_source: Option<Id>,
+
+    /// The widget that requested each pending timer.
+    timers: HashMap<TimerToken, Id>,
+
+    /// The widget handling each touch in progress.
+    touches: BTreeMap<u64, Id>,
+
+    /// The widget handling the pen stroke in progress, if any.
+    pen_target: Option<Id>,
+
+    gestures: GestureRecognizer,
+
+    /// The widget handling the gesture in progress, if any.
+    gesture_target: Option<Id>,
+
+    /// The cursor requested while handling the current mouse move.
+    cursor: Option<Cursor>,
+
+    /// Whether the window is maximized, minimized or fullscreen.
+    window_state: WindowState,
+
+    /// The command sent when the tray icon is clicked.
+    tray_command: Option<u32>,
+
     /// The size of the paint surface
     size: Size,
+
+    /// The images made for drawing `ImageBuf`s into the window.
+    images: ImageCache,
+
+    /// The brushes made for painting the window with colors and gradients.
+    brushes: BrushCache,
+
+    /// How text is drawn into the window; see `Ui::set_text_rendering`.
+    text_rendering: TextRendering,
+
+    /// The widget text is being composed for with an input method, and the
+    /// text composed so far.
+    composition: Option<(Id, String)>,
+
+    /// The accessibility tree last given to the window, if any.
+    access_tree: Option<AccessTree>,
+}
+
+/// Identifies a window, scoped to a `UiState` instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct WindowId(usize);
+
+/// A window requested with `new_window` or `new_palette`, waiting to be built.
+struct PendingWindow {
+    id: WindowId,
+    title: String,
+    menu: Option<Menu>,
+    /// For a palette, its owner window, position relative to the owner and size.
+    palette: Option<(WindowHandle, Point, Size)>,
+}
+
+/// A file dialog requested with `open_file_dialog`, `save_file_dialog` or
+/// `open_directory_dialog`, waiting to be shown.
+struct PendingFileDialog {
+    window: WindowId,
+    ty: FileDialogType,
+    options: FileDialogOptions,
+    command: u32,
 }
 
 #[deprecated(note = "please use `Rect` directly.")]
@@ -125,12 +389,97 @@ pub type Geometry = Rect;
 #[derive(Default)]
 struct PerWidgetState {
     anim_frame_requested: bool,
+    /// Whether the widget invalidated since it was last painted.
+    invalidated: bool,
+    /// The cached painting of the widget and its children; see
+    /// `Ui::set_cached`.
+    layer: Option<Layer>,
+    /// The recorded painting of the widget and its children; see
+    /// `Ui::set_retained`.
+    display_list: Option<DisplayList>,
+    /// How the widget and its children are drawn as a group, if they are;
+    /// see `Ui::set_opacity`.
+    compositing: Option<Compositing>,
+    /// The shape the widget and its children are clipped to, relative to
+    /// its origin; see `Ui::set_clip`.
+    clip: Option<BezPath>,
+}
+
+#[derive(Clone, Copy)]
+struct Compositing {
+    opacity: f64,
+    blend_mode: BlendMode,
+}
+
+/// The bitmap a widget and its children are painted into.
+#[derive(Default)]
+struct Layer {
+    image: Option<<Piet<'static> as RenderContext>::Image>,
+    /// The size of the bitmap, in pixels.
+    size: (usize, usize),
+}
+
+/// What painting the widget tree needs besides the widgets.
+struct PaintTree<'a> {
+    graph: &'a Graph,
+    geom: &'a [Rect],
+    per_widget: &'a mut [PerWidgetState],
+    active: Option<Id>,
+    hot: Option<Id>,
+    focused: Option<Id>,
+    /// The number of pixels per px, for the bitmaps of layers.
+    scale: f64,
+    /// Whether painting into the bitmap of a layer. Layers inside it are
+    /// painted directly, since images made for one bitmap can't be drawn
+    /// into others.
+    in_layer: bool,
+    /// Whether recording the display list of a retained widget. Retained
+    /// widgets inside it are recorded as part of it.
+    recording: bool,
+    /// The number of widgets painted so far.
+    painted: usize,
+}
+
+/// The state of a drag-and-drop session.
+struct DragState {
+    /// The widget that started the drag, or `None` for files dragged in
+    /// from another application.
+    source: Option<Id>,
+    payload: Box<dyn Any>,
+    /// Paints the dragged item, with the origin at the pointer.
+    image: Option<Box<dyn FnMut(&mut PaintCtx)>>,
+    /// The pointer position, in window coordinates.
+    pos: Option<Point>,
+    target: Option<Id>,
+}
+
+/// A request to change the focused widget.
+#[derive(Clone, Copy, Debug)]
+enum FocusChange {
+    /// Focus the given widget.
+    Focus(Id),
+    /// Remove focus from the given widget, if it is focused.
+    Resign(Id),
+    /// Clear focus entirely.
+    Clear,
+    /// Move focus to the next widget in the focus chain.
+    Next,
+    /// Move focus to the previous widget in the focus chain.
+    Previous,
 }
 
+/// What the window has asked the shell for: nothing while idle, so that the
+/// shell can sleep until an event arrives.
 enum AnimState {
     Idle,
+    /// A paint, with no animation frame.
     InvalidationRequested,
+    /// A frame, for widgets waiting on `anim_frame`; it is followed by a
+    /// paint.
+    FrameRequested,
+    /// Widgets are in `anim_frame`.
     AnimFrameStart,
+    /// Widgets in `anim_frame` requested the next frame.
     AnimFrameRequested,
 }
 
@@ -154,6 +503,19 @@ enum Event {
 
     /// Sent when a widget is removed so its listeners can be deleted.
     ClearListeners(Id),
+
+    /// A notification to be offered to the ancestors of a widget.
+    Notification(Id, Box<dyn Any>),
+
+    /// A request to show a context menu, at a position relative to a widget.
+    ContextMenu(Id, MenuDesc, Point),
+
+    /// The caret rectangle of a widget for the input method, relative to
+    /// the widget.
+    CaretRect(Id, Rect),
+
+    /// A request to start a drag out of the application.
+    SystemDrag(Id, DragData),
 }
 
 // Contexts for widget methods.
@@ -180,6 +542,33 @@ pub struct PaintCtx<'a, 'b: 'a> {
     is_active: bool,
     is_hot: bool,
     is_focused: bool,
+    theme: Theme,
+    env: Env,
+    accent_color: Option<Color>,
+    reduce_transparency: bool,
+    text_scale: f64,
+    touch_mode: bool,
+    /// How text is drawn, before bitmaps make it grayscale.
+    text_rendering: TextRendering,
+    /// The number of pixels per px, for the bitmaps of groups.
+    scale: f64,
+    /// The part of the window that can be painted, in the coordinates of
+    /// the painting; see `visible_rect`.
+    visible: Rect,
+    /// Whether the render context was saved for the current widget, to be
+    /// restored once its children are painted.
+    saved: bool,
+    /// Painting deferred with `paint_overlay`, in the order requested.
+    overlays: Vec<Box<dyn FnOnce(&mut PaintCtx)>>,
+    /// The images of the window, or `None` when painting into a bitmap,
+    /// whose images can't be drawn into the window.
+    images: Option<&'a mut ImageCache>,
+    /// The brushes of the window, or `None` when painting into a bitmap,
+    /// like `images`.
+    brushes: Option<&'a mut BrushCache>,
+    /// The display list the painting is recorded into, while painting a
+    /// retained widget.
+    recording: Option<DisplayList>,
     pub render_ctx: &'a mut Piet<'b>,
 }
 
@@ -196,8 +585,163 @@ impl From<druid_shell::Error> for Error {
 
 impl UiMain {
     pub fn new(state: UiState) -> UiMain {
+        let commands = state.layout_ctx.commands.clone();
         UiMain {
-            state: RefCell::new(state),
+            state: Rc::new(RefCell::new(state)),
+            window: WindowId(0),
+            commands,
+        }
+    }
+
+    /// Borrow the state to handle an event, with this handler's window as
+    /// the current one. The time until the borrow ends counts as handling
+    /// events in the frame statistics.
+    fn state(&self) -> EventState<'_> {
+        EventState {
+            state: self.paint_state(),
+            start: Instant::now(),
+        }
+    }
+
+    /// Borrow the state, with this handler's window as the current one, for
+    /// a frame or a paint, which are timed separately.
+    fn paint_state(&self) -> RefMut<'_, UiState> {
+        let mut state = self.state.borrow_mut();
+        state.enter_window(self.window);
+        state
+    }
+
+    /// Build the windows requested with `new_window` or `new_palette`.
+    fn build_pending_windows(&self) {
+        let pending = mem::replace(&mut self.state.borrow_mut().pending_windows, Vec::new());
+        for PendingWindow {
+            id,
+            title,
+            menu,
+            palette,
+        } in pending
+        {
+            let mut builder = WindowBuilder::new();
+            builder.set_handler(Box::new(UiMain {
+                state: self.state.clone(),
+                window: id,
+                commands: self.commands.clone(),
+            }));
+            builder.set_title(title);
+            if let Some(menu) = menu {
+                builder.set_menu(menu);
+            }
+            if let Some((owner, position, size)) = palette {
+                builder.set_kind(WindowKind::Palette);
+                builder.set_owner(&owner);
+                builder.set_position(position.x, position.y);
+                builder.set_size(size.width, size.height);
+            }
+            match builder.build() {
+                Ok(window) => window.show(),
+                Err(e) => println!("failed to build window: {:?}", e),
+            }
+        }
+    }
+
+    /// Show the context menu requested with `show_context_menu`.
+    fn show_pending_context_menu(&self) {
+        let (handle, menu, pos) = {
+            let mut state = self.state.borrow_mut();
+            let (window, menu, pos) = match state.pending_context_menu.take() {
+                Some(pending) => pending,
+                None => return,
+            };
+            state.enter_window(window);
+            (state.layout_ctx.window.handle.clone(), menu, pos)
+        };
+        handle.show_context_menu(menu, pos.x, pos.y);
+    }
+
+    /// Close all windows and quit, unless the close request listener keeps
+    /// one of them open.
+    fn quit(&self) {
+        let handles = {
+            let mut state = self.state();
+            let mut ids: Vec<WindowId> = state.windows.keys().cloned().collect();
+            ids.push(state.layout_ctx.window_id);
+            let mut handles = Vec::new();
+            for id in ids {
+                state.enter_window(id);
+                if !state.close_requested() {
+                    return;
+                }
+                handles.push(state.layout_ctx.window.handle.clone());
+            }
+            handles
+        };
+        for handle in handles {
+            handle.close();
+        }
+        Application::quit();
+    }
+
+    /// Show the pending alert, and deliver the chosen button.
+    fn show_pending_alert(&self) {
+        let (handle, window, options, command) = {
+            let mut state = self.state.borrow_mut();
+            let (window, options, command) = match state.pending_alert.take() {
+                Some(pending) => pending,
+                None => return,
+            };
+            state.enter_window(window);
+            (
+                state.layout_ctx.window.handle.clone(),
+                window,
+                options,
+                command,
+            )
+        };
+        let response = handle.alert(&options);
+        self.commands.borrow_mut().push_with_payload(
+            command,
+            Some(Box::new(response)),
+            CommandPriority::Normal,
+        );
+        let mut state = self.state.borrow_mut();
+        state.enter_window(window);
+        state.dispatch_events();
+    }
+
+    /// Show the pending file dialog, and deliver its result.
+    fn show_pending_file_dialog(&self) {
+        let (handle, pending) = {
+            let mut state = self.state.borrow_mut();
+            let pending = match state.pending_file_dialog.take() {
+                Some(pending) => pending,
+                None => return,
+            };
+            state.enter_window(pending.window);
+            (state.layout_ctx.window.handle.clone(), pending)
+        };
+        let result: Option<Box<dyn Any>> = match pending.ty {
+            FileDialogType::Open => handle
+                .open_file_dialog(&pending.options)
+                .ok()
+                .map(|files| Box::new(files) as Box<dyn Any>),
+            FileDialogType::Save => handle
+                .save_file_dialog(&pending.options)
+                .ok()
+                .map(|file| Box::new(file) as Box<dyn Any>),
+            FileDialogType::Directory => handle
+                .open_directory_dialog(&pending.options)
+                .ok()
+                .map(|dir| Box::new(dir) as Box<dyn Any>),
+        };
+        if let Some(payload) = result {
+            self.commands.borrow_mut().push_with_payload(
+                pending.command,
+                Some(payload),
+                CommandPriority::Normal,
+            );
+            let mut state = self.state.borrow_mut();
+            state.enter_window(pending.window);
+            state.dispatch_events();
         }
     }
 
@@ -207,7 +751,7 @@ impl UiMain {
         let mut boxed_a = Box::new(a);
         idle_handle.add_idle(move |a| {
             let ui_main = a.downcast_ref::<UiMain>().unwrap();
-            let mut state = ui_main.state.borrow_mut();
+            let mut state = ui_main.state();
             state.poke(id, boxed_a.deref_mut());
         });
     }
@@ -215,29 +759,63 @@ impl UiMain {
 
 impl UiState {
     pub fn new() -> UiState {
-        UiState {
+        let mut state = UiState {
             listeners: Default::default(),
             command_listener: None,
+            window_close_listener: None,
+            window_state_listener: None,
+            theme_listener: None,
+            accent_color_listener: None,
+            reduce_transparency_listener: None,
+            power_source_listener: None,
+            text_scale_listener: None,
+            env_config: None,
+            close_request_listener: None,
+            // Mac applications conventionally keep running without windows.
+            quit_on_last_window_closed: !cfg!(target_os = "macos"),
+            keymap: Keymap::new(),
             inner: Ui {
                 widgets: Vec::new(),
                 graph: Default::default(),
                 layout_ctx: LayoutCtx {
                     geom: Vec::new(),
                     per_widget: Vec::new(),
-                    anim_state: AnimState::Idle,
-                    prev_paint_time: None,
-                    handle: Default::default(),
                     event_q: Vec::new(),
-                    focused: None,
-                    active: None,
-                    hot: None,
-                    size: Size::ZERO,
+                    commands: Default::default(),
+                    window_id: WindowId(0),
+                    window: PerWindowState::new(0),
+                    theme: Application::theme(),
+                    env: theme::init(Application::theme()),
+                    env_overrides: Env::empty(),
+                    localization: Default::default(),
+                    accent_color: Application::accent_color(),
+                    reduce_transparency: Application::reduce_transparency(),
+                    power_source: Application::power_source(),
+                    text_scale: Application::text_scale(),
+                    touch_mode: false,
+                    show_frame_stats: false,
                 },
+                windows: BTreeMap::new(),
+                pending_windows: Vec::new(),
+                pending_context_menu: None,
+                pending_file_dialog: None,
+                pending_alert: None,
+                command_payload: None,
+                next_window_id: 1,
             },
-        }
+        };
+        // The layout direction is that of the locale.
+        state.rebuild_env();
+        state
     }
 
     /// Set a listener for menu commands.
+    ///
+    /// Commands, whether from menus, shortcuts or [`submit_command`], are
+    /// queued and delivered one at a time once the events of the current
+    /// input have been handled, so the listener is never called re-entrantly.
+    ///
+    /// [`submit_command`]: struct.Ui.html#method.submit_command
     pub fn set_command_listener<F>(&mut self, f: F)
     where
         F: FnMut(u32, ListenerCtx) + 'static,
@@ -245,6 +823,144 @@ impl UiState {
         self.command_listener = Some(Box::new(f));
     }
 
+    /// Set whether only the latest pending submission of a command is
+    /// delivered.
+    ///
+    /// This suits commands where only the most recent request matters, such
+    /// as scrolling to a position: submitting the command again replaces a
+    /// submission that is still queued.
+    pub fn set_coalesced(&mut self, cmd: u32, coalesced: bool) {
+        self.layout_ctx
+            .commands
+            .borrow_mut()
+            .set_coalesced(cmd, coalesced);
+    }
+
+    /// Set a listener called when a window is closed.
+    ///
+    /// The listener runs before the window's widgets are deleted. See
+    /// `set_quit_on_last_window_closed` for what happens after the last
+    /// window is closed.
+    pub fn set_window_close_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(WindowId, ListenerCtx) + 'static,
+    {
+        self.window_close_listener = Some(Box::new(f));
+    }
+
+    /// Set a listener called when the user asks to close a window, for
+    /// example with its close button, or when `Ui::quit` is called.
+    ///
+    /// Returning `false` keeps the window open. To ask about unsaved changes,
+    /// the listener can return `false`, show an alert with `Ui::show_alert`,
+    /// and call `Ui::close_window` if the user agrees.
+    pub fn set_close_request_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(WindowId, ListenerCtx) -> bool + 'static,
+    {
+        self.close_request_listener = Some(Box::new(f));
+    }
+
+    /// Set whether the application quits when its last window is closed.
+    ///
+    /// By default it does, except on macOS, where applications conventionally
+    /// keep running until quit from the menu.
+    pub fn set_quit_on_last_window_closed(&mut self, quit: bool) {
+        self.quit_on_last_window_closed = quit;
+    }
+
+    /// Set a listener called when a window is maximized, minimized, restored,
+    /// or enters or leaves fullscreen.
+    pub fn set_window_state_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(WindowId, WindowState, ListenerCtx) + 'static,
+    {
+        self.window_state_listener = Some(Box::new(f));
+    }
+
+    /// Set a listener called when the user switches between the light and
+    /// dark system theme. The windows are repainted afterwards, so widgets
+    /// that style themselves from `PaintCtx::theme` follow the change.
+    pub fn set_theme_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(Theme, ListenerCtx) + 'static,
+    {
+        self.theme_listener = Some(Box::new(f));
+    }
+
+    /// Set how the app changes the default theme, such as to use colors of
+    /// its own, or the system's accent color. `f` is called with the system
+    /// appearance and accent color and the environment of `theme::init`,
+    /// now and whenever either changes, and the result replaces the
+    /// environment as with `Ui::set_env`.
+    pub fn configure_env<F>(&mut self, f: F)
+    where
+        F: FnMut(Theme, Option<&Color>, &mut Env) + 'static,
+    {
+        self.env_config = Some(Box::new(f));
+        self.rebuild_env();
+    }
+
+    /// Make the environment again from the default theme and the changes
+    /// of the app, for the current system appearance and locale.
+    fn rebuild_env(&mut self) {
+        let layout_ctx = &self.inner.layout_ctx;
+        let theme = layout_ctx.theme;
+        let direction = LayoutDirection::of_locale(layout_ctx.localization.locale());
+        let mut env = theme::init(theme).adding(theme::LAYOUT_DIRECTION, direction);
+        if let Some(ref mut config) = self.env_config {
+            config(theme, self.inner.layout_ctx.accent_color.as_ref(), &mut env);
+        }
+        env.extend(&self.inner.layout_ctx.env_overrides);
+        self.inner.install_env(env);
+    }
+
+    /// Set a listener called when the system's accent color changes. The
+    /// windows are repainted afterwards, as for theme changes.
+    pub fn set_accent_color_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(Option<Color>, ListenerCtx) + 'static,
+    {
+        self.accent_color_listener = Some(Box::new(f));
+    }
+
+    /// Set a listener called when the user turns the system setting to
+    /// reduce transparency on or off. The windows are repainted afterwards,
+    /// as for theme changes.
+    pub fn set_reduce_transparency_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(bool, ListenerCtx) + 'static,
+    {
+        self.reduce_transparency_listener = Some(Box::new(f));
+    }
+
+    /// Set a listener called when the computer switches between battery and
+    /// external power, for example to put off background work.
+    pub fn set_power_source_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(PowerSource, ListenerCtx) + 'static,
+    {
+        self.power_source_listener = Some(Box::new(f));
+    }
+
+    /// Set a listener called when the user changes the size of text in the
+    /// accessibility settings. The windows are laid out and repainted
+    /// afterwards, so that the built-in widgets follow the change.
+    pub fn set_text_scale_listener<F>(&mut self, f: F)
+    where
+        F: FnMut(f64, ListenerCtx) + 'static,
+    {
+        self.text_scale_listener = Some(Box::new(f));
+    }
+
+    /// Set the keyboard shortcuts.
+    ///
+    /// Key events matching a binding are delivered as commands to the
+    /// command listener rather than to the focused widget.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
     fn mouse(&mut self, pos: Point, raw_event: &window::MouseEvent) {
         fn dispatch_mouse(
             widgets: &mut [Box<dyn Widget>],
@@ -272,9 +988,13 @@ impl UiState {
             let node = ctx.id;
             let g = ctx.layout_ctx.geom[node];
             let Vec2 { x, y } = pos - g.origin();
-            let Size { width, height } = g.size();
+            let slop = if ctx.layout_ctx.touch_mode {
+                TOUCH_SLOP
+            } else {
+                0.0
+            };
             let mut handled = false;
-            if x >= 0.0 && y >= 0.0 && x < width && y < height {
+            if ctx.layout_ctx.hits(node, Point::new(x, y), slop) {
                 handled = dispatch_mouse(widgets, node, Point::new(x, y), raw_event, ctx);
                 for child in graph.children[node].iter().rev() {
                     if handled {
@@ -287,7 +1007,11 @@ impl UiState {
             handled
         }
 
-        if let Some(active) = self.layout_ctx.active {
+        if self.layout_ctx.window.drag.is_some() {
+            if raw_event.count == 0 {
+                self.finish_drag(pos);
+            }
+        } else if let Some(active) = self.layout_ctx.window.active {
             // Send mouse event directly to active widget.
             let pos = pos - self.offset_of_widget(active);
             dispatch_mouse(
@@ -316,12 +1040,22 @@ impl UiState {
     }
 
     fn mouse_move(&mut self, pos: Point) {
+        if self.layout_ctx.window.drag.is_some() {
+            self.update_drag(pos);
+            self.dispatch_events();
+            return;
+        }
         // Note: this logic is similar to that for hit testing on mouse, but is
         // slightly different if child geom's overlap. Maybe we reconcile them,
         // maybe it's fine.
         let mut node = self.graph.root;
         let mut new_hot = None;
         let mut tpos = pos;
+        let slop = if self.layout_ctx.touch_mode {
+            TOUCH_SLOP
+        } else {
+            0.0
+        };
         loop {
             let g = self.layout_ctx.geom[node];
             tpos -= g.origin().to_vec2();
@@ -331,13 +1065,8 @@ impl UiState {
             }
             let mut child_hot = None;
             for child in self.graph.children[node].iter().rev() {
-                let child_g = self.layout_ctx.geom[*child];
-                let cpos = tpos - child_g.origin();
-                let Size { width, height } = child_g.size();
-
-                //FIXME: when kurbo 0.3.2 lands, we can write:
-                // if child_g.with_origin(Point::ORIGIN).contains(cpos)
-                if cpos.x >= 0.0 && cpos.y >= 0.0 && cpos.x < width && cpos.y < height {
+                let cpos = tpos - self.layout_ctx.geom[*child].origin();
+                if self.layout_ctx.hits(*child, cpos.to_point(), slop) {
                     child_hot = Some(child);
                     break;
                 }
@@ -348,12 +1077,12 @@ impl UiState {
                 break;
             }
         }
-        let old_hot = self.layout_ctx.hot;
+        let old_hot = self.layout_ctx.window.hot;
         if new_hot != old_hot {
-            self.layout_ctx.hot = new_hot;
+            self.layout_ctx.window.hot = new_hot;
             if let Some(old_hot) = old_hot {
-                self.inner.widgets[old_hot].on_hot_changed(
-                    false,
+                self.inner.widgets[old_hot].lifecycle(
+                    &LifeCycle::HotChanged(false),
                     &mut HandlerCtx {
                         id: old_hot,
                         layout_ctx: &mut self.inner.layout_ctx,
@@ -361,8 +1090,8 @@ impl UiState {
                 );
             }
             if let Some(new_hot) = new_hot {
-                self.inner.widgets[new_hot].on_hot_changed(
-                    true,
+                self.inner.widgets[new_hot].lifecycle(
+                    &LifeCycle::HotChanged(true),
                     &mut HandlerCtx {
                         id: new_hot,
                         layout_ctx: &mut self.inner.layout_ctx,
@@ -371,7 +1100,10 @@ impl UiState {
             }
         }
 
-        if let Some(node) = self.layout_ctx.active.or(new_hot) {
+        // The widget under the pointer sets the cursor on each move, so that
+        // it reverts to the arrow once the pointer leaves.
+        self.layout_ctx.window.cursor = None;
+        if let Some(node) = self.layout_ctx.window.active.or(new_hot) {
             let pos = pos - self.offset_of_widget(node);
             self.inner.widgets[node].mouse_moved(
                 pos,
@@ -381,64 +1113,736 @@ impl UiState {
                 },
             );
         }
+        let cursor = self
+            .layout_ctx
+            .window
+            .cursor
+            .take()
+            .unwrap_or(Cursor::Arrow);
+        self.layout_ctx.window.handle.set_cursor(&cursor);
+        if self.layout_ctx.window.drag.is_some() {
+            // A drag was started by the move; find the initial target.
+            self.update_drag(pos);
+        }
         self.dispatch_events();
     }
 
-    fn handle_key_down(&mut self, event: &KeyEvent) -> bool {
-        if let Some(id) = self.layout_ctx.focused {
-            let handled = {
+    /// Move the pointer of the drag session, updating the drop target.
+    fn update_drag(&mut self, pos: Point) {
+        let mut drag = match self.layout_ctx.window.drag.take() {
+            Some(drag) => drag,
+            None => return,
+        };
+        let mut new_target = None;
+        for (node, local) in self.hit_path(pos).into_iter().rev() {
+            let kind = if drag.target == Some(node) {
+                DragKind::Over
+            } else {
+                DragKind::Enter
+            };
+            let event = DragEvent {
+                kind,
+                pos: local,
+                payload: drag.payload.deref(),
+            };
+            let mut ctx = HandlerCtx {
+                id: node,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            if self.inner.widgets[node].drag(&event, &mut ctx) {
+                new_target = Some(node);
+                break;
+            }
+        }
+        if let Some(old_target) = drag.target {
+            if new_target != Some(old_target) {
+                let event = DragEvent {
+                    kind: DragKind::Leave,
+                    pos: pos - self.offset_of_widget(old_target),
+                    payload: drag.payload.deref(),
+                };
                 let mut ctx = HandlerCtx {
-                    id,
+                    id: old_target,
                     layout_ctx: &mut self.inner.layout_ctx,
                 };
-                self.inner.widgets[id].key_down(event, &mut ctx)
-            };
-            self.dispatch_events();
-            handled
-        } else {
-            false
+                self.inner.widgets[old_target].drag(&event, &mut ctx);
+            }
         }
+        drag.target = new_target;
+        drag.pos = Some(pos);
+        if drag.image.is_some() {
+            self.layout_ctx.invalidate();
+        }
+        self.layout_ctx.window.drag = Some(drag);
     }
 
-    fn handle_key_up(&mut self, event: &KeyEvent) {
-        if let Some(id) = self.layout_ctx.focused {
+    /// Release the payload over the current drop target, ending the session.
+    ///
+    /// Returns true if the target accepted the payload.
+    fn finish_drag(&mut self, pos: Point) -> bool {
+        self.update_drag(pos);
+        self.end_drag(true)
+    }
+
+    /// End the drag session. The drop target receives `Drop` if `drop` is
+    /// set, and `Leave` otherwise.
+    fn end_drag(&mut self, drop: bool) -> bool {
+        let drag = match self.layout_ctx.window.drag.take() {
+            Some(drag) => drag,
+            None => return false,
+        };
+        let pos = drag.pos.unwrap_or(Point::ORIGIN);
+        let mut dropped = false;
+        if let Some(target) = drag.target {
+            let event = DragEvent {
+                kind: if drop {
+                    DragKind::Drop
+                } else {
+                    DragKind::Leave
+                },
+                pos: pos - self.offset_of_widget(target),
+                payload: drag.payload.deref(),
+            };
             let mut ctx = HandlerCtx {
-                id,
+                id: target,
                 layout_ctx: &mut self.inner.layout_ctx,
             };
-            self.inner.widgets[id].key_up(event, &mut ctx);
-            self.dispatch_events();
+            dropped = self.inner.widgets[target].drag(&event, &mut ctx) && drop;
+        }
+        if let Some(source) = drag.source {
+            let event = DragEvent {
+                kind: DragKind::End { dropped },
+                pos: pos - self.offset_of_widget(source),
+                payload: drag.payload.deref(),
+            };
+            let mut ctx = HandlerCtx {
+                id: source,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            self.inner.widgets[source].drag(&event, &mut ctx);
+            // The mouse up was consumed by the drag, so the source can't clear this.
+            self.layout_ctx.set_active(None);
+        }
+        if let Some((cmd, priority)) = self.layout_ctx.window.drop_command.take() {
+            if dropped {
+                self.layout_ctx.commands.borrow_mut().push_with_payload(
+                    cmd,
+                    Some(drag.payload),
+                    priority,
+                );
+            }
+        }
+        self.layout_ctx.invalidate();
+        self.dispatch_events();
+        dropped
+    }
+
+    /// Start a drag session for files dragged in from another application.
+    ///
+    /// Returns true if there is a drop target under the pointer.
+    fn file_drag_enter(&mut self, paths: Vec<PathBuf>, pos: Point) -> bool {
+        if self.layout_ctx.window.drag.is_some() {
+            return false;
+        }
+        self.layout_ctx.window.drag = Some(DragState {
+            source: None,
+            payload: Box::new(paths),
+            image: None,
+            pos: None,
+            target: None,
+        });
+        self.file_drag_over(pos)
+    }
+
+    fn file_drag_over(&mut self, pos: Point) -> bool {
+        self.update_drag(pos);
+        self.dispatch_events();
+        self.layout_ctx
+            .window
+            .drag
+            .as_ref()
+            .and_then(|d| d.target)
+            .is_some()
+    }
+
+    fn file_drop(&mut self, paths: Vec<PathBuf>, pos: Point) -> bool {
+        if !self.is_file_drag() {
+            return false;
+        }
+        if let Some(ref mut drag) = self.layout_ctx.window.drag {
+            drag.payload = Box::new(paths);
         }
+        self.finish_drag(pos)
     }
 
-    fn handle_scroll(&mut self, event: &window::ScrollEvent) {
-        if let Some(id) = self.layout_ctx.hot {
+    /// Send an expired timer to the widget that asked for it.
+    fn handle_timer(&mut self, token: TimerToken) {
+        if let Some(id) = self.layout_ctx.window.timers.remove(&token) {
             let mut ctx = HandlerCtx {
                 id,
                 layout_ctx: &mut self.inner.layout_ctx,
             };
-            self.inner.widgets[id].scroll(event, &mut ctx);
-            self.dispatch_events();
+            self.inner.widgets[id].timer(token, &mut ctx);
         }
     }
 
-    fn handle_command(&mut self, cmd: u32) {
-        if let Some(ref mut listener) = self.command_listener {
-            let ctx = ListenerCtx {
-                id: self.inner.graph.root,
-                inner: &mut self.inner,
+    /// Tell the widget that started a drag out of the application that it
+    /// is over.
+    fn handle_system_drag_ended(&mut self, effect: DropEffect) {
+        if let Some(id) = self.layout_ctx.window.system_drag_source.take() {
+            let mut ctx = HandlerCtx {
+                id,
+                layout_ctx: &mut self.inner.layout_ctx,
             };
-            listener(cmd, ctx);
-        } else {
-            println!("command received but no handler");
+            self.inner.widgets[id].system_drag_ended(effect, &mut ctx);
+            // The mouse up was consumed by the drag, so the source can't clear this.
+            self.layout_ctx.set_active(None);
         }
     }
 
-    fn dispatch_events(&mut self) {
-        while !self.layout_ctx.event_q.is_empty() {
-            let event_q = mem::replace(&mut self.layout_ctx.event_q, Vec::new());
-            for event in event_q {
-                match event {
+    /// Determine whether the drag in progress comes from another application.
+    fn is_file_drag(&self) -> bool {
+        self.layout_ctx
+            .window
+            .drag
+            .as_ref()
+            .map(|d| d.source.is_none())
+            == Some(true)
+    }
+
+    /// The widgets containing a point, outermost first, each with the point
+    /// relative to its origin.
+    fn hit_path(&self, pos: Point) -> Vec<(Id, Point)> {
+        let mut path = Vec::new();
+        let mut node = self.graph.root;
+        let mut tpos = pos;
+        loop {
+            tpos -= self.layout_ctx.geom[node].origin().to_vec2();
+            if !self.layout_ctx.hits(node, tpos, 0.0) {
+                break;
+            }
+            path.push((node, tpos));
+            match self.graph.children[node].iter().rev().find(|&&child| {
+                let cpos = tpos - self.layout_ctx.geom[child].origin();
+                self.layout_ctx.hits(child, cpos.to_point(), 0.0)
+            }) {
+                Some(&child) => node = child,
+                None => break,
+            }
+        }
+        path
+    }
+
+    fn touch(&mut self, pos: Point, raw_event: &window::TouchEvent) {
+        let id = raw_event.id;
+        let target = match raw_event.phase {
+            TouchPhase::Down => {
+                let mut handler = None;
+                for (node, local) in self.hit_path(pos).into_iter().rev() {
+                    let event = TouchEvent {
+                        id,
+                        pos: local,
+                        phase: TouchPhase::Down,
+                    };
+                    let mut ctx = HandlerCtx {
+                        id: node,
+                        layout_ctx: &mut self.inner.layout_ctx,
+                    };
+                    if self.inner.widgets[node].touch(&event, &mut ctx) {
+                        handler = Some(node);
+                        break;
+                    }
+                }
+                if let Some(node) = handler {
+                    self.layout_ctx.window.touches.insert(id, node);
+                }
+                None
+            }
+            TouchPhase::Move => self.layout_ctx.window.touches.get(&id).cloned(),
+            TouchPhase::Up | TouchPhase::Cancel => self.layout_ctx.window.touches.remove(&id),
+        };
+        if let Some(node) = target {
+            let event = TouchEvent {
+                id,
+                pos: pos - self.offset_of_widget(node),
+                phase: raw_event.phase,
+            };
+            let mut ctx = HandlerCtx {
+                id: node,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            self.inner.widgets[node].touch(&event, &mut ctx);
+        }
+        if let Some(gesture) = self
+            .layout_ctx
+            .window
+            .gestures
+            .touch(id, pos, raw_event.phase)
+        {
+            self.gesture(gesture);
+        }
+        self.dispatch_events();
+    }
+
+    fn trackpad_gesture(&mut self, pos: Point, raw_event: &window::TrackpadGesture) {
+        let gestures = &mut self.layout_ctx.window.gestures;
+        if let Some(gesture) = gestures.trackpad(pos, raw_event.kind, raw_event.phase) {
+            self.gesture(gesture);
+        }
+        self.dispatch_events();
+    }
+
+    fn gesture(&mut self, gesture: Gesture) {
+        let make_event = |center| GestureEvent {
+            phase: gesture.phase,
+            center,
+            scale: gesture.scale,
+            rotation: gesture.rotation,
+            translation: gesture.translation,
+        };
+        let target = match gesture.phase {
+            GesturePhase::Began => {
+                for (node, local) in self.hit_path(gesture.center).into_iter().rev() {
+                    let mut ctx = HandlerCtx {
+                        id: node,
+                        layout_ctx: &mut self.inner.layout_ctx,
+                    };
+                    if self.inner.widgets[node].gesture(&make_event(local), &mut ctx) {
+                        self.layout_ctx.window.gesture_target = Some(node);
+                        break;
+                    }
+                }
+                None
+            }
+            GesturePhase::Changed => self.layout_ctx.window.gesture_target,
+            GesturePhase::Ended => self.layout_ctx.window.gesture_target.take(),
+        };
+        if let Some(node) = target {
+            let event = make_event(gesture.center - self.offset_of_widget(node));
+            let mut ctx = HandlerCtx {
+                id: node,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            self.inner.widgets[node].gesture(&event, &mut ctx);
+        }
+    }
+
+    /// Returns true if a widget handled the pen event.
+    fn pen(&mut self, pos: Point, raw_event: &window::PenEvent) -> bool {
+        let make_event = |pos| PenEvent {
+            pos,
+            phase: raw_event.phase,
+            pressure: raw_event.pressure,
+            tilt_x: raw_event.tilt_x,
+            tilt_y: raw_event.tilt_y,
+            barrel: raw_event.barrel,
+            eraser: raw_event.eraser,
+            mods: raw_event.mods,
+        };
+        let handled = match raw_event.phase {
+            PenPhase::Down | PenPhase::Hover => {
+                let mut handler = None;
+                for (node, local) in self.hit_path(pos).into_iter().rev() {
+                    let mut ctx = HandlerCtx {
+                        id: node,
+                        layout_ctx: &mut self.inner.layout_ctx,
+                    };
+                    if self.inner.widgets[node].pen(&make_event(local), &mut ctx) {
+                        handler = Some(node);
+                        break;
+                    }
+                }
+                if raw_event.phase == PenPhase::Down {
+                    self.layout_ctx.window.pen_target = handler;
+                }
+                handler.is_some()
+            }
+            PenPhase::Move | PenPhase::Up => {
+                let target = if raw_event.phase == PenPhase::Up {
+                    self.layout_ctx.window.pen_target.take()
+                } else {
+                    self.layout_ctx.window.pen_target
+                };
+                match target {
+                    Some(node) => {
+                        let event = make_event(pos - self.offset_of_widget(node));
+                        let mut ctx = HandlerCtx {
+                            id: node,
+                            layout_ctx: &mut self.inner.layout_ctx,
+                        };
+                        self.inner.widgets[node].pen(&event, &mut ctx);
+                        true
+                    }
+                    None => false,
+                }
+            }
+        };
+        self.dispatch_events();
+        handled
+    }
+
+    fn handle_key_down(&mut self, event: &KeyEvent) -> bool {
+        if let Some(cmd) = self.keymap.lookup(event) {
+            self.submit_command(cmd, CommandPriority::Normal);
+            self.dispatch_events();
+            return true;
+        }
+        if let Some(id) = self.layout_ctx.window.focused {
+            let handled = {
+                let mut ctx = HandlerCtx {
+                    id,
+                    layout_ctx: &mut self.inner.layout_ctx,
+                };
+                self.inner.widgets[id].key_down(event, &mut ctx)
+            };
+            let handled = handled || self.move_focus(event);
+            self.dispatch_events();
+            handled
+        } else {
+            let handled = self.move_focus(event);
+            self.dispatch_events();
+            handled
+        }
+    }
+
+    /// Move the focus with a key the focused widget didn't handle: along
+    /// the focus chain with Tab and Shift+Tab, and with the arrows between
+    /// the widgets accepting focus in the same group, the children of one
+    /// parent, such as the buttons of a row.
+    fn move_focus(&mut self, event: &KeyEvent) -> bool {
+        let mods = event.modifiers;
+        if mods.ctrl || mods.alt || mods.meta {
+            return false;
+        }
+        let rtl = self.layout_ctx.env.get(theme::LAYOUT_DIRECTION).is_rtl();
+        let forward = match event.code {
+            KeyCode::Tab => {
+                self.layout_ctx.window.focus_req = Some(if mods.shift {
+                    FocusChange::Previous
+                } else {
+                    FocusChange::Next
+                });
+                return true;
+            }
+            KeyCode::ArrowDown => true,
+            KeyCode::ArrowUp => false,
+            KeyCode::ArrowRight => !rtl,
+            KeyCode::ArrowLeft => rtl,
+            _ => return false,
+        };
+        let focused = match self.layout_ctx.window.focused {
+            Some(focused) => focused,
+            None => return false,
+        };
+        let parent = self.graph.parent[focused];
+        if parent == focused {
+            return false;
+        }
+        let group: Vec<Id> = self.graph.children[parent]
+            .iter()
+            .cloned()
+            .filter(|&id| self.widgets[id].accepts_focus())
+            .collect();
+        let ix = match group.iter().position(|&id| id == focused) {
+            Some(ix) => ix,
+            None => return false,
+        };
+        let next = if forward {
+            group.get(ix + 1)
+        } else if ix > 0 {
+            group.get(ix - 1)
+        } else {
+            None
+        };
+        match next {
+            Some(&next) => {
+                self.layout_ctx.window.focus_req = Some(FocusChange::Focus(next));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Send a composition event to the widget it is for: the focused widget
+    /// when the composition starts. Compositions that were committed when
+    /// the focus moved away are over, so their events are dropped.
+    fn handle_composition(&mut self, event: &CompositionEvent) {
+        let window = &mut self.inner.layout_ctx.window;
+        let id = match (event, &mut window.composition) {
+            (CompositionEvent::Start, composition) => match window.focused {
+                Some(id) => {
+                    *composition = Some((id, String::new()));
+                    id
+                }
+                None => return,
+            },
+            (CompositionEvent::Update { text, .. }, Some((id, composed))) => {
+                *composed = text.clone();
+                *id
+            }
+            (CompositionEvent::Commit(_), composition @ Some(_)) => composition.take().unwrap().0,
+            (_, None) => return,
+        };
+        let mut ctx = HandlerCtx {
+            id,
+            layout_ctx: &mut self.inner.layout_ctx,
+        };
+        self.inner.widgets[id].composition(event, &mut ctx);
+        self.dispatch_events();
+    }
+
+    fn handle_key_up(&mut self, event: &KeyEvent) {
+        if let Some(id) = self.layout_ctx.window.focused {
+            let mut ctx = HandlerCtx {
+                id,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            self.inner.widgets[id].key_up(event, &mut ctx);
+            self.dispatch_events();
+        }
+    }
+
+    fn handle_accessibility_action(&mut self, request: &ActionRequest) -> bool {
+        let id = request.node as Id;
+        if id >= self.widgets.len() || self.window_of(id) != Some(self.layout_ctx.window_id) {
+            return false;
+        }
+        let handled = if request.action == Action::Focus {
+            self.set_focus(Some(id));
+            true
+        } else {
+            let mut ctx = HandlerCtx {
+                id,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            self.inner.widgets[id].accessibility_action(request, &mut ctx)
+        };
+        self.dispatch_events();
+        handled
+    }
+
+    /// Give the window the accessibility tree, if assistive technology
+    /// reads it and it changed.
+    fn sync_accessibility(&mut self) {
+        if !self.layout_ctx.window.handle.accessibility_enabled() {
+            return;
+        }
+        let tree = self.accessibility_tree();
+        if self.layout_ctx.window.access_tree.as_ref() != Some(&tree) {
+            self.layout_ctx
+                .window
+                .handle
+                .update_accessibility(tree.clone());
+            self.layout_ctx.window.access_tree = Some(tree);
+        }
+    }
+
+    fn handle_wheel(&mut self, event: &WheelEvent) {
+        if let Some(id) = self.layout_ctx.window.hot {
+            let mut ctx = HandlerCtx {
+                id,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            self.inner.widgets[id].wheel(event, &mut ctx);
+            self.dispatch_events();
+        }
+    }
+
+    fn deliver_command(&mut self, cmd: u32, payload: Option<Box<dyn Any>>) {
+        self.inner.command_payload = payload;
+        if !self.offer_command(cmd) {
+            if let Some(ref mut listener) = self.command_listener {
+                let ctx = ListenerCtx {
+                    id: self.inner.graph.root,
+                    inner: &mut self.inner,
+                };
+                listener(cmd, ctx);
+            } else {
+                println!("command received but no handler");
+            }
+        }
+        self.inner.command_payload = None;
+    }
+
+    fn window_state_changed(&mut self, state: WindowState) {
+        self.inner.layout_ctx.window.window_state = state;
+        if let Some(ref mut listener) = self.window_state_listener {
+            let window = self.inner.layout_ctx.window_id;
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(window, state, ctx);
+        }
+        self.dispatch_events();
+    }
+
+    fn theme_changed(&mut self, theme: Theme) {
+        // Every window is told about the change; only the first one counts.
+        if self.inner.layout_ctx.theme == theme {
+            return;
+        }
+        self.inner.layout_ctx.theme = theme;
+        self.rebuild_env();
+        if let Some(ref mut listener) = self.theme_listener {
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(theme, ctx);
+        }
+        self.repaint_all();
+        self.dispatch_events();
+    }
+
+    fn accent_color_changed(&mut self, color: Option<Color>) {
+        // Every window is told about the change; only the first one counts.
+        let rgba = color.as_ref().map(Color::as_rgba32);
+        if self
+            .inner
+            .layout_ctx
+            .accent_color
+            .as_ref()
+            .map(Color::as_rgba32)
+            == rgba
+        {
+            return;
+        }
+        self.inner.layout_ctx.accent_color = color.clone();
+        self.rebuild_env();
+        if let Some(ref mut listener) = self.accent_color_listener {
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(color, ctx);
+        }
+        self.repaint_all();
+        self.dispatch_events();
+    }
+
+    fn reduce_transparency_changed(&mut self, reduce: bool) {
+        // Every window is told about the change; only the first one counts.
+        if self.inner.layout_ctx.reduce_transparency == reduce {
+            return;
+        }
+        self.inner.layout_ctx.reduce_transparency = reduce;
+        if let Some(ref mut listener) = self.reduce_transparency_listener {
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(reduce, ctx);
+        }
+        self.repaint_all();
+        self.dispatch_events();
+    }
+
+    fn power_source_changed(&mut self, source: PowerSource) {
+        // Every window is told about the change; only the first one counts.
+        if self.inner.layout_ctx.power_source == source {
+            return;
+        }
+        self.inner.layout_ctx.power_source = source;
+        if let Some(ref mut listener) = self.power_source_listener {
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(source, ctx);
+        }
+        self.dispatch_events();
+    }
+
+    fn text_scale_changed(&mut self, scale: f64) {
+        // Every window is told about the change; only the first one counts.
+        if self.inner.layout_ctx.text_scale == scale {
+            return;
+        }
+        self.inner.layout_ctx.text_scale = scale;
+        if let Some(ref mut listener) = self.text_scale_listener {
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(scale, ctx);
+        }
+        self.repaint_all();
+        self.dispatch_events();
+    }
+
+    /// Lay out and repaint every window, after a change of the system
+    /// appearance.
+    fn repaint_all(&mut self) {
+        self.inner.layout_ctx.invalidate_layers();
+        self.inner.layout_ctx.invalidate();
+        for window in self.inner.windows.values() {
+            window.handle.invalidate();
+        }
+    }
+
+    /// Ask the listener whether the current window may close.
+    fn close_requested(&mut self) -> bool {
+        let allowed = match self.close_request_listener {
+            Some(ref mut listener) => {
+                let window = self.inner.layout_ctx.window_id;
+                let ctx = ListenerCtx {
+                    id: self.inner.graph.root,
+                    inner: &mut self.inner,
+                };
+                listener(window, ctx)
+            }
+            None => true,
+        };
+        self.dispatch_events();
+        allowed
+    }
+
+    /// Notify the listener that a window has closed, then delete its widgets.
+    ///
+    /// Returns true if no windows remain open.
+    fn window_closed(&mut self, id: WindowId) -> bool {
+        if let Some(ref mut listener) = self.window_close_listener {
+            let ctx = ListenerCtx {
+                id: self.inner.graph.root,
+                inner: &mut self.inner,
+            };
+            listener(id, ctx);
+        }
+        self.dispatch_events();
+        let root = self.graph.root;
+        self.delete_subtree(root);
+        self.graph.free_subtree(root);
+        let next = match self.inner.windows.keys().next() {
+            Some(&next) => next,
+            None => return true,
+        };
+        let window = self.inner.windows.remove(&next).unwrap();
+        self.inner.layout_ctx.window = window;
+        self.inner.layout_ctx.window_id = next;
+        self.inner.graph.root = self.layout_ctx.window.root;
+        self.dispatch_events();
+        false
+    }
+
+    fn dispatch_events(&mut self) {
+        loop {
+            self.update_focus();
+            if self.layout_ctx.event_q.is_empty() {
+                // Commands are only delivered once the events resulting from
+                // the previous one have been dispatched.
+                let cmd = self.layout_ctx.commands.borrow_mut().pop();
+                match cmd {
+                    Some((cmd, payload)) => {
+                        self.deliver_command(cmd, payload);
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+            let event_q = mem::replace(&mut self.layout_ctx.event_q, Vec::new());
+            for event in event_q {
+                match event {
                     Event::Event(id, mut event) => {
                         if let Some(listeners) = self.listeners.get_mut(&id) {
                             for listener in listeners {
@@ -454,29 +1858,172 @@ impl UiState {
                         self.listeners.entry(id).or_default().push(listener);
                     }
                     Event::ClearListeners(id) => {
-                        self.listeners.get_mut(&id).map(|l| l.clear());
+                        if let Some(listeners) = self.listeners.get_mut(&id) {
+                            listeners.clear();
+                        }
+                        self.layout_ctx.window.timers.retain(|_, &mut w| w != id);
+                        for window in self.inner.windows.values_mut() {
+                            window.timers.retain(|_, &mut w| w != id);
+                        }
+                    }
+                    Event::Notification(source, payload) => {
+                        self.bubble_notification(source, payload.deref());
+                    }
+                    Event::ContextMenu(id, menu, pos) => {
+                        let pos = pos + self.offset_of_widget(id);
+                        self.inner.show_context_menu(&menu, pos);
+                    }
+                    Event::CaretRect(id, rect) => {
+                        let rect = rect + self.offset_of_widget(id);
+                        self.layout_ctx.window.handle.set_caret_rect(rect);
+                    }
+                    Event::SystemDrag(id, data) => {
+                        self.layout_ctx.window.system_drag_source = Some(id);
+                        if let Err(e) = self.layout_ctx.window.handle.start_drag(data) {
+                            println!("Error starting drag: {:?}", e);
+                            self.handle_system_drag_ended(DropEffect::None);
+                        }
                     }
                 }
             }
         }
     }
 
-    // Process an animation frame. This consists mostly of calling anim_frame on
-    // widgets that have requested a frame.
+    /// Offer a command to the focused widget and then each of its ancestors,
+    /// until one handles it.
+    fn offer_command(&mut self, cmd: u32) -> bool {
+        let mut node = match self.layout_ctx.window.focused {
+            Some(id) => id,
+            None => return false,
+        };
+        loop {
+            let mut ctx = HandlerCtx {
+                id: node,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            if self.inner.widgets[node].command(cmd, &mut ctx) {
+                return true;
+            }
+            let parent = self.graph.parent[node];
+            if parent == node {
+                return false;
+            }
+            node = parent;
+        }
+    }
+
+    /// Offer a notification to each ancestor of the source, innermost first,
+    /// until one handles it.
+    fn bubble_notification(&mut self, source: Id, payload: &dyn Any) {
+        let notification = Notification { source, payload };
+        let mut node = source;
+        loop {
+            let parent = self.graph.parent[node];
+            if parent == node {
+                break;
+            }
+            node = parent;
+            let mut ctx = HandlerCtx {
+                id: node,
+                layout_ctx: &mut self.inner.layout_ctx,
+            };
+            if self.inner.widgets[node].notification(&notification, &mut ctx) {
+                break;
+            }
+        }
+    }
+
+    /// Apply a pending focus change, notifying both the widget losing focus
+    /// and the widget gaining it.
+    fn update_focus(&mut self) {
+        let req = match self.layout_ctx.window.focus_req.take() {
+            Some(req) => req,
+            None => return,
+        };
+        let old_focus = self.layout_ctx.window.focused;
+        let new_focus = match req {
+            FocusChange::Focus(id) => Some(id),
+            FocusChange::Resign(id) if old_focus == Some(id) => None,
+            FocusChange::Resign(_) => old_focus,
+            FocusChange::Clear => None,
+            FocusChange::Next => self.focus_chain_neighbor(old_focus, true),
+            FocusChange::Previous => self.focus_chain_neighbor(old_focus, false),
+        };
+        if new_focus == old_focus {
+            return;
+        }
+        self.layout_ctx.window.focused = new_focus;
+        if let Some(old_focus) = old_focus {
+            // The text composed for the widget is kept as it is, rather than
+            // finished in the next widget.
+            if let Some((id, text)) = self.inner.layout_ctx.window.composition.take() {
+                self.inner.layout_ctx.window.handle.cancel_composition();
+                self.inner.widgets[id].composition(
+                    &CompositionEvent::Commit(text),
+                    &mut HandlerCtx {
+                        id,
+                        layout_ctx: &mut self.inner.layout_ctx,
+                    },
+                );
+            }
+            self.inner.widgets[old_focus].lifecycle(
+                &LifeCycle::FocusChanged(false),
+                &mut HandlerCtx {
+                    id: old_focus,
+                    layout_ctx: &mut self.inner.layout_ctx,
+                },
+            );
+        }
+        if let Some(new_focus) = new_focus {
+            self.inner.widgets[new_focus].lifecycle(
+                &LifeCycle::FocusChanged(true),
+                &mut HandlerCtx {
+                    id: new_focus,
+                    layout_ctx: &mut self.inner.layout_ctx,
+                },
+            );
+        }
+        self.layout_ctx.invalidate();
+    }
+
+    /// Find the widget after (or before) `focus` in the focus chain, wrapping
+    /// around at the ends. If nothing is focused, this is the first (or last)
+    /// widget in the chain.
+    fn focus_chain_neighbor(&self, focus: Option<Id>, forward: bool) -> Option<Id> {
+        let chain = self.focus_chain();
+        if chain.is_empty() {
+            return focus;
+        }
+        let ix = focus.and_then(|focus| chain.iter().position(|&id| id == focus));
+        let len = chain.len();
+        let new_ix = match (ix, forward) {
+            (Some(ix), true) => (ix + 1) % len,
+            (Some(ix), false) => (ix + len - 1) % len,
+            (None, true) => 0,
+            (None, false) => len - 1,
+        };
+        Some(chain[new_ix])
+    }
+
+    // Process an animation frame, just before painting. This consists mostly
+    // of calling anim_frame on widgets that have requested a frame.
     fn anim_frame(&mut self) {
         // TODO: this is just wall-clock time, which will have jitter making
         // animations not as smooth. Should be extracting actual refresh rate
         // from presentation statistics and then doing some processing.
         let this_paint_time = Instant::now();
-        let interval = if let Some(last) = self.layout_ctx.prev_paint_time {
+        let interval = if let Some(last) = self.layout_ctx.window.prev_paint_time {
             let duration = this_paint_time.duration_since(last);
             1_000_000_000 * duration.as_secs() + (duration.subsec_nanos() as u64)
         } else {
             0
         };
-        self.layout_ctx.anim_state = AnimState::AnimFrameStart;
+        self.layout_ctx.window.anim_state = AnimState::AnimFrameStart;
+        let window = self.layout_ctx.window_id;
         for node in 0..self.widgets.len() {
-            if self.layout_ctx.per_widget[node].anim_frame_requested {
+            if self.layout_ctx.per_widget[node].anim_frame_requested
+                && self.window_of(node) == Some(window)
+            {
                 self.layout_ctx.per_widget[node].anim_frame_requested = false;
                 self.inner.widgets[node].anim_frame(
                     interval,
@@ -487,7 +2034,19 @@ impl UiState {
                 );
             }
         }
-        self.layout_ctx.prev_paint_time = Some(this_paint_time);
+        // Requests during the frame are combined into one for the next;
+        // without any, the shell is left to sleep after the paint.
+        match self.layout_ctx.window.anim_state {
+            AnimState::AnimFrameRequested => {
+                self.layout_ctx.window.handle.request_frame();
+                self.layout_ctx.window.anim_state = AnimState::FrameRequested;
+                self.layout_ctx.window.prev_paint_time = Some(this_paint_time);
+            }
+            _ => {
+                self.layout_ctx.window.anim_state = AnimState::InvalidationRequested;
+                self.layout_ctx.window.prev_paint_time = None;
+            }
+        }
         self.dispatch_events();
     }
 
@@ -508,6 +2067,33 @@ impl UiState {
     }
 }
 
+/// The state borrowed by `UiMain::state`.
+struct EventState<'a> {
+    state: RefMut<'a, UiState>,
+    start: Instant,
+}
+
+impl<'a> Deref for EventState<'a> {
+    type Target = UiState;
+
+    fn deref(&self) -> &UiState {
+        &self.state
+    }
+}
+
+impl<'a> DerefMut for EventState<'a> {
+    fn deref_mut(&mut self) -> &mut UiState {
+        &mut self.state
+    }
+}
+
+impl<'a> Drop for EventState<'a> {
+    fn drop(&mut self) {
+        let elapsed = Instant::now().duration_since(self.start);
+        self.state.layout_ctx.window.stats.events += elapsed;
+    }
+}
+
 impl Deref for UiState {
     type Target = Ui;
 
@@ -526,11 +2112,46 @@ impl Ui {
     /// Send an arbitrary payload to a widget. The type and interpretation of the
     /// payload depends on the specific target widget.
     pub fn poke<A: Any>(&mut self, node: Id, payload: &mut A) -> bool {
+        // Enter the widget's window, so that invalidation reaches it.
+        let current = self.layout_ctx.window_id;
+        if let Some(window) = self.window_of(node) {
+            self.enter_window(window);
+        }
         let mut ctx = HandlerCtx {
             id: node,
             layout_ctx: &mut self.layout_ctx,
         };
-        self.widgets[node].poke(payload, &mut ctx)
+        let handled = self.widgets[node].poke(payload, &mut ctx);
+        self.enter_window(current);
+        handled
+    }
+
+    /// Queue a command for the command listener.
+    pub fn submit_command(&mut self, cmd: u32, priority: CommandPriority) {
+        self.layout_ctx.commands.borrow_mut().push(cmd, priority);
+    }
+
+    /// Queue a command carrying a payload, which the command listener can
+    /// retrieve with `command_payload`.
+    pub fn submit_command_with_payload<P: Any>(
+        &mut self,
+        cmd: u32,
+        payload: P,
+        priority: CommandPriority,
+    ) {
+        self.layout_ctx.commands.borrow_mut().push_with_payload(
+            cmd,
+            Some(Box::new(payload)),
+            priority,
+        );
+    }
+
+    /// The payload of the command currently being delivered to the command
+    /// listener, if it has one of type `P`.
+    pub fn command_payload<P: Any>(&self) -> Option<&P> {
+        self.command_payload
+            .as_ref()
+            .and_then(|payload| payload.downcast_ref())
     }
 
     /// Put a widget in the graph and add its children. Returns newly allocated
@@ -549,19 +2170,553 @@ impl Ui {
             self.layout_ctx.geom.push(Default::default());
             self.layout_ctx.per_widget.push(Default::default());
         }
-        for &child in children {
-            self.graph.append_child(id, child);
+        for &child in children {
+            self.graph.append_child(id, child);
+        }
+        let mut ctx = HandlerCtx {
+            id,
+            layout_ctx: &mut self.layout_ctx,
+        };
+        self.widgets[id].lifecycle(&LifeCycle::WidgetAdded, &mut ctx);
+        id
+    }
+
+    /// Set the root widget of the current window.
+    pub fn set_root(&mut self, root: Id) {
+        self.graph.root = root;
+        self.layout_ctx.window.root = root;
+    }
+
+    /// Replace the menubar of the current window.
+    ///
+    /// On macOS the menubar belongs to the application, so this replaces it
+    /// for all windows.
+    pub fn set_menu(&mut self, menu: &MenuDesc) {
+        self.layout_ctx.window.handle.set_menu(menu.build());
+    }
+
+    /// Show a context menu at a position in the current window.
+    ///
+    /// The menu is shown once the current event has been handled. The chosen
+    /// item is delivered to the command listener.
+    pub fn show_context_menu(&mut self, menu: &MenuDesc, pos: impl Into<Point>) {
+        self.pending_context_menu =
+            Some((self.layout_ctx.window_id, menu.build_popup(), pos.into()));
+        // The menu runs a modal loop that sends events to the window, so it
+        // can't be shown while the state is borrowed.
+        if let Some(idle) = self.layout_ctx.window.handle.get_idle_handle() {
+            idle.add_idle(|handler| {
+                if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                    ui_main.show_pending_context_menu();
+                }
+            });
+        }
+    }
+
+    /// Show a file open dialog for the current window.
+    ///
+    /// The dialog is shown once the current event has been handled. When
+    /// the user chooses files, `command` is sent to the command listener
+    /// with a `Vec<FileInfo>` payload; see `command_payload`. If the dialog
+    /// is cancelled, no command is sent.
+    pub fn open_file_dialog(&mut self, options: FileDialogOptions, command: u32) {
+        self.request_file_dialog(FileDialogType::Open, options, command);
+    }
+
+    /// Show a file save dialog for the current window.
+    ///
+    /// The dialog is shown once the current event has been handled, and asks
+    /// before an existing file is replaced. When the user chooses a file,
+    /// `command` is sent to the command listener with a `FileInfo` payload;
+    /// see `command_payload`. If the dialog is cancelled, no command is sent.
+    pub fn save_file_dialog(&mut self, options: FileDialogOptions, command: u32) {
+        self.request_file_dialog(FileDialogType::Save, options, command);
+    }
+
+    /// Show a directory chooser for the current window.
+    ///
+    /// The dialog is shown once the current event has been handled. When
+    /// the user chooses a directory, `command` is sent to the command
+    /// listener with a `FileInfo` payload; see `command_payload`. If the
+    /// dialog is cancelled, no command is sent.
+    pub fn open_directory_dialog(&mut self, options: FileDialogOptions, command: u32) {
+        self.request_file_dialog(FileDialogType::Directory, options, command);
+    }
+
+    fn request_file_dialog(
+        &mut self,
+        ty: FileDialogType,
+        options: FileDialogOptions,
+        command: u32,
+    ) {
+        self.pending_file_dialog = Some(PendingFileDialog {
+            window: self.layout_ctx.window_id,
+            ty,
+            options,
+            command,
+        });
+        // Like a context menu, the dialog runs a modal loop.
+        if let Some(idle) = self.layout_ctx.window.handle.get_idle_handle() {
+            idle.add_idle(|handler| {
+                if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                    ui_main.show_pending_file_dialog();
+                }
+            });
+        }
+    }
+
+    /// Show a native message box for the current window.
+    ///
+    /// The alert is shown once the current event has been handled. When it
+    /// is dismissed, `command` is sent to the command listener with an
+    /// `AlertResponse` payload; see `command_payload`.
+    pub fn show_alert(&mut self, options: AlertOptions, command: u32) {
+        self.pending_alert = Some((self.layout_ctx.window_id, options, command));
+        if let Some(idle) = self.layout_ctx.window.handle.get_idle_handle() {
+            idle.add_idle(|handler| {
+                if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                    ui_main.show_pending_alert();
+                }
+            });
+        }
+    }
+
+    /// The handle of the current window, for querying and changing its
+    /// properties, such as `set_title`.
+    pub fn window(&self) -> &WindowHandle {
+        &self.layout_ctx.window.handle
+    }
+
+    /// Have screen readers read out `text`, such as "3 results found",
+    /// wherever the focus is.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        self.layout_ctx.window.handle.announce(text, politeness);
+    }
+
+    /// Maximize, minimize, restore or make fullscreen the current window.
+    ///
+    /// The change is made once the current event has been handled.
+    pub fn set_window_state(&mut self, state: WindowState) {
+        self.layout_ctx.request_window_state(state);
+    }
+
+    /// Close the current window once the current event has been handled,
+    /// without asking the close request listener.
+    pub fn close_window(&mut self) {
+        self.layout_ctx.with_window_later(|handle| handle.close());
+    }
+
+    /// Ask every window whether it may close, as if the user had clicked its
+    /// close button. If all agree, the windows are closed and the
+    /// application quits.
+    pub fn quit(&mut self) {
+        if let Some(idle) = self.layout_ctx.window.handle.get_idle_handle() {
+            idle.add_idle(|handler| {
+                if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                    ui_main.quit();
+                }
+            });
+        }
+    }
+
+    /// Show or hide the current window, once the current event has been
+    /// handled. A hidden window keeps its widgets and its tray icon.
+    pub fn set_window_visible(&mut self, visible: bool) {
+        self.layout_ctx.with_window_later(move |handle| {
+            if visible {
+                handle.show();
+            } else {
+                handle.hide();
+            }
+        });
+    }
+
+    /// Show an icon for the current window in the system tray (the
+    /// notification area on Windows, the status bar on macOS), replacing any
+    /// previous one.
+    ///
+    /// Clicking the icon sends `click_command` to the command listener. With
+    /// a menu, the menu is shown instead on macOS, and on right click on
+    /// Windows.
+    pub fn set_tray_icon(
+        &mut self,
+        icon: &IconDesc,
+        tooltip: &str,
+        menu: Option<&MenuDesc>,
+        click_command: Option<u32>,
+    ) {
+        self.layout_ctx.window.tray_command = click_command;
+        let menu = menu.map(MenuDesc::build_popup);
+        if let Err(e) = self
+            .layout_ctx
+            .window
+            .handle
+            .set_tray_icon(icon, tooltip, menu)
+        {
+            println!("failed to set tray icon: {:?}", e);
+        }
+    }
+
+    pub fn remove_tray_icon(&mut self) {
+        self.layout_ctx.window.tray_command = None;
+        self.layout_ctx.window.handle.remove_tray_icon();
+    }
+
+    /// The frame of the current window and whether it is maximized, for
+    /// saving in a `GeometryStore`.
+    pub fn window_geometry(&self) -> WindowGeometry {
+        self.layout_ctx.window.handle.get_geometry()
+    }
+
+    /// Restore the frame and maximized state of the current window, once the
+    /// current event has been handled. The frame is kept on screen even if
+    /// the monitor layout has changed.
+    pub fn set_window_geometry(&mut self, geometry: WindowGeometry) {
+        self.layout_ctx
+            .with_window_later(move |handle| handle.set_geometry(&geometry));
+    }
+
+    /// The light or dark system theme.
+    pub fn theme(&self) -> Theme {
+        self.layout_ctx.theme
+    }
+
+    /// The values widgets are drawn with, starting as the default theme for
+    /// the system's appearance; see `theme`.
+    pub fn env(&self) -> &Env {
+        &self.layout_ctx.env
+    }
+
+    /// Replace the values widgets are drawn with, such as to restyle the
+    /// whole UI with colors and fonts of its own. The built-in widgets need
+    /// the keys of `theme`, so the new environment is best made from a
+    /// clone of `env`.
+    ///
+    /// Every widget gets `LifeCycle::EnvChanged`, and all windows are laid
+    /// out and painted again. The values that differ from `env` are kept
+    /// when the environment is made again for a change of the system
+    /// appearance, over the theme and `UiState::configure_env`.
+    pub fn set_env(&mut self, env: Env) {
+        let changes = env.changes_from(&self.layout_ctx.env);
+        self.layout_ctx.env_overrides.extend(&changes);
+        self.install_env(env);
+    }
+
+    /// Install `env` without taking it as changes of the app.
+    fn install_env(&mut self, env: Env) {
+        self.layout_ctx.env = env;
+        self.notify_all(LifeCycle::EnvChanged);
+    }
+
+    /// The messages shown by widgets with a `LocalizedString`, in the
+    /// system's locale until changed. The layout direction of the
+    /// environment follows the locale.
+    pub fn localization(&self) -> &Localization {
+        &self.layout_ctx.localization
+    }
+
+    /// Replace the messages and locale, such as with the resources of the
+    /// app, and update the widgets showing them.
+    pub fn set_localization(&mut self, localization: Localization) {
+        self.layout_ctx.localization = localization;
+        self.locale_changed();
+    }
+
+    /// Show messages in `locale`, such as `"fr-FR"`, and lay out in its
+    /// direction.
+    pub fn set_locale(&mut self, locale: &str) {
+        self.layout_ctx.localization.set_locale(locale);
+        self.locale_changed();
+    }
+
+    fn locale_changed(&mut self) {
+        let direction = LayoutDirection::of_locale(self.layout_ctx.localization.locale());
+        if self.layout_ctx.env.try_get(theme::LAYOUT_DIRECTION) != Some(direction) {
+            let env = self.layout_ctx.env.clone();
+            self.install_env(env.adding(theme::LAYOUT_DIRECTION, direction));
+        }
+        self.notify_all(LifeCycle::LocaleChanged);
+    }
+
+    /// Send `event` to every widget, and lay out and paint all windows.
+    fn notify_all(&mut self, event: LifeCycle) {
+        for id in 0..self.widgets.len() {
+            let mut ctx = HandlerCtx {
+                id,
+                layout_ctx: &mut self.layout_ctx,
+            };
+            self.widgets[id].lifecycle(&event, &mut ctx);
+        }
+        self.layout_ctx.invalidate_layers();
+        self.layout_ctx.request_layout();
+        for window in self.windows.values() {
+            window.handle.invalidate();
+        }
+    }
+
+    /// Lay the UI out for touch, for kiosks and tablets: the built-in
+    /// controls are at least `TOUCH_TARGET_SIZE` tall, taps a little outside
+    /// of a widget still hit it, hover highlights are not shown, and text
+    /// boxes show the on-screen keyboard when they get the focus.
+    pub fn set_touch_mode(&mut self, touch_mode: bool) {
+        if self.layout_ctx.touch_mode != touch_mode {
+            self.layout_ctx.touch_mode = touch_mode;
+            self.layout_ctx.invalidate_layers();
+            self.layout_ctx.invalidate();
+            for window in self.windows.values() {
+                window.handle.invalidate();
+            }
+        }
+    }
+
+    /// Whether the UI is laid out for touch; see `set_touch_mode`.
+    pub fn touch_mode(&self) -> bool {
+        self.layout_ctx.touch_mode
+    }
+
+    /// Show statistics of the last frame in the top left corner of each
+    /// window, for finding performance problems: the frames painted in the
+    /// last second, the time spent handling events, in `anim_frame`, in
+    /// layout and in painting since the frame before, and the number of
+    /// widgets painted. The overlay is only updated when the window paints.
+    pub fn set_frame_stats_visible(&mut self, visible: bool) {
+        if self.layout_ctx.show_frame_stats != visible {
+            self.layout_ctx.show_frame_stats = visible;
+            self.layout_ctx.invalidate();
+            for window in self.windows.values() {
+                window.handle.invalidate();
+            }
+        }
+    }
+
+    /// Draw the text of the current window with `rendering`, such as with
+    /// grayscale antialiasing for a window whose content animates under
+    /// text. Text in bitmaps, such as those of layers and groups, and in
+    /// windows with a backdrop is drawn with grayscale antialiasing anyway.
+    ///
+    /// Only Direct2D windows apply the setting for now, and only its
+    /// antialiasing; see `druid_shell::text_rendering`.
+    pub fn set_text_rendering(&mut self, rendering: TextRendering) {
+        if self.layout_ctx.window.text_rendering != rendering {
+            self.layout_ctx.window.text_rendering = rendering;
+            self.layout_ctx.invalidate_layers();
+            self.layout_ctx.invalidate();
+        }
+    }
+
+    /// The system's accent color, if it has one.
+    pub fn accent_color(&self) -> Option<Color> {
+        self.layout_ctx.accent_color.clone()
+    }
+
+    /// Whether the user asked for less transparency, so that translucent
+    /// backgrounds should be drawn opaque.
+    pub fn reduce_transparency(&self) -> bool {
+        self.layout_ctx.reduce_transparency
+    }
+
+    /// Whether the computer runs on battery.
+    pub fn power_source(&self) -> PowerSource {
+        self.layout_ctx.power_source
+    }
+
+    /// The size of text chosen in the accessibility settings, relative to
+    /// the default.
+    pub fn text_scale(&self) -> f64 {
+        self.layout_ctx.text_scale
+    }
+
+    /// Whether the current window is maximized, minimized or fullscreen.
+    pub fn window_state(&self) -> WindowState {
+        self.layout_ctx.window.window_state
+    }
+
+    /// The window currently handling an event.
+    pub fn window_id(&self) -> WindowId {
+        self.layout_ctx.window_id
+    }
+
+    /// The handle of another window, for example to position a new window
+    /// relative to it. Returns `None` if the window has been closed.
+    pub fn window_handle(&self, id: WindowId) -> Option<&WindowHandle> {
+        if id == self.layout_ctx.window_id {
+            Some(&self.layout_ctx.window.handle)
+        } else {
+            self.windows.get(&id).map(|window| &window.handle)
+        }
+    }
+
+    /// Open a new window showing the widget tree at `root`.
+    ///
+    /// The window shares this state with the other windows, so listeners can
+    /// reach widgets in any of them. It is built once the current event has
+    /// been handled.
+    pub fn new_window(
+        &mut self,
+        root: Id,
+        title: impl Into<String>,
+        menu: Option<Menu>,
+    ) -> WindowId {
+        let id = WindowId(self.next_window_id);
+        self.next_window_id += 1;
+        self.schedule_window(
+            root,
+            PendingWindow {
+                id,
+                title: title.into(),
+                menu,
+                palette: None,
+            },
+        )
+    }
+
+    /// Open a tool palette or inspector showing the widget tree at `root`,
+    /// owned by the current window.
+    ///
+    /// The palette floats above the current window without taking activation
+    /// away from it, and is hidden and shown along with it. `position` is
+    /// relative to the top left corner of the current window.
+    pub fn new_palette(
+        &mut self,
+        root: Id,
+        title: impl Into<String>,
+        position: impl Into<Point>,
+        size: impl Into<Size>,
+    ) -> WindowId {
+        let id = WindowId(self.next_window_id);
+        self.next_window_id += 1;
+        let owner = self.layout_ctx.window.handle.clone();
+        self.schedule_window(
+            root,
+            PendingWindow {
+                id,
+                title: title.into(),
+                menu: None,
+                palette: Some((owner, position.into(), size.into())),
+            },
+        )
+    }
+
+    fn schedule_window(&mut self, root: Id, pending: PendingWindow) -> WindowId {
+        let id = pending.id;
+        self.windows.insert(id, PerWindowState::new(root));
+        self.pending_windows.push(pending);
+        // Building a window sends it events right away, so this can't be
+        // done while the state is borrowed.
+        if let Some(idle) = self.layout_ctx.window.handle.get_idle_handle() {
+            idle.add_idle(|handler| {
+                if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                    ui_main.build_pending_windows();
+                }
+            });
+        }
+        id
+    }
+
+    /// Make a window the current one, saving the state of the previous one.
+    fn enter_window(&mut self, id: WindowId) {
+        if id == self.layout_ctx.window_id {
+            return;
+        }
+        let window = match self.windows.remove(&id) {
+            Some(window) => window,
+            None => return,
+        };
+        let prev = mem::replace(&mut self.layout_ctx.window, window);
+        self.windows.insert(self.layout_ctx.window_id, prev);
+        self.layout_ctx.window_id = id;
+        self.graph.root = self.layout_ctx.window.root;
+    }
+
+    /// The window containing a widget, if it is in one.
+    fn window_of(&self, mut node: Id) -> Option<WindowId> {
+        loop {
+            let parent = self.graph.parent[node];
+            if parent == node {
+                break;
+            }
+            node = parent;
+        }
+        if node == self.layout_ctx.window.root {
+            return Some(self.layout_ctx.window_id);
+        }
+        self.windows
+            .iter()
+            .find(|(_, w)| w.root == node)
+            .map(|(&id, _)| id)
+    }
+
+    /// Set the focused widget.
+    ///
+    /// The change takes effect after the current event has been handled, at
+    /// which point the widgets losing and gaining focus are notified.
+    pub fn set_focus(&mut self, node: Option<Id>) {
+        self.layout_ctx.window.focus_req = Some(match node {
+            Some(node) => FocusChange::Focus(node),
+            None => FocusChange::Clear,
+        });
+    }
+
+    /// The widgets that accept focus, in tab order.
+    ///
+    /// This is a pre-order traversal of the widget tree, so focus order follows
+    /// the order in which children are added.
+    pub fn focus_chain(&self) -> Vec<Id> {
+        fn focus_chain_rec(
+            widgets: &[Box<dyn Widget>],
+            graph: &Graph,
+            node: Id,
+            chain: &mut Vec<Id>,
+        ) {
+            if widgets[node].accepts_focus() {
+                chain.push(node);
+            }
+            for &child in &graph.children[node] {
+                focus_chain_rec(widgets, graph, child, chain);
+            }
+        }
+
+        let mut chain = Vec::new();
+        if !self.widgets.is_empty() {
+            focus_chain_rec(&self.widgets, &self.graph, self.graph.root, &mut chain);
         }
-        id
+        chain
     }
 
-    pub fn set_root(&mut self, root: Id) {
-        self.graph.root = root;
+    /// The widgets of the current window as described to assistive
+    /// technology, as of the last layout; the ids of the nodes are those of
+    /// the widgets. The tree is given to the window after each layout.
+    pub fn accessibility_tree(&self) -> AccessTree {
+        let root = self.graph.root;
+        let mut tree = AccessTree {
+            nodes: Vec::new(),
+            root: root as NodeId,
+            focus: self.layout_ctx.window.focused.map(|id| id as NodeId),
+        };
+        if !self.widgets.is_empty() {
+            self.add_access_node(root, Vec2::default(), &mut tree);
+            if tree.nodes[0].1.role == Role::Group {
+                tree.nodes[0].1.role = Role::Window;
+            }
+        }
+        tree
     }
 
-    /// Set the focused widget.
-    pub fn set_focus(&mut self, node: Option<Id>) {
-        self.layout_ctx.focused = node;
+    /// Add `node` and its children to `tree`, with `offset` the origin of
+    /// its parent in the window.
+    fn add_access_node(&self, node: Id, offset: Vec2, tree: &mut AccessTree) {
+        let bounds = self.layout_ctx.geom[node] + offset;
+        let mut access_node = AccessNode::default();
+        self.widgets[node].accessibility(&mut access_node);
+        access_node.bounds = bounds;
+        access_node.children = self.graph.children[node]
+            .iter()
+            .map(|&child| child as NodeId)
+            .collect();
+        tree.nodes.push((node as NodeId, access_node));
+        for &child in &self.graph.children[node] {
+            self.add_access_node(child, bounds.origin().to_vec2(), tree);
+        }
     }
 
     /// Add a listener that expects a specific type.
@@ -609,80 +2764,299 @@ impl Ui {
     ///
     /// Can panic if child is not a valid child. Deletes the subtree rooted at
     /// the child, drops those widgets, and clears all listeners.
-
+    ///
     /// The id of the child may be reused; callers should take care not to use the
     /// child id in any way afterwards.
     pub fn delete_child(&mut self, node: Id, child: Id) {
+        self.delete_subtree(child);
+        self.remove_child(node, child);
+        self.graph.free_subtree(child);
+    }
+
+    /// Drop the widgets of a subtree and clear their listeners, and forget
+    /// them in the state of every window, leaving the graph untouched.
+    fn delete_subtree(&mut self, node: Id) {
         fn delete_rec(
             widgets: &mut [Box<dyn Widget>],
             q: &mut Vec<Event>,
             graph: &Graph,
             node: Id,
+            deleted: &mut Vec<Id>,
         ) {
             widgets[node] = Box::new(NullWidget);
             q.push(Event::ClearListeners(node));
+            deleted.push(node);
             for &child in &graph.children[node] {
-                delete_rec(widgets, q, graph, child);
+                delete_rec(widgets, q, graph, child, deleted);
             }
         }
+        let mut deleted = Vec::new();
         delete_rec(
             &mut self.widgets,
             &mut self.layout_ctx.event_q,
             &self.graph,
-            child,
+            node,
+            &mut deleted,
         );
-        self.remove_child(node, child);
-        self.graph.free_subtree(child);
+        // The ids may be reused, and a new widget mustn't inherit the focus.
+        self.layout_ctx.window.forget_widgets(&deleted);
+        for window in self.windows.values_mut() {
+            window.forget_widgets(&deleted);
+        }
     }
 
     // The following methods are really UiState methods, but don't need access to listeners
     // so are more concise to implement here.
 
     fn paint(&mut self, render_ctx: &mut Piet, root: Id) {
-        // Do pre-order traversal on graph, painting each node in turn.
-        //
-        // Implemented as a recursion, but we could use an explicit queue instead.
-        fn paint_rec(
-            widgets: &mut [Box<dyn Widget>],
-            graph: &Graph,
-            geom: &[Rect],
-            paint_ctx: &mut PaintCtx,
-            node: Id,
-            pos: Point,
-            active: Option<Id>,
-            hot: Option<Id>,
-            focused: Option<Id>,
-        ) {
-            let g = geom[node] + pos.to_vec2();
-            paint_ctx.is_active = active == Some(node);
-            paint_ctx.is_hot = hot == Some(node) && (paint_ctx.is_active || active.is_none());
-            paint_ctx.is_focused = focused == Some(node);
-            widgets[node].paint(paint_ctx, &g);
-            for &child in &graph.children[node] {
-                let pos = g.origin();
-                paint_rec(
-                    widgets, graph, geom, paint_ctx, child, pos, active, hot, focused,
-                );
-            }
+        let text_rendering = self.layout_ctx.window.text_rendering;
+        // Subpixel antialiasing fringes where the window shows through.
+        if self.layout_ctx.window.handle.backdrop() == WindowBackdrop::None {
+            text_rendering::set_text_rendering(render_ctx, text_rendering);
+        } else {
+            text_rendering::set_text_rendering(render_ctx, text_rendering.grayscale());
+        }
+        let mut paint_ctx = PaintCtx {
+            is_active: false,
+            is_hot: false,
+            is_focused: false,
+            theme: self.layout_ctx.theme,
+            env: self.layout_ctx.env.clone(),
+            accent_color: self.layout_ctx.accent_color.clone(),
+            reduce_transparency: self.layout_ctx.reduce_transparency,
+            text_scale: self.layout_ctx.text_scale,
+            touch_mode: self.layout_ctx.touch_mode,
+            text_rendering,
+            scale: f64::from(self.layout_ctx.window.handle.get_dpi()) / 96.0,
+            visible: Rect::from_origin_size(Point::ORIGIN, self.layout_ctx.window.size),
+            saved: false,
+            overlays: Vec::new(),
+            images: Some(&mut self.layout_ctx.window.images),
+            brushes: Some(&mut self.layout_ctx.window.brushes),
+            recording: None,
+            render_ctx,
+        };
+        let mut tree = PaintTree {
+            graph: &self.graph,
+            geom: &self.layout_ctx.geom,
+            per_widget: &mut self.layout_ctx.per_widget,
+            active: self.layout_ctx.window.active,
+            hot: self.layout_ctx.window.hot,
+            focused: self.layout_ctx.window.focused,
+            scale: f64::from(self.layout_ctx.window.handle.get_dpi()) / 96.0,
+            in_layer: false,
+            recording: false,
+            painted: 0,
+        };
+        tree.paint(&mut self.widgets, &mut paint_ctx, root, Point::ORIGIN);
+        self.layout_ctx.window.stats.widgets_painted += tree.painted;
+        paint_ctx.paint_overlays();
+
+        if let Some(DragState {
+            image: Some(ref mut image),
+            pos: Some(pos),
+            ..
+        }) = self.layout_ctx.window.drag
+        {
+            paint_ctx.with_save(|paint_ctx| {
+                paint_ctx.transform(Affine::translate(pos.to_vec2()));
+                image(paint_ctx);
+            });
         }
+        self.layout_ctx.window.brushes.end_frame();
+    }
+
+    /// Lay out a widget and its children at `size`, and paint them into a
+    /// bitmap with `scale` pixels per px, for thumbnails, exporting, or
+    /// comparing against reference images in tests. The widget may be in a
+    /// window, which lays it out again when it next paints, or not.
+    pub fn render_to_image(
+        &mut self,
+        node: Id,
+        size: Size,
+        scale: f64,
+    ) -> Result<RgbaImage, Error> {
+        self.layout(&BoxConstraints::tight(size), node);
+        let width = (size.width * scale).ceil().max(1.0) as usize;
+        let height = (size.height * scale).ceil().max(1.0) as usize;
+        let text_rendering = self.layout_ctx.window.text_rendering;
+        let image = bitmap::render_to_image(width, height, scale, |render_ctx| {
+            text_rendering::set_text_rendering(render_ctx, text_rendering.grayscale());
+            self.paint_detached(node, size, scale, render_ctx);
+        });
+        image.map_err(Error::ShellError)
+    }
+
+    /// Lay out a widget and its children at `size`, and paint them into a
+    /// vector document at `path`, for printing or for diagrams that stay
+    /// sharp at any size. As with `render_to_image`, the widget may be in a
+    /// window or not.
+    ///
+    /// Painting that has no vector form, such as blurs and some blend modes,
+    /// is written as bitmaps.
+    ///
+    /// Documents can only be written on Linux and macOS. On Windows and on
+    /// the web, this always returns an error and leaves `path` alone.
+    pub fn render_to_file(
+        &mut self,
+        node: Id,
+        size: Size,
+        path: &Path,
+        format: VectorFormat,
+    ) -> Result<(), Error> {
+        self.layout(&BoxConstraints::tight(size), node);
+        // The resolution of bitmaps in the document, such as of groups and
+        // shadows, about that of a printer.
+        let scale = 3.0;
+        let result = vector::render_to_file(path, format, size.width, size.height, |render_ctx| {
+            self.paint_detached(node, size, scale, render_ctx);
+        });
+        result.map_err(Error::ShellError)
+    }
 
+    /// Paint a widget laid out at `size` into `render_ctx`, outside of any
+    /// window, with its origin at that of the context.
+    fn paint_detached(&mut self, node: Id, size: Size, scale: f64, render_ctx: &mut Piet) {
+        let layout_ctx = &mut self.layout_ctx;
+        let (theme, accent_color) = (layout_ctx.theme, layout_ctx.accent_color.clone());
+        let env = layout_ctx.env.clone();
+        let reduce_transparency = layout_ctx.reduce_transparency;
+        let (text_scale, touch_mode) = (layout_ctx.text_scale, layout_ctx.touch_mode);
+        let text_rendering = layout_ctx.window.text_rendering;
+        let widgets = &mut self.widgets;
+        let mut tree = PaintTree {
+            graph: &self.graph,
+            geom: &layout_ctx.geom,
+            per_widget: &mut layout_ctx.per_widget,
+            active: layout_ctx.window.active,
+            hot: layout_ctx.window.hot,
+            focused: layout_ctx.window.focused,
+            scale,
+            // Layers are painted directly, as inside other layers.
+            in_layer: true,
+            recording: false,
+            painted: 0,
+        };
+        let origin = Point::ORIGIN - tree.geom[node].origin().to_vec2();
         let mut paint_ctx = PaintCtx {
             is_active: false,
             is_hot: false,
             is_focused: false,
+            theme,
+            env,
+            accent_color,
+            reduce_transparency,
+            text_scale,
+            touch_mode,
+            text_rendering,
+            scale,
+            visible: Rect::from_origin_size(Point::ORIGIN, size),
+            saved: false,
+            overlays: Vec::new(),
+            images: None,
+            brushes: None,
+            recording: None,
             render_ctx,
         };
-        paint_rec(
-            &mut self.widgets,
-            &self.graph,
-            &self.layout_ctx.geom,
-            &mut paint_ctx,
-            root,
-            Point::ORIGIN,
-            self.layout_ctx.active,
-            self.layout_ctx.hot,
-            self.layout_ctx.focused,
-        );
+        tree.paint(widgets, &mut paint_ctx, node, origin);
+        paint_ctx.paint_overlays();
+    }
+
+    /// Cache the painting of a widget and its children in a bitmap, which
+    /// is drawn instead of painting them until one of them invalidates, or
+    /// the widget changes size. This helps for complex content that rarely
+    /// changes, such as maps or large paths, when other widgets animate.
+    ///
+    /// Widgets in the layer must invalidate for every change of their
+    /// appearance, including on hot and focus changes, since they are not
+    /// painted otherwise. Overlays they paint are clipped to the layer.
+    pub fn set_cached(&mut self, node: Id, cached: bool) {
+        let layer = &mut self.layout_ctx.per_widget[node].layer;
+        if cached != layer.is_some() {
+            *layer = if cached { Some(Layer::default()) } else { None };
+            self.layout_ctx.invalidate();
+        }
+    }
+
+    /// Record the painting of a widget and its children in a display list,
+    /// which is replayed instead of calling their `paint` until one of them
+    /// invalidates, or the widget moves or changes size. Unlike `set_cached`,
+    /// nothing is rasterized, so the list takes little memory and stays
+    /// sharp under transforms.
+    ///
+    /// Only painting through the methods of `PaintCtx` that take colors,
+    /// gradients and images is recorded, and widgets must not draw with
+    /// `render_ctx` directly. Painting with `stroke`, `paint_overlay`,
+    /// groups or layers can't be recorded, and the widgets are painted as
+    /// usual while they do. Widgets must invalidate for every change of
+    /// their appearance, as for `set_cached`.
+    pub fn set_retained(&mut self, node: Id, retained: bool) {
+        let display_list = &mut self.layout_ctx.per_widget[node].display_list;
+        if retained != display_list.is_some() {
+            *display_list = if retained {
+                Some(DisplayList::default())
+            } else {
+                None
+            };
+            self.layout_ctx.invalidate();
+        }
+    }
+
+    /// Clip a widget and its children to `clip`, in the coordinates of the
+    /// widget with its origin at the top left corner, or stop clipping them
+    /// with `None`. Points outside the shape don't hit the widget, so mouse,
+    /// touch and pen events there go to the widgets under it, as for a
+    /// circular avatar or a shaped button.
+    pub fn set_clip(&mut self, node: Id, clip: Option<BezPath>) {
+        self.layout_ctx.per_widget[node].clip = clip;
+        self.layout_ctx.per_widget[node].invalidated = true;
+        self.layout_ctx.invalidate();
+    }
+
+    /// Draw a widget and its children as a group with `opacity`, from 0 for
+    /// transparent to 1, so that the children don't show through each other
+    /// as they fade.
+    ///
+    /// The group is painted into a bitmap every time it is painted, unless it
+    /// is cached with `set_cached`.
+    pub fn set_opacity(&mut self, node: Id, opacity: f64) {
+        let blend_mode = self.compositing(node).blend_mode;
+        self.set_compositing(node, opacity.clamp(0.0, 1.0), blend_mode);
+    }
+
+    /// Blend a widget and its children as a group into what is painted
+    /// under them, as described for `set_opacity`.
+    ///
+    /// Windows only blends normally; see `druid_shell::blend`.
+    pub fn set_blend_mode(&mut self, node: Id, blend_mode: BlendMode) {
+        let opacity = self.compositing(node).opacity;
+        self.set_compositing(node, opacity, blend_mode);
+    }
+
+    fn compositing(&self, node: Id) -> Compositing {
+        self.layout_ctx.per_widget[node]
+            .compositing
+            .unwrap_or(Compositing {
+                opacity: 1.0,
+                blend_mode: BlendMode::Normal,
+            })
+    }
+
+    fn set_compositing(&mut self, node: Id, opacity: f64, blend_mode: BlendMode) {
+        let per_widget = &mut self.layout_ctx.per_widget[node];
+        per_widget.compositing = if opacity < 1.0 || blend_mode != BlendMode::Normal {
+            Some(Compositing {
+                opacity,
+                blend_mode,
+            })
+        } else {
+            None
+        };
+        // The opacity is applied to the pixels of a cached layer.
+        if let Some(ref mut layer) = per_widget.layer {
+            layer.image = None;
+        }
+        self.layout_ctx.invalidate();
     }
 
     fn layout(&mut self, bc: &BoxConstraints, root: Id) {
@@ -698,7 +3072,15 @@ impl Ui {
                 let layout_res = widgets[node].layout(bc, &graph.children[node], size, ctx);
                 match layout_res {
                     LayoutResult::Size(size) => {
+                        let old_size = ctx.geom[node].size();
                         ctx.geom[node] = ctx.geom[node].with_size(size);
+                        if size != old_size {
+                            let mut handler_ctx = HandlerCtx {
+                                id: node,
+                                layout_ctx: ctx,
+                            };
+                            widgets[node].lifecycle(&LifeCycle::Size(size), &mut handler_ctx);
+                        }
                         return size;
                     }
                     LayoutResult::RequestChild(child, child_bc) => {
@@ -745,7 +3127,348 @@ impl BoxConstraints {
     }
 }
 
+impl PerWindowState {
+    fn new(root: Id) -> PerWindowState {
+        PerWindowState {
+            handle: Default::default(),
+            root,
+            anim_state: AnimState::Idle,
+            prev_paint_time: None,
+            stats: Default::default(),
+            focused: None,
+            focus_req: None,
+            active: None,
+            hot: None,
+            drag: None,
+            drop_command: None,
+            touches: BTreeMap::new(),
+            pen_target: None,
+            gestures: Default::default(),
+            gesture_target: None,
+            system_drag_source: None,
+            timers: HashMap::new(),
+            cursor: None,
+            window_state: WindowState::Restored,
+            tray_command: None,
+            size: Size::ZERO,
+            images: Default::default(),
+            brushes: Default::default(),
+            text_rendering: Default::default(),
+            composition: None,
+            access_tree: None,
+        }
+    }
+
+    /// Clear every reference to the deleted widgets `ids`.
+    fn forget_widgets(&mut self, ids: &[Id]) {
+        let deleted = |id: &Option<Id>| id.is_some_and(|id| ids.contains(&id));
+        for id in [
+            &mut self.focused,
+            &mut self.active,
+            &mut self.hot,
+            &mut self.pen_target,
+            &mut self.gesture_target,
+            &mut self.system_drag_source,
+        ] {
+            if deleted(id) {
+                *id = None;
+            }
+        }
+        match self.focus_req {
+            Some(FocusChange::Focus(id)) | Some(FocusChange::Resign(id)) if ids.contains(&id) => {
+                self.focus_req = None;
+            }
+            _ => (),
+        }
+        self.touches.retain(|_, id| !ids.contains(id));
+    }
+}
+
+impl<'a> PaintTree<'a> {
+    /// Paint `node` and its children, in pre-order, with `pos` the origin of
+    /// its parent.
+    fn paint(
+        &mut self,
+        widgets: &mut [Box<dyn Widget>],
+        paint_ctx: &mut PaintCtx,
+        node: Id,
+        pos: Point,
+    ) {
+        let g = self.geom[node] + pos.to_vec2();
+        let visible = paint_ctx.visible;
+        if g.x1 < visible.x0 || g.x0 > visible.x1 || g.y1 < visible.y0 || g.y0 > visible.y1 {
+            return;
+        }
+        let per_widget = &self.per_widget[node];
+        if per_widget.compositing.is_some() || (!self.in_layer && per_widget.layer.is_some()) {
+            self.paint_layer(widgets, paint_ctx, node, pos);
+        } else if !self.recording && per_widget.display_list.is_some() {
+            self.paint_retained(widgets, paint_ctx, node, pos);
+        } else {
+            self.paint_widget(widgets, paint_ctx, node, pos);
+        }
+    }
+
+    /// Paint `node` and its children, outside of any layer of its own.
+    fn paint_widget(
+        &mut self,
+        widgets: &mut [Box<dyn Widget>],
+        paint_ctx: &mut PaintCtx,
+        node: Id,
+        pos: Point,
+    ) {
+        let g = self.geom[node] + pos.to_vec2();
+        self.per_widget[node].invalidated = false;
+        paint_ctx.is_active = self.active == Some(node);
+        // Without hover, only a pressed widget is hot.
+        let hover = self.active.is_none() && !paint_ctx.touch_mode;
+        paint_ctx.is_hot = self.hot == Some(node) && (paint_ctx.is_active || hover);
+        paint_ctx.is_focused = self.focused == Some(node);
+        // A clip or transform set in `paint` applies to the children too.
+        let scope = paint_ctx.begin_scope();
+        if let Some(ref clip) = self.per_widget[node].clip {
+            paint_ctx.clip(Affine::translate(g.origin().to_vec2()) * clip);
+        }
+        widgets[node].paint(paint_ctx, &g);
+        self.painted += 1;
+        for &child in &self.graph.children[node] {
+            self.paint(widgets, paint_ctx, child, g.origin());
+        }
+        paint_ctx.end_scope(scope);
+    }
+
+    /// Draw the bitmap of a layer, painting it again first if it is stale,
+    /// or, for a group that isn't cached, always.
+    fn paint_layer(
+        &mut self,
+        widgets: &mut [Box<dyn Widget>],
+        paint_ctx: &mut PaintCtx,
+        node: Id,
+        pos: Point,
+    ) {
+        // The bitmap can't be kept in a display list.
+        paint_ctx.record_unsupported();
+        let g = self.geom[node] + pos.to_vec2();
+        let width = (g.width() * self.scale).ceil().max(1.0) as usize;
+        let height = (g.height() * self.scale).ceil().max(1.0) as usize;
+        let compositing = self.per_widget[node].compositing;
+        let opacity = compositing.map_or(1.0, |c| c.opacity);
+        let blend_mode = compositing.map_or(BlendMode::Normal, |c| c.blend_mode);
+        let cached = !self.in_layer && self.per_widget[node].layer.is_some();
+        let mut layer = match self.per_widget[node].layer.take() {
+            Some(layer) if cached => layer,
+            layer => {
+                self.per_widget[node].layer = layer;
+                Layer::default()
+            }
+        };
+        if layer.image.is_none() || layer.size != (width, height) || self.is_invalidated(node) {
+            // The bitmap has the origin of the widget at its top left.
+            let origin = Point::ORIGIN - self.geom[node].origin().to_vec2();
+            let in_layer = mem::replace(&mut self.in_layer, true);
+            let pixels = bitmap::render_to_pixels(width, height, self.scale, |render_ctx| {
+                let mut layer_ctx = paint_ctx.with_render_ctx(render_ctx);
+                // The whole layer is cached, including what is out of view.
+                layer_ctx.visible = Rect::from_origin_size(Point::ORIGIN, self.geom[node].size());
+                self.paint_widget(widgets, &mut layer_ctx, node, origin);
+                layer_ctx.paint_overlays();
+            });
+            layer.image = pixels.ok().and_then(|mut pixels| {
+                apply_opacity(&mut pixels, opacity);
+                paint_ctx
+                    .render_ctx
+                    .make_image(width, height, &pixels, ImageFormat::RgbaPremul)
+                    .ok()
+            });
+            layer.size = (width, height);
+            // Without a bitmap, the layer is painted like any widget.
+            if layer.image.is_none() {
+                self.paint_widget(widgets, paint_ctx, node, pos);
+            }
+            self.in_layer = in_layer;
+        }
+        if let Some(ref image) = layer.image {
+            let size = Size::new(width as f64 / self.scale, height as f64 / self.scale);
+            draw_blended(paint_ctx.render_ctx, image, g.with_size(size), blend_mode);
+        }
+        if cached {
+            self.per_widget[node].layer = Some(layer);
+        }
+    }
+
+    /// Replay the display list of a retained widget, recording it again
+    /// first if it is stale.
+    fn paint_retained(
+        &mut self,
+        widgets: &mut [Box<dyn Widget>],
+        paint_ctx: &mut PaintCtx,
+        node: Id,
+        pos: Point,
+    ) {
+        let g = self.geom[node] + pos.to_vec2();
+        let list = self.per_widget[node]
+            .display_list
+            .take()
+            .unwrap_or_default();
+        let list = if list.is_current(g, paint_ctx.scale) && !self.is_invalidated(node) {
+            list.replay(paint_ctx);
+            list
+        } else {
+            // The whole widget is recorded, including what is out of view.
+            let visible = mem::replace(&mut paint_ctx.visible, g);
+            paint_ctx.recording = Some(DisplayList::new(g, paint_ctx.scale));
+            self.recording = true;
+            self.paint_widget(widgets, paint_ctx, node, pos);
+            self.recording = false;
+            paint_ctx.visible = visible;
+            paint_ctx.recording.take().unwrap_or_default()
+        };
+        self.per_widget[node].display_list = Some(list);
+    }
+
+    /// Whether `node` or any of its descendants invalidated.
+    fn is_invalidated(&self, node: Id) -> bool {
+        self.per_widget[node].invalidated
+            || self.graph.children[node]
+                .iter()
+                .any(|&child| self.is_invalidated(child))
+    }
+}
+
+/// `shape` as a path, for recording.
+fn to_path(shape: &impl Shape) -> BezPath {
+    BezPath::from_vec(shape.to_bez_path(0.1).collect())
+}
+
+/// The bounds of `rect` in the coordinates before `transform`, or everything
+/// if the transform can't be inverted.
+fn inverse_transform_rect(transform: Affine, rect: Rect) -> Rect {
+    let [a, b, c, d, e, f] = transform.as_coeffs();
+    let det = a * d - b * c;
+    if det == 0.0 || !rect.x0.is_finite() || !rect.y0.is_finite() {
+        let inf = f64::INFINITY;
+        return Rect::new(-inf, -inf, inf, inf);
+    }
+    let inverse = Affine::new([
+        d / det,
+        -b / det,
+        -c / det,
+        a / det,
+        (c * f - d * e) / det,
+        (b * e - a * f) / det,
+    ]);
+    let corners = [
+        Point::new(rect.x0, rect.y0),
+        Point::new(rect.x1, rect.y0),
+        Point::new(rect.x0, rect.y1),
+        Point::new(rect.x1, rect.y1),
+    ];
+    let first = inverse * corners[0];
+    corners[1..]
+        .iter()
+        .fold(Rect::from_points(first, first), |bounds, &corner| {
+            bounds.union_pt(inverse * corner)
+        })
+}
+
+/// The coordinate `offset` pixels past the pixel boundary nearest to `v`,
+/// with `scale` pixels per px.
+fn snap_to_grid(v: f64, scale: f64, offset: f64) -> f64 {
+    ((v * scale - offset).round() + offset) / scale
+}
+
+/// Scale the premultiplied RGBA `pixels` by `opacity`.
+fn apply_opacity(pixels: &mut [u8], opacity: f64) {
+    if opacity < 1.0 {
+        for c in pixels {
+            *c = (f64::from(*c) * opacity).round() as u8;
+        }
+    }
+}
+
+/// Draw `piet_image` stretched to `rect`, blended with `blend_mode`.
+fn draw_blended(
+    render_ctx: &mut Piet,
+    piet_image: &<Piet<'static> as RenderContext>::Image,
+    rect: Rect,
+    blend_mode: BlendMode,
+) {
+    if blend_mode == BlendMode::Normal {
+        render_ctx.draw_image(piet_image, rect, InterpolationMode::Bilinear);
+    } else {
+        let _ = render_ctx.save();
+        blend::set_blend_mode(render_ctx, blend_mode);
+        render_ctx.draw_image(piet_image, rect, InterpolationMode::Bilinear);
+        let _ = render_ctx.restore();
+    }
+}
+
+/// Draw `piet_image` stretched to `rect`, showing only the part inside
+/// `clip`.
+fn draw_clipped(
+    render_ctx: &mut Piet,
+    piet_image: &<Piet<'static> as RenderContext>::Image,
+    rect: Rect,
+    clip: Rect,
+    interp: InterpolationMode,
+) {
+    let _ = render_ctx.save();
+    render_ctx.clip(clip, FillRule::NonZero);
+    render_ctx.draw_image(piet_image, rect, interp);
+    let _ = render_ctx.restore();
+}
+
 impl LayoutCtx {
+    /// Whether `pos`, relative to the origin of `node`, is on the widget: in
+    /// its rectangle grown by `slop` on each side, and in its clip shape if
+    /// it has one.
+    fn hits(&self, node: Id, pos: Point, slop: f64) -> bool {
+        let Size { width, height } = self.geom[node].size();
+        let in_rect =
+            pos.x >= -slop && pos.y >= -slop && pos.x < width + slop && pos.y < height + slop;
+        in_rect
+            && self.per_widget[node]
+                .clip
+                .as_ref()
+                .map_or(true, |clip| clip.winding(pos) != 0)
+    }
+
+    /// Call `f` with the window handle from an idle callback, for changes
+    /// that the platform may report with a resize while the state is still
+    /// borrowed.
+    fn with_window_later<F>(&self, f: F)
+    where
+        F: FnOnce(&WindowHandle) + Send + 'static,
+    {
+        if let Some(idle) = self.window.handle.get_idle_handle() {
+            idle.add_idle(move |handler| {
+                if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                    let handle = ui_main.state().layout_ctx.window.handle.clone();
+                    f(&handle);
+                }
+            });
+        }
+    }
+
+    fn request_window_state(&self, state: WindowState) {
+        self.with_window_later(move |handle| match state {
+            WindowState::Restored => handle.restore(),
+            WindowState::Maximized => handle.maximize(),
+            WindowState::Minimized => handle.minimize(),
+            WindowState::Fullscreen => handle.set_fullscreen(true),
+        });
+    }
+
+    /// Set the active widget, capturing the mouse while there is one.
+    fn set_active(&mut self, active: Option<Id>) {
+        match (self.window.active, active) {
+            (None, Some(_)) => self.window.handle.capture_mouse(),
+            (Some(_), None) => self.window.handle.release_mouse(),
+            _ => (),
+        }
+        self.window.active = active;
+    }
+
     pub fn position_child(&mut self, child: Id, pos: impl Into<Point>) {
         self.geom[child] = self.geom[child].with_origin(pos.into());
     }
@@ -754,85 +3477,323 @@ impl LayoutCtx {
         self.geom[child].size()
     }
 
+    /// The handle of the window being laid out.
+    pub fn window(&self) -> &WindowHandle {
+        &self.window.handle
+    }
+
+    /// The factor to scale font sizes, and the paddings around text, by.
+    pub fn text_scale(&self) -> f64 {
+        self.text_scale
+    }
+
+    /// Whether the UI is laid out for touch; see `Ui::set_touch_mode`.
+    pub fn touch_mode(&self) -> bool {
+        self.touch_mode
+    }
+
+    /// The values of the theme, and any others set by the app.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    /// Replace the environment, such as for a `Styled` widget, returning
+    /// the one replaced.
+    pub(crate) fn replace_env(&mut self, env: Env) -> Env {
+        std::mem::replace(&mut self.env, env)
+    }
+
+    /// Drop the bitmaps of all layers and the display lists of retained
+    /// widgets, for changes that affect all widgets.
+    fn invalidate_layers(&mut self) {
+        for per_widget in &mut self.per_widget {
+            if let Some(ref mut layer) = per_widget.layer {
+                layer.image = None;
+            }
+            if per_widget.display_list.is_some() {
+                per_widget.display_list = Some(DisplayList::default());
+            }
+        }
+    }
+
     /// Internal logic for widget invalidation.
     fn invalidate(&mut self) {
-        match self.anim_state {
+        match self.window.anim_state {
             AnimState::Idle => {
-                self.handle.invalidate();
-                self.anim_state = AnimState::InvalidationRequested;
+                self.window.handle.invalidate();
+                self.window.anim_state = AnimState::InvalidationRequested;
             }
             _ => (),
         }
     }
 
-    fn request_layout(&mut self) {
-        self.invalidate();
+    fn request_layout(&mut self) {
+        self.invalidate();
+    }
+}
+
+impl<'a> HandlerCtx<'a> {
+    /// Invalidate this widget. Finer-grained invalidation is not yet implemented,
+    /// but when it is, this method will invalidate the widget's bounding box.
+    pub fn invalidate(&mut self) {
+        self.layout_ctx.per_widget[self.id].invalidated = true;
+        self.layout_ctx.invalidate();
+    }
+
+    /// Request layout; implies invalidation.
+    pub fn request_layout(&mut self) {
+        self.layout_ctx.request_layout();
+    }
+
+    /// Send an event, to be handled by listeners.
+    pub fn send_event<A: Any>(&mut self, a: A) {
+        self.layout_ctx
+            .event_q
+            .push(Event::Event(self.id, Box::new(a)));
+    }
+
+    /// Queue a command for the command listener.
+    pub fn submit_command(&mut self, cmd: u32, priority: CommandPriority) {
+        self.layout_ctx.commands.borrow_mut().push(cmd, priority);
+    }
+
+    /// Submit a notification to the widget's ancestors.
+    ///
+    /// After the current event is handled, the notification is offered to
+    /// the parent, then to its parent and so on, until a widget's
+    /// [`notification`] method returns true. This lets a reusable widget
+    /// inform whatever container it is placed in, without knowing about
+    /// app-level listeners or commands.
+    ///
+    /// [`notification`]: widget/trait.Widget.html#method.notification
+    pub fn submit_notification<N: Any>(&mut self, notification: N) {
+        self.layout_ctx
+            .event_q
+            .push(Event::Notification(self.id, Box::new(notification)));
+    }
+
+    /// Show a context menu at a position relative to this widget, typically
+    /// the position of a right click.
+    ///
+    /// The menu is shown after the current event has been handled. The chosen
+    /// item is delivered to the command listener.
+    pub fn show_context_menu(&mut self, menu: &MenuDesc, pos: impl Into<Point>) {
+        self.layout_ctx
+            .event_q
+            .push(Event::ContextMenu(self.id, menu.clone(), pos.into()));
+    }
+
+    /// Tell the input method where the caret of the widget is, relative to
+    /// the widget, so that its candidate window is shown next to it.
+    pub fn set_caret_rect(&mut self, rect: Rect) {
+        self.layout_ctx
+            .event_q
+            .push(Event::CaretRect(self.id, rect));
+    }
+
+    /// Set or unset the widget as active.
+    ///
+    /// While a widget is active, it receives all mouse move and mouse up
+    /// events, even when the pointer leaves the widget or the window; the
+    /// positions may then be outside its bounds.
+    pub fn set_active(&mut self, active: bool) {
+        self.layout_ctx
+            .set_active(if active { Some(self.id) } else { None });
+    }
+
+    /// Start a drag-and-drop session carrying `payload`.
+    ///
+    /// This is usually called from `mouse_moved` while the widget is active.
+    /// Until the mouse button is released, mouse events are consumed by the
+    /// session and delivered to drop targets through `Widget::drag`.
+    pub fn start_drag<A: Any>(&mut self, payload: A) {
+        self.layout_ctx.window.drag = Some(DragState {
+            source: Some(self.id),
+            payload: Box::new(payload),
+            image: None,
+            pos: None,
+            target: None,
+        });
+    }
+
+    /// Resolve the drop being handled into a command.
+    ///
+    /// Call this from `Widget::drag` on `DragKind::Drop`. If the drop is
+    /// accepted, once the drag source has received `End`, `cmd` is submitted
+    /// like any other command, carrying the dragged payload, which the
+    /// command listener retrieves with `Ui::command_payload`.
+    pub fn set_drop_command(&mut self, cmd: u32, priority: CommandPriority) {
+        self.layout_ctx.window.drop_command = Some((cmd, priority));
+    }
+
+    /// Start a drag carrying `data` out of the application, so that it can
+    /// be dropped in other applications, such as files into a file manager.
+    ///
+    /// Like `start_drag`, this is called from `mouse_moved` while the widget
+    /// is active. The drag is run by the platform, and when it is over the
+    /// widget receives `Widget::system_drag_ended`.
+    pub fn start_system_drag(&mut self, data: DragData) {
+        self.layout_ctx
+            .event_q
+            .push(Event::SystemDrag(self.id, data));
+    }
+
+    /// Set the image shown under the pointer during the current drag.
+    ///
+    /// The closure is called each paint cycle, with the origin translated to
+    /// the pointer position.
+    pub fn set_drag_image<F>(&mut self, f: F)
+    where
+        F: FnMut(&mut PaintCtx) + 'static,
+    {
+        if let Some(ref mut drag) = self.layout_ctx.window.drag {
+            drag.image = Some(Box::new(f));
+        }
+    }
+
+    /// Determine whether a drag-and-drop session is in progress.
+    pub fn is_dragging(&self) -> bool {
+        self.layout_ctx.window.drag.is_some()
+    }
+
+    #[deprecated(note = "please use `request_focus` or `resign_focus`.")]
+    pub fn set_focused(&mut self, focused: bool) {
+        if focused {
+            self.request_focus();
+        } else {
+            self.resign_focus();
+        }
+    }
+
+    /// Request keyboard focus for this widget.
+    ///
+    /// The change takes effect after the current event has been handled; the
+    /// previously focused widget (if any) receives `LifeCycle::FocusChanged(false)`,
+    /// and this widget receives `LifeCycle::FocusChanged(true)`.
+    pub fn request_focus(&mut self) {
+        self.layout_ctx.window.focus_req = Some(FocusChange::Focus(self.id));
+    }
+
+    /// Give up keyboard focus, if this widget has it. Afterwards no widget is
+    /// focused.
+    pub fn resign_focus(&mut self) {
+        self.layout_ctx.window.focus_req = Some(FocusChange::Resign(self.id));
+    }
+
+    /// Move focus to the next widget in the focus chain.
+    ///
+    /// The focus chain consists of the widgets whose `accepts_focus` method
+    /// returns true, in tree order. Focus wraps around at the end.
+    pub fn focus_next(&mut self) {
+        self.layout_ctx.window.focus_req = Some(FocusChange::Next);
     }
-}
 
-impl<'a> HandlerCtx<'a> {
-    /// Invalidate this widget. Finer-grained invalidation is not yet implemented,
-    /// but when it is, this method will invalidate the widget's bounding box.
-    pub fn invalidate(&mut self) {
-        self.layout_ctx.invalidate();
+    /// Move focus to the previous widget in the focus chain.
+    pub fn focus_prev(&mut self) {
+        self.layout_ctx.window.focus_req = Some(FocusChange::Previous);
     }
 
-    /// Request layout; implies invalidation.
-    pub fn request_layout(&mut self) {
-        self.layout_ctx.request_layout();
+    /// The handle of the window containing this widget.
+    pub fn window(&self) -> &WindowHandle {
+        &self.layout_ctx.window.handle
     }
 
-    /// Send an event, to be handled by listeners.
-    pub fn send_event<A: Any>(&mut self, a: A) {
-        self.layout_ctx
-            .event_q
-            .push(Event::Event(self.id, Box::new(a)));
+    /// Have screen readers read out `text`, such as "file saved", wherever
+    /// the focus is.
+    pub fn announce(&self, text: &str, politeness: Politeness) {
+        self.layout_ctx.window.handle.announce(text, politeness);
     }
 
-    /// Set or unset the widget as active.
-    // TODO: this should call SetCapture/ReleaseCapture as well.
-    pub fn set_active(&mut self, active: bool) {
-        self.layout_ctx.active = if active { Some(self.id) } else { None };
+    /// Maximize, minimize, restore or make fullscreen the window, once the
+    /// current event has been handled.
+    pub fn set_window_state(&mut self, state: WindowState) {
+        self.layout_ctx.request_window_state(state);
     }
 
-    pub fn set_focused(&mut self, focused: bool) {
-        self.layout_ctx.focused = if focused { Some(self.id) } else { None };
+    /// Set the cursor while the pointer is over this widget.
+    ///
+    /// This is meant to be called from `mouse_moved`; the cursor reverts to
+    /// the arrow on any move for which it is not called again.
+    pub fn set_cursor(&mut self, cursor: &Cursor) {
+        self.layout_ctx.window.cursor = Some(cursor.clone());
+    }
+
+    /// Create a cursor from an image, for use with `set_cursor`.
+    pub fn make_cursor(&self, desc: &CursorDesc) -> Option<Cursor> {
+        self.layout_ctx.window.handle.make_cursor(desc)
+    }
+
+    /// The system clipboard.
+    pub fn clipboard(&self) -> Clipboard {
+        Application::clipboard()
     }
 
     /// Determine whether this widget is active.
     pub fn is_active(&self) -> bool {
-        self.layout_ctx.active == Some(self.id)
+        self.layout_ctx.window.active == Some(self.id)
     }
 
     /// Determine whether this widget is focused.
     pub fn is_focused(&self) -> bool {
-        self.layout_ctx.focused == Some(self.id)
+        self.layout_ctx.window.focused == Some(self.id)
+    }
+
+    /// Whether the UI is laid out for touch; see `Ui::set_touch_mode`.
+    pub fn touch_mode(&self) -> bool {
+        self.layout_ctx.touch_mode
+    }
+
+    /// The values of the theme, and any others set by the app.
+    pub fn env(&self) -> &Env {
+        &self.layout_ctx.env
+    }
+
+    pub(crate) fn replace_env(&mut self, env: Env) -> Env {
+        self.layout_ctx.replace_env(env)
+    }
+
+    /// The messages of `LocalizedString`s; see `Ui::localization`.
+    pub fn localization(&self) -> &Localization {
+        &self.layout_ctx.localization
     }
 
     /// Determine whether this widget is hot. A widget can be both hot and active, but
     /// if a widget is active, it is the only widget that can be hot.
     pub fn is_hot(&self) -> bool {
-        self.layout_ctx.hot == Some(self.id)
-            && (self.is_active() || self.layout_ctx.active.is_none())
+        self.layout_ctx.window.hot == Some(self.id)
+            && (self.is_active() || self.layout_ctx.window.active.is_none())
     }
 
     /// Request an animation frame.
     ///
     /// Calling this schedules an animation frame, and also causes `anim_frame` to be
-    /// called on this widget at the beginning of that frame.
+    /// called on this widget at the beginning of that frame, just before the
+    /// window is painted. Requests from all widgets until then share one frame,
+    /// timed by the display refresh.
     pub fn request_anim_frame(&mut self) {
         self.layout_ctx.per_widget[self.id].anim_frame_requested = true;
-        match self.layout_ctx.anim_state {
-            AnimState::Idle => {
-                self.invalidate();
+        self.layout_ctx.per_widget[self.id].invalidated = true;
+        match self.layout_ctx.window.anim_state {
+            AnimState::Idle | AnimState::InvalidationRequested => {
+                self.layout_ctx.window.handle.request_frame();
+                self.layout_ctx.window.anim_state = AnimState::FrameRequested;
             }
             AnimState::AnimFrameStart => {
-                self.layout_ctx.anim_state = AnimState::AnimFrameRequested;
+                self.layout_ctx.window.anim_state = AnimState::AnimFrameRequested;
             }
             _ => (),
         }
     }
 
+    /// Request a call to `Widget::timer` on this widget after `delay`, with
+    /// the returned token. Timers due within a millisecond of each other
+    /// fire together.
+    pub fn request_timer(&mut self, delay: Duration) -> TimerToken {
+        let token = self.layout_ctx.window.handle.request_timer(delay);
+        self.layout_ctx.window.timers.insert(token, self.id);
+        token
+    }
+
     pub fn get_geom(&self) -> &Rect {
         &self.layout_ctx.geom[self.id]
     }
@@ -872,7 +3833,7 @@ impl<'a> ListenerCtx<'a> {
 
     /// Request the window to be closed.
     pub fn close(&mut self) {
-        self.layout_ctx.handle.close();
+        self.layout_ctx.window.handle.close();
     }
 
     pub fn file_dialog(
@@ -880,9 +3841,26 @@ impl<'a> ListenerCtx<'a> {
         ty: FileDialogType,
         options: FileDialogOptions,
     ) -> Result<OsString, Error> {
-        let result = self.layout_ctx.handle.file_dialog(ty, options)?;
+        let result = self.layout_ctx.window.handle.file_dialog(ty, options)?;
         Ok(result)
     }
+
+    /// Register a system-wide hotkey.
+    ///
+    /// Pressing the key chord sends `command` to the command listener, even
+    /// when the window does not have focus.
+    pub fn register_hotkey(&mut self, command: u32, key: impl Into<MenuKey>) -> Result<(), Error> {
+        self.layout_ctx
+            .window
+            .handle
+            .register_hotkey(command, key.into())?;
+        Ok(())
+    }
+
+    /// Unregister a hotkey registered with `register_hotkey`.
+    pub fn unregister_hotkey(&mut self, command: u32) {
+        self.layout_ctx.window.handle.unregister_hotkey(command);
+    }
 }
 
 impl<'a, 'b> PaintCtx<'a, 'b> {
@@ -900,101 +3878,754 @@ impl<'a, 'b> PaintCtx<'a, 'b> {
     pub fn is_focused(&self) -> bool {
         self.is_focused
     }
+
+    /// The light or dark system theme, for widgets that follow it.
+    pub fn theme(&self) -> Theme {
+        self.theme
+    }
+
+    /// The values of the theme, and any others set by the app.
+    pub fn env(&self) -> &Env {
+        &self.env
+    }
+
+    pub(crate) fn replace_env(&mut self, env: Env) -> Env {
+        std::mem::replace(&mut self.env, env)
+    }
+
+    /// The system's accent color, if it has one.
+    pub fn accent_color(&self) -> Option<&Color> {
+        self.accent_color.as_ref()
+    }
+
+    /// Whether translucent backgrounds should be drawn opaque.
+    pub fn reduce_transparency(&self) -> bool {
+        self.reduce_transparency
+    }
+
+    /// The factor to scale font sizes, and the paddings around text, by.
+    pub fn text_scale(&self) -> f64 {
+        self.text_scale
+    }
+
+    /// Whether the UI is laid out for touch, so that hover-only affordances
+    /// should be left out.
+    pub fn touch_mode(&self) -> bool {
+        self.touch_mode
+    }
+
+    /// The number of physical pixels per px of the surface being painted.
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// `rect` with its edges moved to the nearest pixel boundaries, so that
+    /// it fills whole pixels.
+    ///
+    /// Snapping, here and below, assumes the painting is only translated by
+    /// whole pixels, as it is unless `transform` is called.
+    pub fn snap_rect(&self, rect: Rect) -> Rect {
+        let snap = |v| snap_to_grid(v, self.scale, 0.0);
+        Rect::new(snap(rect.x0), snap(rect.y0), snap(rect.x1), snap(rect.y1))
+    }
+
+    /// `width` rounded to a whole number of pixels, at least one.
+    pub fn snap_stroke_width(&self, width: f64) -> f64 {
+        (width * self.scale).round().max(1.0) / self.scale
+    }
+
+    /// `rect` moved so that stroking it `snap_stroke_width(width)` wide
+    /// covers whole pixels.
+    pub fn snap_stroke_rect(&self, rect: Rect, width: f64) -> Rect {
+        let offset = self.stroke_offset(width);
+        let snap = |v| snap_to_grid(v, self.scale, offset);
+        Rect::new(snap(rect.x0), snap(rect.y0), snap(rect.x1), snap(rect.y1))
+    }
+
+    /// `line` moved so that stroking it `snap_stroke_width(width)` wide
+    /// covers whole pixels, if it is horizontal or vertical.
+    pub fn snap_stroke_line(&self, line: Line, width: f64) -> Line {
+        let offset = self.stroke_offset(width);
+        let snap = |p: Point| {
+            Point::new(
+                snap_to_grid(p.x, self.scale, offset),
+                snap_to_grid(p.y, self.scale, offset),
+            )
+        };
+        Line::new(snap(line.p0), snap(line.p1))
+    }
+
+    /// Stroke the focus ring of the theme just inside `rect`, showing that
+    /// the widget has the keyboard focus.
+    pub fn paint_focus_ring(&mut self, rect: Rect) {
+        let width = self.snap_stroke_width(self.env.get(theme::FOCUS_RING_WIDTH));
+        let rect = self.snap_stroke_rect(rect.inflate(-width / 2.0, -width / 2.0), width);
+        let color = self.env.get(theme::FOCUS_RING_COLOR);
+        self.stroke_color(rect, &color, &Stroke::new(width));
+    }
+
+    /// Where the middle of a stroke of `width` falls in a pixel: at half a
+    /// pixel for an odd number of pixels, or between two for an even one.
+    fn stroke_offset(&self, width: f64) -> f64 {
+        let pixels = (width * self.scale).round().max(1.0);
+        if pixels % 2.0 == 1.0 {
+            0.5
+        } else {
+            0.0
+        }
+    }
+
+    /// The part of the window that can be seen through the clips of the
+    /// widget's ancestors, in the coordinates of its geometry, so that
+    /// widgets painting many items, such as the rows of a list, can skip
+    /// those out of view.
+    ///
+    /// Widgets out of view are not painted at all, nor are their children,
+    /// which are expected to be inside them.
+    pub fn visible_rect(&self) -> Rect {
+        self.visible
+    }
+
+    /// Call `f`, then undo any clip or transform it set, even if it panics.
+    pub fn with_save(&mut self, f: impl FnOnce(&mut PaintCtx)) {
+        self.record(|| Op::Begin);
+        let saved = mem::replace(&mut self.saved, true);
+        let visible = self.visible;
+        let _ = self.render_ctx.save();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| f(self)));
+        let _ = self.render_ctx.restore();
+        self.record(|| Op::End);
+        self.saved = saved;
+        self.visible = visible;
+        if let Err(payload) = result {
+            panic::resume_unwind(payload);
+        }
+    }
+
+    /// Clip painting to `shape`.
+    ///
+    /// Called from `Widget::paint`, the clip applies to the children of the
+    /// widget as well, until they are painted; inside `with_save`, until it
+    /// returns.
+    pub fn clip(&mut self, shape: impl Shape) {
+        self.record(|| Op::Clip(to_path(&shape)));
+        self.save_once();
+        self.visible = self.visible.intersect(shape.bounding_box());
+        self.render_ctx.clip(shape, FillRule::NonZero);
+    }
+
+    /// Transform the coordinates of the following painting, in the same
+    /// scope as `clip`. Children still get their geometry in the coordinates
+    /// of the window, and only their painting is transformed; mouse events
+    /// are not.
+    pub fn transform(&mut self, transform: Affine) {
+        self.record(|| Op::Transform(transform));
+        self.save_once();
+        self.visible = inverse_transform_rect(transform, self.visible);
+        self.render_ctx.transform(transform);
+    }
+
+    /// Paint with `f` above the rest of the window once the widget tree is
+    /// painted, unclipped and in the coordinates of the window, for content
+    /// such as dropdowns and tooltips that must not be covered by siblings
+    /// or clipped by ancestors. Overlays are painted in the order requested,
+    /// under the drag image.
+    ///
+    /// Only painting is affected; events still go to widgets by their
+    /// layout.
+    pub fn paint_overlay<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut PaintCtx) + 'static,
+    {
+        self.record_unsupported();
+        self.overlays.push(Box::new(f));
+    }
+
+    /// Paint with `f` as a group, into a bitmap covering `bounds` that is
+    /// then drawn with `opacity` and `blend_mode`, as for `Ui::set_opacity`
+    /// and `Ui::set_blend_mode`. What `f` paints outside `bounds` is lost.
+    pub fn with_group<F>(&mut self, bounds: Rect, opacity: f64, blend_mode: BlendMode, f: F)
+    where
+        F: FnOnce(&mut PaintCtx),
+    {
+        if opacity >= 1.0 && blend_mode == BlendMode::Normal {
+            self.with_save(f);
+            return;
+        }
+        self.record_unsupported();
+        let width = (bounds.width() * self.scale).ceil().max(1.0) as usize;
+        let height = (bounds.height() * self.scale).ceil().max(1.0) as usize;
+        let mut f = Some(f);
+        let pixels = bitmap::render_to_pixels(width, height, self.scale, |render_ctx| {
+            let mut group_ctx = self.with_render_ctx(render_ctx);
+            group_ctx.visible = self.visible.intersect(bounds);
+            group_ctx
+                .render_ctx
+                .transform(Affine::translate(Point::ORIGIN - bounds.origin()));
+            if let Some(f) = f.take() {
+                f(&mut group_ctx);
+            }
+            group_ctx.paint_overlays();
+        });
+        let image = pixels.ok().and_then(|mut pixels| {
+            apply_opacity(&mut pixels, opacity.max(0.0));
+            self.render_ctx
+                .make_image(width, height, &pixels, ImageFormat::RgbaPremul)
+                .ok()
+        });
+        match image {
+            Some(image) => {
+                let size = Size::new(width as f64 / self.scale, height as f64 / self.scale);
+                draw_blended(self.render_ctx, &image, bounds.with_size(size), blend_mode);
+            }
+            // Without a bitmap, the group is painted directly.
+            None => {
+                if let Some(f) = f.take() {
+                    self.with_save(f);
+                }
+            }
+        }
+    }
+
+    /// Stroke `shape` with `brush`, as `stroke` says.
+    pub fn stroke(
+        &mut self,
+        shape: impl Shape,
+        brush: &<Piet<'b> as RenderContext>::Brush,
+        stroke: &Stroke,
+    ) {
+        self.record_unsupported();
+        self.render_ctx
+            .stroke(shape, brush, stroke.width, Some(&stroke.style));
+    }
+
+    /// Fill `shape` with `color`.
+    ///
+    /// Unlike making a brush with `render_ctx`, this reuses the window's
+    /// brush for the color from earlier paints.
+    pub fn fill_color(&mut self, shape: impl Shape, color: &Color, fill_rule: FillRule) {
+        self.record(|| Op::FillColor(to_path(&shape), color.clone(), fill_rule));
+        self.with_brush(BrushSource::Solid(color), |render_ctx, brush| {
+            render_ctx.fill(shape, brush, fill_rule);
+        });
+    }
+
+    /// Stroke `shape` with `color`, as `stroke` says, reusing the brush for
+    /// the color like `fill_color`.
+    pub fn stroke_color(&mut self, shape: impl Shape, color: &Color, stroke: &Stroke) {
+        self.record(|| Op::StrokeColor(to_path(&shape), color.clone(), stroke.clone()));
+        self.with_brush(BrushSource::Solid(color), |render_ctx, brush| {
+            render_ctx.stroke(shape, brush, stroke.width, Some(&stroke.style));
+        });
+    }
+
+    /// Fill `shape` with `gradient`, reusing the brush for the gradient
+    /// like `fill_color`. An invalid gradient, such as one with a single
+    /// stop, paints nothing.
+    pub fn fill_gradient(&mut self, shape: impl Shape, gradient: &Gradient, fill_rule: FillRule) {
+        self.record(|| Op::FillGradient(to_path(&shape), gradient.clone(), fill_rule));
+        self.with_brush(BrushSource::Gradient(gradient), |render_ctx, brush| {
+            render_ctx.fill(shape, brush, fill_rule);
+        });
+    }
+
+    /// Call `f` with the brush for `source`, from the window's brushes if
+    /// painting into the window.
+    fn with_brush<F>(&mut self, source: BrushSource, f: F)
+    where
+        F: FnOnce(&mut Piet, &brush::Brush),
+    {
+        let made;
+        let brush = match self.brushes {
+            Some(ref mut brushes) => brushes.get(self.render_ctx, source),
+            None => {
+                made = brush::make_brush(self.render_ctx, source);
+                made.as_ref()
+            }
+        };
+        if let Some(brush) = brush {
+            f(self.render_ctx, brush);
+        }
+    }
+
+    /// Draw `layout` with its baseline starting at `pos`, reusing the brush
+    /// for `color` like `fill_color`. The layout is shared, rather than
+    /// borrowed, so that it can be kept in a display list.
+    pub fn draw_text(&mut self, layout: impl Into<Rc<PietTextLayout>>, pos: Point, color: &Color) {
+        let layout = layout.into();
+        self.with_brush(BrushSource::Solid(color), |render_ctx, brush| {
+            render_ctx.draw_text(&*layout, pos, brush);
+        });
+        self.record(|| Op::DrawText(layout, pos, color.clone()));
+    }
+
+    /// Draw the lines of `layout` with its top left corner at `origin`, in
+    /// `color` where its text has none.
+    pub fn draw_text_layout(&mut self, layout: &mut TextLayout, origin: Point, color: &Color) {
+        layout.rebuild_if_needed(self.render_ctx);
+        for op in layout.paint_ops(origin) {
+            match op {
+                TextOp::Glyphs(glyphs, pos, None, run_color) => {
+                    self.draw_text(glyphs, pos, run_color.as_ref().unwrap_or(color))
+                }
+                TextOp::Glyphs(glyphs, pos, Some(slant), run_color) => self.with_save(|ctx| {
+                    ctx.transform(slant);
+                    ctx.draw_text(glyphs, pos, run_color.as_ref().unwrap_or(color));
+                }),
+                TextOp::Line(rect, run_color) => {
+                    self.fill_color(rect, run_color.as_ref().unwrap_or(color), FillRule::NonZero)
+                }
+            }
+        }
+    }
+
+    /// Draw `image` stretched to `rect`.
+    pub fn draw_image(&mut self, image: &ImageBuf, rect: Rect, interp: InterpolationMode) {
+        self.record(|| Op::Image(image.clone(), ImageOp::Image(rect), interp));
+        self.with_image(image, |render_ctx, piet_image| {
+            render_ctx.draw_image(piet_image, rect, interp);
+        });
+    }
+
+    /// Draw the part of `image` at `src`, in pixels, stretched to `dst`.
+    pub fn draw_image_area(
+        &mut self,
+        image: &ImageBuf,
+        src: Rect,
+        dst: Rect,
+        interp: InterpolationMode,
+    ) {
+        self.record(|| Op::Image(image.clone(), ImageOp::ImageArea(src, dst), interp));
+        let size = image.size();
+        self.with_image(image, |render_ctx, piet_image| {
+            draw_clipped(
+                render_ctx,
+                piet_image,
+                image::map_area(size, src, dst),
+                dst,
+                interp,
+            );
+        });
+    }
+
+    /// Draw `image` in `rect`, sized as `fit` says and cropped to `rect`.
+    pub fn draw_image_fit(
+        &mut self,
+        image: &ImageBuf,
+        rect: Rect,
+        fit: FitMode,
+        interp: InterpolationMode,
+    ) {
+        self.record(|| Op::Image(image.clone(), ImageOp::ImageFit(rect, fit), interp));
+        let fitted = fit.fit(image.size(), rect);
+        self.with_image(image, |render_ctx, piet_image| {
+            draw_clipped(render_ctx, piet_image, fitted, rect, interp);
+        });
+    }
+
+    /// Draw `image` stretched to `rect` as nine slices, for scalable chrome
+    /// such as button frames: `center`, in pixels, is stretched both ways,
+    /// the edges around it along their length, and the corners not at all.
+    ///
+    /// The corners are scaled down if `rect` is too small for them.
+    pub fn draw_nine_slice(
+        &mut self,
+        image: &ImageBuf,
+        center: Rect,
+        rect: Rect,
+        interp: InterpolationMode,
+    ) {
+        self.record(|| Op::Image(image.clone(), ImageOp::NineSlice(center, rect), interp));
+        let size = image.size();
+        let (left, top) = (center.x0, center.y0);
+        let (right, bottom) = (size.width - center.x1, size.height - center.y1);
+        let scale = (rect.width() / (left + right))
+            .min(rect.height() / (top + bottom))
+            .min(1.0);
+        let inner = Rect::new(
+            rect.x0 + left * scale,
+            rect.y0 + top * scale,
+            rect.x1 - right * scale,
+            rect.y1 - bottom * scale,
+        );
+        let src = image::nine_slices(Rect::from_origin_size(Point::ORIGIN, size), center);
+        let dst = image::nine_slices(rect, inner);
+        self.with_image(image, |render_ctx, piet_image| {
+            for (&src, &dst) in src.iter().zip(dst.iter()) {
+                if src.area() > 0.0 && dst.area() > 0.0 {
+                    let area = image::map_area(size, src, dst);
+                    draw_clipped(render_ctx, piet_image, area, dst, interp);
+                }
+            }
+        });
+    }
+
+    /// Call `f` with the image for `image`, from the window's images if
+    /// painting into the window.
+    fn with_image<F>(&mut self, image: &ImageBuf, f: F)
+    where
+        F: FnOnce(&mut Piet, &<Piet<'static> as RenderContext>::Image),
+    {
+        let made;
+        let piet_image = match self.images {
+            Some(ref mut images) => images.get(self.render_ctx, image),
+            None => {
+                made = image::make_image(self.render_ctx, image);
+                made.as_ref()
+            }
+        };
+        if let Some(piet_image) = piet_image {
+            f(self.render_ctx, piet_image);
+        }
+    }
+
+    /// A context with the same state, painting into `render_ctx`, which is
+    /// a bitmap.
+    fn with_render_ctx<'c, 'd>(&self, render_ctx: &'c mut Piet<'d>) -> PaintCtx<'c, 'd> {
+        text_rendering::set_text_rendering(render_ctx, self.text_rendering.grayscale());
+        PaintCtx {
+            is_active: self.is_active,
+            is_hot: self.is_hot,
+            is_focused: self.is_focused,
+            theme: self.theme,
+            env: self.env.clone(),
+            accent_color: self.accent_color.clone(),
+            reduce_transparency: self.reduce_transparency,
+            text_scale: self.text_scale,
+            touch_mode: self.touch_mode,
+            text_rendering: self.text_rendering,
+            scale: self.scale,
+            visible: self.visible,
+            saved: false,
+            overlays: Vec::new(),
+            images: None,
+            brushes: None,
+            recording: None,
+            render_ctx,
+        }
+    }
+
+    /// Paint the overlays requested with `paint_overlay`.
+    fn paint_overlays(&mut self) {
+        self.is_active = false;
+        self.is_hot = false;
+        self.is_focused = false;
+        // Overlays may add more overlays, which are painted above them.
+        while !self.overlays.is_empty() {
+            for overlay in mem::replace(&mut self.overlays, Vec::new()) {
+                self.with_save(overlay);
+            }
+        }
+    }
+
+    /// Start the scope of the clips and transforms of a widget, returning
+    /// what `end_scope` restores.
+    fn begin_scope(&mut self) -> (bool, Rect) {
+        self.record(|| Op::Begin);
+        (mem::replace(&mut self.saved, false), self.visible)
+    }
+
+    /// Undo the clips and transforms set since `begin_scope`.
+    fn end_scope(&mut self, (saved, visible): (bool, Rect)) {
+        self.record(|| Op::End);
+        if mem::replace(&mut self.saved, saved) {
+            let _ = self.render_ctx.restore();
+        }
+        self.visible = visible;
+    }
+
+    /// Add the op made by `op` to the display list being recorded, if any.
+    fn record(&mut self, op: impl FnOnce() -> Op) {
+        if let Some(ref mut list) = self.recording {
+            list.ops.push(op());
+        }
+    }
+
+    /// Note that painting that can't be recorded was done, so that the
+    /// display list being recorded, if any, is not replayed.
+    fn record_unsupported(&mut self) {
+        if let Some(ref mut list) = self.recording {
+            list.complete = false;
+        }
+    }
+
+    /// Save the render context for the current scope, if that's not done
+    /// already.
+    fn save_once(&mut self) {
+        if !self.saved {
+            let _ = self.render_ctx.save();
+            self.saved = true;
+        }
+    }
 }
 
 impl WinHandler for UiMain {
     fn connect(&self, handle: &WindowHandle) {
-        let mut state = self.state.borrow_mut();
-        state.layout_ctx.handle = handle.clone();
+        let mut state = self.state();
+        state.layout_ctx.window.handle = handle.clone();
+
+        // Frames requested by widgets added before the window existed.
+        if let AnimState::FrameRequested = state.layout_ctx.window.anim_state {
+            handle.request_frame();
+        }
 
         // Dispatch events; this is mostly to add listeners.
         state.dispatch_events();
+
+        // Windows requested before the first one was connected.
+        if !state.pending_windows.is_empty() {
+            if let Some(idle) = handle.get_idle_handle() {
+                idle.add_idle(|handler| {
+                    if let Some(ui_main) = handler.downcast_ref::<UiMain>() {
+                        ui_main.build_pending_windows();
+                    }
+                });
+            }
+        }
     }
 
-    fn paint(&self, paint_ctx: &mut Piet) -> bool {
-        let mut state = self.state.borrow_mut();
+    fn frame(&self) {
+        let start = Instant::now();
+        let mut state = self.paint_state();
         state.anim_frame();
+        state.layout_ctx.window.stats.update += Instant::now().duration_since(start);
+    }
+
+    fn paint(&self, paint_ctx: &mut Piet) -> bool {
+        let mut state = self.paint_state();
         {
-            paint_ctx.clear(BACKGROUND_COLOR);
+            // A backdrop shows through wherever the widgets don't paint.
+            if state.layout_ctx.window.handle.backdrop() == WindowBackdrop::None {
+                paint_ctx.clear(state.layout_ctx.env.get(theme::WINDOW_BACKGROUND_COLOR));
+            } else {
+                paint_ctx.clear(Color::rgba32(0));
+            }
         }
         let root = state.graph.root;
-        let bc = BoxConstraints::tight(state.inner.layout_ctx.size);
+        let bc = BoxConstraints::tight(state.inner.layout_ctx.window.size);
 
         // TODO: be lazier about relayout
+        let start = Instant::now();
         state.layout(&bc, root);
+        state.sync_accessibility();
+        let laid_out = Instant::now();
         state.paint(paint_ctx, root);
-        match state.layout_ctx.anim_state {
-            AnimState::AnimFrameRequested => true,
-            _ => {
-                state.layout_ctx.anim_state = AnimState::Idle;
-                state.layout_ctx.prev_paint_time = None;
-                false
-            }
+        let painted = Instant::now();
+        let show_frame_stats = state.layout_ctx.show_frame_stats;
+        let stats = &mut state.layout_ctx.window.stats;
+        stats.layout += laid_out.duration_since(start);
+        stats.paint += painted.duration_since(laid_out);
+        stats.end_frame(
+            painted,
+            if show_frame_stats {
+                Some(paint_ctx)
+            } else {
+                None
+            },
+        );
+        // A requested frame paints again; the shell schedules nothing else.
+        match state.layout_ctx.window.anim_state {
+            AnimState::FrameRequested => (),
+            _ => state.layout_ctx.window.anim_state = AnimState::Idle,
         }
+        false
     }
 
     fn command(&self, id: u32) {
-        // TODO: plumb through to client
-        let mut state = self.state.borrow_mut();
-        state.handle_command(id);
+        self.commands.borrow_mut().push(id, CommandPriority::Normal);
+        // If the state is busy, for example because a listener is running a
+        // modal dialog, the command is delivered when it finishes.
+        if let Ok(mut state) = self.state.try_borrow_mut() {
+            state.enter_window(self.window);
+            state.dispatch_events();
+        }
     }
 
     fn key_down(&self, event: KeyEvent) -> bool {
-        let mut state = self.state.borrow_mut();
+        let mut state = self.state();
         state.handle_key_down(&event)
     }
 
     fn key_up(&self, event: KeyEvent) {
-        let mut state = self.state.borrow_mut();
+        let mut state = self.state();
         state.handle_key_up(&event);
     }
 
-    fn mouse_wheel(&self, dy: i32, mods: KeyModifiers) {
-        let mut state = self.state.borrow_mut();
-        state.handle_scroll(&window::ScrollEvent {
-            dx: 0.0,
-            dy: dy as f64,
-            mods,
-        });
+    fn composition(&self, event: &CompositionEvent) {
+        let mut state = self.state();
+        state.handle_composition(event);
     }
 
-    fn mouse_hwheel(&self, dx: i32, mods: KeyModifiers) {
-        let mut state = self.state.borrow_mut();
-        state.handle_scroll(&window::ScrollEvent {
-            dx: dx as f64,
-            dy: 0.0,
-            mods,
-        });
+    fn wheel(&self, event: &WheelEvent) {
+        let mut state = self.state();
+        state.handle_wheel(event);
     }
 
     fn mouse_move(&self, event: &window::MouseEvent) {
-        let mut state = self.state.borrow_mut();
-        let (x, y) = state.layout_ctx.handle.pixels_to_px_xy(event.x, event.y);
+        let mut state = self.state();
+        let (x, y) = state
+            .layout_ctx
+            .window
+            .handle
+            .pixels_to_px_xy(event.x, event.y);
         let pos = Point::new(x as f64, y as f64);
         state.mouse_move(pos);
     }
 
     fn mouse(&self, event: &window::MouseEvent) {
         //println!("mouse {:?}", event);
-        let mut state = self.state.borrow_mut();
-        let (x, y) = state.layout_ctx.handle.pixels_to_px_xy(event.x, event.y);
+        let mut state = self.state();
+        let (x, y) = state
+            .layout_ctx
+            .window
+            .handle
+            .pixels_to_px_xy(event.x, event.y);
         let pos = Point::new(x as f64, y as f64);
         // TODO: detect multiple clicks and pass that down
         state.mouse(pos, event);
     }
 
+    fn touch(&self, event: &window::TouchEvent) {
+        let mut state = self.state();
+        let (x, y) = state
+            .layout_ctx
+            .window
+            .handle
+            .pixels_to_px_xy(event.x, event.y);
+        state.touch(Point::new(x as f64, y as f64), event);
+    }
+
+    fn trackpad_gesture(&self, event: &window::TrackpadGesture) {
+        let mut state = self.state();
+        let (x, y) = state
+            .layout_ctx
+            .window
+            .handle
+            .pixels_to_px_xy(event.x, event.y);
+        state.trackpad_gesture(Point::new(x as f64, y as f64), event);
+    }
+
+    fn pen(&self, event: &window::PenEvent) -> bool {
+        let mut state = self.state();
+        let (x, y) = state
+            .layout_ctx
+            .window
+            .handle
+            .pixels_to_px_xy(event.x, event.y);
+        state.pen(Point::new(x as f64, y as f64), event)
+    }
+
+    fn mouse_capture_lost(&self) {
+        let mut state = self.state();
+        if state.layout_ctx.window.active.take().is_some() {
+            state.layout_ctx.invalidate();
+        }
+    }
+
+    fn file_drag_enter(&self, paths: &[PathBuf], x: i32, y: i32) -> bool {
+        let mut state = self.state();
+        let (x, y) = state.layout_ctx.window.handle.pixels_to_px_xy(x, y);
+        state.file_drag_enter(paths.to_vec(), Point::new(x as f64, y as f64))
+    }
+
+    fn file_drag_over(&self, x: i32, y: i32) -> bool {
+        let mut state = self.state();
+        if !state.is_file_drag() {
+            return false;
+        }
+        let (x, y) = state.layout_ctx.window.handle.pixels_to_px_xy(x, y);
+        state.file_drag_over(Point::new(x as f64, y as f64))
+    }
+
+    fn file_drag_leave(&self) {
+        let mut state = self.state();
+        if state.is_file_drag() {
+            state.end_drag(false);
+        }
+    }
+
+    fn file_drop(&self, paths: Vec<PathBuf>, x: i32, y: i32) -> bool {
+        let mut state = self.state();
+        let (x, y) = state.layout_ctx.window.handle.pixels_to_px_xy(x, y);
+        state.file_drop(paths, Point::new(x as f64, y as f64))
+    }
+
+    fn timer(&self, token: TimerToken) {
+        let mut state = self.state();
+        state.handle_timer(token);
+        state.dispatch_events();
+    }
+
+    fn drag_ended(&self, effect: DropEffect) {
+        let mut state = self.state();
+        state.handle_system_drag_ended(effect);
+        state.dispatch_events();
+    }
+
+    fn request_close(&self) -> bool {
+        self.state().close_requested()
+    }
+
     fn destroy(&self) {
-        Application::quit();
+        let mut state = self.state();
+        let last = state.window_closed(self.window);
+        if last && state.quit_on_last_window_closed {
+            Application::quit();
+        }
     }
 
     fn as_any(&self) -> &dyn Any {
         self
     }
 
+    fn window_state_changed(&self, state: WindowState) {
+        self.state().window_state_changed(state);
+    }
+
+    fn theme_changed(&self, theme: Theme) {
+        self.state().theme_changed(theme);
+    }
+
+    fn accent_color_changed(&self, color: Option<Color>) {
+        self.state().accent_color_changed(color);
+    }
+
+    fn reduce_transparency_changed(&self, reduce: bool) {
+        self.state().reduce_transparency_changed(reduce);
+    }
+
+    fn power_source_changed(&self, source: PowerSource) {
+        self.state().power_source_changed(source);
+    }
+
+    fn text_scale_changed(&self, scale: f64) {
+        self.state().text_scale_changed(scale);
+    }
+
+    fn accessibility_action(&self, request: ActionRequest) -> bool {
+        let mut state = self.state();
+        state.handle_accessibility_action(&request)
+    }
+
+    fn tray_icon_clicked(&self) {
+        let mut state = self.state();
+        if let Some(cmd) = state.layout_ctx.window.tray_command {
+            self.commands
+                .borrow_mut()
+                .push(cmd, CommandPriority::Normal);
+            state.dispatch_events();
+        }
+    }
+
     fn size(&self, width: u32, height: u32) {
-        let mut state = self.state.borrow_mut();
-        let dpi = state.layout_ctx.handle.get_dpi() as f64;
+        let mut state = self.state();
+        let dpi = state.layout_ctx.window.handle.get_dpi() as f64;
         let scale = 96.0 / dpi;
-        state.inner.layout_ctx.size = Size::new(width as f64 * scale, height as f64 * scale);
+        state.inner.layout_ctx.window.size = Size::new(width as f64 * scale, height as f64 * scale);
+        // The images of layers may belong to a render target that resizing
+        // replaces, as may those of the window.
+        state.inner.layout_ctx.invalidate_layers();
+        state.inner.layout_ctx.window.images.clear();
+        state.inner.layout_ctx.window.brushes.clear();
     }
 }
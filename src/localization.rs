@@ -0,0 +1,767 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Strings looked up by key in the resources of the current locale.
+//!
+//! Resources are in the syntax of Fluent (`.ftl` files), of which messages,
+//! terms, attributes, variables, string and number literals, message
+//! references and select expressions on plural categories are supported.
+//! The values of variants are one line each. Numbers are written as
+//! `LocaleFormat::decimal` does in the locale of the message.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use crate::LocaleFormat;
+
+/// How deeply messages may reference each other, to stop cycles.
+const MAX_DEPTH: usize = 8;
+
+/// The direction text runs in, and widgets are laid out in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayoutDirection {
+    LeftToRight,
+    /// Mirrored, as for Arabic and Hebrew: rows start on the right.
+    RightToLeft,
+}
+
+impl LayoutDirection {
+    /// The direction of the language of `locale`.
+    pub fn of_locale(locale: &str) -> LayoutDirection {
+        let language = locale.split(['-', '_']).next().unwrap_or("");
+        match language {
+            "ar" | "he" | "iw" | "fa" | "ur" | "ps" | "yi" | "dv" | "sd" | "ug" | "ckb" => {
+                LayoutDirection::RightToLeft
+            }
+            _ => LayoutDirection::LeftToRight,
+        }
+    }
+
+    pub fn is_rtl(self) -> bool {
+        self == LayoutDirection::RightToLeft
+    }
+}
+
+/// The value of a variable in a message.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ArgValue {
+    Number(f64),
+    String(String),
+}
+
+impl From<f64> for ArgValue {
+    fn from(n: f64) -> ArgValue {
+        ArgValue::Number(n)
+    }
+}
+
+impl From<i32> for ArgValue {
+    fn from(n: i32) -> ArgValue {
+        ArgValue::Number(n.into())
+    }
+}
+
+impl From<usize> for ArgValue {
+    fn from(n: usize) -> ArgValue {
+        ArgValue::Number(n as f64)
+    }
+}
+
+impl From<&str> for ArgValue {
+    fn from(s: &str) -> ArgValue {
+        ArgValue::String(s.to_string())
+    }
+}
+
+impl From<String> for ArgValue {
+    fn from(s: String) -> ArgValue {
+        ArgValue::String(s)
+    }
+}
+
+impl fmt::Display for ArgValue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArgValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => {
+                write!(f, "{}", *n as i64)
+            }
+            ArgValue::Number(n) => write!(f, "{}", n),
+            ArgValue::String(s) => f.write_str(s),
+        }
+    }
+}
+
+/// The key of a message, with the values of its variables, shown as the
+/// message of the current locale.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalizedString {
+    key: String,
+    args: Vec<(String, ArgValue)>,
+}
+
+impl LocalizedString {
+    pub fn new(key: impl Into<String>) -> LocalizedString {
+        LocalizedString {
+            key: key.into(),
+            args: Vec::new(),
+        }
+    }
+
+    /// Set the variable `$name` of the message to `value`.
+    pub fn with_arg(mut self, name: impl Into<String>, value: impl Into<ArgValue>) -> Self {
+        let name = name.into();
+        let value = value.into();
+        match self.args.iter_mut().find(|(n, _)| *n == name) {
+            Some(arg) => arg.1 = value,
+            None => self.args.push((name, value)),
+        }
+        self
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// The message in `localization`, or the key if no resource has it.
+    pub fn resolve(&self, localization: &Localization) -> String {
+        localization
+            .format(&self.key, &self.args)
+            .unwrap_or_else(|| self.key.clone())
+    }
+}
+
+/// An error in the syntax of a resource.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FtlError {
+    /// The line of the message with the error, counting from 1.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for FtlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+impl Error for FtlError {}
+
+/// The messages of each locale, and the locale they are shown in.
+///
+/// Messages missing from the current locale, such as `fr-CA`, are looked up
+/// in its language, `fr`, and then in the fallback locale and its language.
+pub struct Localization {
+    locale: String,
+    fallback: String,
+    resources: HashMap<String, HashMap<String, Pattern>>,
+}
+
+impl Default for Localization {
+    /// No messages, in the system's locale with `en-US` as the fallback.
+    fn default() -> Localization {
+        Localization::new(&Localization::system_locale())
+    }
+}
+
+impl Localization {
+    /// No messages, in `locale`, such as `"en-US"`, which is also the
+    /// fallback.
+    pub fn new(locale: &str) -> Localization {
+        Localization {
+            locale: locale.to_string(),
+            fallback: "en-US".to_string(),
+            resources: HashMap::new(),
+        }
+    }
+
+    /// The locale of the environment variables `LC_ALL`, `LC_MESSAGES` and
+    /// `LANG` if set, such as `fr-FR` for `fr_FR.UTF-8`, or else `en-US`.
+    pub fn system_locale() -> String {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .map(|value| {
+                let name = value.split(['.', '@']).next().unwrap_or("");
+                name.replace('_', "-")
+            })
+            .find(|name| !name.is_empty() && name != "C" && name != "POSIX")
+            .unwrap_or_else(|| "en-US".to_string())
+    }
+
+    pub fn locale(&self) -> &str {
+        &self.locale
+    }
+
+    /// Show messages in `locale`. `Ui::set_locale` also updates the widgets
+    /// showing them.
+    pub fn set_locale(&mut self, locale: &str) {
+        self.locale = locale.to_string();
+    }
+
+    /// Look up the messages missing from the current locale in `locale`.
+    pub fn set_fallback(&mut self, locale: &str) {
+        self.fallback = locale.to_string();
+    }
+
+    /// Add the messages in `source`, in the syntax of Fluent, to those of
+    /// `locale`, replacing any with the same keys. Nothing is added if the
+    /// source has an error.
+    pub fn add_resource(&mut self, locale: &str, source: &str) -> Result<(), FtlError> {
+        let messages = parse_resource(source)?;
+        self.resources
+            .entry(locale.to_string())
+            .or_default()
+            .extend(messages);
+        Ok(())
+    }
+
+    /// The message `key`, such as `"menu-file-open"` or, for an attribute,
+    /// `"menu-file-open.tooltip"`, with the values of its variables.
+    pub fn format(&self, key: &str, args: &[(String, ArgValue)]) -> Option<String> {
+        self.format_message(key, args, 0)
+    }
+
+    fn format_message(
+        &self,
+        key: &str,
+        args: &[(String, ArgValue)],
+        depth: usize,
+    ) -> Option<String> {
+        let (locale, pattern) = self.lookup(key)?;
+        let mut out = String::new();
+        Formatter {
+            localization: self,
+            locale,
+            args,
+            depth,
+        }
+        .pattern(pattern, &mut out);
+        Some(out)
+    }
+
+    /// The pattern of `key`, and the locale it is in.
+    fn lookup(&self, key: &str) -> Option<(&str, &Pattern)> {
+        let language = |locale: &'_ str| locale.split('-').next().unwrap_or("").to_string();
+        let chain = [
+            self.locale.clone(),
+            language(&self.locale),
+            self.fallback.clone(),
+            language(&self.fallback),
+        ];
+        chain.iter().find_map(|locale| {
+            let (locale, messages) = self.resources.get_key_value(locale)?;
+            messages.get(key).map(|pattern| (locale.as_str(), pattern))
+        })
+    }
+}
+
+/// The plural category of `n` in the language of `locale`, for the common
+/// languages; the others use the rules of English.
+fn plural_category(locale: &str, n: f64) -> &'static str {
+    let language = locale.split('-').next().unwrap_or("");
+    let integer = n.fract() == 0.0;
+    let i = n.abs().trunc() as u64;
+    let few = (2..=4).contains(&(i % 10)) && !(12..=14).contains(&(i % 100));
+    match language {
+        "ja" | "ko" | "zh" | "th" | "vi" | "id" | "ms" => "other",
+        "fr" => {
+            if i <= 1 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+        "ru" | "uk" => {
+            if !integer {
+                "other"
+            } else if i % 10 == 1 && i % 100 != 11 {
+                "one"
+            } else if few {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "pl" => {
+            if !integer {
+                "other"
+            } else if i == 1 {
+                "one"
+            } else if few {
+                "few"
+            } else {
+                "many"
+            }
+        }
+        "cs" | "sk" => {
+            if !integer {
+                "many"
+            } else if i == 1 {
+                "one"
+            } else if (2..=4).contains(&i) {
+                "few"
+            } else {
+                "other"
+            }
+        }
+        _ => {
+            if n == 1.0 {
+                "one"
+            } else {
+                "other"
+            }
+        }
+    }
+}
+
+type Pattern = Vec<Element>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Element {
+    Text(String),
+    Placeable(Expr),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    Variable(String),
+    String(String),
+    Number(f64),
+    /// A reference to another message or to a term.
+    Message(String),
+    Select(Box<Expr>, Vec<Variant>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Variant {
+    key: String,
+    default: bool,
+    value: Pattern,
+}
+
+struct Formatter<'a> {
+    localization: &'a Localization,
+    locale: &'a str,
+    args: &'a [(String, ArgValue)],
+    depth: usize,
+}
+
+impl<'a> Formatter<'a> {
+    fn pattern(&self, pattern: &[Element], out: &mut String) {
+        for element in pattern {
+            match element {
+                Element::Text(text) => out.push_str(text),
+                Element::Placeable(expr) => match self.expr(expr) {
+                    ArgValue::Number(n) => out.push_str(&LocaleFormat::new(self.locale).decimal(n)),
+                    ArgValue::String(s) => out.push_str(&s),
+                },
+            }
+        }
+    }
+
+    fn expr(&self, expr: &Expr) -> ArgValue {
+        match expr {
+            Expr::Variable(name) => match self.args.iter().find(|(n, _)| n == name) {
+                Some((_, value)) => value.clone(),
+                None => ArgValue::String(format!("{{${}}}", name)),
+            },
+            Expr::String(s) => ArgValue::String(s.clone()),
+            Expr::Number(n) => ArgValue::Number(*n),
+            Expr::Message(key) => {
+                let message = if self.depth < MAX_DEPTH {
+                    self.localization
+                        .format_message(key, self.args, self.depth + 1)
+                } else {
+                    None
+                };
+                ArgValue::String(message.unwrap_or_else(|| format!("{{{}}}", key)))
+            }
+            Expr::Select(selector, variants) => {
+                let selector = self.expr(selector);
+                let matches = |key: &str| match &selector {
+                    ArgValue::Number(n) => match key.parse::<f64>() {
+                        Ok(k) => k == *n,
+                        Err(_) => key == plural_category(self.locale, *n),
+                    },
+                    ArgValue::String(s) => key == s,
+                };
+                // Exact numbers are tried before plural categories.
+                let variant = variants
+                    .iter()
+                    .find(|v| v.key.parse::<f64>().is_ok() && matches(&v.key))
+                    .or_else(|| variants.iter().find(|v| matches(&v.key)))
+                    .or_else(|| variants.iter().find(|v| v.default));
+                let mut out = String::new();
+                if let Some(variant) = variant {
+                    self.pattern(&variant.value, &mut out);
+                }
+                ArgValue::String(out)
+            }
+        }
+    }
+}
+
+/// The messages and attributes of a resource, by key.
+fn parse_resource(source: &str) -> Result<HashMap<String, Pattern>, FtlError> {
+    // Messages with their first line, joining the indented lines after them.
+    let mut entries: Vec<(usize, String, String)> = Vec::new();
+    let mut in_message = false;
+    for (i, line) in source.lines().enumerate() {
+        let line_no = i + 1;
+        let error = |message: &str| FtlError {
+            line: line_no,
+            message: message.to_string(),
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.starts_with('#') {
+            in_message = false;
+            continue;
+        }
+        // Closing braces may also be unindented.
+        let indented =
+            line.starts_with(char::is_whitespace) || (in_message && line.starts_with('}'));
+        let trimmed = line.trim();
+        if indented && !in_message {
+            return Err(error("indented line outside a message"));
+        }
+        if indented && trimmed.starts_with('.') {
+            let eq = trimmed.find('=').ok_or_else(|| error("expected `=`"))?;
+            let attr = trimmed[1..eq].trim();
+            if !is_identifier(attr) {
+                return Err(error("invalid attribute name"));
+            }
+            let message = entries.iter().rev().find(|e| !e.1.contains('.')).unwrap();
+            let key = format!("{}.{}", message.1, attr);
+            entries.push((line_no, key, trimmed[eq + 1..].trim().to_string()));
+        } else if indented {
+            let value = &mut entries.last_mut().unwrap().2;
+            if !value.is_empty() {
+                value.push('\n');
+            }
+            value.push_str(trimmed);
+        } else {
+            let eq = line.find('=').ok_or_else(|| error("expected `=`"))?;
+            let id = line[..eq].trim();
+            let name = id.trim_start_matches('-');
+            if !is_identifier(name) || id.len() > name.len() + 1 {
+                return Err(error("invalid message name"));
+            }
+            entries.push((line_no, id.to_string(), line[eq + 1..].trim().to_string()));
+            in_message = true;
+        }
+    }
+
+    let mut messages = HashMap::new();
+    for (line, key, value) in entries {
+        let mut parser = Parser {
+            chars: value.chars().collect(),
+            pos: 0,
+        };
+        let pattern = parser.pattern(false).map_err(|message| FtlError {
+            line,
+            message: message.to_string(),
+        })?;
+        messages.insert(key, pattern);
+    }
+    Ok(messages)
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) if c.is_ascii_alphabetic() => (),
+        _ => return false,
+    }
+    chars.all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char, message: &'static str) -> Result<(), &'static str> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(message)
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// Text and placeables, up to the end, or up to the end of the line or
+    /// a `}` in the value of a variant.
+    fn pattern(&mut self, in_variant: bool) -> Result<Pattern, &'static str> {
+        let mut pattern = Vec::new();
+        let mut text = String::new();
+        while let Some(c) = self.peek() {
+            match c {
+                '{' => {
+                    self.pos += 1;
+                    if !text.is_empty() {
+                        pattern.push(Element::Text(text.split_off(0)));
+                    }
+                    pattern.push(Element::Placeable(self.placeable()?));
+                }
+                '}' if in_variant => break,
+                '}' => return Err("unbalanced `}`"),
+                '\n' if in_variant => break,
+                _ => {
+                    text.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+        let text = if in_variant { text.trim_end() } else { &text };
+        if !text.is_empty() {
+            pattern.push(Element::Text(text.to_string()));
+        }
+        Ok(pattern)
+    }
+
+    /// The inside of `{ }`, after the `{`.
+    fn placeable(&mut self) -> Result<Expr, &'static str> {
+        self.skip_whitespace();
+        let expr = self.inline()?;
+        self.skip_whitespace();
+        if self.eat('-') {
+            self.expect('>', "expected `->`")?;
+            return self.variants(expr);
+        }
+        self.expect('}', "expected `}`")?;
+        Ok(expr)
+    }
+
+    fn identifier(&mut self) -> Result<String, &'static str> {
+        let start = self.pos;
+        while self
+            .peek()
+            .is_some_and(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            self.pos += 1;
+        }
+        let id: String = self.chars[start..self.pos].iter().collect();
+        if is_identifier(&id) {
+            Ok(id)
+        } else {
+            Err("invalid name")
+        }
+    }
+
+    fn inline(&mut self) -> Result<Expr, &'static str> {
+        match self.peek() {
+            Some('$') => {
+                self.pos += 1;
+                Ok(Expr::Variable(self.identifier()?))
+            }
+            Some('"') => {
+                self.pos += 1;
+                let mut s = String::new();
+                loop {
+                    match self.peek() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            self.pos += 1;
+                            match self.peek() {
+                                Some(c @ '"') | Some(c @ '\\') => s.push(c),
+                                _ => return Err("unknown escape in string literal"),
+                            }
+                        }
+                        Some('\n') | None => return Err("unterminated string literal"),
+                        Some(c) => s.push(c),
+                    }
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                Ok(Expr::String(s))
+            }
+            Some('{') => {
+                self.pos += 1;
+                self.placeable()
+            }
+            Some(c) if c.is_ascii_digit() || c == '-' => {
+                let next = self.chars.get(self.pos + 1).cloned();
+                if c == '-' && !next.is_some_and(|c| c.is_ascii_digit()) {
+                    // A term, such as `-brand-name`.
+                    self.pos += 1;
+                    return Ok(Expr::Message(format!("-{}", self.identifier()?)));
+                }
+                let start = self.pos;
+                self.pos += 1;
+                while self.peek().is_some_and(|c| c.is_ascii_digit() || c == '.') {
+                    self.pos += 1;
+                }
+                let number: String = self.chars[start..self.pos].iter().collect();
+                number
+                    .parse()
+                    .map(Expr::Number)
+                    .map_err(|_| "invalid number")
+            }
+            Some(c) if c.is_ascii_alphabetic() => Ok(Expr::Message(self.identifier()?)),
+            _ => Err("expected an expression"),
+        }
+    }
+
+    /// The variants of a select expression, after the `->`.
+    fn variants(&mut self, selector: Expr) -> Result<Expr, &'static str> {
+        let mut variants = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.eat('}') {
+                break;
+            }
+            let default = self.eat('*');
+            self.expect('[', "expected a variant")?;
+            let start = self.pos;
+            while self.peek().is_some_and(|c| c != ']' && c != '\n') {
+                self.pos += 1;
+            }
+            let key: String = self.chars[start..self.pos].iter().collect();
+            self.expect(']', "expected `]`")?;
+            while self.peek() == Some(' ') {
+                self.pos += 1;
+            }
+            let value = self.pattern(true)?;
+            variants.push(Variant {
+                key: key.trim().to_string(),
+                default,
+                value,
+            });
+        }
+        match variants.iter().filter(|v| v.default).count() {
+            1 => Ok(Expr::Select(Box::new(selector), variants)),
+            0 => Err("select expression has no default variant"),
+            _ => Err("select expression has more than one default variant"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EN: &str = "
+# Menus
+-brand = Druid
+menu-file-open = Open…
+    .tooltip = Open a file in { -brand }
+emails =
+    { $count ->
+        [0] No new emails
+        [one] One new email
+       *[other] { $count } new emails
+    }
+greeting = Hello, { $name }!
+";
+
+    const FR: &str = "
+emails = { $count ->
+    [one] { $count } nouveau courriel
+   *[other] { $count } nouveaux courriels
+}
+";
+
+    fn localization() -> Localization {
+        let mut l10n = Localization::new("en-US");
+        l10n.add_resource("en-US", EN).unwrap();
+        l10n.add_resource("fr", FR).unwrap();
+        l10n
+    }
+
+    fn emails(l10n: &Localization, count: usize) -> String {
+        LocalizedString::new("emails")
+            .with_arg("count", count)
+            .resolve(l10n)
+    }
+
+    #[test]
+    fn messages() {
+        let mut l10n = localization();
+        assert_eq!(
+            l10n.format("menu-file-open", &[]),
+            Some("Open…".to_string())
+        );
+        assert_eq!(
+            l10n.format("menu-file-open.tooltip", &[]),
+            Some("Open a file in Druid".to_string())
+        );
+        let greeting = LocalizedString::new("greeting").with_arg("name", "Ada");
+        assert_eq!(greeting.resolve(&l10n), "Hello, Ada!");
+        assert_eq!(
+            LocalizedString::new("greeting").resolve(&l10n),
+            "Hello, {$name}!"
+        );
+        assert_eq!(LocalizedString::new("missing").resolve(&l10n), "missing");
+
+        assert_eq!(emails(&l10n, 0), "No new emails");
+        assert_eq!(emails(&l10n, 1), "One new email");
+        assert_eq!(emails(&l10n, 5), "5 new emails");
+
+        // French, by its language, and English for the messages it lacks.
+        l10n.set_locale("fr-CA");
+        assert_eq!(emails(&l10n, 0), "0 nouveau courriel");
+        assert_eq!(emails(&l10n, 2), "2 nouveaux courriels");
+        assert_eq!(emails(&l10n, 1200), "1\u{202f}200 nouveaux courriels");
+        assert_eq!(
+            l10n.format("menu-file-open", &[]),
+            Some("Open…".to_string())
+        );
+    }
+
+    #[test]
+    fn plural_categories() {
+        assert_eq!(plural_category("en-US", 1.0), "one");
+        assert_eq!(plural_category("en-US", 1.5), "other");
+        assert_eq!(plural_category("ru", 21.0), "one");
+        assert_eq!(plural_category("ru", 22.0), "few");
+        assert_eq!(plural_category("ru", 12.0), "many");
+        assert_eq!(plural_category("pl", 21.0), "many");
+        assert_eq!(plural_category("ja", 1.0), "other");
+    }
+
+    #[test]
+    fn errors() {
+        let mut l10n = Localization::new("en-US");
+        let error = l10n.add_resource("en-US", "a = ok\nb = { $x -> [one] x }\n");
+        assert_eq!(error.unwrap_err().line, 2);
+        assert!(l10n.add_resource("en-US", "  indented = x").is_err());
+        assert!(l10n
+            .add_resource("en-US", "c = { \"unterminated }")
+            .is_err());
+        // Nothing is added from a resource with an error.
+        assert_eq!(l10n.format("a", &[]), None);
+    }
+}
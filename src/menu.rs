@@ -0,0 +1,136 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Platform independent descriptions of menus.
+
+use druid_shell::keycodes::MenuKey;
+use druid_shell::menu::Menu;
+
+/// A description of a menu: either a menubar, whose entries are submenus, or
+/// one of the menus inside it.
+///
+/// Selecting an item delivers its command to the command listener, just like
+/// a keyboard shortcut. Since the description is plain data, the app can build
+/// a fresh one from its current state and install it again with
+/// `Ui::set_menu` whenever the enabled or checked items change.
+#[derive(Clone, Default)]
+pub struct MenuDesc {
+    title: String,
+    entries: Vec<MenuEntry>,
+}
+
+#[derive(Clone)]
+enum MenuEntry {
+    Item(MenuItem),
+    Separator,
+    Submenu(MenuDesc),
+}
+
+/// A single item of a menu.
+#[derive(Clone)]
+pub struct MenuItem {
+    command: u32,
+    text: String,
+    key: MenuKey,
+    enabled: bool,
+    checked: bool,
+}
+
+impl MenuDesc {
+    /// Create an empty menu. The title is shown when the menu is a submenu;
+    /// it may mark an access key with `&`, as in `"&File"`.
+    pub fn new(title: impl Into<String>) -> MenuDesc {
+        MenuDesc {
+            title: title.into(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add an item.
+    pub fn item(mut self, item: MenuItem) -> MenuDesc {
+        self.entries.push(MenuEntry::Item(item));
+        self
+    }
+
+    /// Add a separator.
+    pub fn separator(mut self) -> MenuDesc {
+        self.entries.push(MenuEntry::Separator);
+        self
+    }
+
+    /// Add a submenu.
+    pub fn submenu(mut self, menu: MenuDesc) -> MenuDesc {
+        self.entries.push(MenuEntry::Submenu(menu));
+        self
+    }
+
+    /// Build the platform menu.
+    pub fn build(&self) -> Menu {
+        self.build_into(Menu::new())
+    }
+
+    /// Build the platform menu for use as a context menu.
+    pub fn build_popup(&self) -> Menu {
+        self.build_into(Menu::new_for_popup())
+    }
+
+    fn build_into(&self, mut menu: Menu) -> Menu {
+        for entry in &self.entries {
+            match entry {
+                MenuEntry::Item(item) => menu.add_item_with_state(
+                    item.command,
+                    &item.text,
+                    item.key,
+                    item.enabled,
+                    item.checked,
+                ),
+                MenuEntry::Separator => menu.add_separator(),
+                MenuEntry::Submenu(submenu) => menu.add_dropdown(submenu.build(), &submenu.title),
+            }
+        }
+        menu
+    }
+}
+
+impl MenuItem {
+    /// Create an enabled, unchecked item that sends `command` when selected.
+    pub fn new(command: u32, text: impl Into<String>) -> MenuItem {
+        MenuItem {
+            command,
+            text: text.into(),
+            key: ().into(),
+            enabled: true,
+            checked: false,
+        }
+    }
+
+    /// Display a key chord next to the item. On macOS this is also the key
+    /// equivalent of the item; elsewhere the key should be bound in the
+    /// keymap as well.
+    pub fn key(mut self, key: impl Into<MenuKey>) -> MenuItem {
+        self.key = key.into();
+        self
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> MenuItem {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Show a checkmark next to the item.
+    pub fn checked(mut self, checked: bool) -> MenuItem {
+        self.checked = checked;
+        self
+    }
+}
@@ -0,0 +1,189 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Blurred shadows of rectangles.
+//!
+//! piet has no blur, so the shadow is blurred in memory and drawn as an
+//! image.
+
+use std::cell::RefCell;
+
+use crate::kurbo::{Rect, Size, Vec2};
+use crate::piet::{Color, ImageFormat, InterpolationMode};
+use crate::{ImageBuf, PaintCtx};
+
+/// The shadow of a rectangle, optionally with rounded corners, such as
+/// under a card, a popup or a drag preview.
+///
+/// The shadow is blurred again only when the size of the rectangle
+/// changes, so a widget should keep its shadow rather than make a new one
+/// for each paint.
+#[derive(Clone)]
+pub struct BoxShadow {
+    color: Color,
+    blur_radius: f64,
+    offset: Vec2,
+    spread: f64,
+    corner_radius: f64,
+    /// The blurred shadow for the last size painted.
+    image: RefCell<Option<(Size, ImageBuf)>>,
+}
+
+impl BoxShadow {
+    /// A shadow of `color`, blurred over `blur_radius` px as in CSS.
+    pub fn new(color: Color, blur_radius: f64) -> BoxShadow {
+        BoxShadow {
+            color,
+            blur_radius: blur_radius.max(0.0),
+            offset: Vec2::new(0.0, 0.0),
+            spread: 0.0,
+            corner_radius: 0.0,
+            image: RefCell::new(None),
+        }
+    }
+
+    /// Move the shadow by `offset` from the rectangle.
+    pub fn offset(mut self, offset: Vec2) -> BoxShadow {
+        self.offset = offset;
+        self
+    }
+
+    /// Grow the shadow by `spread` px on each side before blurring it.
+    pub fn spread(mut self, spread: f64) -> BoxShadow {
+        self.spread = spread;
+        self.image = RefCell::new(None);
+        self
+    }
+
+    pub fn corner_radius(mut self, corner_radius: f64) -> BoxShadow {
+        self.corner_radius = corner_radius.max(0.0);
+        self.image = RefCell::new(None);
+        self
+    }
+
+    /// Paint the shadow of `rect`.
+    ///
+    /// The shadow is painted under the rectangle too, so it shows through
+    /// content that isn't opaque.
+    pub fn paint(&self, paint_ctx: &mut PaintCtx, rect: Rect) {
+        let shape = rect.inflate(self.spread, self.spread) + self.offset;
+        if shape.width() <= 0.0 || shape.height() <= 0.0 {
+            return;
+        }
+        let extent = self.extent();
+        let mut image = self.image.borrow_mut();
+        match *image {
+            Some((size, _)) if size == shape.size() => (),
+            _ => *image = Some((shape.size(), self.blur(shape.size()))),
+        }
+        if let Some((_, ref buf)) = *image {
+            let origin = shape.origin() - Vec2::new(extent, extent);
+            paint_ctx.draw_image(
+                buf,
+                Rect::from_origin_size(origin, buf.size()),
+                InterpolationMode::Bilinear,
+            );
+        }
+    }
+
+    /// How far the blur reaches out from the rectangle.
+    fn extent(&self) -> f64 {
+        (self.blur_radius * 1.5).ceil()
+    }
+
+    /// The shadow of a rectangle of `size`, with a margin of `extent` px
+    /// around it for the blur.
+    fn blur(&self, size: Size) -> ImageBuf {
+        let extent = self.extent();
+        let width = (size.width + 2.0 * extent).ceil() as usize;
+        let height = (size.height + 2.0 * extent).ceil() as usize;
+        let radius = self
+            .corner_radius
+            .min(size.width / 2.0)
+            .min(size.height / 2.0);
+        let mut mask = vec![0.0f32; width * height];
+        for (i, value) in mask.iter_mut().enumerate() {
+            let x = (i % width) as f64 + 0.5 - extent;
+            let y = (i / width) as f64 + 0.5 - extent;
+            // The distance into the corner, if the point is in one.
+            let dx = (radius - x).max(x - (size.width - radius)).max(0.0);
+            let dy = (radius - y).max(y - (size.height - radius)).max(0.0);
+            let inside = x >= 0.0 && x < size.width && y >= 0.0 && y < size.height;
+            if inside && dx * dx + dy * dy <= radius * radius {
+                *value = 1.0;
+            }
+        }
+        // The blur radius is twice the standard deviation, as in CSS.
+        let kernel = gaussian_kernel(self.blur_radius / 2.0, extent as usize);
+        let mask = convolve(&mask, width, height, &kernel, 1, width);
+        let mask = convolve(&mask, width, height, &kernel, width, 1);
+        let rgba = self.color.as_rgba32();
+        let (r, g, b) = ((rgba >> 24) as u8, (rgba >> 16) as u8, (rgba >> 8) as u8);
+        let alpha = (rgba & 0xff) as f32;
+        let mut pixels = Vec::with_capacity(width * height * 4);
+        for value in mask {
+            pixels.extend_from_slice(&[r, g, b, (value * alpha).round() as u8]);
+        }
+        ImageBuf::from_raw(pixels, ImageFormat::RgbaSeparate, width, height)
+    }
+}
+
+/// The weights of a gaussian of standard deviation `sigma`, from `-extent`
+/// to `extent`, summing to 1.
+fn gaussian_kernel(sigma: f64, extent: usize) -> Vec<f32> {
+    if sigma <= 0.0 {
+        return vec![1.0];
+    }
+    let weights = (0..=2 * extent)
+        .map(|i| {
+            let x = i as f64 - extent as f64;
+            (-x * x / (2.0 * sigma * sigma)).exp()
+        })
+        .collect::<Vec<_>>();
+    let sum: f64 = weights.iter().sum();
+    weights.iter().map(|w| (w / sum) as f32).collect()
+}
+
+/// Blur `mask` with `kernel` along one axis: `step` apart between the
+/// samples of a run, and `stride` apart between runs.
+fn convolve(
+    mask: &[f32],
+    width: usize,
+    height: usize,
+    kernel: &[f32],
+    step: usize,
+    stride: usize,
+) -> Vec<f32> {
+    let (runs, len) = if step == 1 {
+        (height, width)
+    } else {
+        (width, height)
+    };
+    let half = kernel.len() / 2;
+    let mut out = vec![0.0; mask.len()];
+    for run in 0..runs {
+        let base = run * stride;
+        for i in 0..len {
+            let mut sum = 0.0;
+            for (k, weight) in kernel.iter().enumerate() {
+                let j = i as isize + k as isize - half as isize;
+                if j >= 0 && (j as usize) < len {
+                    sum += weight * mask[base + j as usize * step];
+                }
+            }
+            out[base + i * step] = sum;
+        }
+    }
+    out
+}
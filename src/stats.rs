@@ -0,0 +1,101 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Statistics of the frames of a window, for the overlay shown with
+//! `Ui::set_frame_stats_visible`.
+
+use std::collections::VecDeque;
+use std::time::Duration;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use druid_shell::util::Instant;
+
+use crate::kurbo::{Point, Rect, Size};
+use crate::piet::{Color, FillRule, Piet, RenderContext};
+use crate::TextLayout;
+
+const FONT_SIZE: f64 = 12.0;
+const PADDING: f64 = 6.0;
+/// The distance of the overlay from the top left corner of the window.
+const MARGIN: f64 = 8.0;
+
+const BG_COLOR: Color = Color::rgba32(0x00_00_00_c0);
+const TEXT_COLOR: Color = Color::rgb24(0x80_ff_80);
+
+/// The time a window spent on each pass since it was last painted.
+#[derive(Default)]
+pub(crate) struct FrameStats {
+    /// Handling events from the platform, including the listeners and
+    /// commands they led to.
+    pub events: Duration,
+    /// Calling `anim_frame` on widgets.
+    pub update: Duration,
+    pub layout: Duration,
+    pub paint: Duration,
+    pub widgets_painted: usize,
+    /// When the frames of the last second were painted.
+    paints: VecDeque<Instant>,
+}
+
+impl FrameStats {
+    /// Finish the frame just painted at `now`, painting its statistics into
+    /// `overlay` if given, and start counting the next one.
+    pub fn end_frame(&mut self, now: Instant, overlay: Option<&mut Piet>) {
+        while let Some(&first) = self.paints.front() {
+            if now.duration_since(first) < Duration::from_secs(1) {
+                break;
+            }
+            self.paints.pop_front();
+        }
+        self.paints.push_back(now);
+        if let Some(render_ctx) = overlay {
+            let text = format!(
+                "{} fps\nevents {:.1} ms\nupdate {:.1} ms\nlayout {:.1} ms\npaint {:.1} ms\n\
+                 {} widgets painted",
+                self.paints.len(),
+                millis(self.events),
+                millis(self.update),
+                millis(self.layout),
+                millis(self.paint),
+                self.widgets_painted,
+            );
+            paint_overlay(render_ctx, text);
+        }
+        *self = FrameStats {
+            paints: std::mem::take(&mut self.paints),
+            ..Default::default()
+        };
+    }
+}
+
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs() as f64 * 1e3 + f64::from(duration.subsec_nanos()) * 1e-6
+}
+
+fn paint_overlay(render_ctx: &mut Piet, text: String) {
+    let mut layout = TextLayout::new(text, "Segoe UI", FONT_SIZE);
+    layout.rebuild_if_needed(render_ctx);
+    let size = layout.size();
+    let rect = Rect::from_origin_size(
+        Point::new(MARGIN, MARGIN),
+        Size::new(size.width + 2.0 * PADDING, size.height + 2.0 * PADDING),
+    );
+    let brush = render_ctx.solid_brush(BG_COLOR);
+    render_ctx.fill(rect, &brush, FillRule::NonZero);
+    let brush = render_ctx.solid_brush(TEXT_COLOR);
+    let origin = Point::new(MARGIN + PADDING, MARGIN + PADDING);
+    layout.draw(render_ctx, origin, &brush);
+}
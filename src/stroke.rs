@@ -0,0 +1,58 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The width and style of stroked lines.
+
+use crate::piet::{LineCap, LineJoin, StrokeStyle};
+
+/// How to stroke a line: its width, and the dashes, caps and joins of
+/// `StrokeStyle`, solid with butt caps and miter joins unless set.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stroke {
+    pub width: f64,
+    pub style: StrokeStyle,
+}
+
+impl Stroke {
+    pub fn new(width: f64) -> Stroke {
+        Stroke {
+            width,
+            style: StrokeStyle::new(),
+        }
+    }
+
+    /// Dash the line, alternating the lengths in `dashes` between on and
+    /// off, starting `offset` into the pattern.
+    pub fn dash(mut self, dashes: &[f64], offset: f64) -> Stroke {
+        self.style.dash = Some((dashes.to_vec(), offset));
+        self
+    }
+
+    pub fn line_cap(mut self, line_cap: LineCap) -> Stroke {
+        self.style.line_cap = Some(line_cap);
+        self
+    }
+
+    pub fn line_join(mut self, line_join: LineJoin) -> Stroke {
+        self.style.line_join = Some(line_join);
+        self
+    }
+
+    /// The longest a miter join may be, as a multiple of the width, before
+    /// it is beveled instead.
+    pub fn miter_limit(mut self, miter_limit: f64) -> Stroke {
+        self.style.miter_limit = Some(miter_limit);
+        self
+    }
+}
@@ -0,0 +1,1025 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SVG path data, and simple SVG documents, parsed into paths to paint.
+//!
+//! Documents can be parsed at runtime, or embedded with `include_str!`.
+//! Only what icons drawn in a vector editor need is supported: paths and
+//! basic shapes, filled and stroked with solid colors, in groups with
+//! transforms and opacity. Gradients, text, clipping, `use` and style
+//! sheets are left out.
+
+use std::fmt;
+
+use crate::kurbo::{Affine, Arc, BezPath, Point, Rect, Shape, SvgArc, Vec2};
+use crate::piet::{Color, FillRule, LineCap, LineJoin};
+use crate::{ColorExt, FitMode, PaintCtx, Stroke};
+
+/// Elements whose content isn't painted.
+const IGNORED: &[&str] = &[
+    "clipPath",
+    "defs",
+    "desc",
+    "filter",
+    "linearGradient",
+    "marker",
+    "mask",
+    "metadata",
+    "pattern",
+    "radialGradient",
+    "script",
+    "style",
+    "symbol",
+    "text",
+    "title",
+];
+
+/// An error in SVG path data or markup, at a byte offset into it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SvgError {
+    pub offset: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for SvgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for SvgError {}
+
+/// A solid color to paint with.
+#[derive(Clone)]
+pub enum SvgPaint {
+    Color(Color),
+    /// The color the document is painted with, at an opacity.
+    CurrentColor(f64),
+}
+
+/// A path of an SVG document, in the coordinates of its view box.
+#[derive(Clone)]
+pub struct SvgShape {
+    pub path: BezPath,
+    pub fill: Option<SvgPaint>,
+    pub fill_rule: FillRule,
+    pub stroke: Option<(SvgPaint, Stroke)>,
+}
+
+/// A parsed SVG document.
+#[derive(Clone)]
+pub struct Svg {
+    view_box: Rect,
+    shapes: Vec<SvgShape>,
+}
+
+/// The painting properties inherited from enclosing elements.
+#[derive(Clone)]
+struct Style {
+    fill: Option<SvgPaint>,
+    fill_rule: FillRule,
+    fill_opacity: f64,
+    stroke: Option<SvgPaint>,
+    stroke_width: f64,
+    stroke_opacity: f64,
+    line_cap: LineCap,
+    line_join: LineJoin,
+    /// The product of the opacities of the element and its ancestors.
+    opacity: f64,
+    transform: Affine,
+}
+
+impl Default for Style {
+    fn default() -> Style {
+        Style {
+            fill: Some(SvgPaint::Color(Color::BLACK)),
+            fill_rule: FillRule::NonZero,
+            fill_opacity: 1.0,
+            stroke: None,
+            stroke_width: 1.0,
+            stroke_opacity: 1.0,
+            line_cap: LineCap::Butt,
+            line_join: LineJoin::Miter,
+            opacity: 1.0,
+            transform: Affine::default(),
+        }
+    }
+}
+
+/// Parse the `d` attribute of an SVG path, such as `"M0 0 L10 0 L5 8 Z"`.
+///
+/// All the commands are supported; arcs are approximated with cubic
+/// Béziers.
+pub fn parse_path(data: &str) -> Result<BezPath, SvgError> {
+    let mut lexer = Lexer::new(data);
+    let mut path = BezPath::new();
+    let mut current = Point::ORIGIN;
+    let mut start = Point::ORIGIN;
+    // The last control point of a cubic or quadratic segment, for `S` and
+    // `T` to reflect.
+    let mut last_cubic = None;
+    let mut last_quad = None;
+    let mut last_cmd = None;
+    loop {
+        lexer.skip_separators();
+        let cmd = match lexer.peek() {
+            None => break,
+            Some(c) if c.is_ascii_alphabetic() => {
+                lexer.pos += 1;
+                c
+            }
+            // Numbers alone repeat the last command; after a move, as lines.
+            Some(_) => match last_cmd {
+                Some(b'M') => b'L',
+                Some(b'm') => b'l',
+                Some(c) if c != b'Z' && c != b'z' => c,
+                _ => return Err(lexer.error("expected a command")),
+            },
+        };
+        if last_cmd.is_none() && cmd != b'M' && cmd != b'm' {
+            return Err(lexer.error("expected a move"));
+        }
+        let base = if cmd.is_ascii_lowercase() {
+            current.to_vec2()
+        } else {
+            Vec2::new(0.0, 0.0)
+        };
+        let (mut cubic, mut quad) = (None, None);
+        match cmd.to_ascii_uppercase() {
+            b'M' => {
+                current = lexer.point()? + base;
+                start = current;
+                path.move_to(current);
+            }
+            b'L' => {
+                current = lexer.point()? + base;
+                path.line_to(current);
+            }
+            b'H' => {
+                current.x = lexer.number()? + base.x;
+                path.line_to(current);
+            }
+            b'V' => {
+                current.y = lexer.number()? + base.y;
+                path.line_to(current);
+            }
+            b'C' => {
+                let p1 = lexer.point()? + base;
+                let p2 = lexer.point()? + base;
+                current = lexer.point()? + base;
+                path.curve_to(p1, p2, current);
+                cubic = Some(p2);
+            }
+            b'S' => {
+                let p1 = reflect(last_cubic, current);
+                let p2 = lexer.point()? + base;
+                current = lexer.point()? + base;
+                path.curve_to(p1, p2, current);
+                cubic = Some(p2);
+            }
+            b'Q' => {
+                let p1 = lexer.point()? + base;
+                current = lexer.point()? + base;
+                path.quad_to(p1, current);
+                quad = Some(p1);
+            }
+            b'T' => {
+                let p1 = reflect(last_quad, current);
+                current = lexer.point()? + base;
+                path.quad_to(p1, current);
+                quad = Some(p1);
+            }
+            b'A' => {
+                let radii = lexer.point()?.to_vec2();
+                let x_rotation = lexer.number()?;
+                let large_arc = lexer.flag()?;
+                let sweep = lexer.flag()?;
+                let to = lexer.point()? + base;
+                arc_to(&mut path, current, radii, x_rotation, large_arc, sweep, to);
+                current = to;
+            }
+            b'Z' => {
+                path.close_path();
+                current = start;
+            }
+            _ => return Err(lexer.error("unknown command")),
+        }
+        last_cubic = cubic;
+        last_quad = quad;
+        last_cmd = Some(cmd);
+    }
+    Ok(path)
+}
+
+/// The reflection of `ctrl` about `current`, or `current` without a
+/// control point.
+fn reflect(ctrl: Option<Point>, current: Point) -> Point {
+    match ctrl {
+        Some(ctrl) => current + (current - ctrl),
+        None => current,
+    }
+}
+
+fn arc_to(
+    path: &mut BezPath,
+    from: Point,
+    radii: Vec2,
+    x_rotation: f64,
+    large_arc: bool,
+    sweep: bool,
+    to: Point,
+) {
+    let arc = SvgArc {
+        from,
+        to,
+        radii: Vec2::new(radii.x.abs(), radii.y.abs()),
+        x_rotation: x_rotation.to_radians(),
+        large_arc,
+        sweep,
+    };
+    match Arc::from_svg_arc(&arc) {
+        Some(arc) => arc.to_cubic_beziers(0.1, |p1, p2, p3| path.curve_to(p1, p2, p3)),
+        None => path.line_to(to),
+    }
+}
+
+impl Svg {
+    /// Parse an SVG document, keeping the shapes it paints.
+    pub fn parse(text: &str) -> Result<Svg, SvgError> {
+        let mut view_box = None;
+        let mut shapes = Vec::new();
+        // The styles of the open elements, and how deep in ignored ones.
+        let mut styles = vec![Style::default()];
+        let mut ignoring = 0;
+        let mut pos = 0;
+        while let Some(found) = text[pos..].find('<') {
+            let start = pos + found;
+            let rest = &text[start..];
+            let error = |message| SvgError {
+                offset: start,
+                message,
+            };
+            if rest.starts_with("<!--") {
+                let end = rest.find("-->").ok_or_else(|| error("unclosed comment"))?;
+                pos = start + end + 3;
+                continue;
+            }
+            if rest.starts_with("<![CDATA[") {
+                let end = rest.find("]]>").ok_or_else(|| error("unclosed CDATA"))?;
+                pos = start + end + 3;
+                continue;
+            }
+            let end = tag_end(rest).ok_or_else(|| error("unclosed tag"))?;
+            pos = start + end + 1;
+            let tag = &rest[1..end];
+            if tag.starts_with('?') || tag.starts_with('!') {
+                continue;
+            }
+            if tag.starts_with('/') {
+                if ignoring > 0 {
+                    ignoring -= 1;
+                } else if styles.len() > 1 {
+                    styles.pop();
+                }
+                continue;
+            }
+            let empty = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let name_end = tag
+                .find(|c: char| c.is_ascii_whitespace())
+                .unwrap_or(tag.len());
+            let name = &tag[..name_end];
+            if ignoring > 0 || IGNORED.contains(&name) {
+                if !empty {
+                    ignoring += 1;
+                }
+                continue;
+            }
+            let attrs = attributes(&tag[name_end..]).ok_or_else(|| error("bad attribute"))?;
+            let attr = |key| attrs.iter().find(|&&(k, _)| k == key).map(|&(_, v)| v);
+            let mut style = styles.last().cloned().unwrap_or_default();
+            for &(key, value) in &attrs {
+                style.set(key, value);
+            }
+            // Declarations in `style` take precedence over attributes.
+            if let Some(declarations) = attr("style") {
+                for declaration in declarations.split(';') {
+                    let mut parts = declaration.splitn(2, ':');
+                    if let (Some(key), Some(value)) = (parts.next(), parts.next()) {
+                        style.set(key.trim(), value.trim());
+                    }
+                }
+            }
+            let path = match name {
+                "svg" if view_box.is_none() => {
+                    view_box = match attr("viewBox").map(numbers) {
+                        Some(Some(ref v)) if v.len() == 4 => {
+                            Some(Rect::new(v[0], v[1], v[0] + v[2], v[1] + v[3]))
+                        }
+                        _ => match (attr("width"), attr("height")) {
+                            (Some(width), Some(height)) => Some(Rect::new(
+                                0.0,
+                                0.0,
+                                length(width).unwrap_or(0.0),
+                                length(height).unwrap_or(0.0),
+                            )),
+                            _ => None,
+                        },
+                    };
+                    None
+                }
+                "path" => match attr("d") {
+                    Some(d) => Some(parse_path(d).map_err(|e| error(e.message))?),
+                    None => None,
+                },
+                "rect" => {
+                    let num = |key| attr(key).and_then(length);
+                    let (x, y) = (num("x").unwrap_or(0.0), num("y").unwrap_or(0.0));
+                    let width = num("width").unwrap_or(0.0);
+                    let height = num("height").unwrap_or(0.0);
+                    let rx = num("rx").or(num("ry")).unwrap_or(0.0);
+                    let ry = num("ry").or(num("rx")).unwrap_or(0.0);
+                    if width > 0.0 && height > 0.0 {
+                        let rect = Rect::new(x, y, x + width, y + height);
+                        Some(rounded_rect(
+                            rect,
+                            rx.min(width / 2.0),
+                            ry.min(height / 2.0),
+                        ))
+                    } else {
+                        None
+                    }
+                }
+                "circle" | "ellipse" => {
+                    let num = |key| attr(key).and_then(length);
+                    let center = Point::new(num("cx").unwrap_or(0.0), num("cy").unwrap_or(0.0));
+                    let (rx, ry) = match num("r") {
+                        Some(r) if name == "circle" => (r, r),
+                        _ => (num("rx").unwrap_or(0.0), num("ry").unwrap_or(0.0)),
+                    };
+                    if rx > 0.0 && ry > 0.0 {
+                        Some(ellipse(center, Vec2::new(rx, ry)))
+                    } else {
+                        None
+                    }
+                }
+                "line" => {
+                    let num = |key| attr(key).and_then(length).unwrap_or(0.0);
+                    let mut path = BezPath::new();
+                    path.move_to((num("x1"), num("y1")));
+                    path.line_to((num("x2"), num("y2")));
+                    Some(path)
+                }
+                "polyline" | "polygon" => {
+                    let points = attr("points").map_or(Some(Vec::new()), numbers);
+                    let points = points.ok_or_else(|| error("bad points"))?;
+                    let mut path = BezPath::new();
+                    for (i, point) in points.chunks(2).filter(|p| p.len() == 2).enumerate() {
+                        if i == 0 {
+                            path.move_to((point[0], point[1]));
+                        } else {
+                            path.line_to((point[0], point[1]));
+                        }
+                    }
+                    if name == "polygon" && !path.is_empty() {
+                        path.close_path();
+                    }
+                    Some(path)
+                }
+                _ => None,
+            };
+            if let Some(mut path) = path {
+                path.apply_affine(style.transform);
+                shapes.push(style.shape(path));
+            }
+            if !empty {
+                styles.push(style);
+            }
+        }
+        let view_box = view_box.unwrap_or_else(|| {
+            let mut boxes = shapes.iter().map(|shape| shape.path.bounding_box());
+            let first = boxes
+                .next()
+                .unwrap_or_else(|| Rect::new(0.0, 0.0, 0.0, 0.0));
+            boxes.fold(first, |bounds, rect| bounds.union(rect))
+        });
+        Ok(Svg { view_box, shapes })
+    }
+
+    /// The part of the plane the document covers, in the coordinates of its
+    /// shapes: its `viewBox`, or else its size, or else the bounds of its
+    /// shapes.
+    pub fn view_box(&self) -> Rect {
+        self.view_box
+    }
+
+    /// The shapes, in the order to paint them.
+    pub fn shapes(&self) -> &[SvgShape] {
+        &self.shapes
+    }
+
+    /// Paint the document scaled to fit in `rect`, keeping its aspect
+    /// ratio, with `color` as the `currentColor`.
+    pub fn paint(&self, paint_ctx: &mut PaintCtx, rect: Rect, color: &Color) {
+        if self.view_box.width() <= 0.0 || self.view_box.height() <= 0.0 {
+            return;
+        }
+        let dst = FitMode::Contain.fit(self.view_box.size(), rect);
+        let scale = dst.width() / self.view_box.width();
+        let transform = Affine::translate(dst.origin().to_vec2())
+            * Affine::scale(scale)
+            * Affine::translate(Point::ORIGIN - self.view_box.origin());
+        for shape in &self.shapes {
+            let mut path = shape.path.clone();
+            path.apply_affine(transform);
+            if let Some(ref fill) = shape.fill {
+                paint_ctx.fill_color(&path, &fill.resolve(color), shape.fill_rule);
+            }
+            if let Some((ref paint, ref stroke)) = shape.stroke {
+                let mut stroke = stroke.clone();
+                stroke.width *= scale;
+                paint_ctx.stroke_color(&path, &paint.resolve(color), &stroke);
+            }
+        }
+    }
+}
+
+impl SvgPaint {
+    /// The color to paint, with `current` as the `currentColor`.
+    pub fn resolve(&self, current: &Color) -> Color {
+        match *self {
+            SvgPaint::Color(ref color) => color.clone(),
+            SvgPaint::CurrentColor(opacity) => fade(current, opacity),
+        }
+    }
+
+    /// This paint at `opacity` times its own.
+    fn fade(&self, opacity: f64) -> SvgPaint {
+        match *self {
+            SvgPaint::Color(ref color) => SvgPaint::Color(fade(color, opacity)),
+            SvgPaint::CurrentColor(own) => SvgPaint::CurrentColor(own * opacity),
+        }
+    }
+}
+
+impl Style {
+    /// Set the property named `key`, if it is one and `value` is valid.
+    fn set(&mut self, key: &str, value: &str) {
+        match key {
+            "fill" => {
+                if let Some(paint) = paint(value) {
+                    self.fill = paint;
+                }
+            }
+            "stroke" => {
+                if let Some(paint) = paint(value) {
+                    self.stroke = paint;
+                }
+            }
+            "fill-rule" => match value {
+                "nonzero" => self.fill_rule = FillRule::NonZero,
+                "evenodd" => self.fill_rule = FillRule::EvenOdd,
+                _ => (),
+            },
+            "stroke-linecap" => match value {
+                "butt" => self.line_cap = LineCap::Butt,
+                "round" => self.line_cap = LineCap::Round,
+                "square" => self.line_cap = LineCap::Square,
+                _ => (),
+            },
+            "stroke-linejoin" => match value {
+                "miter" => self.line_join = LineJoin::Miter,
+                "round" => self.line_join = LineJoin::Round,
+                "bevel" => self.line_join = LineJoin::Bevel,
+                _ => (),
+            },
+            "fill-opacity" => self.fill_opacity = length(value).unwrap_or(self.fill_opacity),
+            "stroke-opacity" => self.stroke_opacity = length(value).unwrap_or(self.stroke_opacity),
+            "stroke-width" => self.stroke_width = length(value).unwrap_or(self.stroke_width),
+            "opacity" => self.opacity *= length(value).unwrap_or(1.0),
+            "transform" => {
+                if let Some(transform) = transform(value) {
+                    self.transform *= transform;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    /// A shape painting `path`, already transformed, in this style.
+    fn shape(&self, path: BezPath) -> SvgShape {
+        let [a, b, c, d, _, _] = self.transform.as_coeffs();
+        let width = self.stroke_width * (a * d - b * c).abs().sqrt();
+        let stroke = self.stroke.as_ref().map(|paint| {
+            let stroke = Stroke::new(width)
+                .line_cap(self.line_cap)
+                .line_join(self.line_join);
+            (paint.fade(self.stroke_opacity * self.opacity), stroke)
+        });
+        SvgShape {
+            path,
+            fill: self
+                .fill
+                .as_ref()
+                .map(|paint| paint.fade(self.fill_opacity * self.opacity)),
+            fill_rule: self.fill_rule,
+            stroke,
+        }
+    }
+}
+
+/// `color` at `opacity` times its own.
+fn fade(color: &Color, opacity: f64) -> Color {
+    color.with_alpha(color.alpha() * opacity)
+}
+
+/// The offset of the `>` ending the tag at the start of `text`.
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(q), _) if q == c => quote = None,
+            (None, '>') => return Some(i),
+            _ => (),
+        }
+    }
+    None
+}
+
+/// The `key="value"` pairs of a tag.
+fn attributes(mut text: &str) -> Option<Vec<(&str, &str)>> {
+    let mut attrs = Vec::new();
+    loop {
+        text = text.trim_start();
+        if text.is_empty() {
+            return Some(attrs);
+        }
+        let eq = text.find('=')?;
+        let key = text[..eq].trim();
+        let rest = text[eq + 1..].trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let end = rest[1..].find(quote)? + 1;
+        attrs.push((key, &rest[1..end]));
+        text = &rest[end + 1..];
+    }
+}
+
+/// A paint, `None` for `none`, or `None` overall if `value` isn't one.
+#[allow(clippy::option_option)]
+fn paint(value: &str) -> Option<Option<SvgPaint>> {
+    let rgb = |rgb: u32| Some(Some(SvgPaint::Color(Color::rgb24(rgb))));
+    match value {
+        "none" | "transparent" => return Some(None),
+        "currentColor" => return Some(Some(SvgPaint::CurrentColor(1.0))),
+        "black" => return rgb(0x00_00_00),
+        "white" => return rgb(0xff_ff_ff),
+        "gray" | "grey" => return rgb(0x80_80_80),
+        "silver" => return rgb(0xc0_c0_c0),
+        "red" => return rgb(0xff_00_00),
+        "maroon" => return rgb(0x80_00_00),
+        "orange" => return rgb(0xff_a5_00),
+        "yellow" => return rgb(0xff_ff_00),
+        "olive" => return rgb(0x80_80_00),
+        "lime" => return rgb(0x00_ff_00),
+        "green" => return rgb(0x00_80_00),
+        "aqua" | "cyan" => return rgb(0x00_ff_ff),
+        "teal" => return rgb(0x00_80_80),
+        "blue" => return rgb(0x00_00_ff),
+        "navy" => return rgb(0x00_00_80),
+        "fuchsia" | "magenta" => return rgb(0xff_00_ff),
+        "purple" => return rgb(0x80_00_80),
+        _ => (),
+    }
+    if value.starts_with('#') {
+        return Color::from_hex_str(value)
+            .ok()
+            .map(|color| Some(SvgPaint::Color(color)));
+    }
+    if value.starts_with("rgb(") && value.ends_with(')') {
+        let channels = value[4..value.len() - 1]
+            .split(',')
+            .map(|channel| {
+                let channel = channel.trim();
+                let value = match channel.strip_suffix('%') {
+                    Some(percent) => percent.parse::<f64>().ok()? * 2.55,
+                    None => channel.parse::<f64>().ok()?,
+                };
+                Some(value.round().clamp(0.0, 255.0) as u32)
+            })
+            .collect::<Option<Vec<_>>>()?;
+        if channels.len() == 3 {
+            return rgb(channels[0] << 16 | channels[1] << 8 | channels[2]);
+        }
+    }
+    None
+}
+
+/// A list of numbers, separated by whitespace or commas.
+fn numbers(text: &str) -> Option<Vec<f64>> {
+    let mut lexer = Lexer::new(text);
+    let mut numbers = Vec::new();
+    loop {
+        lexer.skip_separators();
+        if lexer.peek().is_none() {
+            return Some(numbers);
+        }
+        numbers.push(lexer.number().ok()?);
+    }
+}
+
+/// A length in px, ignoring units.
+fn length(text: &str) -> Option<f64> {
+    Lexer::new(text).number().ok()
+}
+
+/// A list of transforms, such as `"translate(4 4) rotate(45)"`.
+fn transform(text: &str) -> Option<Affine> {
+    let mut result = Affine::default();
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let open = rest.find('(')?;
+        let close = open + rest[open..].find(')')?;
+        let name = rest[..open].trim_matches(|c: char| c == ',' || c.is_ascii_whitespace());
+        let args = numbers(&rest[open + 1..close])?;
+        let arg = |i: usize| args.get(i).cloned();
+        let transform = match (name, args.len()) {
+            ("matrix", 6) => Affine::new([args[0], args[1], args[2], args[3], args[4], args[5]]),
+            ("translate", 1) | ("translate", 2) => {
+                Affine::translate((args[0], arg(1).unwrap_or(0.0)))
+            }
+            ("scale", 1) | ("scale", 2) => {
+                Affine::new([args[0], 0.0, 0.0, arg(1).unwrap_or(args[0]), 0.0, 0.0])
+            }
+            ("rotate", 1) | ("rotate", 3) => {
+                let center = Vec2::new(arg(1).unwrap_or(0.0), arg(2).unwrap_or(0.0));
+                Affine::translate(center)
+                    * Affine::rotate(args[0].to_radians())
+                    * Affine::translate(-center)
+            }
+            ("skewX", 1) => Affine::new([1.0, 0.0, args[0].to_radians().tan(), 1.0, 0.0, 0.0]),
+            ("skewY", 1) => Affine::new([1.0, args[0].to_radians().tan(), 0.0, 1.0, 0.0, 0.0]),
+            _ => return None,
+        };
+        result *= transform;
+        rest = rest[close + 1..].trim_start();
+    }
+    Some(result)
+}
+
+fn rounded_rect(rect: Rect, rx: f64, ry: f64) -> BezPath {
+    let mut path = BezPath::new();
+    if rx <= 0.0 || ry <= 0.0 {
+        path.move_to((rect.x0, rect.y0));
+        path.line_to((rect.x1, rect.y0));
+        path.line_to((rect.x1, rect.y1));
+        path.line_to((rect.x0, rect.y1));
+        path.close_path();
+        return path;
+    }
+    let radii = Vec2::new(rx, ry);
+    let corner = |path: &mut BezPath, from: (f64, f64), to: (f64, f64)| {
+        arc_to(path, from.into(), radii, 0.0, false, true, to.into());
+        path.line_to(to);
+    };
+    path.move_to((rect.x0 + rx, rect.y0));
+    path.line_to((rect.x1 - rx, rect.y0));
+    corner(&mut path, (rect.x1 - rx, rect.y0), (rect.x1, rect.y0 + ry));
+    path.line_to((rect.x1, rect.y1 - ry));
+    corner(&mut path, (rect.x1, rect.y1 - ry), (rect.x1 - rx, rect.y1));
+    path.line_to((rect.x0 + rx, rect.y1));
+    corner(&mut path, (rect.x0 + rx, rect.y1), (rect.x0, rect.y1 - ry));
+    path.line_to((rect.x0, rect.y0 + ry));
+    corner(&mut path, (rect.x0, rect.y0 + ry), (rect.x0 + rx, rect.y0));
+    path.close_path();
+    path
+}
+
+fn ellipse(center: Point, radii: Vec2) -> BezPath {
+    let right = center + Vec2::new(radii.x, 0.0);
+    let left = center - Vec2::new(radii.x, 0.0);
+    let mut path = BezPath::new();
+    path.move_to(right);
+    arc_to(&mut path, right, radii, 0.0, false, true, left);
+    arc_to(&mut path, left, radii, 0.0, false, true, right);
+    path.close_path();
+    path
+}
+
+/// Numbers and flags in path data and attribute values.
+struct Lexer<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Lexer<'a> {
+    fn new(text: &'a str) -> Lexer<'a> {
+        Lexer { text, pos: 0 }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).cloned()
+    }
+
+    fn skip_separators(&mut self) {
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_whitespace() || c == b',') {
+                break;
+            }
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &'static str) -> SvgError {
+        SvgError {
+            offset: self.pos,
+            message,
+        }
+    }
+
+    fn number(&mut self) -> Result<f64, SvgError> {
+        self.skip_separators();
+        let start = self.pos;
+        if let Some(b'-') | Some(b'+') = self.peek() {
+            self.pos += 1;
+        }
+        let mut digits = self.digits();
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            digits += self.digits();
+        }
+        if digits == 0 {
+            self.pos = start;
+            return Err(self.error("expected a number"));
+        }
+        // An exponent, unless the `e` starts something else.
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            let mantissa_end = self.pos;
+            self.pos += 1;
+            if let Some(b'-') | Some(b'+') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                self.pos = mantissa_end;
+            }
+        }
+        self.text[start..self.pos]
+            .parse()
+            .map_err(|_| self.error("bad number"))
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn point(&mut self) -> Result<Point, SvgError> {
+        let x = self.number()?;
+        let y = self.number()?;
+        Ok(Point::new(x, y))
+    }
+
+    /// An arc flag, which needs no separator after it.
+    fn flag(&mut self) -> Result<bool, SvgError> {
+        self.skip_separators();
+        match self.peek() {
+            Some(b'0') => {
+                self.pos += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.pos += 1;
+                Ok(true)
+            }
+            _ => Err(self.error("expected a flag")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kurbo::PathEl;
+
+    /// The points of each element of `path`, with its kind.
+    fn elements(path: &BezPath) -> Vec<(char, Vec<(f64, f64)>)> {
+        let xy = |p: &Point| (p.x, p.y);
+        path.elements()
+            .iter()
+            .map(|el| match el {
+                PathEl::MoveTo(p) => ('M', vec![xy(p)]),
+                PathEl::LineTo(p) => ('L', vec![xy(p)]),
+                PathEl::QuadTo(p1, p2) => ('Q', vec![xy(p1), xy(p2)]),
+                PathEl::CurveTo(p1, p2, p3) => ('C', vec![xy(p1), xy(p2), xy(p3)]),
+                PathEl::ClosePath => ('Z', vec![]),
+            })
+            .collect()
+    }
+
+    fn path(data: &str) -> Vec<(char, Vec<(f64, f64)>)> {
+        elements(&parse_path(data).unwrap())
+    }
+
+    fn rgba(paint: &Option<SvgPaint>) -> Option<u32> {
+        paint
+            .as_ref()
+            .map(|paint| paint.resolve(&Color::rgb24(0x12_34_56)).as_rgba32())
+    }
+
+    #[test]
+    fn relative_and_absolute_commands() {
+        assert_eq!(
+            path("M10 10 l5 0 v5 h-5 z"),
+            path("M10 10 L15 10 V15 H10 Z")
+        );
+        assert_eq!(path("m1 2 c1 1 2 1 3 0"), path("M1 2 C2 3 3 3 4 2"));
+        assert_eq!(path("M1 1 q1 1 2 0 t2 0"), path("M1 1 Q2 2 3 1 T5 1"));
+        // After a close, relative commands start from the start again.
+        assert_eq!(path("M1 1 l2 0 z l0 2"), path("M1 1 L3 1 Z L1 3"));
+    }
+
+    #[test]
+    fn implicit_repeats() {
+        // Numbers after a move are lines, relative after a relative move.
+        assert_eq!(path("M0 0 10 0 10 10"), path("M0 0 L10 0 L10 10"));
+        assert_eq!(path("m1 1 2 0 0 2"), path("M1 1 L3 1 L3 3"));
+        assert_eq!(path("M0 0 h1 2 3"), path("M0 0 H1 H3 H6"));
+        assert_eq!(
+            path("M0 0 c0 1 1 1 1 0 0 1 1 1 1 0"),
+            path("M0 0 C0 1 1 1 1 0 C1 1 2 1 2 0")
+        );
+        // Without separators where the signs or points tell the numbers apart.
+        assert_eq!(path("M0,0L-1-2.5.5.5"), path("M0 0 L-1 -2.5 L0.5 0.5"));
+        assert_eq!(path("M0 0 L1e1 2E-1"), path("M0 0 L10 0.2"));
+    }
+
+    #[test]
+    fn smooth_curves_reflect_control_points() {
+        assert_eq!(
+            path("M0 0 C0 1 1 1 1 0 S2 -1 2 0"),
+            path("M0 0 C0 1 1 1 1 0 C1 -1 2 -1 2 0")
+        );
+        // Without a curve before, the control point is the current point.
+        assert_eq!(path("M0 0 S1 1 2 0"), path("M0 0 C0 0 1 1 2 0"));
+        assert_eq!(path("M0 0 L1 0 T2 0"), path("M0 0 L1 0 Q1 0 2 0"));
+    }
+
+    /// The ends of the segments of an arc from the origin to `(10, 0)`.
+    fn arc_points(data: &str) -> Vec<(f64, f64)> {
+        let elements = path(data);
+        assert_eq!(elements[0], ('M', vec![(0.0, 0.0)]));
+        let ends = elements[1..]
+            .iter()
+            .map(|(kind, points)| {
+                assert_eq!(*kind, 'C');
+                points[2]
+            })
+            .collect::<Vec<_>>();
+        let last = *ends.last().unwrap();
+        assert!((last.0 - 10.0).abs() < 1e-9 && last.1.abs() < 1e-9);
+        ends
+    }
+
+    fn all_at_distance(points: &[(f64, f64)], center: (f64, f64), radius: f64) -> bool {
+        points
+            .iter()
+            .all(|p| ((p.0 - center.0).hypot(p.1 - center.1) - radius).abs() < 1e-6)
+    }
+
+    #[test]
+    fn arcs_find_their_center() {
+        // A half circle, clockwise on screen through the top.
+        let points = arc_points("M0 0 A5 5 0 0 1 10 0");
+        assert!(all_at_distance(&points, (5.0, 0.0), 5.0));
+        assert!(points.iter().any(|p| (p.1 + 5.0).abs() < 1e-6));
+        // The other way round, through the bottom.
+        let points = arc_points("M0 0 A5 5 0 0 0 10 0");
+        assert!(points.iter().any(|p| (p.1 - 5.0).abs() < 1e-6));
+        // Radii too small to reach are scaled up to a half circle.
+        let points = arc_points("M0 0 A1 1 0 0 1 10 0");
+        assert!(all_at_distance(&points, (5.0, 0.0), 5.0));
+        // With a larger radius, the flags pick one of the two centers.
+        let dy = 75.0f64.sqrt();
+        let small = arc_points("M0 0 A10 10 0 0 1 10 0");
+        assert!(all_at_distance(&small, (5.0, dy), 10.0));
+        assert!(small.iter().all(|p| p.1 <= 1e-9));
+        let large = arc_points("M0 0 A10 10 0 1 1 10 0");
+        assert!(all_at_distance(&large, (5.0, -dy), 10.0));
+        assert!(large.iter().any(|p| p.1 < -10.0));
+        // Flags need no separators, and a zero radius is a line.
+        assert_eq!(arc_points("M0 0 A5 5 0 0110 0").len(), points.len());
+        assert_eq!(path("M0 0 A0 5 0 0 1 10 0"), path("M0 0 L10 0"));
+    }
+
+    #[test]
+    fn transform_lists() {
+        let apply = |text: &str, p: (f64, f64)| {
+            let p = transform(text).unwrap() * Point::new(p.0, p.1);
+            ((p.x * 1e6).round() / 1e6, (p.y * 1e6).round() / 1e6)
+        };
+        assert_eq!(apply("translate(10)", (1.0, 1.0)), (11.0, 1.0));
+        // Transforms apply right to left.
+        assert_eq!(apply("translate(10 0) scale(2)", (1.0, 1.0)), (12.0, 2.0));
+        assert_eq!(apply("scale(2),translate(10,0)", (1.0, 1.0)), (22.0, 2.0));
+        assert_eq!(apply("scale(2 3)", (1.0, 1.0)), (2.0, 3.0));
+        assert_eq!(apply("rotate(90)", (1.0, 0.0)), (0.0, 1.0));
+        assert_eq!(apply("rotate(90 5 5)", (10.0, 5.0)), (5.0, 10.0));
+        assert_eq!(apply("skewX(45)", (0.0, 2.0)), (2.0, 2.0));
+        assert_eq!(apply("matrix(1 0 0 1 3 4)", (1.0, 1.0)), (4.0, 5.0));
+        assert!(transform("rotate(1 2)").is_none());
+        assert!(transform("spin(45)").is_none());
+        assert!(transform("translate(1").is_none());
+        assert!(transform("scale)(2").is_none());
+    }
+
+    #[test]
+    fn colors_and_none() {
+        assert_eq!(paint("none").map(|p| rgba(&p)), Some(None));
+        assert_eq!(paint("red").map(|p| rgba(&p)), Some(Some(0xff_00_00_ff)));
+        assert_eq!(paint("#0f0").map(|p| rgba(&p)), Some(Some(0x00_ff_00_ff)));
+        assert_eq!(
+            paint("rgb(255, 0, 128)").map(|p| rgba(&p)),
+            Some(Some(0xff_00_80_ff))
+        );
+        assert_eq!(
+            paint("rgb(100%,0%,20%)").map(|p| rgba(&p)),
+            Some(Some(0xff_00_33_ff))
+        );
+        assert_eq!(
+            paint("currentColor").map(|p| rgba(&p)),
+            Some(Some(0x12_34_56_ff))
+        );
+        assert!(paint("rgb(1, 2)").is_none());
+        assert!(paint("#12").is_none());
+        assert!(paint("sparkly").is_none());
+
+        let svg = Svg::parse(
+            r#"<svg viewBox="0 0 10 10"><g fill="none" stroke="blue">
+                <rect width="4" height="4"/>
+                <circle r="2" style="fill: #ff0000; stroke: none"/>
+                <path d="M0 0 L1 1" fill="bogus" opacity="0.5"/>
+            </g></svg>"#,
+        )
+        .unwrap();
+        let view_box = svg.view_box();
+        assert_eq!((view_box.x0, view_box.y1), (0.0, 10.0));
+        let shapes = svg.shapes();
+        assert_eq!(shapes.len(), 3);
+        assert_eq!(rgba(&shapes[0].fill), None);
+        let stroke = shapes[0].stroke.as_ref().map(|(paint, _)| paint.clone());
+        assert_eq!(rgba(&stroke), Some(0x00_00_ff_ff));
+        assert_eq!(rgba(&shapes[1].fill), Some(0xff_00_00_ff));
+        assert!(shapes[1].stroke.is_none());
+        // An invalid paint leaves the inherited one.
+        assert_eq!(rgba(&shapes[2].fill), None);
+        let stroke = shapes[2].stroke.as_ref().map(|(paint, _)| paint.clone());
+        assert_eq!(rgba(&stroke), Some(0x00_00_ff_80));
+    }
+
+    #[test]
+    fn malformed_input_is_an_error() {
+        assert!(parse_path("").unwrap().elements().is_empty());
+        let error = |data: &str| parse_path(data).unwrap_err();
+        assert_eq!(error("L1 1").message, "expected a move");
+        assert_eq!(error("M1").message, "expected a number");
+        assert_eq!(error("M1 1 X2 2").message, "unknown command");
+        assert_eq!(error("M1 1 Z 2 2").message, "expected a command");
+        assert_eq!(error("M0 0 A1 1 0 2 0 3 3").message, "expected a flag");
+        assert_eq!(error("M0 0 L1 é").offset, 8);
+        for text in &["<svg", "<svg><!-- x", "<svg><path d='M0 0 L'/></svg>"] {
+            assert!(Svg::parse(text).is_err(), "{} parsed", text);
+        }
+        assert!(Svg::parse("<svg><polygon points='0 0 x'/></svg>").is_err());
+        assert!(Svg::parse("<svg><rect width='1/></svg>").is_err());
+    }
+
+    #[test]
+    fn truncated_input_does_not_panic() {
+        let data = "M10 10 l-5.5e1,0 C1 2 3 4 5 6 s1 1 2 2 q1 1 2 2 T3 3 a5 5 30 1 0 10 0 z";
+        let document = r#"<?xml version="1.0"?><!-- é --><svg width="24px" height="24">
+            <g transform="rotate(45 12 12) scale(.5)" style="fill:rgb(10%,20%,30%)">
+            <path d="M0 0 H10 V10"/><ellipse cx="5" cy="5" rx="2" ry="3"/>
+            <polyline points="0,0 1,2 3,4" stroke='#abc'/></g><text>é</text></svg>"#;
+        for text in &[data, document] {
+            for (end, _) in text.char_indices() {
+                let _ = parse_path(&text[..end]);
+                let _ = Svg::parse(&text[..end]);
+            }
+        }
+        assert_eq!(Svg::parse(document).unwrap().shapes().len(), 3);
+    }
+}
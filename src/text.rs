@@ -0,0 +1,1056 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Laid out text, measured for layout and hit testing.
+
+use std::collections::HashMap;
+use std::ops::Range;
+use std::rc::Rc;
+
+use druid_shell::{bidi as platform_bidi, bitmap, font_fallback};
+
+use crate::display_list::PietTextLayout;
+use crate::kurbo::{Affine, Point, Rect, Size};
+use crate::piet::{
+    self, Color, FillRule, FontBuilder, Piet, RenderContext, Text, TextLayoutBuilder,
+};
+
+mod bidi;
+pub mod editing;
+pub(crate) mod keybindings;
+pub(crate) mod rich;
+
+use self::rich::{FontStyle, FontWeight, RichText, RunStyle};
+
+/// The height of a line, as a multiple of the font size.
+const LINE_HEIGHT: f64 = 1.2;
+
+type PietFont = <<Piet<'static> as RenderContext>::Text as Text>::Font;
+
+/// The horizontal offset of the second drawing of faux bold text, and the
+/// slant of faux italic text, as multiples of the font size and height.
+const BOLD_OFFSET: f64 = 1.0 / 30.0;
+const ITALIC_SLANT: f64 = 0.2;
+
+/// The char shown where text is cut short.
+const ELLIPSIS: &str = "\u{2026}";
+
+/// A string laid out in a font, split into lines at newlines and, with a
+/// maximum width, as its `LineBreaking` says; built once and drawn as often
+/// as needed.
+///
+/// The text may be `RichText`, whose attributes override the font of the
+/// layout and the color it is drawn with. piet can't choose the weight or
+/// style of a font, so bold and italic text are drawn by thickening and
+/// slanting the regular face.
+///
+/// The layout is built the first time it is drawn or measured, and again
+/// after the text or font changes. Offsets are byte offsets into the text,
+/// at char boundaries.
+pub struct TextLayout {
+    text: RichText,
+    font_name: String,
+    font_size: f64,
+    max_width: Option<f64>,
+    line_breaking: LineBreaking,
+    max_lines: Option<usize>,
+    lines: Option<Vec<Line>>,
+}
+
+/// How lines too long for the maximum width of a `TextLayout` are shown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineBreaking {
+    /// Lines run past the width, as if there were none.
+    #[default]
+    Overflow,
+    /// Lines are broken after spaces to fit the width, and within words
+    /// too long for it.
+    WordWrap,
+    /// Lines are cut short after the last char that fits.
+    Clip,
+    /// The end of a line too long is replaced with an ellipsis.
+    EllipsisEnd,
+    /// The middle of a line too long is replaced with an ellipsis.
+    EllipsisMiddle,
+}
+
+/// The metrics of one line of a `TextLayout`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LineMetrics {
+    /// The range of the line in the text, without its newline.
+    pub range: Range<usize>,
+    /// The top of the line, from the top of the layout.
+    pub y_offset: f64,
+    pub width: f64,
+    pub height: f64,
+    /// The baseline, from the top of the line.
+    pub baseline: f64,
+    /// Whether the line is a right to left paragraph, by its first strong
+    /// char. Such lines are aligned to the right of the layout.
+    pub rtl: bool,
+}
+
+/// Where a point is in a `TextLayout`, from `TextLayout::hit_test`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HitTestPoint {
+    /// The offset of the char boundary nearest to the point, where a click
+    /// places the caret.
+    pub offset: usize,
+    /// The index of the line nearest to the point.
+    pub line: usize,
+    /// Whether the point is on the text of the line, rather than beside,
+    /// above or below it.
+    pub is_inside: bool,
+}
+
+struct Line {
+    metrics: LineMetrics,
+    /// The runs in the order of the text.
+    runs: Vec<Run>,
+}
+
+/// A part of a line in one style and direction.
+struct Run {
+    layout: Option<Rc<PietTextLayout>>,
+    range: Range<usize>,
+    /// The distance of the run from the left of the layout.
+    x: f64,
+    width: f64,
+    /// The offset of each grapheme cluster boundary in the run, with the
+    /// advance of the text before it.
+    advances: Vec<(usize, f64)>,
+    rtl: bool,
+    style: RunStyle,
+}
+
+/// A part of the painting of a `TextLayout`, with the color it is drawn
+/// with if the text doesn't set one.
+pub(crate) enum TextOp {
+    /// Glyphs with their baseline starting at the point, slanted by the
+    /// transform of faux italics.
+    Glyphs(Rc<PietTextLayout>, Point, Option<Affine>, Option<Color>),
+    /// An underline or strikethrough.
+    Line(Rect, Option<Color>),
+}
+
+impl TextLayout {
+    pub fn new(
+        text: impl Into<String>,
+        font_name: impl Into<String>,
+        font_size: f64,
+    ) -> TextLayout {
+        TextLayout {
+            text: RichText::new(text),
+            font_name: font_name.into(),
+            font_size,
+            max_width: None,
+            line_breaking: LineBreaking::default(),
+            max_lines: None,
+            lines: None,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        self.text.as_str()
+    }
+
+    pub fn set_text(&mut self, text: impl Into<String>) {
+        self.set_rich_text(RichText::new(text));
+    }
+
+    pub fn rich_text(&self) -> &RichText {
+        &self.text
+    }
+
+    pub fn set_rich_text(&mut self, text: RichText) {
+        if text != self.text {
+            self.text = text;
+            self.lines = None;
+        }
+    }
+
+    pub fn font_size(&self) -> f64 {
+        self.font_size
+    }
+
+    pub fn set_font(&mut self, font_name: impl Into<String>, font_size: f64) {
+        let font_name = font_name.into();
+        if font_name != self.font_name || font_size != self.font_size {
+            self.font_name = font_name;
+            self.font_size = font_size;
+            self.lines = None;
+        }
+    }
+
+    /// Set the width lines are fitted to, as `line_breaking` says, or none
+    /// for lines as long as their text.
+    pub fn set_max_width(&mut self, max_width: Option<f64>) {
+        if max_width != self.max_width {
+            self.max_width = max_width;
+            self.lines = None;
+        }
+    }
+
+    pub fn set_line_breaking(&mut self, line_breaking: LineBreaking) {
+        if line_breaking != self.line_breaking {
+            self.line_breaking = line_breaking;
+            self.lines = None;
+        }
+    }
+
+    /// Set the number of lines shown, or none for all of them. The last
+    /// line shown ends with an ellipsis if there is more text, unless lines
+    /// are clipped.
+    pub fn set_max_lines(&mut self, max_lines: Option<usize>) {
+        if max_lines != self.max_lines {
+            self.max_lines = max_lines;
+            self.lines = None;
+        }
+    }
+
+    /// Build the layout with the text factory of `render_ctx`, if it is
+    /// stale.
+    pub fn rebuild_if_needed(&mut self, render_ctx: &mut Piet) {
+        if self.lines.is_none() {
+            self.lines = Some(self.build(render_ctx));
+        }
+    }
+
+    /// The size of the text, for layout: the width of its widest line by
+    /// the height of its lines.
+    pub fn size(&mut self) -> Size {
+        let lines = self.lines();
+        let width = lines
+            .iter()
+            .map(|line| line.metrics.width)
+            .fold(0.0, f64::max);
+        let height = lines
+            .last()
+            .map_or(0.0, |line| line.metrics.y_offset + line.metrics.height);
+        Size::new(width, height)
+    }
+
+    pub fn line_metrics(&mut self) -> Vec<LineMetrics> {
+        self.lines()
+            .iter()
+            .map(|line| line.metrics.clone())
+            .collect()
+    }
+
+    /// Draw the text with its top left corner at `origin`.
+    pub fn draw(
+        &mut self,
+        render_ctx: &mut Piet,
+        origin: Point,
+        brush: &<Piet as RenderContext>::Brush,
+    ) {
+        self.rebuild_if_needed(render_ctx);
+        for op in self.paint_ops(origin) {
+            let (color, slant) = match op {
+                TextOp::Glyphs(_, _, ref slant, ref color) => (color, *slant),
+                TextOp::Line(_, ref color) => (color, None),
+            };
+            let made = color
+                .as_ref()
+                .map(|color| render_ctx.solid_brush(color.clone()));
+            let op_brush = made.as_ref().unwrap_or(brush);
+            match op {
+                TextOp::Glyphs(layout, pos, _, _) => {
+                    let _ = render_ctx.save();
+                    if let Some(slant) = slant {
+                        render_ctx.transform(slant);
+                    }
+                    render_ctx.draw_text(&*layout, pos, op_brush);
+                    let _ = render_ctx.restore();
+                }
+                TextOp::Line(rect, _) => render_ctx.fill(rect, op_brush, FillRule::NonZero),
+            }
+        }
+    }
+
+    /// The painting of the built text at `origin`, run by run.
+    pub(crate) fn paint_ops(&self, origin: Point) -> Vec<TextOp> {
+        let mut ops = Vec::new();
+        for line in self.lines.iter().flatten() {
+            let y = origin.y + line.metrics.y_offset + line.metrics.baseline;
+            for run in &line.runs {
+                let style = &run.style;
+                let pos = Point::new(origin.x + run.x, y);
+                if let Some(ref layout) = run.layout {
+                    let slant = match style.style {
+                        FontStyle::Italic => Some(Affine::new([
+                            1.0,
+                            0.0,
+                            -ITALIC_SLANT,
+                            1.0,
+                            ITALIC_SLANT * y,
+                            0.0,
+                        ])),
+                        FontStyle::Normal => None,
+                    };
+                    let color = style.color.clone();
+                    ops.push(TextOp::Glyphs(layout.clone(), pos, slant, color.clone()));
+                    if style.weight >= FontWeight::SEMIBOLD {
+                        let pos = Point::new(pos.x + style.size * BOLD_OFFSET, pos.y);
+                        ops.push(TextOp::Glyphs(layout.clone(), pos, slant, color));
+                    }
+                }
+                let thickness = (style.size / 16.0).max(1.0);
+                let mut decorate = |y: f64| {
+                    let rect = Rect::new(pos.x, y, pos.x + run.width, y + thickness);
+                    ops.push(TextOp::Line(rect, style.color.clone()));
+                };
+                if style.underline {
+                    decorate(y + style.size * 0.1);
+                }
+                if style.strikethrough {
+                    decorate(y - style.size * 0.3);
+                }
+            }
+        }
+        ops
+    }
+
+    /// The ranges of the links in the text, with their targets and the
+    /// rectangles covering them, relative to the top left corner of the
+    /// text.
+    pub fn link_rects(&mut self) -> Vec<(Range<usize>, String, Vec<Rect>)> {
+        let links = self
+            .text
+            .links()
+            .map(|(range, target)| (range, target.to_string()))
+            .collect::<Vec<_>>();
+        links
+            .into_iter()
+            .map(|(range, target)| {
+                let rects = self.rects_for_range(range.clone());
+                (range, target, rects)
+            })
+            .collect()
+    }
+
+    /// The offset of the char boundary nearest to `point`, relative to the
+    /// top left corner of the text.
+    pub fn hit_test_point(&mut self, point: Point) -> usize {
+        self.hit_test(point).offset
+    }
+
+    /// Where `point`, relative to the top left corner of the text, is in
+    /// the text.
+    pub fn hit_test(&mut self, point: Point) -> HitTestPoint {
+        let lines = self.lines();
+        let index = lines
+            .iter()
+            .position(|line| point.y < line.metrics.y_offset + line.metrics.height)
+            .unwrap_or_else(|| lines.len().saturating_sub(1));
+        let line = match lines.get(index) {
+            Some(line) => line,
+            None => {
+                return HitTestPoint {
+                    offset: 0,
+                    line: 0,
+                    is_inside: false,
+                };
+            }
+        };
+        let is_inside = point.y >= line.metrics.y_offset
+            && point.y < line.metrics.y_offset + line.metrics.height
+            && line.run_at_x(point.x).is_some();
+        let mut nearest = line.metrics.range.start;
+        let mut distance = std::f64::INFINITY;
+        for run in &line.runs {
+            for &(offset, advance) in &run.advances {
+                let x = run.x_of_advance(advance);
+                if (x - point.x).abs() < distance {
+                    nearest = offset;
+                    distance = (x - point.x).abs();
+                }
+            }
+        }
+        HitTestPoint {
+            offset: nearest,
+            line: index,
+            is_inside,
+        }
+    }
+
+    /// The offset of the char drawn under `point`, relative to the top left
+    /// corner of the text, if any; for finding what the pointer is over,
+    /// such as a link.
+    pub fn char_at_point(&mut self, point: Point) -> Option<usize> {
+        let line = self.lines().iter().find(|line| {
+            point.y >= line.metrics.y_offset
+                && point.y < line.metrics.y_offset + line.metrics.height
+        })?;
+        let run = line.run_at_x(point.x)?;
+        let advance = if run.rtl {
+            run.x + run.width - point.x
+        } else {
+            point.x - run.x
+        };
+        run.advances
+            .iter()
+            .take_while(|&&(_, start)| start <= advance)
+            .last()
+            .map(|&(offset, _)| offset)
+            .filter(|&offset| offset < run.range.end)
+    }
+
+    /// The position of a caret before the char at `offset`: the top of its
+    /// line, relative to the top left corner of the text.
+    pub fn hit_test_offset(&mut self, offset: usize) -> Point {
+        let lines = self.lines();
+        let line = lines
+            .iter()
+            .find(|line| offset <= line.metrics.range.end)
+            .or_else(|| lines.last());
+        match line {
+            Some(line) => Point::new(line.x_at(offset), line.metrics.y_offset),
+            None => Point::ORIGIN,
+        }
+    }
+
+    /// The rectangles covering the text in `range`, relative to the top
+    /// left corner of the text: one for each line it touches, or more where
+    /// the line changes direction.
+    pub fn rects_for_range(&mut self, range: Range<usize>) -> Vec<Rect> {
+        let mut rects = Vec::new();
+        for line in self.lines() {
+            let metrics = &line.metrics;
+            if range.start > metrics.range.end || range.end < metrics.range.start {
+                continue;
+            }
+            let (y0, y1) = (metrics.y_offset, metrics.y_offset + metrics.height);
+            // An empty range only touches the line it is in.
+            if range.start == range.end {
+                let x = line.x_at(range.start);
+                rects.push(Rect::new(x, y0, x, y1));
+                continue;
+            }
+            for run in &line.runs {
+                let start = range.start.max(run.range.start);
+                let end = range.end.min(run.range.end);
+                if start < end {
+                    let (xa, xb) = (run.x_at(start), run.x_at(end));
+                    rects.push(Rect::new(xa.min(xb), y0, xa.max(xb), y1));
+                }
+            }
+        }
+        rects
+    }
+
+    /// The lines, built in a scratch bitmap if the layout is stale, so that
+    /// text can be measured outside of painting.
+    fn lines(&mut self) -> &[Line] {
+        if self.lines.is_none() {
+            let mut lines = None;
+            let _ = bitmap::render_to_pixels(1, 1, 1.0, |render_ctx| {
+                lines = Some(self.build(render_ctx));
+            });
+            self.lines = Some(lines.unwrap_or_default());
+        }
+        self.lines.as_ref().unwrap()
+    }
+
+    fn build(&self, render_ctx: &mut Piet) -> Vec<Line> {
+        let mut fonts = Fonts::default();
+        let paragraphs = self.text.as_str().split('\n').collect::<Vec<_>>();
+        let max_width = self.max_width.unwrap_or(f64::INFINITY);
+        let mut lines = Vec::new();
+        let mut start = 0;
+        let mut y_offset = 0.0;
+        for (i, text) in paragraphs.iter().enumerate() {
+            let para = self.measure_paragraph(render_ctx, &mut fonts, start..start + text.len());
+            let ranges = match (self.line_breaking, self.max_width) {
+                (LineBreaking::WordWrap, Some(max_width)) => {
+                    para.wrap(self.text.as_str(), max_width)
+                }
+                _ => vec![para.range.clone()],
+            };
+            for (j, range) in ranges.iter().enumerate() {
+                if Some(lines.len()) == self.max_lines {
+                    return Self::align(lines);
+                }
+                let is_last = Some(lines.len() + 1) == self.max_lines;
+                let more = j + 1 < ranges.len() || i + 1 < paragraphs.len();
+                let text = self.text.as_str();
+                let pieces = if is_last && more && self.line_breaking != LineBreaking::Clip {
+                    let line = LineFit {
+                        range: range.start..para.range.end,
+                        max_width,
+                        line_breaking: LineBreaking::EllipsisEnd,
+                        more: true,
+                    };
+                    para.fit(text, render_ctx, &mut fonts, line)
+                } else {
+                    let line = LineFit {
+                        range: range.clone(),
+                        max_width,
+                        line_breaking: self.line_breaking,
+                        more: false,
+                    };
+                    para.fit(text, render_ctx, &mut fonts, line)
+                };
+                let line = self.build_line(render_ctx, &mut fonts, &para, &pieces, y_offset);
+                y_offset += line.metrics.height;
+                lines.push(line);
+            }
+            start += text.len() + 1;
+        }
+        Self::align(lines)
+    }
+
+    /// The runs of the paragraph of the text at `range`, measured.
+    fn measure_paragraph(
+        &self,
+        render_ctx: &mut Piet,
+        fonts: &mut Fonts,
+        range: Range<usize>,
+    ) -> Paragraph {
+        let default = RunStyle::new(&self.font_name, self.font_size);
+        let text = &self.text.as_str()[range.clone()];
+        let start = range.start;
+        let level = bidi::paragraph_level(text);
+        let mut levels = vec![level; text.len()];
+        for ((offset, c), char_level) in text.char_indices().zip(bidi::levels(text, level)) {
+            levels[offset..offset + c.len_utf8()]
+                .iter_mut()
+                .for_each(|l| *l = char_level);
+        }
+        let mut x = 0.0;
+        let runs = self
+            .text
+            .runs(range.clone(), &default)
+            .into_iter()
+            .flat_map(|(range, style)| fallback_runs(self.text.as_str(), range, style))
+            .flat_map(|(range, style)| {
+                split_by_level(range, style, |offset| levels[offset - start])
+            })
+            .map(|(range, style, level)| {
+                let font = fonts.index(render_ctx, &style);
+                let run_text = &self.text.as_str()[range.clone()];
+                // Each cluster is measured on its own, once per font, so
+                // that long text isn't shaped again for every prefix.
+                let mut advance = 0.0;
+                let mut advances = vec![(range.start, 0.0)];
+                for cluster in editing::grapheme_ranges(run_text) {
+                    let cluster_text = &run_text[cluster.clone()];
+                    advance += fonts.cluster_advance(render_ctx, font, cluster_text);
+                    advances.push((range.start + cluster.end, advance));
+                }
+                let run = MeasuredRun {
+                    range,
+                    style,
+                    level,
+                    font,
+                    x,
+                    advances,
+                };
+                x += run.advances.last().map_or(0.0, |&(_, end)| end);
+                run
+            })
+            .collect();
+        Paragraph {
+            range,
+            level,
+            default,
+            runs,
+        }
+    }
+
+    /// A line of the pieces of paragraph `para`, at `y_offset` from the top
+    /// of the layout.
+    fn build_line(
+        &self,
+        render_ctx: &mut Piet,
+        fonts: &mut Fonts,
+        para: &Paragraph,
+        pieces: &[Piece],
+        y_offset: f64,
+    ) -> Line {
+        let text = self.text.as_str();
+        let mut line_runs = Vec::new();
+        for piece in pieces {
+            match *piece {
+                Piece::Text(ref range) => {
+                    for run in &para.runs {
+                        let start = range.start.max(run.range.start);
+                        let end = range.end.min(run.range.end);
+                        if start >= end {
+                            continue;
+                        }
+                        let base = run.advance_at(start);
+                        let advances = run
+                            .advances
+                            .iter()
+                            .filter(|&&(offset, _)| offset >= start && offset <= end)
+                            .map(|&(offset, advance)| (offset, advance - base))
+                            .collect::<Vec<_>>();
+                        let rtl = run.level % 2 == 1;
+                        let run_text = &text[start..end];
+                        let layout = if rtl && !platform_bidi::ORDERS_TEXT {
+                            fonts.layout(render_ctx, run.font, &visual_text(run_text))
+                        } else {
+                            fonts.layout(render_ctx, run.font, run_text)
+                        };
+                        line_runs.push((
+                            run.level,
+                            Run {
+                                layout: layout.map(Rc::new),
+                                range: start..end,
+                                x: 0.0,
+                                width: run.advance_at(end) - base,
+                                advances,
+                                rtl,
+                                style: run.style.clone(),
+                            },
+                        ));
+                    }
+                }
+                Piece::Ellipsis(offset) => {
+                    let style = para.style_at(offset).clone();
+                    let font = fonts.index(render_ctx, &style);
+                    line_runs.push((
+                        para.level,
+                        Run {
+                            layout: fonts.layout(render_ctx, font, ELLIPSIS).map(Rc::new),
+                            range: offset..offset,
+                            x: 0.0,
+                            width: fonts.advance(render_ctx, font, ELLIPSIS),
+                            advances: vec![(offset, 0.0)],
+                            rtl: para.level % 2 == 1,
+                            style,
+                        },
+                    ));
+                }
+            }
+        }
+        let range = match (pieces.first(), pieces.last()) {
+            (Some(first), Some(last)) => first.start()..last.end(),
+            _ => para.range.clone(),
+        };
+        // An empty line is as tall as the default font.
+        if line_runs.is_empty() {
+            line_runs.push((
+                para.level,
+                Run {
+                    layout: None,
+                    range: range.start..range.start,
+                    x: 0.0,
+                    width: 0.0,
+                    advances: vec![(range.start, 0.0)],
+                    rtl: para.level % 2 == 1,
+                    style: para.default.clone(),
+                },
+            ));
+        }
+        let order = bidi::visual_order(&line_runs.iter().map(|r| r.0).collect::<Vec<_>>());
+        let mut x = 0.0;
+        for i in order {
+            line_runs[i].1.x = x;
+            x += line_runs[i].1.width;
+        }
+        let runs = line_runs
+            .into_iter()
+            .map(|(_, run)| run)
+            .collect::<Vec<_>>();
+        let size = runs.iter().map(|run| run.style.size).fold(0.0, f64::max);
+        // Spaces a line is broken after don't count towards its width.
+        let trimmed = range.start + text[range.clone()].trim_end().len();
+        let hanging = match pieces.last() {
+            Some(Piece::Text(_)) => para.width_of(&(trimmed..range.end)),
+            _ => 0.0,
+        };
+        Line {
+            metrics: LineMetrics {
+                range,
+                y_offset,
+                width: x - hanging,
+                height: size * LINE_HEIGHT,
+                baseline: size,
+                rtl: para.level == 1,
+            },
+            runs,
+        }
+    }
+
+    /// `lines`, with those of right to left paragraphs moved to the right
+    /// of the layout.
+    fn align(mut lines: Vec<Line>) -> Vec<Line> {
+        let width = lines
+            .iter()
+            .map(|line| line.metrics.width)
+            .fold(0.0, f64::max);
+        for line in lines.iter_mut().filter(|line| line.metrics.rtl) {
+            let shift = width - line.metrics.width;
+            line.runs.iter_mut().for_each(|run| run.x += shift);
+        }
+        lines
+    }
+}
+
+/// A paragraph of the text, measured in the order of the text before it
+/// is broken into lines.
+struct Paragraph {
+    range: Range<usize>,
+    level: u8,
+    default: RunStyle,
+    runs: Vec<MeasuredRun>,
+}
+
+struct MeasuredRun {
+    range: Range<usize>,
+    style: RunStyle,
+    level: u8,
+    /// The index of the font in the layout's `Fonts`.
+    font: usize,
+    /// The advance of the paragraph before the run.
+    x: f64,
+    /// The offset of each grapheme cluster boundary in the run, with the
+    /// advance of the run before it.
+    advances: Vec<(usize, f64)>,
+}
+
+/// A line for `Paragraph::fit` to fit.
+struct LineFit {
+    range: Range<usize>,
+    max_width: f64,
+    line_breaking: LineBreaking,
+    /// Whether more text follows, so that the line ends with an ellipsis.
+    more: bool,
+}
+
+/// A part of a line: text of the paragraph, or an ellipsis in place of
+/// text left out at an offset.
+enum Piece {
+    Text(Range<usize>),
+    Ellipsis(usize),
+}
+
+impl Piece {
+    fn start(&self) -> usize {
+        match *self {
+            Piece::Text(ref range) => range.start,
+            Piece::Ellipsis(offset) => offset,
+        }
+    }
+
+    fn end(&self) -> usize {
+        match *self {
+            Piece::Text(ref range) => range.end,
+            Piece::Ellipsis(offset) => offset,
+        }
+    }
+}
+
+impl Paragraph {
+    /// The advance of the paragraph before `offset`.
+    fn advance_at(&self, offset: usize) -> f64 {
+        self.runs
+            .iter()
+            .find(|run| offset <= run.range.end)
+            .map_or(0.0, |run| run.x + run.advance_at(offset))
+    }
+
+    fn width_of(&self, range: &Range<usize>) -> f64 {
+        self.advance_at(range.end) - self.advance_at(range.start)
+    }
+
+    fn style_at(&self, offset: usize) -> &RunStyle {
+        self.runs
+            .iter()
+            .find(|run| offset <= run.range.end)
+            .map_or(&self.default, |run| &run.style)
+    }
+
+    /// The ranges of the lines of the paragraph broken to fit `max_width`.
+    fn wrap(&self, text: &str, max_width: f64) -> Vec<Range<usize>> {
+        let mut ranges = Vec::new();
+        let mut line_start = self.range.start;
+        let mut opportunity = None;
+        let clusters = editing::grapheme_ranges(&text[self.range.clone()]);
+        for cluster in clusters {
+            let cluster = self.range.start + cluster.start..self.range.start + cluster.end;
+            // Spaces may hang past the end of a line, which breaks after them.
+            if text[cluster.clone()].trim().is_empty() {
+                opportunity = Some(cluster.end);
+                continue;
+            }
+            while cluster.start > line_start
+                && self.width_of(&(line_start..cluster.end)) > max_width
+            {
+                let end = opportunity
+                    .take()
+                    .filter(|&end| end > line_start)
+                    .unwrap_or(cluster.start);
+                ranges.push(line_start..end);
+                line_start = end;
+            }
+        }
+        ranges.push(line_start..self.range.end);
+        ranges
+    }
+
+    /// The pieces of a line of the text at `line.range`, fitted to
+    /// `line.max_width` as `line.line_breaking` says, and ending with an
+    /// ellipsis if more text follows.
+    fn fit(
+        &self,
+        text: &str,
+        render_ctx: &mut Piet,
+        fonts: &mut Fonts,
+        line: LineFit,
+    ) -> Vec<Piece> {
+        let LineFit {
+            range,
+            max_width,
+            line_breaking,
+            more,
+        } = line;
+        let fits = match line_breaking {
+            LineBreaking::Overflow | LineBreaking::WordWrap => true,
+            _ => self.width_of(&range) <= max_width,
+        };
+        if fits && !more {
+            return vec![Piece::Text(range)];
+        }
+        let font = fonts.index(render_ctx, self.style_at(range.start));
+        let ellipsis = fonts.advance(render_ctx, font, ELLIPSIS);
+        let start = range.start;
+        let ends = editing::grapheme_ranges(&text[range.clone()])
+            .into_iter()
+            .map(|cluster| start + cluster.end)
+            .collect::<Vec<_>>();
+        // The end of the longest prefix no wider than `width`.
+        let prefix = |width: f64| {
+            ends.iter()
+                .cloned()
+                .take_while(|&end| self.width_of(&(start..end)) <= width)
+                .last()
+                .unwrap_or(start)
+        };
+        match line_breaking {
+            LineBreaking::Clip => vec![Piece::Text(start..prefix(max_width))],
+            LineBreaking::EllipsisMiddle if !more => {
+                let budget = max_width - ellipsis;
+                let head = prefix(budget / 2.0);
+                let left = budget - self.width_of(&(start..head));
+                let tail = ends
+                    .iter()
+                    .cloned()
+                    .filter(|&end| end >= head)
+                    .find(|&end| self.width_of(&(end..range.end)) <= left)
+                    .unwrap_or(range.end);
+                vec![
+                    Piece::Text(start..head),
+                    Piece::Ellipsis(head),
+                    Piece::Text(tail..range.end),
+                ]
+            }
+            _ => {
+                let end = prefix(max_width - ellipsis);
+                vec![Piece::Text(start..end), Piece::Ellipsis(end)]
+            }
+        }
+    }
+}
+
+impl MeasuredRun {
+    /// The advance of the run before `offset`.
+    fn advance_at(&self, offset: usize) -> f64 {
+        self.advances
+            .iter()
+            .take_while(|&&(boundary, _)| boundary <= offset)
+            .last()
+            .map_or(0.0, |&(_, advance)| advance)
+    }
+}
+
+/// The fonts made for building a layout, with the width of the bars that
+/// text is measured between, and the advance of each cluster measured in
+/// each font.
+#[derive(Default)]
+struct Fonts {
+    fonts: Vec<(String, f64, Option<PietFont>, f64)>,
+    clusters: HashMap<(usize, String), f64>,
+}
+
+impl Fonts {
+    /// The index of the font for `style`, made with `render_ctx` unless it
+    /// is there already.
+    fn index(&mut self, render_ctx: &mut Piet, style: &RunStyle) -> usize {
+        let existing = self
+            .fonts
+            .iter()
+            .position(|(family, size, _, _)| *family == style.family && *size == style.size);
+        existing.unwrap_or_else(|| {
+            let font = render_ctx
+                .text()
+                .new_font_by_name(&style.family, style.size)
+                .and_then(|builder| builder.build())
+                .ok();
+            self.fonts
+                .push((style.family.clone(), style.size, font, 0.0));
+            let index = self.fonts.len() - 1;
+            let bars = self
+                .layout(render_ctx, index, "||")
+                .map_or(0.0, |layout| piet::TextLayout::width(&layout));
+            self.fonts[index].3 = bars;
+            index
+        })
+    }
+
+    fn layout(&self, render_ctx: &mut Piet, font: usize, text: &str) -> Option<PietTextLayout> {
+        if text.is_empty() {
+            return None;
+        }
+        let font = self.fonts[font].2.as_ref()?;
+        render_ctx
+            .text()
+            .new_text_layout(font, text)
+            .and_then(|builder| builder.build())
+            .ok()
+    }
+
+    /// The advance of `text` in `font`. Cairo measures the ink rather than
+    /// the advance, which leaves out the space at either end, so text is
+    /// measured between two bars.
+    fn advance(&self, render_ctx: &mut Piet, font: usize, text: &str) -> f64 {
+        self.layout(render_ctx, font, &format!("|{}|", text))
+            .map_or(0.0, |layout| {
+                piet::TextLayout::width(&layout) - self.fonts[font].3
+            })
+    }
+
+    /// The advance of grapheme cluster `cluster` in `font`, measured the
+    /// first time it is asked for.
+    fn cluster_advance(&mut self, render_ctx: &mut Piet, font: usize, cluster: &str) -> f64 {
+        let key = (font, cluster.to_string());
+        if let Some(&advance) = self.clusters.get(&key) {
+            return advance;
+        }
+        let advance = self.advance(render_ctx, font, cluster);
+        self.clusters.insert(key, advance);
+        advance
+    }
+}
+
+/// The run at `range` in `style`, split into runs of the bidi levels given
+/// by `level_at` for each offset.
+fn split_by_level(
+    range: Range<usize>,
+    style: RunStyle,
+    level_at: impl Fn(usize) -> u8,
+) -> Vec<(Range<usize>, RunStyle, u8)> {
+    let mut runs: Vec<(Range<usize>, RunStyle, u8)> = Vec::new();
+    let mut offset = range.start;
+    while offset < range.end {
+        let level = level_at(offset);
+        let mut end = offset + 1;
+        while end < range.end && level_at(end) == level {
+            end += 1;
+        }
+        runs.push((offset..end, style.clone(), level));
+        offset = end;
+    }
+    runs
+}
+
+/// Right to left `text` in the order cairo draws it in: grapheme clusters
+/// from last to first, with mirrored brackets.
+fn visual_text(text: &str) -> String {
+    editing::grapheme_ranges(text)
+        .into_iter()
+        .rev()
+        .flat_map(|cluster| text[cluster].chars().map(bidi::mirror).collect::<Vec<_>>())
+        .collect()
+}
+
+/// The run of `text` at `range` in `style`, split into runs in the fonts
+/// that have glyphs for each part of it.
+fn fallback_runs(
+    text: &str,
+    range: Range<usize>,
+    style: RunStyle,
+) -> Vec<(Range<usize>, RunStyle)> {
+    let mut runs: Vec<(Range<usize>, RunStyle)> = Vec::new();
+    for cluster in editing::grapheme_ranges(&text[range.clone()]) {
+        let cluster = range.start + cluster.start..range.start + cluster.end;
+        let cluster_text = &text[cluster.clone()];
+        // Spaces stay in the font of the text around them.
+        if let (Some(last), true) = (runs.last_mut(), cluster_text.trim().is_empty()) {
+            last.0.end = cluster.end;
+            continue;
+        }
+        let emoji = editing::is_emoji(cluster_text);
+        let family = font_fallback::family_for(&style.family, cluster_text, emoji);
+        match runs.last_mut() {
+            Some(last) if last.1.family == family => last.0.end = cluster.end,
+            _ => {
+                let family = family.to_string();
+                runs.push((
+                    cluster,
+                    RunStyle {
+                        family,
+                        ..style.clone()
+                    },
+                ));
+            }
+        }
+    }
+    runs
+}
+
+impl Line {
+    /// The run with text drawn at distance `x` from the left of the layout.
+    fn run_at_x(&self, x: f64) -> Option<&Run> {
+        self.runs
+            .iter()
+            .find(|run| !run.range.is_empty() && x >= run.x && x < run.x + run.width)
+    }
+
+    /// The distance of a caret at `offset` from the left of the layout, in
+    /// the run the char at `offset` is in.
+    fn x_at(&self, offset: usize) -> f64 {
+        let run = self
+            .runs
+            .iter()
+            .find(|run| run.range.start <= offset && offset < run.range.end)
+            .or_else(|| self.runs.iter().find(|run| run.range.end == offset))
+            .or_else(|| self.runs.last());
+        run.map_or(0.0, |run| run.x_at(offset))
+    }
+}
+
+impl Run {
+    fn x_at(&self, offset: usize) -> f64 {
+        let advance = self
+            .advances
+            .iter()
+            .take_while(|&&(boundary, _)| boundary <= offset)
+            .last()
+            .map_or(0.0, |&(_, advance)| advance);
+        self.x_of_advance(advance)
+    }
+
+    /// The distance from the left of the layout of the point `advance`
+    /// into the run, in the direction it runs.
+    fn x_of_advance(&self, advance: f64) -> f64 {
+        if self.rtl {
+            self.x + self.width - advance
+        } else {
+            self.x + advance
+        }
+    }
+}
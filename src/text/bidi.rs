@@ -0,0 +1,289 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The order bidirectional text is shown in, by the Unicode Bidirectional
+//! Algorithm without explicit embeddings, overrides and isolates.
+
+/// The bidirectional class of a char, as far as the algorithm without
+/// explicit formatting needs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Class {
+    L,
+    R,
+    AL,
+    EN,
+    ES,
+    ET,
+    AN,
+    CS,
+    Nsm,
+    B,
+    S,
+    WS,
+    ON,
+}
+
+use self::Class::*;
+
+fn class(c: char) -> Class {
+    match c as u32 {
+        0x30..=0x39 | 0xb2 | 0xb3 | 0xb9 | 0x6f0..=0x6f9 | 0x2070..=0x2079 | 0xff10..=0xff19 => EN,
+        0x2b | 0x2d | 0x207a | 0x207b | 0x2212 => ES,
+        0x23..=0x25 | 0xa2..=0xa5 | 0xb0 | 0xb1 | 0x2030..=0x2034 | 0x20a0..=0x20cf => ET,
+        0x2c | 0x2e | 0x2f | 0x3a | 0xa0 | 0x60c | 0x202f | 0x2044 => CS,
+        0x0a | 0x0d | 0x1c..=0x1e | 0x85 | 0x2029 => B,
+        0x09 | 0x0b | 0x1f => S,
+        0x0c | 0x20 | 0x1680 | 0x2000..=0x200a | 0x2028 | 0x205f | 0x3000 => WS,
+        0x0300..=0x036f
+        | 0x0483..=0x0489
+        | 0x0591..=0x05bd
+        | 0x05bf
+        | 0x05c1..=0x05c2
+        | 0x05c4..=0x05c5
+        | 0x05c7
+        | 0x0610..=0x061a
+        | 0x064b..=0x065f
+        | 0x0670
+        | 0x06d6..=0x06dc
+        | 0x06df..=0x06e4
+        | 0x06e7..=0x06e8
+        | 0x06ea..=0x06ed
+        | 0x1ab0..=0x1aff
+        | 0x1dc0..=0x1dff
+        | 0x20d0..=0x20ff
+        | 0xfe00..=0xfe0f
+        | 0xfe20..=0xfe2f => Nsm,
+        0x0660..=0x0669 | 0x066b..=0x066c => AN,
+        0x0590..=0x05ff
+        | 0x07c0..=0x085f
+        | 0xfb1d..=0xfb4f
+        | 0x10800..=0x10fff
+        | 0x1e800..=0x1edff => R,
+        0x0600..=0x07bf
+        | 0x0860..=0x08ff
+        | 0xfb50..=0xfdff
+        | 0xfe70..=0xfeff
+        | 0x1ee00..=0x1eeff => AL,
+        _ if c.is_alphanumeric() => L,
+        _ if c.is_control() => ON,
+        0x21..=0x7e | 0xa1..=0xbf | 0x2010..=0x2027 | 0x2035..=0x205e | 0x2100..=0x2bff => ON,
+        0x3001..=0x303f | 0xfe30..=0xfe6f | 0xff01..=0xff0f | 0xff1a..=0xff20 => ON,
+        _ => L,
+    }
+}
+
+/// The level of paragraph `text`: 1 if its first strong char is right to
+/// left, otherwise 0.
+pub(crate) fn paragraph_level(text: &str) -> u8 {
+    for c in text.chars() {
+        match class(c) {
+            L => return 0,
+            R | AL => return 1,
+            _ => (),
+        }
+    }
+    0
+}
+
+/// The embedding level of each char of paragraph `text`, whose own level
+/// is `para_level`. Chars at odd levels run right to left.
+pub(crate) fn levels(text: &str, para_level: u8) -> Vec<u8> {
+    let original = text.chars().map(class).collect::<Vec<_>>();
+    let mut types = original.clone();
+    let sos = if para_level.is_multiple_of(2) { L } else { R };
+    let len = types.len();
+
+    // W1 to W3: marks take the class before them, European numbers after
+    // Arabic letters are Arabic, and Arabic letters are right to left.
+    let mut last_strong = sos;
+    for i in 0..len {
+        if types[i] == Nsm {
+            types[i] = if i == 0 { sos } else { types[i - 1] };
+        }
+        match types[i] {
+            L | R | AL => last_strong = types[i],
+            EN if last_strong == AL => types[i] = AN,
+            _ => (),
+        }
+    }
+    for t in types.iter_mut() {
+        if *t == AL {
+            *t = R;
+        }
+    }
+    // W4: single separators between numbers of one kind join them.
+    for i in 1..len.saturating_sub(1) {
+        let (prev, next) = (types[i - 1], types[i + 1]);
+        types[i] = match (prev, types[i], next) {
+            (EN, ES, EN) | (EN, CS, EN) => EN,
+            (AN, CS, AN) => AN,
+            (_, t, _) => t,
+        };
+    }
+    // W5: terminators next to European numbers are part of them.
+    let mut i = 0;
+    while i < len {
+        if types[i] != ET {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < len && types[i] == ET {
+            i += 1;
+        }
+        let touches_number = (start > 0 && types[start - 1] == EN) || (i < len && types[i] == EN);
+        if touches_number {
+            types[start..i].iter_mut().for_each(|t| *t = EN);
+        }
+    }
+    // W6 and W7: other separators are neutral, and European numbers after
+    // left to right text are left to right.
+    let mut last_strong = sos;
+    for t in types.iter_mut() {
+        match *t {
+            ES | ET | CS => *t = ON,
+            L | R => last_strong = *t,
+            EN if last_strong == L => *t = L,
+            _ => (),
+        }
+    }
+    // N1 and N2: neutrals between text of one direction take it, with
+    // numbers counting as right to left; others take the paragraph's.
+    let direction = |t: Class| match t {
+        L => Some(L),
+        R | EN | AN => Some(R),
+        _ => None,
+    };
+    let mut i = 0;
+    while i < len {
+        if direction(types[i]).is_some() {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < len && direction(types[i]).is_none() {
+            i += 1;
+        }
+        let before = if start == 0 {
+            sos
+        } else {
+            direction(types[start - 1]).unwrap()
+        };
+        let after = if i == len {
+            sos
+        } else {
+            direction(types[i]).unwrap()
+        };
+        let resolved = if before == after { before } else { sos };
+        types[start..i].iter_mut().for_each(|t| *t = resolved);
+    }
+    // I1 and I2.
+    let mut levels = types
+        .iter()
+        .map(|&t| match (para_level.is_multiple_of(2), t) {
+            (true, R) => para_level + 1,
+            (true, AN) | (true, EN) => para_level + 2,
+            (false, L) | (false, AN) | (false, EN) => para_level + 1,
+            _ => para_level,
+        })
+        .collect::<Vec<_>>();
+    // L1: separators, and the whitespace before them and at the end, are at
+    // the paragraph's level.
+    let mut trailing = true;
+    for i in (0..len).rev() {
+        match original[i] {
+            S | B => {
+                levels[i] = para_level;
+                trailing = true;
+            }
+            WS if trailing => levels[i] = para_level,
+            _ => trailing = false,
+        }
+    }
+    levels
+}
+
+/// The indices of runs at `levels`, in the order they are shown from left
+/// to right.
+pub(crate) fn visual_order(levels: &[u8]) -> Vec<usize> {
+    let mut order = (0..levels.len()).collect::<Vec<_>>();
+    let highest = levels.iter().cloned().max().unwrap_or(0);
+    let lowest_odd = levels.iter().cloned().filter(|l| l % 2 == 1).min();
+    let lowest_odd = match lowest_odd {
+        Some(level) => level,
+        None => return order,
+    };
+    // L2: from the highest level down, reverse each sequence of runs at
+    // that level or higher.
+    for level in (lowest_odd..=highest).rev() {
+        let mut i = 0;
+        while i < order.len() {
+            if levels[order[i]] < level {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < order.len() && levels[order[i]] >= level {
+                i += 1;
+            }
+            order[start..i].reverse();
+        }
+    }
+    order
+}
+
+/// The char shown for `c` in right to left text, with brackets facing the
+/// other way.
+pub(crate) fn mirror(c: char) -> char {
+    match c {
+        '(' => ')',
+        ')' => '(',
+        '[' => ']',
+        ']' => '[',
+        '{' => '}',
+        '}' => '{',
+        '<' => '>',
+        '>' => '<',
+        '«' => '»',
+        '»' => '«',
+        _ => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_of_mixed_text() {
+        // "abc אבג 123": the number after Hebrew in left to right text.
+        let text = "abc \u{5d0}\u{5d1}\u{5d2} 123";
+        assert_eq!(paragraph_level(text), 0);
+        assert_eq!(levels(text, 0), vec![0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2]);
+        // A right to left paragraph with English in it.
+        let text = "\u{5d0}\u{5d1} ab, \u{5d2}.";
+        assert_eq!(paragraph_level(text), 1);
+        assert_eq!(levels(text, 1), vec![1, 1, 1, 2, 2, 1, 1, 1, 1]);
+        // Arabic digits and European ones after Arabic letters.
+        assert_eq!(levels("\u{627} 12", 0), vec![1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn order() {
+        assert_eq!(visual_order(&[0, 1, 1, 0]), vec![0, 2, 1, 3]);
+        assert_eq!(visual_order(&[0, 1, 2, 1]), vec![0, 3, 2, 1]);
+        assert_eq!(visual_order(&[1, 1, 2]), vec![2, 1, 0]);
+        assert_eq!(visual_order(&[0, 0]), vec![0, 1]);
+    }
+}
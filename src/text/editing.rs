@@ -0,0 +1,723 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Editing text: selections, caret movement, and applying edits, shared by
+//! the widgets that edit text.
+//!
+//! Carets stop between grapheme clusters, the characters a user sees, so a
+//! letter with combining accents, an emoji sequence joined with zero width
+//! joiners or a flag moves and deletes as one. The clusters approximate
+//! those of Unicode with a few ranges rather than its full tables.
+//!
+//! An `EditHistory` records the edits made to a text to undo and redo them,
+//! grouping runs of typing and deleting into single steps.
+
+use std::borrow::Cow;
+use std::ops::Range;
+
+use crate::kurbo::Point;
+use crate::TextLayout;
+
+/// Text that can be edited, stored as UTF-8. Offsets are byte offsets.
+pub trait EditableText {
+    /// Replace the text in `range` with `new`.
+    fn edit(&mut self, range: Range<usize>, new: &str);
+
+    /// The text in `range`, if it is in bounds and at char boundaries.
+    fn slice(&self, range: Range<usize>) -> Option<Cow<'_, str>>;
+
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The start of the grapheme cluster before `offset`.
+    fn prev_grapheme_offset(&self, offset: usize) -> Option<usize>;
+
+    /// The end of the grapheme cluster after `offset`.
+    fn next_grapheme_offset(&self, offset: usize) -> Option<usize>;
+
+    /// The start of the word before `offset`, past any spaces and
+    /// punctuation on the way.
+    fn prev_word_offset(&self, offset: usize) -> Option<usize>;
+
+    /// The end of the word after `offset`, past any spaces and punctuation
+    /// on the way.
+    fn next_word_offset(&self, offset: usize) -> Option<usize>;
+}
+
+impl EditableText for String {
+    fn edit(&mut self, range: Range<usize>, new: &str) {
+        self.replace_range(range, new);
+    }
+
+    fn slice(&self, range: Range<usize>) -> Option<Cow<'_, str>> {
+        self.get(range).map(Cow::from)
+    }
+
+    fn len(&self) -> usize {
+        self.as_str().len()
+    }
+
+    fn prev_grapheme_offset(&self, offset: usize) -> Option<usize> {
+        let mut offset = prev_char_offset(self, offset)?;
+        while !is_grapheme_boundary(self, offset) {
+            offset = prev_char_offset(self, offset)?;
+        }
+        Some(offset)
+    }
+
+    fn next_grapheme_offset(&self, offset: usize) -> Option<usize> {
+        let mut offset = next_char_offset(self, offset)?;
+        while !is_grapheme_boundary(self, offset) {
+            offset = next_char_offset(self, offset)?;
+        }
+        Some(offset)
+    }
+
+    fn prev_word_offset(&self, offset: usize) -> Option<usize> {
+        let before = self.get(..offset)?;
+        if before.is_empty() {
+            return None;
+        }
+        let word_end = before.trim_end_matches(|c| !is_word_char(c)).len();
+        Some(before[..word_end].trim_end_matches(is_word_char).len())
+    }
+
+    fn next_word_offset(&self, offset: usize) -> Option<usize> {
+        let after = self.get(offset..)?;
+        if after.is_empty() {
+            return None;
+        }
+        let word = after.trim_start_matches(|c| !is_word_char(c));
+        Some(self.len() - word.trim_start_matches(is_word_char).len())
+    }
+}
+
+/// The selected text, from where the selection started to where the caret
+/// is. When both are the same, the selection is just a caret.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Selection {
+    /// The end that stays put when the selection is extended.
+    pub anchor: usize,
+    /// The end with the caret, which moves.
+    pub active: usize,
+    /// How far from the left the caret keeps to when moving up and down,
+    /// across lines too short to reach it.
+    pub h_pos: Option<f64>,
+}
+
+impl Selection {
+    pub fn new(anchor: usize, active: usize) -> Selection {
+        Selection {
+            anchor,
+            active,
+            h_pos: None,
+        }
+    }
+
+    /// A caret at `offset`, with nothing selected.
+    pub fn caret(offset: usize) -> Selection {
+        Selection::new(offset, offset)
+    }
+
+    pub fn is_caret(&self) -> bool {
+        self.anchor == self.active
+    }
+
+    pub fn min(&self) -> usize {
+        self.anchor.min(self.active)
+    }
+
+    pub fn max(&self) -> usize {
+        self.anchor.max(self.active)
+    }
+
+    /// The selected range, in order.
+    pub fn range(&self) -> Range<usize> {
+        self.min()..self.max()
+    }
+
+    /// The selection with its ends clamped to the length of `text`.
+    pub fn constrained(&self, text: &impl EditableText) -> Selection {
+        let len = text.len();
+        Selection {
+            anchor: self.anchor.min(len),
+            active: self.active.min(len),
+            h_pos: self.h_pos,
+        }
+    }
+}
+
+/// A way of moving the caret, as with the arrow keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Movement {
+    /// By one grapheme cluster.
+    Left,
+    Right,
+    /// To the start or end of a word.
+    LeftWord,
+    RightWord,
+    /// To the start or end of the line of the layout.
+    LineStart,
+    LineEnd,
+    /// To the line above or below, or the start or end of the text from the
+    /// first or last line.
+    Up,
+    Down,
+    DocumentStart,
+    DocumentEnd,
+}
+
+/// Move the caret of `selection` in `text`, laid out in `layout`, by
+/// `movement`, extending the selection if `extend`.
+///
+/// Without extending, moving left or right by a cluster from a selection
+/// collapses it to its start or end.
+pub fn movement(
+    movement: Movement,
+    selection: Selection,
+    text: &impl EditableText,
+    layout: &mut TextLayout,
+    extend: bool,
+) -> Selection {
+    let selection = selection.constrained(text);
+    if !extend && !selection.is_caret() {
+        match movement {
+            Movement::Left => return Selection::caret(selection.min()),
+            Movement::Right => return Selection::caret(selection.max()),
+            _ => (),
+        }
+    }
+    let (active, h_pos) = move_offset(movement, &selection, text, layout);
+    let anchor = if extend { selection.anchor } else { active };
+    Selection {
+        anchor,
+        active,
+        h_pos,
+    }
+}
+
+/// Replace the selected text with `new`, leaving a caret after it.
+pub fn insert(text: &mut impl EditableText, selection: Selection, new: &str) -> Selection {
+    let range = selection.constrained(text).range();
+    text.edit(range.clone(), new);
+    Selection::caret(range.start + new.len())
+}
+
+/// Delete the selected text, or, for a caret, the text between it and where
+/// `movement` would take it, as with Backspace and Delete.
+pub fn delete(
+    text: &mut impl EditableText,
+    selection: Selection,
+    movement: Movement,
+    layout: &mut TextLayout,
+) -> Selection {
+    let range = delete_range(text, selection, movement, layout);
+    text.edit(range.clone(), "");
+    Selection::caret(range.start)
+}
+
+/// The text `delete` would delete.
+pub fn delete_range(
+    text: &impl EditableText,
+    selection: Selection,
+    movement: Movement,
+    layout: &mut TextLayout,
+) -> Range<usize> {
+    let selection = selection.constrained(text);
+    if selection.is_caret() {
+        let (offset, _) = move_offset(movement, &selection, text, layout);
+        Selection::new(selection.active, offset).range()
+    } else {
+        selection.range()
+    }
+}
+
+/// What an edit was made by, for grouping it with the edits before it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditKind {
+    /// Typing into the text, which groups with typing just before it.
+    Typing,
+    /// Deleting with Backspace or Delete, which groups with deleting in the
+    /// same direction just before it.
+    Deleting,
+    /// Any other edit, such as a paste, which is a step of its own.
+    Other,
+}
+
+/// An edit as recorded: the text from `start` that was `old` and is `new`.
+struct Edit {
+    kind: EditKind,
+    start: usize,
+    old: String,
+    new: String,
+    /// The selection before and after the edit, restored by undo and redo.
+    before: Selection,
+    after: Selection,
+}
+
+impl Edit {
+    /// Take `next` into this edit, if it continues it: typing straight
+    /// after the text typed, or deleting up to or from where the last
+    /// deletion was.
+    fn merge(&mut self, next: &Edit) -> bool {
+        if self.kind != next.kind {
+            return false;
+        }
+        match next.kind {
+            EditKind::Typing
+                if next.old.is_empty() && next.start == self.start + self.new.len() =>
+            {
+                self.new.push_str(&next.new);
+            }
+            EditKind::Deleting if self.new.is_empty() && next.new.is_empty() => {
+                if next.start + next.old.len() == self.start {
+                    self.old.insert_str(0, &next.old);
+                    self.start = next.start;
+                } else if next.start == self.start {
+                    self.old.push_str(&next.old);
+                } else {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+        self.after = next.after;
+        true
+    }
+}
+
+/// The edits made to a text, to undo and redo them.
+///
+/// Edits go through `edit`, which groups typing and deleting with that just
+/// before until `break_group` is called, as when the caret is moved.
+#[derive(Default)]
+pub struct EditHistory {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+    /// Whether the next edit may join the last one recorded.
+    open: bool,
+}
+
+impl EditHistory {
+    pub fn new() -> EditHistory {
+        Default::default()
+    }
+
+    /// Replace `range` of `text` with `new`, recording it for undo, and
+    /// return the caret after it. `selection` is the selection before the
+    /// edit, restored when it is undone.
+    pub fn edit(
+        &mut self,
+        text: &mut impl EditableText,
+        range: Range<usize>,
+        new: &str,
+        selection: Selection,
+        kind: EditKind,
+    ) -> Selection {
+        let old = match text.slice(range.clone()) {
+            Some(old) => old.into_owned(),
+            None => return selection,
+        };
+        text.edit(range.clone(), new);
+        let after = Selection::caret(range.start + new.len());
+        if old.is_empty() && new.is_empty() {
+            return after;
+        }
+        let edit = Edit {
+            kind,
+            start: range.start,
+            old,
+            new: new.to_string(),
+            before: selection,
+            after,
+        };
+        self.redo.clear();
+        let merged = match self.undo.last_mut() {
+            Some(last) if self.open => last.merge(&edit),
+            _ => false,
+        };
+        if !merged {
+            self.undo.push(edit);
+        }
+        self.open = kind != EditKind::Other;
+        after
+    }
+
+    /// Start a new step, so the next edit doesn't join the last one.
+    pub fn break_group(&mut self) {
+        self.open = false;
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Undo the last step in `text`, returning the selection from before
+    /// it, or `None` if there is nothing to undo.
+    pub fn undo(&mut self, text: &mut impl EditableText) -> Option<Selection> {
+        let edit = self.undo.pop()?;
+        text.edit(edit.start..edit.start + edit.new.len(), &edit.old);
+        self.open = false;
+        let selection = edit.before;
+        self.redo.push(edit);
+        Some(selection)
+    }
+
+    /// Redo the last step undone in `text`, returning the selection from
+    /// after it, or `None` if there is nothing to redo.
+    pub fn redo(&mut self, text: &mut impl EditableText) -> Option<Selection> {
+        let edit = self.redo.pop()?;
+        text.edit(edit.start..edit.start + edit.old.len(), &edit.new);
+        self.open = false;
+        let selection = edit.after;
+        self.undo.push(edit);
+        Some(selection)
+    }
+
+    /// Forget all edits, as when the text is replaced.
+    pub fn clear(&mut self) {
+        *self = Default::default();
+    }
+}
+
+/// `text` as a single line, for pasting into a field of one: line breaks
+/// between lines become spaces and those at the end are dropped, as are
+/// other control characters but tabs, which become spaces.
+pub fn single_line(text: &str) -> Cow<'_, str> {
+    if !text.chars().any(char::is_control) {
+        return Cow::Borrowed(text);
+    }
+    let mut line = String::with_capacity(text.len());
+    for (i, part) in text
+        .trim_end_matches(['\r', '\n'])
+        .split("\r\n")
+        .enumerate()
+    {
+        if i > 0 {
+            line.push(' ');
+        }
+        for c in part.chars() {
+            match c {
+                '\n' | '\r' | '\t' => line.push(' '),
+                c if c.is_control() => (),
+                c => line.push(c),
+            }
+        }
+    }
+    Cow::Owned(line)
+}
+
+/// The grapheme boundary at or before `offset` in `text`, for placing a
+/// caret at a point hit in a layout, which may be within a cluster.
+pub fn snap_to_grapheme(text: &impl EditableText, offset: usize) -> usize {
+    let offset = offset.min(text.len());
+    match text.prev_grapheme_offset(offset) {
+        Some(prev) if text.next_grapheme_offset(prev) != Some(offset) => prev,
+        _ => offset,
+    }
+}
+
+/// Where `movement` takes the caret of `selection`, and the horizontal
+/// position to keep for moving on up or down.
+fn move_offset(
+    movement: Movement,
+    selection: &Selection,
+    text: &impl EditableText,
+    layout: &mut TextLayout,
+) -> (usize, Option<f64>) {
+    let active = selection.active;
+    let offset = match movement {
+        Movement::Left => text.prev_grapheme_offset(active).unwrap_or(0),
+        Movement::Right => text.next_grapheme_offset(active).unwrap_or(active),
+        Movement::LeftWord => text.prev_word_offset(active).unwrap_or(0),
+        Movement::RightWord => text.next_word_offset(active).unwrap_or(active),
+        Movement::LineStart => line_of(layout, active).map_or(0, |range| range.start),
+        Movement::LineEnd => line_of(layout, active).map_or(text.len(), |range| range.end),
+        Movement::Up | Movement::Down => return move_vertically(movement, selection, text, layout),
+        Movement::DocumentStart => 0,
+        Movement::DocumentEnd => text.len(),
+    };
+    (offset, None)
+}
+
+fn move_vertically(
+    movement: Movement,
+    selection: &Selection,
+    text: &impl EditableText,
+    layout: &mut TextLayout,
+) -> (usize, Option<f64>) {
+    let caret = layout.hit_test_offset(selection.active);
+    let h_pos = selection.h_pos.unwrap_or(caret.x);
+    let lines = layout.line_metrics();
+    let index = lines
+        .iter()
+        .position(|line| selection.active <= line.range.end)
+        .unwrap_or_else(|| lines.len().saturating_sub(1));
+    let target = match movement {
+        Movement::Up => index.checked_sub(1),
+        _ => Some(index + 1).filter(|&i| i < lines.len()),
+    };
+    match target {
+        Some(i) => {
+            let y = lines[i].y_offset + lines[i].height / 2.0;
+            let offset = layout.hit_test_point(Point::new(h_pos, y));
+            (snap_to_grapheme(text, offset), Some(h_pos))
+        }
+        None if movement == Movement::Up => (0, Some(h_pos)),
+        None => (text.len(), Some(h_pos)),
+    }
+}
+
+/// The range of the line of `layout` that `offset` is in.
+fn line_of(layout: &mut TextLayout, offset: usize) -> Option<Range<usize>> {
+    let lines = layout.line_metrics();
+    lines
+        .iter()
+        .find(|line| offset <= line.range.end)
+        .or_else(|| lines.last())
+        .map(|line| line.range.clone())
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn prev_char_offset(text: &str, offset: usize) -> Option<usize> {
+    text.get(..offset)?.char_indices().last().map(|(i, _)| i)
+}
+
+fn next_char_offset(text: &str, offset: usize) -> Option<usize> {
+    text.get(offset..)?
+        .chars()
+        .next()
+        .map(|c| offset + c.len_utf8())
+}
+
+/// The ranges of the grapheme clusters of `text`.
+pub(crate) fn grapheme_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    for (offset, _) in text.char_indices().skip(1) {
+        if is_grapheme_boundary(text, offset) {
+            ranges.push(start..offset);
+            start = offset;
+        }
+    }
+    if start < text.len() {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+/// Whether grapheme cluster `cluster` is shown as an emoji: a pictograph in
+/// the emoji blocks or asking for emoji presentation, or a flag.
+pub(crate) fn is_emoji(cluster: &str) -> bool {
+    let first = match cluster.chars().next() {
+        Some(first) => first,
+        None => return false,
+    };
+    is_regional_indicator(first)
+        || (is_pictographic(first) && (first as u32 >= 0x1f300 || cluster.contains('\u{fe0f}')))
+}
+
+/// Whether `offset`, at a char boundary of `text`, is between two grapheme
+/// clusters.
+fn is_grapheme_boundary(text: &str, offset: usize) -> bool {
+    let (before, after) = text.split_at(offset);
+    let (prev, next) = match (before.chars().next_back(), after.chars().next()) {
+        (Some(prev), Some(next)) => (prev, next),
+        _ => return true,
+    };
+    if prev == '\r' && next == '\n' {
+        return false;
+    }
+    if prev.is_control() || next.is_control() {
+        return true;
+    }
+    if let Some(joined) = hangul_joins(prev, next) {
+        return !joined;
+    }
+    if is_extend(next) {
+        return false;
+    }
+    if prev == '\u{200d}' && is_pictographic(next) {
+        return false;
+    }
+    if is_regional_indicator(prev) && is_regional_indicator(next) {
+        // Flags are pairs of indicators, counted from the last other char.
+        let run = before
+            .chars()
+            .rev()
+            .take_while(|&c| is_regional_indicator(c))
+            .count();
+        return run % 2 == 0;
+    }
+    true
+}
+
+/// Chars that attach to the one before them: combining marks, joiners,
+/// variation selectors, emoji modifiers and tags.
+fn is_extend(c: char) -> bool {
+    matches!(
+        c as u32,
+        0x0300..=0x036f
+            | 0x0483..=0x0489
+            | 0x0591..=0x05bd
+            | 0x0610..=0x061a
+            | 0x064b..=0x065f
+            | 0x0900..=0x0903
+            | 0x093a..=0x094f
+            | 0x0e31
+            | 0x0e34..=0x0e3a
+            | 0x0e47..=0x0e4e
+            | 0x1ab0..=0x1aff
+            | 0x1dc0..=0x1dff
+            | 0x200c..=0x200d
+            | 0x20d0..=0x20ff
+            | 0x302a..=0x302f
+            | 0x3099..=0x309a
+            | 0xfe00..=0xfe0f
+            | 0xfe20..=0xfe2f
+            | 0x1f3fb..=0x1f3ff
+            | 0xe0020..=0xe007f
+            | 0xe0100..=0xe01ef
+    )
+}
+
+fn is_pictographic(c: char) -> bool {
+    matches!(c as u32, 0x2600..=0x27bf | 0x2b00..=0x2bff | 0x1f000..=0x1faff)
+}
+
+fn is_regional_indicator(c: char) -> bool {
+    matches!(c as u32, 0x1f1e6..=0x1f1ff)
+}
+
+/// Whether Hangul jamo or syllables `prev` and `next` form one syllable, if
+/// both are Hangul.
+fn hangul_joins(prev: char, next: char) -> Option<bool> {
+    #[derive(PartialEq)]
+    enum Jamo {
+        Leading,
+        Vowel,
+        Trailing,
+        /// A syllable of a leading consonant and a vowel.
+        Lv,
+        /// A syllable with a trailing consonant too.
+        Lvt,
+    }
+    fn jamo(c: char) -> Option<Jamo> {
+        match c as u32 {
+            0x1100..=0x115f | 0xa960..=0xa97c => Some(Jamo::Leading),
+            0x1160..=0x11a7 | 0xd7b0..=0xd7c6 => Some(Jamo::Vowel),
+            0x11a8..=0x11ff | 0xd7cb..=0xd7fb => Some(Jamo::Trailing),
+            c @ 0xac00..=0xd7a3 if (c - 0xac00) % 28 == 0 => Some(Jamo::Lv),
+            0xac00..=0xd7a3 => Some(Jamo::Lvt),
+            _ => None,
+        }
+    }
+    let (prev, next) = (jamo(prev)?, jamo(next)?);
+    Some(match prev {
+        Jamo::Leading => next != Jamo::Trailing,
+        Jamo::Vowel | Jamo::Lv => next == Jamo::Vowel || next == Jamo::Trailing,
+        Jamo::Trailing | Jamo::Lvt => next == Jamo::Trailing,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clusters(text: &str) -> Vec<usize> {
+        let text = text.to_string();
+        let mut offsets = vec![0];
+        while let Some(next) = text.next_grapheme_offset(*offsets.last().unwrap()) {
+            offsets.push(next);
+        }
+        offsets
+    }
+
+    #[test]
+    fn graphemes() {
+        assert_eq!(clusters("ab"), vec![0, 1, 2]);
+        // e with a combining acute accent, then CRLF.
+        assert_eq!(clusters("e\u{301}x\r\n"), vec![0, 3, 4, 6]);
+        // Two flags, and a family joined with zero width joiners.
+        assert_eq!(clusters("🇨🇦🇫🇷"), vec![0, 8, 16]);
+        assert_eq!(clusters("👩\u{200d}👧!"), vec![0, 11, 12]);
+        // A syllable spelled with jamo.
+        assert_eq!(clusters("\u{1100}\u{1161}\u{11a8}가"), vec![0, 9, 12]);
+        let text = "e\u{301}x".to_string();
+        assert_eq!(text.prev_grapheme_offset(3), Some(0));
+        assert_eq!(snap_to_grapheme(&text, 1), 0);
+        assert_eq!(snap_to_grapheme(&text, 3), 3);
+    }
+
+    #[test]
+    fn words() {
+        let text = "hello, big_world ".to_string();
+        assert_eq!(text.next_word_offset(0), Some(5));
+        assert_eq!(text.next_word_offset(5), Some(16));
+        assert_eq!(text.next_word_offset(16), Some(17));
+        assert_eq!(text.prev_word_offset(17), Some(7));
+        assert_eq!(text.prev_word_offset(7), Some(0));
+        assert_eq!(text.prev_word_offset(0), None);
+    }
+
+    #[test]
+    fn single_lines() {
+        assert_eq!(single_line("plain"), "plain");
+        assert_eq!(single_line("one\r\ntwo\nthree\r\n"), "one two three");
+        assert_eq!(single_line("a\tb\u{7}c\n\n"), "a bc");
+    }
+
+    #[test]
+    fn edits() {
+        let mut text = "naïve".to_string();
+        let selection = insert(&mut text, Selection::new(2, 0), "Na");
+        assert_eq!((text.as_str(), selection), ("Naïve", Selection::caret(2)));
+        let selection = insert(&mut text, Selection::caret(6), "!");
+        assert_eq!((text.as_str(), selection), ("Naïve!", Selection::caret(7)));
+    }
+
+    #[test]
+    fn history() {
+        let mut text = String::new();
+        let mut history = EditHistory::new();
+        let mut selection = Selection::caret(0);
+        for (i, c) in ["a", "b", " ", "c"].iter().enumerate() {
+            selection = history.edit(&mut text, i..i, c, selection, EditKind::Typing);
+        }
+        history.break_group();
+        selection = history.edit(&mut text, 3..4, "", selection, EditKind::Deleting);
+        history.edit(&mut text, 2..3, "", selection, EditKind::Deleting);
+        assert_eq!(text, "ab");
+        assert_eq!(history.undo(&mut text), Some(Selection::caret(4)));
+        assert_eq!(text, "ab c");
+        assert_eq!(history.undo(&mut text), Some(Selection::caret(0)));
+        assert_eq!(text, "");
+        assert_eq!(history.undo(&mut text), None);
+        assert_eq!(history.redo(&mut text), Some(Selection::caret(4)));
+        assert_eq!(text, "ab c");
+        assert!(history.can_redo());
+        history.edit(&mut text, 0..0, "x", selection, EditKind::Typing);
+        assert!(!history.can_redo());
+    }
+}
@@ -0,0 +1,270 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The keys that move the caret and edit text in the widgets editing it.
+
+use druid_shell::commands;
+use druid_shell::keyboard::{KeyCode, KeyEvent};
+use druid_shell::keycodes::{
+    command_modifier, modifiers_mask, MenuKey, Modifiers, M_ALT, M_CTRL, M_META, M_SHIFT,
+};
+
+use super::editing::Movement;
+
+/// A key with modifiers, by the physical key or, as for menu shortcuts, by
+/// the character it produces.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyChord {
+    /// The key with exactly the modifiers given, such as an arrow key.
+    Code(Modifiers, KeyCode),
+    /// A key matched as with `MenuKey::matches`.
+    Char(MenuKey),
+}
+
+impl KeyChord {
+    pub fn matches(&self, event: &KeyEvent) -> bool {
+        match self {
+            KeyChord::Code(mods, code) => {
+                event.code == *code && modifiers_mask(event.modifiers) == *mods
+            }
+            KeyChord::Char(key) => key.matches(event),
+        }
+    }
+}
+
+impl From<KeyCode> for KeyChord {
+    fn from(code: KeyCode) -> KeyChord {
+        KeyChord::Code(0, code)
+    }
+}
+
+impl From<MenuKey> for KeyChord {
+    fn from(key: MenuKey) -> KeyChord {
+        KeyChord::Char(key)
+    }
+}
+
+/// What a key does to the text being edited.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EditAction {
+    /// Move the caret, collapsing the selection.
+    Move(Movement),
+    /// Move the caret, extending the selection.
+    Select(Movement),
+    /// Delete the selection, or the text up to where the caret would move.
+    Delete(Movement),
+    /// Handle a standard command, such as `commands::UNDO`.
+    Command(u32),
+}
+
+/// A table of the keys for editing text, from their chords to actions.
+///
+/// The default table has the platform's bindings: the arrow keys, Home and
+/// End with modifiers for moving by words and lines, Backspace and Delete,
+/// and the shortcuts for the clipboard, undo and redo, with the Emacs keys
+/// of macOS there. Apps can add their own, or start from an empty table.
+#[derive(Clone)]
+pub struct EditKeymap {
+    bindings: Vec<(KeyChord, EditAction)>,
+}
+
+impl EditKeymap {
+    /// A table with no bindings.
+    pub fn new() -> EditKeymap {
+        EditKeymap {
+            bindings: Vec::new(),
+        }
+    }
+
+    /// Bind a chord to an action, replacing any binding of the chord.
+    pub fn add(&mut self, chord: impl Into<KeyChord>, action: EditAction) {
+        let chord = chord.into();
+        self.remove(chord);
+        self.bindings.push((chord, action));
+    }
+
+    pub fn remove(&mut self, chord: KeyChord) {
+        self.bindings.retain(|(c, _)| *c != chord);
+    }
+
+    /// Bind a chord to moving the caret by `movement`, and the chord with
+    /// Shift to extending the selection by it.
+    pub fn add_movement(&mut self, mods: Modifiers, code: KeyCode, movement: Movement) {
+        self.add(KeyChord::Code(mods, code), EditAction::Move(movement));
+        self.add(
+            KeyChord::Code(mods | M_SHIFT, code),
+            EditAction::Select(movement),
+        );
+    }
+
+    /// The action bound to the key event, if any.
+    pub fn lookup(&self, event: &KeyEvent) -> Option<EditAction> {
+        self.bindings
+            .iter()
+            .find(|(chord, _)| chord.matches(event))
+            .map(|&(_, action)| action)
+    }
+}
+
+impl Default for EditKeymap {
+    fn default() -> EditKeymap {
+        let mac = cfg!(target_os = "macos");
+        let word = if mac { M_ALT } else { M_CTRL };
+        let mut keymap = EditKeymap::new();
+        keymap.add_movement(0, KeyCode::ArrowLeft, Movement::Left);
+        keymap.add_movement(0, KeyCode::ArrowRight, Movement::Right);
+        keymap.add_movement(0, KeyCode::ArrowUp, Movement::Up);
+        keymap.add_movement(0, KeyCode::ArrowDown, Movement::Down);
+        keymap.add_movement(word, KeyCode::ArrowLeft, Movement::LeftWord);
+        keymap.add_movement(word, KeyCode::ArrowRight, Movement::RightWord);
+        keymap.add_movement(0, KeyCode::Home, Movement::LineStart);
+        keymap.add_movement(0, KeyCode::End, Movement::LineEnd);
+        keymap.add_movement(M_CTRL, KeyCode::Home, Movement::DocumentStart);
+        keymap.add_movement(M_CTRL, KeyCode::End, Movement::DocumentEnd);
+
+        let delete = |keymap: &mut EditKeymap, mods, code, movement| {
+            keymap.add(KeyChord::Code(mods, code), EditAction::Delete(movement));
+        };
+        // Backspace deletes with Shift held too, as it is when typing capitals.
+        delete(&mut keymap, 0, KeyCode::Backspace, Movement::Left);
+        delete(&mut keymap, M_SHIFT, KeyCode::Backspace, Movement::Left);
+        delete(&mut keymap, word, KeyCode::Backspace, Movement::LeftWord);
+        delete(&mut keymap, 0, KeyCode::Delete, Movement::Right);
+        delete(&mut keymap, word, KeyCode::Delete, Movement::RightWord);
+
+        let shortcuts = [
+            ('x', commands::CUT),
+            ('c', commands::COPY),
+            ('v', commands::PASTE),
+            ('a', commands::SELECT_ALL),
+            ('z', commands::UNDO),
+        ];
+        for &(key, cmd) in &shortcuts {
+            keymap.add(MenuKey::command(key), EditAction::Command(cmd));
+        }
+        let redo = MenuKey::new(command_modifier() | M_SHIFT, 'z');
+        keymap.add(redo, EditAction::Command(commands::REDO));
+
+        if mac {
+            keymap.add_movement(M_META, KeyCode::ArrowLeft, Movement::LineStart);
+            keymap.add_movement(M_META, KeyCode::ArrowRight, Movement::LineEnd);
+            keymap.add_movement(M_META, KeyCode::ArrowUp, Movement::DocumentStart);
+            keymap.add_movement(M_META, KeyCode::ArrowDown, Movement::DocumentEnd);
+            delete(&mut keymap, M_META, KeyCode::Backspace, Movement::LineStart);
+            // The Emacs keys of the text fields of macOS.
+            keymap.add_movement(M_CTRL, KeyCode::KeyA, Movement::LineStart);
+            keymap.add_movement(M_CTRL, KeyCode::KeyE, Movement::LineEnd);
+            keymap.add_movement(M_CTRL, KeyCode::KeyB, Movement::Left);
+            keymap.add_movement(M_CTRL, KeyCode::KeyF, Movement::Right);
+            keymap.add_movement(M_CTRL, KeyCode::KeyP, Movement::Up);
+            keymap.add_movement(M_CTRL, KeyCode::KeyN, Movement::Down);
+            delete(&mut keymap, M_CTRL, KeyCode::KeyH, Movement::Left);
+            delete(&mut keymap, M_CTRL, KeyCode::KeyD, Movement::Right);
+            delete(&mut keymap, M_CTRL, KeyCode::KeyK, Movement::LineEnd);
+        } else {
+            // The older clipboard keys, and Ctrl+Y for redo.
+            let commands = [
+                (M_SHIFT, KeyCode::Delete, commands::CUT),
+                (M_CTRL, KeyCode::Insert, commands::COPY),
+                (M_SHIFT, KeyCode::Insert, commands::PASTE),
+            ];
+            for &(mods, code, cmd) in &commands {
+                keymap.add(KeyChord::Code(mods, code), EditAction::Command(cmd));
+            }
+            let redo = MenuKey::new(M_CTRL, 'y');
+            keymap.add(redo, EditAction::Command(commands::REDO));
+        }
+        keymap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The action bound to exactly `chord`.
+    fn action(keymap: &EditKeymap, chord: impl Into<KeyChord>) -> Option<EditAction> {
+        let chord = chord.into();
+        keymap
+            .bindings
+            .iter()
+            .find(|(c, _)| *c == chord)
+            .map(|&(_, action)| action)
+    }
+
+    #[test]
+    fn default_bindings() {
+        let keymap = EditKeymap::default();
+        let word = if cfg!(target_os = "macos") {
+            M_ALT
+        } else {
+            M_CTRL
+        };
+        let code = |mods, code| KeyChord::Code(mods, code);
+        assert_eq!(
+            action(&keymap, KeyCode::ArrowLeft),
+            Some(EditAction::Move(Movement::Left))
+        );
+        assert_eq!(
+            action(&keymap, code(M_SHIFT, KeyCode::ArrowRight)),
+            Some(EditAction::Select(Movement::Right))
+        );
+        assert_eq!(
+            action(&keymap, code(word | M_SHIFT, KeyCode::ArrowLeft)),
+            Some(EditAction::Select(Movement::LeftWord))
+        );
+        assert_eq!(
+            action(&keymap, KeyCode::Home),
+            Some(EditAction::Move(Movement::LineStart))
+        );
+        assert_eq!(
+            action(&keymap, KeyCode::Backspace),
+            Some(EditAction::Delete(Movement::Left))
+        );
+        assert_eq!(
+            action(&keymap, code(word, KeyCode::Delete)),
+            Some(EditAction::Delete(Movement::RightWord))
+        );
+        assert_eq!(
+            action(&keymap, MenuKey::command('c')),
+            Some(EditAction::Command(commands::COPY))
+        );
+        assert_eq!(
+            action(&keymap, MenuKey::command('v')),
+            Some(EditAction::Command(commands::PASTE))
+        );
+        let redo = MenuKey::new(command_modifier() | M_SHIFT, 'z');
+        assert_eq!(
+            action(&keymap, redo),
+            Some(EditAction::Command(commands::REDO))
+        );
+        assert_eq!(action(&keymap, KeyCode::Escape), None);
+    }
+
+    #[test]
+    fn add_replaces_binding() {
+        let mut keymap = EditKeymap::new();
+        assert_eq!(action(&keymap, KeyCode::ArrowLeft), None);
+        keymap.add_movement(0, KeyCode::ArrowLeft, Movement::LineStart);
+        keymap.add(KeyCode::ArrowLeft, EditAction::Move(Movement::Left));
+        assert_eq!(
+            action(&keymap, KeyCode::ArrowLeft),
+            Some(EditAction::Move(Movement::Left))
+        );
+        assert_eq!(
+            action(&keymap, KeyChord::Code(M_SHIFT, KeyCode::ArrowLeft)),
+            Some(EditAction::Select(Movement::LineStart))
+        );
+    }
+}
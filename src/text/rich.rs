@@ -0,0 +1,311 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Text with attributes on spans of it, such as fonts, colors and links.
+
+use std::fmt;
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::piet::Color;
+
+/// The weight of a font, from 100 for thin to 900 for black.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct FontWeight(pub u16);
+
+impl FontWeight {
+    pub const LIGHT: FontWeight = FontWeight(300);
+    pub const NORMAL: FontWeight = FontWeight(400);
+    pub const MEDIUM: FontWeight = FontWeight(500);
+    pub const SEMIBOLD: FontWeight = FontWeight(600);
+    pub const BOLD: FontWeight = FontWeight(700);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FontStyle {
+    Normal,
+    Italic,
+}
+
+/// A way a span of `RichText` looks or behaves.
+#[derive(Clone)]
+pub enum Attribute {
+    FontFamily(String),
+    FontSize(f64),
+    Weight(FontWeight),
+    Style(FontStyle),
+    TextColor(Color),
+    Underline(bool),
+    Strikethrough(bool),
+    /// A target, such as a URL, that the span links to.
+    Link(String),
+}
+
+impl PartialEq for Attribute {
+    fn eq(&self, other: &Attribute) -> bool {
+        use Attribute::*;
+        match (self, other) {
+            (FontFamily(a), FontFamily(b)) | (Link(a), Link(b)) => a == b,
+            (FontSize(a), FontSize(b)) => a == b,
+            (Weight(a), Weight(b)) => a == b,
+            (Style(a), Style(b)) => a == b,
+            (TextColor(a), TextColor(b)) => a.as_rgba32() == b.as_rgba32(),
+            (Underline(a), Underline(b)) | (Strikethrough(a), Strikethrough(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Attribute {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Attribute::FontFamily(family) => write!(f, "FontFamily({:?})", family),
+            Attribute::FontSize(size) => write!(f, "FontSize({})", size),
+            Attribute::Weight(weight) => write!(f, "Weight({})", weight.0),
+            Attribute::Style(style) => write!(f, "Style({:?})", style),
+            Attribute::TextColor(color) => write!(f, "TextColor(#{:08x})", color.as_rgba32()),
+            Attribute::Underline(on) => write!(f, "Underline({})", on),
+            Attribute::Strikethrough(on) => write!(f, "Strikethrough({})", on),
+            Attribute::Link(target) => write!(f, "Link({:?})", target),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Span {
+    range: Range<usize>,
+    attribute: Attribute,
+}
+
+/// A string with attributes on spans of it, cheap to clone so that it can
+/// be kept in app state and passed to widgets.
+///
+/// Where spans overlap, the attributes of later ones win. Offsets are byte
+/// offsets into the text, at char boundaries.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RichText {
+    text: Rc<str>,
+    spans: Rc<Vec<Span>>,
+}
+
+impl RichText {
+    /// Text with no attributes, shown with the defaults of where it is used.
+    pub fn new(text: impl Into<String>) -> RichText {
+        RichText {
+            text: text.into().into(),
+            spans: Rc::new(Vec::new()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    /// Add `attribute` to the text in `range`.
+    pub fn with_attribute(mut self, range: Range<usize>, attribute: Attribute) -> RichText {
+        let end = range.end.min(self.text.len());
+        let range = range.start.min(end)..end;
+        Rc::make_mut(&mut self.spans).push(Span { range, attribute });
+        self
+    }
+
+    /// The ranges of the links in the text, with their targets.
+    pub fn links(&self) -> impl Iterator<Item = (Range<usize>, &str)> {
+        self.spans.iter().filter_map(|span| match span.attribute {
+            Attribute::Link(ref target) => Some((span.range.clone(), target.as_str())),
+            _ => None,
+        })
+    }
+
+    /// The target of the link the char at `offset` is in, if any.
+    pub fn link_at(&self, offset: usize) -> Option<&str> {
+        self.links()
+            .filter(|(range, _)| range.start <= offset && offset < range.end)
+            .last()
+            .map(|(_, target)| target)
+    }
+
+    /// The runs of the text in `range` that look the same, with how they
+    /// look given `default`.
+    pub(crate) fn runs(
+        &self,
+        range: Range<usize>,
+        default: &RunStyle,
+    ) -> Vec<(Range<usize>, RunStyle)> {
+        let mut bounds = vec![range.start, range.end];
+        for span in self.spans.iter() {
+            for &bound in &[span.range.start, span.range.end] {
+                if bound > range.start && bound < range.end {
+                    bounds.push(bound);
+                }
+            }
+        }
+        bounds.sort();
+        bounds.dedup();
+        bounds
+            .windows(2)
+            .map(|pair| {
+                let mut style = default.clone();
+                for span in self.spans.iter() {
+                    if span.range.start <= pair[0] && pair[1] <= span.range.end {
+                        style.apply(&span.attribute);
+                    }
+                }
+                (pair[0]..pair[1], style)
+            })
+            .collect()
+    }
+}
+
+impl Default for RichText {
+    fn default() -> RichText {
+        RichText::new("")
+    }
+}
+
+impl From<String> for RichText {
+    fn from(text: String) -> RichText {
+        RichText::new(text)
+    }
+}
+
+impl<'a> From<&'a str> for RichText {
+    fn from(text: &str) -> RichText {
+        RichText::new(text)
+    }
+}
+
+/// Builds `RichText` a piece at a time, with the attributes of each piece.
+#[derive(Default)]
+pub struct RichTextBuilder {
+    text: String,
+    spans: Vec<Span>,
+}
+
+/// Adds attributes to a piece of text pushed to a `RichTextBuilder`.
+pub struct AttributesAdder<'a> {
+    spans: &'a mut Vec<Span>,
+    range: Range<usize>,
+}
+
+impl RichTextBuilder {
+    pub fn new() -> RichTextBuilder {
+        RichTextBuilder::default()
+    }
+
+    /// Append `text`, returning an adder for its attributes.
+    pub fn push(&mut self, text: &str) -> AttributesAdder<'_> {
+        let start = self.text.len();
+        self.text.push_str(text);
+        AttributesAdder {
+            spans: &mut self.spans,
+            range: start..self.text.len(),
+        }
+    }
+
+    pub fn build(self) -> RichText {
+        RichText {
+            text: self.text.into(),
+            spans: Rc::new(self.spans),
+        }
+    }
+}
+
+impl<'a> AttributesAdder<'a> {
+    pub fn add_attr(&mut self, attribute: Attribute) -> &mut Self {
+        let range = self.range.clone();
+        self.spans.push(Span { range, attribute });
+        self
+    }
+
+    pub fn font_family(&mut self, family: impl Into<String>) -> &mut Self {
+        self.add_attr(Attribute::FontFamily(family.into()))
+    }
+
+    pub fn size(&mut self, size: f64) -> &mut Self {
+        self.add_attr(Attribute::FontSize(size))
+    }
+
+    pub fn weight(&mut self, weight: FontWeight) -> &mut Self {
+        self.add_attr(Attribute::Weight(weight))
+    }
+
+    pub fn style(&mut self, style: FontStyle) -> &mut Self {
+        self.add_attr(Attribute::Style(style))
+    }
+
+    pub fn text_color(&mut self, color: Color) -> &mut Self {
+        self.add_attr(Attribute::TextColor(color))
+    }
+
+    pub fn underline(&mut self, underline: bool) -> &mut Self {
+        self.add_attr(Attribute::Underline(underline))
+    }
+
+    pub fn strikethrough(&mut self, strikethrough: bool) -> &mut Self {
+        self.add_attr(Attribute::Strikethrough(strikethrough))
+    }
+
+    pub fn link(&mut self, target: impl Into<String>) -> &mut Self {
+        self.add_attr(Attribute::Link(target.into()))
+    }
+}
+
+/// How a run of text looks, with all attributes resolved.
+#[derive(Clone)]
+pub(crate) struct RunStyle {
+    pub family: String,
+    pub size: f64,
+    pub weight: FontWeight,
+    pub style: FontStyle,
+    /// The color, if not that the text is drawn with.
+    pub color: Option<Color>,
+    pub underline: bool,
+    pub strikethrough: bool,
+}
+
+impl RunStyle {
+    pub fn new(family: &str, size: f64) -> RunStyle {
+        RunStyle {
+            family: family.to_string(),
+            size,
+            weight: FontWeight::NORMAL,
+            style: FontStyle::Normal,
+            color: None,
+            underline: false,
+            strikethrough: false,
+        }
+    }
+
+    fn apply(&mut self, attribute: &Attribute) {
+        match attribute {
+            Attribute::FontFamily(family) => self.family = family.clone(),
+            Attribute::FontSize(size) => self.size = *size,
+            Attribute::Weight(weight) => self.weight = *weight,
+            Attribute::Style(style) => self.style = *style,
+            Attribute::TextColor(color) => self.color = Some(color.clone()),
+            Attribute::Underline(on) => self.underline = *on,
+            Attribute::Strikethrough(on) => self.strikethrough = *on,
+            Attribute::Link(_) => (),
+        }
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The keys of the colors, fonts and sizes the built-in widgets are drawn
+//! with, and their light and dark defaults.
+//!
+//! A `Ui` starts with the theme of the system's appearance. Setting the keys
+//! with `Ui::set_env` restyles all widgets, and styles such as `ButtonStyle`
+//! set them for a single widget with `WidgetExt::with_style`.
+
+use crate::piet::Color;
+use crate::{Env, EnvKey, LayoutDirection, Theme};
+
+/// Whether rows, padding and directional icons are mirrored. It follows
+/// the locale; see `Ui::set_locale`.
+pub const LAYOUT_DIRECTION: EnvKey<LayoutDirection> = EnvKey::new("druid.theme.layout_direction");
+
+pub const WINDOW_BACKGROUND_COLOR: EnvKey<Color> =
+    EnvKey::new("druid.theme.window_background_color");
+
+/// The color of text, in labels, buttons and text boxes.
+pub const LABEL_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.label_color");
+/// The color of icons, the knobs of sliders and the bars of progress bars.
+pub const FOREGROUND_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.foreground_color");
+/// The color behind the knobs of sliders and the bars of progress bars.
+pub const TRACK_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.track_color");
+
+pub const BUTTON_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.button_color");
+pub const BUTTON_HOVER_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.button_hover_color");
+pub const BUTTON_PRESSED_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.button_pressed_color");
+pub const BUTTON_CORNER_RADIUS: EnvKey<f64> = EnvKey::new("druid.theme.button_corner_radius");
+
+/// The border of text boxes without the focus.
+pub const BORDER_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.border_color");
+/// The border of the text box with the focus.
+pub const FOCUSED_BORDER_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.focused_border_color");
+/// The ring painted around buttons, sliders and other widgets with the
+/// keyboard focus.
+pub const FOCUS_RING_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.focus_ring_color");
+pub const FOCUS_RING_WIDTH: EnvKey<f64> = EnvKey::new("druid.theme.focus_ring_width");
+pub const CURSOR_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.cursor_color");
+pub const SELECTION_COLOR: EnvKey<Color> = EnvKey::new("druid.theme.selection_color");
+
+pub const FONT_NAME: EnvKey<String> = EnvKey::new("druid.theme.font_name");
+/// The font size of labels and buttons at the default text scale.
+pub const TEXT_SIZE_NORMAL: EnvKey<f64> = EnvKey::new("druid.theme.text_size_normal");
+
+/// The height of text boxes, sliders and progress bars at the default text
+/// scale, in px. Text boxes fit their text to it.
+pub const BASIC_WIDGET_HEIGHT: EnvKey<f64> = EnvKey::new("druid.theme.basic_widget_height");
+pub const TEXT_BOX_BORDER_WIDTH: EnvKey<f64> = EnvKey::new("druid.theme.text_box_border_width");
+
+/// The default theme for the light or dark appearance `theme`.
+pub fn init(theme: Theme) -> Env {
+    let env = Env::empty()
+        .adding(LAYOUT_DIRECTION, LayoutDirection::LeftToRight)
+        .adding(FONT_NAME, "Segoe UI")
+        .adding(TEXT_SIZE_NORMAL, 15.0)
+        .adding(BASIC_WIDGET_HEIGHT, 24.0)
+        .adding(BUTTON_CORNER_RADIUS, 0.0)
+        .adding(FOCUS_RING_WIDTH, 2.0)
+        .adding(TEXT_BOX_BORDER_WIDTH, 2.0);
+    match theme {
+        Theme::Dark => env
+            .adding(WINDOW_BACKGROUND_COLOR, Color::rgb24(0x27_28_22))
+            .adding(LABEL_COLOR, Color::rgb24(0xf0_f0_ea))
+            .adding(FOREGROUND_COLOR, Color::rgb24(0xf0_f0_ea))
+            .adding(TRACK_COLOR, Color::rgb24(0x55_55_55))
+            .adding(BUTTON_COLOR, Color::rgb24(0x40_40_48))
+            .adding(BUTTON_HOVER_COLOR, Color::rgb24(0x50_50_58))
+            .adding(BUTTON_PRESSED_COLOR, Color::rgb24(0x60_60_68))
+            .adding(BORDER_COLOR, Color::rgb24(0x55_55_55))
+            .adding(FOCUSED_BORDER_COLOR, Color::rgb24(0xff_00_00))
+            .adding(FOCUS_RING_COLOR, Color::rgb24(0x5c_9d_ff))
+            .adding(CURSOR_COLOR, Color::WHITE)
+            .adding(SELECTION_COLOR, Color::rgb24(0x3b_5b_8c)),
+        Theme::Light => env
+            .adding(WINDOW_BACKGROUND_COLOR, Color::rgb24(0xf4_f4_f0))
+            .adding(LABEL_COLOR, Color::rgb24(0x1e_1e_1c))
+            .adding(FOREGROUND_COLOR, Color::rgb24(0x3c_3c_40))
+            .adding(TRACK_COLOR, Color::rgb24(0xc8_c8_c8))
+            .adding(BUTTON_COLOR, Color::rgb24(0xdc_dc_e0))
+            .adding(BUTTON_HOVER_COLOR, Color::rgb24(0xcc_cc_d2))
+            .adding(BUTTON_PRESSED_COLOR, Color::rgb24(0xbc_bc_c4))
+            .adding(BORDER_COLOR, Color::rgb24(0xa0_a0_a0))
+            .adding(FOCUSED_BORDER_COLOR, Color::rgb24(0xd0_00_00))
+            .adding(FOCUS_RING_COLOR, Color::rgb24(0x1a_66_d9))
+            .adding(CURSOR_COLOR, Color::BLACK)
+            .adding(SELECTION_COLOR, Color::rgb24(0xb3_d4_fc)),
+    }
+}
@@ -16,63 +16,266 @@
 
 use std::any::Any;
 
-use crate::kurbo::{Point, Rect, Size};
-use crate::piet::{Color, FillRule, FontBuilder, Piet, RenderContext, Text, TextLayoutBuilder};
+use crate::accessibility::{Action, Role};
+use crate::kurbo::{BezPath, Point, Rect, Size};
+use crate::piet::{Color, FillRule};
 
-use crate::widget::Widget;
-use crate::{BoxConstraints, LayoutResult};
-use crate::{HandlerCtx, Id, LayoutCtx, MouseEvent, PaintCtx, Ui};
+use crate::widget::styled::apply_value;
+use crate::widget::{LifeCycle, Stylable, Style, Widget};
+use crate::{commands, editing, Cursor, EditAction, EditKeymap, KeyCode, KeyEvent, Selection};
+use crate::{theme, Env, TextLayout, TOUCH_TARGET_SIZE};
+use crate::{AccessNode, ActionRequest, BoxConstraints, LayoutResult, LineBreaking, RichText};
+use crate::{HandlerCtx, Id, LayoutCtx, LocalizedString, MouseEvent, PaintCtx, Ui};
 
-const BUTTON_BG_COLOR: Color = Color::rgba32(0x40_40_48_ff);
-const BUTTON_HOVER_COLOR: Color = Color::rgba32(0x50_50_58_ff);
-const BUTTON_PRESSED_COLOR: Color = Color::rgba32(0x60_60_68_ff);
-const LABEL_TEXT_COLOR: Color = Color::rgba32(0xf0_f0_ea_ff);
-
-/// A text label with no interaction.
+/// A text label, plain or rich, with no interaction but its links, and
+/// selecting and copying its text if made `selectable`.
 pub struct Label {
-    label: String,
+    layout: TextLayout,
+    line_breaking: LineBreaking,
+    /// The link pressed, until the mouse is released.
+    pressed_link: Option<String>,
+    /// The selected text, if the label is selectable.
+    selection: Option<Selection>,
+    /// The keys that extend the selection and copy it.
+    keymap: EditKeymap,
+    /// The message shown, looked up again when the locale changes.
+    localized: Option<LocalizedString>,
+}
+
+/// The text of a label: a string, or a message of the current locale.
+#[derive(Debug, Clone, PartialEq)]
+pub enum LabelText {
+    Static(String),
+    Localized(LocalizedString),
+}
+
+impl From<&str> for LabelText {
+    fn from(s: &str) -> LabelText {
+        LabelText::Static(s.to_string())
+    }
+}
+
+impl From<String> for LabelText {
+    fn from(s: String) -> LabelText {
+        LabelText::Static(s)
+    }
 }
 
-/// A clickable button with a label.
+impl From<LocalizedString> for LabelText {
+    fn from(s: LocalizedString) -> LabelText {
+        LabelText::Localized(s)
+    }
+}
+
+/// The event a `Label` sends to listeners when a link in its text is
+/// clicked, with the target of the link.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LinkClicked(pub String);
+
+/// A clickable button with a label. It sends `true` to listeners when
+/// clicked, or pressed with Space or Enter while it has the focus.
 pub struct Button {
     label: Label,
 }
 
+/// The font and color of a `Label`, over those of the theme.
+#[derive(Clone, Default)]
+pub struct LabelStyle {
+    font_name: Option<String>,
+    text_size: Option<f64>,
+    color: Option<Color>,
+}
+
+impl LabelStyle {
+    pub fn new() -> LabelStyle {
+        Default::default()
+    }
+
+    pub fn font_name(mut self, font_name: &str) -> LabelStyle {
+        self.font_name = Some(font_name.to_string());
+        self
+    }
+
+    /// The font size at the default text scale.
+    pub fn text_size(mut self, text_size: f64) -> LabelStyle {
+        self.text_size = Some(text_size);
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> LabelStyle {
+        self.color = Some(color);
+        self
+    }
+}
+
+impl Style for LabelStyle {
+    fn apply(&self, env: &mut Env) {
+        apply_value(env, theme::FONT_NAME, &self.font_name);
+        apply_value(env, theme::TEXT_SIZE_NORMAL, &self.text_size);
+        apply_value(env, theme::LABEL_COLOR, &self.color);
+    }
+}
+
+impl Stylable for Label {
+    type Style = LabelStyle;
+}
+
+/// The colors and shape of a `Button`, over those of the theme.
+#[derive(Clone, Default)]
+pub struct ButtonStyle {
+    color: Option<Color>,
+    hover_color: Option<Color>,
+    pressed_color: Option<Color>,
+    label_color: Option<Color>,
+    corner_radius: Option<f64>,
+}
+
+impl ButtonStyle {
+    pub fn new() -> ButtonStyle {
+        Default::default()
+    }
+
+    pub fn color(mut self, color: Color) -> ButtonStyle {
+        self.color = Some(color);
+        self
+    }
+
+    pub fn hover_color(mut self, color: Color) -> ButtonStyle {
+        self.hover_color = Some(color);
+        self
+    }
+
+    pub fn pressed_color(mut self, color: Color) -> ButtonStyle {
+        self.pressed_color = Some(color);
+        self
+    }
+
+    /// The color of the label, and of the focus ring.
+    pub fn label_color(mut self, color: Color) -> ButtonStyle {
+        self.label_color = Some(color);
+        self
+    }
+
+    pub fn corner_radius(mut self, radius: f64) -> ButtonStyle {
+        self.corner_radius = Some(radius);
+        self
+    }
+}
+
+impl Style for ButtonStyle {
+    fn apply(&self, env: &mut Env) {
+        apply_value(env, theme::BUTTON_COLOR, &self.color);
+        apply_value(env, theme::BUTTON_HOVER_COLOR, &self.hover_color);
+        apply_value(env, theme::BUTTON_PRESSED_COLOR, &self.pressed_color);
+        apply_value(env, theme::LABEL_COLOR, &self.label_color);
+        apply_value(env, theme::BUTTON_CORNER_RADIUS, &self.corner_radius);
+    }
+}
+
+impl Stylable for Button {
+    type Style = ButtonStyle;
+}
+
 impl Label {
-    pub fn new<S: Into<String>>(label: S) -> Label {
+    pub fn new<S: Into<LabelText>>(label: S) -> Label {
+        match label.into() {
+            LabelText::Static(s) => Label::rich(RichText::new(s)),
+            LabelText::Localized(s) => {
+                // The message is looked up once the label is added.
+                let mut label = Label::rich(RichText::new(""));
+                label.localized = Some(s);
+                label
+            }
+        }
+    }
+
+    /// A label showing `text` with its attributes, over the font and color
+    /// of plain labels.
+    pub fn rich(text: RichText) -> Label {
+        // The font is set from the theme before the text is laid out.
+        let mut layout = TextLayout::new("", "", 0.0);
+        layout.set_rich_text(text);
         Label {
-            label: label.into(),
+            layout,
+            line_breaking: LineBreaking::Overflow,
+            pressed_link: None,
+            selection: None,
+            keymap: EditKeymap::default(),
+            localized: None,
         }
     }
 
+    /// Let the text be selected with the mouse and copied, though not
+    /// edited, as for messages the user may want to paste elsewhere.
+    pub fn selectable(mut self) -> Label {
+        self.selection = Some(Selection::caret(0));
+        self
+    }
+
+    /// Select with the keys of `keymap` rather than the platform's, as for
+    /// a `TextBox`. Only its selection movements and commands are used.
+    pub fn keymap(mut self, keymap: EditKeymap) -> Label {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Fit the text to the width the label is given as `line_breaking`
+    /// says. Unless it overflows, the label is then as big as its text
+    /// rather than a fixed size.
+    pub fn line_breaking(mut self, line_breaking: LineBreaking) -> Label {
+        self.line_breaking = line_breaking;
+        self.layout.set_line_breaking(line_breaking);
+        self
+    }
+
+    /// Show at most `max_lines` lines, ending the last with an ellipsis if
+    /// there is more text.
+    pub fn max_lines(mut self, max_lines: usize) -> Label {
+        self.layout.set_max_lines(Some(max_lines));
+        self
+    }
+
     pub fn ui(self, ctx: &mut Ui) -> Id {
         ctx.add(self, &[])
     }
 
-    fn get_layout(&self, rt: &mut Piet, font_size: f64) -> <Piet as RenderContext>::TextLayout {
-        // TODO: caching of both the format and the layout
-        let font = rt
-            .text()
-            .new_font_by_name("Segoe UI", font_size)
-            .unwrap()
-            .build()
-            .unwrap();
-        rt.text()
-            .new_text_layout(&font, &self.label)
-            .unwrap()
-            .build()
-            .unwrap()
+    /// The target of the link at `pos`, relative to the label.
+    fn link_at(&mut self, pos: Point) -> Option<String> {
+        let offset = self.layout.char_at_point(pos)?;
+        self.layout.rich_text().link_at(offset).map(str::to_string)
+    }
+
+    /// The offset of the caret for a click at `pos`, relative to the label.
+    fn offset_at(&mut self, pos: Point) -> usize {
+        let offset = self.layout.hit_test_point(pos);
+        editing::snap_to_grapheme(&self.layout.rich_text().as_str().to_string(), offset)
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let text = self.layout.rich_text().as_str();
+        let range = self.selection?.range();
+        text.get(range)
+            .filter(|text| !text.is_empty())
+            .map(str::to_string)
     }
 }
 
 impl Widget for Label {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
-        let font_size = 15.0;
-        let text_layout = self.get_layout(paint_ctx.render_ctx, font_size);
-        let brush = paint_ctx.render_ctx.solid_brush(LABEL_TEXT_COLOR);
-
-        let pos = Point::new(geom.origin().x, geom.origin().y + font_size);
-        paint_ctx.render_ctx.draw_text(&text_layout, pos, &brush);
+        let env = paint_ctx.env().clone();
+        let font_size = env.get(theme::TEXT_SIZE_NORMAL) * paint_ctx.text_scale();
+        self.layout.set_font(&env.get(theme::FONT_NAME), font_size);
+        if let (Some(selection), true) = (self.selection, paint_ctx.is_focused()) {
+            let color = env.get(theme::SELECTION_COLOR);
+            for rect in self.layout.rects_for_range(selection.range()) {
+                if rect.width() > 0.0 {
+                    let rect = rect + geom.origin().to_vec2();
+                    paint_ctx.fill_color(rect, &color, FillRule::NonZero);
+                }
+            }
+        }
+        let color = env.get(theme::LABEL_COLOR);
+        paint_ctx.draw_text_layout(&mut self.layout, geom.origin(), &color);
     }
 
     fn layout(
@@ -80,26 +283,165 @@ impl Widget for Label {
         bc: &BoxConstraints,
         _children: &[Id],
         _size: Option<Size>,
-        _ctx: &mut LayoutCtx,
+        ctx: &mut LayoutCtx,
     ) -> LayoutResult {
-        // TODO: measure text properly
-        LayoutResult::Size(bc.constrain((100.0, 17.0)))
+        let scale = ctx.text_scale();
+        if self.line_breaking == LineBreaking::Overflow {
+            // TODO: measure text properly
+            return LayoutResult::Size(bc.constrain((100.0 * scale, 17.0 * scale)));
+        }
+        let max_width = bc.max().width;
+        let font_size = ctx.env().get(theme::TEXT_SIZE_NORMAL) * scale;
+        self.layout
+            .set_font(&ctx.env().get(theme::FONT_NAME), font_size);
+        self.layout
+            .set_max_width(Some(max_width).filter(|width| width.is_finite()));
+        LayoutResult::Size(bc.constrain(self.layout.size()))
     }
 
-    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
-        if let Some(string) = payload.downcast_ref::<String>() {
-            self.label = string.clone();
+    fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
+        if event.count > 0 {
+            self.pressed_link = self.link_at(event.pos);
+            if self.pressed_link.is_some() {
+                ctx.set_active(true);
+                return true;
+            }
+            let selection = match self.selection {
+                Some(selection) => selection,
+                None => return false,
+            };
+            ctx.request_focus();
+            let offset = self.offset_at(event.pos);
+            self.selection = Some(if event.mods.shift {
+                Selection::new(selection.anchor, offset)
+            } else {
+                Selection::caret(offset)
+            });
+            ctx.set_active(true);
             ctx.invalidate();
             true
+        } else if let Some(target) = self.pressed_link.take() {
+            ctx.set_active(false);
+            if self.link_at(event.pos).as_ref() == Some(&target) {
+                ctx.send_event(LinkClicked(target));
+            }
+            true
+        } else {
+            ctx.set_active(false);
+            self.selection.is_some()
+        }
+    }
+
+    fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => return,
+        };
+        ctx.set_cursor(&Cursor::IBeam);
+        if ctx.is_active() && self.pressed_link.is_none() {
+            let offset = self.offset_at(pos);
+            if offset != selection.active {
+                self.selection = Some(Selection::new(selection.anchor, offset));
+                ctx.invalidate();
+            }
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.selection.is_some()
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        node.role = Role::Label;
+        node.name = Some(self.layout.rich_text().as_str().to_string());
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        match event {
+            LifeCycle::WidgetAdded | LifeCycle::LocaleChanged => {
+                if let Some(localized) = &self.localized {
+                    self.layout.set_text(localized.resolve(ctx.localization()));
+                    if let Some(selection) = &mut self.selection {
+                        *selection = Selection::caret(0);
+                    }
+                    ctx.invalidate();
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn command(&mut self, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        if self.selection.is_none() {
+            return false;
+        }
+        match cmd {
+            commands::COPY => {
+                if let Some(text) = self.selected_text() {
+                    ctx.clipboard().put_string(&text);
+                }
+            }
+            commands::SELECT_ALL => {
+                let len = self.layout.rich_text().len();
+                self.selection = Some(Selection::new(0, len));
+                ctx.invalidate();
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        let selection = match self.selection {
+            Some(selection) => selection,
+            None => return false,
+        };
+        // Keys that would move the caret extend the selection read-only.
+        match self.keymap.lookup(event) {
+            Some(EditAction::Command(cmd)) => return self.command(cmd, ctx),
+            Some(EditAction::Select(movement)) => {
+                let text = self.layout.rich_text().as_str().to_string();
+                let layout = &mut self.layout;
+                let selection = editing::movement(movement, selection, &text, layout, true);
+                self.selection = Some(selection);
+                ctx.invalidate();
+                return true;
+            }
+            _ => (),
+        }
+        match event.code {
+            KeyCode::Escape => ctx.resign_focus(),
+            KeyCode::Tab if event.modifiers.shift => ctx.focus_prev(),
+            KeyCode::Tab => ctx.focus_next(),
+            _ => return false,
+        }
+        true
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        if let Some(string) = payload.downcast_ref::<String>() {
+            self.layout.set_text(string.clone());
+            self.localized = None;
+        } else if let Some(text) = payload.downcast_ref::<RichText>() {
+            self.layout.set_rich_text(text.clone());
+            self.localized = None;
+        } else if let Some(localized) = payload.downcast_ref::<LocalizedString>() {
+            self.layout.set_text(localized.resolve(ctx.localization()));
+            self.localized = Some(localized.clone());
         } else {
             println!("downcast failed");
-            false
+            return false;
         }
+        if let Some(selection) = &mut self.selection {
+            *selection = Selection::caret(0);
+        }
+        ctx.invalidate();
+        true
     }
 }
 
 impl Button {
-    pub fn new<S: Into<String>>(label: S) -> Button {
+    pub fn new<S: Into<LabelText>>(label: S) -> Button {
         Button {
             label: Label::new(label),
         }
@@ -116,14 +458,23 @@ impl Widget for Button {
             let is_active = paint_ctx.is_active();
             let is_hot = paint_ctx.is_hot();
             let bg_color = match (is_active, is_hot) {
-                (true, true) => BUTTON_PRESSED_COLOR,
-                (false, true) => BUTTON_HOVER_COLOR,
-                _ => BUTTON_BG_COLOR,
+                (true, true) => theme::BUTTON_PRESSED_COLOR,
+                (false, true) => theme::BUTTON_HOVER_COLOR,
+                _ => theme::BUTTON_COLOR,
             };
-            let brush = paint_ctx.render_ctx.solid_brush(bg_color);
-            paint_ctx.render_ctx.fill(geom, &brush, FillRule::NonZero);
+            let bg_color = paint_ctx.env().get(bg_color);
+            let radius = paint_ctx.env().get(theme::BUTTON_CORNER_RADIUS);
+            let rect = paint_ctx.snap_rect(*geom);
+            if radius > 0.0 {
+                paint_ctx.fill_color(rounded_rect(rect, radius), &bg_color, FillRule::NonZero);
+            } else {
+                paint_ctx.fill_color(rect, &bg_color, FillRule::NonZero);
+            }
         }
         self.label.paint(paint_ctx, geom);
+        if paint_ctx.is_focused() {
+            paint_ctx.paint_focus_ring(*geom);
+        }
     }
 
     fn layout(
@@ -133,7 +484,13 @@ impl Widget for Button {
         size: Option<Size>,
         ctx: &mut LayoutCtx,
     ) -> LayoutResult {
-        self.label.layout(bc, children, size, ctx)
+        match self.label.layout(bc, children, size, ctx) {
+            LayoutResult::Size(size) if ctx.touch_mode() => {
+                let height = size.height.max(TOUCH_TARGET_SIZE);
+                LayoutResult::Size(bc.constrain((size.width, height)))
+            }
+            result => result,
+        }
     }
 
     fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
@@ -149,11 +506,70 @@ impl Widget for Button {
         true
     }
 
-    fn on_hot_changed(&mut self, _hot: bool, ctx: &mut HandlerCtx) {
-        ctx.invalidate();
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        let mods = event.modifiers;
+        if mods.ctrl || mods.alt || mods.meta {
+            return false;
+        }
+        match event.code {
+            KeyCode::Space | KeyCode::Return | KeyCode::NumpadEnter => {
+                if !event.is_repeat {
+                    ctx.send_event(true);
+                }
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        if let LifeCycle::HotChanged(_) | LifeCycle::FocusChanged(_) = event {
+            ctx.invalidate();
+        }
+        self.label.lifecycle(event, ctx);
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        self.label.accessibility(node);
+        node.role = Role::Button;
+        node.actions = vec![Action::Focus, Action::Click];
+    }
+
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        match request.action {
+            Action::Click => {
+                ctx.send_event(true);
+                true
+            }
+            _ => false,
+        }
     }
 
     fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
         self.label.poke(payload, ctx)
     }
 }
+
+/// The outline of `rect` with its corners rounded by `radius`.
+fn rounded_rect(rect: Rect, radius: f64) -> BezPath {
+    let r = radius.min(rect.width() / 2.0).min(rect.height() / 2.0);
+    // How far the control points of a quarter circle are from its corner.
+    let k = r * (1.0 - 0.552_284_75);
+    let (x0, y0, x1, y1) = (rect.x0, rect.y0, rect.x1, rect.y1);
+    let mut path = BezPath::new();
+    path.move_to((x0 + r, y0));
+    path.line_to((x1 - r, y0));
+    path.curve_to((x1 - k, y0), (x1, y0 + k), (x1, y0 + r));
+    path.line_to((x1, y1 - r));
+    path.curve_to((x1, y1 - k), (x1 - k, y1), (x1 - r, y1));
+    path.line_to((x0 + r, y1));
+    path.curve_to((x0 + k, y1), (x0, y1 - k), (x0, y1 - r));
+    path.line_to((x0, y0 + r));
+    path.curve_to((x0, y0 + k), (x0 + k, y0), (x0 + r, y0));
+    path.close_path();
+    path
+}
@@ -0,0 +1,117 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A controller that makes any widget clickable.
+
+use crate::accessibility::Action;
+use crate::kurbo::Rect;
+use crate::widget::{Controller, KeyCode, KeyEvent, LifeCycle, MouseButton, MouseEvent, Widget};
+use crate::{AccessNode, ActionRequest, HandlerCtx, PaintCtx};
+
+/// Calls an action when the widget is clicked.
+///
+/// A click is a left button press followed by a release while the pointer is
+/// still over the widget. The widget is active in between, so the press can
+/// be painted. The widget also joins the focus chain, and Space or Enter
+/// clicks it while focused; the focus ring is painted around widgets that
+/// don't accept focus of their own.
+///
+/// Usually attached with [`WidgetExt::on_click`].
+///
+/// [`WidgetExt::on_click`]: trait.WidgetExt.html#method.on_click
+pub struct Click<F> {
+    action: F,
+}
+
+impl<F: FnMut(&mut HandlerCtx)> Click<F> {
+    pub fn new(action: F) -> Click<F> {
+        Click { action }
+    }
+}
+
+impl<W: Widget, F: FnMut(&mut HandlerCtx)> Controller<W> for Click<F> {
+    fn paint(&mut self, child: &mut W, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        child.paint(paint_ctx, geom);
+        if paint_ctx.is_focused() && !child.accepts_focus() {
+            paint_ctx.paint_focus_ring(*geom);
+        }
+    }
+
+    fn mouse(&mut self, child: &mut W, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
+        let handled = child.mouse(event, ctx);
+        if event.button != MouseButton::Left {
+            return handled;
+        }
+        if event.count > 0 {
+            ctx.set_active(true);
+        } else if ctx.is_active() {
+            ctx.set_active(false);
+            if ctx.is_hot() {
+                (self.action)(ctx);
+            }
+        }
+        ctx.invalidate();
+        true
+    }
+
+    fn key_down(&mut self, child: &mut W, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        let mods = event.modifiers;
+        let plain = !(mods.ctrl || mods.alt || mods.meta);
+        match event.code {
+            KeyCode::Space | KeyCode::Return | KeyCode::NumpadEnter if plain => {
+                if !event.is_repeat {
+                    (self.action)(ctx);
+                }
+                true
+            }
+            _ => child.key_down(event, ctx),
+        }
+    }
+
+    fn lifecycle(&mut self, child: &mut W, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        child.lifecycle(event, ctx);
+        match event {
+            LifeCycle::HotChanged(_) | LifeCycle::FocusChanged(_) => ctx.invalidate(),
+            _ => (),
+        }
+    }
+
+    fn accepts_focus(&self, _child: &W) -> bool {
+        true
+    }
+
+    fn accessibility(&self, child: &W, node: &mut AccessNode) {
+        child.accessibility(node);
+        for &action in &[Action::Focus, Action::Click] {
+            if !node.supports(action) {
+                node.actions.push(action);
+            }
+        }
+    }
+
+    fn accessibility_action(
+        &mut self,
+        child: &mut W,
+        request: &ActionRequest,
+        ctx: &mut HandlerCtx,
+    ) -> bool {
+        match request.action {
+            Action::Click => {
+                (self.action)(ctx);
+                true
+            }
+            _ => child.accessibility_action(request, ctx),
+        }
+    }
+}
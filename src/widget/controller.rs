@@ -0,0 +1,255 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Layering behavior onto an existing widget.
+
+use std::any::Any;
+
+use crate::kurbo::{Point, Rect, Size};
+use crate::widget::{
+    CompositionEvent, DragEvent, GestureEvent, KeyEvent, LifeCycle, MouseEvent, Notification,
+    PenEvent, TouchEvent, WheelEvent, Widget,
+};
+use crate::{AccessNode, ActionRequest, BoxConstraints, LayoutResult};
+use crate::{DropEffect, HandlerCtx, Id, LayoutCtx, PaintCtx, TimerToken, Ui};
+
+/// Intercepts the input and lifecycle methods of a widget.
+///
+/// Each method is given the wrapped widget, and the default implementations
+/// simply call through to it. A controller overrides only the methods it is
+/// interested in, and decides whether the widget sees the event at all.
+///
+/// Controllers are attached with [`WidgetExt::controller`].
+///
+/// [`WidgetExt::controller`]: trait.WidgetExt.html#method.controller
+pub trait Controller<W: Widget> {
+    fn paint(&mut self, child: &mut W, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        child.paint(paint_ctx, geom)
+    }
+
+    fn mouse(&mut self, child: &mut W, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
+        child.mouse(event, ctx)
+    }
+
+    fn mouse_moved(&mut self, child: &mut W, pos: Point, ctx: &mut HandlerCtx) {
+        child.mouse_moved(pos, ctx)
+    }
+
+    fn touch(&mut self, child: &mut W, event: &TouchEvent, ctx: &mut HandlerCtx) -> bool {
+        child.touch(event, ctx)
+    }
+
+    fn gesture(&mut self, child: &mut W, event: &GestureEvent, ctx: &mut HandlerCtx) -> bool {
+        child.gesture(event, ctx)
+    }
+
+    fn pen(&mut self, child: &mut W, event: &PenEvent, ctx: &mut HandlerCtx) -> bool {
+        child.pen(event, ctx)
+    }
+
+    fn key_down(&mut self, child: &mut W, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        child.key_down(event, ctx)
+    }
+
+    fn key_up(&mut self, child: &mut W, event: &KeyEvent, ctx: &mut HandlerCtx) {
+        child.key_up(event, ctx)
+    }
+
+    fn composition(&mut self, child: &mut W, event: &CompositionEvent, ctx: &mut HandlerCtx) {
+        child.composition(event, ctx)
+    }
+
+    fn wheel(&mut self, child: &mut W, event: &WheelEvent, ctx: &mut HandlerCtx) {
+        child.wheel(event, ctx)
+    }
+
+    fn drag(&mut self, child: &mut W, event: &DragEvent, ctx: &mut HandlerCtx) -> bool {
+        child.drag(event, ctx)
+    }
+
+    fn system_drag_ended(&mut self, child: &mut W, effect: DropEffect, ctx: &mut HandlerCtx) {
+        child.system_drag_ended(effect, ctx)
+    }
+
+    fn timer(&mut self, child: &mut W, token: TimerToken, ctx: &mut HandlerCtx) {
+        child.timer(token, ctx)
+    }
+
+    fn notification(
+        &mut self,
+        child: &mut W,
+        notification: &Notification,
+        ctx: &mut HandlerCtx,
+    ) -> bool {
+        child.notification(notification, ctx)
+    }
+
+    fn poke(&mut self, child: &mut W, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        child.poke(payload, ctx)
+    }
+
+    fn command(&mut self, child: &mut W, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        child.command(cmd, ctx)
+    }
+
+    fn lifecycle(&mut self, child: &mut W, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        child.lifecycle(event, ctx)
+    }
+
+    /// Override the widget's focus policy.
+    fn accepts_focus(&self, child: &W) -> bool {
+        child.accepts_focus()
+    }
+
+    fn accessibility(&self, child: &W, node: &mut AccessNode) {
+        child.accessibility(node)
+    }
+
+    fn accessibility_action(
+        &mut self,
+        child: &mut W,
+        request: &ActionRequest,
+        ctx: &mut HandlerCtx,
+    ) -> bool {
+        child.accessibility_action(request, ctx)
+    }
+}
+
+/// A widget paired with the [`Controller`] intercepting its events.
+///
+/// The host takes the widget's place in the graph, so the widget's children
+/// are unaffected.
+///
+/// [`Controller`]: trait.Controller.html
+pub struct ControllerHost<W, C> {
+    widget: W,
+    controller: C,
+}
+
+impl<W: Widget, C: Controller<W>> ControllerHost<W, C> {
+    pub fn new(widget: W, controller: C) -> ControllerHost<W, C> {
+        ControllerHost { widget, controller }
+    }
+
+    pub fn ui(self, children: &[Id], ctx: &mut Ui) -> Id
+    where
+        W: 'static,
+        C: 'static,
+    {
+        ctx.add(self, children)
+    }
+}
+
+impl<W: Widget, C: Controller<W>> Widget for ControllerHost<W, C> {
+    fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        self.controller.paint(&mut self.widget, paint_ctx, geom)
+    }
+
+    fn layout(
+        &mut self,
+        bc: &BoxConstraints,
+        children: &[Id],
+        size: Option<Size>,
+        ctx: &mut LayoutCtx,
+    ) -> LayoutResult {
+        self.widget.layout(bc, children, size, ctx)
+    }
+
+    fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
+        self.controller.mouse(&mut self.widget, event, ctx)
+    }
+
+    fn touch(&mut self, event: &TouchEvent, ctx: &mut HandlerCtx) -> bool {
+        self.controller.touch(&mut self.widget, event, ctx)
+    }
+
+    fn gesture(&mut self, event: &GestureEvent, ctx: &mut HandlerCtx) -> bool {
+        self.controller.gesture(&mut self.widget, event, ctx)
+    }
+
+    fn pen(&mut self, event: &PenEvent, ctx: &mut HandlerCtx) -> bool {
+        self.controller.pen(&mut self.widget, event, ctx)
+    }
+
+    fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {
+        self.controller.mouse_moved(&mut self.widget, pos, ctx)
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        self.controller.lifecycle(&mut self.widget, event, ctx)
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.controller.accepts_focus(&self.widget)
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        self.controller.accessibility(&self.widget, node)
+    }
+
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        self.controller
+            .accessibility_action(&mut self.widget, request, ctx)
+    }
+
+    fn drag(&mut self, event: &DragEvent, ctx: &mut HandlerCtx) -> bool {
+        self.controller.drag(&mut self.widget, event, ctx)
+    }
+
+    fn system_drag_ended(&mut self, effect: DropEffect, ctx: &mut HandlerCtx) {
+        self.controller
+            .system_drag_ended(&mut self.widget, effect, ctx)
+    }
+
+    fn timer(&mut self, token: TimerToken, ctx: &mut HandlerCtx) {
+        self.controller.timer(&mut self.widget, token, ctx)
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        self.controller.poke(&mut self.widget, payload, ctx)
+    }
+
+    fn notification(&mut self, notification: &Notification, ctx: &mut HandlerCtx) -> bool {
+        self.controller
+            .notification(&mut self.widget, notification, ctx)
+    }
+
+    fn command(&mut self, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        self.controller.command(&mut self.widget, cmd, ctx)
+    }
+
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        self.controller.key_down(&mut self.widget, event, ctx)
+    }
+
+    fn key_up(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) {
+        self.controller.key_up(&mut self.widget, event, ctx)
+    }
+
+    fn composition(&mut self, event: &CompositionEvent, ctx: &mut HandlerCtx) {
+        self.controller.composition(&mut self.widget, event, ctx)
+    }
+
+    fn wheel(&mut self, event: &WheelEvent, ctx: &mut HandlerCtx) {
+        self.controller.wheel(&mut self.widget, event, ctx)
+    }
+
+    fn anim_frame(&mut self, interval: u64, ctx: &mut HandlerCtx) {
+        self.widget.anim_frame(interval, ctx)
+    }
+
+    fn on_child_removed(&mut self, child: Id) {
+        self.widget.on_child_removed(child)
+    }
+}
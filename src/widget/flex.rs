@@ -19,7 +19,7 @@ use std::collections::BTreeMap;
 use crate::kurbo::Size;
 
 use crate::widget::Widget;
-use crate::{BoxConstraints, LayoutResult};
+use crate::{theme, BoxConstraints, LayoutResult};
 use crate::{Id, LayoutCtx, Ui};
 
 pub struct Row;
@@ -169,13 +169,24 @@ impl Flex {
         children: &[Id],
         ctx: &mut LayoutCtx,
     ) -> LayoutResult {
+        let total_major = self.direction.major(bc.max);
+        // Rows start on the right in right-to-left layouts.
+        let mirrored = match self.direction {
+            Axis::Horizontal => ctx.env().get(theme::LAYOUT_DIRECTION).is_rtl(),
+            Axis::Vertical => false,
+        };
         let mut major = 0.0;
         for &child in children {
+            let child_major = self.direction.major(ctx.get_child_size(child));
+            let pos = if mirrored {
+                total_major - major - child_major
+            } else {
+                major
+            };
             // top-align, could do center etc. based on child height
-            ctx.position_child(child, self.direction.pack(major, 0.0));
-            major += self.direction.major(ctx.get_child_size(child));
+            ctx.position_child(child, self.direction.pack(pos, 0.0));
+            major += child_major;
         }
-        let total_major = self.direction.major(bc.max);
         let (width, height) = self.direction.pack(total_major, self.minor);
         LayoutResult::Size(Size::new(width, height))
     }
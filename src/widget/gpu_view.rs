@@ -0,0 +1,86 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A widget rendered with a GPU API of the application's choice.
+
+use druid_shell::surface::GpuSurface;
+
+use crate::widget::Widget;
+use crate::{BoxConstraints, Id, LayoutCtx, LayoutResult, PaintCtx, Ui};
+
+use crate::kurbo::{Rect, Size};
+
+/// A widget that owns a native surface, for 3D viewports, video and other
+/// content rendered with OpenGL, Direct3D, Metal or wgpu rather than piet.
+///
+/// The system composites the surface with the window, over whatever druid
+/// paints beneath it. The surface follows the widget's layout, and is
+/// destroyed when the widget is deleted.
+///
+/// On Windows, the window must be built with `PresentStrategy::Hwnd`.
+pub struct GpuView {
+    surface: Option<GpuSurface>,
+    /// Set when creating the surface failed, so that it isn't retried on
+    /// every layout.
+    failed: bool,
+    render: Box<dyn FnMut(&GpuSurface, Size)>,
+}
+
+impl GpuView {
+    /// Create the view. `render` is called on each paint of the widget with
+    /// the surface, whose `raw_window_handle` gives its native handles, and
+    /// the size of the widget in px.
+    ///
+    /// The view fills the space it is given.
+    pub fn new(render: impl FnMut(&GpuSurface, Size) + 'static) -> GpuView {
+        GpuView {
+            surface: None,
+            failed: false,
+            render: Box::new(render),
+        }
+    }
+
+    pub fn ui(self, ctx: &mut Ui) -> Id {
+        ctx.add(self, &[])
+    }
+}
+
+impl Widget for GpuView {
+    fn paint(&mut self, _paint_ctx: &mut PaintCtx, geom: &Rect) {
+        if let Some(ref surface) = self.surface {
+            surface.set_frame(*geom);
+            surface.set_visible(true);
+            (self.render)(surface, geom.size());
+        }
+    }
+
+    fn layout(
+        &mut self,
+        bc: &BoxConstraints,
+        _children: &[Id],
+        _size: Option<Size>,
+        ctx: &mut LayoutCtx,
+    ) -> LayoutResult {
+        if self.surface.is_none() && !self.failed {
+            match ctx.window().create_gpu_surface() {
+                Ok(surface) => self.surface = Some(surface),
+                Err(e) => {
+                    println!("Error creating GPU surface: {:?}", e);
+                    self.failed = true;
+                }
+            }
+        }
+        LayoutResult::Size(bc.max)
+    }
+}
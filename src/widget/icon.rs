@@ -0,0 +1,133 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A widget showing a vector icon.
+
+use std::any::Any;
+
+use crate::accessibility::Role;
+use crate::icons;
+use crate::kurbo::{Affine, BezPath, Rect, Size, Vec2};
+use crate::piet::{Color, FillRule};
+
+use crate::widget::Widget;
+use crate::{theme, AccessNode, BoxConstraints, HandlerCtx, Id, LayoutCtx, LayoutResult};
+use crate::{PaintCtx, Ui};
+
+/// A path in a square of `icons::SIZE` px, such as one of `icons`, scaled
+/// to a size and filled with a color.
+///
+/// The icon scales with the text. It has the foreground color of the theme
+/// unless given one. Directional icons, such as back arrows, are mirrored
+/// in right-to-left layouts. Poking it with a `Color` tints it, and with a `BezPath`
+/// replaces the path.
+///
+/// Screen readers skip the icon as decoration unless it has a description.
+pub struct Icon {
+    path: BezPath,
+    size: f64,
+    color: Option<Color>,
+    directional: bool,
+    description: Option<String>,
+}
+
+impl Icon {
+    pub fn new(path: BezPath) -> Icon {
+        Icon {
+            path,
+            size: 16.0,
+            color: None,
+            directional: false,
+            description: None,
+        }
+    }
+
+    /// Mirror the icon in right-to-left layouts, for icons pointing along
+    /// the text, such as `icons::chevron_left` for going back.
+    pub fn directional(mut self) -> Icon {
+        self.directional = true;
+        self
+    }
+
+    /// Draw the icon `size` px wide and high at the default text scale.
+    pub fn size(mut self, size: f64) -> Icon {
+        self.size = size;
+        self
+    }
+
+    pub fn color(mut self, color: Color) -> Icon {
+        self.color = Some(color);
+        self
+    }
+
+    /// What the icon shows, read out by screen readers.
+    pub fn description(mut self, description: impl Into<String>) -> Icon {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn ui(self, ctx: &mut Ui) -> Id {
+        ctx.add(self, &[])
+    }
+}
+
+impl Widget for Icon {
+    fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        let size = (self.size * paint_ctx.text_scale())
+            .min(geom.width())
+            .min(geom.height());
+        let offset = geom.center() - Vec2::new(size / 2.0, size / 2.0);
+        let mut path = self.path.clone();
+        let mut transform = Affine::translate(offset.to_vec2()) * Affine::scale(size / icons::SIZE);
+        if self.directional && paint_ctx.env().get(theme::LAYOUT_DIRECTION).is_rtl() {
+            transform = transform * Affine::new([-1.0, 0.0, 0.0, 1.0, icons::SIZE, 0.0]);
+        }
+        path.apply_affine(transform);
+        let color = match &self.color {
+            Some(color) => color.clone(),
+            None => paint_ctx.env().get(theme::FOREGROUND_COLOR),
+        };
+        paint_ctx.fill_color(path, &color, FillRule::NonZero);
+    }
+
+    fn layout(
+        &mut self,
+        bc: &BoxConstraints,
+        _children: &[Id],
+        _size: Option<Size>,
+        ctx: &mut LayoutCtx,
+    ) -> LayoutResult {
+        let size = self.size * ctx.text_scale();
+        LayoutResult::Size(bc.constrain((size, size)))
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        if let Some(description) = &self.description {
+            node.role = Role::Image;
+            node.name = Some(description.clone());
+        }
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        if let Some(color) = payload.downcast_ref::<Color>() {
+            self.color = Some(color.clone());
+        } else if let Some(path) = payload.downcast_ref::<BezPath>() {
+            self.path = path.clone();
+        } else {
+            return false;
+        }
+        ctx.invalidate();
+        true
+    }
+}
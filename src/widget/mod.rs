@@ -16,15 +16,27 @@
 
 use std::any::Any;
 
-pub use druid_shell::keyboard::{KeyCode, KeyEvent, KeyModifiers};
-pub use druid_shell::window::{MouseButton, ScrollEvent};
+pub use druid_shell::keyboard::{Key, KeyCode, KeyEvent, KeyLocation, KeyModifiers};
+#[allow(deprecated)]
+pub use druid_shell::window::ScrollEvent;
+pub use druid_shell::window::{
+    CompositionEvent, GesturePhase, MouseButton, PenPhase, TouchPhase, WheelEvent, WheelPhase,
+    WheelUnit,
+};
 
-use crate::kurbo::{Point, Rect, Size};
-use crate::{BoxConstraints, LayoutResult};
+use crate::kurbo::{Point, Rect, Size, Vec2};
+use crate::{AccessNode, ActionRequest, BoxConstraints, LayoutResult};
+use crate::{DropEffect, TimerToken};
 use crate::{HandlerCtx, Id, LayoutCtx, PaintCtx};
 
 mod button;
-pub use crate::widget::button::{Button, Label};
+pub use crate::widget::button::{Button, ButtonStyle, Label, LabelStyle, LabelText, LinkClicked};
+
+mod click;
+pub use crate::widget::click::Click;
+
+mod controller;
+pub use crate::widget::controller::{Controller, ControllerHost};
 
 mod event_forwarder;
 pub use crate::widget::event_forwarder::EventForwarder;
@@ -32,17 +44,32 @@ pub use crate::widget::event_forwarder::EventForwarder;
 mod flex;
 pub use crate::widget::flex::{Column, Flex, Row};
 
+mod gpu_view;
+pub use crate::widget::gpu_view::GpuView;
+
+mod icon;
+pub use crate::widget::icon::Icon;
+
 mod key_listener;
 pub use crate::widget::key_listener::KeyListener;
 
+mod native_host;
+pub use crate::widget::native_host::NativeHost;
+
 mod null;
 pub(crate) use crate::widget::null::NullWidget;
 
 mod padding;
 pub use crate::widget::padding::Padding;
 
+mod styled;
+pub use crate::widget::styled::{Stylable, Style, Styled};
+
 mod textbox;
-pub use crate::widget::textbox::TextBox;
+pub use crate::widget::textbox::{TextBox, TextBoxStyle};
+
+mod value_textbox;
+pub use crate::widget::value_textbox::ValueTextBox;
 
 mod slider;
 pub use crate::widget::slider::Slider;
@@ -50,12 +77,17 @@ pub use crate::widget::slider::Slider;
 mod progress_bar;
 pub use crate::widget::progress_bar::ProgressBar;
 
+mod widget_ext;
+pub use crate::widget::widget_ext::WidgetExt;
+
 /// The trait implemented by all widgets.
 pub trait Widget {
     /// Paint the widget's appearance into the paint context.
     ///
     /// The implementer is responsible for translating the coordinates as
-    /// specified in the geometry.
+    /// specified in the geometry. A clip or transform set with
+    /// `PaintCtx::clip` or `PaintCtx::transform` also applies to the
+    /// painting of the children.
     #[allow(unused)]
     fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {}
 
@@ -90,15 +122,122 @@ pub trait Widget {
         false
     }
 
+    /// Sent to the widget on touch screen events.
+    ///
+    /// When a touch goes down, it is offered to the widgets under it,
+    /// innermost first, until one returns true. That widget then receives the
+    /// rest of the touch, even when it moves outside its bounds.
+    #[allow(unused)]
+    fn touch(&mut self, event: &TouchEvent, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
+    /// Sent to the widget on pinch, rotate and two-finger pan gestures.
+    ///
+    /// Gestures are recognized from two-finger touches and from trackpad
+    /// pinching and rotation. When a gesture begins, it is offered to the
+    /// widgets under its center, innermost first, until one returns true.
+    /// That widget then receives the rest of the gesture.
+    #[allow(unused)]
+    fn gesture(&mut self, event: &GestureEvent, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
+    /// Sent to the widget on pen or stylus input.
+    ///
+    /// Pen events are offered to the widgets under the pen, innermost first,
+    /// until one returns true. The widget handling `Down` receives the rest
+    /// of the stroke up to `Up`. Pen input that no widget handles is delivered
+    /// as mouse events.
+    #[allow(unused)]
+    fn pen(&mut self, event: &PenEvent, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
     /// Sent to the active or hot widget on mouse move events.
     // TODO: should mods be plumbed here?
     #[allow(unused)]
     fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {}
 
+    /// Sent to the widget when its place in the UI changes, as described by
+    /// [`LifeCycle`]. These notifications are separate from input events.
+    ///
+    /// The default implementation forwards hot changes to the deprecated
+    /// `on_hot_changed` method.
+    ///
+    /// [`LifeCycle`]: enum.LifeCycle.html
+    #[allow(unused, deprecated)]
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        if let LifeCycle::HotChanged(hot) = *event {
+            self.on_hot_changed(hot, ctx);
+        }
+    }
+
     /// Sent to the widget when its "hot" status changes.
+    #[deprecated(note = "please handle `LifeCycle::HotChanged` in `lifecycle`.")]
     #[allow(unused)]
     fn on_hot_changed(&mut self, hot: bool, ctx: &mut HandlerCtx) {}
 
+    /// Whether this widget should be part of the focus chain, reachable with
+    /// `focus_next` and `focus_prev`.
+    ///
+    /// Any widget can be focused explicitly with `request_focus`, regardless
+    /// of this value.
+    fn accepts_focus(&self) -> bool {
+        false
+    }
+
+    /// Describe the widget to assistive technology, such as screen readers:
+    /// its role, name and value, and the actions it supports. The node
+    /// starts out as a nameless `Role::Group`; druid fills in its bounds and
+    /// children.
+    #[allow(unused)]
+    fn accessibility(&self, node: &mut AccessNode) {}
+
+    /// Sent to the widget when assistive technology asks it to perform one
+    /// of the actions of its node. `Action::Focus` is handled by druid,
+    /// which focuses the widget.
+    ///
+    /// Returns true if the action was performed.
+    #[allow(unused)]
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
+    /// Sent to the widget during a drag-and-drop session.
+    ///
+    /// Widgets under the pointer receive `Enter`, then `Over` as the pointer
+    /// moves, starting with the innermost; the first to return true becomes
+    /// the drop target. The target receives `Leave` when it is no longer under
+    /// the pointer, or `Drop` when the mouse button is released, in which case
+    /// the return value indicates whether the payload was accepted. Finally the
+    /// widget that started the drag receives `End`.
+    ///
+    /// To have the drop handled by the command listener, the target calls
+    /// [`HandlerCtx::set_drop_command`] on `Drop`.
+    ///
+    /// [`HandlerCtx::set_drop_command`]: ../struct.HandlerCtx.html#method.set_drop_command
+    #[allow(unused)]
+    fn drag(&mut self, event: &DragEvent, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
+    /// Sent to the widget when a drag it started with
+    /// [`HandlerCtx::start_system_drag`] is over, with what the drop target
+    /// did with the data. On `DropEffect::Move`, the widget should remove the
+    /// dragged item.
+    ///
+    /// [`HandlerCtx::start_system_drag`]: ../struct.HandlerCtx.html#method.start_system_drag
+    #[allow(unused)]
+    fn system_drag_ended(&mut self, effect: DropEffect, ctx: &mut HandlerCtx) {}
+
+    /// Sent to the widget when a timer it requested with
+    /// [`HandlerCtx::request_timer`] fires.
+    ///
+    /// [`HandlerCtx::request_timer`]: ../struct.HandlerCtx.html#method.request_timer
+    #[allow(unused)]
+    fn timer(&mut self, token: TimerToken, ctx: &mut HandlerCtx) {}
+
     /// An "escape hatch" of sorts for accessing widget state beyond the widget
     /// methods. Returns true if it is handled.
     #[allow(unused)]
@@ -106,6 +245,29 @@ pub trait Widget {
         false
     }
 
+    /// Sent to the widget when a descendant submits a notification with
+    /// [`HandlerCtx::submit_notification`].
+    ///
+    /// Returns true if the notification is handled, which stops it from
+    /// bubbling further up.
+    ///
+    /// [`HandlerCtx::submit_notification`]: ../struct.HandlerCtx.html#method.submit_notification
+    #[allow(unused)]
+    fn notification(&mut self, notification: &Notification, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
+    /// Sent to the focused widget and then its ancestors when a command is
+    /// delivered, for example one of the standard edit commands in
+    /// `druid::commands`.
+    ///
+    /// Returns true if the command is handled, in which case it does not
+    /// reach the command listener.
+    #[allow(unused)]
+    fn command(&mut self, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        false
+    }
+
     /// Sent to the widget on key event.
     ///
     /// Key events are only sent to the focused widget.
@@ -120,8 +282,29 @@ pub trait Widget {
     #[allow(unused)]
     fn key_up(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) {}
 
+    /// Sent to the focused widget while the user composes text with an
+    /// input method. The widget should report where its caret is with
+    /// [`HandlerCtx::set_caret_rect`], at least when the composition starts.
+    ///
+    /// When the focus moves away during a composition, the widget is sent a
+    /// `Commit` of the text composed so far before it loses focus, and the
+    /// input method's composition is discarded.
+    ///
+    /// [`HandlerCtx::set_caret_rect`]: ../struct.HandlerCtx.html#method.set_caret_rect
+    #[allow(unused)]
+    fn composition(&mut self, event: &CompositionEvent, ctx: &mut HandlerCtx) {}
+
+    /// Sent to the hot widget on mouse wheel and trackpad scroll events.
+    ///
+    /// The default implementation forwards to the deprecated `scroll` method.
+    #[allow(unused, deprecated)]
+    fn wheel(&mut self, event: &WheelEvent, ctx: &mut HandlerCtx) {
+        self.scroll(event, ctx);
+    }
+
+    #[deprecated(note = "please use `wheel`.")]
     #[allow(unused)]
-    fn scroll(&mut self, event: &ScrollEvent, ctx: &mut HandlerCtx) {}
+    fn scroll(&mut self, event: &WheelEvent, ctx: &mut HandlerCtx) {}
 
     /// Called at the beginning of a new animation frame.
     ///
@@ -169,3 +352,114 @@ pub struct MouseEvent {
     /// and then again as a double-click.
     pub count: u32,
 }
+
+/// A touch screen event.
+#[derive(Debug, Clone)]
+pub struct TouchEvent {
+    /// Identifies the touch, from `Down` until the touch ends. Several
+    /// touches can be in progress at once.
+    pub id: u64,
+    /// The location of the touch, relative to the widget.
+    pub pos: Point,
+    /// The stage of the touch.
+    pub phase: TouchPhase,
+}
+
+/// A pinch, rotate or pan gesture.
+///
+/// The values are cumulative since the gesture began, rather than changes
+/// since the previous event.
+#[derive(Debug, Clone)]
+pub struct GestureEvent {
+    /// The stage of the gesture.
+    pub phase: GesturePhase,
+    /// The center of the gesture, relative to the widget.
+    pub center: Point,
+    /// The scale factor, where 1 means unchanged.
+    pub scale: f64,
+    /// The rotation in radians, positive clockwise.
+    pub rotation: f64,
+    /// How far the center has moved.
+    pub translation: Vec2,
+}
+
+/// A pen or stylus event.
+#[derive(Debug, Clone)]
+pub struct PenEvent {
+    /// The location of the pen tip, relative to the widget.
+    pub pos: Point,
+    /// The stage of the stroke.
+    pub phase: PenPhase,
+    /// The pressure of the tip, from 0 to 1.
+    pub pressure: f64,
+    /// The tilt of the pen on the x axis, in degrees from -90 to 90.
+    pub tilt_x: f64,
+    /// The tilt of the pen on the y axis, in degrees from -90 to 90.
+    pub tilt_y: f64,
+    /// Whether a barrel button is pressed.
+    pub barrel: bool,
+    /// Whether the eraser end of the pen is being used.
+    pub eraser: bool,
+    /// The currently active modifiers.
+    pub mods: KeyModifiers,
+}
+
+/// A notification about a change to a widget's place in the UI.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LifeCycle {
+    /// Sent once, when the widget is added to the UI. Widgets can use this to
+    /// request focus or send initial events to their listeners.
+    WidgetAdded,
+    /// The widget became hot (hovered) or stopped being hot.
+    HotChanged(bool),
+    /// The widget gained or lost keyboard focus.
+    FocusChanged(bool),
+    /// The widget's size changed during layout.
+    Size(Size),
+    /// The environment changed, such as when the system switched between
+    /// light and dark. Sent to every widget, for those keeping values made
+    /// from it; all windows are laid out and painted again anyway.
+    EnvChanged,
+    /// The locale or the messages of `LocalizedString`s changed; see
+    /// `Ui::set_locale`. Sent to every widget.
+    LocaleChanged,
+}
+
+/// An event delivered to widgets during drag and drop.
+pub struct DragEvent<'a> {
+    /// The stage of the drag session.
+    pub kind: DragKind,
+    /// The location of the pointer, relative to the widget.
+    pub pos: Point,
+    /// The dragged data. Drop targets use `downcast_ref` to check whether
+    /// it is something they accept. Files dragged from other applications
+    /// arrive as a `Vec<PathBuf>`.
+    pub payload: &'a dyn Any,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DragKind {
+    /// The pointer has moved onto a widget that is not the drop target.
+    Enter,
+    /// The pointer has moved within the drop target.
+    Over,
+    /// The pointer has left the drop target.
+    Leave,
+    /// The payload was released over the drop target.
+    Drop,
+    /// Sent to the drag source when the session is over. Not sent for
+    /// files dragged from other applications.
+    End {
+        /// Whether the drop target accepted the payload.
+        dropped: bool,
+    },
+}
+
+/// A notification bubbling up from a descendant widget.
+pub struct Notification<'a> {
+    /// The widget that submitted the notification.
+    pub source: Id,
+    /// The notification itself. Widgets use `downcast_ref` to check whether
+    /// it is one they handle.
+    pub payload: &'a dyn Any,
+}
@@ -0,0 +1,121 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A widget showing a native view of the platform.
+
+use std::any::Any;
+
+use druid_shell::native_view::NativeView;
+use druid_shell::window::{RawWindowHandle, WindowHandle};
+
+use crate::widget::{LifeCycle, Widget};
+use crate::{BoxConstraints, HandlerCtx, Id, LayoutCtx, LayoutResult, PaintCtx, Ui};
+
+use crate::kurbo::{Rect, Size};
+
+/// A widget showing a native view created by the application, such as a
+/// web view or a platform control, laid out like any other widget.
+///
+/// The view is clipped to the window, takes the keyboard focus along with
+/// the widget, and can be hidden by poking the widget with `false`. It is
+/// handed back to its owner when the widget is deleted.
+///
+/// On Windows, the window must be built with `PresentStrategy::Hwnd`.
+pub struct NativeHost {
+    view: RawWindowHandle,
+    native: Option<(NativeView, WindowHandle)>,
+    /// Set when hosting the view failed, so that it isn't retried on every
+    /// layout.
+    failed: bool,
+    visible: bool,
+}
+
+impl NativeHost {
+    /// Host the view with the given handle, as described for
+    /// `WindowHandle::add_native_view`.
+    ///
+    /// # Safety
+    ///
+    /// `view` must be a valid handle of the same platform as the window,
+    /// which outlives the widget.
+    pub unsafe fn new(view: RawWindowHandle) -> NativeHost {
+        NativeHost {
+            view,
+            native: None,
+            failed: false,
+            visible: true,
+        }
+    }
+
+    pub fn ui(self, ctx: &mut Ui) -> Id {
+        ctx.add(self, &[])
+    }
+}
+
+impl Widget for NativeHost {
+    fn paint(&mut self, _paint_ctx: &mut PaintCtx, geom: &Rect) {
+        if let Some((ref native, ref window)) = self.native {
+            let (width, height) = window.get_size();
+            native.set_frame(*geom, geom.intersect(Rect::new(0.0, 0.0, width, height)));
+            native.set_visible(self.visible);
+        }
+    }
+
+    fn layout(
+        &mut self,
+        bc: &BoxConstraints,
+        _children: &[Id],
+        _size: Option<Size>,
+        ctx: &mut LayoutCtx,
+    ) -> LayoutResult {
+        if self.native.is_none() && !self.failed {
+            // The handle was checked when the widget was created.
+            match unsafe { ctx.window().add_native_view(self.view) } {
+                Ok(native) => self.native = Some((native, ctx.window().clone())),
+                Err(e) => {
+                    println!("Error hosting native view: {:?}", e);
+                    self.failed = true;
+                }
+            }
+        }
+        LayoutResult::Size(bc.max)
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, _ctx: &mut HandlerCtx) {
+        if let (LifeCycle::FocusChanged(focused), Some((ref native, _))) = (event, &self.native) {
+            if *focused {
+                native.focus();
+            } else {
+                native.blur();
+            }
+        }
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        if let Some(visible) = payload.downcast_ref::<bool>() {
+            self.visible = *visible;
+            if let Some((ref native, _)) = self.native {
+                native.set_visible(*visible);
+            }
+            ctx.invalidate();
+            true
+        } else {
+            false
+        }
+    }
+}
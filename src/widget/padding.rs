@@ -16,13 +16,15 @@
 
 use crate::kurbo::Size;
 use crate::widget::Widget;
-use crate::{BoxConstraints, LayoutResult};
+use crate::{theme, BoxConstraints, LayoutResult};
 use crate::{Id, LayoutCtx, Ui};
 
 /// A padding widget. Is expected to have exactly one child.
+///
+/// The leading side is the left, or the right in right-to-left layouts.
 pub struct Padding {
-    left: f64,
-    right: f64,
+    leading: f64,
+    trailing: f64,
     top: f64,
     bottom: f64,
 }
@@ -30,11 +32,15 @@ pub struct Padding {
 impl Padding {
     /// Create widget with uniform padding.
     pub fn uniform(padding: f64) -> Padding {
+        Padding::new(padding, padding, padding, padding)
+    }
+
+    pub fn new(leading: f64, top: f64, trailing: f64, bottom: f64) -> Padding {
         Padding {
-            left: padding,
-            right: padding,
-            top: padding,
-            bottom: padding,
+            leading,
+            trailing,
+            top,
+            bottom,
         }
     }
 
@@ -51,14 +57,19 @@ impl Widget for Padding {
         size: Option<Size>,
         ctx: &mut LayoutCtx,
     ) -> LayoutResult {
-        let hpad = self.left + self.right;
+        let hpad = self.leading + self.trailing;
         let vpad = self.top + self.bottom;
         if let Some(size) = size {
-            ctx.position_child(children[0], (self.left, self.top));
+            let left = if ctx.env().get(theme::LAYOUT_DIRECTION).is_rtl() {
+                self.trailing
+            } else {
+                self.leading
+            };
+            ctx.position_child(children[0], (left, self.top));
             LayoutResult::Size(Size::new(size.width + hpad, size.height + vpad))
         } else {
-            let min = Size::new(bc.min.width - hpad, bc.min.height - hpad);
-            let max = Size::new(bc.max.width - hpad, bc.max.height - hpad);
+            let min = Size::new(bc.min.width - hpad, bc.min.height - vpad);
+            let max = Size::new(bc.max.width - hpad, bc.max.height - vpad);
             LayoutResult::RequestChild(children[0], BoxConstraints::new(min, max))
         }
     }
@@ -16,15 +16,13 @@
 
 use std::any::Any;
 
+use crate::accessibility::Role;
 use crate::widget::Widget;
-use crate::{BoxConstraints, HandlerCtx, Id, LayoutCtx, LayoutResult, PaintCtx, Ui};
+use crate::{theme, AccessNode, BoxConstraints, HandlerCtx, Id, LayoutCtx, LayoutResult};
+use crate::{PaintCtx, Ui};
 
 use crate::kurbo::{Rect, Size};
-use crate::piet::{Color, FillRule, RenderContext};
-
-const BOX_HEIGHT: f64 = 24.;
-const BACKGROUND_COLOR: Color = Color::rgb24(0x55_55_55);
-const BAR_COLOR: Color = Color::rgb24(0xf0_f0_ea);
+use crate::piet::FillRule;
 
 pub struct ProgressBar {
     value: f64,
@@ -44,17 +42,17 @@ impl ProgressBar {
 impl Widget for ProgressBar {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
         //Paint the background
-        let brush = paint_ctx.render_ctx.solid_brush(BACKGROUND_COLOR);
-
-        paint_ctx.render_ctx.fill(geom, &brush, FillRule::NonZero);
+        let rect = paint_ctx.snap_rect(*geom);
+        let color = paint_ctx.env().get(theme::TRACK_COLOR);
+        paint_ctx.fill_color(rect, &color, FillRule::NonZero);
 
         //Paint the bar
-        let brush = paint_ctx.render_ctx.solid_brush(BAR_COLOR);
-
         let calculated_bar_width = self.value * geom.width();
 
         let rect = geom.with_size(Size::new(calculated_bar_width, geom.height()));
-        paint_ctx.render_ctx.fill(rect, &brush, FillRule::NonZero);
+        let rect = paint_ctx.snap_rect(rect);
+        let color = paint_ctx.env().get(theme::FOREGROUND_COLOR);
+        paint_ctx.fill_color(rect, &color, FillRule::NonZero);
     }
 
     fn layout(
@@ -62,9 +60,17 @@ impl Widget for ProgressBar {
         bc: &BoxConstraints,
         _children: &[Id],
         _size: Option<Size>,
-        _ctx: &mut LayoutCtx,
+        ctx: &mut LayoutCtx,
     ) -> LayoutResult {
-        LayoutResult::Size(bc.constrain((bc.max.width, BOX_HEIGHT)))
+        let height = ctx.env().get(theme::BASIC_WIDGET_HEIGHT);
+        LayoutResult::Size(bc.constrain((bc.max.width, height)))
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        node.role = Role::ProgressIndicator;
+        node.numeric_value = Some(self.value);
+        node.min_value = Some(0.0);
+        node.max_value = Some(1.0);
     }
 
     fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
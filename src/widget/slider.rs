@@ -14,16 +14,23 @@
 
 //! A slider widget.
 
-use crate::widget::Widget;
-use crate::{BoxConstraints, HandlerCtx, Id, LayoutCtx, LayoutResult, MouseEvent, PaintCtx, Ui};
+use crate::accessibility::{Action, Role};
+use crate::widget::{LifeCycle, Widget};
+use crate::{
+    theme, AccessNode, ActionRequest, BoxConstraints, HandlerCtx, Id, KeyCode, KeyEvent, LayoutCtx,
+    LayoutResult, MouseEvent, PaintCtx, Ui, TOUCH_TARGET_SIZE,
+};
 
 use crate::kurbo::{Point, Rect, Size};
-use crate::piet::{Color, FillRule, RenderContext};
+use crate::piet::FillRule;
 
-const BOX_HEIGHT: f64 = 24.;
-const BACKGROUND_COLOR: Color = Color::rgb24(0x55_55_55);
-const SLIDER_COLOR: Color = Color::rgb24(0xf0_f0_ea);
+/// How much the arrow keys, `Action::Increment` and `Action::Decrement`
+/// change the value.
+const STEP: f64 = 0.1;
 
+/// A slider setting a value from 0 to 1, which it sends to listeners as it
+/// changes. It takes the focus when pressed; the arrow keys then move the
+/// knob, and Home and End move it to the ends.
 pub struct Slider {
     value: f64,
 }
@@ -38,20 +45,27 @@ impl Slider {
     pub fn ui(self, ctx: &mut Ui) -> Id {
         ctx.add(self, &[])
     }
+
+    fn set_value(&mut self, value: f64, ctx: &mut HandlerCtx) {
+        let value = value.max(0.0).min(1.0);
+        if value != self.value {
+            self.value = value;
+            ctx.send_event(value);
+            ctx.invalidate();
+        }
+    }
 }
 
 impl Widget for Slider {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
         //Paint the background
-        let brush = paint_ctx.render_ctx.solid_brush(BACKGROUND_COLOR);
-
-        paint_ctx.render_ctx.fill(geom, &brush, FillRule::NonZero);
+        let rect = paint_ctx.snap_rect(*geom);
+        let color = paint_ctx.env().get(theme::TRACK_COLOR);
+        paint_ctx.fill_color(rect, &color, FillRule::NonZero);
         //Paint the slider
-        let brush = paint_ctx.render_ctx.solid_brush(SLIDER_COLOR);
-
-        let slider_absolute_position = (geom.width() - BOX_HEIGHT) * self.value + BOX_HEIGHT / 2.;
         let half_box = geom.height() / 2.;
         let full_box = geom.height();
+        let slider_absolute_position = (geom.width() - full_box) * self.value + half_box;
 
         let mut position = slider_absolute_position - half_box;
         if position < 0. {
@@ -62,11 +76,13 @@ impl Widget for Slider {
 
         let knob_origin = Point::new(geom.origin().x + position, geom.origin().y);
         let knob_size = Size::new(full_box, geom.height());
-        let knob_rect = Rect::from((knob_origin, knob_size));
+        let knob_rect = paint_ctx.snap_rect(Rect::from((knob_origin, knob_size)));
 
-        paint_ctx
-            .render_ctx
-            .fill(knob_rect, &brush, FillRule::NonZero);
+        let color = paint_ctx.env().get(theme::FOREGROUND_COLOR);
+        paint_ctx.fill_color(knob_rect, &color, FillRule::NonZero);
+        if paint_ctx.is_focused() {
+            paint_ctx.paint_focus_ring(*geom);
+        }
     }
 
     fn layout(
@@ -74,17 +90,21 @@ impl Widget for Slider {
         bc: &BoxConstraints,
         _children: &[Id],
         _size: Option<Size>,
-        _ctx: &mut LayoutCtx,
+        ctx: &mut LayoutCtx,
     ) -> LayoutResult {
-        LayoutResult::Size(bc.constrain((bc.max.width, BOX_HEIGHT)))
+        let height = if ctx.touch_mode() {
+            TOUCH_TARGET_SIZE
+        } else {
+            ctx.env().get(theme::BASIC_WIDGET_HEIGHT)
+        };
+        LayoutResult::Size(bc.constrain((bc.max.width, height)))
     }
 
     fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
         if event.count == 1 {
             ctx.set_active(true);
-            self.value = ((event.pos.x - BOX_HEIGHT / 2.) / (ctx.get_geom().width() - BOX_HEIGHT))
-                .max(0.0)
-                .min(1.0);
+            ctx.request_focus();
+            self.value = value_at(event.pos.x, ctx.get_geom());
             ctx.send_event(self.value);
         } else {
             ctx.set_active(false);
@@ -95,12 +115,73 @@ impl Widget for Slider {
 
     fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {
         if ctx.is_active() {
-            self.value = ((pos.x - BOX_HEIGHT / 2.) / (ctx.get_geom().width() - BOX_HEIGHT))
-                .max(0.0)
-                .min(1.0);
+            self.value = value_at(pos.x, ctx.get_geom());
 
             ctx.send_event(self.value);
             ctx.invalidate();
         }
     }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        if let LifeCycle::FocusChanged(_) = event {
+            ctx.invalidate();
+        }
+    }
+
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        let mods = event.modifiers;
+        if mods.ctrl || mods.alt || mods.meta {
+            return false;
+        }
+        // The value increases to the right, or to the left when mirrored.
+        let rtl = ctx.env().get(theme::LAYOUT_DIRECTION).is_rtl();
+        let value = match event.code {
+            KeyCode::ArrowRight if rtl => self.value - STEP,
+            KeyCode::ArrowLeft if rtl => self.value + STEP,
+            KeyCode::ArrowRight | KeyCode::ArrowUp => self.value + STEP,
+            KeyCode::ArrowLeft | KeyCode::ArrowDown => self.value - STEP,
+            KeyCode::Home => 0.0,
+            KeyCode::End => 1.0,
+            _ => return false,
+        };
+        self.set_value(value, ctx);
+        true
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        node.role = Role::Slider;
+        node.numeric_value = Some(self.value);
+        node.min_value = Some(0.0);
+        node.max_value = Some(1.0);
+        node.actions = vec![
+            Action::Focus,
+            Action::Increment,
+            Action::Decrement,
+            Action::SetValue,
+        ];
+    }
+
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        let value = match request.action {
+            Action::Increment => self.value + STEP,
+            Action::Decrement => self.value - STEP,
+            Action::SetValue => match request.value.as_ref().and_then(|v| v.parse().ok()) {
+                Some(value) => value,
+                None => return false,
+            },
+            _ => return false,
+        };
+        self.set_value(value, ctx);
+        true
+    }
+}
+
+/// The value with the knob, as wide as the slider is tall, centered at `x`.
+fn value_at(x: f64, geom: &Rect) -> f64 {
+    let knob = geom.height();
+    ((x - knob / 2.) / (geom.width() - knob)).max(0.0).min(1.0)
 }
@@ -0,0 +1,209 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Styling a single widget over the theme.
+
+use std::any::Any;
+
+use crate::kurbo::{Point, Rect, Size};
+use crate::widget::{
+    CompositionEvent, DragEvent, GestureEvent, KeyEvent, LifeCycle, MouseEvent, Notification,
+    PenEvent, TouchEvent, WheelEvent, Widget,
+};
+use crate::{AccessNode, ActionRequest, BoxConstraints, Env, EnvKey, LayoutResult, ValueType};
+use crate::{DropEffect, HandlerCtx, Id, LayoutCtx, PaintCtx, TimerToken, Ui};
+
+/// Changes to the environment a widget is drawn with, such as the
+/// `ButtonStyle` of a button. Closures changing an `Env` are styles too.
+pub trait Style {
+    fn apply(&self, env: &mut Env);
+}
+
+impl<F: Fn(&mut Env)> Style for F {
+    fn apply(&self, env: &mut Env) {
+        self(env)
+    }
+}
+
+/// A widget with a style of its own, given with `WidgetExt::with_style`.
+pub trait Stylable: Widget {
+    type Style: Style;
+}
+
+/// Set `key` to `value`, if there is one; for the fields of styles.
+pub(crate) fn apply_value<T: ValueType + Clone>(env: &mut Env, key: EnvKey<T>, value: &Option<T>) {
+    if let Some(value) = value {
+        env.set(key, value.clone());
+    }
+}
+
+/// A widget drawn and laid out with a style applied to the environment.
+///
+/// The style applies to the widget alone, not to its children in the
+/// graph; it lasts across theme changes.
+pub struct Styled<W> {
+    widget: W,
+    style: Box<dyn Style>,
+    /// The environment the style was last applied to, and the result.
+    cache: Option<(Env, Env)>,
+}
+
+impl<W: Widget> Styled<W> {
+    pub fn new(widget: W, style: impl Style + 'static) -> Styled<W> {
+        Styled {
+            widget,
+            style: Box::new(style),
+            cache: None,
+        }
+    }
+
+    pub fn ui(self, children: &[Id], ctx: &mut Ui) -> Id
+    where
+        W: 'static,
+    {
+        ctx.add(self, children)
+    }
+
+    fn scoped_env(&mut self, env: &Env) -> Env {
+        match &self.cache {
+            Some((base, scoped)) if base.same(env) => scoped.clone(),
+            _ => {
+                let mut scoped = env.clone();
+                self.style.apply(&mut scoped);
+                self.cache = Some((env.clone(), scoped.clone()));
+                scoped
+            }
+        }
+    }
+
+    /// Call `f` with the style applied to the environment of `ctx`.
+    fn handle<R>(
+        &mut self,
+        ctx: &mut HandlerCtx,
+        f: impl FnOnce(&mut W, &mut HandlerCtx) -> R,
+    ) -> R {
+        let env = self.scoped_env(ctx.env());
+        let outer = ctx.replace_env(env);
+        let result = f(&mut self.widget, ctx);
+        ctx.replace_env(outer);
+        result
+    }
+}
+
+impl<W: Widget> Widget for Styled<W> {
+    fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        let env = self.scoped_env(paint_ctx.env());
+        let outer = paint_ctx.replace_env(env);
+        self.widget.paint(paint_ctx, geom);
+        paint_ctx.replace_env(outer);
+    }
+
+    fn layout(
+        &mut self,
+        bc: &BoxConstraints,
+        children: &[Id],
+        size: Option<Size>,
+        ctx: &mut LayoutCtx,
+    ) -> LayoutResult {
+        let env = self.scoped_env(ctx.env());
+        let outer = ctx.replace_env(env);
+        let result = self.widget.layout(bc, children, size, ctx);
+        ctx.replace_env(outer);
+        result
+    }
+
+    fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.mouse(event, ctx))
+    }
+
+    fn touch(&mut self, event: &TouchEvent, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.touch(event, ctx))
+    }
+
+    fn gesture(&mut self, event: &GestureEvent, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.gesture(event, ctx))
+    }
+
+    fn pen(&mut self, event: &PenEvent, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.pen(event, ctx))
+    }
+
+    fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.mouse_moved(pos, ctx))
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.lifecycle(event, ctx))
+    }
+
+    fn accepts_focus(&self) -> bool {
+        self.widget.accepts_focus()
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        self.widget.accessibility(node)
+    }
+
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.accessibility_action(request, ctx))
+    }
+
+    fn drag(&mut self, event: &DragEvent, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.drag(event, ctx))
+    }
+
+    fn system_drag_ended(&mut self, effect: DropEffect, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.system_drag_ended(effect, ctx))
+    }
+
+    fn timer(&mut self, token: TimerToken, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.timer(token, ctx))
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.poke(payload, ctx))
+    }
+
+    fn notification(&mut self, notification: &Notification, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.notification(notification, ctx))
+    }
+
+    fn command(&mut self, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.command(cmd, ctx))
+    }
+
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        self.handle(ctx, |w, ctx| w.key_down(event, ctx))
+    }
+
+    fn key_up(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.key_up(event, ctx))
+    }
+
+    fn composition(&mut self, event: &CompositionEvent, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.composition(event, ctx))
+    }
+
+    fn wheel(&mut self, event: &WheelEvent, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.wheel(event, ctx))
+    }
+
+    fn anim_frame(&mut self, interval: u64, ctx: &mut HandlerCtx) {
+        self.handle(ctx, |w, ctx| w.anim_frame(interval, ctx))
+    }
+
+    fn on_child_removed(&mut self, child: Id) {
+        self.widget.on_child_removed(child)
+    }
+}
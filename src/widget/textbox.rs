@@ -14,121 +14,345 @@
 
 //! A textbox widget.
 
-use crate::widget::Widget;
-use crate::{
-    BoxConstraints, HandlerCtx, Id, KeyCode, KeyEvent, LayoutCtx, LayoutResult, MouseEvent,
-    PaintCtx, Ui,
-};
+use std::any::Any;
+use std::ops::Range;
+use std::time::Duration;
 
-use crate::kurbo::{Line, Rect, Size, Vec2};
-use crate::piet::{
-    Color, FillRule, FontBuilder, Piet, RenderContext, Text, TextLayout, TextLayoutBuilder,
+use crate::accessibility::{Action, Role};
+use crate::widget::styled::apply_value;
+use crate::widget::{LifeCycle, Stylable, Style, Widget};
+use crate::{
+    commands, editing, theme, AccessNode, ActionRequest, BoxConstraints, CompositionEvent, Cursor,
+    EditAction, EditHistory, EditKeymap, EditKind, EditableText, Env, HandlerCtx, Id, KeyCode,
+    KeyEvent, LayoutCtx, LayoutResult, MouseEvent, Movement, PaintCtx, Selection, Stroke,
+    TextLayout, TimerToken, Ui, TOUCH_TARGET_SIZE,
 };
 
-const ACTIVE_BORDER_COLOR: Color = Color::rgb24(0xff_00_00);
-const INACTIVE_BORDER_COLOR: Color = Color::rgb24(0x55_55_55);
-const TEXT_COLOR: Color = Color::rgb24(0xf0_f0_ea);
-const CURSOR_COLOR: Color = Color::WHITE;
+use crate::kurbo::{Line, Point, Rect, Size, Vec2};
+use crate::piet::{Color, FillRule};
 
-const BOX_HEIGHT: f64 = 24.;
-const BORDER_WIDTH: f64 = 2.;
+/// The character shown for each one of a password.
+const MASK_CHAR: char = '\u{2022}';
+/// How long the character last typed into a password stays readable in
+/// touch mode.
+const REVEAL_TIME: Duration = Duration::from_millis(1500);
 
+/// A box editing a single line of text. It sends its text to listeners as a
+/// `String` when it is edited, and replaces it when poked with one.
 pub struct TextBox {
     text: String,
+    /// Whether the text is a password, shown masked.
+    secure: bool,
+    /// The masking of the text as last laid out, if it is a password.
+    mask: Option<Mask>,
+    /// The character last typed into a password, shown until the timer
+    /// fires.
+    reveal: Option<(usize, TimerToken)>,
+    /// Whether the text was edited since it was last sent to listeners.
+    changed: bool,
+    /// Whether edits are sent to listeners, which they aren't when the
+    /// text box is part of another widget.
+    sends_text: bool,
+    selection: Selection,
+    history: EditHistory,
+    /// Text being composed with an input method, shown underlined in place
+    /// of the selection, with the position of its cursor.
+    composition: String,
+    composition_cursor: usize,
+    /// The text with the composition, in the font of the last paint.
+    layout: TextLayout,
+    keymap: EditKeymap,
     width: f64,
-    font: Option<<<Piet<'static> as RenderContext>::Text as Text>::Font>,
 }
 
 impl TextBox {
     pub fn new(default_text: Option<String>, width: f64) -> TextBox {
         TextBox {
             text: default_text.unwrap_or_else(|| String::new()),
+            secure: false,
+            mask: None,
+            reveal: None,
+            changed: false,
+            sends_text: true,
+            selection: Selection::caret(0),
+            history: EditHistory::new(),
+            composition: String::new(),
+            composition_cursor: 0,
+            // The font is set from the theme before the text is laid out.
+            layout: TextLayout::new("", "", 0.),
+            keymap: EditKeymap::default(),
             width,
-            font: None,
         }
     }
+
+    /// Edit with the keys of `keymap` rather than the platform's.
+    pub fn keymap(mut self, keymap: EditKeymap) -> TextBox {
+        self.keymap = keymap;
+        self
+    }
+
+    /// Edit a password: the text is shown as bullets, can't be copied or
+    /// cut, and the caret moves by words and lines to either end. In touch
+    /// mode, the character just typed shows for a moment.
+    pub fn password(mut self) -> TextBox {
+        self.secure = true;
+        self
+    }
+
     pub fn ui(self, ctx: &mut Ui) -> Id {
         ctx.add(self, &[])
     }
 
-    fn load_font(&mut self, rt: &mut Piet, font_size: f64) {
-        let font = rt
-            .text()
-            .new_font_by_name("Segoe UI", font_size)
-            .unwrap()
-            .build()
-            .unwrap();
+    /// The text box without sending its text to listeners, for widgets
+    /// built on it.
+    pub(crate) fn without_events(mut self) -> TextBox {
+        self.sends_text = false;
+        self
+    }
+
+    pub(crate) fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Replace the text, with the caret at its end and no history.
+    pub(crate) fn set_text(&mut self, text: String) {
+        self.selection = Selection::caret(text.len());
+        self.text = text;
+        self.history.clear();
+        self.reveal = None;
+    }
+
+    /// Lay out the text as shown, with the composition in place of the
+    /// selection, masked if it is a password.
+    fn update_layout(&mut self) {
+        let range = self.selection.constrained(&self.text).range();
+        let mut text = self.text.clone();
+        text.edit(range, &self.composition);
+        if self.secure {
+            let reveal = self.reveal.filter(|_| self.composition.is_empty());
+            let (shown, mask) = Mask::new(&text, reveal.map(|(offset, _)| offset));
+            self.mask = Some(mask);
+            text = shown;
+        }
+        self.layout.set_text(text);
+    }
+
+    /// The offset in the layout of `offset` in the text with the
+    /// composition.
+    fn shown_offset(&self, offset: usize) -> usize {
+        self.mask
+            .as_ref()
+            .map_or(offset, |mask| mask.shown_offset(offset))
+    }
 
-        self.font = Some(font);
+    /// The offset of the caret in the text with the composition.
+    fn caret_offset(&self) -> usize {
+        if self.composition.is_empty() {
+            self.selection.active
+        } else {
+            self.selection.min() + self.composition_cursor
+        }
     }
 
-    fn get_layout(&mut self, rt: &mut Piet, font_size: f64) -> <Piet as RenderContext>::TextLayout {
-        // TODO: caching of both the format and the layout
-        match &self.font {
-            Some(font) => {
-                return rt
-                    .text()
-                    .new_text_layout(&font, &self.text)
-                    .unwrap()
-                    .build()
-                    .unwrap()
+    /// Tell the input method where the caret is, for its candidate window.
+    fn report_caret(&mut self, ctx: &mut HandlerCtx) {
+        let height = ctx.get_geom().height();
+        self.update_layout();
+        let caret = self.shown_offset(self.caret_offset());
+        let x = self.layout.hit_test_offset(caret).x + 1.;
+        ctx.set_caret_rect(Rect::new(x, 2., x + 1., height - 2.));
+    }
+
+    /// The offset of the caret for a click at `pos`.
+    fn offset_at(&mut self, pos: Point) -> usize {
+        self.update_layout();
+        let offset = self.layout.hit_test_point(pos);
+        let offset = match &self.mask {
+            Some(mask) => mask.text_offset(offset),
+            None => offset,
+        };
+        editing::snap_to_grapheme(&self.text, offset)
+    }
+
+    /// The movement made in place of `movement`: in a password, moving by
+    /// words or lines would tell where its spaces are.
+    fn secure_movement(&self, movement: Movement) -> Movement {
+        match movement {
+            Movement::LeftWord | Movement::LineStart | Movement::Up if self.secure => {
+                Movement::DocumentStart
             }
-            _ => {
-                self.load_font(rt, font_size);
+            Movement::RightWord | Movement::LineEnd | Movement::Down if self.secure => {
+                Movement::DocumentEnd
+            }
+            movement => movement,
+        }
+    }
+
+    fn move_caret(&mut self, movement: Movement, extend: bool) {
+        let movement = self.secure_movement(movement);
+        self.update_layout();
+        let selection = self.selection;
+        self.history.break_group();
+        self.selection =
+            editing::movement(movement, selection, &self.text, &mut self.layout, extend);
+    }
 
-                //QUESTION this recursion makes me uncomfortable
-                //but it solved my borrowing issues!
-                return self.get_layout(rt, font_size);
+    fn delete(&mut self, movement: Movement) {
+        let movement = self.secure_movement(movement);
+        self.update_layout();
+        let selection = self.selection;
+        let range = editing::delete_range(&self.text, selection, movement, &mut self.layout);
+        self.edit(range, "", EditKind::Deleting);
+    }
+
+    /// Replace the selection with `text`.
+    fn insert(&mut self, text: &str, kind: EditKind) {
+        let range = self.selection.constrained(&self.text).range();
+        self.edit(range, text, kind);
+    }
+
+    fn edit(&mut self, range: Range<usize>, text: &str, kind: EditKind) {
+        let selection = self.selection;
+        self.selection = self
+            .history
+            .edit(&mut self.text, range, text, selection, kind);
+        self.changed = true;
+    }
+
+    /// Send the text to listeners, if it was edited.
+    fn send_changes(&mut self, ctx: &mut HandlerCtx) {
+        if self.changed {
+            self.changed = false;
+            if self.sends_text {
+                ctx.send_event(self.text.clone());
             }
-        };
+        }
+    }
+
+    /// Show the character just typed into a password for a moment, in
+    /// touch mode, where typing on the on-screen keyboard is easily missed.
+    fn reveal_typed(&mut self, ctx: &mut HandlerCtx) {
+        if !self.secure || !ctx.touch_mode() {
+            return;
+        }
+        if let Some(offset) = self.text.prev_grapheme_offset(self.selection.active) {
+            self.reveal = Some((offset, ctx.request_timer(REVEAL_TIME)));
+        }
+    }
+
+    /// Handle a key with no edit action bound to it.
+    fn key_down_other(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        match event {
+            event if event.code == KeyCode::Escape => {
+                ctx.resign_focus();
+                return true;
+            }
+            event if event.code == KeyCode::Tab => {
+                if event.modifiers.shift {
+                    ctx.focus_prev();
+                } else {
+                    ctx.focus_next();
+                }
+                return true;
+            }
+            event if event.code.is_printable() => {
+                self.insert(event.text().unwrap_or(""), EditKind::Typing);
+                self.reveal_typed(ctx);
+            }
+            _ => return false,
+        }
+        self.send_changes(ctx);
+        ctx.invalidate();
+        true
+    }
+
+    fn selected_text(&self) -> Option<String> {
+        let range = self.selection.constrained(&self.text).range();
+        if range.start == range.end {
+            None
+        } else {
+            self.text.slice(range).map(String::from)
+        }
     }
 }
 
 impl Widget for TextBox {
     fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        let env = paint_ctx.env().clone();
         let border_color = if paint_ctx.is_focused() {
-            ACTIVE_BORDER_COLOR
+            env.get(theme::FOCUSED_BORDER_COLOR)
         } else {
-            INACTIVE_BORDER_COLOR
+            env.get(theme::BORDER_COLOR)
         };
         // Paint the border
-        let brush = paint_ctx.render_ctx.solid_brush(border_color);
-        let clip_rect = geom.with_size(Size::new(geom.width() - BORDER_WIDTH, geom.height()));
+        let border_width = env.get(theme::TEXT_BOX_BORDER_WIDTH);
+        let clip_rect = geom.with_size(Size::new(geom.width() - border_width, geom.height()));
+        let border_rect = paint_ctx.snap_stroke_rect(*geom, border_width);
+        let border_width = paint_ctx.snap_stroke_width(border_width);
 
-        paint_ctx
-            .render_ctx
-            .stroke(geom, &brush, BORDER_WIDTH, None);
+        paint_ctx.stroke_color(border_rect, &border_color, &Stroke::new(border_width));
 
         // Paint the text
-        let font_size = BOX_HEIGHT - 4.;
-        let text_layout = self.get_layout(paint_ctx.render_ctx, font_size);
-        let brush = paint_ctx.render_ctx.solid_brush(TEXT_COLOR);
-
-        let height_delta = Vec2::new(0., font_size);
-        let pos = geom.origin() + height_delta;
-
+        let font_size = (env.get(theme::BASIC_WIDGET_HEIGHT) - 4.) * paint_ctx.text_scale();
+        self.layout.set_font(&env.get(theme::FONT_NAME), font_size);
+        self.update_layout();
+        let origin = geom.origin();
         let focused = paint_ctx.is_focused();
+        let selection = if focused && self.composition.is_empty() {
+            let range = self.selection.constrained(&self.text).range();
+            let range = self.shown_offset(range.start)..self.shown_offset(range.end);
+            self.layout.rects_for_range(range)
+        } else {
+            Vec::new()
+        };
+        let start = self.selection.constrained(&self.text).min();
+        let composition =
+            self.shown_offset(start)..self.shown_offset(start + self.composition.len());
+        let underline_width = paint_ctx.snap_stroke_width(1.);
+        let underlines = self
+            .layout
+            .rects_for_range(composition)
+            .into_iter()
+            .filter(|rect| rect.width() > 0.)
+            .map(|rect| {
+                let y = origin.y + rect.y0 + font_size + 2.;
+                let line = Line::new((origin.x + rect.x0, y), (origin.x + rect.x1, y));
+                paint_ctx.snap_stroke_line(line, 1.)
+            })
+            .collect::<Vec<_>>();
+        let caret = self
+            .layout
+            .hit_test_offset(self.shown_offset(self.caret_offset()));
+        let xy = origin + caret.to_vec2() + Vec2::new(1., 2.);
+        let cursor = paint_ctx.snap_stroke_line(Line::new(xy, xy + Vec2::new(0., font_size)), 1.);
+        let cursor_width = paint_ctx.snap_stroke_width(1.);
 
         //Render text and cursor inside a clip
-        paint_ctx
-            .render_ctx
-            .with_save(|rc| {
-                rc.clip(clip_rect, FillRule::NonZero);
-                rc.draw_text(&text_layout, pos, &brush);
-
-                // Paint the cursor if focused
-                if focused {
-                    let brush = rc.solid_brush(CURSOR_COLOR);
-
-                    let xy = geom.origin() + Vec2::new(text_layout.width() + 2., 2.);
-                    let x2y2 = xy + height_delta;
-                    let line = Line::new(xy, x2y2);
-
-                    rc.stroke(line, &brush, 1., None);
+        let text_color = env.get(theme::LABEL_COLOR);
+        let selection_color = env.get(theme::SELECTION_COLOR);
+        let cursor_color = env.get(theme::CURSOR_COLOR);
+        let layout = &mut self.layout;
+        paint_ctx.with_save(|paint_ctx| {
+            paint_ctx.clip(clip_rect);
+            for rect in selection {
+                if rect.width() > 0. {
+                    paint_ctx.fill_color(
+                        rect + origin.to_vec2(),
+                        &selection_color,
+                        FillRule::NonZero,
+                    );
                 }
-                Ok(())
-            })
-            .unwrap();
+            }
+            paint_ctx.draw_text_layout(layout, origin, &text_color);
+            for line in underlines {
+                paint_ctx.stroke_color(line, &text_color, &Stroke::new(underline_width));
+            }
+
+            // Paint the cursor if focused
+            if focused {
+                paint_ctx.stroke_color(cursor, &cursor_color, &Stroke::new(cursor_width));
+            }
+        });
     }
 
     fn layout(
@@ -136,31 +360,290 @@ impl Widget for TextBox {
         bc: &BoxConstraints,
         _children: &[Id],
         _size: Option<Size>,
-        _ctx: &mut LayoutCtx,
+        ctx: &mut LayoutCtx,
     ) -> LayoutResult {
-        LayoutResult::Size(bc.constrain((self.width, BOX_HEIGHT)))
+        let mut height = ctx.env().get(theme::BASIC_WIDGET_HEIGHT) * ctx.text_scale();
+        if ctx.touch_mode() {
+            height = height.max(TOUCH_TARGET_SIZE);
+        }
+        LayoutResult::Size(bc.constrain((self.width, height)))
     }
 
     fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
         if event.count > 0 {
-            ctx.set_focused(true);
-            ctx.invalidate();
+            self.reveal = None;
+            ctx.request_focus();
+            // Clicks while composing leave the composition in place.
+            if self.composition.is_empty() {
+                let offset = self.offset_at(event.pos);
+                self.history.break_group();
+                self.selection = if event.mods.shift {
+                    Selection::new(self.selection.anchor, offset)
+                } else {
+                    Selection::caret(offset)
+                };
+                ctx.set_active(true);
+                ctx.invalidate();
+            }
+        } else {
+            ctx.set_active(false);
         }
         true
     }
 
-    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+    fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {
+        ctx.set_cursor(&Cursor::IBeam);
+        if ctx.is_active() && self.composition.is_empty() {
+            let offset = self.offset_at(pos);
+            if offset != self.selection.active {
+                self.selection = Selection::new(self.selection.anchor, offset);
+                ctx.invalidate();
+            }
+        }
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        if self.secure {
+            // A password isn't read out.
+            node.role = Role::PasswordInput;
+        } else {
+            node.role = Role::TextInput;
+            node.value = Some(self.text.clone());
+        }
+        node.actions = vec![Action::Focus, Action::SetValue];
+    }
+
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        match (request.action, &request.value) {
+            (Action::SetValue, Some(value)) => {
+                self.reveal = None;
+                self.edit(0..self.text.len(), value, EditKind::Other);
+                self.selection = Selection::caret(self.text.len());
+                self.send_changes(ctx);
+                ctx.invalidate();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        // Without a keyboard, typing needs the on-screen one.
+        if let (LifeCycle::FocusChanged(focused), true) = (event, ctx.touch_mode()) {
+            if *focused {
+                ctx.window().show_soft_keyboard();
+            } else {
+                ctx.window().hide_soft_keyboard();
+            }
+        }
+    }
+
+    fn composition(&mut self, event: &CompositionEvent, ctx: &mut HandlerCtx) {
         match event {
-            event if event.key_code == KeyCode::Backspace => {
-                self.text.pop();
+            CompositionEvent::Start => {
+                self.composition.clear();
+                self.composition_cursor = 0;
+                self.report_caret(ctx);
+            }
+            CompositionEvent::Update { text, cursor } => {
+                self.composition = text.clone();
+                self.composition_cursor = (*cursor).min(text.len());
+                self.report_caret(ctx);
+            }
+            CompositionEvent::Commit(text) => {
+                self.composition.clear();
+                self.insert(text, EditKind::Typing);
+                self.reveal_typed(ctx);
+                self.send_changes(ctx);
+            }
+        }
+        ctx.invalidate();
+    }
+
+    fn timer(&mut self, token: TimerToken, ctx: &mut HandlerCtx) {
+        if self.reveal.map(|(_, reveal)| reveal) == Some(token) {
+            self.reveal = None;
+            ctx.invalidate();
+        }
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        if let Some(text) = payload.downcast_ref::<String>() {
+            self.set_text(text.clone());
+            ctx.invalidate();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn command(&mut self, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        self.reveal = None;
+        match cmd {
+            // A password stays in the box.
+            commands::COPY | commands::CUT if self.secure => (),
+            commands::COPY => {
+                if let Some(text) = self.selected_text() {
+                    ctx.clipboard().put_string(&text);
+                }
+            }
+            commands::CUT => {
+                if let Some(text) = self.selected_text() {
+                    ctx.clipboard().put_string(&text);
+                    self.insert("", EditKind::Other);
+                }
+            }
+            commands::PASTE => {
+                // The box holds a single line, so pasted lines are joined.
+                if let Some(text) = ctx.clipboard().get_string() {
+                    self.insert(&editing::single_line(&text), EditKind::Other);
+                }
+            }
+            commands::UNDO | commands::REDO => {
+                let selection = if cmd == commands::UNDO {
+                    self.history.undo(&mut self.text)
+                } else {
+                    self.history.redo(&mut self.text)
+                };
+                match selection {
+                    Some(selection) => self.selection = selection,
+                    None => return false,
+                }
+                self.changed = true;
             }
-            event if event.key_code.is_printable() => {
-                self.text.push_str(event.text().unwrap_or(""))
+            commands::SELECT_ALL => {
+                self.history.break_group();
+                self.selection = Selection::new(0, self.text.len());
             }
             _ => return false,
         }
+        self.send_changes(ctx);
+        ctx.invalidate();
+        true
+    }
 
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        self.reveal = None;
+        match self.keymap.lookup(event) {
+            Some(EditAction::Command(cmd)) => return self.command(cmd, ctx),
+            Some(EditAction::Move(movement)) => self.move_caret(movement, false),
+            Some(EditAction::Select(movement)) => self.move_caret(movement, true),
+            Some(EditAction::Delete(movement)) => self.delete(movement),
+            None => return self.key_down_other(event, ctx),
+        }
+        self.send_changes(ctx);
         ctx.invalidate();
         true
     }
 }
+
+/// The colors and border of a `TextBox`, over those of the theme.
+#[derive(Clone, Default)]
+pub struct TextBoxStyle {
+    border_color: Option<Color>,
+    focused_border_color: Option<Color>,
+    border_width: Option<f64>,
+    text_color: Option<Color>,
+    cursor_color: Option<Color>,
+    selection_color: Option<Color>,
+}
+
+impl TextBoxStyle {
+    pub fn new() -> TextBoxStyle {
+        Default::default()
+    }
+
+    pub fn border_color(mut self, color: Color) -> TextBoxStyle {
+        self.border_color = Some(color);
+        self
+    }
+
+    pub fn focused_border_color(mut self, color: Color) -> TextBoxStyle {
+        self.focused_border_color = Some(color);
+        self
+    }
+
+    pub fn border_width(mut self, width: f64) -> TextBoxStyle {
+        self.border_width = Some(width);
+        self
+    }
+
+    pub fn text_color(mut self, color: Color) -> TextBoxStyle {
+        self.text_color = Some(color);
+        self
+    }
+
+    pub fn cursor_color(mut self, color: Color) -> TextBoxStyle {
+        self.cursor_color = Some(color);
+        self
+    }
+
+    pub fn selection_color(mut self, color: Color) -> TextBoxStyle {
+        self.selection_color = Some(color);
+        self
+    }
+}
+
+impl Style for TextBoxStyle {
+    fn apply(&self, env: &mut Env) {
+        apply_value(env, theme::BORDER_COLOR, &self.border_color);
+        apply_value(env, theme::FOCUSED_BORDER_COLOR, &self.focused_border_color);
+        apply_value(env, theme::TEXT_BOX_BORDER_WIDTH, &self.border_width);
+        apply_value(env, theme::LABEL_COLOR, &self.text_color);
+        apply_value(env, theme::CURSOR_COLOR, &self.cursor_color);
+        apply_value(env, theme::SELECTION_COLOR, &self.selection_color);
+    }
+}
+
+impl Stylable for TextBox {
+    type Style = TextBoxStyle;
+}
+
+/// The text shown in place of a password: a bullet for each grapheme
+/// cluster but the one revealed.
+struct Mask {
+    /// Where each cluster starts in the text and in the mask, then where
+    /// the last ends.
+    offsets: Vec<(usize, usize)>,
+}
+
+impl Mask {
+    /// The text shown for `text`, and its mask, revealing the cluster
+    /// starting at `reveal`.
+    fn new(text: &str, reveal: Option<usize>) -> (String, Mask) {
+        let mut shown = String::new();
+        let mut offsets = Vec::new();
+        for range in editing::grapheme_ranges(text) {
+            offsets.push((range.start, shown.len()));
+            if Some(range.start) == reveal {
+                shown.push_str(&text[range]);
+            } else {
+                shown.push(MASK_CHAR);
+            }
+        }
+        offsets.push((text.len(), shown.len()));
+        (shown, Mask { offsets })
+    }
+
+    /// The offset in the mask of the cluster at `offset` in the text.
+    fn shown_offset(&self, offset: usize) -> usize {
+        self.offsets
+            .iter()
+            .rev()
+            .find(|&&(text, _)| text <= offset)
+            .map_or(0, |&(_, shown)| shown)
+    }
+
+    /// The offset in the text of the cluster at `offset` in the mask.
+    fn text_offset(&self, offset: usize) -> usize {
+        self.offsets
+            .iter()
+            .rev()
+            .find(|&&(_, shown)| shown <= offset)
+            .map_or(0, |&(text, _)| text)
+    }
+}
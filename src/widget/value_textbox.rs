@@ -0,0 +1,174 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A text box editing a value other than text.
+
+use std::any::Any;
+
+use crate::accessibility::Action;
+use crate::kurbo::{Point, Rect, Size};
+use crate::widget::{CompositionEvent, LifeCycle, TextBox, Widget};
+use crate::TimerToken;
+use crate::{AccessNode, ActionRequest, BoxConstraints, Formatter, LayoutResult, LocaleFormat};
+use crate::{HandlerCtx, Id, KeyCode, KeyEvent, LayoutCtx, MouseEvent, PaintCtx, Ui};
+
+/// A text box editing a value, such as a number, written by a `Formatter`
+/// as usual in the current locale.
+///
+/// What was typed is read back when Enter is pressed or the box loses the
+/// focus, and the value is sent to listeners if it changed; text that
+/// can't be read is replaced by the value. Escape reverts the text. Poke
+/// the box with a value of its type to replace it.
+pub struct ValueTextBox<T> {
+    text_box: TextBox,
+    formatter: Box<dyn Formatter<T>>,
+    value: T,
+    editing: bool,
+}
+
+impl<T: Clone + PartialEq + 'static> ValueTextBox<T> {
+    pub fn new<F>(value: T, formatter: F, width: f64) -> ValueTextBox<T>
+    where
+        F: Formatter<T> + 'static,
+    {
+        ValueTextBox {
+            text_box: TextBox::new(None, width).without_events(),
+            formatter: Box::new(formatter),
+            value,
+            editing: false,
+        }
+    }
+
+    pub fn ui(self, ctx: &mut Ui) -> Id {
+        ctx.add(self, &[])
+    }
+
+    /// Show the value, formatted for editing while the box has the focus.
+    fn show(&mut self, ctx: &mut HandlerCtx) {
+        let locale = LocaleFormat::new(ctx.localization().locale());
+        let text = if self.editing {
+            self.formatter.format_for_editing(&self.value, &locale)
+        } else {
+            self.formatter.format(&self.value, &locale)
+        };
+        self.text_box.set_text(text);
+        ctx.invalidate();
+    }
+
+    fn commit(&mut self, ctx: &mut HandlerCtx) {
+        let locale = LocaleFormat::new(ctx.localization().locale());
+        if let Some(value) = self.formatter.parse(self.text_box.text(), &locale) {
+            if value != self.value {
+                self.value = value.clone();
+                ctx.send_event(value);
+            }
+        }
+        self.show(ctx);
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> Widget for ValueTextBox<T> {
+    fn paint(&mut self, paint_ctx: &mut PaintCtx, geom: &Rect) {
+        self.text_box.paint(paint_ctx, geom);
+    }
+
+    fn layout(
+        &mut self,
+        bc: &BoxConstraints,
+        children: &[Id],
+        size: Option<Size>,
+        ctx: &mut LayoutCtx,
+    ) -> LayoutResult {
+        self.text_box.layout(bc, children, size, ctx)
+    }
+
+    fn mouse(&mut self, event: &MouseEvent, ctx: &mut HandlerCtx) -> bool {
+        self.text_box.mouse(event, ctx)
+    }
+
+    fn mouse_moved(&mut self, pos: Point, ctx: &mut HandlerCtx) {
+        self.text_box.mouse_moved(pos, ctx);
+    }
+
+    fn accepts_focus(&self) -> bool {
+        true
+    }
+
+    fn accessibility(&self, node: &mut AccessNode) {
+        self.text_box.accessibility(node);
+    }
+
+    fn accessibility_action(&mut self, request: &ActionRequest, ctx: &mut HandlerCtx) -> bool {
+        // A value set by assistive technology is committed right away.
+        let handled = self.text_box.accessibility_action(request, ctx);
+        if handled && request.action == Action::SetValue {
+            self.commit(ctx);
+        }
+        handled
+    }
+
+    fn lifecycle(&mut self, event: &LifeCycle, ctx: &mut HandlerCtx) {
+        match event {
+            LifeCycle::WidgetAdded => self.show(ctx),
+            // Text being edited is kept.
+            LifeCycle::LocaleChanged if !self.editing => self.show(ctx),
+            LifeCycle::FocusChanged(true) => {
+                self.editing = true;
+                self.show(ctx);
+            }
+            LifeCycle::FocusChanged(false) => {
+                self.editing = false;
+                self.commit(ctx);
+            }
+            _ => (),
+        }
+        self.text_box.lifecycle(event, ctx);
+    }
+
+    fn composition(&mut self, event: &CompositionEvent, ctx: &mut HandlerCtx) {
+        self.text_box.composition(event, ctx);
+    }
+
+    fn timer(&mut self, token: TimerToken, ctx: &mut HandlerCtx) {
+        self.text_box.timer(token, ctx);
+    }
+
+    fn poke(&mut self, payload: &mut dyn Any, ctx: &mut HandlerCtx) -> bool {
+        if let Some(value) = payload.downcast_ref::<T>() {
+            self.value = value.clone();
+            self.show(ctx);
+            true
+        } else {
+            self.text_box.poke(payload, ctx)
+        }
+    }
+
+    fn command(&mut self, cmd: u32, ctx: &mut HandlerCtx) -> bool {
+        self.text_box.command(cmd, ctx)
+    }
+
+    fn key_down(&mut self, event: &KeyEvent, ctx: &mut HandlerCtx) -> bool {
+        match event.code {
+            KeyCode::Return | KeyCode::NumpadEnter => {
+                self.commit(ctx);
+                true
+            }
+            KeyCode::Escape => {
+                self.show(ctx);
+                self.text_box.key_down(event, ctx)
+            }
+            _ => self.text_box.key_down(event, ctx),
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Convenience methods for wrapping widgets.
+
+use crate::widget::{Click, Controller, ControllerHost, Stylable, Styled, Widget};
+use crate::HandlerCtx;
+
+/// Methods available on every widget, for layering on common behavior.
+pub trait WidgetExt: Widget + Sized {
+    /// Attach a controller that intercepts the widget's events.
+    fn controller<C: Controller<Self>>(self, controller: C) -> ControllerHost<Self, C> {
+        ControllerHost::new(self, controller)
+    }
+
+    /// Call an action when the widget is clicked, with the mouse or with the
+    /// keyboard while focused. See [`Click`] for details.
+    ///
+    /// [`Click`]: struct.Click.html
+    fn on_click<F>(self, action: F) -> ControllerHost<Self, Click<F>>
+    where
+        F: FnMut(&mut HandlerCtx),
+    {
+        self.controller(Click::new(action))
+    }
+
+    /// Draw the widget with a style of its own over the theme, such as a
+    /// `ButtonStyle` for a button.
+    fn with_style(self, style: <Self as Stylable>::Style) -> Styled<Self>
+    where
+        Self: Stylable,
+        <Self as Stylable>::Style: 'static,
+    {
+        Styled::new(self, style)
+    }
+}
+
+impl<W: Widget> WidgetExt for W {}